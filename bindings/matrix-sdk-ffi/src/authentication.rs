@@ -12,10 +12,13 @@ use matrix_sdk::{
     },
     Error,
 };
-use ruma::serde::Raw;
+use ruma::{api::client::session::get_login_types::v3::IdentityProvider, serde::Raw};
 use url::Url;
 
-use crate::client::{Client, OidcPrompt, SlidingSyncVersion};
+use crate::{
+    client::{Client, OidcPrompt, SlidingSyncVersion},
+    ruma::UiaaInfo,
+};
 
 #[derive(uniffi::Object)]
 pub struct HomeserverLoginDetails {
@@ -24,6 +27,7 @@ pub struct HomeserverLoginDetails {
     pub(crate) supports_oidc_login: bool,
     pub(crate) supported_oidc_prompts: Vec<OidcPrompt>,
     pub(crate) supports_password_login: bool,
+    pub(crate) supports_sso_login: bool,
 }
 
 #[matrix_sdk_ffi_macros::export]
@@ -53,6 +57,11 @@ impl HomeserverLoginDetails {
     pub fn supports_password_login(&self) -> bool {
         self.supports_password_login
     }
+
+    /// Whether the current homeserver supports the legacy SSO login flow.
+    pub fn supports_sso_login(&self) -> bool {
+        self.supports_sso_login
+    }
 }
 
 /// An object encapsulating the SSO login flow
@@ -76,12 +85,7 @@ impl SsoHandler {
 
     /// Completes the SSO login process.
     pub async fn finish(&self, callback_url: String) -> Result<(), SsoError> {
-        let auth = self.client.inner.matrix_auth();
-        let url = Url::parse(&callback_url).map_err(|_| SsoError::CallbackUrlInvalid)?;
-        let builder =
-            auth.login_with_sso_callback(url).map_err(|_| SsoError::CallbackUrlInvalid)?;
-        builder.await.map_err(|_| SsoError::LoginWithTokenFailed)?;
-        Ok(())
+        self.client.login_with_sso_callback(callback_url, None, None).await
     }
 }
 
@@ -91,6 +95,48 @@ impl Debug for SsoHandler {
     }
 }
 
+/// An identity provider offered by the homeserver for the legacy SSO login
+/// flow.
+#[derive(uniffi::Record)]
+pub struct SsoIdentityProvider {
+    /// The ID of the identity provider, to pass to
+    /// [`Client::start_sso_login`].
+    pub id: String,
+
+    /// The name of the identity provider, to show to the user.
+    pub name: String,
+
+    /// The MXC URI of the icon of the identity provider, if any.
+    pub icon_url: Option<String>,
+
+    /// The brand of the identity provider, e.g. `google` or `github`, if any,
+    /// to style its button accordingly.
+    pub brand: Option<String>,
+}
+
+impl From<IdentityProvider> for SsoIdentityProvider {
+    fn from(value: IdentityProvider) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            icon_url: value.icon.map(|icon| icon.to_string()),
+            brand: value.brand.map(|brand| brand.as_str().to_owned()),
+        }
+    }
+}
+
+/// The outcome of a call to [`Client::register()`].
+#[derive(uniffi::Enum)]
+pub enum RegistrationResult {
+    /// The account has been created, and the client is logged in with it.
+    Registered,
+    /// The homeserver requires the user to complete a stage of the
+    /// user-interactive authentication. The registration must be attempted
+    /// again with the [`AuthData`](crate::ruma::AuthData) of one of the
+    /// stages.
+    AuthenticationRequired { info: UiaaInfo },
+}
+
 #[derive(Debug, thiserror::Error, uniffi::Error)]
 #[uniffi(flat_error)]
 pub enum SsoError {
@@ -39,7 +39,7 @@ use matrix_sdk::{
     },
     sliding_sync::Version as SdkSlidingSyncVersion,
     store::RoomLoadSettings as SdkRoomLoadSettings,
-    AuthApi, AuthSession, Client as MatrixClient, SessionChange, SessionTokens,
+    Account, AuthApi, AuthSession, Client as MatrixClient, SessionChange, SessionTokens,
     STATE_STORE_DATABASE_NAME,
 };
 use matrix_sdk_common::{stream::StreamExt, SendOutsideWasm, SyncOutsideWasm};
@@ -78,12 +78,13 @@ use ruma::{
         RoomAccountDataEvent as RumaRoomAccountDataEvent,
     },
     push::{HttpPusherData as RumaHttpPusherData, PushFormat as RumaPushFormat},
-    OwnedServerName, RoomAliasId, RoomOrAliasId, ServerName,
+    room::RoomType,
+    ClientSecret, OwnedServerName, RoomAliasId, RoomOrAliasId, ServerName,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tokio::sync::broadcast::error::RecvError;
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 use url::Url;
 
 use super::{
@@ -91,9 +92,15 @@ use super::{
     session_verification::SessionVerificationController,
 };
 use crate::{
-    authentication::{HomeserverLoginDetails, OidcConfiguration, OidcError, SsoError, SsoHandler},
+    authentication::{
+        HomeserverLoginDetails, OidcConfiguration, OidcError, RegistrationResult, SsoError,
+        SsoHandler, SsoIdentityProvider,
+    },
     client,
     encryption::Encryption,
+    media_transfer::{
+        MediaTransferHandle, MediaTransferListener, MediaTransferOutcome, ThroughputEstimator,
+    },
     notification::NotificationClient,
     notification_settings::NotificationSettings,
     room::{RoomHistoryVisibility, RoomInfoListener},
@@ -104,10 +111,11 @@ use crate::{
         MediaPreviews, MediaSource, RoomAccountDataEvent, RoomAccountDataEventType,
     },
     runtime::get_runtime_handle,
+    space::Space,
     sync_service::{SyncService, SyncServiceBuilder},
     task_handle::TaskHandle,
     utd::{UnableToDecryptDelegate, UtdHook},
-    utils::AsyncRuntimeDropped,
+    utils::{u64_to_uint, AsyncRuntimeDropped},
     ClientError,
 };
 
@@ -340,6 +348,7 @@ impl Client {
         };
 
         let supports_password_login = self.supports_password_login().await.ok().unwrap_or(false);
+        let supports_sso_login = self.sso_login_type().await.ok().flatten().is_some();
         let sliding_sync_version = self.sliding_sync_version();
 
         Arc::new(HomeserverLoginDetails {
@@ -348,6 +357,7 @@ impl Client {
             supports_oidc_login,
             supported_oidc_prompts,
             supports_password_login,
+            supports_sso_login,
         })
     }
 
@@ -370,6 +380,87 @@ impl Client {
         Ok(())
     }
 
+    /// Checks if a username is still available on the homeserver, before
+    /// registering an account with it.
+    ///
+    /// Returns:
+    /// - `Ok(true)` if the username is available.
+    /// - `Ok(false)` if it's already taken.
+    /// - An `Err` otherwise, e.g. if the username is invalid.
+    pub async fn is_username_available(&self, username: String) -> Result<bool, ClientError> {
+        Ok(self.inner.matrix_auth().is_username_available(&username).await?)
+    }
+
+    /// Register a new account using a username and password, and log in with
+    /// it.
+    ///
+    /// # Arguments
+    ///
+    /// * `auth_data` - This request uses the [User-Interactive Authentication
+    ///   API][uiaa]. The first request should set this to `None`, and will
+    ///   return the stages the homeserver wants the user to complete. The same
+    ///   request must then be made again with the `auth_data` of the next
+    ///   stage, until the account is registered.
+    ///
+    /// [uiaa]: https://spec.matrix.org/v1.14/client-server-api/#user-interactive-authentication-api
+    pub async fn register(
+        &self,
+        username: String,
+        password: String,
+        initial_device_name: Option<String>,
+        device_id: Option<String>,
+        auth_data: Option<AuthData>,
+    ) -> Result<RegistrationResult, ClientError> {
+        let mut builder = self.inner.matrix_auth().register_username(&username, &password);
+        if let Some(initial_device_name) = initial_device_name.as_ref() {
+            builder = builder.initial_device_display_name(initial_device_name);
+        }
+        if let Some(device_id) = device_id.as_ref() {
+            builder = builder.device_id(device_id);
+        }
+        if let Some(auth_data) = auth_data {
+            builder = builder.auth(auth_data.into());
+        }
+
+        match builder.send().await {
+            Ok(_) => Ok(RegistrationResult::Registered),
+            Err(error) => match error.as_uiaa_response() {
+                Some(info) => Ok(RegistrationResult::AuthenticationRequired { info: info.into() }),
+                None => Err(error.into()),
+            },
+        }
+    }
+
+    /// Ask the homeserver to send a token to the given email address, to
+    /// validate it during the registration.
+    ///
+    /// Returns the ID of the validation session, to use in
+    /// [`AuthData::EmailIdentity`] with the same `client_secret` once the user
+    /// has validated their email address.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_secret` - A secret generated by the client, unique to this
+    ///   validation attempt.
+    ///
+    /// * `send_attempt` - The number of the attempt. A new email is only sent
+    ///   if it's greater than the one of the previous request with the same
+    ///   `client_secret`.
+    pub async fn request_registration_email_token(
+        &self,
+        email: String,
+        client_secret: String,
+        send_attempt: u64,
+    ) -> Result<String, ClientError> {
+        let client_secret = ClientSecret::parse(client_secret)?;
+        let response = self
+            .inner
+            .matrix_auth()
+            .request_registration_email_token(&email, &client_secret, u64_to_uint(send_attempt))
+            .await?;
+        Ok(response.sid.to_string())
+    }
+
     /// Login using JWT
     /// This is an implementation of the custom_login https://docs.rs/matrix-sdk/latest/matrix_sdk/matrix_auth/struct.MatrixAuth.html#method.login_custom
     /// For more information on logging in with JWT: https://element-hq.github.io/synapse/latest/jwt.html
@@ -421,8 +512,18 @@ impl Client {
         Ok(())
     }
 
-    /// Returns a handler to start the SSO login process.
-    pub(crate) async fn start_sso_login(
+    /// Returns a handler to start the legacy SSO login process, i.e. the
+    /// `m.login.sso` flow of the homeserver, not OIDC.
+    ///
+    /// # Arguments
+    ///
+    /// * `redirect_url` - The URL the homeserver redirects to with a login
+    ///   token, once the user has logged in.
+    ///
+    /// * `idp_id` - The ID of the identity provider to use, as returned by
+    ///   [`Client::sso_identity_providers`]. If it's `None`, the homeserver
+    ///   lets the user choose one.
+    pub async fn start_sso_login(
         self: &Arc<Self>,
         redirect_url: String,
         idp_id: Option<String>,
@@ -435,6 +536,51 @@ impl Client {
         Ok(Arc::new(SsoHandler { client: Arc::clone(self), url }))
     }
 
+    /// Get the identity providers offered by the homeserver for the legacy SSO
+    /// login flow, to show a button for each of them.
+    ///
+    /// Returns an empty list if the homeserver doesn't support the SSO login
+    /// flow, or if it doesn't advertise its identity providers.
+    pub async fn sso_identity_providers(&self) -> Result<Vec<SsoIdentityProvider>, ClientError> {
+        let Some(sso_login_type) = self.sso_login_type().await? else {
+            return Ok(Vec::new());
+        };
+
+        Ok(sso_login_type.identity_providers.into_iter().map(Into::into).collect())
+    }
+
+    /// Completes a legacy SSO login, by exchanging the login token of the
+    /// callback URL the homeserver redirected to.
+    ///
+    /// This is an alternative to [`SsoHandler::finish`], for apps which don't
+    /// keep the handler around during the login.
+    pub async fn login_with_sso_callback(
+        &self,
+        callback_url: String,
+        initial_device_name: Option<String>,
+        device_id: Option<String>,
+    ) -> Result<(), SsoError> {
+        let url = Url::parse(&callback_url).map_err(|_| SsoError::CallbackUrlInvalid)?;
+
+        let mut builder = self
+            .inner
+            .matrix_auth()
+            .login_with_sso_callback(url)
+            .map_err(|_| SsoError::CallbackUrlInvalid)?;
+
+        if let Some(initial_device_name) = initial_device_name.as_ref() {
+            builder = builder.initial_device_display_name(initial_device_name);
+        }
+
+        if let Some(device_id) = device_id.as_ref() {
+            builder = builder.device_id(device_id);
+        }
+
+        builder.send().await.map_err(|_| SsoError::LoginWithTokenFailed)?;
+
+        Ok(())
+    }
+
     /// Requests the URL needed for opening a web view using OIDC. Once the web
     /// view has succeeded, call `login_with_oidc_callback` with the callback it
     /// returns. If a failure occurs and a callback isn't available, make sure
@@ -709,6 +855,20 @@ impl Client {
     pub async fn reset_server_info(&self) -> Result<(), ClientError> {
         Ok(self.inner.reset_server_info().await?)
     }
+
+    /// Get the content of the homeserver's client well-known file, from the
+    /// cache or the server.
+    ///
+    /// Returns `None` if the server doesn't have a client well-known file.
+    pub async fn client_well_known(&self) -> Result<Option<ClientWellKnown>, ClientError> {
+        Ok(self.inner.client_well_known().await?.map(Into::into))
+    }
+
+    /// Reset the cached server info and fetch the homeserver's client
+    /// well-known file from the server again.
+    pub async fn refresh_client_well_known(&self) -> Result<Option<ClientWellKnown>, ClientError> {
+        Ok(self.inner.refresh_client_well_known().await?.map(Into::into))
+    }
 }
 
 #[cfg(not(target_family = "wasm"))]
@@ -742,6 +902,65 @@ impl Client {
 
         Ok(Arc::new(MediaFileHandle::new(handle)))
     }
+
+    /// Export all the room keys of the current device to the file at the given
+    /// path, in the standard encrypted key export format.
+    ///
+    /// The export is encrypted with the given passphrase.
+    pub async fn export_room_keys(
+        &self,
+        path: String,
+        passphrase: String,
+    ) -> Result<(), ClientError> {
+        Ok(self.inner.encryption().export_room_keys(path.into(), &passphrase, |_| true).await?)
+    }
+
+    /// Import the room keys from the file at the given path, which is in the
+    /// standard encrypted key export format.
+    ///
+    /// The `progress_listener`, if any, is notified of the progress of the
+    /// import, which can take a while for large key exports.
+    pub async fn import_room_keys(
+        &self,
+        path: String,
+        passphrase: String,
+        progress_listener: Option<Box<dyn RoomKeyImportProgressListener>>,
+    ) -> Result<RoomKeyImportResult, ClientError> {
+        let result = self
+            .inner
+            .encryption()
+            .import_room_keys_with_progress(path.into(), &passphrase, |processed, total| {
+                if let Some(listener) = &progress_listener {
+                    listener.on_progress(processed as u64, total as u64);
+                }
+            })
+            .await
+            .map_err(ClientError::from_err)?;
+
+        Ok(result.into())
+    }
+}
+
+#[matrix_sdk_ffi_macros::export(callback_interface)]
+pub trait RoomKeyImportProgressListener: SyncOutsideWasm + SendOutsideWasm {
+    /// Called with the number of room keys processed so far, and the total
+    /// number of room keys to import.
+    fn on_progress(&self, processed: u64, total: u64);
+}
+
+/// The result of an import of room keys.
+#[derive(uniffi::Record)]
+pub struct RoomKeyImportResult {
+    /// The number of room keys that were imported.
+    pub imported_count: u64,
+    /// The total number of room keys that were found in the export.
+    pub total_count: u64,
+}
+
+impl From<matrix_sdk::encryption::RoomKeyImportResult> for RoomKeyImportResult {
+    fn from(value: matrix_sdk::encryption::RoomKeyImportResult) -> Self {
+        Self { imported_count: value.imported_count as u64, total_count: value.total_count as u64 }
+    }
 }
 
 impl Client {
@@ -754,6 +973,17 @@ impl Client {
             .any(|login_type| matches!(login_type, get_login_types::v3::LoginType::Password(_)));
         Ok(supports_password)
     }
+
+    /// The legacy SSO login flow of the client's homeserver, if it supports it.
+    pub(crate) async fn sso_login_type(
+        &self,
+    ) -> anyhow::Result<Option<get_login_types::v3::SsoLoginType>> {
+        let login_types = self.inner.matrix_auth().get_login_types().await?;
+        Ok(login_types.flows.into_iter().find_map(|login_type| match login_type {
+            get_login_types::v3::LoginType::Sso(sso_login_type) => Some(sso_login_type),
+            _ => None,
+        }))
+    }
 }
 
 #[matrix_sdk_ffi_macros::export]
@@ -931,6 +1161,20 @@ impl Client {
         Ok(String::from(response.room_id()))
     }
 
+    /// Create a new space, with the same parameters as a room.
+    pub async fn create_space(
+        &self,
+        request: CreateRoomParameters,
+    ) -> Result<Arc<Space>, ClientError> {
+        let mut request: create_room::v3::Request = request.try_into()?;
+        let mut creation_content = create_room::v3::CreationContent::new();
+        creation_content.room_type = Some(RoomType::Space);
+        request.creation_content = Some(Raw::new(&creation_content)?);
+
+        let room = self.inner.create_room(request).await?;
+        Ok(Arc::new(Space::new(room)))
+    }
+
     /// Get the content of the event of the given type out of the account data
     /// store.
     ///
@@ -1015,6 +1259,92 @@ impl Client {
             .await?)
     }
 
+    /// Start uploading the given media in the background.
+    ///
+    /// The returned handle allows to wait for the content URI of the uploaded
+    /// media, or to pause or cancel the upload. The listener, if any, is
+    /// notified of the progress of the upload.
+    pub fn start_media_upload(
+        &self,
+        mime_type: String,
+        data: Vec<u8>,
+        listener: Option<Box<dyn MediaTransferListener>>,
+    ) -> Result<Arc<MediaTransferHandle>, ClientError> {
+        let mime_type: mime::Mime = mime_type.parse().context("Parsing mime type")?;
+        let listener: Option<Arc<dyn MediaTransferListener>> = listener.map(Arc::from);
+        let client = (*self.inner).clone();
+
+        Ok(MediaTransferHandle::spawn(move || {
+            let client = client.clone();
+            let mime_type = mime_type.clone();
+            let data = data.clone();
+            let listener = listener.clone();
+
+            async move {
+                let request = client.media().upload(&mime_type, data, None);
+
+                if let Some(listener) = listener {
+                    let estimator = ThroughputEstimator::start();
+                    let mut subscriber = request.subscribe_to_send_progress();
+
+                    get_runtime_handle().spawn(async move {
+                        while let Some(progress) = subscriber.next().await {
+                            let progress = TransmissionProgress::from(progress);
+                            listener.on_progress(
+                                estimator.progress(progress.current, Some(progress.total)),
+                            );
+                        }
+                    });
+                }
+
+                let response = request.await?;
+
+                Ok(MediaTransferOutcome::Uploaded { content_uri: response.content_uri.to_string() })
+            }
+        }))
+    }
+
+    /// Start downloading the given media in the background.
+    ///
+    /// The returned handle allows to wait for the content of the media, or to
+    /// pause or cancel the download. The listener, if any, is notified once
+    /// the download is finished, since its intermediate progress isn't known.
+    pub fn start_media_download(
+        &self,
+        media_source: Arc<MediaSource>,
+        listener: Option<Box<dyn MediaTransferListener>>,
+    ) -> Arc<MediaTransferHandle> {
+        let source = (*media_source).clone().media_source;
+        let listener: Option<Arc<dyn MediaTransferListener>> = listener.map(Arc::from);
+        let client = (*self.inner).clone();
+
+        MediaTransferHandle::spawn(move || {
+            let client = client.clone();
+            let source = source.clone();
+            let listener = listener.clone();
+
+            async move {
+                let estimator = ThroughputEstimator::start();
+
+                debug!(?source, "downloading media file");
+                let data = client
+                    .media()
+                    .get_media_content(
+                        &MediaRequestParameters { source, format: MediaFormat::File },
+                        true,
+                    )
+                    .await?;
+
+                if let Some(listener) = listener {
+                    let size = data.len() as u64;
+                    listener.on_progress(estimator.progress(size, Some(size)));
+                }
+
+                Ok(MediaTransferOutcome::Downloaded { data })
+            }
+        })
+    }
+
     pub async fn get_session_verification_controller(
         &self,
     ) -> Result<Arc<SessionVerificationController>, ClientError> {
@@ -1126,6 +1456,17 @@ impl Client {
         Ok(room)
     }
 
+    /// Get the space with the given ID, if it's known and is a space.
+    pub fn get_space(&self, room_id: String) -> Result<Option<Arc<Space>>, ClientError> {
+        let room_id = RoomId::parse(room_id)?;
+        let space = self
+            .inner
+            .get_room(&room_id)
+            .filter(|room| room.is_space())
+            .map(|room| Arc::new(Space::new(room)));
+        Ok(space)
+    }
+
     pub fn get_dm_room(&self, user_id: String) -> Result<Option<Arc<Room>>, ClientError> {
         let user_id = UserId::parse(user_id)?;
         let sdk_room = self.inner.get_dm_room(&user_id);
@@ -1211,18 +1552,66 @@ impl Client {
         Ok(())
     }
 
+    /// Subscribe to the list of ignored users.
+    ///
+    /// The listener is first called with the current list of ignored users, as
+    /// known from the last sync, then each time it changes.
     pub fn subscribe_to_ignored_users(
         &self,
         listener: Box<dyn IgnoredUsersListener>,
     ) -> Arc<TaskHandle> {
         let mut subscriber = self.inner.subscribe_to_ignore_user_list_changes();
+        let account = self.inner.account();
         Arc::new(TaskHandle::new(get_runtime_handle().spawn(async move {
+            // Send the initial value to the listener.
+            let initial_user_ids =
+                stored_ignored_user_ids(&account).await.unwrap_or_else(|error| {
+                    warn!("Couldn't load the ignored user list: {error}");
+                    Vec::new()
+                });
+            listener.call(initial_user_ids);
+
+            // Listen for changes and notify the listener.
             while let Some(user_ids) = subscriber.next().await {
                 listener.call(user_ids);
             }
         })))
     }
 
+    /// Enable or disable the low-bandwidth mode.
+    ///
+    /// This switches at once a set of behaviors: smaller timeline limits when
+    /// syncing, longer sync timeouts, downscaled images when sending, and
+    /// media hints for the application (no prefetch, thumbnails only).
+    pub fn set_low_bandwidth_mode(&self, enabled: bool) {
+        self.inner.set_low_bandwidth_mode(enabled);
+    }
+
+    /// Whether the low-bandwidth mode is enabled.
+    pub fn is_low_bandwidth_mode_enabled(&self) -> bool {
+        self.inner.is_low_bandwidth_mode_enabled()
+    }
+
+    /// Get the behaviors matching the current state of the low-bandwidth mode.
+    pub fn bandwidth_profile(&self) -> BandwidthProfile {
+        self.inner.bandwidth_profile().into()
+    }
+
+    /// Subscribe to the changes of the low-bandwidth mode.
+    ///
+    /// The listener is called with the new state of the mode.
+    pub fn subscribe_to_low_bandwidth_mode(
+        &self,
+        listener: Box<dyn LowBandwidthModeListener>,
+    ) -> Arc<TaskHandle> {
+        let mut subscriber = self.inner.subscribe_to_low_bandwidth_mode();
+        Arc::new(TaskHandle::new(get_runtime_handle().spawn(async move {
+            while let Some(enabled) = subscriber.next().await {
+                listener.call(enabled);
+            }
+        })))
+    }
+
     pub fn room_directory_search(&self) -> Arc<RoomDirectorySearch> {
         Arc::new(RoomDirectorySearch::new(
             matrix_sdk::room_directory_search::RoomDirectorySearch::new((*self.inner).clone()),
@@ -1261,6 +1650,45 @@ impl Client {
         Ok(Arc::new(Room::new(room, self.utd_hook_manager.get().cloned())))
     }
 
+    /// Join the successor of a tombstoned room, i.e. the room replacing it
+    /// after an upgrade.
+    ///
+    /// The successor room is joined via the servers returned by
+    /// [`Room::successor_room_with_via`]. If `leave_old_room` is true, the
+    /// tombstoned room is left once the successor room has been joined.
+    ///
+    /// Returns an error if the room is unknown or isn't tombstoned.
+    pub async fn join_upgraded_room(
+        &self,
+        room_id: String,
+        leave_old_room: bool,
+    ) -> Result<Arc<Room>, ClientError> {
+        let room_id = RoomId::parse(room_id)?;
+        let Some(old_room) = self.inner.get_room(&room_id) else {
+            return Err(ClientError::Generic {
+                msg: format!("Room {room_id} not found"),
+                details: None,
+            });
+        };
+        let old_room = Room::new(old_room, self.utd_hook_manager.get().cloned());
+
+        let Some((successor_room, via)) = old_room.successor_room_and_via().await? else {
+            return Err(ClientError::Generic {
+                msg: format!("Room {room_id} isn't tombstoned"),
+                details: None,
+            });
+        };
+
+        let room =
+            self.inner.join_room_by_id_or_alias((&*successor_room.room_id).into(), &via).await?;
+
+        if leave_old_room {
+            old_room.leave().await?;
+        }
+
+        Ok(Arc::new(Room::new(room, self.utd_hook_manager.get().cloned())))
+    }
+
     /// Knock on a room to join it using its ID or alias.
     pub async fn knock(
         &self,
@@ -1654,11 +2082,133 @@ pub trait MediaPreviewConfigListener: SyncOutsideWasm + SendOutsideWasm {
     fn on_change(&self, media_preview_config: Option<MediaPreviewConfig>);
 }
 
+/// Get the IDs of the ignored users, from the account data in the store.
+async fn stored_ignored_user_ids(account: &Account) -> Result<Vec<String>, ClientError> {
+    let Some(raw_content) = account.account_data::<IgnoredUserListEventContent>().await? else {
+        return Ok(Vec::new());
+    };
+    let content = raw_content.deserialize()?;
+    Ok(content.ignored_users.keys().map(|id| id.to_string()).collect())
+}
+
 #[matrix_sdk_ffi_macros::export(callback_interface)]
 pub trait IgnoredUsersListener: SyncOutsideWasm + SendOutsideWasm {
     fn call(&self, ignored_user_ids: Vec<String>);
 }
 
+#[matrix_sdk_ffi_macros::export(callback_interface)]
+pub trait LowBandwidthModeListener: SyncOutsideWasm + SendOutsideWasm {
+    fn call(&self, enabled: bool);
+}
+
+/// The set of behaviors that depend on whether the low-bandwidth mode is
+/// enabled.
+#[derive(uniffi::Record)]
+pub struct BandwidthProfile {
+    /// The maximum number of timeline events to request for a room, when
+    /// syncing.
+    pub max_sync_timeline_limit: Option<u32>,
+    /// The factor applied to the network timeout of the sync requests.
+    pub sync_network_timeout_factor: u32,
+    /// Whether media should be fetched before being displayed.
+    pub prefetch_media: bool,
+    /// Whether only the thumbnails of the media should be downloaded
+    /// automatically.
+    pub thumbnails_only: bool,
+    /// The maximum width or height, in pixels, of the images sent through the
+    /// send queue; larger images are downscaled before being uploaded.
+    pub max_image_dimension: Option<u32>,
+}
+
+impl From<matrix_sdk::BandwidthProfile> for BandwidthProfile {
+    fn from(value: matrix_sdk::BandwidthProfile) -> Self {
+        Self {
+            max_sync_timeline_limit: value.max_sync_timeline_limit,
+            sync_network_timeout_factor: value.sync_network_timeout_factor,
+            prefetch_media: value.prefetch_media,
+            thumbnails_only: value.thumbnails_only,
+            max_image_dimension: value.max_image_dimension,
+        }
+    }
+}
+
+/// The content of the homeserver's client well-known file.
+#[derive(uniffi::Record)]
+pub struct ClientWellKnown {
+    /// The base URL of the homeserver.
+    pub homeserver_url: String,
+    /// The base URL of the identity server, if any.
+    pub identity_server_url: Option<String>,
+    /// The URL of the map style of the tile server used to display locations,
+    /// if any.
+    pub tile_server_map_style_url: Option<String>,
+    /// The service URLs of the LiveKit MatrixRTC foci, ordered by priority.
+    pub livekit_service_urls: Vec<String>,
+    /// The end-to-end encryption defaults of the deployment, from the
+    /// `io.element.e2ee` field, if any.
+    pub e2ee: Option<WellKnownE2eeConfig>,
+    /// The preferred Jitsi domain of the deployment, from the
+    /// `im.vector.riot.jitsi` field, if any.
+    pub jitsi_preferred_domain: Option<String>,
+    /// The full JSON content of the well-known file, to read custom fields
+    /// that aren't exposed above.
+    pub raw_json: Option<String>,
+}
+
+impl From<matrix_sdk::store::WellKnownResponse> for ClientWellKnown {
+    fn from(value: matrix_sdk::store::WellKnownResponse) -> Self {
+        let e2ee = value
+            .field("io.element.e2ee")
+            .and_then(|e2ee| serde_json::from_value::<WellKnownE2eeConfig>(e2ee.clone()).ok());
+        let jitsi_preferred_domain = value
+            .field("im.vector.riot.jitsi")
+            .and_then(|jitsi| jitsi.get("preferredDomain"))
+            .and_then(|domain| domain.as_str())
+            .map(ToOwned::to_owned);
+        let raw_json = value.raw.as_ref().and_then(|raw| serde_json::to_string(raw).ok());
+
+        Self {
+            homeserver_url: value.homeserver.base_url,
+            identity_server_url: value.identity_server.map(|info| info.base_url),
+            tile_server_map_style_url: value.tile_server.map(|info| info.map_style_url),
+            livekit_service_urls: value
+                .rtc_foci
+                .into_iter()
+                .filter_map(|focus| match focus {
+                    RtcFocusInfo::LiveKit(info) => Some(info.service_url),
+                    _ => None,
+                })
+                .collect(),
+            e2ee,
+            jitsi_preferred_domain,
+            raw_json,
+        }
+    }
+}
+
+/// The end-to-end encryption defaults advertised in the client well-known
+/// file.
+#[derive(Deserialize, uniffi::Record)]
+pub struct WellKnownE2eeConfig {
+    /// Whether new rooms should be encrypted by default.
+    #[serde(default = "default_true")]
+    pub default: bool,
+    /// Whether the user must set up a secure backup.
+    #[serde(default)]
+    pub secure_backup_required: bool,
+    /// The methods allowed to set up the secure backup, e.g. `key` or
+    /// `passphrase`.
+    #[serde(default)]
+    pub secure_backup_setup_methods: Vec<String>,
+    /// Whether end-to-end encryption should be disabled entirely.
+    #[serde(default)]
+    pub force_disable: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
 #[derive(uniffi::Enum)]
 pub enum NotificationProcessSetup {
     MultipleProcesses,
@@ -2457,3 +3007,254 @@ impl TryFrom<RumaAllowRule> for AllowRule {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use matrix_sdk::{store::WellKnownResponse, test_utils::mocks::MatrixMockServer};
+    use matrix_sdk_common::timeout::timeout;
+    use matrix_sdk_test::{async_test, test_json};
+    use ruma::{
+        api::client::discovery::discover_homeserver::{HomeserverInfo, RtcFocusInfo},
+        events::AnyGlobalAccountDataEvent,
+        serde::Raw,
+    };
+    use serde_json::json;
+    use tokio::sync::mpsc;
+    use wiremock::{
+        matchers::{body_partial_json, method, path_regex},
+        Mock, ResponseTemplate,
+    };
+
+    use super::{Client, ClientWellKnown, IgnoredUsersListener, WellKnownE2eeConfig};
+    use crate::authentication::SsoError;
+
+    async fn unlogged_client(server: &MatrixMockServer) -> Client {
+        let sdk_client = server.client_builder().unlogged().build().await;
+        Client::new(sdk_client, false, None, None).await.unwrap()
+    }
+
+    #[test]
+    fn test_client_well_known_from_response() {
+        let raw = json!({
+            "m.homeserver": { "base_url": "https://matrix.example.com" },
+            "io.element.e2ee": {
+                "default": false,
+                "secure_backup_required": true,
+                "secure_backup_setup_methods": ["passphrase"],
+            },
+            "im.vector.riot.jitsi": { "preferredDomain": "jitsi.example.com" },
+            "com.example.custom": 42,
+        });
+
+        let response = WellKnownResponse {
+            homeserver: HomeserverInfo::new("https://matrix.example.com".to_owned()),
+            identity_server: None,
+            tile_server: None,
+            rtc_foci: vec![RtcFocusInfo::livekit("https://livekit.example.com".to_owned())],
+            raw: raw.as_object().cloned(),
+        };
+
+        let well_known = ClientWellKnown::from(response);
+        assert_eq!(well_known.homeserver_url, "https://matrix.example.com");
+        assert_eq!(well_known.identity_server_url, None);
+        assert_eq!(well_known.livekit_service_urls, ["https://livekit.example.com"]);
+        assert_eq!(well_known.jitsi_preferred_domain.as_deref(), Some("jitsi.example.com"));
+
+        let e2ee = well_known.e2ee.expect("the E2EE configuration should be parsed");
+        assert!(!e2ee.default);
+        assert!(e2ee.secure_backup_required);
+        assert_eq!(e2ee.secure_backup_setup_methods, ["passphrase"]);
+        // Missing fields use their default values.
+        assert!(!e2ee.force_disable);
+
+        // The custom fields are kept in the raw JSON.
+        let raw_json: serde_json::Value =
+            serde_json::from_str(&well_known.raw_json.unwrap()).unwrap();
+        assert_eq!(raw_json, raw);
+    }
+
+    #[test]
+    fn test_client_well_known_without_raw_content() {
+        let response = WellKnownResponse {
+            homeserver: HomeserverInfo::new("https://matrix.example.com".to_owned()),
+            identity_server: None,
+            tile_server: None,
+            rtc_foci: Vec::new(),
+            raw: None,
+        };
+
+        // The custom fields can't be read without the raw content.
+        let well_known = ClientWellKnown::from(response);
+        assert_eq!(well_known.homeserver_url, "https://matrix.example.com");
+        assert!(well_known.livekit_service_urls.is_empty());
+        assert!(well_known.e2ee.is_none());
+        assert!(well_known.jitsi_preferred_domain.is_none());
+        assert!(well_known.raw_json.is_none());
+    }
+
+    #[test]
+    fn test_well_known_e2ee_config_defaults() {
+        let e2ee: WellKnownE2eeConfig = serde_json::from_value(json!({})).unwrap();
+        assert!(e2ee.default);
+        assert!(!e2ee.secure_backup_required);
+        assert!(e2ee.secure_backup_setup_methods.is_empty());
+        assert!(!e2ee.force_disable);
+    }
+
+    #[async_test]
+    async fn test_sso_identity_providers() {
+        let server = MatrixMockServer::new().await;
+        let client = unlogged_client(&server).await;
+
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/_matrix/client/.*/login$"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "flows": [
+                    { "type": "m.login.password" },
+                    {
+                        "type": "m.login.sso",
+                        "identity_providers": [
+                            {
+                                "id": "oidc-github",
+                                "name": "GitHub",
+                                "icon": "mxc://example.org/github",
+                                "brand": "github",
+                            },
+                            { "id": "oidc-custom", "name": "Custom" },
+                        ],
+                    },
+                ],
+            })))
+            .mount(server.server())
+            .await;
+
+        assert!(client.homeserver_login_details().await.supports_sso_login());
+
+        let providers = client.sso_identity_providers().await.unwrap();
+        assert_eq!(providers.len(), 2);
+
+        assert_eq!(providers[0].id, "oidc-github");
+        assert_eq!(providers[0].name, "GitHub");
+        assert_eq!(providers[0].icon_url.as_deref(), Some("mxc://example.org/github"));
+        assert_eq!(providers[0].brand.as_deref(), Some("github"));
+
+        assert_eq!(providers[1].id, "oidc-custom");
+        assert_eq!(providers[1].name, "Custom");
+        assert!(providers[1].icon_url.is_none());
+        assert!(providers[1].brand.is_none());
+    }
+
+    #[async_test]
+    async fn test_sso_identity_providers_without_sso_support() {
+        let server = MatrixMockServer::new().await;
+        let client = unlogged_client(&server).await;
+
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/_matrix/client/.*/login$"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "flows": [{ "type": "m.login.password" }],
+            })))
+            .mount(server.server())
+            .await;
+
+        assert!(!client.homeserver_login_details().await.supports_sso_login());
+        assert!(client.sso_identity_providers().await.unwrap().is_empty());
+    }
+
+    #[async_test]
+    async fn test_login_with_sso_callback() {
+        let server = MatrixMockServer::new().await;
+        let client = unlogged_client(&server).await;
+
+        Mock::given(method("POST"))
+            .and(path_regex(r"^/_matrix/client/.*/login$"))
+            .and(body_partial_json(json!({
+                "type": "m.login.token",
+                "token": "averysmalltoken",
+                "device_id": "GHTYAJCE",
+                "initial_device_display_name": "My device",
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::LOGIN))
+            .expect(1)
+            .mount(server.server())
+            .await;
+
+        // The callback URL must contain a login token.
+        assert!(matches!(
+            client.login_with_sso_callback("http://127.0.0.1:3030".to_owned(), None, None).await,
+            Err(SsoError::CallbackUrlInvalid)
+        ));
+
+        client
+            .login_with_sso_callback(
+                "http://127.0.0.1:3030?loginToken=averysmalltoken".to_owned(),
+                Some("My device".to_owned()),
+                Some("GHTYAJCE".to_owned()),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(client.inner.user_id().unwrap().as_str(), "@cheeky_monkey:matrix.org");
+        assert_eq!(client.inner.device_id().unwrap().as_str(), "GHTYAJCE");
+    }
+
+    fn ignored_user_list(user_ids: &[&str]) -> Raw<AnyGlobalAccountDataEvent> {
+        let ignored_users = user_ids
+            .iter()
+            .map(|user_id| (user_id.to_string(), json!({})))
+            .collect::<serde_json::Map<_, _>>();
+
+        Raw::new(&json!({
+            "type": "m.ignored_user_list",
+            "content": { "ignored_users": ignored_users },
+        }))
+        .unwrap()
+        .cast()
+    }
+
+    struct IgnoredUsersRecorder(mpsc::UnboundedSender<Vec<String>>);
+
+    impl IgnoredUsersListener for IgnoredUsersRecorder {
+        fn call(&self, ignored_user_ids: Vec<String>) {
+            self.0.send(ignored_user_ids).unwrap();
+        }
+    }
+
+    #[async_test]
+    async fn test_subscribe_to_ignored_users() {
+        let server = MatrixMockServer::new().await;
+        let sdk_client = server.client_builder().build().await;
+        let client = Client::new(sdk_client.clone(), false, None, None).await.unwrap();
+
+        server
+            .mock_sync()
+            .ok_and_run(&sdk_client, |builder| {
+                builder.add_global_account_data_bulk([ignored_user_list(&["@bob:example.org"])]);
+            })
+            .await;
+
+        let (sender, mut updates) = mpsc::unbounded_channel();
+        let _handle = client.subscribe_to_ignored_users(Box::new(IgnoredUsersRecorder(sender)));
+
+        // The listener is called with the current list first.
+        let ignored = timeout(updates.recv(), Duration::from_secs(1)).await.unwrap().unwrap();
+        assert_eq!(ignored, ["@bob:example.org"]);
+
+        // Then with every change.
+        server
+            .mock_sync()
+            .ok_and_run(&sdk_client, |builder| {
+                builder.add_global_account_data_bulk([ignored_user_list(&[
+                    "@bob:example.org",
+                    "@carol:example.org",
+                ])]);
+            })
+            .await;
+
+        let mut ignored = timeout(updates.recv(), Duration::from_secs(1)).await.unwrap().unwrap();
+        ignored.sort();
+        assert_eq!(ignored, ["@bob:example.org", "@carol:example.org"]);
+    }
+}
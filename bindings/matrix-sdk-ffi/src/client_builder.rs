@@ -1,12 +1,9 @@
 use std::{fs, num::NonZeroUsize, path::Path, sync::Arc, time::Duration};
 
-use futures_util::StreamExt;
 #[cfg(not(target_family = "wasm"))]
 use matrix_sdk::reqwest::Certificate;
 use matrix_sdk::{
-    crypto::{
-        types::qr_login::QrCodeModeData, CollectStrategy, DecryptionSettings, TrustRequirement,
-    },
+    crypto::{CollectStrategy, DecryptionSettings, TrustRequirement},
     encryption::{BackupDownloadStrategy, EncryptionSettings},
     event_cache::EventCacheError,
     ruma::{ServerName, UserId},
@@ -18,7 +15,7 @@ use matrix_sdk::{
     RumaApiError, SqliteStoreConfig,
 };
 use ruma::api::error::{DeserializationError, FromHttpResponseError};
-use tracing::{debug, error};
+use tracing::debug;
 use zeroize::Zeroizing;
 
 use super::client::Client;
@@ -27,9 +24,8 @@ use crate::{
     client::ClientSessionDelegate,
     error::ClientError,
     helpers::unwrap_or_clone_arc,
-    qr_code::{HumanQrLoginError, QrCodeData, QrLoginProgressListener},
-    runtime::get_runtime_handle,
-    task_handle::TaskHandle,
+    media_blob_store::{MediaBlobStore, MediaBlobStoreBridge},
+    qr_code::{HumanQrLoginError, QrCodeData, QrCodeLoginHandle, QrLoginProgressListener},
 };
 
 /// A list of bytes containing a certificate in DER or PEM form.
@@ -124,6 +120,7 @@ pub struct ClientBuilder {
     cross_process_store_locks_holder_name: Option<String>,
     enable_oidc_refresh_lock: bool,
     session_delegate: Option<Arc<dyn ClientSessionDelegate>>,
+    media_blob_store: Option<Arc<dyn MediaBlobStore>>,
     encryption_settings: EncryptionSettings,
     room_key_recipient_strategy: CollectStrategy,
     decryption_settings: DecryptionSettings,
@@ -163,6 +160,7 @@ impl ClientBuilder {
             cross_process_store_locks_holder_name: None,
             enable_oidc_refresh_lock: false,
             session_delegate: None,
+            media_blob_store: None,
             additional_root_certificates: Default::default(),
             disable_built_in_root_certificates: false,
             encryption_settings: EncryptionSettings {
@@ -204,6 +202,20 @@ impl ClientBuilder {
         Arc::new(builder)
     }
 
+    /// Store the content of the downloaded media in the given
+    /// [`MediaBlobStore`], e.g. in a location managed by the platform, while
+    /// the event cache store only keeps their metadata.
+    ///
+    /// This only applies when [`ClientBuilder::session_paths`] is set.
+    pub fn media_blob_store(
+        self: Arc<Self>,
+        media_blob_store: Box<dyn MediaBlobStore>,
+    ) -> Arc<Self> {
+        let mut builder = unwrap_or_clone_arc(self);
+        builder.media_blob_store = Some(media_blob_store.into());
+        Arc::new(builder)
+    }
+
     /// Sets the paths that the client will use to store its data and caches.
     /// Both paths **must** be unique per session as the SDK stores aren't
     /// capable of handling multiple users, however it is valid to use the
@@ -438,6 +450,11 @@ impl ClientBuilder {
             inner_builder = inner_builder
                 .sqlite_store_with_config_and_cache_path(sqlite_store_config, Some(cache_path));
 
+            if let Some(media_blob_store) = builder.media_blob_store {
+                inner_builder = inner_builder
+                    .media_blob_store(Arc::new(MediaBlobStoreBridge(media_blob_store)));
+            }
+
             Some(data_path.to_owned())
         } else {
             debug!("Not using a store path.");
@@ -586,40 +603,27 @@ impl ClientBuilder {
         oidc_configuration: &OidcConfiguration,
         progress_listener: Box<dyn QrLoginProgressListener>,
     ) -> Result<Arc<Client>, HumanQrLoginError> {
-        let QrCodeModeData::Reciprocate { server_name } = &qr_code_data.inner.mode_data else {
-            return Err(HumanQrLoginError::OtherDeviceNotSignedIn);
-        };
-
-        let builder = self.server_name_or_homeserver_url(server_name.to_owned());
+        let handle = QrCodeLoginHandle::new(self, qr_code_data, oidc_configuration)?;
+        let _progress_task = handle.subscribe_to_progress(progress_listener);
 
-        let client = builder.build().await.map_err(|e| match e {
-            ClientBuildError::SlidingSync(_) => HumanQrLoginError::SlidingSyncNotAvailable,
-            _ => {
-                error!("Couldn't build the client {e:?}");
-                HumanQrLoginError::Unknown
-            }
-        })?;
-
-        let registration_data = oidc_configuration
-            .registration_data()
-            .map_err(|_| HumanQrLoginError::OidcMetadataInvalid)?;
-
-        let oauth = client.inner.oauth();
-        let login = oauth.login_with_qr_code(&qr_code_data.inner, Some(&registration_data));
-
-        let mut progress = login.subscribe_to_progress();
-
-        // We create this task, which will get cancelled once it's dropped, just in case
-        // the progress stream doesn't end.
-        let _progress_task = TaskHandle::new(get_runtime_handle().spawn(async move {
-            while let Some(state) = progress.next().await {
-                progress_listener.on_update(state.into());
-            }
-        }));
-
-        login.await?;
+        handle.login().await
+    }
 
-        Ok(client)
+    /// Prepare the login of a new device with the provided [`QrCodeData`],
+    /// scanned from an existing device, using the login mechanism described
+    /// in [MSC4108].
+    ///
+    /// Contrary to [`ClientBuilder::build_with_qr_code`], this returns a
+    /// [`QrCodeLoginHandle`], which can be observed before starting the login
+    /// and queried for its current progress.
+    ///
+    /// [MSC4108]: https://github.com/matrix-org/matrix-spec-proposals/pull/4108
+    pub fn login_with_qr_code(
+        self: Arc<Self>,
+        qr_code_data: &QrCodeData,
+        oidc_configuration: &OidcConfiguration,
+    ) -> Result<Arc<QrCodeLoginHandle>, HumanQrLoginError> {
+        Ok(Arc::new(QrCodeLoginHandle::new(self, qr_code_data, oidc_configuration)?))
     }
 }
 
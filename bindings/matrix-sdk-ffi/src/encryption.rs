@@ -6,13 +6,15 @@ use matrix_sdk::{
     encryption::{backups, recovery},
 };
 use matrix_sdk_common::{SendOutsideWasm, SyncOutsideWasm};
+use ruma::{uint, MilliSecondsSinceUnixEpoch, OwnedDeviceId, SecondsSinceUnixEpoch, UserId};
 use thiserror::Error;
 use tracing::{error, info};
+use url::Url;
 use zeroize::Zeroize;
 
 use crate::{
     client::Client, error::ClientError, ruma::AuthData, runtime::get_runtime_handle,
-    task_handle::TaskHandle,
+    task_handle::TaskHandle, utils::Timestamp,
 };
 
 #[derive(uniffi::Object)]
@@ -47,6 +49,11 @@ pub trait VerificationStateListener: SyncOutsideWasm + SendOutsideWasm {
     fn on_update(&self, status: VerificationState);
 }
 
+#[matrix_sdk_ffi_macros::export(callback_interface)]
+pub trait DetailedVerificationStateListener: SyncOutsideWasm + SendOutsideWasm {
+    fn on_update(&self, state: DetailedVerificationState);
+}
+
 #[derive(uniffi::Enum)]
 pub enum BackupUploadState {
     Waiting,
@@ -216,6 +223,52 @@ impl From<encryption::VerificationState> for VerificationState {
     }
 }
 
+/// The verification state of our own device, with the reason why it's
+/// unverified.
+#[derive(uniffi::Enum)]
+pub enum DetailedVerificationState {
+    /// The verification state is unknown for now.
+    Unknown,
+    /// The device has been signed by the user identity of the account.
+    Verified,
+    /// The device is unverified.
+    Unverified { reason: UnverifiedReason },
+}
+
+impl From<encryption::DetailedVerificationState> for DetailedVerificationState {
+    fn from(value: encryption::DetailedVerificationState) -> Self {
+        match value {
+            encryption::DetailedVerificationState::Unknown => Self::Unknown,
+            encryption::DetailedVerificationState::Verified => Self::Verified,
+            encryption::DetailedVerificationState::Unverified { reason } => {
+                Self::Unverified { reason: reason.into() }
+            }
+        }
+    }
+}
+
+/// Why our own device is unverified.
+#[derive(uniffi::Enum)]
+pub enum UnverifiedReason {
+    /// Cross-signing hasn't been set up for the account yet.
+    MissingIdentity,
+    /// The user identity of the account has been replaced since it was
+    /// verified, e.g. because it has been reset from another device.
+    NewIdentity,
+    /// The user identity of the account hasn't signed this device.
+    MissingSignature,
+}
+
+impl From<encryption::UnverifiedReason> for UnverifiedReason {
+    fn from(value: encryption::UnverifiedReason) -> Self {
+        match value {
+            encryption::UnverifiedReason::MissingIdentity => Self::MissingIdentity,
+            encryption::UnverifiedReason::NewIdentity => Self::NewIdentity,
+            encryption::UnverifiedReason::MissingSignature => Self::MissingSignature,
+        }
+    }
+}
+
 #[matrix_sdk_ffi_macros::export]
 impl Encryption {
     /// Get the public ed25519 key of our own device. This is usually what is
@@ -375,7 +428,8 @@ impl Encryption {
         if let Some(reset_handle) =
             self.inner.recovery().reset_identity().await.map_err(ClientError::from_err)?
         {
-            return Ok(Some(Arc::new(IdentityResetHandle { inner: reset_handle })));
+            let homeserver = self._client.inner.homeserver();
+            return Ok(Some(Arc::new(IdentityResetHandle { inner: reset_handle, homeserver })));
         }
 
         Ok(None)
@@ -406,6 +460,24 @@ impl Encryption {
         })))
     }
 
+    /// Subscribe to the verification state of our own device, with the reason
+    /// why it's unverified.
+    ///
+    /// The listener is called with the current state immediately, then every
+    /// time it's updated.
+    pub fn subscribe_to_verification_state(
+        self: Arc<Self>,
+        listener: Box<dyn DetailedVerificationStateListener>,
+    ) -> Arc<TaskHandle> {
+        let mut subscriber = self.inner.detailed_verification_state();
+
+        Arc::new(TaskHandle::new(get_runtime_handle().spawn(async move {
+            while let Some(state) = subscriber.next().await {
+                listener.on_update(state.into());
+            }
+        })))
+    }
+
     /// Waits for end-to-end encryption initialization tasks to finish, if any
     /// was running in the background.
     pub async fn wait_for_e2ee_initialization_tasks(&self) {
@@ -449,6 +521,98 @@ impl Encryption {
         let identity = self.inner.request_user_identity(user_id.as_str().try_into()?).await?;
         Ok(identity.map(|identity| Arc::new(UserIdentity { inner: identity })))
     }
+
+    /// Get the health of the Olm sessions shared with a device: the known
+    /// sessions, and whether the current one is wedged.
+    ///
+    /// This is useful to diagnose why a device can't decrypt our messages, or
+    /// why we can't decrypt its messages.
+    ///
+    /// Returns `None` if the device is unknown.
+    pub async fn olm_session_health(
+        &self,
+        user_id: String,
+        device_id: String,
+    ) -> Result<Option<OlmSessionHealth>, ClientError> {
+        let user_id = UserId::parse(user_id)?;
+        let device_id: OwnedDeviceId = device_id.into();
+
+        let Some(device) = self.inner.get_device(&user_id, &device_id).await? else {
+            return Ok(None);
+        };
+
+        Ok(Some(device.olm_session_health().await?.into()))
+    }
+
+    /// Create a new Olm session with a device, and send it a dummy message with
+    /// the new session, so it starts using it too.
+    ///
+    /// This is useful to resolve the case where a device can't decrypt our
+    /// messages anymore.
+    ///
+    /// Returns `false` if the device is unknown, or if a new session can't be
+    /// created with it.
+    pub async fn reestablish_olm_session(
+        &self,
+        user_id: String,
+        device_id: String,
+    ) -> Result<bool, ClientError> {
+        let user_id = UserId::parse(user_id)?;
+        let device_id: OwnedDeviceId = device_id.into();
+
+        let Some(device) = self.inner.get_device(&user_id, &device_id).await? else {
+            return Ok(false);
+        };
+
+        Ok(device.reestablish_olm_session().await?)
+    }
+}
+
+/// Diagnostic information about an Olm session shared with a device.
+#[derive(uniffi::Record)]
+pub struct OlmSessionInfo {
+    /// The unique ID of the session.
+    session_id: String,
+    /// When the session was created, in milliseconds since epoch.
+    creation_timestamp: Timestamp,
+    /// When the session was last used to encrypt or decrypt a message, in
+    /// milliseconds since epoch.
+    last_use_timestamp: Timestamp,
+}
+
+impl From<matrix_sdk::crypto::OlmSessionInfo> for OlmSessionInfo {
+    fn from(value: matrix_sdk::crypto::OlmSessionInfo) -> Self {
+        Self {
+            session_id: value.session_id,
+            creation_timestamp: seconds_to_timestamp(value.creation_time),
+            last_use_timestamp: seconds_to_timestamp(value.last_use_time),
+        }
+    }
+}
+
+/// The health of the Olm sessions shared with a device.
+#[derive(uniffi::Record)]
+pub struct OlmSessionHealth {
+    /// The Olm sessions shared with the device, from the most recently created
+    /// one, which is used to encrypt messages to the device, to the oldest
+    /// one.
+    sessions: Vec<OlmSessionInfo>,
+    /// Whether the Olm session with the device is wedged, i.e. a message from
+    /// the device couldn't be decrypted, and a new session will be created.
+    is_wedged: bool,
+}
+
+impl From<matrix_sdk::crypto::OlmSessionHealth> for OlmSessionHealth {
+    fn from(value: matrix_sdk::crypto::OlmSessionHealth) -> Self {
+        Self {
+            sessions: value.sessions.into_iter().map(Into::into).collect(),
+            is_wedged: value.is_wedged,
+        }
+    }
+}
+
+fn seconds_to_timestamp(seconds: SecondsSinceUnixEpoch) -> Timestamp {
+    MilliSecondsSinceUnixEpoch(seconds.0.saturating_mul(uint!(1000))).into()
 }
 
 /// The E2EE identity of a user.
@@ -518,9 +682,17 @@ impl UserIdentity {
     }
 }
 
+#[matrix_sdk_ffi_macros::export(callback_interface)]
+pub trait UiaaFallbackUrlOpener: SyncOutsideWasm + SendOutsideWasm {
+    /// Open the given URL of the fallback web page of the homeserver, where the
+    /// user can complete a stage of the user-interactive authentication.
+    fn open_url(&self, url: String);
+}
+
 #[derive(uniffi::Object)]
 pub struct IdentityResetHandle {
     pub(crate) inner: matrix_sdk::encryption::recovery::IdentityResetHandle,
+    homeserver: Url,
 }
 
 #[matrix_sdk_ffi_macros::export]
@@ -546,15 +718,105 @@ impl IdentityResetHandle {
         }
     }
 
+    /// Continue the identity reset by completing the given user-interactive
+    /// authentication `stage`, e.g. `m.login.sso`, on the fallback web page of
+    /// the homeserver.
+    ///
+    /// The URL of the page is given to `opener`, then the reset is resumed as
+    /// soon as the homeserver reports that the stage has been completed.
+    pub async fn reset_with_fallback(
+        &self,
+        stage: String,
+        opener: Box<dyn UiaaFallbackUrlOpener>,
+    ) -> Result<(), ClientError> {
+        let encryption::CrossSigningResetAuthType::Uiaa(info) = self.inner.auth_type() else {
+            return Err(ClientError::from_str(
+                "The identity reset doesn't use user-interactive authentication",
+                None,
+            ));
+        };
+
+        let Some(session) = info.session.clone() else {
+            return Err(ClientError::from_str(
+                "The homeserver didn't start a user-interactive authentication session",
+                None,
+            ));
+        };
+
+        opener.open_url(uiaa_fallback_url(&self.homeserver, &stage, &session).to_string());
+
+        self.inner
+            .reset(Some(AuthData::FallbackAcknowledgement { session }.into()))
+            .await
+            .map_err(ClientError::from_err)
+    }
+
+    /// Get a handle to wait for the approval of the identity reset, if it's
+    /// using OIDC.
+    pub fn oidc_handle(self: Arc<Self>) -> Option<Arc<OidcCrossSigningResetHandle>> {
+        let encryption::CrossSigningResetAuthType::OAuth(info) = self.inner.auth_type() else {
+            return None;
+        };
+
+        let approval_url = info.approval_url.to_string();
+        Some(Arc::new(OidcCrossSigningResetHandle { identity_reset_handle: self, approval_url }))
+    }
+
     pub async fn cancel(&self) {
         self.inner.cancel().await;
     }
 }
 
+/// A handle to wait for the user to approve the reset of their cross-signing
+/// keys, on the account management page of the OIDC provider.
+#[derive(uniffi::Object)]
+pub struct OidcCrossSigningResetHandle {
+    identity_reset_handle: Arc<IdentityResetHandle>,
+    approval_url: String,
+}
+
+#[matrix_sdk_ffi_macros::export]
+impl OidcCrossSigningResetHandle {
+    /// The URL where the user can approve the reset of the cross-signing keys.
+    pub fn approval_url(&self) -> String {
+        self.approval_url.clone()
+    }
+
+    /// Poll the homeserver until the user has approved the reset, then finish
+    /// the identity reset.
+    ///
+    /// Returns early, without an error, if the reset is cancelled.
+    pub async fn wait_for_approval(&self) -> Result<(), ClientError> {
+        self.identity_reset_handle.inner.reset(None).await.map_err(ClientError::from_err)
+    }
+
+    /// Cancel the identity reset.
+    pub async fn cancel(&self) {
+        self.identity_reset_handle.cancel().await;
+    }
+}
+
+/// Build the URL of the fallback web page of the homeserver for the given
+/// user-interactive authentication `stage` and `session`.
+fn uiaa_fallback_url(homeserver: &Url, stage: &str, session: &str) -> Url {
+    let mut url = homeserver.clone();
+
+    if let Ok(mut segments) = url.path_segments_mut() {
+        segments
+            .pop_if_empty()
+            .extend(["_matrix", "client", "v3", "auth", stage, "fallback", "web"]);
+    }
+
+    url.query_pairs_mut().append_pair("session", session);
+    url
+}
+
 #[derive(uniffi::Enum)]
 pub enum CrossSigningResetAuthType {
     /// The homeserver requires user-interactive authentication.
-    Uiaa,
+    Uiaa {
+        info: UiaaCrossSigningResetInfo,
+    },
     // /// OIDC is used for authentication and the user needs to open a URL to
     // /// approve the upload of cross-signing keys.
     Oidc {
@@ -565,12 +827,38 @@ pub enum CrossSigningResetAuthType {
 impl From<&matrix_sdk::encryption::CrossSigningResetAuthType> for CrossSigningResetAuthType {
     fn from(value: &matrix_sdk::encryption::CrossSigningResetAuthType) -> Self {
         match value {
-            encryption::CrossSigningResetAuthType::Uiaa(_) => Self::Uiaa,
+            encryption::CrossSigningResetAuthType::Uiaa(info) => Self::Uiaa { info: info.into() },
             encryption::CrossSigningResetAuthType::OAuth(info) => Self::Oidc { info: info.into() },
         }
     }
 }
 
+#[derive(uniffi::Record)]
+pub struct UiaaCrossSigningResetInfo {
+    /// The ID of the user-interactive authentication session, to use with
+    /// [`AuthData::FallbackAcknowledgement`].
+    pub session: Option<String>,
+    /// The flows the user can complete, each being a list of stage types, e.g.
+    /// `m.login.password` or `m.login.sso`.
+    pub flows: Vec<Vec<String>>,
+    /// The stages that have already been completed.
+    pub completed: Vec<String>,
+}
+
+impl From<&ruma::api::client::uiaa::UiaaInfo> for UiaaCrossSigningResetInfo {
+    fn from(value: &ruma::api::client::uiaa::UiaaInfo) -> Self {
+        Self {
+            session: value.session.clone(),
+            flows: value
+                .flows
+                .iter()
+                .map(|flow| flow.stages.iter().map(ToString::to_string).collect())
+                .collect(),
+            completed: value.completed.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
 #[derive(uniffi::Record)]
 pub struct OidcCrossSigningResetInfo {
     /// The URL where the user can approve the reset of the cross-signing keys.
@@ -582,3 +870,68 @@ impl From<&matrix_sdk::encryption::OAuthCrossSigningResetInfo> for OidcCrossSign
         Self { approval_url: value.approval_url.to_string() }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ruma::api::client::uiaa::UiaaInfo;
+    use serde_json::json;
+    use url::Url;
+
+    use super::{uiaa_fallback_url, UiaaCrossSigningResetInfo};
+    use crate::ruma::AuthData;
+
+    #[test]
+    fn test_uiaa_fallback_url() {
+        let homeserver = Url::parse("https://matrix.example.com").unwrap();
+        assert_eq!(
+            uiaa_fallback_url(&homeserver, "m.login.sso", "abc").as_str(),
+            "https://matrix.example.com/_matrix/client/v3/auth/m.login.sso/fallback/web?session=abc"
+        );
+
+        // The path of the homeserver is kept, with or without a trailing slash.
+        let homeserver = Url::parse("https://example.com/matrix/").unwrap();
+        assert_eq!(
+            uiaa_fallback_url(&homeserver, "m.login.sso", "abc").as_str(),
+            "https://example.com/matrix/_matrix/client/v3/auth/m.login.sso/fallback/web?session=abc"
+        );
+        let homeserver = Url::parse("https://example.com/matrix").unwrap();
+        assert_eq!(
+            uiaa_fallback_url(&homeserver, "m.login.sso", "abc").as_str(),
+            "https://example.com/matrix/_matrix/client/v3/auth/m.login.sso/fallback/web?session=abc"
+        );
+
+        // The stage and session are escaped.
+        let homeserver = Url::parse("https://matrix.example.com").unwrap();
+        assert_eq!(
+            uiaa_fallback_url(&homeserver, "org.example/stage", "a b&c").as_str(),
+            "https://matrix.example.com/_matrix/client/v3/auth/org.example%2Fstage/fallback/web?session=a+b%26c"
+        );
+    }
+
+    #[test]
+    fn test_uiaa_cross_signing_reset_info() {
+        let info: UiaaInfo = serde_json::from_value(json!({
+            "flows": [
+                { "stages": ["m.login.password"] },
+                { "stages": ["m.login.sso", "m.login.terms"] },
+            ],
+            "completed": ["m.login.terms"],
+            "params": {},
+            "session": "abc",
+        }))
+        .unwrap();
+
+        let info = UiaaCrossSigningResetInfo::from(&info);
+        assert_eq!(info.session.as_deref(), Some("abc"));
+        assert_eq!(info.flows, [vec!["m.login.password"], vec!["m.login.sso", "m.login.terms"]]);
+        assert_eq!(info.completed, ["m.login.terms"]);
+    }
+
+    #[test]
+    fn test_fallback_acknowledgement_auth_data() {
+        let auth_data: ruma::api::client::uiaa::AuthData =
+            AuthData::FallbackAcknowledgement { session: "abc".to_owned() }.into();
+
+        assert_eq!(serde_json::to_value(&auth_data).unwrap(), json!({ "session": "abc" }));
+    }
+}
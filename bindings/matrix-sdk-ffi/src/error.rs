@@ -1,5 +1,12 @@
-use std::{collections::HashMap, error::Error, fmt, fmt::Display};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fmt,
+    fmt::Display,
+    iter,
+};
 
+use as_variant::as_variant;
 use matrix_sdk::{
     authentication::oauth::OAuthError,
     encryption::{identities::RequestVerificationError, CryptoStoreError},
@@ -8,12 +15,12 @@ use matrix_sdk::{
     room::edit::EditError,
     send_queue::RoomSendQueueError,
     HttpError, IdParseError, NotificationSettingsError as SdkNotificationSettingsError,
-    QueueWedgeError as SdkQueueWedgeError, StoreError,
+    QueueWedgeError as SdkQueueWedgeError, RumaApiError, StoreError,
 };
 use matrix_sdk_ui::{encryption_sync_service, notification_client, sync_service, timeline};
 use ruma::{
     api::client::error::{ErrorBody, ErrorKind as RumaApiErrorKind, RetryAfter},
-    MilliSecondsSinceUnixEpoch,
+    MilliSecondsSinceUnixEpoch, OwnedDeviceId, OwnedUserId,
 };
 use tracing::warn;
 use uniffi::UnexpectedUniFFICallbackError;
@@ -24,8 +31,64 @@ use crate::{room_list::RoomListError, timeline::FocusEventError};
 pub enum ClientError {
     #[error("client error: {msg}")]
     Generic { msg: String, details: Option<String> },
+    /// The homeserver returned a Matrix error, with an `errcode`.
     #[error("api error {code}: {msg}")]
-    MatrixApi { kind: ErrorKind, code: String, msg: String, details: Option<String> },
+    MatrixApi {
+        kind: ErrorKind,
+        /// The Matrix error code, e.g. `M_LIMIT_EXCEEDED`.
+        code: String,
+        msg: String,
+        details: Option<String>,
+        /// The HTTP status code of the response.
+        status_code: u16,
+        /// How long to wait before retrying the request, in milliseconds, if
+        /// the homeserver told us.
+        retry_after_ms: Option<u64>,
+        category: ErrorCategory,
+    },
+    /// The homeserver returned an HTTP error which isn't a Matrix error.
+    #[error("http error {status_code}: {msg}")]
+    Http { status_code: u16, category: ErrorCategory, msg: String, details: Option<String> },
+    /// The homeserver couldn't be reached, or the request timed out.
+    #[error("network error: {msg}")]
+    Network { msg: String, details: Option<String>, category: ErrorCategory },
+}
+
+/// The broad category of a [`ClientError`] coming from the homeserver, so apps
+/// can decide how to react to it without matching on error messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, uniffi::Enum)]
+pub enum ErrorCategory {
+    /// The homeserver couldn't be reached; retrying later may help.
+    Network,
+    /// The session isn't valid anymore, or the user can't use their account.
+    Auth,
+    /// The homeserver failed to handle the request.
+    Server,
+    /// The homeserver rejected the request.
+    Client,
+}
+
+impl ErrorCategory {
+    fn from_http_status(status_code: u16, kind: Option<&ErrorKind>) -> Self {
+        let is_auth_error = matches!(
+            kind,
+            Some(
+                ErrorKind::MissingToken
+                    | ErrorKind::UnknownToken { .. }
+                    | ErrorKind::UserDeactivated
+                    | ErrorKind::UserLocked
+                    | ErrorKind::UserSuspended
+            )
+        );
+
+        if is_auth_error || status_code == 401 {
+            Self::Auth
+        } else if status_code >= 500 {
+            Self::Server
+        } else {
+            Self::Client
+        }
+    }
 }
 
 impl ClientError {
@@ -34,10 +97,110 @@ impl ClientError {
         Self::Generic { msg: error.to_string(), details }
     }
 
-    pub(crate) fn from_err<E: Error>(e: E) -> Self {
+    pub(crate) fn from_err<E: Error + 'static>(e: E) -> Self {
+        // Errors returned by the homeserver, or failing to reach it, may be wrapped
+        // into other errors; they're still HTTP or network errors for the app.
+        if let Some(error) = find_http_error(&e) {
+            return error;
+        }
+
         let details = Some(format!("{e:?}"));
         Self::from_str(e, details)
     }
+
+    fn network(e: &reqwest::Error) -> Self {
+        warn!("Network error: {e}");
+        Self::Network {
+            msg: e.to_string(),
+            details: Some(format!("{e:?}")),
+            category: ErrorCategory::Network,
+        }
+    }
+
+    /// Convert the given error of the HTTP client, if it has a response from
+    /// the homeserver, or if it failed to reach it.
+    fn from_reqwest(e: &reqwest::Error) -> Option<Self> {
+        if let Some(status) = e.status() {
+            let status_code = status.as_u16();
+            warn!("HTTP error {status_code}: {e}");
+            Some(Self::Http {
+                status_code,
+                category: ErrorCategory::from_http_status(status_code, None),
+                msg: e.to_string(),
+                details: Some(format!("{e:?}")),
+            })
+        } else {
+            is_network_error(e).then(|| Self::network(e))
+        }
+    }
+
+    /// Convert the given error of an SDK request, if it has a response from
+    /// the homeserver, or if it failed to reach it.
+    fn from_http_error(e: &HttpError) -> Option<Self> {
+        let status_code = match e.as_ruma_api_error() {
+            Some(RumaApiError::ClientApi(api_error)) => {
+                let status_code = api_error.status_code.as_u16();
+
+                if let ErrorBody::Standard { kind, message } = &api_error.body {
+                    // If we can't parse the API error kind, an HTTP error is returned instead.
+                    if let Ok(ffi_kind) = ErrorKind::try_from(kind.to_owned()) {
+                        let retry_after_ms = as_variant!(
+                            &ffi_kind,
+                            ErrorKind::LimitExceeded { retry_after_ms } => *retry_after_ms
+                        )
+                        .flatten();
+
+                        return Some(Self::MatrixApi {
+                            code: kind.errcode().to_string(),
+                            msg: message.to_owned(),
+                            details: Some(format!("{api_error:?}")),
+                            status_code,
+                            retry_after_ms,
+                            category: ErrorCategory::from_http_status(status_code, Some(&ffi_kind)),
+                            kind: ffi_kind,
+                        });
+                    }
+                }
+
+                status_code
+            }
+            Some(RumaApiError::Other(matrix_error)) => matrix_error.status_code.as_u16(),
+            Some(RumaApiError::Uiaa(_)) | None => {
+                return as_variant!(e, HttpError::Reqwest).and_then(Self::from_reqwest);
+            }
+        };
+
+        warn!("HTTP error {status_code}: {e}");
+
+        Some(Self::Http {
+            status_code,
+            category: ErrorCategory::from_http_status(status_code, None),
+            msg: e.to_string(),
+            details: Some(format!("{e:?}")),
+        })
+    }
+}
+
+/// Find the error returned by the homeserver, or which failed to reach it, in
+/// the chain of sources of the given error, and convert it.
+fn find_http_error(error: &(dyn Error + 'static)) -> Option<ClientError> {
+    iter::successors(Some(error), |error| error.source()).find_map(|error| {
+        // The SDK errors wrap the HTTP errors transparently, so they don't appear in
+        // the chain of sources by themselves.
+        if let Some(matrix_sdk::Error::Http(http_error)) = error.downcast_ref() {
+            ClientError::from_http_error(http_error)
+        } else if let Some(http_error) = error.downcast_ref::<HttpError>() {
+            ClientError::from_http_error(http_error)
+        } else {
+            ClientError::from_reqwest(error.downcast_ref::<reqwest::Error>()?)
+        }
+    })
+}
+
+/// Whether the given error happened before getting a response from the
+/// homeserver.
+fn is_network_error(e: &reqwest::Error) -> bool {
+    e.status().is_none() && (e.is_timeout() || e.is_connect() || e.is_request())
 }
 
 impl From<anyhow::Error> for ClientError {
@@ -49,7 +212,7 @@ impl From<anyhow::Error> for ClientError {
 
 impl From<reqwest::Error> for ClientError {
     fn from(e: reqwest::Error) -> Self {
-        Self::from_err(e)
+        Self::from_reqwest(&e).unwrap_or_else(|| Self::from_err(e))
     }
 }
 
@@ -62,24 +225,7 @@ impl From<UnexpectedUniFFICallbackError> for ClientError {
 impl From<matrix_sdk::Error> for ClientError {
     fn from(e: matrix_sdk::Error) -> Self {
         match e {
-            matrix_sdk::Error::Http(http_error) => {
-                if let Some(api_error) = http_error.as_client_api_error() {
-                    if let ErrorBody::Standard { kind, message } = &api_error.body {
-                        let code = kind.errcode().to_string();
-                        let Ok(kind) = kind.to_owned().try_into() else {
-                            // We couldn't parse the API error, so we return a generic one instead
-                            return (*http_error).into();
-                        };
-                        return Self::MatrixApi {
-                            kind,
-                            code,
-                            msg: message.to_owned(),
-                            details: Some(format!("{api_error:?}")),
-                        };
-                    }
-                }
-                (*http_error).into()
-            }
+            matrix_sdk::Error::Http(http_error) => (*http_error).into(),
             _ => Self::from_err(e),
         }
     }
@@ -99,7 +245,7 @@ impl From<CryptoStoreError> for ClientError {
 
 impl From<HttpError> for ClientError {
     fn from(e: HttpError) -> Self {
-        Self::from_err(e)
+        Self::from_http_error(&e).unwrap_or_else(|| Self::from_err(e))
     }
 }
 
@@ -177,7 +323,10 @@ impl From<RoomListError> for ClientError {
 
 impl From<EventCacheError> for ClientError {
     fn from(e: EventCacheError) -> Self {
-        Self::from_err(e)
+        match e {
+            EventCacheError::BackpaginationError(error) => (*error).into(),
+            _ => Self::from_err(e),
+        }
     }
 }
 
@@ -228,6 +377,14 @@ pub enum QueueWedgeError {
         user_device_map: HashMap<String, Vec<String>>,
     },
 
+    /// This error occurs when the room only shares its keys with trusted
+    /// devices, and some devices of its members are neither trusted nor have
+    /// been confirmed by the user.
+    UntrustedDevices {
+        /// The untrusted devices as a Map of userID to deviceID.
+        user_device_map: HashMap<String, Vec<String>>,
+    },
+
     /// This error occurs when a previously verified user is not anymore, and
     /// the current encryption setting prohibit sharing when it happens.
     IdentityViolations {
@@ -257,6 +414,9 @@ impl Display for QueueWedgeError {
             QueueWedgeError::InsecureDevices { .. } => {
                 f.write_str("There are insecure devices in the room")
             }
+            QueueWedgeError::UntrustedDevices { .. } => {
+                f.write_str("There are unconfirmed untrusted devices in the room")
+            }
             QueueWedgeError::IdentityViolations { .. } => {
                 f.write_str("Some users that were previously verified are not anymore")
             }
@@ -277,17 +437,12 @@ impl Display for QueueWedgeError {
 impl From<SdkQueueWedgeError> for QueueWedgeError {
     fn from(value: SdkQueueWedgeError) -> Self {
         match value {
-            SdkQueueWedgeError::InsecureDevices { user_device_map } => Self::InsecureDevices {
-                user_device_map: user_device_map
-                    .iter()
-                    .map(|(user_id, devices)| {
-                        (
-                            user_id.to_string(),
-                            devices.iter().map(|device_id| device_id.to_string()).collect(),
-                        )
-                    })
-                    .collect(),
-            },
+            SdkQueueWedgeError::InsecureDevices { user_device_map } => {
+                Self::InsecureDevices { user_device_map: user_device_map_to_ffi(&user_device_map) }
+            }
+            SdkQueueWedgeError::UntrustedDevices { user_device_map } => {
+                Self::UntrustedDevices { user_device_map: user_device_map_to_ffi(&user_device_map) }
+            }
             SdkQueueWedgeError::IdentityViolations { users } => Self::IdentityViolations {
                 users: users.iter().map(ruma::OwnedUserId::to_string).collect(),
             },
@@ -301,6 +456,17 @@ impl From<SdkQueueWedgeError> for QueueWedgeError {
     }
 }
 
+fn user_device_map_to_ffi(
+    user_device_map: &BTreeMap<OwnedUserId, Vec<OwnedDeviceId>>,
+) -> HashMap<String, Vec<String>> {
+    user_device_map
+        .iter()
+        .map(|(user_id, devices)| {
+            (user_id.to_string(), devices.iter().map(|device_id| device_id.to_string()).collect())
+        })
+        .collect()
+}
+
 #[derive(Debug, thiserror::Error, uniffi::Error)]
 #[uniffi(flat_error)]
 pub enum RoomError {
@@ -817,3 +983,176 @@ impl TryFrom<RumaApiErrorKind> for ErrorKind {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use matrix_sdk::{
+        event_cache::EventCacheError, reqwest, reqwest::StatusCode, HttpError, RumaApiError,
+    };
+    use matrix_sdk_ui::timeline;
+    use ruma::api::{
+        client::error::{ErrorBody, ErrorKind as RumaApiErrorKind, RetryAfter},
+        error::FromHttpResponseError,
+    };
+    use serde_json::json;
+
+    use super::{ClientError, ErrorCategory, ErrorKind};
+
+    /// Get the error of a request which can't reach its server.
+    async fn unreachable_server_error() -> reqwest::Error {
+        reqwest::Client::new()
+            .get("http://127.0.0.1:1/")
+            .send()
+            .await
+            .expect_err("nothing should be listening on port 1")
+    }
+
+    fn api_error(status_code: StatusCode, body: ErrorBody) -> HttpError {
+        HttpError::Api(Box::new(FromHttpResponseError::Server(RumaApiError::ClientApi(
+            ruma::api::client::Error::new(status_code, body),
+        ))))
+    }
+
+    #[test]
+    fn test_matrix_api_error_has_retry_hint() {
+        let error: ClientError = api_error(
+            StatusCode::TOO_MANY_REQUESTS,
+            ErrorBody::Standard {
+                kind: RumaApiErrorKind::LimitExceeded {
+                    retry_after: Some(RetryAfter::Delay(Duration::from_secs(2))),
+                },
+                message: "slow down".to_owned(),
+            },
+        )
+        .into();
+
+        let ClientError::MatrixApi { kind, code, status_code, retry_after_ms, category, .. } =
+            error
+        else {
+            panic!("expected a Matrix API error");
+        };
+        assert_eq!(kind, ErrorKind::LimitExceeded { retry_after_ms: Some(2000) });
+        assert_eq!(code, "M_LIMIT_EXCEEDED");
+        assert_eq!(status_code, 429);
+        assert_eq!(retry_after_ms, Some(2000));
+        assert_eq!(category, ErrorCategory::Client);
+    }
+
+    #[test]
+    fn test_matrix_api_error_categories() {
+        let error: ClientError = api_error(
+            StatusCode::UNAUTHORIZED,
+            ErrorBody::Standard {
+                kind: RumaApiErrorKind::UnknownToken { soft_logout: false },
+                message: "who are you?".to_owned(),
+            },
+        )
+        .into();
+        let ClientError::MatrixApi { category, retry_after_ms, .. } = error else {
+            panic!("expected a Matrix API error");
+        };
+        assert_eq!(category, ErrorCategory::Auth);
+        assert_eq!(retry_after_ms, None);
+
+        let error: ClientError = api_error(
+            StatusCode::NOT_FOUND,
+            ErrorBody::Standard {
+                kind: RumaApiErrorKind::NotFound,
+                message: "nothing here".to_owned(),
+            },
+        )
+        .into();
+        let ClientError::MatrixApi { category, status_code, .. } = error else {
+            panic!("expected a Matrix API error");
+        };
+        assert_eq!(category, ErrorCategory::Client);
+        assert_eq!(status_code, 404);
+    }
+
+    #[test]
+    fn test_non_matrix_http_error() {
+        let error: ClientError =
+            api_error(StatusCode::BAD_GATEWAY, ErrorBody::Json(json!({ "oops": true }))).into();
+
+        let ClientError::Http { status_code, category, .. } = error else {
+            panic!("expected an HTTP error");
+        };
+        assert_eq!(status_code, 502);
+        assert_eq!(category, ErrorCategory::Server);
+    }
+
+    #[tokio::test]
+    async fn test_network_error() {
+        let error: ClientError = unreachable_server_error().await.into();
+        let ClientError::Network { category, .. } = error else {
+            panic!("expected a network error");
+        };
+        assert_eq!(category, ErrorCategory::Network);
+
+        // Also when it comes from an SDK request.
+        let error: ClientError = matrix_sdk::Error::from(unreachable_server_error().await).into();
+        let ClientError::Network { category, .. } = error else {
+            panic!("expected a network error");
+        };
+        assert_eq!(category, ErrorCategory::Network);
+    }
+
+    #[tokio::test]
+    async fn test_wrapped_network_error() {
+        // A network error which is the source of another error.
+        let error: ClientError =
+            timeline::Error::FailedFetchingMedia(unreachable_server_error().await.into()).into();
+        assert!(matches!(error, ClientError::Network { category: ErrorCategory::Network, .. }));
+
+        // A network error which is wrapped transparently into another error.
+        let error: ClientError =
+            EventCacheError::BackpaginationError(Box::new(unreachable_server_error().await.into()))
+                .into();
+        assert!(matches!(error, ClientError::Network { category: ErrorCategory::Network, .. }));
+
+        // Other errors are still generic.
+        let error: ClientError = timeline::Error::UnsupportedEvent.into();
+        assert!(matches!(error, ClientError::Generic { .. }));
+    }
+
+    #[test]
+    fn test_wrapped_matrix_api_error() {
+        // A Matrix API error which is the source of another error.
+        let error: ClientError = timeline::Error::FailedFetchingMedia(
+            api_error(
+                StatusCode::TOO_MANY_REQUESTS,
+                ErrorBody::Standard {
+                    kind: RumaApiErrorKind::LimitExceeded {
+                        retry_after: Some(RetryAfter::Delay(Duration::from_secs(3))),
+                    },
+                    message: "slow down".to_owned(),
+                },
+            )
+            .into(),
+        )
+        .into();
+
+        let ClientError::MatrixApi { kind, code, status_code, retry_after_ms, category, .. } =
+            error
+        else {
+            panic!("expected a Matrix API error");
+        };
+        assert_eq!(kind, ErrorKind::LimitExceeded { retry_after_ms: Some(3000) });
+        assert_eq!(code, "M_LIMIT_EXCEEDED");
+        assert_eq!(status_code, 429);
+        assert_eq!(retry_after_ms, Some(3000));
+        assert_eq!(category, ErrorCategory::Client);
+
+        // A non-Matrix HTTP error which is the source of another error.
+        let error: ClientError = timeline::Error::FailedFetchingMedia(
+            api_error(StatusCode::BAD_GATEWAY, ErrorBody::Json(json!({ "oops": true }))).into(),
+        )
+        .into();
+        assert!(matches!(
+            error,
+            ClientError::Http { status_code: 502, category: ErrorCategory::Server, .. }
+        ));
+    }
+}
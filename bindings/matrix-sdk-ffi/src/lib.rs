@@ -12,6 +12,8 @@ mod event;
 mod helpers;
 mod identity_status_change;
 mod live_location_share;
+mod media_blob_store;
+mod media_transfer;
 mod notification;
 mod notification_settings;
 mod platform;
@@ -25,6 +27,7 @@ mod room_preview;
 mod ruma;
 mod runtime;
 mod session_verification;
+mod space;
 mod sync_service;
 mod task_handle;
 mod timeline;
@@ -30,3 +30,22 @@ pub struct LiveLocationShare {
     /// The user ID of the person sharing their live location.
     pub user_id: String,
 }
+
+impl From<matrix_sdk::live_location_share::LiveLocationShare> for LiveLocationShare {
+    fn from(share: matrix_sdk::live_location_share::LiveLocationShare) -> Self {
+        Self {
+            last_location: LastLocation {
+                location: LocationContent {
+                    body: "".to_owned(),
+                    geo_uri: share.last_location.location.uri.to_string(),
+                    description: None,
+                    zoom_level: None,
+                    asset: None,
+                },
+                ts: share.last_location.ts.0.into(),
+            },
+            is_live: share.beacon_info.is_some_and(|beacon_info| beacon_info.is_live()),
+            user_id: share.user_id.to_string(),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fmt, sync::Arc};
+
+use matrix_sdk::{async_trait, event_cache::EventCacheStoreError, media};
+use matrix_sdk_common::{SendOutsideWasm, SyncOutsideWasm};
+
+use crate::{error::ClientError, runtime::get_runtime_handle};
+
+/// A storage for the content of the downloaded media, in a location managed by
+/// the platform, like a file provider on iOS or the Storage Access Framework on
+/// Android.
+///
+/// The SDK keeps the metadata of the media in its event cache store, and only
+/// delegates the storage of their content. The keys are random alphanumeric
+/// strings, which can be used as file names.
+///
+/// The methods are called on a background thread, so they can block.
+#[matrix_sdk_ffi_macros::export(callback_interface)]
+pub trait MediaBlobStore: SyncOutsideWasm + SendOutsideWasm {
+    /// Store a blob with the given key, replacing any blob with the same key.
+    fn put_blob(&self, key: String, content: Vec<u8>) -> Result<(), ClientError>;
+
+    /// Get the blob with the given key, or `None` if it doesn't exist, e.g.
+    /// because the platform evicted it.
+    fn get_blob(&self, key: String) -> Result<Option<Vec<u8>>, ClientError>;
+
+    /// Remove the blob with the given key, if it exists.
+    fn remove_blob(&self, key: String) -> Result<(), ClientError>;
+}
+
+/// Bridge between a [`MediaBlobStore`] implemented by the platform and the
+/// [`media::MediaBlobStore`] of the SDK.
+pub(crate) struct MediaBlobStoreBridge(pub(crate) Arc<dyn MediaBlobStore>);
+
+impl fmt::Debug for MediaBlobStoreBridge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MediaBlobStoreBridge").finish_non_exhaustive()
+    }
+}
+
+#[cfg_attr(target_family = "wasm", async_trait(?Send))]
+#[cfg_attr(not(target_family = "wasm"), async_trait)]
+impl media::MediaBlobStore for MediaBlobStoreBridge {
+    async fn put_blob(&self, key: &str, content: Vec<u8>) -> Result<(), EventCacheStoreError> {
+        let key = key.to_owned();
+        self.run_blocking(move |store| store.put_blob(key, content)).await
+    }
+
+    async fn get_blob(&self, key: &str) -> Result<Option<Vec<u8>>, EventCacheStoreError> {
+        let key = key.to_owned();
+        self.run_blocking(move |store| store.get_blob(key)).await
+    }
+
+    async fn remove_blob(&self, key: &str) -> Result<(), EventCacheStoreError> {
+        let key = key.to_owned();
+        self.run_blocking(move |store| store.remove_blob(key)).await
+    }
+}
+
+impl MediaBlobStoreBridge {
+    /// Call the platform's store on one of tokio's blocking task threads,
+    /// since it likely does I/O and the callback interface can't be async yet.
+    async fn run_blocking<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&dyn MediaBlobStore) -> Result<T, ClientError> + Send + 'static,
+    ) -> Result<T, EventCacheStoreError> {
+        let store = self.0.clone();
+
+        get_runtime_handle()
+            .spawn_blocking(move || f(&*store))
+            .await
+            // propagate panics from the blocking task
+            .unwrap()
+            .map_err(backend_error)
+    }
+}
+
+fn backend_error(error: ClientError) -> EventCacheStoreError {
+    EventCacheStoreError::Backend(Box::new(error))
+}
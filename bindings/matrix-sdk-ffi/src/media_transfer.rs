@@ -0,0 +1,297 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{future::Future, panic, sync::Arc};
+
+use matrix_sdk_common::{
+    executor::{AbortHandle, JoinHandle},
+    SendOutsideWasm, SyncOutsideWasm,
+};
+use ruma::time::Instant;
+use tokio::sync::{watch, Mutex};
+use tracing::{debug, error};
+
+use crate::{error::ClientError, runtime::get_runtime_handle};
+
+/// The progress of a media transfer.
+#[derive(Clone, Copy, uniffi::Record)]
+pub struct MediaTransferProgress {
+    /// The number of bytes transferred so far.
+    pub bytes_transferred: u64,
+    /// The total number of bytes to transfer, if known.
+    pub total_bytes: Option<u64>,
+    /// The average throughput since the start of the transfer, in bytes per
+    /// second, if it could be estimated.
+    pub bytes_per_second: Option<u64>,
+}
+
+/// A listener to the progress of a media transfer.
+#[matrix_sdk_ffi_macros::export(callback_interface)]
+pub trait MediaTransferListener: SyncOutsideWasm + SendOutsideWasm {
+    fn on_progress(&self, progress: MediaTransferProgress);
+}
+
+/// The outcome of a successful media transfer.
+#[derive(uniffi::Enum)]
+pub enum MediaTransferOutcome {
+    /// The media has been uploaded to the given content URI.
+    Uploaded { content_uri: String },
+    /// The media has been downloaded.
+    Downloaded { data: Vec<u8> },
+}
+
+/// Estimates the throughput of a media transfer.
+#[derive(Clone, Copy)]
+pub(crate) struct ThroughputEstimator {
+    started_at: Instant,
+}
+
+impl ThroughputEstimator {
+    /// Start estimating the throughput of a transfer starting now.
+    pub(crate) fn start() -> Self {
+        Self { started_at: Instant::now() }
+    }
+
+    /// Get the progress of the transfer, given the number of bytes
+    /// transferred so far.
+    pub(crate) fn progress(
+        &self,
+        bytes_transferred: u64,
+        total_bytes: Option<u64>,
+    ) -> MediaTransferProgress {
+        let elapsed = self.started_at.elapsed().as_secs_f64();
+        let bytes_per_second = (elapsed > 0.0).then(|| (bytes_transferred as f64 / elapsed) as u64);
+
+        MediaTransferProgress { bytes_transferred, total_bytes, bytes_per_second }
+    }
+}
+
+/// A handle to a media transfer running in the background, to wait for it,
+/// pause it or cancel it.
+#[derive(uniffi::Object)]
+pub struct MediaTransferHandle {
+    join_handle: Arc<Mutex<JoinHandle<Result<MediaTransferOutcome, ClientError>>>>,
+    abort_handle: AbortHandle,
+    paused: watch::Sender<bool>,
+}
+
+impl MediaTransferHandle {
+    /// Spawn the given media transfer in the background.
+    ///
+    /// `transfer` is called to start the transfer, and again every time it's
+    /// resumed after having been paused.
+    pub(crate) fn spawn<F, Fut>(transfer: F) -> Arc<Self>
+    where
+        F: FnMut() -> Fut + SendOutsideWasm + 'static,
+        Fut: Future<Output = Result<MediaTransferOutcome, ClientError>> + SendOutsideWasm,
+    {
+        let (paused, paused_receiver) = watch::channel(false);
+
+        let join_handle = get_runtime_handle().spawn(run_pausable(paused_receiver, transfer));
+        let abort_handle = join_handle.abort_handle();
+        let join_handle = Arc::new(Mutex::new(join_handle));
+
+        Arc::new(Self { join_handle, abort_handle, paused })
+    }
+}
+
+/// Run a media transfer until it's finished, interrupting it while it's
+/// paused.
+///
+/// The media APIs of the homeserver don't allow to resume a transfer where it
+/// stopped, so the transfer is started again from scratch when it's resumed.
+async fn run_pausable<F, Fut>(
+    mut paused: watch::Receiver<bool>,
+    mut transfer: F,
+) -> Result<MediaTransferOutcome, ClientError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<MediaTransferOutcome, ClientError>>,
+{
+    loop {
+        if paused.wait_for(|paused| !paused).await.is_err() {
+            // The handle has been dropped while the transfer was paused, so it can't be
+            // resumed anymore.
+            return Err(ClientError::Generic {
+                msg: "The media transfer has been cancelled".to_owned(),
+                details: None,
+            });
+        }
+
+        tokio::select! {
+            result = transfer() => return result,
+
+            // Dropping the transfer future interrupts the ongoing request.
+            Ok(_) = paused.wait_for(|paused| *paused) => {
+                debug!("media transfer paused");
+            }
+        }
+    }
+}
+
+#[matrix_sdk_ffi_macros::export]
+impl MediaTransferHandle {
+    /// Wait until the media transfer is finished.
+    ///
+    /// If the transfer had been cancelled, returns an error immediately.
+    pub async fn join(&self) -> Result<MediaTransferOutcome, ClientError> {
+        let handle = self.join_handle.clone();
+        let mut locked_handle = handle.lock().await;
+        let join_result = (&mut *locked_handle).await;
+        match join_result {
+            Ok(res) => res,
+            Err(err) => {
+                if err.is_cancelled() {
+                    return Err(ClientError::Generic {
+                        msg: "The media transfer has been cancelled".to_owned(),
+                        details: None,
+                    });
+                }
+                error!("task panicked! resuming panic from here.");
+                #[cfg(not(target_family = "wasm"))]
+                panic::resume_unwind(err.into_panic());
+                #[cfg(target_family = "wasm")]
+                panic!("task panicked! {err}");
+            }
+        }
+    }
+
+    /// Pause the media transfer.
+    ///
+    /// The ongoing request is interrupted, and the transfer won't make progress
+    /// until [`Self::resume`] is called.
+    pub fn pause(&self) {
+        self.paused.send_replace(true);
+    }
+
+    /// Resume the media transfer, if it had been paused.
+    ///
+    /// The transfer is started again from the beginning, since the homeserver
+    /// doesn't allow to resume a partial upload or download.
+    pub fn resume(&self) {
+        self.paused.send_replace(false);
+    }
+
+    /// Whether the media transfer is paused.
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Cancel the media transfer.
+    ///
+    /// A subsequent call to [`Self::join`] will return an error immediately.
+    pub fn cancel(&self) {
+        self.abort_handle.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::watch;
+
+    use super::{run_pausable, MediaTransferHandle, MediaTransferOutcome};
+
+    #[tokio::test]
+    async fn test_paused_transfer_is_restarted_when_resumed() {
+        let (paused, paused_receiver) = watch::channel(false);
+        let (attempts, mut attempts_receiver) = watch::channel(0);
+        let finish = Arc::new(watch::Sender::new(false));
+
+        let transfer = {
+            let finish = finish.clone();
+
+            move || {
+                attempts.send_modify(|attempts| *attempts += 1);
+                let mut finished = finish.subscribe();
+
+                async move {
+                    finished.wait_for(|finished| *finished).await.unwrap();
+                    Ok(MediaTransferOutcome::Downloaded { data: vec![1, 2, 3] })
+                }
+            }
+        };
+        let task = tokio::spawn(run_pausable(paused_receiver, transfer));
+
+        attempts_receiver.wait_for(|attempts| *attempts == 1).await.unwrap();
+
+        // Pausing the transfer interrupts the ongoing attempt.
+        paused.send_replace(true);
+        finish.closed().await;
+
+        // Resuming the transfer starts it again.
+        paused.send_replace(false);
+        attempts_receiver.wait_for(|attempts| *attempts == 2).await.unwrap();
+
+        finish.send_replace(true);
+        let outcome = task.await.unwrap().unwrap();
+        assert!(matches!(outcome, MediaTransferOutcome::Downloaded { data } if data == [1, 2, 3]));
+    }
+
+    #[tokio::test]
+    async fn test_handle_completes_transfer_after_pause_and_resume() {
+        let (attempts, mut attempts_receiver) = watch::channel(0);
+        let finish = Arc::new(watch::Sender::new(false));
+
+        let handle = MediaTransferHandle::spawn({
+            let finish = finish.clone();
+
+            move || {
+                attempts.send_modify(|attempts| *attempts += 1);
+                let mut finished = finish.subscribe();
+
+                async move {
+                    finished.wait_for(|finished| *finished).await.unwrap();
+                    Ok(MediaTransferOutcome::Uploaded {
+                        content_uri: "mxc://localhost/media".to_owned(),
+                    })
+                }
+            }
+        });
+
+        attempts_receiver.wait_for(|attempts| *attempts == 1).await.unwrap();
+        assert!(!handle.is_paused());
+
+        // Pausing the transfer interrupts the ongoing attempt.
+        handle.pause();
+        assert!(handle.is_paused());
+        finish.closed().await;
+
+        // Resuming the transfer starts it again, and it can complete.
+        handle.resume();
+        assert!(!handle.is_paused());
+        attempts_receiver.wait_for(|attempts| *attempts == 2).await.unwrap();
+
+        finish.send_replace(true);
+        let outcome = handle.join().await.unwrap();
+        assert!(matches!(
+            outcome,
+            MediaTransferOutcome::Uploaded { content_uri } if content_uri == "mxc://localhost/media"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_paused_transfer_fails_when_handle_is_dropped() {
+        let (paused, paused_receiver) = watch::channel(true);
+
+        let task = tokio::spawn(run_pausable(paused_receiver, || async {
+            Ok(MediaTransferOutcome::Downloaded { data: Vec::new() })
+        }));
+
+        // The transfer can't be resumed anymore.
+        drop(paused);
+        assert!(task.await.unwrap().is_err());
+    }
+}
@@ -163,6 +163,36 @@ impl NotificationClient {
         }
         Ok(result)
     }
+
+    /// Send a quick reply to the event of a notification, without starting
+    /// a sync.
+    ///
+    /// The reply is persisted in the send queue of the room, so if it can't
+    /// be sent right away, it will be sent later by the app.
+    pub async fn send_quick_reply(
+        &self,
+        room_id: String,
+        reply_to_event_id: String,
+        body: String,
+    ) -> Result<(), ClientError> {
+        let room_id = RoomId::parse(room_id)?;
+        let event_id = EventId::parse(reply_to_event_id)?;
+
+        self.inner.send_quick_reply(&room_id, &event_id, body).await?;
+
+        Ok(())
+    }
+
+    /// Mark the room of a notification as read, up to the given event,
+    /// without starting a sync.
+    pub async fn mark_as_read(&self, room_id: String, event_id: String) -> Result<(), ClientError> {
+        let room_id = RoomId::parse(room_id)?;
+        let event_id = EventId::parse(event_id)?;
+
+        self.inner.mark_as_read(&room_id, &event_id).await?;
+
+        Ok(())
+    }
 }
 
 /// A request for notification items grouped by their room.
@@ -724,6 +724,51 @@ impl NotificationSettings {
         Ok(())
     }
 
+    /// Get whether the `.m.rule.master` push rule is enabled.
+    ///
+    /// When it is enabled, no notification is sent for any event, for all the
+    /// devices of the user.
+    pub async fn is_master_rule_enabled(&self) -> Result<bool, NotificationSettingsError> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        let enabled = notification_settings
+            .is_push_rule_enabled(SdkRuleKind::Override, PredefinedOverrideRuleId::Master)
+            .await?;
+        Ok(enabled)
+    }
+
+    /// Set whether the `.m.rule.master` push rule is enabled, i.e. whether all
+    /// notifications are muted.
+    pub async fn set_master_rule_enabled(
+        &self,
+        enabled: bool,
+    ) -> Result<(), NotificationSettingsError> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        notification_settings
+            .set_push_rule_enabled(SdkRuleKind::Override, PredefinedOverrideRuleId::Master, enabled)
+            .await?;
+        Ok(())
+    }
+
+    /// Get the keywords which have enabled push rules.
+    pub async fn get_enabled_keywords(&self) -> Vec<String> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        notification_settings.enabled_keywords().await.into_iter().collect()
+    }
+
+    /// Add or enable a push rule for the given keyword.
+    pub async fn add_keyword(&self, keyword: String) -> Result<(), NotificationSettingsError> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        notification_settings.add_keyword(keyword).await?;
+        Ok(())
+    }
+
+    /// Remove the push rules for the given keyword.
+    pub async fn remove_keyword(&self, keyword: String) -> Result<(), NotificationSettingsError> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        notification_settings.remove_keyword(&keyword).await?;
+        Ok(())
+    }
+
     /// Sets a custom push rule with the given actions and conditions.
     pub async fn set_custom_push_rule(
         &self,
@@ -1,12 +1,25 @@
 use std::sync::Arc;
 
+use futures_util::StreamExt;
 use matrix_sdk::{
-    authentication::oauth::qrcode::{self, DeviceCodeErrorResponseType, LoginFailureReason},
+    authentication::oauth::{
+        qrcode::{self, DeviceCodeErrorResponseType, LoginFailureReason},
+        ClientRegistrationData,
+    },
     crypto::types::qr_login::{LoginQrCodeDecodeError, QrCodeModeData},
 };
 use matrix_sdk_common::{SendOutsideWasm, SyncOutsideWasm};
+use tokio::sync::watch;
 use tracing::error;
 
+use crate::{
+    authentication::OidcConfiguration,
+    client::Client,
+    client_builder::{ClientBuildError, ClientBuilder},
+    runtime::get_runtime_handle,
+    task_handle::TaskHandle,
+};
+
 /// Data for the QR code login mechanism.
 ///
 /// The [`QrCodeData`] can be serialized and encoded as a QR code or it can be
@@ -136,6 +149,9 @@ pub enum QrLoginProgress {
     /// We are waiting for the login and for the OAuth 2.0 authorization server
     /// to give us an access token.
     WaitingForToken { user_code: String },
+    /// We are logged in, and waiting for the other device to send us the
+    /// secrets needed to set up end-to-end encryption.
+    SyncingSecrets,
     /// The login has successfully finished.
     Done,
 }
@@ -160,7 +176,238 @@ impl From<qrcode::LoginProgress> for QrLoginProgress {
                 }
             }
             LoginProgress::WaitingForToken { user_code } => Self::WaitingForToken { user_code },
+            LoginProgress::SyncingSecrets => Self::SyncingSecrets,
             LoginProgress::Done => Self::Done,
         }
     }
 }
+
+/// A handle on the login of a new device by scanning the QR code shown by an
+/// existing device of the user, as described in [MSC4108].
+///
+/// The progress of the login can be observed with
+/// [`QrCodeLoginHandle::subscribe_to_progress`], which is necessary to show
+/// the check code to the user, so they can enter it on the existing device.
+/// [`QrCodeLoginHandle::login`] builds the [`Client`] and logs it in.
+///
+/// [MSC4108]: https://github.com/matrix-org/matrix-spec-proposals/pull/4108
+#[derive(uniffi::Object)]
+pub struct QrCodeLoginHandle {
+    client_builder: Arc<ClientBuilder>,
+    qr_code_data: qrcode::QrCodeData,
+    server_name: String,
+    registration_data: ClientRegistrationData,
+    progress: Arc<watch::Sender<QrLoginProgress>>,
+}
+
+impl QrCodeLoginHandle {
+    pub(crate) fn new(
+        client_builder: Arc<ClientBuilder>,
+        qr_code_data: &QrCodeData,
+        oidc_configuration: &OidcConfiguration,
+    ) -> Result<Self, HumanQrLoginError> {
+        let QrCodeModeData::Reciprocate { server_name } = &qr_code_data.inner.mode_data else {
+            return Err(HumanQrLoginError::OtherDeviceNotSignedIn);
+        };
+
+        let registration_data = oidc_configuration
+            .registration_data()
+            .map_err(|_| HumanQrLoginError::OidcMetadataInvalid)?;
+
+        Ok(Self {
+            client_builder,
+            qr_code_data: qr_code_data.inner.clone(),
+            server_name: server_name.to_owned(),
+            registration_data,
+            progress: Arc::new(watch::Sender::new(QrLoginProgress::default())),
+        })
+    }
+}
+
+#[matrix_sdk_ffi_macros::export]
+impl QrCodeLoginHandle {
+    /// The server name of the existing device, read from the scanned QR code.
+    pub fn server_name(&self) -> String {
+        self.server_name.clone()
+    }
+
+    /// The current progress of the login.
+    pub fn progress(&self) -> QrLoginProgress {
+        self.progress.borrow().clone()
+    }
+
+    /// Subscribe to the progress of the login.
+    ///
+    /// The listener is called with the current progress first, then with
+    /// every update, until the login is done.
+    pub fn subscribe_to_progress(
+        &self,
+        listener: Box<dyn QrLoginProgressListener>,
+    ) -> Arc<TaskHandle> {
+        let mut progress = self.progress.subscribe();
+
+        Arc::new(TaskHandle::new(get_runtime_handle().spawn(async move {
+            loop {
+                let state = progress.borrow_and_update().clone();
+                let is_done = matches!(state, QrLoginProgress::Done);
+
+                listener.on_update(state);
+
+                if is_done || progress.changed().await.is_err() {
+                    break;
+                }
+            }
+        })))
+    }
+
+    /// Build the client for the server of the existing device, and log it in.
+    ///
+    /// This requires the server to support OAuth 2.0 and sliding sync. The
+    /// returned client is fully set up for end-to-end encryption, with the
+    /// secrets received from the existing device.
+    pub async fn login(&self) -> Result<Arc<Client>, HumanQrLoginError> {
+        let builder =
+            self.client_builder.clone().server_name_or_homeserver_url(self.server_name.clone());
+
+        let client = builder.build().await.map_err(|e| match e {
+            ClientBuildError::SlidingSync(_) => HumanQrLoginError::SlidingSyncNotAvailable,
+            _ => {
+                error!("Couldn't build the client {e:?}");
+                HumanQrLoginError::Unknown
+            }
+        })?;
+
+        let oauth = client.inner.oauth();
+        let login = oauth.login_with_qr_code(&self.qr_code_data, Some(&self.registration_data));
+
+        let mut sdk_progress = login.subscribe_to_progress();
+        let progress = self.progress.clone();
+
+        // We create this task, which will get cancelled once it's dropped, just in case
+        // the progress stream doesn't end.
+        let _progress_task = TaskHandle::new(get_runtime_handle().spawn(async move {
+            while let Some(state) = sdk_progress.next().await {
+                progress.send_replace(state.into());
+            }
+        }));
+
+        login.await?;
+
+        // The progress task may not have forwarded the last update yet.
+        self.progress.send_replace(QrLoginProgress::Done);
+
+        Ok(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use matrix_sdk::crypto::types::qr_login::LoginQrCodeDecodeError;
+
+    use super::{HumanQrLoginError, QrCodeData, QrCodeDecodeError, QrCodeLoginHandle};
+    use crate::{authentication::OidcConfiguration, client_builder::ClientBuilder};
+
+    // Test vector for the QR code data shown by a new device, copied from the MSC.
+    const QR_CODE_DATA_LOGIN: &[u8] = &[
+        0x4D, 0x41, 0x54, 0x52, 0x49, 0x58, 0x02, 0x03, 0xd8, 0x86, 0x68, 0x6a, 0xb2, 0x19, 0x7b,
+        0x78, 0x0e, 0x30, 0x0a, 0x9d, 0x4a, 0x21, 0x47, 0x48, 0x07, 0x00, 0xd7, 0x92, 0x9f, 0x39,
+        0xab, 0x31, 0xb9, 0xe5, 0x14, 0x37, 0x02, 0x48, 0xed, 0x6b, 0x00, 0x47, 0x68, 0x74, 0x74,
+        0x70, 0x73, 0x3a, 0x2f, 0x2f, 0x72, 0x65, 0x6e, 0x64, 0x65, 0x7a, 0x76, 0x6f, 0x75, 0x73,
+        0x2e, 0x6c, 0x61, 0x62, 0x2e, 0x65, 0x6c, 0x65, 0x6d, 0x65, 0x6e, 0x74, 0x2e, 0x64, 0x65,
+        0x76, 0x2f, 0x65, 0x38, 0x64, 0x61, 0x36, 0x33, 0x35, 0x35, 0x2d, 0x35, 0x35, 0x30, 0x62,
+        0x2d, 0x34, 0x61, 0x33, 0x32, 0x2d, 0x61, 0x31, 0x39, 0x33, 0x2d, 0x31, 0x36, 0x31, 0x39,
+        0x64, 0x39, 0x38, 0x33, 0x30, 0x36, 0x36, 0x38,
+    ];
+
+    // The same test vector, shown by an existing device of the matrix.org
+    // homeserver.
+    const QR_CODE_DATA_RECIPROCATE: &[u8] = &[
+        0x4D, 0x41, 0x54, 0x52, 0x49, 0x58, 0x02, 0x04, 0xd8, 0x86, 0x68, 0x6a, 0xb2, 0x19, 0x7b,
+        0x78, 0x0e, 0x30, 0x0a, 0x9d, 0x4a, 0x21, 0x47, 0x48, 0x07, 0x00, 0xd7, 0x92, 0x9f, 0x39,
+        0xab, 0x31, 0xb9, 0xe5, 0x14, 0x37, 0x02, 0x48, 0xed, 0x6b, 0x00, 0x47, 0x68, 0x74, 0x74,
+        0x70, 0x73, 0x3a, 0x2f, 0x2f, 0x72, 0x65, 0x6e, 0x64, 0x65, 0x7a, 0x76, 0x6f, 0x75, 0x73,
+        0x2e, 0x6c, 0x61, 0x62, 0x2e, 0x65, 0x6c, 0x65, 0x6d, 0x65, 0x6e, 0x74, 0x2e, 0x64, 0x65,
+        0x76, 0x2f, 0x65, 0x38, 0x64, 0x61, 0x36, 0x33, 0x35, 0x35, 0x2d, 0x35, 0x35, 0x30, 0x62,
+        0x2d, 0x34, 0x61, 0x33, 0x32, 0x2d, 0x61, 0x31, 0x39, 0x33, 0x2d, 0x31, 0x36, 0x31, 0x39,
+        0x64, 0x39, 0x38, 0x33, 0x30, 0x36, 0x36, 0x38, 0x00, 0x0A, 0x6d, 0x61, 0x74, 0x72, 0x69,
+        0x78, 0x2e, 0x6f, 0x72, 0x67,
+    ];
+
+    fn oidc_configuration() -> OidcConfiguration {
+        OidcConfiguration {
+            client_name: Some("Test client".to_owned()),
+            redirect_uri: "io.example.app:/callback".to_owned(),
+            client_uri: "https://app.example.io".to_owned(),
+            logo_uri: None,
+            tos_uri: None,
+            policy_uri: None,
+            static_registrations: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_decode_qr_code_of_existing_device() {
+        let data = QrCodeData::from_bytes(QR_CODE_DATA_RECIPROCATE.to_vec()).unwrap();
+        assert_eq!(data.server_name().as_deref(), Some("matrix.org"));
+
+        // Encoding the data again gives back the scanned bytes.
+        assert_eq!(data.inner.to_bytes(), QR_CODE_DATA_RECIPROCATE);
+    }
+
+    #[test]
+    fn test_decode_qr_code_of_new_device() {
+        let data = QrCodeData::from_bytes(QR_CODE_DATA_LOGIN.to_vec()).unwrap();
+        assert_eq!(data.server_name(), None);
+
+        assert_eq!(data.inner.to_bytes(), QR_CODE_DATA_LOGIN);
+    }
+
+    #[test]
+    fn test_decode_invalid_qr_code() {
+        // The prefix is wrong.
+        let mut bytes = QR_CODE_DATA_RECIPROCATE.to_vec();
+        bytes[..6].copy_from_slice(b"MATRIY");
+        assert!(matches!(
+            QrCodeData::from_bytes(bytes),
+            Err(QrCodeDecodeError::Crypto { error: LoginQrCodeDecodeError::InvalidPrefix { .. } })
+        ));
+
+        // The data is truncated.
+        let bytes = QR_CODE_DATA_RECIPROCATE[..20].to_vec();
+        assert!(QrCodeData::from_bytes(bytes).is_err());
+
+        // There is no data at all.
+        assert!(QrCodeData::from_bytes(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_login_handle_requires_qr_code_of_existing_device() {
+        let oidc_configuration = oidc_configuration();
+
+        let data = QrCodeData::from_bytes(QR_CODE_DATA_LOGIN.to_vec()).unwrap();
+        assert!(matches!(
+            QrCodeLoginHandle::new(ClientBuilder::new(), &data, &oidc_configuration),
+            Err(HumanQrLoginError::OtherDeviceNotSignedIn)
+        ));
+
+        let data = QrCodeData::from_bytes(QR_CODE_DATA_RECIPROCATE.to_vec()).unwrap();
+        let handle =
+            QrCodeLoginHandle::new(ClientBuilder::new(), &data, &oidc_configuration).unwrap();
+        assert_eq!(handle.server_name(), "matrix.org");
+        assert!(matches!(handle.progress(), super::QrLoginProgress::Starting));
+    }
+
+    #[test]
+    fn test_login_handle_rejects_invalid_oidc_configuration() {
+        let oidc_configuration =
+            OidcConfiguration { redirect_uri: "not a URL".to_owned(), ..oidc_configuration() };
+
+        let data = QrCodeData::from_bytes(QR_CODE_DATA_RECIPROCATE.to_vec()).unwrap();
+        assert!(matches!(
+            QrCodeLoginHandle::new(ClientBuilder::new(), &data, &oidc_configuration),
+            Err(HumanQrLoginError::OidcMetadataInvalid)
+        ));
+    }
+}
@@ -4,10 +4,19 @@ use anyhow::{Context, Result};
 use futures_util::{pin_mut, StreamExt};
 use matrix_sdk::{
     crypto::LocalTrust,
+    deserialized_responses::{RawAnySyncOrStrippedState, TimelineEvent as SdkTimelineEvent},
+    event_cache::{
+        DebugChunk as SdkDebugChunk, DebugChunkContent as SdkDebugChunkContent,
+        DebugEvent as SdkDebugEvent, DebugEventOrigin as SdkDebugEventOrigin,
+    },
     room::{
-        edit::EditedContent, power_levels::RoomPowerLevelChanges, Room as SdkRoom, RoomMemberRole,
-        TryFromReportedContentScoreError,
+        edit::EditedContent, power_levels::RoomPowerLevelChanges, EventWithContextResponse,
+        LeaveOptions, LeaveOutcome, Room as SdkRoom, RoomMemberRole,
+        TryFromReportedContentScoreError, UntrustedDevice as SdkUntrustedDevice,
+        UntrustedDeviceReason as SdkUntrustedDeviceReason,
+        UntrustedDevicesReport as SdkUntrustedDevicesReport,
     },
+    send_queue::RoomSendQueueUpdate,
     ComposerDraft as SdkComposerDraft, ComposerDraftType as SdkComposerDraftType, EncryptionState,
     PredecessorRoom as SdkPredecessorRoom, RoomHero as SdkRoomHero, RoomMemberships, RoomState,
     SuccessorRoom as SdkSuccessorRoom,
@@ -22,33 +31,42 @@ use ruma::{
     assign,
     events::{
         call::notify,
+        receipt::ReceiptThread,
         room::{
             avatar::ImageInfo as RumaAvatarImageInfo,
             history_visibility::HistoryVisibility as RumaHistoryVisibility,
             join_rules::JoinRule as RumaJoinRule, message::RoomMessageEventContentWithoutRelation,
+            pinned_events::RoomPinnedEventsEventContent, tombstone::RoomTombstoneEventContent,
             MediaSource,
         },
-        AnyMessageLikeEventContent, AnySyncTimelineEvent,
+        AnyMessageLikeEventContent, AnySyncStateEvent, AnySyncTimelineEvent,
+        StateEventType as RumaStateEventType,
     },
     EventId, Int, OwnedDeviceId, OwnedRoomOrAliasId, OwnedServerName, OwnedUserId, RoomAliasId,
-    ServerName, UserId,
+    ServerName, UInt, UserId,
 };
+use tokio::sync::{broadcast::error::RecvError, mpsc};
 use tracing::{error, warn};
 
-use self::{power_levels::RoomPowerLevels, room_info::RoomInfo};
+use self::{
+    power_levels::RoomPowerLevels,
+    room_info::RoomInfo,
+    server_acl::{ServerAcl, ServerAclError},
+};
 use crate::{
     chunk_iterator::ChunkIterator,
     client::{JoinRule, RoomVisibility},
     error::{ClientError, MediaInfoError, NotYetImplemented, RoomError},
+    event::TimelineEvent,
     identity_status_change::IdentityStatusChange,
-    live_location_share::{LastLocation, LiveLocationShare},
+    live_location_share::LiveLocationShare,
     room_member::{RoomMember, RoomMemberWithSenderInfo},
     room_preview::RoomPreview,
     ruma::{ImageInfo, LocationContent, Mentions, NotifyType},
     runtime::get_runtime_handle,
     timeline::{
         configuration::{TimelineConfiguration, TimelineFilter},
-        EventTimelineItem, ReceiptType, SendHandle, Timeline,
+        EventTimelineItem, ProfileDetails, ReceiptType, SendHandle, Timeline,
     },
     utils::{u64_to_uint, AsyncRuntimeDropped},
     TaskHandle,
@@ -56,6 +74,7 @@ use crate::{
 
 mod power_levels;
 pub mod room_info;
+mod server_acl;
 
 #[derive(Debug, Clone, uniffi::Enum)]
 pub enum Membership {
@@ -88,6 +107,139 @@ impl Room {
     pub(crate) fn new(inner: SdkRoom, utd_hook_manager: Option<Arc<UtdHookManager>>) -> Self {
         Room { inner, utd_hook_manager }
     }
+
+    /// Look for an event and the `context_size` events around it in the event
+    /// cache.
+    ///
+    /// Returns `None` if the event cache doesn't contain the event, or not
+    /// enough events around it.
+    async fn cached_event_with_context(
+        &self,
+        event_id: &EventId,
+        context_size: usize,
+    ) -> Result<Option<EventWithContextResponse>, ClientError> {
+        let (room_event_cache, _drop_handles) = self.inner.event_cache().await?;
+        let mut events = room_event_cache.events().await;
+
+        let Some(position) =
+            events.iter().position(|event| event.event_id().as_deref() == Some(event_id))
+        else {
+            return Ok(None);
+        };
+
+        if position < context_size || events.len() - position - 1 < context_size {
+            return Ok(None);
+        }
+
+        let events_after = events.split_off(position + 1).into_iter().take(context_size).collect();
+        let event = events.pop().expect("the target event must be at the end of the events");
+
+        // Like in a `/context` response, the events before the target event are in
+        // reverse chronological order.
+        let mut events_before = events.split_off(position - context_size);
+        events_before.reverse();
+
+        Ok(Some(EventWithContextResponse {
+            event: Some(event),
+            events_before,
+            events_after,
+            ..Default::default()
+        }))
+    }
+
+    /// Convert the events around the target event of
+    /// [`Room::event_with_context`] for the FFI, along with the profiles of
+    /// their senders.
+    ///
+    /// The events that can't be deserialized are skipped.
+    async fn context_events_with_profiles(
+        &self,
+        events: impl IntoIterator<Item = SdkTimelineEvent>,
+        profiles: &mut HashMap<OwnedUserId, ProfileDetails>,
+    ) -> Vec<EventWithProfile> {
+        let mut events_with_profiles = Vec::new();
+
+        for event in events {
+            let event = match event.into_raw().deserialize() {
+                Ok(event) => TimelineEvent(Box::new(event)),
+                Err(error) => {
+                    warn!("Skipping a context event that couldn't be deserialized: {error}");
+                    continue;
+                }
+            };
+
+            events_with_profiles.push(self.event_with_profile(event, profiles).await);
+        }
+
+        events_with_profiles
+    }
+
+    /// Attach the profile of its sender to an event.
+    ///
+    /// The profiles are cached in `profiles`, so they're only loaded once per
+    /// sender.
+    async fn event_with_profile(
+        &self,
+        event: TimelineEvent,
+        profiles: &mut HashMap<OwnedUserId, ProfileDetails>,
+    ) -> EventWithProfile {
+        let sender = event.0.sender().to_owned();
+
+        let sender_profile = if let Some(profile) = profiles.get(&sender) {
+            profile.clone()
+        } else {
+            let profile = match self.inner.get_member_no_sync(&sender).await {
+                Ok(Some(member)) => ProfileDetails::Ready {
+                    display_name: member.display_name().map(ToOwned::to_owned),
+                    display_name_ambiguous: member.name_ambiguous(),
+                    avatar_url: member.avatar_url().map(ToString::to_string),
+                },
+                Ok(None) => ProfileDetails::Unavailable,
+                Err(error) => ProfileDetails::Error { message: error.to_string() },
+            };
+            profiles.insert(sender, profile.clone());
+            profile
+        };
+
+        EventWithProfile { event: Arc::new(event), sender_profile }
+    }
+
+    /// If this room is tombstoned, return its successor room along with the
+    /// servers which can be used to join it.
+    ///
+    /// The server of the sender of the [`m.room.tombstone`] event comes first,
+    /// since it's necessarily in the successor room, followed by the servers
+    /// routing to this room.
+    ///
+    /// [`m.room.tombstone`]: https://spec.matrix.org/v1.14/client-server-api/#mroomtombstone
+    pub(crate) async fn successor_room_and_via(
+        &self,
+    ) -> Result<Option<(SdkSuccessorRoom, Vec<OwnedServerName>)>, ClientError> {
+        let Some(successor_room) = self.inner.successor_room() else {
+            return Ok(None);
+        };
+
+        let tombstone_sender = self
+            .inner
+            .get_state_event_static::<RoomTombstoneEventContent>()
+            .await?
+            .and_then(|raw| raw.deserialize().ok())
+            .map(|event| event.sender().to_owned());
+
+        let mut via = Vec::new();
+
+        for server_name in tombstone_sender
+            .iter()
+            .map(|sender| sender.server_name().to_owned())
+            .chain(self.inner.route().await?)
+        {
+            if !via.contains(&server_name) {
+                via.push(server_name);
+            }
+        }
+
+        Ok(Some((successor_room, via)))
+    }
 }
 
 #[matrix_sdk_ffi_macros::export]
@@ -138,6 +290,25 @@ impl Room {
         self.inner.successor_room().map(Into::into)
     }
 
+    /// Whether this room has been tombstoned, i.e. replaced by a successor
+    /// room.
+    pub fn is_tombstoned(&self) -> bool {
+        self.inner.is_tombstoned()
+    }
+
+    /// Like [`Room::successor_room`], but also returns the servers which can be
+    /// used to join the successor room, e.g. with
+    /// [`Client::join_room_by_id_or_alias`][crate::client::Client::join_room_by_id_or_alias].
+    pub async fn successor_room_with_via(
+        &self,
+    ) -> Result<Option<SuccessorRoomWithVia>, ClientError> {
+        Ok(self.successor_room_and_via().await?.map(|(successor_room, via)| SuccessorRoomWithVia {
+            room_id: successor_room.room_id.to_string(),
+            reason: successor_room.reason,
+            via: via.into_iter().map(|server_name| server_name.to_string()).collect(),
+        }))
+    }
+
     /// If this room is the successor of a tombstoned room, return the
     /// “reference” to the predecessor room.
     ///
@@ -227,7 +398,9 @@ impl Room {
 
         builder = builder
             .with_focus(configuration.focus.try_into()?)
-            .with_date_divider_mode(configuration.date_divider_mode.into());
+            .with_date_divider_mode(configuration.date_divider_mode.into())
+            .with_clock_skew_correction(configuration.correct_clock_skew)
+            .with_profile_resolution(configuration.profile_resolution.into());
 
         if configuration.track_read_receipts {
             builder = builder.track_read_marker_and_receipts();
@@ -378,6 +551,48 @@ impl Room {
         })))
     }
 
+    /// Subscribe to changes of the given types of state in this room, without
+    /// having to create a timeline.
+    ///
+    /// Every time a state event of one of the requested types is received, the
+    /// listener is called with the up-to-date value of that state.
+    pub fn observe_state(
+        self: Arc<Self>,
+        event_types: Vec<RoomStateUpdateType>,
+        listener: Box<dyn RoomStateListener>,
+    ) -> Arc<TaskHandle> {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+
+        let handle = self.inner.add_event_handler(move |event: AnySyncStateEvent| {
+            let sender = sender.clone();
+            async move {
+                // Ignore the result. It can only fail if the subscription has been cancelled.
+                let _ = sender.send(event.event_type());
+            }
+        });
+        let drop_guard = self.inner.client().event_handler_drop_guard(handle);
+
+        Arc::new(TaskHandle::new(get_runtime_handle().spawn(async move {
+            let _drop_guard = drop_guard;
+
+            while let Some(event_type) = receiver.recv().await {
+                let Some(update_type) = RoomStateUpdateType::from_event_type(&event_type) else {
+                    continue;
+                };
+
+                if !event_types.contains(&update_type) {
+                    continue;
+                }
+
+                // The event handlers are called once the state has been saved, so we can
+                // read the new value from the room.
+                if let Some(update) = RoomStateUpdate::load(&self.inner, update_type).await {
+                    listener.call(update);
+                }
+            }
+        })))
+    }
+
     pub async fn set_is_favourite(
         &self,
         is_favourite: bool,
@@ -415,6 +630,88 @@ impl Room {
         Ok(())
     }
 
+    /// Get the state event with the given type and state key, as it is known
+    /// locally.
+    ///
+    /// Returns the whole event encoded as a JSON string, or `None` if the room
+    /// has no such state event.
+    ///
+    /// # Arguments
+    ///
+    /// * `event_type` - The type of the state event, e.g. `m.room.topic`.
+    ///
+    /// * `state_key` - The state key of the state event, usually empty.
+    pub async fn get_state_event(
+        &self,
+        event_type: String,
+        state_key: String,
+    ) -> Result<Option<String>, ClientError> {
+        let event = self.inner.get_state_event(event_type.into(), &state_key).await?;
+
+        Ok(event.map(|event| match event {
+            RawAnySyncOrStrippedState::Sync(raw) => raw.json().get().to_owned(),
+            RawAnySyncOrStrippedState::Stripped(raw) => raw.json().get().to_owned(),
+        }))
+    }
+
+    /// Send a state event to the room.
+    ///
+    /// Returns the ID of the state event.
+    ///
+    /// # Arguments
+    ///
+    /// * `event_type` - The type of the state event to send.
+    ///
+    /// * `state_key` - The state key of the state event to send, usually empty.
+    ///
+    /// * `content` - The content of the state event to send encoded as JSON
+    ///   string.
+    pub async fn send_state_event(
+        &self,
+        event_type: String,
+        state_key: String,
+        content: String,
+    ) -> Result<String, ClientError> {
+        let content_json: serde_json::Value =
+            serde_json::from_str(&content).map_err(|e| ClientError::Generic {
+                msg: format!("Failed to parse JSON: {e}"),
+                details: Some(format!("{e:?}")),
+            })?;
+
+        let response =
+            self.inner.send_state_event_raw(&event_type, &state_key, content_json).await?;
+
+        Ok(response.event_id.to_string())
+    }
+
+    /// The join rule of the room, from the `m.room.join_rules` state event.
+    ///
+    /// Can be `None` if the join rules state event is not available for this
+    /// room.
+    pub fn join_rule(&self) -> Result<Option<JoinRule>, ClientError> {
+        self.inner
+            .join_rule()
+            .map(TryInto::try_into)
+            .transpose()
+            .map_err(|msg| ClientError::Generic { msg, details: None })
+    }
+
+    /// The IDs of the pinned events of the room, from the
+    /// `m.room.pinned_events` state event.
+    pub fn pinned_event_ids(&self) -> Vec<String> {
+        self.inner.pinned_event_ids().unwrap_or_default().iter().map(ToString::to_string).collect()
+    }
+
+    /// Replace the pinned events of the room with the given ones, in order.
+    pub async fn set_pinned_event_ids(&self, event_ids: Vec<String>) -> Result<(), ClientError> {
+        let event_ids =
+            event_ids.iter().map(|event_id| EventId::parse(event_id)).collect::<Result<_, _>>()?;
+
+        self.inner.send_state_event(RoomPinnedEventsEventContent::new(event_ids)).await?;
+
+        Ok(())
+    }
+
     /// Redacts an event from the room.
     ///
     /// # Arguments
@@ -503,12 +800,27 @@ impl Room {
 
     /// Leave this room.
     ///
-    /// Only invited and joined rooms can be left.
+    /// If the room was knocked on, this withdraws the request to join it.
+    ///
+    /// Only invited, knocked and joined rooms can be left.
     pub async fn leave(&self) -> Result<(), ClientError> {
         self.inner.leave().await?;
         Ok(())
     }
 
+    /// Leave this room, with the given options.
+    ///
+    /// Like [`Room::leave`], this rejects the invite of an invited room, which
+    /// is then forgotten, and withdraws the request to join a knocked room.
+    ///
+    /// Returns what leaving the room meant, depending on its previous state.
+    pub async fn leave_with_options(
+        &self,
+        options: LeaveOptions,
+    ) -> Result<LeaveOutcome, ClientError> {
+        Ok(self.inner.leave_with_options(options).await?)
+    }
+
     /// Join this room.
     ///
     /// Only invited and left rooms can be joined via this method.
@@ -666,6 +978,36 @@ impl Room {
         Ok(())
     }
 
+    /// Send a receipt of the given type on the given event.
+    ///
+    /// `thread_id` is the thread where the receipt applies: either the ID of
+    /// the root event of a thread, or `main` for the main timeline. If it's
+    /// `None`, the receipt is unthreaded, and the unread flag of the room is
+    /// unset.
+    ///
+    /// A fully-read marker can't be scoped to a thread.
+    pub async fn send_read_receipt(
+        &self,
+        event_id: String,
+        receipt_type: ReceiptType,
+        thread_id: Option<String>,
+    ) -> Result<(), ClientError> {
+        let event_id = EventId::parse(event_id)?;
+
+        let thread = match thread_id.as_deref() {
+            None => ReceiptThread::Unthreaded,
+            Some("main") => ReceiptThread::Main,
+            Some(thread_id) => ReceiptThread::Thread(EventId::parse(thread_id)?),
+        };
+
+        if matches!(receipt_type, ReceiptType::FullyRead) && thread != ReceiptThread::Unthreaded {
+            return Err(ClientError::from_str("a fully-read marker can't be threaded", None));
+        }
+
+        self.inner.send_single_receipt(receipt_type.into(), thread, event_id).await?;
+        Ok(())
+    }
+
     pub async fn get_power_levels(&self) -> Result<Arc<RoomPowerLevels>, ClientError> {
         let power_levels = self.inner.power_levels().await.map_err(matrix_sdk::Error::from)?;
         Ok(Arc::new(RoomPowerLevels::new(power_levels, self.inner.own_user_id().to_owned())))
@@ -692,10 +1034,44 @@ impl Room {
             })
             .collect::<Result<Vec<_>>>()?;
 
-        self.inner.update_power_levels(updates).await.map_err(ClientError::from_err)?;
+        self.inner.update_power_levels(updates).await?;
+        Ok(())
+    }
+
+    /// Apply changes to the power levels of some users and to the levels
+    /// required for actions in the room, in a single update.
+    ///
+    /// `base` are the power levels the changes have been made from. If the
+    /// power levels of the room have been modified since, e.g. by another
+    /// admin, nothing is sent and an error is returned, so the changes can be
+    /// reviewed against the new power levels.
+    pub async fn update_power_levels(
+        &self,
+        base: Arc<RoomPowerLevels>,
+        users: HashMap<String, i64>,
+        settings: RoomPowerLevelChanges,
+    ) -> Result<(), ClientError> {
+        let users = users
+            .iter()
+            .map(|(user_id, power_level)| {
+                let user_id: &UserId = user_id.as_str().try_into()?;
+                let power_level = Int::new(*power_level).context("Invalid power level")?;
+                Ok((user_id, power_level))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.inner.update_power_levels_from(base.inner(), users, settings).await?;
         Ok(())
     }
 
+    /// Get an editor for the server access control list of the room.
+    ///
+    /// The editor refuses to save changes that would deny the own server of
+    /// the user, which would lock them out of the room.
+    pub async fn server_acl(&self) -> Result<Arc<ServerAcl>, ServerAclError> {
+        Ok(Arc::new(ServerAcl::new(self.inner.server_acl().await?)))
+    }
+
     pub async fn suggested_role_for_user(
         &self,
         user_id: String,
@@ -720,6 +1096,46 @@ impl Room {
         Ok(self.inner.matrix_to_event_permalink(event_id).await?.to_string())
     }
 
+    /// Get an event along with up to `context_size` events before and after
+    /// it, decrypted if possible, and with the profiles of their senders.
+    ///
+    /// The events are taken from the event cache if it contains the event and
+    /// enough events around it, otherwise they're fetched from the server.
+    ///
+    /// This is meant to display a preview of a permalink, without having to
+    /// build a whole timeline.
+    pub async fn event_with_context(
+        &self,
+        event_id: String,
+        context_size: u16,
+    ) -> Result<EventWithContext, ClientError> {
+        let event_id = EventId::parse(event_id)?;
+
+        let response = if let Some(response) =
+            self.cached_event_with_context(&event_id, context_size.into()).await?
+        {
+            response
+        } else {
+            self.inner.event_with_context(&event_id, true, UInt::from(context_size), None).await?
+        };
+
+        let event = response.event.context("The event couldn't be found")?;
+
+        let event = TimelineEvent(Box::new(event.into_raw().deserialize()?));
+
+        let mut profiles = HashMap::new();
+        let event = self.event_with_profile(event, &mut profiles).await;
+
+        // The events before the target event are in reverse chronological order.
+        let events_before = self
+            .context_events_with_profiles(response.events_before.into_iter().rev(), &mut profiles)
+            .await;
+        let events_after =
+            self.context_events_with_profiles(response.events_after, &mut profiles).await;
+
+        Ok(EventWithContext { event, events_before, events_after })
+    }
+
     /// This will only send a call notification event if appropriate.
     ///
     /// This function is supposed to be called whenever the user creates a room
@@ -778,6 +1194,70 @@ impl Room {
         self.inner.send_queue().set_enabled(enable);
     }
 
+    /// Subscribe to the enablement status of the send queue for that
+    /// particular room.
+    ///
+    /// The given listener will be immediately called with the initial value of
+    /// the enablement status, then every time the queue is paused or resumed,
+    /// including when it disables itself after an error.
+    pub fn subscribe_to_send_queue_status(
+        &self,
+        listener: Box<dyn SendQueueStatusListener>,
+    ) -> Arc<TaskHandle> {
+        let mut subscriber = self.inner.send_queue().subscribe_enabled();
+
+        Arc::new(TaskHandle::new(get_runtime_handle().spawn(async move {
+            listener.on_update(subscriber.get());
+
+            while let Some(is_enabled) = subscriber.next().await {
+                listener.on_update(is_enabled);
+            }
+        })))
+    }
+
+    /// Subscribe to the events sent from the send queue of this room.
+    ///
+    /// The given listener is called with the transaction ID of the local echo
+    /// of every event that has been sent, and the event ID the server gave
+    /// it. The transaction ID of an event is given by
+    /// `SendHandle::transaction_id` when it's queued.
+    pub async fn subscribe_to_sent_events(
+        &self,
+        listener: Box<dyn SentEventListener>,
+    ) -> Result<Arc<TaskHandle>, ClientError> {
+        let (_, mut receiver) = self.inner.send_queue().subscribe().await?;
+
+        Ok(Arc::new(TaskHandle::new(get_runtime_handle().spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(RoomSendQueueUpdate::SentEvent { transaction_id, event_id }) => {
+                        listener.on_sent(transaction_id.to_string(), event_id.to_string());
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(num_skipped)) => {
+                        warn!(num_skipped, "Lagged behind the send queue updates");
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        }))))
+    }
+
+    /// Remove all the messages that haven't been sent yet in this room,
+    /// including the ones that failed to be sent.
+    ///
+    /// The message being sent at the moment, if any, is still sent. It's
+    /// recommended to disable the send queue of the room first, with
+    /// [`Self::enable_send_queue`], e.g. before leaving a room that has
+    /// unsent messages.
+    ///
+    /// Returns the number of removed messages and reactions.
+    pub async fn clear_send_queue(&self) -> Result<u64, ClientError> {
+        let num_removed =
+            self.inner.send_queue().clear_unsent().await.map_err(ClientError::from_err)?;
+        Ok(num_removed as u64)
+    }
+
     /// Store the given `ComposerDraft` in the state store using the current
     /// room id, as identifier.
     pub async fn save_composer_draft(
@@ -892,6 +1372,37 @@ impl Room {
         Ok(())
     }
 
+    /// Whether this room only shares its room keys with trusted devices.
+    pub async fn is_trusted_devices_only(&self) -> Result<bool, ClientError> {
+        Ok(self.inner.is_trusted_devices_only().await?)
+    }
+
+    /// Mark this room as only sharing its room keys with trusted devices.
+    ///
+    /// When enabled, sending fails with the
+    /// `QueueWedgeError::UntrustedDevices` error as long as some devices of
+    /// the members of the room are neither verified nor have been confirmed
+    /// with [`Room::confirm_untrusted_devices`].
+    pub async fn set_trusted_devices_only(&self, enabled: bool) -> Result<(), ClientError> {
+        Ok(self.inner.set_trusted_devices_only(enabled).await?)
+    }
+
+    /// Get the devices of the members of this room which would receive the
+    /// room key without being trusted.
+    pub async fn untrusted_devices_report(&self) -> Result<UntrustedDevicesReport, ClientError> {
+        Ok(self.inner.untrusted_devices_report().await?.into())
+    }
+
+    /// Confirm that the room keys can be shared with the devices of the given
+    /// report, so that sending isn't blocked by them anymore.
+    pub async fn confirm_untrusted_devices(
+        &self,
+        report: UntrustedDevicesReport,
+    ) -> Result<(), ClientError> {
+        let report = report.try_into()?;
+        Ok(self.inner.confirm_untrusted_devices(&report).await?)
+    }
+
     /// Clear the event cache storage for the current room.
     ///
     /// This will remove all the information related to the event cache, in
@@ -933,6 +1444,16 @@ impl Room {
         Ok(cache.debug_string().await)
     }
 
+    /// Return a machine-readable representation of the internal room events
+    /// data structure, one entry per chunk loaded in memory.
+    ///
+    /// This is meant for debugging purposes only, e.g. to display the
+    /// structure of the event cache or to attach it to bug reports.
+    pub async fn room_events_debug_chunks(&self) -> Result<Vec<EventCacheDebugChunk>, ClientError> {
+        let (cache, _drop_guards) = self.inner.event_cache().await?;
+        Ok(cache.debug_chunks().await.into_iter().map(Into::into).collect())
+    }
+
     /// Update the canonical alias of the room.
     ///
     /// Note that publishing the alias in the room directory is done separately.
@@ -1032,32 +1553,46 @@ impl Room {
         Ok(visibility.into())
     }
 
-    /// Start the current users live location share in the room.
-    pub async fn start_live_location_share(&self, duration_millis: u64) -> Result<(), ClientError> {
-        self.inner.start_live_location_share(duration_millis, None).await?;
+    /// Start sharing the live location of the current user in the room.
+    ///
+    /// The location itself is then sent with [`Room::update_live_location`].
+    ///
+    /// # Arguments
+    ///
+    /// * `duration_millis` - The duration for which the live location is
+    ///   shared, in milliseconds.
+    ///
+    /// * `description` - An optional description of the live location share.
+    pub async fn start_live_location(
+        &self,
+        duration_millis: u64,
+        description: Option<String>,
+    ) -> Result<(), ClientError> {
+        self.inner.start_live_location_share(duration_millis, description).await?;
         Ok(())
     }
 
-    /// Stop the current users live location share in the room.
-    pub async fn stop_live_location_share(&self) -> Result<(), ClientError> {
-        self.inner.stop_live_location_share().await.expect("Unable to stop live location share");
+    /// Send the current location of the current user in the room, for the
+    /// live location share started with [`Room::start_live_location`].
+    ///
+    /// Fails if the live location share has been stopped, or has expired.
+    pub async fn update_live_location(&self, geo_uri: String) -> Result<(), ClientError> {
+        self.inner.send_location_beacon(geo_uri).await?;
         Ok(())
     }
 
-    /// Send the current users live location beacon in the room.
-    pub async fn send_live_location(&self, geo_uri: String) -> Result<(), ClientError> {
-        self.inner
-            .send_location_beacon(geo_uri)
-            .await
-            .expect("Unable to send live location beacon");
+    /// Stop sharing the live location of the current user in the room.
+    pub async fn stop_live_location(&self) -> Result<(), ClientError> {
+        self.inner.stop_live_location_share().await?;
         Ok(())
     }
 
-    /// Subscribes to live location shares in this room, using a `listener` to
-    /// be notified of the changes.
+    /// Subscribes to the active live location shares of the other users of
+    /// this room, using a `listener` to be notified of the changes.
     ///
-    /// The current live location shares will be emitted immediately when
-    /// subscribing, along with a [`TaskHandle`] to cancel the subscription.
+    /// The whole list of active shares, with their last known location, is
+    /// emitted each time a location is received or a share is updated. Shares
+    /// which have been stopped, or have expired, are removed from the list.
     pub fn subscribe_to_live_location_shares(
         self: Arc<Self>,
         listener: Box<dyn LiveLocationShareListener>,
@@ -1066,31 +1601,11 @@ impl Room {
 
         Arc::new(TaskHandle::new(get_runtime_handle().spawn(async move {
             let subscription = room.observe_live_location_shares();
-            let stream = subscription.subscribe();
+            let stream = subscription.subscribe_to_active_shares();
             let mut pinned_stream = pin!(stream);
 
-            while let Some(event) = pinned_stream.next().await {
-                let last_location = LocationContent {
-                    body: "".to_owned(),
-                    geo_uri: event.last_location.location.uri.clone().to_string(),
-                    description: None,
-                    zoom_level: None,
-                    asset: None,
-                };
-
-                let Some(beacon_info) = event.beacon_info else {
-                    warn!("Live location share is missing the associated beacon_info state, skipping event.");
-                    continue;
-                };
-
-                listener.call(vec![LiveLocationShare {
-                    last_location: LastLocation {
-                        location: last_location,
-                        ts: event.last_location.ts.0.into(),
-                    },
-                    is_live: beacon_info.is_live(),
-                    user_id: event.user_id.to_string(),
-                }])
+            while let Some(shares) = pinned_stream.next().await {
+                listener.call(shares.into_iter().map(LiveLocationShare::from).collect());
             }
         })))
     }
@@ -1141,6 +1656,25 @@ impl Room {
     }
 }
 
+/// An event with the events surrounding it, as returned by
+/// [`Room::event_with_context`].
+#[derive(uniffi::Record)]
+pub struct EventWithContext {
+    /// The requested event.
+    pub event: EventWithProfile,
+    /// The events before the requested event, in chronological order.
+    pub events_before: Vec<EventWithProfile>,
+    /// The events after the requested event, in chronological order.
+    pub events_after: Vec<EventWithProfile>,
+}
+
+/// An event along with the profile of its sender.
+#[derive(uniffi::Record)]
+pub struct EventWithProfile {
+    pub event: Arc<TimelineEvent>,
+    pub sender_profile: ProfileDetails,
+}
+
 /// A listener for receiving new live location shares in a room.
 #[matrix_sdk_ffi_macros::export(callback_interface)]
 pub trait LiveLocationShareListener: SyncOutsideWasm + SendOutsideWasm {
@@ -1163,6 +1697,93 @@ impl From<matrix_sdk::room::knock_requests::KnockRequest> for KnockRequest {
     }
 }
 
+/// The types of room state that can be observed with [`Room::observe_state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum RoomStateUpdateType {
+    /// The `m.room.name` state event.
+    Name,
+    /// The `m.room.topic` state event.
+    Topic,
+    /// The `m.room.avatar` state event.
+    Avatar,
+    /// The `m.room.power_levels` state event.
+    PowerLevels,
+    /// The `m.room.join_rules` state event.
+    JoinRules,
+}
+
+impl RoomStateUpdateType {
+    fn from_event_type(event_type: &RumaStateEventType) -> Option<Self> {
+        match event_type {
+            RumaStateEventType::RoomName => Some(Self::Name),
+            RumaStateEventType::RoomTopic => Some(Self::Topic),
+            RumaStateEventType::RoomAvatar => Some(Self::Avatar),
+            RumaStateEventType::RoomPowerLevels => Some(Self::PowerLevels),
+            RumaStateEventType::RoomJoinRules => Some(Self::JoinRules),
+            _ => None,
+        }
+    }
+}
+
+/// The new value of a room state observed with [`Room::observe_state`].
+#[derive(uniffi::Enum)]
+pub enum RoomStateUpdate {
+    /// The raw name of the room changed.
+    Name { name: Option<String> },
+    /// The topic of the room changed.
+    Topic { topic: Option<String> },
+    /// The avatar of the room changed.
+    Avatar { url: Option<String> },
+    /// The power levels of the room changed.
+    PowerLevels { power_levels: Arc<RoomPowerLevels> },
+    /// The join rule of the room changed.
+    JoinRules { join_rule: Option<JoinRule> },
+}
+
+impl RoomStateUpdate {
+    /// Read the current value of the given type of state from the room.
+    async fn load(room: &SdkRoom, update_type: RoomStateUpdateType) -> Option<Self> {
+        Some(match update_type {
+            RoomStateUpdateType::Name => Self::Name { name: room.name() },
+            RoomStateUpdateType::Topic => Self::Topic { topic: room.topic() },
+            RoomStateUpdateType::Avatar => {
+                Self::Avatar { url: room.avatar_url().map(|url| url.to_string()) }
+            }
+            RoomStateUpdateType::PowerLevels => match room.power_levels().await {
+                Ok(power_levels) => Self::PowerLevels {
+                    power_levels: Arc::new(RoomPowerLevels::new(
+                        power_levels,
+                        room.own_user_id().to_owned(),
+                    )),
+                },
+                Err(err) => {
+                    error!("Failed to load the power levels: {err}");
+                    return None;
+                }
+            },
+            RoomStateUpdateType::JoinRules => {
+                let join_rule = room
+                    .join_rule()
+                    .map(TryInto::try_into)
+                    .transpose()
+                    .inspect_err(|err| {
+                        warn!("Failed to parse join rule: {err}");
+                    })
+                    .ok()
+                    .flatten();
+
+                Self::JoinRules { join_rule }
+            }
+        })
+    }
+}
+
+/// A listener for changes of the state of a room, see [`Room::observe_state`].
+#[matrix_sdk_ffi_macros::export(callback_interface)]
+pub trait RoomStateListener: SyncOutsideWasm + SendOutsideWasm {
+    fn call(&self, update: RoomStateUpdate);
+}
+
 /// A listener for receiving new requests to a join a room.
 #[matrix_sdk_ffi_macros::export(callback_interface)]
 pub trait KnockRequestsListener: SendOutsideWasm + SyncOutsideWasm {
@@ -1241,6 +1862,19 @@ pub trait RoomInfoListener: SyncOutsideWasm + SendOutsideWasm {
     fn call(&self, room_info: RoomInfo);
 }
 
+#[matrix_sdk_ffi_macros::export(callback_interface)]
+pub trait SendQueueStatusListener: SyncOutsideWasm + SendOutsideWasm {
+    /// Called every time the send queue of the room is enabled or disabled.
+    fn on_update(&self, is_enabled: bool);
+}
+
+#[matrix_sdk_ffi_macros::export(callback_interface)]
+pub trait SentEventListener: SyncOutsideWasm + SendOutsideWasm {
+    /// Called every time an event of the send queue has been sent, with the
+    /// transaction ID of its local echo and its event ID.
+    fn on_sent(&self, transaction_id: String, event_id: String);
+}
+
 #[matrix_sdk_ffi_macros::export(callback_interface)]
 pub trait TypingNotificationsListener: SyncOutsideWasm + SendOutsideWasm {
     fn call(&self, typing_user_ids: Vec<String>);
@@ -1492,6 +2126,20 @@ impl From<SdkSuccessorRoom> for SuccessorRoom {
     }
 }
 
+/// A [`SuccessorRoom`], along with the servers which can be used to join it.
+/// See [`Room::successor_room_with_via`].
+#[derive(uniffi::Record)]
+pub struct SuccessorRoomWithVia {
+    /// The ID of the replacement room.
+    pub room_id: String,
+
+    /// The message explaining why the room has been tombstoned.
+    pub reason: Option<String>,
+
+    /// The servers which can be used to join the replacement room.
+    pub via: Vec<String>,
+}
+
 /// When a room A is tombstoned, it is replaced by a room B. The room A is the
 /// predecessor of B, and B is the successor of A. This type holds information
 /// about the predecessor room. See [`Room::predecessor_room`].
@@ -1514,3 +2162,439 @@ impl From<SdkPredecessorRoom> for PredecessorRoom {
         Self { room_id: value.room_id.to_string(), last_event_id: value.last_event_id.to_string() }
     }
 }
+
+/// The reason why a device is considered untrusted.
+#[derive(uniffi::Enum)]
+pub enum UntrustedDeviceReason {
+    /// The device hasn't been signed by its owner.
+    UnsignedDevice,
+    /// The identity of the owner of the device hasn't been verified.
+    UnverifiedUser,
+}
+
+impl From<SdkUntrustedDeviceReason> for UntrustedDeviceReason {
+    fn from(value: SdkUntrustedDeviceReason) -> Self {
+        match value {
+            SdkUntrustedDeviceReason::UnsignedDevice => Self::UnsignedDevice,
+            SdkUntrustedDeviceReason::UnverifiedUser => Self::UnverifiedUser,
+        }
+    }
+}
+
+impl From<UntrustedDeviceReason> for SdkUntrustedDeviceReason {
+    fn from(value: UntrustedDeviceReason) -> Self {
+        match value {
+            UntrustedDeviceReason::UnsignedDevice => Self::UnsignedDevice,
+            UntrustedDeviceReason::UnverifiedUser => Self::UnverifiedUser,
+        }
+    }
+}
+
+/// An untrusted device of a member of a room.
+#[derive(uniffi::Record)]
+pub struct UntrustedDevice {
+    pub user_id: String,
+    pub device_id: String,
+    pub display_name: Option<String>,
+    pub reason: UntrustedDeviceReason,
+}
+
+/// The untrusted devices of the members of a room. See
+/// [`Room::untrusted_devices_report`].
+#[derive(uniffi::Record)]
+pub struct UntrustedDevicesReport {
+    pub devices: Vec<UntrustedDevice>,
+}
+
+impl From<SdkUntrustedDevicesReport> for UntrustedDevicesReport {
+    fn from(value: SdkUntrustedDevicesReport) -> Self {
+        let devices = value
+            .devices
+            .into_iter()
+            .map(|device| UntrustedDevice {
+                user_id: device.user_id.to_string(),
+                device_id: device.device_id.to_string(),
+                display_name: device.display_name,
+                reason: device.reason.into(),
+            })
+            .collect();
+
+        Self { devices }
+    }
+}
+
+impl TryFrom<UntrustedDevicesReport> for SdkUntrustedDevicesReport {
+    type Error = ClientError;
+
+    fn try_from(value: UntrustedDevicesReport) -> Result<Self, Self::Error> {
+        let devices = value
+            .devices
+            .into_iter()
+            .map(|device| {
+                Ok(SdkUntrustedDevice {
+                    user_id: UserId::parse(&device.user_id)?,
+                    device_id: device.device_id.into(),
+                    display_name: device.display_name,
+                    reason: device.reason.into(),
+                })
+            })
+            .collect::<Result<_, ClientError>>()?;
+
+        Ok(Self { devices })
+    }
+}
+
+/// A chunk of the internal room events data structure, as returned by
+/// [`Room::room_events_debug_chunks`].
+#[derive(uniffi::Record)]
+pub struct EventCacheDebugChunk {
+    /// The identifier of the chunk.
+    identifier: u64,
+    /// The identifier of the previous chunk, if this is the first chunk loaded
+    /// in memory and there are more chunks in the store.
+    lazy_previous: Option<u64>,
+    /// The content of the chunk.
+    content: EventCacheDebugChunkContent,
+}
+
+impl From<SdkDebugChunk> for EventCacheDebugChunk {
+    fn from(value: SdkDebugChunk) -> Self {
+        Self {
+            identifier: value.identifier.index(),
+            lazy_previous: value.lazy_previous.map(|identifier| identifier.index()),
+            content: value.content.into(),
+        }
+    }
+}
+
+/// The content of an [`EventCacheDebugChunk`].
+#[derive(uniffi::Enum)]
+pub enum EventCacheDebugChunkContent {
+    /// A gap in the history, with the token to paginate backwards from it.
+    Gap { prev_token: String },
+    /// Some events.
+    Events { events: Vec<EventCacheDebugEvent> },
+}
+
+impl From<SdkDebugChunkContent> for EventCacheDebugChunkContent {
+    fn from(value: SdkDebugChunkContent) -> Self {
+        match value {
+            SdkDebugChunkContent::Gap { prev_token } => Self::Gap { prev_token },
+            SdkDebugChunkContent::Events(events) => {
+                Self::Events { events: events.into_iter().map(Into::into).collect() }
+            }
+        }
+    }
+}
+
+/// An event of an [`EventCacheDebugChunk`].
+#[derive(uniffi::Record)]
+pub struct EventCacheDebugEvent {
+    /// The event ID, if the event has one.
+    event_id: Option<String>,
+    /// The position of the event in the ordering of all the events of the
+    /// room, if known.
+    order: Option<u64>,
+    /// Where the content of the event comes from.
+    origin: EventCacheDebugEventOrigin,
+}
+
+impl From<SdkDebugEvent> for EventCacheDebugEvent {
+    fn from(value: SdkDebugEvent) -> Self {
+        Self {
+            event_id: value.event_id.map(|event_id| event_id.to_string()),
+            order: value.order.map(|order| order as u64),
+            origin: value.origin.into(),
+        }
+    }
+}
+
+/// Where the content of an [`EventCacheDebugEvent`] comes from.
+#[derive(uniffi::Enum)]
+pub enum EventCacheDebugEventOrigin {
+    /// The event was received unencrypted.
+    PlainText,
+    /// The event was encrypted, and has been decrypted.
+    Decrypted,
+    /// The event is encrypted, and couldn't be decrypted.
+    UnableToDecrypt,
+}
+
+impl From<SdkDebugEventOrigin> for EventCacheDebugEventOrigin {
+    fn from(value: SdkDebugEventOrigin) -> Self {
+        match value {
+            SdkDebugEventOrigin::PlainText => Self::PlainText,
+            SdkDebugEventOrigin::Decrypted => Self::Decrypted,
+            SdkDebugEventOrigin::UnableToDecrypt => Self::UnableToDecrypt,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use matrix_sdk::test_utils::mocks::MatrixMockServer;
+    use matrix_sdk_common::timeout::timeout;
+    use matrix_sdk_test::{
+        async_test, event_factory::EventFactory, sync_state_event, JoinedRoomBuilder,
+    };
+    use ruma::{
+        api::client::receipt::create_receipt::v3::ReceiptType as RumaReceiptType,
+        event_id,
+        events::{
+            room::join_rules::{JoinRule as RumaJoinRule, RoomJoinRulesEventContent},
+            StateEventType,
+        },
+        room_id, user_id,
+    };
+    use serde_json::json;
+    use tokio::sync::mpsc;
+
+    use super::{Room, RoomStateListener, RoomStateUpdate, RoomStateUpdateType};
+    use crate::{client::JoinRule, timeline::ReceiptType};
+
+    #[async_test]
+    async fn test_get_state_event() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+
+        let room_id = room_id!("!room:localhost");
+        let sdk_room = server
+            .sync_room(
+                &client,
+                JoinedRoomBuilder::new(room_id).add_state_event(sync_state_event!({
+                    "content": { "answer": 42 },
+                    "event_id": "$custom_state",
+                    "origin_server_ts": 151800140,
+                    "sender": "@example:localhost",
+                    "state_key": "foo",
+                    "type": "com.example.state",
+                })),
+            )
+            .await;
+        let room = Room::new(sdk_room, None);
+
+        let event = room
+            .get_state_event("com.example.state".to_owned(), "foo".to_owned())
+            .await
+            .unwrap()
+            .expect("the state event should be known");
+        let event: serde_json::Value = serde_json::from_str(&event).unwrap();
+        assert_eq!(event["event_id"], "$custom_state");
+        assert_eq!(event["content"], json!({ "answer": 42 }));
+
+        // Another state key or event type isn't known.
+        assert!(room
+            .get_state_event("com.example.state".to_owned(), "bar".to_owned())
+            .await
+            .unwrap()
+            .is_none());
+        assert!(room
+            .get_state_event("com.example.other".to_owned(), "foo".to_owned())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[async_test]
+    async fn test_send_state_event() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+
+        let sdk_room = server.sync_joined_room(&client, room_id!("!room:localhost")).await;
+        let room = Room::new(sdk_room, None);
+
+        server.mock_room_state_encryption().plain().mount().await;
+        server
+            .mock_room_send_state()
+            .body_matches_partial_json(json!({ "answer": 42 }))
+            .ok(event_id!("$custom_state"))
+            .expect(1)
+            .mount()
+            .await;
+
+        let event_id = room
+            .send_state_event(
+                "com.example.state".to_owned(),
+                "foo".to_owned(),
+                r#"{ "answer": 42 }"#.to_owned(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(event_id, "$custom_state");
+
+        // Invalid JSON content is rejected without sending a request.
+        room.send_state_event("com.example.state".to_owned(), "foo".to_owned(), "{".to_owned())
+            .await
+            .unwrap_err();
+    }
+
+    #[async_test]
+    async fn test_join_rule_and_pinned_events() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+
+        let room_id = room_id!("!room:localhost");
+        let sdk_room = server
+            .sync_room(
+                &client,
+                JoinedRoomBuilder::new(room_id).add_state_bulk([
+                    sync_state_event!({
+                        "content": { "join_rule": "public" },
+                        "event_id": "$join_rules",
+                        "origin_server_ts": 151800140,
+                        "sender": "@example:localhost",
+                        "state_key": "",
+                        "type": "m.room.join_rules",
+                    }),
+                    sync_state_event!({
+                        "content": { "pinned": ["$first", "$second"] },
+                        "event_id": "$pinned_events",
+                        "origin_server_ts": 151800141,
+                        "sender": "@example:localhost",
+                        "state_key": "",
+                        "type": "m.room.pinned_events",
+                    }),
+                ]),
+            )
+            .await;
+        let room = Room::new(sdk_room, None);
+
+        assert!(matches!(room.join_rule().unwrap(), Some(JoinRule::Public)));
+        assert_eq!(room.pinned_event_ids(), ["$first", "$second"]);
+
+        server.mock_room_state_encryption().plain().mount().await;
+        server
+            .mock_room_send_state()
+            .for_type(StateEventType::RoomPinnedEvents)
+            .body_matches_partial_json(json!({ "pinned": ["$second", "$third"] }))
+            .ok(event_id!("$new_pinned_events"))
+            .expect(1)
+            .mount()
+            .await;
+
+        room.set_pinned_event_ids(vec!["$second".to_owned(), "$third".to_owned()]).await.unwrap();
+
+        // Invalid event IDs are rejected without sending a request.
+        room.set_pinned_event_ids(vec!["not an event ID".to_owned()]).await.unwrap_err();
+    }
+
+    struct RoomStateRecorder(mpsc::UnboundedSender<RoomStateUpdate>);
+
+    impl RoomStateListener for RoomStateRecorder {
+        fn call(&self, update: RoomStateUpdate) {
+            self.0.send(update).unwrap();
+        }
+    }
+
+    #[async_test]
+    async fn test_observe_state() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+
+        let room_id = room_id!("!room:localhost");
+        let sdk_room = server.sync_joined_room(&client, room_id).await;
+        let room = Arc::new(Room::new(sdk_room, None));
+
+        let (sender, mut updates) = mpsc::unbounded_channel();
+        let _handle = room.clone().observe_state(
+            vec![RoomStateUpdateType::Topic, RoomStateUpdateType::JoinRules],
+            Box::new(RoomStateRecorder(sender)),
+        );
+
+        let f = EventFactory::new().room(room_id).sender(user_id!("@example:localhost"));
+
+        // The name isn't observed, only the topic is.
+        server
+            .sync_room(
+                &client,
+                JoinedRoomBuilder::new(room_id)
+                    .add_state_event(f.room_name("Name"))
+                    .add_state_event(f.room_topic("Topic")),
+            )
+            .await;
+
+        let update = timeout(updates.recv(), Duration::from_secs(1)).await.unwrap().unwrap();
+        assert!(
+            matches!(update, RoomStateUpdate::Topic { topic } if topic.as_deref() == Some("Topic"))
+        );
+
+        server
+            .sync_room(
+                &client,
+                JoinedRoomBuilder::new(room_id).add_state_event(
+                    f.event(RoomJoinRulesEventContent::new(RumaJoinRule::Invite)).state_key(""),
+                ),
+            )
+            .await;
+
+        let update = timeout(updates.recv(), Duration::from_secs(1)).await.unwrap().unwrap();
+        assert!(matches!(update, RoomStateUpdate::JoinRules { join_rule: Some(JoinRule::Invite) }));
+
+        // No other update was received.
+        assert!(updates.try_recv().is_err());
+    }
+
+    #[async_test]
+    async fn test_send_read_receipt() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+
+        let sdk_room = server.sync_joined_room(&client, room_id!("!room:localhost")).await;
+        let room = Room::new(sdk_room, None);
+
+        server
+            .mock_send_receipt(RumaReceiptType::Read)
+            .body_matches_partial_json(json!({ "thread_id": "$root" }))
+            .ok()
+            .expect(1)
+            .mount()
+            .await;
+        server
+            .mock_send_receipt(RumaReceiptType::ReadPrivate)
+            .body_matches_partial_json(json!({ "thread_id": "main" }))
+            .ok()
+            .expect(1)
+            .mount()
+            .await;
+        server.mock_send_receipt(RumaReceiptType::FullyRead).ok().expect(1).mount().await;
+
+        // A receipt in a thread.
+        room.send_read_receipt(
+            "$in_thread".to_owned(),
+            ReceiptType::Read,
+            Some("$root".to_owned()),
+        )
+        .await
+        .unwrap();
+
+        // A receipt in the main timeline.
+        room.send_read_receipt(
+            "$in_main".to_owned(),
+            ReceiptType::ReadPrivate,
+            Some("main".to_owned()),
+        )
+        .await
+        .unwrap();
+
+        // An unthreaded fully-read marker.
+        room.send_read_receipt("$fully_read".to_owned(), ReceiptType::FullyRead, None)
+            .await
+            .unwrap();
+
+        // A fully-read marker can't be threaded, and the IDs must be valid, so no
+        // request is sent for these.
+        room.send_read_receipt(
+            "$fully_read".to_owned(),
+            ReceiptType::FullyRead,
+            Some("$root".to_owned()),
+        )
+        .await
+        .unwrap_err();
+        room.send_read_receipt("$event".to_owned(), ReceiptType::Read, Some("root".to_owned()))
+            .await
+            .unwrap_err();
+        room.send_read_receipt("event".to_owned(), ReceiptType::Read, None).await.unwrap_err();
+    }
+}
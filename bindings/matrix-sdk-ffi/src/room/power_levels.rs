@@ -21,6 +21,10 @@ impl RoomPowerLevels {
     pub fn new(value: RumaPowerLevels, own_user_id: OwnedUserId) -> Self {
         Self { inner: value, own_user_id }
     }
+
+    pub(crate) fn inner(&self) -> &RumaPowerLevels {
+        &self.inner
+    }
 }
 
 #[matrix_sdk_ffi_macros::export]
@@ -206,6 +210,12 @@ pub struct RoomPowerLevelsValues {
     pub room_avatar: i64,
     /// The level required to change the room's topic.
     pub room_topic: i64,
+    /// The level required to pin or unpin events in the room.
+    pub room_pinned_events: i64,
+    /// The level required to change the power levels of the room.
+    pub room_power_levels: i64,
+    /// The level required to trigger an `@room` notification.
+    pub room_notification: i64,
 }
 
 impl From<RumaPowerLevels> for RoomPowerLevelsValues {
@@ -228,6 +238,9 @@ impl From<RumaPowerLevels> for RoomPowerLevelsValues {
             room_name: state_event_level_for(&value, &TimelineEventType::RoomName),
             room_avatar: state_event_level_for(&value, &TimelineEventType::RoomAvatar),
             room_topic: state_event_level_for(&value, &TimelineEventType::RoomTopic),
+            room_pinned_events: state_event_level_for(&value, &TimelineEventType::RoomPinnedEvents),
+            room_power_levels: state_event_level_for(&value, &TimelineEventType::RoomPowerLevels),
+            room_notification: value.notifications.room.into(),
         }
     }
 }
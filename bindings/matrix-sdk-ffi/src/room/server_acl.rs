@@ -0,0 +1,117 @@
+use std::sync::Mutex;
+
+use matrix_sdk::room::server_acl::{
+    ServerAcl as SdkServerAcl, ServerAclError as SdkServerAclError,
+};
+use ruma::ServerName;
+
+/// An error when editing the server access control list of a room.
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum ServerAclError {
+    /// The server name pattern isn't valid.
+    #[error("`{pattern}` isn't a valid server name pattern")]
+    InvalidServerName { pattern: String },
+    /// The changes would deny the own server of the user, which would lock it
+    /// out of the room.
+    #[error("the server {server_name} of the user wouldn't be allowed in the room anymore")]
+    OwnServerDenied { server_name: String },
+    /// The state event couldn't be read or sent.
+    #[error("client error: {msg}")]
+    Generic { msg: String },
+}
+
+impl From<SdkServerAclError> for ServerAclError {
+    fn from(value: SdkServerAclError) -> Self {
+        match value {
+            SdkServerAclError::InvalidServerName(pattern) => Self::InvalidServerName { pattern },
+            SdkServerAclError::OwnServerDenied(server_name) => {
+                Self::OwnServerDenied { server_name: server_name.to_string() }
+            }
+            SdkServerAclError::Sdk(error) => Self::Generic { msg: error.to_string() },
+        }
+    }
+}
+
+/// An editor for the server access control list of a room.
+///
+/// The server name patterns can use the `*` and `?` wildcards. The changes
+/// are only sent to the room with [`ServerAcl::save`], which refuses to deny
+/// the own server of the user.
+#[derive(uniffi::Object)]
+pub struct ServerAcl {
+    inner: Mutex<SdkServerAcl>,
+}
+
+impl ServerAcl {
+    pub(crate) fn new(inner: SdkServerAcl) -> Self {
+        Self { inner: Mutex::new(inner) }
+    }
+}
+
+#[matrix_sdk_ffi_macros::export]
+impl ServerAcl {
+    /// The patterns of the servers allowed in the room.
+    pub fn allow(&self) -> Vec<String> {
+        self.inner.lock().unwrap().allow().to_vec()
+    }
+
+    /// The patterns of the servers denied in the room.
+    pub fn deny(&self) -> Vec<String> {
+        self.inner.lock().unwrap().deny().to_vec()
+    }
+
+    /// Whether servers using an IP address as their name are allowed in the
+    /// room.
+    pub fn allow_ip_literals(&self) -> bool {
+        self.inner.lock().unwrap().allow_ip_literals()
+    }
+
+    /// Whether the given server is allowed in the room with the current
+    /// changes.
+    pub fn is_allowed(&self, server_name: String) -> Result<bool, ServerAclError> {
+        let server_name = <&ServerName>::try_from(server_name.as_str())
+            .map_err(|_| ServerAclError::InvalidServerName { pattern: server_name.clone() })?;
+        Ok(self.inner.lock().unwrap().is_allowed(server_name))
+    }
+
+    /// Allow the servers matching the given pattern, and remove it from the
+    /// denied servers.
+    pub fn allow_server(&self, pattern: String) -> Result<(), ServerAclError> {
+        Ok(self.inner.lock().unwrap().allow_server(&pattern)?)
+    }
+
+    /// Deny the servers matching the given pattern, and remove it from the
+    /// allowed servers.
+    pub fn deny_server(&self, pattern: String) -> Result<(), ServerAclError> {
+        Ok(self.inner.lock().unwrap().deny_server(&pattern)?)
+    }
+
+    /// Remove the given pattern from the allowed and the denied servers.
+    ///
+    /// Returns `false` if the pattern was in neither list.
+    pub fn remove_server(&self, pattern: String) -> bool {
+        self.inner.lock().unwrap().remove_server(&pattern)
+    }
+
+    /// Set whether servers using an IP address as their name are allowed in
+    /// the room.
+    pub fn set_allow_ip_literals(&self, allow_ip_literals: bool) {
+        self.inner.lock().unwrap().set_allow_ip_literals(allow_ip_literals);
+    }
+
+    /// Check that the own server of the user is still allowed in the room
+    /// with the current changes.
+    pub fn validate(&self) -> Result<(), ServerAclError> {
+        Ok(self.inner.lock().unwrap().validate()?)
+    }
+
+    /// Send the server access control list with the current changes to the
+    /// room.
+    ///
+    /// Nothing is sent if [`ServerAcl::validate`] fails.
+    pub async fn save(&self) -> Result<(), ServerAclError> {
+        let acl = self.inner.lock().unwrap().clone();
+        acl.save().await?;
+        Ok(())
+    }
+}
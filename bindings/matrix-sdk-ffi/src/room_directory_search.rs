@@ -101,7 +101,7 @@ impl RoomDirectorySearch {
     /// You can specify a `batch_size` to control the number of rooms to fetch
     /// per request.
     ///
-    /// If the `via_server` is not provided it will search in the current
+    /// If the `via_server_name` is not provided it will search in the current
     /// homeserver by default.
     ///
     /// This method will clear the current search results and start a new one.
@@ -9,7 +9,7 @@ use matrix_sdk::{
         api::client::sync::sync_events::UnreadNotificationsCount as RumaUnreadNotificationsCount,
         RoomId,
     },
-    Room as SdkRoom,
+    Room as SdkRoom, SlidingSyncExtension as SdkSlidingSyncExtension,
 };
 use matrix_sdk_common::{SendOutsideWasm, SyncOutsideWasm};
 use matrix_sdk_ui::{
@@ -58,6 +58,7 @@ impl From<matrix_sdk_ui::room_list_service::Error> for RoomListError {
             UnknownList(list_name) => Self::UnknownList { list_name },
             RoomNotFound(room_id) => Self::RoomNotFound { room_name: room_id.to_string() },
             EventCache(error) => Self::EventCache { error: error.to_string() },
+            error @ UnsupportedExtension(_) => Self::SlidingSync { error: error.to_string() },
         }
     }
 }
@@ -133,6 +134,39 @@ impl RoomListService {
 
         Ok(())
     }
+
+    /// Enable or disable a sliding sync extension while syncing, e.g. to stop
+    /// receiving the typing notifications and the read receipts in a battery
+    /// saver mode.
+    async fn set_extension_enabled(
+        &self,
+        extension: SlidingSyncExtension,
+        enabled: bool,
+    ) -> Result<(), RoomListError> {
+        Ok(self.inner.set_extension_enabled(extension.into(), enabled).await?)
+    }
+}
+
+/// An extension of sliding sync, which can be enabled or disabled with
+/// [`RoomListService::set_extension_enabled`].
+///
+/// The end-to-end encryption and to-device extensions are handled by the
+/// encryption sync, so they can't be toggled on the room list sync.
+#[derive(uniffi::Enum)]
+pub enum SlidingSyncExtension {
+    AccountData,
+    Receipts,
+    Typing,
+}
+
+impl From<SlidingSyncExtension> for SdkSlidingSyncExtension {
+    fn from(value: SlidingSyncExtension) -> Self {
+        match value {
+            SlidingSyncExtension::AccountData => Self::AccountData,
+            SlidingSyncExtension::Receipts => Self::Receipts,
+            SlidingSyncExtension::Typing => Self::Typing,
+        }
+    }
 }
 
 #[derive(uniffi::Object)]
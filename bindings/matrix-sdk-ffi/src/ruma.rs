@@ -98,6 +98,28 @@ use crate::{
 pub enum AuthData {
     /// Password-based authentication (`m.login.password`).
     Password { password_details: AuthDataPasswordDetails },
+
+    /// Acknowledgement that a stage of the given user-interactive
+    /// authentication `session` has been completed on the fallback web page
+    /// of the homeserver, e.g. for `m.login.sso`.
+    FallbackAcknowledgement { session: String },
+
+    /// Registration token-based authentication
+    /// (`m.login.registration_token`).
+    RegistrationToken { token: String, session: Option<String> },
+
+    /// Email-based authentication (`m.login.email.identity`), once the user
+    /// has validated the email address with the token requested with
+    /// `Client::request_registration_email_token()`.
+    EmailIdentity { sid: String, client_secret: String, session: Option<String> },
+
+    /// ReCAPTCHA-based authentication (`m.login.recaptcha`), with the
+    /// response of the challenge completed by the user.
+    ReCaptcha { response: String, session: Option<String> },
+
+    /// Dummy authentication (`m.login.dummy`), for stages which don't require
+    /// anything from the user.
+    Dummy { session: Option<String> },
 }
 
 #[derive(uniffi::Record)]
@@ -120,6 +142,73 @@ impl From<AuthData> for ruma::api::client::uiaa::AuthData {
                     password_details.password,
                 ))
             }
+            AuthData::FallbackAcknowledgement { session } => {
+                ruma::api::client::uiaa::AuthData::FallbackAcknowledgement(
+                    ruma::api::client::uiaa::FallbackAcknowledgement::new(session),
+                )
+            }
+            AuthData::RegistrationToken { token, session } => {
+                ruma::api::client::uiaa::AuthData::RegistrationToken(assign!(
+                    ruma::api::client::uiaa::RegistrationToken::new(token),
+                    { session }
+                ))
+            }
+            AuthData::EmailIdentity { sid, client_secret, session } => {
+                let credentials = ruma::api::client::uiaa::ThirdpartyIdCredentials::new(
+                    sid.into(),
+                    client_secret.into(),
+                );
+                ruma::api::client::uiaa::AuthData::EmailIdentity(assign!(
+                    ruma::api::client::uiaa::EmailIdentity::new(credentials),
+                    { session }
+                ))
+            }
+            AuthData::ReCaptcha { response, session } => {
+                ruma::api::client::uiaa::AuthData::ReCaptcha(assign!(
+                    ruma::api::client::uiaa::ReCaptcha::new(response),
+                    { session }
+                ))
+            }
+            AuthData::Dummy { session } => ruma::api::client::uiaa::AuthData::Dummy(assign!(
+                ruma::api::client::uiaa::Dummy::new(),
+                { session }
+            )),
+        }
+    }
+}
+
+/// The state of a user-interactive authentication session, returned by the
+/// homeserver when a request requires the user to authenticate.
+#[derive(uniffi::Record)]
+pub struct UiaaInfo {
+    /// The ID of the session, to send back with the [`AuthData`] of the next
+    /// stage.
+    pub session: Option<String>,
+    /// The flows the user can complete, each being a list of stage types, e.g.
+    /// `m.login.registration_token` or `m.login.recaptcha`.
+    pub flows: Vec<Vec<String>>,
+    /// The stages that have already been completed.
+    pub completed: Vec<String>,
+    /// The parameters of the stages, as a JSON object, e.g. the public key of
+    /// the ReCAPTCHA under `m.login.recaptcha`.
+    pub params: String,
+    /// The error message of the previous attempt to complete a stage, if it
+    /// failed.
+    pub error: Option<String>,
+}
+
+impl From<&ruma::api::client::uiaa::UiaaInfo> for UiaaInfo {
+    fn from(value: &ruma::api::client::uiaa::UiaaInfo) -> Self {
+        Self {
+            session: value.session.clone(),
+            flows: value
+                .flows
+                .iter()
+                .map(|flow| flow.stages.iter().map(ToString::to_string).collect())
+                .collect(),
+            completed: value.completed.iter().map(ToString::to_string).collect(),
+            params: value.params.get().to_owned(),
+            error: value.auth_error.as_ref().map(|error| error.message.clone()),
         }
     }
 }
@@ -153,6 +242,12 @@ pub struct MatrixEntity {
     via: Vec<String>,
 }
 
+impl From<matrix_sdk::widget::NavigationRequest> for MatrixEntity {
+    fn from(value: matrix_sdk::widget::NavigationRequest) -> Self {
+        Self { id: (&value.id).into(), via: value.via.iter().map(|via| via.to_string()).collect() }
+    }
+}
+
 /// A Matrix ID that can be a room, room alias, user, or event.
 #[derive(Clone, uniffi::Enum)]
 pub enum MatrixId {
@@ -204,11 +299,17 @@ pub fn message_event_content_from_markdown(
     Arc::new(RoomMessageEventContentWithoutRelation::new(RumaMessageType::text_markdown(md)))
 }
 
+/// Creates the content of an emote from Markdown, with the given intentional
+/// mentions.
 #[matrix_sdk_ffi_macros::export]
 pub fn message_event_content_from_markdown_as_emote(
     md: String,
+    mentions: Option<Mentions>,
 ) -> Arc<RoomMessageEventContentWithoutRelation> {
-    Arc::new(RoomMessageEventContentWithoutRelation::new(RumaMessageType::emote_markdown(md)))
+    let mut content =
+        RoomMessageEventContentWithoutRelation::new(RumaMessageType::emote_markdown(md));
+    content.mentions = mentions.map(Into::into);
+    Arc::new(content)
 }
 
 #[matrix_sdk_ffi_macros::export]
@@ -221,14 +322,18 @@ pub fn message_event_content_from_html(
     )))
 }
 
+/// Creates the content of an emote from HTML, with the given intentional
+/// mentions.
 #[matrix_sdk_ffi_macros::export]
 pub fn message_event_content_from_html_as_emote(
     body: String,
     html_body: String,
+    mentions: Option<Mentions>,
 ) -> Arc<RoomMessageEventContentWithoutRelation> {
-    Arc::new(RoomMessageEventContentWithoutRelation::new(RumaMessageType::emote_html(
-        body, html_body,
-    )))
+    let mut content =
+        RoomMessageEventContentWithoutRelation::new(RumaMessageType::emote_html(body, html_body));
+    content.mentions = mentions.map(Into::into);
+    Arc::new(content)
 }
 
 #[derive(Clone, uniffi::Object)]
@@ -376,6 +481,11 @@ pub enum MessageType {
     Other {
         msgtype: String,
         body: String,
+        /// The remaining fields of the content, as a serialized JSON object.
+        ///
+        /// Doesn't contain the `msgtype` and `body` fields, and is `{}` if
+        /// there are no other fields.
+        data: String,
     },
 }
 
@@ -425,8 +535,9 @@ impl TryFrom<MessageType> for RumaMessageType {
             MessageType::Location { content } => {
                 Self::Location(RumaLocationMessageEventContent::new(content.body, content.geo_uri))
             }
-            MessageType::Other { msgtype, body } => {
-                Self::new(&msgtype, body, JsonObject::default())?
+            MessageType::Other { msgtype, body, data } => {
+                let data: JsonObject = serde_json::from_str(&data)?;
+                Self::new(&msgtype, body, data)?
             }
         })
     }
@@ -481,6 +592,7 @@ impl TryFrom<RumaMessageType> for MessageType {
             _ => MessageType::Other {
                 msgtype: value.msgtype().to_owned(),
                 body: value.body().to_owned(),
+                data: serde_json::to_string(&value.data())?,
             },
         })
     }
@@ -768,6 +880,58 @@ impl From<UnstableAudioDetailsContent> for RumaUnstableAudioDetailsContentBlock
     }
 }
 
+/// The minimum number of values in the waveform of a voice message.
+const MIN_WAVEFORM_LEN: usize = 30;
+/// The maximum number of values in the waveform of a voice message.
+const MAX_WAVEFORM_LEN: usize = 120;
+
+/// Build the audio details of a voice message from the raw PCM amplitude
+/// samples of its recording.
+///
+/// The samples are downsampled to a waveform of 30 to 120 values, each value
+/// being the average of the absolute amplitudes of the samples it covers. The
+/// waveform is then normalized so its loudest value is the maximum amplitude
+/// allowed by the specification.
+#[matrix_sdk_ffi_macros::export]
+pub fn unstable_audio_details_from_samples(
+    duration: Duration,
+    samples: Vec<f32>,
+) -> UnstableAudioDetailsContent {
+    UnstableAudioDetailsContent { duration, waveform: downsample_waveform(&samples) }
+}
+
+fn downsample_waveform(samples: &[f32]) -> Vec<u16> {
+    let len = samples.len().clamp(MIN_WAVEFORM_LEN, MAX_WAVEFORM_LEN);
+
+    if samples.is_empty() {
+        return vec![0; len];
+    }
+
+    // Average the absolute amplitudes in each bucket. If there are fewer samples
+    // than buckets, samples are repeated over several buckets.
+    let buckets = (0..len)
+        .map(|nth| {
+            let start = nth * samples.len() / len;
+            let end = ((nth + 1) * samples.len() / len).max(start + 1);
+            let bucket = &samples[start..end];
+            let sum =
+                bucket.iter().filter(|sample| sample.is_finite()).map(|s| s.abs()).sum::<f32>();
+            sum / bucket.len() as f32
+        })
+        .collect::<Vec<_>>();
+
+    let max = buckets.iter().copied().fold(0.0, f32::max);
+    if max <= 0.0 {
+        return vec![0; len];
+    }
+
+    let max_amplitude = f32::from(UnstableAmplitude::MAX);
+    buckets
+        .into_iter()
+        .map(|value| (value / max * max_amplitude).round().clamp(0.0, max_amplitude) as u16)
+        .collect()
+}
+
 #[derive(Clone, uniffi::Record)]
 pub struct UnstableVoiceContent {}
 
@@ -1077,9 +1241,28 @@ impl From<RumaPollKind> for PollKind {
 
 /// Creates a [`RoomMessageEventContentWithoutRelation`] given a
 /// [`MessageContent`] value.
+///
+/// The intentional mentions of the message are kept, so editing a message,
+/// including an emote, doesn't drop them. Use
+/// [`forwarded_content_from_message`] to forward the message instead.
 #[matrix_sdk_ffi_macros::export]
 pub fn content_without_relation_from_message(
     message: MessageContent,
+) -> Result<Arc<RoomMessageEventContentWithoutRelation>, ClientError> {
+    let msg_type = message.msg_type.try_into()?;
+    let mut content = RoomMessageEventContentWithoutRelation::new(msg_type);
+    content.mentions = message.mentions.map(Into::into);
+    Ok(Arc::new(content))
+}
+
+/// Creates a [`RoomMessageEventContentWithoutRelation`] to forward the given
+/// [`MessageContent`] to another room.
+///
+/// The intentional mentions of the message are dropped, so the users
+/// mentioned in the original message aren't notified again.
+#[matrix_sdk_ffi_macros::export]
+pub fn forwarded_content_from_message(
+    message: MessageContent,
 ) -> Result<Arc<RoomMessageEventContentWithoutRelation>, ClientError> {
     let msg_type = message.msg_type.try_into()?;
     Ok(Arc::new(RoomMessageEventContentWithoutRelation::new(msg_type)))
@@ -1855,3 +2038,177 @@ mod galleries {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::ruma::{events::room::message::MessageType as RumaMessageType, owned_user_id};
+    use serde_json::json;
+
+    use super::{
+        content_without_relation_from_message, downsample_waveform, forwarded_content_from_message,
+        message_event_content_from_html_as_emote, message_event_content_from_markdown_as_emote,
+        EmoteMessageContent, FormattedBody, Mentions, MessageFormat, MessageType,
+    };
+    use crate::timeline::MessageContent;
+
+    #[test]
+    fn test_custom_message_type_round_trip() {
+        let ruma_message_type: RumaMessageType = serde_json::from_value(json!({
+            "msgtype": "org.example.custom",
+            "body": "A custom message",
+            "org.example.payload": { "answer": 42 },
+        }))
+        .unwrap();
+
+        let message_type = MessageType::try_from(ruma_message_type).unwrap();
+        let MessageType::Other { msgtype, body, data } = &message_type else {
+            panic!("the custom message type should be mapped to `MessageType::Other`");
+        };
+        assert_eq!(msgtype, "org.example.custom");
+        assert_eq!(body, "A custom message");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(data).unwrap(),
+            json!({ "org.example.payload": { "answer": 42 } })
+        );
+
+        // Converting back must not lose the extra fields.
+        let ruma_message_type = RumaMessageType::try_from(message_type).unwrap();
+        assert_eq!(
+            serde_json::to_value(&ruma_message_type).unwrap(),
+            json!({
+                "msgtype": "org.example.custom",
+                "body": "A custom message",
+                "org.example.payload": { "answer": 42 },
+            })
+        );
+    }
+
+    #[test]
+    fn test_content_without_relation_from_emote() {
+        let message = MessageContent {
+            msg_type: MessageType::Emote {
+                content: EmoteMessageContent {
+                    body: "waves at Alice".to_owned(),
+                    formatted: Some(FormattedBody {
+                        format: MessageFormat::Html,
+                        body:
+                            "waves at <a href=\"https://matrix.to/#/@alice:example.org\">Alice</a>"
+                                .to_owned(),
+                    }),
+                },
+            },
+            body: "waves at Alice".to_owned(),
+            is_edited: false,
+            mentions: Some(Mentions {
+                user_ids: vec!["@alice:example.org".to_owned()],
+                room: false,
+            }),
+            effect: None,
+            additional_content: "{}".to_owned(),
+        };
+
+        let content = content_without_relation_from_message(message).unwrap();
+
+        // The emote keeps its formatted body and its intentional mentions.
+        let RumaMessageType::Emote(emote) = &content.msgtype else {
+            panic!("the message type should still be an emote");
+        };
+        assert_eq!(emote.body, "waves at Alice");
+        assert_eq!(
+            emote.formatted.as_ref().unwrap().body,
+            "waves at <a href=\"https://matrix.to/#/@alice:example.org\">Alice</a>"
+        );
+
+        let mentions = content.mentions.as_ref().unwrap();
+        assert_eq!(
+            mentions.user_ids.iter().collect::<Vec<_>>(),
+            [&owned_user_id!("@alice:example.org")]
+        );
+        assert!(!mentions.room);
+    }
+
+    #[test]
+    fn test_forwarded_content_from_message() {
+        let message = MessageContent {
+            msg_type: MessageType::Emote {
+                content: EmoteMessageContent { body: "waves at Alice".to_owned(), formatted: None },
+            },
+            body: "waves at Alice".to_owned(),
+            is_edited: false,
+            mentions: Some(Mentions {
+                user_ids: vec!["@alice:example.org".to_owned()],
+                room: true,
+            }),
+            effect: None,
+            additional_content: "{}".to_owned(),
+        };
+
+        let content = forwarded_content_from_message(message).unwrap();
+
+        // The emote is kept, but the mentioned users aren't notified again.
+        let RumaMessageType::Emote(emote) = &content.msgtype else {
+            panic!("the message type should still be an emote");
+        };
+        assert_eq!(emote.body, "waves at Alice");
+        assert!(content.mentions.is_none());
+    }
+
+    #[test]
+    fn test_emote_content_with_mentions() {
+        let mentions =
+            || Some(Mentions { user_ids: vec!["@alice:example.org".to_owned()], room: false });
+
+        let content = message_event_content_from_markdown_as_emote(
+            "waves at **Alice**".to_owned(),
+            mentions(),
+        );
+        let RumaMessageType::Emote(emote) = &content.msgtype else {
+            panic!("the message type should be an emote");
+        };
+        assert_eq!(emote.formatted.as_ref().unwrap().body, "waves at <strong>Alice</strong>");
+        assert_eq!(
+            content.mentions.as_ref().unwrap().user_ids.iter().collect::<Vec<_>>(),
+            [&owned_user_id!("@alice:example.org")]
+        );
+
+        let content = message_event_content_from_html_as_emote(
+            "waves at Alice".to_owned(),
+            "waves at <a href=\"https://matrix.to/#/@alice:example.org\">Alice</a>".to_owned(),
+            mentions(),
+        );
+        assert!(matches!(&content.msgtype, RumaMessageType::Emote(_)));
+        assert_eq!(
+            content.mentions.as_ref().unwrap().user_ids.iter().collect::<Vec<_>>(),
+            [&owned_user_id!("@alice:example.org")]
+        );
+
+        // Without mentions, nobody is mentioned.
+        let content = message_event_content_from_markdown_as_emote("waves".to_owned(), None);
+        assert!(content.mentions.is_none());
+    }
+
+    #[test]
+    fn test_downsample_waveform() {
+        // Long recordings are downsampled to 120 values, normalized to the maximum
+        // amplitude.
+        let samples = (0..1200).map(|nth| if nth < 600 { 0.25 } else { -0.5 }).collect::<Vec<_>>();
+        let waveform = downsample_waveform(&samples);
+        assert_eq!(waveform.len(), 120);
+        assert!(waveform[..60].iter().all(|value| *value == 512));
+        assert!(waveform[60..].iter().all(|value| *value == 1024));
+
+        // Short recordings are upsampled to 30 values.
+        let waveform = downsample_waveform(&[0.1, 0.2, 0.4]);
+        assert_eq!(waveform.len(), 30);
+        assert_eq!(waveform[0], 256);
+        assert_eq!(waveform[15], 512);
+        assert_eq!(waveform[29], 1024);
+
+        // Lengths in between are kept as is.
+        assert_eq!(downsample_waveform(&[1.0; 50]), vec![1024; 50]);
+
+        // Silence and empty recordings give a flat waveform.
+        assert_eq!(downsample_waveform(&[0.0; 200]), vec![0; 120]);
+        assert_eq!(downsample_waveform(&[]), vec![0; 30]);
+    }
+}
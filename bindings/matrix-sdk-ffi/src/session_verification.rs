@@ -4,7 +4,10 @@ use futures_util::StreamExt;
 use matrix_sdk::{
     encryption::{
         identities::UserIdentity,
-        verification::{SasState, SasVerification, VerificationRequest, VerificationRequestState},
+        verification::{
+            EmojiShortAuthString, SasState, SasVerification, VerificationRequest,
+            VerificationRequestState,
+        },
         Encryption,
     },
     ruma::events::key::verification::VerificationMethod,
@@ -16,6 +19,7 @@ use tracing::{error, warn};
 
 use crate::{
     client::UserProfile, error::ClientError, runtime::get_runtime_handle, utils::Timestamp,
+    TaskHandle,
 };
 
 #[derive(uniffi::Object)]
@@ -41,6 +45,27 @@ pub enum SessionVerificationData {
     Decimals { values: Vec<u16> },
 }
 
+impl SessionVerificationData {
+    fn new(emojis: Option<EmojiShortAuthString>, decimals: (u16, u16, u16)) -> Self {
+        match emojis {
+            Some(emojis) => Self::Emojis {
+                emojis: emojis
+                    .emojis
+                    .into_iter()
+                    .map(|emoji| {
+                        Arc::new(SessionVerificationEmoji {
+                            symbol: emoji.symbol.to_owned(),
+                            description: emoji.description.to_owned(),
+                        })
+                    })
+                    .collect(),
+                indices: emojis.indices.to_vec(),
+            },
+            None => Self::Decimals { values: vec![decimals.0, decimals.1, decimals.2] },
+        }
+    }
+}
+
 /// Details about the incoming verification request
 #[derive(uniffi::Record)]
 pub struct SessionVerificationRequestDetails {
@@ -124,23 +149,73 @@ impl SessionVerificationController {
 
     /// Request verification for the given user
     pub async fn request_user_verification(&self, user_id: String) -> Result<(), ClientError> {
-        let user_id = UserId::parse(user_id)?;
+        let user_identity = self.unverified_user_identity(user_id).await?;
 
-        let user_identity = self
-            .encryption
-            .get_user_identity(&user_id)
-            .await?
-            .ok_or(ClientError::from_str("Unknown user identity", None))?;
+        let methods = vec![VerificationMethod::SasV1];
 
-        if user_identity.is_verified() {
-            return Err(ClientError::from_str("User is already verified", None));
-        }
+        let verification_request = user_identity.request_verification_with_methods(methods).await?;
 
+        self.set_ongoing_verification_request(verification_request)
+    }
+
+    /// Request verification for the current device, in a new flow which can
+    /// run concurrently with the other ones.
+    ///
+    /// The state changes of the flow are reported to the given `listener`
+    /// instead of the delegate.
+    pub async fn request_device_verification_flow(
+        &self,
+        listener: Box<dyn VerificationFlowListener>,
+    ) -> Result<Arc<VerificationFlow>, ClientError> {
         let methods = vec![VerificationMethod::SasV1];
+        let verification_request =
+            self.user_identity.request_verification_with_methods(methods).await?;
+
+        Ok(VerificationFlow::new(verification_request, listener))
+    }
+
+    /// Request verification for the given user, in a new flow which can run
+    /// concurrently with the other ones.
+    ///
+    /// The state changes of the flow are reported to the given `listener`
+    /// instead of the delegate.
+    pub async fn request_user_verification_flow(
+        &self,
+        user_id: String,
+        listener: Box<dyn VerificationFlowListener>,
+    ) -> Result<Arc<VerificationFlow>, ClientError> {
+        let user_identity = self.unverified_user_identity(user_id).await?;
 
+        let methods = vec![VerificationMethod::SasV1];
         let verification_request = user_identity.request_verification_with_methods(methods).await?;
 
-        self.set_ongoing_verification_request(verification_request)
+        Ok(VerificationFlow::new(verification_request, listener))
+    }
+
+    /// Get the flow of an incoming verification request, reported with
+    /// [`SessionVerificationControllerDelegate::did_receive_verification_request`],
+    /// to handle it concurrently with the other flows.
+    ///
+    /// The state changes of the flow are reported to the given `listener`
+    /// instead of the delegate.
+    ///
+    /// * `sender_id` - The user requesting verification.
+    /// * `flow_id` - The ID that uniquely identifies the verification flow.
+    pub async fn verification_flow(
+        &self,
+        sender_id: String,
+        flow_id: String,
+        listener: Box<dyn VerificationFlowListener>,
+    ) -> Result<Arc<VerificationFlow>, ClientError> {
+        let sender_id = UserId::parse(sender_id)?;
+
+        let verification_request = self
+            .encryption
+            .get_verification_request(&sender_id, flow_id)
+            .await
+            .ok_or(ClientError::from_str("Unknown session verification request", None))?;
+
+        Ok(VerificationFlow::new(verification_request, listener))
     }
 
     /// Transition the current verification request into a SAS verification
@@ -224,6 +299,23 @@ impl SessionVerificationController {
         }
     }
 
+    /// Get the identity of the given user, making sure it isn't verified yet.
+    async fn unverified_user_identity(&self, user_id: String) -> Result<UserIdentity, ClientError> {
+        let user_id = UserId::parse(user_id)?;
+
+        let user_identity = self
+            .encryption
+            .get_user_identity(&user_id)
+            .await?
+            .ok_or(ClientError::from_str("Unknown user identity", None))?;
+
+        if user_identity.is_verified() {
+            return Err(ClientError::from_str("User is already verified", None));
+        }
+
+        Ok(user_identity)
+    }
+
     /// Ask the controller to process an incoming request based on the sender
     /// and flow identifier. It will fetch the request, verify that it's in the
     /// correct state and then and notify the delegate.
@@ -355,29 +447,9 @@ impl SessionVerificationController {
             match state {
                 SasState::KeysExchanged { emojis, decimals } => {
                     if let Some(delegate) = &*delegate.read().unwrap() {
-                        if let Some(emojis) = emojis {
-                            delegate.did_receive_verification_data(
-                                SessionVerificationData::Emojis {
-                                    emojis: emojis
-                                        .emojis
-                                        .into_iter()
-                                        .map(|emoji| {
-                                            Arc::new(SessionVerificationEmoji {
-                                                symbol: emoji.symbol.to_owned(),
-                                                description: emoji.description.to_owned(),
-                                            })
-                                        })
-                                        .collect(),
-                                    indices: emojis.indices.to_vec(),
-                                },
-                            );
-                        } else {
-                            delegate.did_receive_verification_data(
-                                SessionVerificationData::Decimals {
-                                    values: vec![decimals.0, decimals.1, decimals.2],
-                                },
-                            )
-                        }
+                        delegate.did_receive_verification_data(SessionVerificationData::new(
+                            emojis, decimals,
+                        ));
                     }
                 }
                 SasState::Done { .. } => {
@@ -402,3 +474,189 @@ impl SessionVerificationController {
         }
     }
 }
+
+/// A state change of a [`VerificationFlow`].
+#[derive(uniffi::Enum)]
+pub enum VerificationFlowState {
+    /// The other side accepted the request, the SAS verification can be
+    /// started.
+    Ready,
+    /// The SAS verification has started.
+    SasStarted,
+    /// The short auth strings are ready to be compared.
+    ReceivedVerificationData { data: SessionVerificationData },
+    /// The verification succeeded.
+    Finished,
+    /// The verification has been cancelled, by us or by the other side.
+    Cancelled,
+    /// The verification failed, e.g. because the other side started a method
+    /// which isn't supported.
+    Failed,
+}
+
+#[matrix_sdk_ffi_macros::export(callback_interface)]
+pub trait VerificationFlowListener: SyncOutsideWasm + SendOutsideWasm {
+    fn on_update(&self, state: VerificationFlowState);
+}
+
+/// A verification flow with another user or device.
+///
+/// Unlike the flow driven by the [`SessionVerificationController`] itself,
+/// several of these flows can run concurrently: each one is identified by its
+/// flow ID and reports its own state changes to its own listener. The
+/// listener is notified as long as this object is alive.
+#[derive(uniffi::Object)]
+pub struct VerificationFlow {
+    verification_request: VerificationRequest,
+    sas_verification: Arc<RwLock<Option<SasVerification>>>,
+    _listener_task: TaskHandle,
+}
+
+impl VerificationFlow {
+    fn new(
+        verification_request: VerificationRequest,
+        listener: Box<dyn VerificationFlowListener>,
+    ) -> Arc<Self> {
+        let sas_verification = Arc::new(RwLock::new(None));
+
+        let listener_task = TaskHandle::new(get_runtime_handle().spawn(Self::listen_to_changes(
+            verification_request.clone(),
+            sas_verification.clone(),
+            listener,
+        )));
+
+        Arc::new(Self { verification_request, sas_verification, _listener_task: listener_task })
+    }
+
+    async fn listen_to_changes(
+        verification_request: VerificationRequest,
+        sas_verification: Arc<RwLock<Option<SasVerification>>>,
+        listener: Box<dyn VerificationFlowListener>,
+    ) {
+        let mut stream = verification_request.changes();
+
+        while let Some(state) = stream.next().await {
+            match state {
+                VerificationRequestState::Ready { .. } => {
+                    listener.on_update(VerificationFlowState::Ready);
+                }
+                VerificationRequestState::Transitioned { verification } => {
+                    let Some(sas) = verification.sas() else {
+                        error!("Invalid, non-sas verification flow. Returning.");
+                        listener.on_update(VerificationFlowState::Failed);
+                        return;
+                    };
+
+                    *sas_verification.write().unwrap() = Some(sas.clone());
+
+                    // Accept the SAS verification if the other side started it.
+                    if !sas.we_started() && sas.accept().await.is_err() {
+                        listener.on_update(VerificationFlowState::Failed);
+                        return;
+                    }
+
+                    listener.on_update(VerificationFlowState::SasStarted);
+
+                    Self::listen_to_sas_changes(sas, &*listener).await;
+                    return;
+                }
+                VerificationRequestState::Done => {
+                    listener.on_update(VerificationFlowState::Finished);
+                    return;
+                }
+                VerificationRequestState::Cancelled(..) => {
+                    listener.on_update(VerificationFlowState::Cancelled);
+                    return;
+                }
+                VerificationRequestState::Created { .. }
+                | VerificationRequestState::Requested { .. } => {}
+            }
+        }
+    }
+
+    async fn listen_to_sas_changes(sas: SasVerification, listener: &dyn VerificationFlowListener) {
+        let mut stream = sas.changes();
+
+        while let Some(state) = stream.next().await {
+            match state {
+                SasState::KeysExchanged { emojis, decimals } => {
+                    let data = SessionVerificationData::new(emojis, decimals);
+                    listener.on_update(VerificationFlowState::ReceivedVerificationData { data });
+                }
+                SasState::Done { .. } => {
+                    listener.on_update(VerificationFlowState::Finished);
+                    break;
+                }
+                SasState::Cancelled(_) => {
+                    listener.on_update(VerificationFlowState::Cancelled);
+                    break;
+                }
+                SasState::Created { .. }
+                | SasState::Started { .. }
+                | SasState::Accepted { .. }
+                | SasState::Confirmed => (),
+            }
+        }
+    }
+
+    fn sas_verification(&self) -> Result<SasVerification, ClientError> {
+        self.sas_verification
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or(ClientError::from_str("SAS verification missing", None))
+    }
+}
+
+#[matrix_sdk_ffi_macros::export]
+impl VerificationFlow {
+    /// The ID that uniquely identifies this verification flow.
+    pub fn flow_id(&self) -> String {
+        self.verification_request.flow_id().to_owned()
+    }
+
+    /// The ID of the user on the other side of the verification.
+    pub fn other_user_id(&self) -> String {
+        self.verification_request.other_user_id().to_string()
+    }
+
+    /// Whether this is a verification of one of our own devices.
+    pub fn is_self_verification(&self) -> bool {
+        self.verification_request.is_self_verification()
+    }
+
+    /// Whether we started this verification flow.
+    pub fn we_started(&self) -> bool {
+        self.verification_request.we_started()
+    }
+
+    /// Accept the incoming verification request.
+    pub async fn accept(&self) -> Result<(), ClientError> {
+        let methods = vec![VerificationMethod::SasV1];
+        Ok(self.verification_request.accept_with_methods(methods).await?)
+    }
+
+    /// Transition the verification request into a SAS verification.
+    pub async fn start_sas_verification(&self) -> Result<(), ClientError> {
+        match self.verification_request.start_sas().await? {
+            // The listener is notified when the request transitions.
+            Some(_) => Ok(()),
+            None => Err(ClientError::from_str("The SAS verification couldn't be started", None)),
+        }
+    }
+
+    /// Confirm that the short auth strings match on both sides.
+    pub async fn approve_verification(&self) -> Result<(), ClientError> {
+        Ok(self.sas_verification()?.confirm().await?)
+    }
+
+    /// Reject the short auth strings.
+    pub async fn decline_verification(&self) -> Result<(), ClientError> {
+        Ok(self.sas_verification()?.mismatch().await?)
+    }
+
+    /// Cancel the verification flow.
+    pub async fn cancel_verification(&self) -> Result<(), ClientError> {
+        Ok(self.verification_request.cancel().await?)
+    }
+}
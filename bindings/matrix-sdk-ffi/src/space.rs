@@ -0,0 +1,133 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use matrix_sdk::{
+    room::{LeaveOptions, LeaveOutcome},
+    Room as SdkRoom,
+};
+use ruma::RoomId;
+
+use crate::error::ClientError;
+
+/// A space, i.e. a room grouping other rooms, its children.
+#[derive(uniffi::Object)]
+pub struct Space {
+    inner: SdkRoom,
+}
+
+impl Space {
+    pub(crate) fn new(inner: SdkRoom) -> Self {
+        Self { inner }
+    }
+}
+
+#[matrix_sdk_ffi_macros::export]
+impl Space {
+    /// The ID of the room of this space.
+    pub fn id(&self) -> String {
+        self.inner.room_id().to_string()
+    }
+
+    /// Add the given room as a child of this space.
+    ///
+    /// The child room also advertises this space as its parent, if the current
+    /// user is allowed to.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_id` - The ID of the room to add to this space.
+    ///
+    /// * `previous_child_id` - The ID of the child after which the room is
+    ///   sorted, or `None` to sort it first.
+    ///
+    /// * `next_child_id` - The ID of the child before which the room is sorted,
+    ///   or `None` to sort it last.
+    ///
+    /// * `suggested` - Whether the child room is suggested to the members of
+    ///   this space.
+    ///
+    /// If both `previous_child_id` and `next_child_id` are `None`, or if the
+    /// room can't be sorted between them, it's added without order, i.e. after
+    /// the children with an order.
+    pub async fn add_child(
+        &self,
+        room_id: String,
+        previous_child_id: Option<String>,
+        next_child_id: Option<String>,
+        suggested: bool,
+    ) -> Result<(), ClientError> {
+        let room_id = RoomId::parse(room_id)?;
+        let previous_child_id = previous_child_id.map(RoomId::parse).transpose()?;
+        let next_child_id = next_child_id.map(RoomId::parse).transpose()?;
+
+        let order = if previous_child_id.is_none() && next_child_id.is_none() {
+            None
+        } else {
+            self.inner
+                .space_child_order_between(previous_child_id.as_deref(), next_child_id.as_deref())
+                .await?
+        };
+
+        self.inner.add_space_child(&room_id, order, suggested).await?;
+
+        Ok(())
+    }
+
+    /// Remove the given room from the children of this space.
+    ///
+    /// The child room also stops advertising this space as its parent, if the
+    /// current user is allowed to.
+    pub async fn remove_child(&self, room_id: String) -> Result<(), ClientError> {
+        let room_id = RoomId::parse(room_id)?;
+
+        self.inner.remove_space_child(&room_id).await?;
+
+        Ok(())
+    }
+
+    /// Leave the rooms of this space the current user is in, then this space
+    /// itself, with the given options.
+    ///
+    /// Only the direct children of this space are left. Failing to leave a
+    /// room doesn't prevent leaving the other ones, so the result of leaving
+    /// each room is returned, in the order the rooms have been left.
+    pub async fn leave(&self, options: LeaveOptions) -> Result<Vec<SpaceLeaveResult>, ClientError> {
+        let results = self.inner.leave_space(options).await?;
+
+        Ok(results
+            .into_iter()
+            .map(|(room_id, result)| {
+                let (outcome, error) = match result {
+                    Ok(outcome) => (Some(outcome), None),
+                    Err(error) => (None, Some(error.to_string())),
+                };
+
+                SpaceLeaveResult { room_id: room_id.to_string(), outcome, error }
+            })
+            .collect())
+    }
+}
+
+/// The result of leaving a room, when leaving a [`Space`].
+#[derive(uniffi::Record)]
+pub struct SpaceLeaveResult {
+    /// The ID of the room.
+    pub room_id: String,
+
+    /// What leaving the room meant, if it was left successfully.
+    pub outcome: Option<LeaveOutcome>,
+
+    /// The error that occurred when leaving the room, if any.
+    pub error: Option<String>,
+}
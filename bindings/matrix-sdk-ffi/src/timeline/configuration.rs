@@ -142,6 +142,31 @@ impl From<DateDividerMode> for matrix_sdk_ui::timeline::DateDividerMode {
     }
 }
 
+/// How the timeline resolves the profiles of the senders of its events.
+#[derive(uniffi::Enum)]
+pub enum ProfileResolution {
+    /// Resolve the profiles from the memberships of the room.
+    RoomMembership,
+    /// Resolve the profiles from the global profiles of the senders, fetched
+    /// in the background and cached for the lifetime of the timeline.
+    GlobalProfile,
+    /// Don't resolve the profiles, only the IDs of the senders are available.
+    ///
+    /// The profile of a specific sender can still be resolved on demand,
+    /// with `Timeline::fetch_sender_profile`.
+    None,
+}
+
+impl From<ProfileResolution> for matrix_sdk_ui::timeline::ProfileResolution {
+    fn from(value: ProfileResolution) -> Self {
+        match value {
+            ProfileResolution::RoomMembership => Self::RoomMembership,
+            ProfileResolution::GlobalProfile => Self::GlobalProfile,
+            ProfileResolution::None => Self::None,
+        }
+    }
+}
+
 #[derive(uniffi::Enum)]
 pub enum TimelineFilter {
     /// Show all the events in the timeline, independent of their type.
@@ -183,4 +208,15 @@ pub struct TimelineConfiguration {
     /// Whether this timeline instance should report UTDs through the client's
     /// delegate.
     pub report_utds: bool,
+
+    /// Should the local echoes be stamped with the time of the homeserver,
+    /// estimated from the events received from sync, rather than the time of
+    /// the local clock?
+    ///
+    /// This avoids misplacing the local echoes and their date dividers on
+    /// devices with a wrong clock.
+    pub correct_clock_skew: bool,
+
+    /// Where should the profiles of the senders be resolved from?
+    pub profile_resolution: ProfileResolution,
 }
@@ -20,8 +20,9 @@ use eyeball_im::VectorDiff;
 use futures_util::pin_mut;
 use matrix_sdk::{
     attachment::{
-        AttachmentConfig, AttachmentInfo, BaseAudioInfo, BaseFileInfo, BaseImageInfo,
-        BaseVideoInfo, Thumbnail,
+        AttachmentConfig, AttachmentInfo, AttachmentProcessing as SdkAttachmentProcessing,
+        BaseAudioInfo, BaseFileInfo, BaseImageInfo, BaseVideoInfo,
+        ImageOutputFormat as SdkImageOutputFormat, Thumbnail,
     },
     deserialized_responses::{ShieldState as SdkShieldState, ShieldStateCode},
     event_cache::RoomPaginationStatus,
@@ -58,9 +59,10 @@ use ruma::{
         },
         AnyMessageLikeEventContent,
     },
-    EventId, UInt,
+    serde::Raw,
+    EventId, UInt, UserId,
 };
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast::error::RecvError, Mutex};
 use tracing::{error, warn};
 use uuid::Uuid;
 
@@ -72,7 +74,7 @@ use crate::{
     event::EventOrTransactionId,
     helpers::unwrap_or_clone_arc,
     ruma::{
-        AssetType, AudioInfo, FileInfo, FormattedBody, ImageInfo, Mentions, PollKind,
+        AssetType, AudioInfo, FileInfo, FormattedBody, ImageInfo, MediaSource, Mentions, PollKind,
         ThumbnailInfo, VideoInfo,
     },
     runtime::get_runtime_handle,
@@ -118,7 +120,7 @@ impl Timeline {
             params.formatted_caption.map(Into::into),
         );
 
-        let attachment_config = AttachmentConfig::new()
+        let mut attachment_config = AttachmentConfig::new()
             .thumbnail(thumbnail)
             .info(attachment_info)
             .caption(params.caption)
@@ -126,6 +128,10 @@ impl Timeline {
             .mentions(params.mentions.map(Into::into))
             .reply(params.reply_params.map(|p| p.try_into()).transpose()?);
 
+        if let Some(processing) = params.processing {
+            attachment_config = attachment_config.processing(processing.into());
+        }
+
         let handle = SendAttachmentJoinHandle::new(get_runtime_handle().spawn(async move {
             let mut request =
                 self.inner.send_attachment(params.source, mime_type, attachment_config);
@@ -212,6 +218,50 @@ pub struct UploadParameters {
     ///
     /// Watching progress only works with the synchronous method, at the moment.
     use_send_queue: bool,
+    /// Optional privacy-preserving processing to apply to the media before
+    /// uploading it.
+    ///
+    /// Only applies to images.
+    #[uniffi(default = None)]
+    processing: Option<AttachmentProcessing>,
+}
+
+/// Processing to apply to an image before uploading it.
+#[derive(uniffi::Record)]
+pub struct AttachmentProcessing {
+    /// Whether to strip the metadata of the image, like its location.
+    strip_metadata: bool,
+    /// The maximum width and height of the image, in pixels; larger images
+    /// are downscaled to fit.
+    max_dimension: Option<u32>,
+    /// The format to transcode the image to, if any.
+    output_format: Option<ImageOutputFormat>,
+}
+
+impl From<AttachmentProcessing> for SdkAttachmentProcessing {
+    fn from(value: AttachmentProcessing) -> Self {
+        Self {
+            strip_metadata: value.strip_metadata,
+            max_dimension: value.max_dimension,
+            output_format: value.output_format.map(Into::into),
+        }
+    }
+}
+
+/// The format an image can be transcoded to before uploading it.
+#[derive(uniffi::Enum)]
+pub enum ImageOutputFormat {
+    Jpeg,
+    Png,
+}
+
+impl From<ImageOutputFormat> for SdkImageOutputFormat {
+    fn from(value: ImageOutputFormat) -> Self {
+        match value {
+            ImageOutputFormat::Jpeg => Self::Jpeg,
+            ImageOutputFormat::Png => Self::Png,
+        }
+    }
 }
 
 /// A source for uploading a file
@@ -307,6 +357,26 @@ impl Timeline {
         self.inner.fetch_members().await
     }
 
+    /// Resolve the profile of a single sender on demand, whatever the profile
+    /// resolution of the timeline, and use it for all the items they sent.
+    pub async fn fetch_sender_profile(
+        &self,
+        user_id: String,
+    ) -> Result<ProfileDetails, ClientError> {
+        let user_id = UserId::parse(user_id)?;
+        let profile = self.inner.fetch_sender_profile(&user_id).await;
+        Ok(profile.map_or(TimelineDetails::Unavailable, TimelineDetails::Ready).into())
+    }
+
+    /// The current time, corrected from the estimated clock skew with the
+    /// homeserver if enabled in the `TimelineConfiguration`.
+    ///
+    /// Use it rather than the local clock to compute relative times like "X
+    /// minutes ago" for the items of this timeline.
+    pub async fn now(&self) -> Timestamp {
+        self.inner.now().await.into()
+    }
+
     pub async fn subscribe_to_back_pagination_status(
         &self,
         listener: Box<dyn PaginationStatusListener>,
@@ -331,6 +401,47 @@ impl Timeline {
         }))))
     }
 
+    /// Subscribe to the media of the events redacted in the room of this
+    /// timeline.
+    ///
+    /// The media of a redacted event is removed from the media cache of the
+    /// SDK automatically, the listener is called so that any copy of the media
+    /// kept outside of the SDK can be removed too.
+    pub async fn subscribe_to_redacted_media(
+        &self,
+        listener: Box<dyn RedactedMediaListener>,
+    ) -> Arc<TaskHandle> {
+        let room = self.inner.room();
+        let room_id = room.room_id().to_owned();
+        let mut receiver = room.client().event_cache().subscribe_to_redacted_media();
+
+        Arc::new(TaskHandle::new(get_runtime_handle().spawn(async move {
+            loop {
+                let media = match receiver.recv().await {
+                    Ok(media) => media,
+                    Err(RecvError::Lagged(num_skipped)) => {
+                        warn!(num_skipped, "Lagged behind the redacted media");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                if media.room_id != room_id {
+                    continue;
+                }
+
+                let sources = media
+                    .sources
+                    .into_iter()
+                    .filter_map(|source| MediaSource::try_from(source).ok())
+                    .map(Arc::new)
+                    .collect();
+
+                listener.on_redacted_media(media.event_id.to_string(), sources);
+            }
+        })))
+    }
+
     /// Paginate backwards, whether we are in focused mode or in live mode.
     ///
     /// Returns whether we hit the start of the timeline or not.
@@ -386,6 +497,33 @@ impl Timeline {
         }
     }
 
+    /// Queues an event in the room's send queue, with custom keys added to its
+    /// content.
+    ///
+    /// This allows to tag the events sent by a specific client, e.g. a bridge
+    /// or a bot. The keys must be namespaced, like `com.example.tag`, and
+    /// can't be in the reserved `m.` namespace. The values are JSON strings.
+    ///
+    /// Returns an abort handle that allows to abort sending, if it hasn't
+    /// happened yet, and which gives the transaction ID of the local echo.
+    pub async fn send_with_custom_content(
+        &self,
+        msg: Arc<RoomMessageEventContentWithoutRelation>,
+        custom_content: HashMap<String, String>,
+    ) -> Result<Arc<SendHandle>, ClientError> {
+        let content: AnyMessageLikeEventContent = (*msg).to_owned().with_relation(None).into();
+        let event_type = content.event_type().to_string();
+        let content = content_with_custom_keys(&content, custom_content)?;
+
+        match self.inner.room().send_queue().send_raw(content, event_type).await {
+            Ok(handle) => Ok(Arc::new(SendHandle::new(handle))),
+            Err(err) => {
+                error!("error when sending a message with custom content: {err}");
+                Err(err.into())
+            }
+        }
+    }
+
     pub fn send_image(
         self: Arc<Self>,
         params: UploadParameters,
@@ -748,20 +886,76 @@ impl Timeline {
     }
 }
 
+/// Add the given custom keys, whose values are JSON strings, to the given event
+/// content.
+///
+/// The keys must be namespaced, see [`is_namespaced_key`], and must not be in
+/// the content already.
+fn content_with_custom_keys(
+    content: &AnyMessageLikeEventContent,
+    custom_content: HashMap<String, String>,
+) -> Result<Raw<AnyMessageLikeEventContent>, ClientError> {
+    let serde_json::Value::Object(mut json) = serde_json::to_value(content)? else {
+        return Err(ClientError::from_str("the content isn't a JSON object", None));
+    };
+
+    for (key, value) in custom_content {
+        if !is_namespaced_key(&key) {
+            return Err(ClientError::from_str(
+                format!("the custom key `{key}` isn't namespaced"),
+                None,
+            ));
+        }
+
+        if json.contains_key(&key) {
+            return Err(ClientError::from_str(
+                format!("the custom key `{key}` is already in the content"),
+                None,
+            ));
+        }
+
+        let value = serde_json::from_str(&value).map_err(|e| ClientError::Generic {
+            msg: format!("Failed to parse the JSON value of `{key}`: {e}"),
+            details: Some(format!("{e:?}")),
+        })?;
+        json.insert(key, value);
+    }
+
+    Ok(Raw::new(&json)?.cast())
+}
+
+/// Whether the given key of an event content is namespaced, i.e. uses the
+/// Java package naming convention, outside of the reserved `m.` namespace.
+fn is_namespaced_key(key: &str) -> bool {
+    !key.starts_with("m.")
+        && key.split('.').count() > 1
+        && key.split('.').all(|part| !part.is_empty())
+}
+
 /// A handle to perform actions onto a local echo.
 #[derive(uniffi::Object)]
 pub struct SendHandle {
     inner: Mutex<Option<matrix_sdk::send_queue::SendHandle>>,
+    transaction_id: String,
 }
 
 impl SendHandle {
     fn new(handle: matrix_sdk::send_queue::SendHandle) -> Self {
-        Self { inner: Mutex::new(Some(handle)) }
+        let transaction_id = handle.transaction_id().to_string();
+        Self { inner: Mutex::new(Some(handle)), transaction_id }
     }
 }
 
 #[matrix_sdk_ffi_macros::export]
 impl SendHandle {
+    /// The transaction ID of the local echo.
+    ///
+    /// Use it to correlate the local echo with the event ID it gets once it's
+    /// been sent, see `Room::subscribe_to_sent_events`.
+    pub fn transaction_id(&self) -> String {
+        self.transaction_id.clone()
+    }
+
     /// Try to abort the sending of the current event.
     ///
     /// If this returns `true`, then the sending could be aborted, because the
@@ -827,6 +1021,12 @@ pub trait PaginationStatusListener: SyncOutsideWasm + SendOutsideWasm {
     fn on_update(&self, status: RoomPaginationStatus);
 }
 
+#[matrix_sdk_ffi_macros::export(callback_interface)]
+pub trait RedactedMediaListener: SyncOutsideWasm + SendOutsideWasm {
+    /// Called when an event with the given media has been redacted.
+    fn on_redacted_media(&self, event_id: String, sources: Vec<Arc<MediaSource>>);
+}
+
 #[derive(Clone, uniffi::Object)]
 pub enum TimelineDiff {
     Append { values: Vec<Arc<TimelineItem>> },
@@ -1002,6 +1202,7 @@ impl TimelineItem {
             VItem::DateDivider(ts) => Some(VirtualTimelineItem::DateDivider { ts: (*ts).into() }),
             VItem::ReadMarker => Some(VirtualTimelineItem::ReadMarker),
             VItem::TimelineStart => Some(VirtualTimelineItem::TimelineStart),
+            VItem::HistoryUnavailable => Some(VirtualTimelineItem::HistoryUnavailable),
         }
     }
 
@@ -1271,6 +1472,10 @@ pub enum VirtualTimelineItem {
 
     /// The timeline start, that is, the *oldest* event in time for that room.
     TimelineStart,
+
+    /// The start of the readable history, that is, the server refused to
+    /// return older events for that room.
+    HistoryUnavailable,
 }
 
 /// A [`TimelineItem`](super::TimelineItem) that doesn't correspond to an event.
@@ -1619,3 +1824,72 @@ mod galleries {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use matrix_sdk::ruma::events::{
+        room::message::RoomMessageEventContent, AnyMessageLikeEventContent,
+    };
+    use serde_json::json;
+
+    use super::{content_with_custom_keys, is_namespaced_key};
+
+    #[test]
+    fn test_is_namespaced_key() {
+        assert!(is_namespaced_key("com.example.tag"));
+        assert!(is_namespaced_key("io.element.x"));
+
+        // The `m.` namespace is reserved.
+        assert!(!is_namespaced_key("m.relates_to"));
+        assert!(!is_namespaced_key("m.example"));
+
+        // The key must have several non-empty parts.
+        assert!(!is_namespaced_key("tag"));
+        assert!(!is_namespaced_key(""));
+        assert!(!is_namespaced_key("com..tag"));
+        assert!(!is_namespaced_key(".com.example"));
+        assert!(!is_namespaced_key("com.example."));
+    }
+
+    #[test]
+    fn test_content_with_custom_keys() {
+        let content =
+            AnyMessageLikeEventContent::RoomMessage(RoomMessageEventContent::text_plain("hello"));
+
+        let custom_content = HashMap::from([
+            ("com.example.tag".to_owned(), r#""bot""#.to_owned()),
+            ("com.example.data".to_owned(), r#"{ "answer": 42 }"#.to_owned()),
+        ]);
+
+        let raw = content_with_custom_keys(&content, custom_content).unwrap();
+        assert_eq!(
+            raw.deserialize_as::<serde_json::Value>().unwrap(),
+            json!({
+                "msgtype": "m.text",
+                "body": "hello",
+                "com.example.tag": "bot",
+                "com.example.data": { "answer": 42 },
+            })
+        );
+    }
+
+    #[test]
+    fn test_content_with_invalid_custom_keys() {
+        let content =
+            AnyMessageLikeEventContent::RoomMessage(RoomMessageEventContent::text_plain("hello"));
+
+        // A key which isn't namespaced.
+        let custom_content = HashMap::from([("tag".to_owned(), r#""bot""#.to_owned())]);
+        content_with_custom_keys(&content, custom_content).unwrap_err();
+
+        // A key in the reserved namespace.
+        let custom_content = HashMap::from([("m.mentions".to_owned(), "{}".to_owned())]);
+        content_with_custom_keys(&content, custom_content).unwrap_err();
+
+        // A value which isn't valid JSON.
+        let custom_content = HashMap::from([("com.example.tag".to_owned(), "bot".to_owned())]);
+        content_with_custom_keys(&content, custom_content).unwrap_err();
+    }
+}
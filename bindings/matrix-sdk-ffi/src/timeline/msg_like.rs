@@ -73,6 +73,39 @@ pub struct MessageContent {
     pub body: String,
     pub is_edited: bool,
     pub mentions: Option<Mentions>,
+    /// The visual effect to render along with this message, if any.
+    pub effect: Option<MessageEffect>,
+    /// The fields of the content which aren't part of the `msgtype`, nor
+    /// handled otherwise by the SDK, as a serialized JSON object.
+    ///
+    /// It's `{}` if there are no such fields.
+    pub additional_content: String,
+}
+
+/// A visual effect to render along with a message, like confetti.
+#[derive(Clone, uniffi::Enum)]
+pub enum MessageEffect {
+    Confetti,
+    Fireworks,
+    Hearts,
+    Rainfall,
+    Snowfall,
+    SpaceInvaders,
+}
+
+impl From<matrix_sdk_ui::timeline::MessageEffect> for MessageEffect {
+    fn from(value: matrix_sdk_ui::timeline::MessageEffect) -> Self {
+        use matrix_sdk_ui::timeline::MessageEffect as Effect;
+
+        match value {
+            Effect::Confetti => Self::Confetti,
+            Effect::Fireworks => Self::Fireworks,
+            Effect::Hearts => Self::Hearts,
+            Effect::Rainfall => Self::Rainfall,
+            Effect::Snowfall => Self::Snowfall,
+            Effect::SpaceInvaders => Self::SpaceInvaders,
+        }
+    }
 }
 
 impl TryFrom<matrix_sdk_ui::timeline::MsgLikeContent> for MsgLikeContent {
@@ -106,6 +139,8 @@ impl TryFrom<matrix_sdk_ui::timeline::MsgLikeContent> for MsgLikeContent {
             Kind::Message(message) => {
                 let msg_type = TryInto::<MessageType>::try_into(message.msgtype().clone())
                     .map_err(|e| (e, message.msgtype().msgtype().to_owned()))?;
+                let additional_content = serde_json::to_string(message.additional_content())
+                    .map_err(|e| (e.into(), message.msgtype().msgtype().to_owned()))?;
 
                 Self {
                     kind: MsgLikeKind::Message {
@@ -114,6 +149,8 @@ impl TryFrom<matrix_sdk_ui::timeline::MsgLikeContent> for MsgLikeContent {
                             body: message.body().to_owned(),
                             is_edited: message.is_edited(),
                             mentions: message.mentions().cloned().map(|m| m.into()),
+                            effect: message.effect().map(Into::into),
+                            additional_content,
                         },
                     },
                     reactions,
@@ -242,6 +279,8 @@ pub struct PollAnswer {
 pub struct ThreadSummary {
     pub latest_event: EmbeddedEventDetails,
     pub num_replies: u32,
+    pub current_user_participated: bool,
+    pub is_unread: bool,
 }
 
 #[matrix_sdk_ffi_macros::export]
@@ -253,6 +292,17 @@ impl ThreadSummary {
     pub fn num_replies(&self) -> u64 {
         self.num_replies as u64
     }
+
+    /// Whether the current user has sent an event in the thread.
+    pub fn current_user_participated(&self) -> bool {
+        self.current_user_participated
+    }
+
+    /// Whether the latest event in the thread hasn't been read by the current
+    /// user yet.
+    pub fn is_unread(&self) -> bool {
+        self.is_unread
+    }
 }
 
 impl From<matrix_sdk_ui::timeline::ThreadSummary> for ThreadSummary {
@@ -260,6 +310,8 @@ impl From<matrix_sdk_ui::timeline::ThreadSummary> for ThreadSummary {
         Self {
             latest_event: EmbeddedEventDetails::from(value.latest_event),
             num_replies: value.num_replies,
+            current_user_participated: value.current_user_participated,
+            is_unread: value.is_unread,
         }
     }
 }
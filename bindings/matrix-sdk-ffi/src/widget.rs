@@ -6,7 +6,7 @@ use matrix_sdk_common::{SendOutsideWasm, SyncOutsideWasm};
 use ruma::events::MessageLikeEventType;
 use tracing::error;
 
-use crate::{room::Room, runtime::get_runtime_handle};
+use crate::{room::Room, ruma::MatrixEntity, runtime::get_runtime_handle};
 
 #[derive(uniffi::Record)]
 pub struct WidgetDriverAndHandle {
@@ -225,6 +225,13 @@ pub fn get_element_call_required_permissions(
         requires_client: true,
         update_delayed_event: true,
         send_delayed_event: true,
+        upload_file: false,
+        download_file: false,
+        read_receipts: false,
+        fully_read_marker: false,
+        typing: false,
+        send_typing: false,
+        navigate: false,
     }
 }
 
@@ -271,6 +278,16 @@ impl WidgetDriverHandle {
     pub async fn send(&self, msg: String) -> bool {
         self.0.send(msg).await
     }
+
+    /// Receive a request from the widget to navigate to a Matrix entity.
+    ///
+    /// The URI sent by the widget has already been validated, and the widget
+    /// has been granted the navigation capability.
+    ///
+    /// Returns `None` if the widget driver is no longer running.
+    pub async fn recv_navigation_request(&self) -> Option<MatrixEntity> {
+        self.0.recv_navigation_request().await.map(Into::into)
+    }
 }
 
 /// Capabilities that a widget can request from a client.
@@ -290,6 +307,25 @@ pub struct WidgetCapabilities {
     pub update_delayed_event: bool,
     /// This allows the widget to send events with a delay.
     pub send_delayed_event: bool,
+    /// This allows the widget to upload media, and to get the media
+    /// configuration of the homeserver.
+    pub upload_file: bool,
+    /// This allows the widget to download media.
+    pub download_file: bool,
+    /// This allows the widget to observe the public read receipts of the room
+    /// members.
+    pub read_receipts: bool,
+    /// This allows the widget to observe the position of the fully-read
+    /// marker of the user in the room.
+    pub fully_read_marker: bool,
+    /// This allows the widget to observe which room members are typing.
+    pub typing: bool,
+    /// This allows the widget to send typing notifications on behalf of the
+    /// user.
+    pub send_typing: bool,
+    /// This allows the widget to ask the client to navigate to a Matrix
+    /// entity, like a room or a user.
+    pub navigate: bool,
 }
 
 impl From<WidgetCapabilities> for matrix_sdk::widget::Capabilities {
@@ -300,6 +336,13 @@ impl From<WidgetCapabilities> for matrix_sdk::widget::Capabilities {
             requires_client: value.requires_client,
             update_delayed_event: value.update_delayed_event,
             send_delayed_event: value.send_delayed_event,
+            upload_file: value.upload_file,
+            download_file: value.download_file,
+            read_receipts: value.read_receipts,
+            fully_read_marker: value.fully_read_marker,
+            typing: value.typing,
+            send_typing: value.send_typing,
+            navigate: value.navigate,
         }
     }
 }
@@ -312,6 +355,13 @@ impl From<matrix_sdk::widget::Capabilities> for WidgetCapabilities {
             requires_client: value.requires_client,
             update_delayed_event: value.update_delayed_event,
             send_delayed_event: value.send_delayed_event,
+            upload_file: value.upload_file,
+            download_file: value.download_file,
+            read_receipts: value.read_receipts,
+            fully_read_marker: value.fully_read_marker,
+            typing: value.typing,
+            send_typing: value.send_typing,
+            navigate: value.navigate,
         }
     }
 }
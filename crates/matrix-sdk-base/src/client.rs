@@ -894,10 +894,23 @@ impl BaseClient {
 
                 let members = self.state_store.get_user_ids(room_id, filter).await?;
 
+                // Rooms marked as only sharing their keys with trusted devices override the
+                // strategy of the client, unless some untrusted devices have been confirmed for
+                // this room: they must receive the key too, and it's up to the caller to make
+                // sure that no other untrusted device would.
+                let sharing_strategy =
+                    if o.store().get_room_only_allow_trusted_devices(room_id).await?
+                        && o.store().get_room_confirmed_untrusted_devices(room_id).await?.is_empty()
+                    {
+                        CollectStrategy::OnlyTrustedDevices
+                    } else {
+                        self.room_key_recipient_strategy.clone()
+                    };
+
                 let settings = EncryptionSettings::new(
                     room_encryption_event,
                     history_visibility,
-                    self.room_key_recipient_strategy.clone(),
+                    sharing_strategy,
                 );
 
                 Ok(o.share_room_key(room_id, members.iter().map(Deref::deref), settings).await?)
@@ -932,6 +945,37 @@ impl BaseClient {
         Ok(())
     }
 
+    /// Archive the room with the given room ID.
+    ///
+    /// The room will be dropped from the room list, but will be kept in the
+    /// store, so it can be restored with
+    /// [`BaseClient::restore_archived_room`]. The room stays archived across
+    /// restarts, i.e. it isn't loaded when the client starts.
+    ///
+    /// Returns `true` if the room was known by the client and has been
+    /// archived.
+    pub async fn archive_room(&self, room_id: &RoomId) -> Result<bool> {
+        let _sync_lock = self.sync_lock().lock().await;
+
+        Ok(self.state_store.archive_room(room_id).await?)
+    }
+
+    /// Restore a room which has been archived with
+    /// [`BaseClient::archive_room`] from the store.
+    ///
+    /// If the room isn't archived, it's returned as is. Returns `None` if the
+    /// room is unknown.
+    pub async fn restore_archived_room(&self, room_id: &RoomId) -> Result<Option<Room>> {
+        let user_id = self.session_meta().ok_or(Error::AuthenticationRequired)?.user_id.clone();
+
+        let _sync_lock = self.sync_lock().lock().await;
+
+        Ok(self
+            .state_store
+            .restore_archived_room(&user_id, room_id, &self.room_info_notable_update_sender)
+            .await?)
+    }
+
     /// Get the olm machine.
     #[cfg(feature = "e2e-encryption")]
     pub async fn olm_machine(&self) -> RwLockReadGuard<'_, Option<OlmMachine>> {
@@ -1062,6 +1106,7 @@ impl From<&v5::Request> for RequestedRequiredStates {
 mod tests {
     use std::collections::HashMap;
 
+    use assert_matches::assert_matches;
     use assert_matches2::assert_let;
     use futures_util::FutureExt as _;
     use matrix_sdk_test::{
@@ -1080,6 +1125,7 @@ mod tests {
 
     use super::{BaseClient, RequestedRequiredStates};
     use crate::{
+        error::Error,
         store::{RoomLoadSettings, StateStoreExt, StoreConfig},
         test_utils::logged_in_base_client,
         RoomDisplayName, RoomState, SessionMeta,
@@ -1637,6 +1683,17 @@ mod tests {
         assert!(ignored.is_empty());
     }
 
+    #[async_test]
+    async fn test_restore_archived_room_requires_authentication() {
+        let client =
+            BaseClient::new(StoreConfig::new("cross-process-store-locks-holder-name".to_owned()));
+
+        assert_matches!(
+            client.restore_archived_room(room_id!("!r0:localhost")).await,
+            Err(Error::AuthenticationRequired)
+        );
+    }
+
     #[async_test]
     async fn test_is_user_ignored() {
         let ignored_user_id = user_id!("@alice:example.org");
@@ -80,4 +80,8 @@ pub enum Error {
     /// There was a [`serde_json`] deserialization error.
     #[error(transparent)]
     DeserializationError(#[from] serde_json::error::Error),
+
+    /// The operation requires the client to be logged in.
+    #[error("The client must be logged in to perform this operation")]
+    AuthenticationRequired,
 }
@@ -14,7 +14,7 @@
 
 //! Trait and macro of integration tests for `EventCacheStore` implementations.
 
-use std::sync::Arc;
+use std::{collections::BTreeSet, sync::Arc};
 
 use assert_matches::assert_matches;
 use matrix_sdk_common::{
@@ -32,7 +32,13 @@ use ruma::{
     event_id,
     events::{
         relation::RelationType,
-        room::{message::RoomMessageEventContentWithoutRelation, MediaSource},
+        room::{
+            encrypted::{
+                EncryptedEventScheme, MegolmV1AesSha2ContentInit, RoomEncryptedEventContent,
+            },
+            message::RoomMessageEventContentWithoutRelation,
+            MediaSource,
+        },
     },
     mxc_uri,
     push::Action,
@@ -145,14 +151,23 @@ pub trait EventCacheStoreIntegrationTests {
     /// Test that filtering duplicated events works as expected.
     async fn test_filter_duplicated_events(&self);
 
+    /// Test that the position of an event can be found or not.
+    async fn test_position_of_event(&self);
+
     /// Test that an event can be found or not.
     async fn test_find_event(&self);
 
     /// Test that finding event relations works as expected.
     async fn test_find_event_relations(&self);
 
+    /// Test that the events which couldn't be decrypted are found.
+    async fn test_find_utd_events(&self);
+
     /// Test that saving an event works as expected.
     async fn test_save_event(&self);
+
+    /// Test that removing events works as expected.
+    async fn test_remove_events(&self);
 }
 
 impl EventCacheStoreIntegrationTests for DynEventCacheStore {
@@ -921,6 +936,71 @@ impl EventCacheStoreIntegrationTests for DynEventCacheStore {
         );
     }
 
+    async fn test_position_of_event(&self) {
+        let room_id = room_id!("!r0:matrix.org");
+        let linked_chunk_id = LinkedChunkId::Room(room_id);
+        let another_room_id = room_id!("!r1:matrix.org");
+        let another_linked_chunk_id = LinkedChunkId::Room(another_room_id);
+        let event = |msg: &str| make_test_event(room_id, msg);
+
+        let event_comte = event("comté");
+        let event_brigand = event("brigand du jorat");
+        let event_morbier = event("morbier");
+        let event_tome = event("tome");
+
+        self.handle_linked_chunk_updates(
+            linked_chunk_id,
+            vec![
+                Update::NewItemsChunk { previous: None, new: CId::new(0), next: None },
+                Update::PushItems {
+                    at: Position::new(CId::new(0), 0),
+                    items: vec![event_comte.clone(), event_brigand.clone()],
+                },
+                Update::NewItemsChunk { previous: Some(CId::new(0)), new: CId::new(1), next: None },
+                Update::PushItems {
+                    at: Position::new(CId::new(1), 0),
+                    items: vec![event_morbier.clone()],
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+        self.handle_linked_chunk_updates(
+            another_linked_chunk_id,
+            vec![
+                Update::NewItemsChunk { previous: None, new: CId::new(0), next: None },
+                Update::PushItems {
+                    at: Position::new(CId::new(0), 0),
+                    items: vec![event_tome.clone()],
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+        let position_of = async |event: &TimelineEvent| {
+            self.position_of_event(linked_chunk_id, &event.event_id().unwrap()).await.unwrap()
+        };
+
+        assert_eq!(position_of(&event_comte).await, Some(Position::new(CId::new(0), 0)));
+        assert_eq!(position_of(&event_brigand).await, Some(Position::new(CId::new(0), 1)));
+        assert_eq!(position_of(&event_morbier).await, Some(Position::new(CId::new(1), 0)));
+        // The event is in another linked chunk.
+        assert_eq!(position_of(&event_tome).await, None);
+
+        // Removing an event shifts the position of the next ones.
+        self.handle_linked_chunk_updates(
+            linked_chunk_id,
+            vec![Update::RemoveItem { at: Position::new(CId::new(0), 0) }],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(position_of(&event_comte).await, None);
+        assert_eq!(position_of(&event_brigand).await, Some(Position::new(CId::new(0), 0)));
+    }
+
     async fn test_find_event(&self) {
         let room_id = room_id!("!r0:matrix.org");
         let another_room_id = room_id!("!r1:matrix.org");
@@ -1057,6 +1137,100 @@ impl EventCacheStoreIntegrationTests for DynEventCacheStore {
         assert!(relations.is_empty());
     }
 
+    async fn test_find_utd_events(&self) {
+        let room_id = room_id!("!r0:matrix.org");
+        let linked_chunk_id = LinkedChunkId::Room(room_id);
+        let f = EventFactory::new().room(room_id).sender(*ALICE);
+
+        let utd = |event_id: &EventId, session_id: &str| {
+            f.event(RoomEncryptedEventContent::new(
+                EncryptedEventScheme::MegolmV1AesSha2(
+                    MegolmV1AesSha2ContentInit {
+                        ciphertext: "ciphertext".to_owned(),
+                        sender_key: "sender_key".to_owned(),
+                        device_id: "DEVICEID".into(),
+                        session_id: session_id.to_owned(),
+                    }
+                    .into(),
+                ),
+                None,
+            ))
+            .event_id(event_id)
+            .into_utd_sync_timeline_event()
+        };
+
+        let utd_0_id = event_id!("$utd0");
+        let utd_1_id = event_id!("$utd1");
+        let utd_2_id = event_id!("$utd2");
+
+        self.handle_linked_chunk_updates(
+            linked_chunk_id,
+            vec![
+                Update::NewItemsChunk { previous: None, new: CId::new(0), next: None },
+                Update::PushItems {
+                    at: Position::new(CId::new(0), 0),
+                    items: vec![utd(utd_0_id, "session_a"), make_test_event(room_id, "comté")],
+                },
+                Update::NewGapChunk {
+                    previous: Some(CId::new(0)),
+                    new: CId::new(1),
+                    next: None,
+                    gap: Gap { prev_token: "gap".to_owned() },
+                },
+                Update::NewItemsChunk { previous: Some(CId::new(1)), new: CId::new(2), next: None },
+                Update::PushItems {
+                    at: Position::new(CId::new(2), 0),
+                    items: vec![utd(utd_1_id, "session_b"), utd(utd_2_id, "session_a")],
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+        let find_utd_events = async |session_ids: Option<&[&str]>| {
+            let session_ids = session_ids
+                .map(|ids| ids.iter().map(|id| (*id).to_owned()).collect::<BTreeSet<_>>());
+
+            self.find_utd_events(linked_chunk_id, session_ids.as_ref())
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|(position, event)| (position, event.event_id().unwrap()))
+                .collect::<Vec<_>>()
+        };
+
+        // Only the UTDs of the given sessions are returned, with their position.
+        assert_eq!(
+            find_utd_events(Some(&["session_a"])).await,
+            [
+                (Position::new(CId::new(0), 0), utd_0_id.to_owned()),
+                (Position::new(CId::new(2), 1), utd_2_id.to_owned()),
+            ]
+        );
+        assert_eq!(
+            find_utd_events(Some(&["session_b", "session_c"])).await,
+            [(Position::new(CId::new(2), 0), utd_1_id.to_owned())]
+        );
+        assert!(find_utd_events(Some(&["session_c"])).await.is_empty());
+
+        // All the UTDs are returned without sessions.
+        assert_eq!(
+            find_utd_events(None).await,
+            [
+                (Position::new(CId::new(0), 0), utd_0_id.to_owned()),
+                (Position::new(CId::new(2), 0), utd_1_id.to_owned()),
+                (Position::new(CId::new(2), 1), utd_2_id.to_owned()),
+            ]
+        );
+
+        // The UTDs of other linked chunks aren't returned.
+        assert!(self
+            .find_utd_events(LinkedChunkId::Room(room_id!("!r1:matrix.org")), None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
     async fn test_save_event(&self) {
         let room_id = room_id!("!r0:matrix.org");
         let another_room_id = room_id!("!r1:matrix.org");
@@ -1098,6 +1272,43 @@ impl EventCacheStoreIntegrationTests for DynEventCacheStore {
             .expect("failed to query for finding an event")
             .is_none());
     }
+
+    async fn test_remove_events(&self) {
+        let room_id = room_id!("!r0:matrix.org");
+        let linked_chunk_id = LinkedChunkId::Room(room_id);
+
+        let event = |msg: &str| make_test_event(room_id, msg);
+        let event_comte = event("comté");
+        let event_gruyere = event("gruyère");
+        let comte_id = event_comte.event_id().unwrap();
+        let gruyere_id = event_gruyere.event_id().unwrap();
+
+        // One event is saved out-of-band, the other one is part of the linked chunk.
+        self.save_event(room_id, event_comte).await.unwrap();
+        self.handle_linked_chunk_updates(
+            linked_chunk_id,
+            vec![
+                Update::NewItemsChunk { previous: None, new: CId::new(0), next: None },
+                Update::PushItems { at: Position::new(CId::new(0), 0), items: vec![event_gruyere] },
+            ],
+        )
+        .await
+        .unwrap();
+
+        self.remove_events(room_id, vec![comte_id.clone(), gruyere_id.clone()]).await.unwrap();
+
+        // The event which is part of the linked chunk is kept.
+        assert!(self.find_event(room_id, &comte_id).await.unwrap().is_none());
+        assert!(self.find_event(room_id, &gruyere_id).await.unwrap().is_some());
+
+        // Once its chunk has been removed, it can be removed too.
+        self.handle_linked_chunk_updates(linked_chunk_id, vec![Update::RemoveChunk(CId::new(0))])
+            .await
+            .unwrap();
+        self.remove_events(room_id, vec![gruyere_id.clone()]).await.unwrap();
+
+        assert!(self.find_event(room_id, &gruyere_id).await.unwrap().is_none());
+    }
 }
 
 /// Macro building to allow your `EventCacheStore` implementation to run the
@@ -1202,6 +1413,13 @@ macro_rules! event_cache_store_integration_tests {
                 event_cache_store.test_filter_duplicated_events().await;
             }
 
+            #[async_test]
+            async fn test_position_of_event() {
+                let event_cache_store =
+                    get_event_cache_store().await.unwrap().into_event_cache_store();
+                event_cache_store.test_position_of_event().await;
+            }
+
             #[async_test]
             async fn test_find_event() {
                 let event_cache_store =
@@ -1216,12 +1434,26 @@ macro_rules! event_cache_store_integration_tests {
                 event_cache_store.test_find_event_relations().await;
             }
 
+            #[async_test]
+            async fn test_find_utd_events() {
+                let event_cache_store =
+                    get_event_cache_store().await.unwrap().into_event_cache_store();
+                event_cache_store.test_find_utd_events().await;
+            }
+
             #[async_test]
             async fn test_save_event() {
                 let event_cache_store =
                     get_event_cache_store().await.unwrap().into_event_cache_store();
                 event_cache_store.test_save_event().await;
             }
+
+            #[async_test]
+            async fn test_remove_events() {
+                let event_cache_store =
+                    get_event_cache_store().await.unwrap().into_event_cache_store();
+                event_cache_store.test_remove_events().await;
+            }
         }
     };
 }
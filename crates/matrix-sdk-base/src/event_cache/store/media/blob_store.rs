@@ -0,0 +1,843 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Storage of the content of the media outside of the event cache store.
+
+use std::{collections::BTreeSet, fmt, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use matrix_sdk_common::{
+    linked_chunk::{
+        ChunkIdentifier, ChunkIdentifierGenerator, ChunkMetadata, LinkedChunkId, Position,
+        RawChunk, Update,
+    },
+    AsyncTraitDeps,
+};
+use ruma::{
+    events::{relation::RelationType, room::MediaSource},
+    owned_mxc_uri,
+    time::SystemTime,
+    EventId, MxcUri, OwnedEventId, OwnedMxcUri, RoomId, TransactionId,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard, OnceCell};
+use tracing::{trace, warn};
+
+use super::{EventCacheStoreMedia, IgnoreMediaRetentionPolicy, MediaRetentionPolicy, MediaService};
+use crate::{
+    event_cache::{
+        store::{DynEventCacheStore, EventCacheStore, EventCacheStoreError, IntoEventCacheStore},
+        Event, Gap,
+    },
+    media::{MediaFormat, MediaRequestParameters, UniqueKey},
+};
+
+/// A storage backend for the content of the media, i.e. their blobs.
+///
+/// This allows platforms to store the media in locations managed by the
+/// operating system, like a file provider on iOS or the Storage Access
+/// Framework on Android, while the event cache store keeps the metadata of
+/// the media, see [`MediaBlobEventCacheStore`].
+///
+/// The keys are random alphanumeric strings generated by the SDK, so they can
+/// be used as file names.
+#[cfg_attr(target_family = "wasm", async_trait(?Send))]
+#[cfg_attr(not(target_family = "wasm"), async_trait)]
+pub trait MediaBlobStore: AsyncTraitDeps {
+    /// Store a blob with the given key, replacing any blob with the same key.
+    async fn put_blob(&self, key: &str, content: Vec<u8>) -> Result<(), EventCacheStoreError>;
+
+    /// Get the blob with the given key, if it exists.
+    async fn get_blob(&self, key: &str) -> Result<Option<Vec<u8>>, EventCacheStoreError>;
+
+    /// Remove the blob with the given key.
+    ///
+    /// This should not raise an error if there is no blob with this key.
+    async fn remove_blob(&self, key: &str) -> Result<(), EventCacheStoreError>;
+}
+
+/// An [`EventCacheStore`] that stores the content of the media in a
+/// [`MediaBlobStore`], and delegates everything else to another event cache
+/// store.
+///
+/// This store keeps its own index of the media: for each media, it records the
+/// key of its blob, its size, its last access time and whether the
+/// [`MediaRetentionPolicy`] must be ignored for it. This index is what the
+/// policy is applied to, so the blobs are removed along with the media that
+/// are cleaned up, and their size counts towards the maximum cache size. It's
+/// persisted in the inner event cache store.
+pub struct MediaBlobEventCacheStore {
+    inner: Arc<DynEventCacheStore>,
+    media: BlobMedia,
+    media_service: MediaService,
+
+    /// Whether the state of the [`MediaService`] was restored from the index
+    /// of the media.
+    media_service_restored: OnceCell<()>,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for MediaBlobEventCacheStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MediaBlobEventCacheStore")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MediaBlobEventCacheStore {
+    /// Create a new `MediaBlobEventCacheStore`, storing the metadata in the
+    /// given event cache store, and the content of the media in the given
+    /// blob store.
+    pub fn new(inner: impl IntoEventCacheStore, blobs: Arc<dyn MediaBlobStore>) -> Self {
+        let inner = inner.into_event_cache_store();
+
+        Self {
+            media: BlobMedia { inner: inner.clone(), blobs, index: Default::default() },
+            inner,
+            media_service: MediaService::new(),
+            media_service_restored: OnceCell::new(),
+        }
+    }
+
+    /// Get the [`MediaService`], once its state has been restored from the
+    /// index of the media.
+    async fn media_service(&self) -> Result<&MediaService, EventCacheStoreError> {
+        self.media_service_restored
+            .get_or_try_init(|| async {
+                let index = self.media.lock_index().await?;
+                self.media_service.restore(index.policy, index.last_cleanup_time());
+                Ok::<_, EventCacheStoreError>(())
+            })
+            .await?;
+
+        Ok(&self.media_service)
+    }
+}
+
+/// The key of the index of the media, in the inner event cache store.
+fn media_index_request() -> MediaRequestParameters {
+    MediaRequestParameters {
+        source: MediaSource::Plain(owned_mxc_uri!("mxc://localhost/matrix-sdk-media-blob-index")),
+        format: MediaFormat::File,
+    }
+}
+
+/// The index of the media whose content is in a [`MediaBlobStore`].
+///
+/// The media are sorted by last access, the least recently accessed first.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MediaIndex {
+    /// The persisted media retention policy.
+    policy: Option<MediaRetentionPolicy>,
+
+    /// The time of the last media cache cleanup, since the Unix epoch.
+    last_cleanup_time: Option<Duration>,
+
+    /// The media in the cache.
+    media: Vec<MediaIndexEntry>,
+}
+
+impl MediaIndex {
+    fn last_cleanup_time(&self) -> Option<SystemTime> {
+        self.last_cleanup_time.map(|time| SystemTime::UNIX_EPOCH + time)
+    }
+
+    /// Remove the media matching the given predicate from the index, and
+    /// return them.
+    fn remove(&mut self, predicate: impl Fn(&MediaIndexEntry) -> bool) -> Vec<MediaIndexEntry> {
+        let (removed, kept): (Vec<_>, Vec<_>) = self.media.drain(..).partition(predicate);
+        self.media = kept;
+        removed
+    }
+}
+
+/// A media in a [`MediaIndex`].
+#[derive(Debug, Serialize, Deserialize)]
+struct MediaIndexEntry {
+    /// The URI of the media.
+    uri: OwnedMxcUri,
+
+    /// The unique key of the [`MediaRequestParameters`] of the media.
+    key: String,
+
+    /// The key of the blob of the media.
+    blob_key: String,
+
+    /// The size of the content of the media.
+    size: u64,
+
+    /// Whether the media retention policy must be ignored for this media.
+    ignore_policy: bool,
+
+    /// The time of the last access to the media, since the Unix epoch.
+    last_access: Duration,
+}
+
+impl MediaIndexEntry {
+    fn last_access(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + self.last_access
+    }
+}
+
+fn since_unix_epoch(time: SystemTime) -> Duration {
+    time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default()
+}
+
+/// The media cache of a [`MediaBlobEventCacheStore`].
+#[derive(Clone, Debug)]
+struct BlobMedia {
+    inner: Arc<DynEventCacheStore>,
+    blobs: Arc<dyn MediaBlobStore>,
+
+    /// The index of the media, loaded lazily from the inner store.
+    index: Arc<Mutex<Option<MediaIndex>>>,
+}
+
+impl BlobMedia {
+    /// Lock the index of the media, loading it first if needed.
+    async fn lock_index(&self) -> Result<MappedMutexGuard<'_, MediaIndex>, EventCacheStoreError> {
+        let mut index = self.index.lock().await;
+
+        if index.is_none() {
+            let loaded = match self.inner.get_media_content(&media_index_request()).await? {
+                Some(value) => serde_json::from_slice(&value).unwrap_or_else(|error| {
+                    warn!("Failed to deserialize the index of the media, resetting it: {error}");
+                    MediaIndex::default()
+                }),
+                None => MediaIndex::default(),
+            };
+
+            *index = Some(loaded);
+        }
+
+        Ok(MutexGuard::map(index, |index| index.as_mut().expect("the index was just loaded")))
+    }
+
+    /// Persist the index of the media.
+    async fn save_index(&self, index: &MediaIndex) -> Result<(), EventCacheStoreError> {
+        let value = serde_json::to_vec(index).expect("the index of the media can be serialized");
+        self.inner
+            .add_media_content(&media_index_request(), value, IgnoreMediaRetentionPolicy::Yes)
+            .await
+    }
+
+    /// Remove the blobs of the given media, which were removed from the index.
+    ///
+    /// If this fails, the media are put back in the index, so their blobs are
+    /// never leaked. The ones whose blob was removed are dropped from the index
+    /// the next time they're accessed.
+    async fn remove_blobs(
+        &self,
+        index: &mut MediaIndex,
+        removed: Vec<MediaIndexEntry>,
+    ) -> Result<(), EventCacheStoreError> {
+        let mut result = Ok(());
+
+        for entry in &removed {
+            result = self.blobs.remove_blob(&entry.blob_key).await;
+
+            if result.is_err() {
+                break;
+            }
+        }
+
+        if result.is_err() {
+            index.media.extend(removed);
+        }
+
+        result
+    }
+
+    /// Remove the media matching the given predicate, and their blobs.
+    async fn remove_media(
+        &self,
+        predicate: impl Fn(&MediaIndexEntry) -> bool,
+    ) -> Result<(), EventCacheStoreError> {
+        let mut index = self.lock_index().await?;
+
+        let removed = index.remove(predicate);
+        if removed.is_empty() {
+            return Ok(());
+        }
+
+        self.remove_blobs(&mut index, removed).await?;
+        self.save_index(&index).await
+    }
+
+    /// Get the content of the first media matching the given predicate, and
+    /// update its last access time.
+    ///
+    /// The new last access time is only persisted with the next change of the
+    /// index, to avoid rewriting it on every access.
+    async fn get_media(
+        &self,
+        predicate: impl Fn(&MediaIndexEntry) -> bool,
+        current_time: SystemTime,
+    ) -> Result<Option<Vec<u8>>, EventCacheStoreError> {
+        let mut index = self.lock_index().await?;
+
+        let Some(position) = index.media.iter().position(predicate) else {
+            return Ok(None);
+        };
+
+        let Some(content) = self.blobs.get_blob(&index.media[position].blob_key).await? else {
+            // The platform evicted the blob, treat it as a cache miss.
+            let entry = index.media.remove(position);
+            trace!(key = entry.blob_key, "The blob of a media is missing");
+            self.save_index(&index).await?;
+            return Ok(None);
+        };
+
+        // Keep the media sorted by last access.
+        let mut entry = index.media.remove(position);
+        entry.last_access = since_unix_epoch(current_time);
+        index.media.push(entry);
+
+        Ok(Some(content))
+    }
+}
+
+#[cfg_attr(target_family = "wasm", async_trait(?Send))]
+#[cfg_attr(not(target_family = "wasm"), async_trait)]
+impl EventCacheStoreMedia for BlobMedia {
+    type Error = EventCacheStoreError;
+
+    async fn media_retention_policy_inner(
+        &self,
+    ) -> Result<Option<MediaRetentionPolicy>, Self::Error> {
+        Ok(self.lock_index().await?.policy)
+    }
+
+    async fn set_media_retention_policy_inner(
+        &self,
+        policy: MediaRetentionPolicy,
+    ) -> Result<(), Self::Error> {
+        let mut index = self.lock_index().await?;
+        index.policy = Some(policy);
+        self.save_index(&index).await
+    }
+
+    async fn add_media_content_inner(
+        &self,
+        request: &MediaRequestParameters,
+        content: Vec<u8>,
+        current_time: SystemTime,
+        policy: MediaRetentionPolicy,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+    ) -> Result<(), Self::Error> {
+        let ignore_policy = ignore_policy.is_yes();
+        let size = content.len() as u64;
+
+        if !ignore_policy && policy.exceeds_max_file_size(size) {
+            // Do not store it.
+            return Ok(());
+        }
+
+        let mut index = self.lock_index().await?;
+        let key = request.unique_key();
+
+        // Reuse the key of the previous content of the media, if any, so its blob is
+        // replaced.
+        let blob_key = match index.media.iter().find(|entry| entry.key == key) {
+            Some(entry) => entry.blob_key.clone(),
+            None => TransactionId::new().to_string(),
+        };
+
+        self.blobs.put_blob(&blob_key, content).await?;
+
+        index.remove(|entry| entry.key == key);
+        index.media.push(MediaIndexEntry {
+            uri: request.uri().to_owned(),
+            key,
+            blob_key,
+            size,
+            ignore_policy,
+            last_access: since_unix_epoch(current_time),
+        });
+        self.save_index(&index).await
+    }
+
+    async fn set_ignore_media_retention_policy_inner(
+        &self,
+        request: &MediaRequestParameters,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+    ) -> Result<(), Self::Error> {
+        let mut index = self.lock_index().await?;
+        let key = request.unique_key();
+
+        let Some(entry) = index.media.iter_mut().find(|entry| entry.key == key) else {
+            return Ok(());
+        };
+
+        entry.ignore_policy = ignore_policy.is_yes();
+        self.save_index(&index).await
+    }
+
+    async fn get_media_content_inner(
+        &self,
+        request: &MediaRequestParameters,
+        current_time: SystemTime,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        let key = request.unique_key();
+        self.get_media(|entry| entry.key == key, current_time).await
+    }
+
+    async fn get_media_content_for_uri_inner(
+        &self,
+        uri: &MxcUri,
+        current_time: SystemTime,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.get_media(|entry| entry.uri == uri, current_time).await
+    }
+
+    async fn clean_up_media_cache_inner(
+        &self,
+        policy: MediaRetentionPolicy,
+        current_time: SystemTime,
+    ) -> Result<(), Self::Error> {
+        if !policy.has_limitations() {
+            // We can safely skip all the checks.
+            return Ok(());
+        }
+
+        let mut index = self.lock_index().await?;
+
+        // First, remove the media that exceed the max file size, and the expired media.
+        let mut removed = index.remove(|entry| {
+            !entry.ignore_policy
+                && (policy.exceeds_max_file_size(entry.size)
+                    || policy.has_content_expired(current_time, entry.last_access()))
+        });
+
+        // Then, if the cache size is too big, remove the least recently accessed media
+        // until it fits.
+        if let Some(max_cache_size) = policy.max_cache_size {
+            let mut cache_size = 0u64;
+            let mut exceeded = false;
+
+            // The media are sorted by last access, so start counting from the most recently
+            // accessed one.
+            let (mut kept, too_old): (Vec<_>, Vec<_>) =
+                index.media.drain(..).rev().partition(|entry| {
+                    if entry.ignore_policy {
+                        // Do not count it.
+                        return true;
+                    }
+
+                    if !exceeded {
+                        cache_size = cache_size.saturating_add(entry.size);
+                        exceeded = cache_size > max_cache_size;
+                    }
+
+                    !exceeded
+                });
+
+            kept.reverse();
+            index.media = kept;
+            removed.extend(too_old);
+        }
+
+        self.remove_blobs(&mut index, removed).await?;
+
+        index.last_cleanup_time = Some(since_unix_epoch(current_time));
+        self.save_index(&index).await
+    }
+
+    async fn last_media_cleanup_time_inner(&self) -> Result<Option<SystemTime>, Self::Error> {
+        Ok(self.lock_index().await?.last_cleanup_time())
+    }
+}
+
+#[cfg_attr(target_family = "wasm", async_trait(?Send))]
+#[cfg_attr(not(target_family = "wasm"), async_trait)]
+impl EventCacheStore for MediaBlobEventCacheStore {
+    type Error = EventCacheStoreError;
+
+    async fn try_take_leased_lock(
+        &self,
+        lease_duration_ms: u32,
+        key: &str,
+        holder: &str,
+    ) -> Result<bool, Self::Error> {
+        self.inner.try_take_leased_lock(lease_duration_ms, key, holder).await
+    }
+
+    async fn handle_linked_chunk_updates(
+        &self,
+        linked_chunk_id: LinkedChunkId<'_>,
+        updates: Vec<Update<Event, Gap>>,
+    ) -> Result<(), Self::Error> {
+        self.inner.handle_linked_chunk_updates(linked_chunk_id, updates).await
+    }
+
+    async fn load_all_chunks(
+        &self,
+        linked_chunk_id: LinkedChunkId<'_>,
+    ) -> Result<Vec<RawChunk<Event, Gap>>, Self::Error> {
+        self.inner.load_all_chunks(linked_chunk_id).await
+    }
+
+    async fn load_all_chunks_metadata(
+        &self,
+        linked_chunk_id: LinkedChunkId<'_>,
+    ) -> Result<Vec<ChunkMetadata>, Self::Error> {
+        self.inner.load_all_chunks_metadata(linked_chunk_id).await
+    }
+
+    async fn load_last_chunk(
+        &self,
+        linked_chunk_id: LinkedChunkId<'_>,
+    ) -> Result<(Option<RawChunk<Event, Gap>>, ChunkIdentifierGenerator), Self::Error> {
+        self.inner.load_last_chunk(linked_chunk_id).await
+    }
+
+    async fn load_previous_chunk(
+        &self,
+        linked_chunk_id: LinkedChunkId<'_>,
+        before_chunk_identifier: ChunkIdentifier,
+    ) -> Result<Option<RawChunk<Event, Gap>>, Self::Error> {
+        self.inner.load_previous_chunk(linked_chunk_id, before_chunk_identifier).await
+    }
+
+    async fn clear_all_linked_chunks(&self) -> Result<(), Self::Error> {
+        self.inner.clear_all_linked_chunks().await
+    }
+
+    async fn filter_duplicated_events(
+        &self,
+        linked_chunk_id: LinkedChunkId<'_>,
+        events: Vec<OwnedEventId>,
+    ) -> Result<Vec<(OwnedEventId, Position)>, Self::Error> {
+        self.inner.filter_duplicated_events(linked_chunk_id, events).await
+    }
+
+    async fn position_of_event(
+        &self,
+        linked_chunk_id: LinkedChunkId<'_>,
+        event_id: &EventId,
+    ) -> Result<Option<Position>, Self::Error> {
+        self.inner.position_of_event(linked_chunk_id, event_id).await
+    }
+
+    async fn find_event(
+        &self,
+        room_id: &RoomId,
+        event_id: &EventId,
+    ) -> Result<Option<Event>, Self::Error> {
+        self.inner.find_event(room_id, event_id).await
+    }
+
+    async fn find_event_relations(
+        &self,
+        room_id: &RoomId,
+        event_id: &EventId,
+        filter: Option<&[RelationType]>,
+    ) -> Result<Vec<Event>, Self::Error> {
+        self.inner.find_event_relations(room_id, event_id, filter).await
+    }
+
+    async fn find_utd_events(
+        &self,
+        linked_chunk_id: LinkedChunkId<'_>,
+        session_ids: Option<&BTreeSet<String>>,
+    ) -> Result<Vec<(Position, Event)>, Self::Error> {
+        self.inner.find_utd_events(linked_chunk_id, session_ids).await
+    }
+
+    async fn save_event(&self, room_id: &RoomId, event: Event) -> Result<(), Self::Error> {
+        self.inner.save_event(room_id, event).await
+    }
+
+    async fn remove_events(
+        &self,
+        room_id: &RoomId,
+        event_ids: Vec<OwnedEventId>,
+    ) -> Result<(), Self::Error> {
+        self.inner.remove_events(room_id, event_ids).await
+    }
+
+    async fn add_media_content(
+        &self,
+        request: &MediaRequestParameters,
+        content: Vec<u8>,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+    ) -> Result<(), Self::Error> {
+        self.media_service()
+            .await?
+            .add_media_content(&self.media, request, content, ignore_policy)
+            .await
+    }
+
+    async fn replace_media_key(
+        &self,
+        from: &MediaRequestParameters,
+        to: &MediaRequestParameters,
+    ) -> Result<(), Self::Error> {
+        let mut index = self.media.lock_index().await?;
+        let from_key = from.unique_key();
+        let to_key = to.unique_key();
+
+        if !index.media.iter().any(|entry| entry.key == from_key) {
+            return Ok(());
+        }
+
+        // The media at the destination, if any, is replaced.
+        let replaced = index.remove(|entry| entry.key == to_key);
+        self.media.remove_blobs(&mut index, replaced).await?;
+
+        for entry in index.media.iter_mut().filter(|entry| entry.key == from_key) {
+            entry.uri = to.uri().to_owned();
+            entry.key = to_key.clone();
+        }
+
+        self.media.save_index(&index).await
+    }
+
+    async fn get_media_content(
+        &self,
+        request: &MediaRequestParameters,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.media_service().await?.get_media_content(&self.media, request).await
+    }
+
+    async fn remove_media_content(
+        &self,
+        request: &MediaRequestParameters,
+    ) -> Result<(), Self::Error> {
+        let key = request.unique_key();
+        self.media.remove_media(|entry| entry.key == key).await
+    }
+
+    async fn get_media_content_for_uri(
+        &self,
+        uri: &MxcUri,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.media_service().await?.get_media_content_for_uri(&self.media, uri).await
+    }
+
+    async fn remove_media_content_for_uri(&self, uri: &MxcUri) -> Result<(), Self::Error> {
+        // All the formats of the media are removed.
+        self.media.remove_media(|entry| entry.uri == uri).await
+    }
+
+    async fn set_media_retention_policy(
+        &self,
+        policy: MediaRetentionPolicy,
+    ) -> Result<(), Self::Error> {
+        self.media_service().await?.set_media_retention_policy(&self.media, policy).await
+    }
+
+    fn media_retention_policy(&self) -> MediaRetentionPolicy {
+        self.media_service.media_retention_policy()
+    }
+
+    async fn set_ignore_media_retention_policy(
+        &self,
+        request: &MediaRequestParameters,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+    ) -> Result<(), Self::Error> {
+        self.media_service()
+            .await?
+            .set_ignore_media_retention_policy(&self.media, request, ignore_policy)
+            .await
+    }
+
+    async fn clean_up_media_cache(&self) -> Result<(), Self::Error> {
+        self.media_service().await?.clean_up_media_cache(&self.media).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use async_trait::async_trait;
+    use matrix_sdk_common::locks::Mutex;
+    use matrix_sdk_test::async_test;
+    use ruma::{events::room::MediaSource, mxc_uri, uint, MxcUri};
+
+    use super::{BlobMedia, MediaBlobEventCacheStore, MediaBlobStore};
+    use crate::{
+        event_cache::store::{
+            media::{IgnoreMediaRetentionPolicy, MediaRetentionPolicy},
+            EventCacheStore, EventCacheStoreError, IntoEventCacheStore, MemoryStore,
+        },
+        event_cache_store_media_integration_tests,
+        media::{MediaFormat, MediaRequestParameters, MediaThumbnailSettings},
+    };
+
+    #[derive(Debug, Default)]
+    struct MemoryBlobStore {
+        blobs: Mutex<HashMap<String, Vec<u8>>>,
+    }
+
+    #[cfg_attr(target_family = "wasm", async_trait(?Send))]
+    #[cfg_attr(not(target_family = "wasm"), async_trait)]
+    impl MediaBlobStore for MemoryBlobStore {
+        async fn put_blob(&self, key: &str, content: Vec<u8>) -> Result<(), EventCacheStoreError> {
+            self.blobs.lock().insert(key.to_owned(), content);
+            Ok(())
+        }
+
+        async fn get_blob(&self, key: &str) -> Result<Option<Vec<u8>>, EventCacheStoreError> {
+            Ok(self.blobs.lock().get(key).cloned())
+        }
+
+        async fn remove_blob(&self, key: &str) -> Result<(), EventCacheStoreError> {
+            self.blobs.lock().remove(key);
+            Ok(())
+        }
+    }
+
+    async fn get_event_cache_store() -> Result<BlobMedia, EventCacheStoreError> {
+        Ok(BlobMedia {
+            inner: MemoryStore::new().into_event_cache_store(),
+            blobs: Arc::new(MemoryBlobStore::default()),
+            index: Default::default(),
+        })
+    }
+
+    event_cache_store_media_integration_tests!(with_media_size_tests);
+
+    fn file_request(uri: &MxcUri) -> MediaRequestParameters {
+        MediaRequestParameters {
+            source: MediaSource::Plain(uri.to_owned()),
+            format: MediaFormat::File,
+        }
+    }
+
+    #[async_test]
+    async fn test_media_content_is_stored_in_blob_store() {
+        let blobs = Arc::new(MemoryBlobStore::default());
+        let inner = Arc::new(MemoryStore::new());
+        let store = MediaBlobEventCacheStore::new(inner.clone(), blobs.clone());
+
+        let uri = mxc_uri!("mxc://localhost/media");
+        let request = file_request(uri);
+        let content = b"hello world".to_vec();
+
+        store
+            .add_media_content(&request, content.clone(), IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+
+        // The content is in the blob store, not in the inner store.
+        {
+            let blobs = blobs.blobs.lock();
+            assert_eq!(blobs.len(), 1);
+            assert_eq!(*blobs.values().next().unwrap(), content);
+        }
+        assert_eq!(inner.get_media_content(&request).await.unwrap(), None);
+
+        assert_eq!(store.get_media_content(&request).await.unwrap(), Some(content.clone()));
+        assert_eq!(store.get_media_content_for_uri(uri).await.unwrap(), Some(content));
+
+        // Replacing the content reuses the same blob.
+        let new_content = b"hello again".to_vec();
+        store
+            .add_media_content(&request, new_content.clone(), IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+        assert_eq!(blobs.blobs.lock().len(), 1);
+        assert_eq!(store.get_media_content(&request).await.unwrap(), Some(new_content.clone()));
+
+        // The index of the media is persisted in the inner store.
+        let other_store = MediaBlobEventCacheStore::new(inner, blobs.clone());
+        assert_eq!(other_store.get_media_content(&request).await.unwrap(), Some(new_content));
+
+        // A blob evicted by the platform is a cache miss.
+        blobs.blobs.lock().clear();
+        assert_eq!(store.get_media_content(&request).await.unwrap(), None);
+
+        // Removing the media removes its blob.
+        store
+            .add_media_content(&request, b"hello".to_vec(), IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+        store.remove_media_content(&request).await.unwrap();
+        assert!(blobs.blobs.lock().is_empty());
+        assert_eq!(store.get_media_content(&request).await.unwrap(), None);
+    }
+
+    #[async_test]
+    async fn test_remove_media_content_for_uri_removes_all_blobs() {
+        let blobs = Arc::new(MemoryBlobStore::default());
+        let store = MediaBlobEventCacheStore::new(MemoryStore::new(), blobs.clone());
+
+        let uri = mxc_uri!("mxc://localhost/media");
+        let file_request = file_request(uri);
+        let thumbnail_request = MediaRequestParameters {
+            source: MediaSource::Plain(uri.to_owned()),
+            format: MediaFormat::Thumbnail(MediaThumbnailSettings::new(uint!(100), uint!(100))),
+        };
+        let other_request = self::file_request(mxc_uri!("mxc://localhost/other"));
+
+        for request in [&file_request, &thumbnail_request, &other_request] {
+            store
+                .add_media_content(request, b"hello".to_vec(), IgnoreMediaRetentionPolicy::No)
+                .await
+                .unwrap();
+        }
+        assert_eq!(blobs.blobs.lock().len(), 3);
+
+        // All the formats of the media are removed, along with their blobs.
+        store.remove_media_content_for_uri(uri).await.unwrap();
+
+        assert_eq!(blobs.blobs.lock().len(), 1);
+        assert_eq!(store.get_media_content(&file_request).await.unwrap(), None);
+        assert_eq!(store.get_media_content(&thumbnail_request).await.unwrap(), None);
+        assert!(store.get_media_content(&other_request).await.unwrap().is_some());
+    }
+
+    #[async_test]
+    async fn test_clean_up_media_cache_removes_blobs() {
+        let blobs = Arc::new(MemoryBlobStore::default());
+        let store = MediaBlobEventCacheStore::new(MemoryStore::new(), blobs.clone());
+
+        // The size of the blobs counts towards the maximum cache size.
+        store
+            .set_media_retention_policy(
+                MediaRetentionPolicy::empty().with_max_cache_size(Some(150)),
+            )
+            .await
+            .unwrap();
+
+        let old_request = file_request(mxc_uri!("mxc://localhost/old"));
+        let new_request = file_request(mxc_uri!("mxc://localhost/new"));
+
+        store
+            .add_media_content(&old_request, vec![0; 100], IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+        store
+            .add_media_content(&new_request, vec![1; 100], IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+        // Access the new media last.
+        store.get_media_content(&new_request).await.unwrap().unwrap();
+        assert_eq!(blobs.blobs.lock().len(), 2);
+
+        store.clean_up_media_cache().await.unwrap();
+
+        // The least recently accessed media was removed, with its blob.
+        {
+            let blobs = blobs.blobs.lock();
+            assert_eq!(blobs.len(), 1);
+            assert_eq!(*blobs.values().next().unwrap(), vec![1; 100]);
+        }
+        assert_eq!(store.get_media_content(&old_request).await.unwrap(), None);
+        assert!(store.get_media_content(&new_request).await.unwrap().is_some());
+    }
+}
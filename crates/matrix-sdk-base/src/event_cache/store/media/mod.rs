@@ -14,6 +14,7 @@
 
 //! Types and traits regarding media caching of the event cache store.
 
+mod blob_store;
 mod media_retention_policy;
 mod media_service;
 #[cfg(any(test, feature = "testing"))]
@@ -23,6 +24,7 @@ pub mod integration_tests;
 #[cfg(any(test, feature = "testing"))]
 pub use self::integration_tests::EventCacheStoreMediaIntegrationTests;
 pub use self::{
+    blob_store::{MediaBlobEventCacheStore, MediaBlobStore},
     media_retention_policy::MediaRetentionPolicy,
     media_service::{EventCacheStoreMedia, IgnoreMediaRetentionPolicy, MediaService},
 };
@@ -190,32 +190,37 @@ impl EventCacheStore for MemoryStore {
     async fn filter_duplicated_events(
         &self,
         linked_chunk_id: LinkedChunkId<'_>,
-        mut events: Vec<OwnedEventId>,
+        events: Vec<OwnedEventId>,
     ) -> Result<Vec<(OwnedEventId, Position)>, Self::Error> {
-        // Collect all duplicated events.
+        // Collect all duplicated events, thanks to the index of the positions.
         let inner = self.inner.read().unwrap();
 
-        let mut duplicated_events = Vec::new();
-
-        for (event, position) in inner.events.unordered_linked_chunk_items(linked_chunk_id) {
-            // If `events` is empty, we can short-circuit.
-            if events.is_empty() {
-                break;
-            }
+        let mut duplicated_events = events
+            .into_iter()
+            .filter_map(|event_id| {
+                let position = inner.events.item_position(linked_chunk_id, &event_id)?;
+                Some((event_id, position))
+            })
+            .collect::<Vec<_>>();
 
-            if let Some(known_event_id) = event.event_id() {
-                // This event is a duplicate!
-                if let Some(index) =
-                    events.iter().position(|new_event_id| &known_event_id == new_event_id)
-                {
-                    duplicated_events.push((events.remove(index), position));
-                }
-            }
-        }
+        // Return the duplicated events ordered by their positions, like the other
+        // stores do.
+        duplicated_events
+            .sort_by_key(|(_, position)| (position.chunk_identifier(), position.index()));
 
         Ok(duplicated_events)
     }
 
+    async fn position_of_event(
+        &self,
+        linked_chunk_id: LinkedChunkId<'_>,
+        event_id: &EventId,
+    ) -> Result<Option<Position>, Self::Error> {
+        let inner = self.inner.read().unwrap();
+
+        Ok(inner.events.item_position(linked_chunk_id, &event_id.to_owned()))
+    }
+
     async fn find_event(
         &self,
         room_id: &RoomId,
@@ -279,6 +284,20 @@ impl EventCacheStore for MemoryStore {
         Ok(())
     }
 
+    async fn remove_events(
+        &self,
+        room_id: &RoomId,
+        event_ids: Vec<OwnedEventId>,
+    ) -> Result<(), Self::Error> {
+        let mut inner = self.inner.write().unwrap();
+
+        for event_id in &event_ids {
+            inner.events.remove_item(room_id, event_id);
+        }
+
+        Ok(())
+    }
+
     async fn add_media_content(
         &self,
         request: &MediaRequestParameters,
@@ -92,6 +92,12 @@ impl EventCacheStoreLock {
 
         Ok(EventCacheStoreLockGuard { cross_process_lock_guard, store: self.store.deref() })
     }
+
+    /// Wait until the cross-process lock has been released (see
+    /// [`CrossProcessStoreLock::wait_for_release`]).
+    pub async fn wait_for_release(&self) {
+        self.cross_process_lock.wait_for_release().await
+    }
 }
 
 /// An RAII implementation of a “scoped lock” of an [`EventCacheStoreLock`].
@@ -12,13 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{fmt, sync::Arc};
+use std::{collections::BTreeSet, fmt, sync::Arc};
 
 use async_trait::async_trait;
 use matrix_sdk_common::{
+    deserialized_responses::TimelineEventKind,
     linked_chunk::{
-        ChunkIdentifier, ChunkIdentifierGenerator, ChunkMetadata, LinkedChunkId, Position,
-        RawChunk, Update,
+        ChunkContent, ChunkIdentifier, ChunkIdentifierGenerator, ChunkMetadata, LinkedChunkId,
+        Position, RawChunk, Update,
     },
     AsyncTraitDeps,
 };
@@ -127,6 +128,18 @@ pub trait EventCacheStore: AsyncTraitDeps {
         events: Vec<OwnedEventId>,
     ) -> Result<Vec<(OwnedEventId, Position)>, Self::Error>;
 
+    /// Find the position of an event in a linked chunk, by its ID.
+    ///
+    /// The stores keep an index of the event IDs of each linked chunk, so this
+    /// doesn't require loading the chunks.
+    ///
+    /// Returns `None` if the event isn't part of this linked chunk.
+    async fn position_of_event(
+        &self,
+        linked_chunk_id: LinkedChunkId<'_>,
+        event_id: &EventId,
+    ) -> Result<Option<Position>, Self::Error>;
+
     /// Find an event by its ID in a room.
     async fn find_event(
         &self,
@@ -150,6 +163,49 @@ pub trait EventCacheStore: AsyncTraitDeps {
         filter: Option<&[RelationType]>,
     ) -> Result<Vec<Event>, Self::Error>;
 
+    /// Find the events of a linked chunk which couldn't be decrypted, along
+    /// with their position.
+    ///
+    /// If `session_ids` is set, only the events encrypted with one of these
+    /// megolm sessions are returned.
+    ///
+    /// This is used to retry decrypting those events when the room keys of
+    /// their sessions are received late, including the events which aren't
+    /// loaded in memory. The default implementation loads all the chunks of
+    /// the linked chunk.
+    async fn find_utd_events(
+        &self,
+        linked_chunk_id: LinkedChunkId<'_>,
+        session_ids: Option<&BTreeSet<String>>,
+    ) -> Result<Vec<(Position, Event)>, Self::Error> {
+        let chunks = self.load_all_chunks(linked_chunk_id).await?;
+
+        Ok(chunks
+            .into_iter()
+            .filter_map(|chunk| match chunk.content {
+                ChunkContent::Items(events) => Some((chunk.identifier, events)),
+                ChunkContent::Gap(_) => None,
+            })
+            .flat_map(|(chunk_identifier, events)| {
+                events
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(index, event)| (Position::new(chunk_identifier, index), event))
+            })
+            .filter(|(_, event)| match &event.kind {
+                TimelineEventKind::UnableToDecrypt { utd_info, .. } => {
+                    session_ids.is_none_or(|session_ids| {
+                        utd_info
+                            .session_id
+                            .as_ref()
+                            .is_some_and(|session_id| session_ids.contains(session_id))
+                    })
+                }
+                TimelineEventKind::Decrypted(_) | TimelineEventKind::PlainText { .. } => false,
+            })
+            .collect())
+    }
+
     /// Save an event, that might or might not be part of an existing linked
     /// chunk.
     ///
@@ -160,6 +216,17 @@ pub trait EventCacheStore: AsyncTraitDeps {
     /// without causing an error.
     async fn save_event(&self, room_id: &RoomId, event: Event) -> Result<(), Self::Error>;
 
+    /// Remove events of a room, e.g. once their chunks have been removed
+    /// from the room's linked chunk.
+    ///
+    /// The events which are still part of a linked chunk, and the unknown
+    /// events, are skipped.
+    async fn remove_events(
+        &self,
+        room_id: &RoomId,
+        event_ids: Vec<OwnedEventId>,
+    ) -> Result<(), Self::Error>;
+
     /// Add a media file's content in the media store.
     ///
     /// # Arguments
@@ -360,6 +427,14 @@ impl<T: EventCacheStore> EventCacheStore for EraseEventCacheStoreError<T> {
         self.0.filter_duplicated_events(linked_chunk_id, events).await.map_err(Into::into)
     }
 
+    async fn position_of_event(
+        &self,
+        linked_chunk_id: LinkedChunkId<'_>,
+        event_id: &EventId,
+    ) -> Result<Option<Position>, Self::Error> {
+        self.0.position_of_event(linked_chunk_id, event_id).await.map_err(Into::into)
+    }
+
     async fn find_event(
         &self,
         room_id: &RoomId,
@@ -377,10 +452,26 @@ impl<T: EventCacheStore> EventCacheStore for EraseEventCacheStoreError<T> {
         self.0.find_event_relations(room_id, event_id, filter).await.map_err(Into::into)
     }
 
+    async fn find_utd_events(
+        &self,
+        linked_chunk_id: LinkedChunkId<'_>,
+        session_ids: Option<&BTreeSet<String>>,
+    ) -> Result<Vec<(Position, Event)>, Self::Error> {
+        self.0.find_utd_events(linked_chunk_id, session_ids).await.map_err(Into::into)
+    }
+
     async fn save_event(&self, room_id: &RoomId, event: Event) -> Result<(), Self::Error> {
         self.0.save_event(room_id, event).await.map_err(Into::into)
     }
 
+    async fn remove_events(
+        &self,
+        room_id: &RoomId,
+        event_ids: Vec<OwnedEventId>,
+    ) -> Result<(), Self::Error> {
+        self.0.remove_events(room_id, event_ids).await.map_err(Into::into)
+    }
+
     async fn add_media_content(
         &self,
         request: &MediaRequestParameters,
@@ -464,6 +464,13 @@ pub struct RoomInfo {
     /// more accurate than relying on the latest event.
     #[serde(default)]
     pub(crate) recency_stamp: Option<u64>,
+
+    /// Whether the room has been archived with [`BaseClient::archive_room`],
+    /// in which case it isn't loaded when the client starts.
+    ///
+    /// [`BaseClient::archive_room`]: crate::BaseClient::archive_room
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) archived: bool,
 }
 
 impl RoomInfo {
@@ -486,6 +493,7 @@ impl RoomInfo {
             cached_display_name: None,
             cached_user_defined_notification_mode: None,
             recency_stamp: None,
+            archived: false,
         }
     }
 
@@ -1221,6 +1229,7 @@ mod tests {
             cached_display_name: None,
             cached_user_defined_notification_mode: None,
             recency_stamp: Some(42),
+            archived: false,
         };
 
         let info_json = json!({
@@ -485,6 +485,7 @@ impl StateStoreIntegrationTests for DynStateStore {
                 identity_server: None,
                 tile_server: None,
                 rtc_foci: vec![RtcFocusInfo::livekit("livekit.example.com".to_owned())],
+                raw: None,
             }),
         );
 
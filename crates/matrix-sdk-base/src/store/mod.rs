@@ -198,11 +198,17 @@ impl BaseStateStore {
     ) -> Result<()> {
         *self.room_load_settings.write().await = room_load_settings.clone();
 
-        let room_infos = self.load_and_migrate_room_infos(room_load_settings).await?;
+        let room_infos = self.load_and_migrate_room_infos(room_load_settings.clone()).await?;
 
         let mut rooms = self.rooms.write().unwrap();
 
         for room_info in room_infos {
+            // Archived rooms are only loaded on demand, unless they've been explicitly
+            // asked for.
+            if room_info.archived && matches!(room_load_settings, RoomLoadSettings::All) {
+                continue;
+            }
+
             let new_room = Room::restore(
                 user_id,
                 self.inner.clone(),
@@ -354,6 +360,81 @@ impl BaseStateStore {
         self.rooms.write().unwrap().remove(room_id);
         Ok(())
     }
+
+    /// Archive the room with the given room ID.
+    ///
+    /// The room is marked as archived in the [`StateStore`], so it's kept
+    /// there but isn't loaded by [`BaseStateStore::load_rooms`] anymore, and
+    /// it's removed from the in-memory rooms. It can be restored with
+    /// [`BaseStateStore::restore_archived_room`].
+    ///
+    /// Returns `true` if the room was loaded and has been archived.
+    pub(crate) async fn archive_room(&self, room_id: &RoomId) -> Result<bool> {
+        let Some(room) = self.room(room_id) else {
+            return Ok(false);
+        };
+
+        let mut room_info = room.clone_info();
+        room_info.archived = true;
+
+        let mut changes = StateChanges::default();
+        changes.add_room(room_info);
+        self.inner.save_changes(&changes).await?;
+
+        self.rooms.write().unwrap().remove(room_id);
+
+        Ok(true)
+    }
+
+    /// Restore a room which has been archived with
+    /// [`BaseStateStore::archive_room`], or which hasn't been loaded by
+    /// [`BaseStateStore::load_rooms`], from the [`StateStore`].
+    ///
+    /// If the room is already loaded, it's returned as is. Returns `None` if
+    /// the room isn't known by the [`StateStore`].
+    pub(crate) async fn restore_archived_room(
+        &self,
+        user_id: &UserId,
+        room_id: &RoomId,
+        room_info_notable_update_sender: &broadcast::Sender<RoomInfoNotableUpdate>,
+    ) -> Result<Option<Room>> {
+        if let Some(room) = self.room(room_id) {
+            return Ok(Some(room));
+        }
+
+        let Some(mut room_info) = self
+            .load_and_migrate_room_infos(RoomLoadSettings::One(room_id.to_owned()))
+            .await?
+            .into_iter()
+            .next()
+        else {
+            return Ok(None);
+        };
+
+        if room_info.archived {
+            room_info.archived = false;
+
+            let mut changes = StateChanges::default();
+            changes.add_room(room_info.clone());
+            self.inner.save_changes(&changes).await?;
+        }
+
+        let room = self
+            .rooms
+            .write()
+            .unwrap()
+            .get_or_create(room_id, || {
+                Room::restore(
+                    user_id,
+                    self.inner.clone(),
+                    room_info,
+                    room_info_notable_update_sender.clone(),
+                )
+            })
+            .clone();
+
+        Ok(Some(room))
+    }
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -818,4 +899,76 @@ mod tests {
             assert_eq!(rooms[0].own_user_id(), user_id);
         }
     }
+
+    #[async_test]
+    async fn test_archive_and_restore_room() {
+        let room_id_0 = room_id!("!r0");
+        let room_id_1 = room_id!("!r1");
+        let user_id = owned_user_id!("@mnt_io:matrix.org");
+        let session_meta =
+            SessionMeta { user_id: user_id.clone(), device_id: owned_device_id!("HELLOYOU") };
+
+        let memory_store = Arc::new(MemoryStore::new());
+        let store = BaseStateStore::new(memory_store.clone());
+        let (room_info_notable_update_sender, _) = broadcast::channel(2);
+
+        let mut changes = StateChanges::default();
+        changes.add_room(RoomInfo::new(room_id_0, RoomState::Left));
+        changes.add_room(RoomInfo::new(room_id_1, RoomState::Joined));
+        store.inner.save_changes(&changes).await.unwrap();
+
+        store
+            .load_rooms(&user_id, RoomLoadSettings::All, &room_info_notable_update_sender)
+            .await
+            .unwrap();
+        store.set_session_meta(session_meta);
+        assert_eq!(store.rooms().len(), 2);
+
+        // Archive the left room: it's not loaded anymore.
+        assert!(store.archive_room(room_id_0).await.unwrap());
+        assert!(store.room(room_id_0).is_none());
+        assert_eq!(store.rooms().len(), 1);
+
+        // Archiving it twice does nothing.
+        assert!(!store.archive_room(room_id_0).await.unwrap());
+
+        // The room stays archived after a restart.
+        {
+            let store = BaseStateStore::new(memory_store.clone());
+            store
+                .load_rooms(&user_id, RoomLoadSettings::All, &room_info_notable_update_sender)
+                .await
+                .unwrap();
+            assert!(store.room(room_id_0).is_none());
+            assert!(store.room(room_id_1).is_some());
+        }
+
+        // It can be restored from the state store.
+        let room = store
+            .restore_archived_room(&user_id, room_id_0, &room_info_notable_update_sender)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(room.room_id(), room_id_0);
+        assert_eq!(room.state(), RoomState::Left);
+        assert!(store.room(room_id_0).is_some());
+        assert_eq!(store.rooms().len(), 2);
+
+        // Once restored, it's loaded again after a restart.
+        {
+            let store = BaseStateStore::new(memory_store);
+            store
+                .load_rooms(&user_id, RoomLoadSettings::All, &room_info_notable_update_sender)
+                .await
+                .unwrap();
+            assert!(store.room(room_id_0).is_some());
+        }
+
+        // An unknown room can't be restored.
+        assert!(store
+            .restore_archived_room(&user_id, room_id!("!r2"), &room_info_notable_update_sender)
+            .await
+            .unwrap()
+            .is_none());
+    }
 }
@@ -20,7 +20,7 @@ use as_variant::as_variant;
 use ruma::{
     events::{
         room::{message::RoomMessageEventContent, MediaSource},
-        AnyMessageLikeEventContent, EventContent as _, RawExt as _,
+        AnyMessageLikeEventContent, AnyStateEventContent, EventContent as _, RawExt as _,
     },
     serde::Raw,
     MilliSecondsSinceUnixEpoch, OwnedDeviceId, OwnedEventId, OwnedTransactionId, OwnedUserId,
@@ -110,6 +110,18 @@ pub enum QueuedRequestKind {
         #[serde(default)]
         accumulated: Vec<AccumulatedSentMediaInfo>,
     },
+
+    /// A state event to be sent via the send queue.
+    StateEvent {
+        /// The type of the state event.
+        event_type: String,
+
+        /// The state key of the state event.
+        state_key: String,
+
+        /// The content of the state event.
+        content: Raw<AnyStateEventContent>,
+    },
 }
 
 impl From<SerializableEventContent> for QueuedRequestKind {
@@ -173,6 +185,15 @@ pub enum QueueWedgeError {
         user_device_map: BTreeMap<OwnedUserId, Vec<OwnedDeviceId>>,
     },
 
+    /// This error occurs when the room only shares its keys with trusted
+    /// devices, and some devices of its members are neither trusted nor have
+    /// been confirmed by the user.
+    #[error("There are unconfirmed untrusted devices in the room")]
+    UntrustedDevices {
+        /// The untrusted devices as a Map of userID to deviceID.
+        user_device_map: BTreeMap<OwnedUserId, Vec<OwnedDeviceId>>,
+    },
+
     /// This error occurs when a previously verified user is not anymore, and
     /// the current encryption setting prohibits sharing when it happens.
     #[error("Some users that were previously verified are not anymore")]
@@ -271,6 +292,15 @@ pub enum DependentQueuedRequestKind {
         /// Metadata about the gallery items.
         item_infos: Vec<FinishGalleryItemInfo>,
     },
+
+    /// Send the next request of a group of requests, once the previous request
+    /// of the group (the parent) has been sent.
+    SendInGroup {
+        /// The request to send.
+        ///
+        /// `Box` the request so that it reduces the size of the whole enum.
+        request: Box<QueuedRequestKind>,
+    },
 }
 
 /// If parent_is_thumbnail_upload is missing, we assume the request is for a
@@ -479,6 +509,10 @@ impl DependentQueuedRequest {
                 // This one graduates into a new gallery event.
                 true
             }
+            DependentQueuedRequestKind::SendInGroup { .. } => {
+                // This one graduates into a new event.
+                true
+            }
         }
     }
 }
@@ -42,7 +42,7 @@ use ruma::{
         RedactedStateEventContent, RoomAccountDataEvent, RoomAccountDataEventContent,
         RoomAccountDataEventType, StateEventType, StaticEventContent, StaticStateEventContent,
     },
-    serde::Raw,
+    serde::{JsonObject, Raw},
     time::SystemTime,
     EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedMxcUri, OwnedRoomId,
     OwnedTransactionId, OwnedUserId, RoomId, TransactionId, UserId,
@@ -1029,6 +1029,28 @@ pub struct WellKnownResponse {
 
     /// A list of the available MatrixRTC foci, ordered by priority.
     pub rtc_foci: Vec<RtcFocusInfo>,
+
+    /// The full JSON content of the well-known file, including the custom
+    /// fields that aren't known to the SDK, if it's been kept around.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<JsonObject>,
+}
+
+impl WellKnownResponse {
+    /// Create a new `WellKnownResponse` from the typed response and the raw
+    /// JSON content of the well-known file.
+    pub fn with_raw(response: discover_homeserver::Response, raw: JsonObject) -> Self {
+        Self { raw: Some(raw), ..response.into() }
+    }
+
+    /// Get the value of a field of the well-known file by its key, e.g.
+    /// `io.element.e2ee`.
+    ///
+    /// Returns `None` if the field isn't present, or if the raw content of the
+    /// well-known file hasn't been kept around.
+    pub fn field(&self, key: &str) -> Option<&serde_json::Value> {
+        self.raw.as_ref()?.get(key)
+    }
 }
 
 impl From<discover_homeserver::Response> for WellKnownResponse {
@@ -1038,6 +1060,7 @@ impl From<discover_homeserver::Response> for WellKnownResponse {
             identity_server: response.identity_server,
             tile_server: response.tile_server,
             rtc_foci: response.rtc_foci,
+            raw: None,
         }
     }
 }
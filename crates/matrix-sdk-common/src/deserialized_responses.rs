@@ -407,6 +407,11 @@ pub struct ThreadSummary {
     /// events in the thread are considered to be meaningful (or they've all
     /// been redacted).
     pub num_replies: u32,
+
+    /// Whether the current user has participated in the thread, i.e. sent an
+    /// event in it.
+    #[serde(default)]
+    pub current_user_participated: bool,
 }
 
 /// The status of a thread summary.
@@ -481,6 +486,11 @@ pub struct TimelineEvent {
     /// Not serialized.
     #[serde(skip)]
     pub bundled_latest_thread_event: Option<Box<TimelineEvent>>,
+
+    /// Whether this event has been imported from a room history export,
+    /// instead of being received from the homeserver.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub imported: bool,
 }
 
 // Don't serialize push actions if they're `None` or an empty vec.
@@ -534,7 +544,7 @@ impl TimelineEvent {
         let (thread_summary, latest_thread_event) = extract_bundled_thread_summary(kind.raw());
         let bundled_latest_thread_event =
             Self::from_bundled_latest_event(&kind, latest_thread_event);
-        Self { kind, push_actions, thread_summary, bundled_latest_thread_event }
+        Self { kind, push_actions, thread_summary, bundled_latest_thread_event, imported: false }
     }
 
     /// Try to create a new [`TimelineEvent`] for the bundled latest thread
@@ -1099,17 +1109,27 @@ struct SyncTimelineEventDeserializationHelperV1 {
     /// If the event is part of a thread, a thread summary.
     #[serde(default)]
     thread_summary: ThreadSummaryStatus,
+
+    /// Whether the event has been imported from a room history export.
+    #[serde(default)]
+    imported: bool,
 }
 
 impl From<SyncTimelineEventDeserializationHelperV1> for TimelineEvent {
     fn from(value: SyncTimelineEventDeserializationHelperV1) -> Self {
-        let SyncTimelineEventDeserializationHelperV1 { kind, push_actions, thread_summary } = value;
+        let SyncTimelineEventDeserializationHelperV1 {
+            kind,
+            push_actions,
+            thread_summary,
+            imported,
+        } = value;
         TimelineEvent {
             kind,
             push_actions: Some(push_actions),
             thread_summary,
             // Bundled latest thread event is not persisted.
             bundled_latest_thread_event: None,
+            imported,
         }
     }
 }
@@ -1170,6 +1190,8 @@ impl From<SyncTimelineEventDeserializationHelperV0> for TimelineEvent {
             thread_summary: ThreadSummaryStatus::Unknown,
             // Bundled latest thread event is not persisted.
             bundled_latest_thread_event: None,
+            // No events were imported at this version of the struct.
+            imported: false,
         }
     }
 }
@@ -1264,6 +1286,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_imported_marker_is_persisted() {
+        let mut event = TimelineEvent::from_plaintext(Raw::new(&example_event()).unwrap().cast());
+
+        // Events aren't imported by default, and the marker isn't serialized then.
+        let serialized = serde_json::to_value(&event).unwrap();
+        assert!(serialized.get("imported").is_none());
+
+        event.imported = true;
+        let serialized = serde_json::to_value(&event).unwrap();
+        assert_eq!(serialized["imported"], true);
+
+        let deserialized: TimelineEvent = serde_json::from_value(serialized).unwrap();
+        assert!(deserialized.imported);
+    }
+
     #[test]
     fn old_verification_state_to_new_migration() {
         #[derive(Deserialize)]
@@ -1398,6 +1436,7 @@ mod tests {
             push_actions: Default::default(),
             thread_summary: ThreadSummaryStatus::Unknown,
             bundled_latest_thread_event: None,
+            imported: false,
         };
 
         let serialized = serde_json::to_value(&room_event).unwrap();
@@ -1556,8 +1595,9 @@ mod tests {
         // When creating a timeline event from a raw event, the thread summary is always
         // extracted, if available.
         let timeline_event = TimelineEvent::from_plaintext(raw);
-        assert_matches!(timeline_event.thread_summary, ThreadSummaryStatus::Some(ThreadSummary { num_replies, latest_reply }) => {
+        assert_matches!(timeline_event.thread_summary, ThreadSummaryStatus::Some(ThreadSummary { num_replies, latest_reply, current_user_participated }) => {
             assert_eq!(num_replies, 2);
+            assert!(current_user_participated);
             assert_eq!(latest_reply.as_deref(), Some(event_id!("$latest_event:example.com")));
         });
 
@@ -1847,8 +1887,10 @@ mod tests {
             thread_summary: ThreadSummaryStatus::Some(ThreadSummary {
                 num_replies: 2,
                 latest_reply: None,
+                current_user_participated: false,
             }),
             bundled_latest_thread_event: None,
+            imported: false,
         };
 
         with_settings!({ sort_maps => true, prepend_module_to_snapshot => false }, {
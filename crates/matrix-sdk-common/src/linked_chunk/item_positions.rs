@@ -0,0 +1,276 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, RwLock},
+};
+
+use super::{
+    updates::{ReaderToken, Update, UpdatesInner},
+    ChunkContent, ChunkIdentifier, Iter, Position,
+};
+
+/// An index of the positions of the items of a linked chunk, by key.
+///
+/// Finding the position of an item in a linked chunk requires iterating over
+/// all its items; this index makes it a hash map lookup instead. It's kept up
+/// to date by reading the updates of the linked chunk, which must happen
+/// before querying it, with [`ItemPositions::flush_updates`].
+///
+/// Only the items for which the key function returns a key are indexed.
+pub struct ItemPositions<Item, Gap, Key> {
+    /// Strong reference to [`UpdatesInner`].
+    updates: Arc<RwLock<UpdatesInner<Item, Gap>>>,
+
+    /// The token to read the updates.
+    token: ReaderToken,
+
+    /// The function returning the key of an item, if it must be indexed.
+    key: fn(&Item) -> Option<Key>,
+
+    /// The position of each indexed item.
+    positions: HashMap<Key, Position>,
+
+    /// The keys of the items of each items chunk, in order.
+    chunks: HashMap<ChunkIdentifier, Vec<Option<Key>>>,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl<Item, Gap, Key> std::fmt::Debug for ItemPositions<Item, Gap, Key> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ItemPositions").field("num_items", &self.positions.len()).finish()
+    }
+}
+
+impl<Item, Gap, Key> ItemPositions<Item, Gap, Key>
+where
+    Key: Clone + Eq + Hash,
+{
+    /// Create a new [`ItemPositions`], indexing the items of the given chunks.
+    pub(super) fn new<const CAP: usize>(
+        updates: Arc<RwLock<UpdatesInner<Item, Gap>>>,
+        token: ReaderToken,
+        key: fn(&Item) -> Option<Key>,
+        chunks: Iter<'_, CAP, Item, Gap>,
+    ) -> Self {
+        // Drain previous updates so that this type is synced with `Updates`.
+        {
+            let mut updates = updates.write().unwrap();
+            let _ = updates.take_with_token(token);
+        }
+
+        let mut this =
+            Self { updates, token, key, positions: HashMap::new(), chunks: HashMap::new() };
+
+        for chunk in chunks {
+            if let ChunkContent::Items(items) = chunk.content() {
+                this.push_items(Position::new(chunk.identifier(), 0), items);
+            }
+        }
+
+        this
+    }
+
+    /// Read the pending updates of the linked chunk, to bring the index up to
+    /// date.
+    pub fn flush_updates(&mut self) {
+        let updates = self.updates.clone();
+        let mut updates = updates.write().unwrap();
+
+        for update in updates.take_with_token(self.token) {
+            match update {
+                Update::NewItemsChunk { new, .. } => {
+                    self.chunks.insert(*new, Vec::new());
+                }
+
+                Update::NewGapChunk { .. }
+                | Update::StartReattachItems
+                | Update::EndReattachItems => {}
+
+                Update::RemoveChunk(chunk_identifier) => {
+                    for key in self.chunks.remove(chunk_identifier).into_iter().flatten().flatten()
+                    {
+                        self.positions.remove(&key);
+                    }
+                }
+
+                Update::PushItems { at, items } => self.push_items(*at, items),
+
+                Update::ReplaceItem { at, item } => {
+                    let new_key = (self.key)(item);
+
+                    if let Some(key) = self
+                        .chunks
+                        .get_mut(&at.chunk_identifier())
+                        .and_then(|keys| keys.get_mut(at.index()))
+                    {
+                        if let Some(previous_key) = key.take() {
+                            self.positions.remove(&previous_key);
+                        }
+
+                        if let Some(new_key) = new_key {
+                            self.positions.insert(new_key.clone(), *at);
+                            *key = Some(new_key);
+                        }
+                    }
+                }
+
+                Update::RemoveItem { at } => {
+                    let Some(keys) = self.chunks.get_mut(&at.chunk_identifier()) else {
+                        continue;
+                    };
+
+                    if at.index() >= keys.len() {
+                        continue;
+                    }
+
+                    if let Some(key) = keys.remove(at.index()) {
+                        self.positions.remove(&key);
+                    }
+
+                    // The items after the removed one are shifted to the left.
+                    for (index, key) in keys.iter().enumerate().skip(at.index()) {
+                        if let Some(key) = key {
+                            self.positions
+                                .insert(key.clone(), Position::new(at.chunk_identifier(), index));
+                        }
+                    }
+                }
+
+                Update::DetachLastItems { at } => {
+                    let Some(keys) = self.chunks.get_mut(&at.chunk_identifier()) else {
+                        continue;
+                    };
+
+                    if at.index() < keys.len() {
+                        for key in keys.drain(at.index()..).flatten() {
+                            self.positions.remove(&key);
+                        }
+                    }
+                }
+
+                Update::Clear => {
+                    self.positions.clear();
+                    self.chunks.clear();
+                }
+            }
+        }
+    }
+
+    /// Return the position of the item with the given key, if it's in the
+    /// linked chunk.
+    ///
+    /// Precondition: the reader must be up to date, i.e.
+    /// [`Self::flush_updates`] must have been called before this method.
+    pub fn position<Q>(&self, key: &Q) -> Option<Position>
+    where
+        Key: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        debug_assert!(self.updates.read().unwrap().is_reader_up_to_date(self.token));
+
+        self.positions.get(key).copied()
+    }
+
+    /// Index the given items, pushed at the given position of an items chunk.
+    fn push_items(&mut self, at: Position, items: &[Item]) {
+        let keys = self.chunks.entry(at.chunk_identifier()).or_default();
+
+        // Items are always pushed at the end of a chunk; the items which were after
+        // `at` have been detached beforehand.
+        keys.truncate(at.index());
+
+        for (offset, item) in items.iter().enumerate() {
+            let key = (self.key)(item);
+
+            if let Some(key) = &key {
+                self.positions
+                    .insert(key.clone(), Position::new(at.chunk_identifier(), at.index() + offset));
+            }
+
+            keys.push(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::linked_chunk::{LinkedChunk, Position};
+
+    fn key(item: &char) -> Option<char> {
+        // Don't index the `_` items.
+        (*item != '_').then_some(*item)
+    }
+
+    #[test]
+    fn test_item_positions() {
+        let mut linked_chunk = LinkedChunk::<3, char, ()>::new_with_update_history();
+        linked_chunk.push_items_back(['a', 'b']);
+
+        let mut item_positions = linked_chunk.item_positions(key).unwrap();
+
+        let chunk_0 = linked_chunk.chunks().next().unwrap().identifier();
+        assert_eq!(item_positions.position(&'a'), Some(Position::new(chunk_0, 0)));
+        assert_eq!(item_positions.position(&'b'), Some(Position::new(chunk_0, 1)));
+
+        // Pushing items, some of them in a new chunk.
+        linked_chunk.push_items_back(['_', 'c', 'd']);
+        item_positions.flush_updates();
+
+        let chunk_1 = linked_chunk.chunks().nth(1).unwrap().identifier();
+        assert_eq!(item_positions.position(&'_'), None);
+        assert_eq!(item_positions.position(&'c'), Some(Position::new(chunk_1, 0)));
+        assert_eq!(item_positions.position(&'d'), Some(Position::new(chunk_1, 1)));
+
+        // Inserting an item in the middle of a chunk shifts the following ones.
+        linked_chunk.insert_items_at(['e'], Position::new(chunk_0, 0)).unwrap();
+        item_positions.flush_updates();
+
+        for (position, item) in linked_chunk.items() {
+            if *item != '_' {
+                assert_eq!(item_positions.position(item), Some(position), "for {item}");
+            }
+        }
+
+        // Removing an item shifts the following ones too.
+        let position_of_e = item_positions.position(&'e').unwrap();
+        linked_chunk.remove_item_at(position_of_e).unwrap();
+        item_positions.flush_updates();
+
+        assert_eq!(item_positions.position(&'e'), None);
+        for (position, item) in linked_chunk.items() {
+            if *item != '_' {
+                assert_eq!(item_positions.position(item), Some(position), "for {item}");
+            }
+        }
+
+        // Replacing an item.
+        let position_of_a = item_positions.position(&'a').unwrap();
+        linked_chunk.replace_item_at(position_of_a, 'f').unwrap();
+        item_positions.flush_updates();
+
+        assert_eq!(item_positions.position(&'a'), None);
+        assert_eq!(item_positions.position(&'f'), Some(position_of_a));
+
+        // Clearing the linked chunk.
+        linked_chunk.clear();
+        item_positions.flush_updates();
+
+        assert_eq!(item_positions.position(&'f'), None);
+        assert_eq!(item_positions.position(&'c'), None);
+    }
+}
@@ -210,6 +210,57 @@ fn emit_new_first_chunk_updates<const CAP: usize, Item, Gap>(
     }
 }
 
+/// Unload the first chunk of a `LinkedChunk` from memory.
+///
+/// The chunk is not removed from the persisted storage: its identifier becomes
+/// the `lazy_previous` of the new first chunk, so that it can be reloaded later
+/// with [`insert_new_first_chunk`].
+///
+/// Returns the identifier of the unloaded chunk, or `None` if the `LinkedChunk`
+/// contains a single chunk, which can't be unloaded.
+pub fn unload_first_chunk<const CAP: usize, Item, Gap>(
+    linked_chunk: &mut LinkedChunk<CAP, Item, Gap>,
+) -> Option<ChunkIdentifier> {
+    let links = &mut linked_chunk.links;
+
+    // The last chunk is never unloaded.
+    let mut next_chunk_ptr = links.first_chunk().next?;
+
+    let old_first_chunk_ptr = links.first;
+    let old_first_chunk_identifier = links.first_chunk().identifier();
+
+    // Update the next chunk, which is about to become the first one.
+    {
+        // SAFETY: Pointer is convertible to a reference.
+        let next_chunk = unsafe { next_chunk_ptr.as_mut() };
+
+        next_chunk.previous = None;
+        next_chunk.lazy_previous = Some(old_first_chunk_identifier);
+    }
+
+    // Update `links`.
+    {
+        links.first = next_chunk_ptr;
+
+        // If the next chunk was the last one, there is now a single chunk.
+        if links.last == Some(next_chunk_ptr) {
+            links.last = None;
+        }
+    }
+
+    // Re-box the old first chunk, and let Rust drop it.
+    //
+    // SAFETY: the chunk isn't referenced by any other chunk anymore.
+    let _chunk_boxed = unsafe { Box::from_raw(old_first_chunk_ptr.as_ptr()) };
+
+    // Emit the updates.
+    if let Some(updates) = linked_chunk.updates.as_mut() {
+        updates.push(Update::RemoveChunk(old_first_chunk_identifier));
+    }
+
+    Some(old_first_chunk_identifier)
+}
+
 /// Replace the items with the given last chunk of items and generator.
 ///
 /// This clears all the chunks in memory before resetting to the new chunk,
@@ -372,8 +423,8 @@ mod tests {
 
     use super::{
         super::Position, from_all_chunks, from_last_chunk, insert_new_first_chunk, replace_with,
-        ChunkContent, ChunkIdentifier, ChunkIdentifierGenerator, LazyLoaderError, LinkedChunk,
-        RawChunk, Update,
+        unload_first_chunk, ChunkContent, ChunkIdentifier, ChunkIdentifierGenerator,
+        LazyLoaderError, LinkedChunk, RawChunk, Update,
     };
 
     #[test]
@@ -677,6 +728,82 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_unload_first_chunk_single_chunk() {
+        let mut linked_chunk = LinkedChunk::<2, char, ()>::new_with_update_history();
+        linked_chunk.push_items_back(vec!['a', 'b']);
+
+        // Drain initial updates.
+        let _ = linked_chunk.updates().unwrap().take();
+
+        // The last chunk is never unloaded.
+        assert!(unload_first_chunk(&mut linked_chunk).is_none());
+        assert_eq!(linked_chunk.chunks().count(), 1);
+        assert!(linked_chunk.updates().unwrap().take().is_empty());
+    }
+
+    #[test]
+    fn test_unload_first_chunk() {
+        let mut linked_chunk = LinkedChunk::<2, char, ()>::new_with_update_history();
+        linked_chunk.push_items_back(vec!['a', 'b']);
+        linked_chunk.push_gap_back(());
+        linked_chunk.push_items_back(vec!['c', 'd']);
+
+        // Drain initial updates.
+        let _ = linked_chunk.updates().unwrap().take();
+
+        // Unload the first items chunk.
+        assert_eq!(unload_first_chunk(&mut linked_chunk), Some(ChunkIdentifier::new(0)));
+
+        {
+            let mut chunks = linked_chunk.chunks();
+
+            assert_matches!(chunks.next(), Some(chunk) => {
+                assert_eq!(chunk.identifier(), 1);
+                assert!(chunk.is_gap());
+                assert_eq!(chunk.lazy_previous(), Some(ChunkIdentifier::new(0)));
+            });
+            assert_matches!(chunks.next(), Some(chunk) => {
+                assert_eq!(chunk.identifier(), 2);
+                assert!(chunk.is_items());
+            });
+            assert!(chunks.next().is_none());
+        }
+
+        assert_eq!(
+            linked_chunk.updates().unwrap().take(),
+            [Update::RemoveChunk(ChunkIdentifier::new(0))]
+        );
+
+        // Unload the gap, there's only the last chunk left.
+        assert_eq!(unload_first_chunk(&mut linked_chunk), Some(ChunkIdentifier::new(1)));
+
+        {
+            let mut rchunks = linked_chunk.rchunks();
+
+            assert_matches!(rchunks.next(), Some(chunk) => {
+                assert_eq!(chunk.identifier(), 2);
+                assert_eq!(chunk.lazy_previous(), Some(ChunkIdentifier::new(1)));
+            });
+            assert!(rchunks.next().is_none());
+        }
+
+        // The unloaded chunks can be lazy-loaded again.
+        insert_new_first_chunk(
+            &mut linked_chunk,
+            RawChunk {
+                previous: Some(ChunkIdentifier::new(0)),
+                identifier: ChunkIdentifier::new(1),
+                next: Some(ChunkIdentifier::new(2)),
+                content: ChunkContent::Gap(()),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(linked_chunk.chunks().count(), 2);
+        assert_eq!(linked_chunk.items().map(|(_, item)| *item).collect::<Vec<_>>(), ['c', 'd']);
+    }
+
     #[test]
     fn test_replace_with_chunk_too_large() {
         // Start with a linked chunk with 3 chunks: one item, one gap, one item.
@@ -92,6 +92,7 @@ macro_rules! assert_items_eq {
 }
 
 mod as_vector;
+mod item_positions;
 pub mod lazy_loader;
 mod order_tracker;
 pub mod relational;
@@ -105,6 +106,7 @@ use std::{
 };
 
 pub use as_vector::*;
+pub use item_positions::ItemPositions;
 pub use order_tracker::OrderTracker;
 use ruma::{OwnedRoomId, RoomId};
 pub use updates::*;
@@ -1087,6 +1089,27 @@ impl<const CAP: usize, Item, Gap> LinkedChunk<CAP, Item, Gap> {
         Some(AsVector::new(updates, token, chunk_iterator))
     }
 
+    /// Get an [`ItemPositions`] index for the linked chunk, which can be used
+    /// to find the position of an item by its key without iterating over all
+    /// the items.
+    ///
+    /// It returns `None` if updates are disabled, i.e. if this linked chunk has
+    /// been constructed with [`Self::new`].
+    pub fn item_positions<Key>(
+        &mut self,
+        key: fn(&Item) -> Option<Key>,
+    ) -> Option<ItemPositions<Item, Gap, Key>>
+    where
+        Key: Clone + Eq + std::hash::Hash,
+    {
+        let (updates, token) = self
+            .updates
+            .as_mut()
+            .map(|updates| (updates.inner.clone(), updates.new_reader_token()))?;
+
+        Some(ItemPositions::new(updates, token, key, self.chunks()))
+    }
+
     /// Get an [`OrderTracker`] for the linked chunk, which can be used to
     /// compare the relative position of two events in this linked chunk.
     ///
@@ -17,7 +17,7 @@
 
 use std::{collections::HashMap, hash::Hash};
 
-use ruma::{OwnedEventId, OwnedRoomId};
+use ruma::{OwnedEventId, OwnedRoomId, RoomId};
 
 use super::{ChunkContent, ChunkIdentifierGenerator, RawChunk};
 use crate::{
@@ -82,6 +82,10 @@ pub struct RelationalLinkedChunk<ItemId, Item, Gap> {
 
     /// The items' content themselves.
     items: HashMap<OwnedLinkedChunkId, HashMap<ItemId, Item>>,
+
+    /// The position of each item of [`Self::items_chunks`], so that an item
+    /// can be found without iterating over all the rows.
+    item_positions: HashMap<OwnedLinkedChunkId, HashMap<ItemId, Position>>,
 }
 
 /// The [`IndexableItem`] trait is used to mark items that can be indexed into a
@@ -109,7 +113,12 @@ where
 {
     /// Create a new relational linked chunk.
     pub fn new() -> Self {
-        Self { chunks: Vec::new(), items_chunks: Vec::new(), items: HashMap::new() }
+        Self {
+            chunks: Vec::new(),
+            items_chunks: Vec::new(),
+            items: HashMap::new(),
+            item_positions: HashMap::new(),
+        }
     }
 
     /// Removes all the chunks and items from this relational linked chunk.
@@ -117,6 +126,7 @@ where
         self.chunks.clear();
         self.items_chunks.clear();
         self.items.clear();
+        self.item_positions.clear();
     }
 
     /// Apply [`Update`]s. That's the only way to write data inside this
@@ -165,7 +175,11 @@ where
                         .collect::<Vec<_>>();
 
                     for index_to_remove in indices_to_remove.into_iter().rev() {
-                        self.items_chunks.remove(index_to_remove);
+                        remove_item_row(
+                            &mut self.items_chunks,
+                            &mut self.item_positions,
+                            index_to_remove,
+                        );
                     }
                 }
 
@@ -176,6 +190,10 @@ where
                             .entry(linked_chunk_id.to_owned())
                             .or_default()
                             .insert(item_id.clone(), item);
+                        self.item_positions
+                            .entry(linked_chunk_id.to_owned())
+                            .or_default()
+                            .insert(item_id.clone(), at);
                         self.items_chunks.push(ItemRow {
                             linked_chunk_id: linked_chunk_id.to_owned(),
                             position: at,
@@ -200,6 +218,14 @@ where
                         .entry(linked_chunk_id.to_owned())
                         .or_default()
                         .insert(item_id.clone(), item);
+
+                    let positions =
+                        self.item_positions.entry(linked_chunk_id.to_owned()).or_default();
+                    if let Either::Item(previous_item_id) = &existing.item {
+                        positions.remove(previous_item_id);
+                    }
+                    positions.insert(item_id.clone(), at);
+
                     existing.item = Either::Item(item_id);
                 }
 
@@ -208,7 +234,7 @@ where
 
                     for (
                         nth,
-                        ItemRow { linked_chunk_id: linked_chunk_id_candidate, position, .. },
+                        ItemRow { linked_chunk_id: linked_chunk_id_candidate, position, item },
                     ) in self.items_chunks.iter_mut().enumerate()
                     {
                         // Filter by linked chunk id.
@@ -228,10 +254,25 @@ where
                             && position.index() > at.index()
                         {
                             position.decrement_index();
+
+                            // Keep the index of the positions in sync.
+                            if let Either::Item(item_id) = item {
+                                if let Some(indexed_position) = self
+                                    .item_positions
+                                    .get_mut(&*linked_chunk_id_candidate)
+                                    .and_then(|positions| positions.get_mut(&*item_id))
+                                {
+                                    *indexed_position = *position;
+                                }
+                            }
                         }
                     }
 
-                    self.items_chunks.remove(entry_to_remove.expect("Remove an unknown item"));
+                    remove_item_row(
+                        &mut self.items_chunks,
+                        &mut self.item_positions,
+                        entry_to_remove.expect("Remove an unknown item"),
+                    );
                     // We deliberately keep the item in the items collection.
                 }
 
@@ -258,7 +299,11 @@ where
                         .collect::<Vec<_>>();
 
                     for index_to_remove in indices_to_remove.into_iter().rev() {
-                        self.items_chunks.remove(index_to_remove);
+                        remove_item_row(
+                            &mut self.items_chunks,
+                            &mut self.item_positions,
+                            index_to_remove,
+                        );
                     }
                 }
 
@@ -267,11 +312,28 @@ where
                 Update::Clear => {
                     self.chunks.retain(|chunk| chunk.linked_chunk_id != linked_chunk_id);
                     self.items_chunks.retain(|chunk| chunk.linked_chunk_id != linked_chunk_id);
+                    self.item_positions.remove(&linked_chunk_id.to_owned());
                     // We deliberately leave the items intact.
                 }
             }
         }
 
+        fn remove_item_row<ItemId, Gap>(
+            items_chunks: &mut Vec<ItemRow<ItemId, Gap>>,
+            item_positions: &mut HashMap<OwnedLinkedChunkId, HashMap<ItemId, Position>>,
+            nth: usize,
+        ) where
+            ItemId: Hash + PartialEq + Eq,
+        {
+            let ItemRow { linked_chunk_id, item, .. } = items_chunks.remove(nth);
+
+            if let Either::Item(item_id) = item {
+                if let Some(positions) = item_positions.get_mut(&linked_chunk_id) {
+                    positions.remove(&item_id);
+                }
+            }
+        }
+
         fn insert_chunk(
             chunks: &mut Vec<ChunkRow>,
             linked_chunk_id: LinkedChunkId<'_>,
@@ -389,6 +451,15 @@ where
         })
     }
 
+    /// Return the position of an item of a particular linked chunk, if it's
+    /// part of it.
+    ///
+    /// Unlike iterating over [`Self::unordered_linked_chunk_items`], this
+    /// doesn't depend on the number of items.
+    pub fn item_position(&self, target: LinkedChunkId<'_>, item_id: &ItemId) -> Option<Position> {
+        self.item_positions.get(&target.to_owned())?.get(item_id).copied()
+    }
+
     /// Return an iterator over all items of all room linked chunks, without
     /// their actual positions.
     ///
@@ -405,6 +476,20 @@ where
         let linked_chunk_id = OwnedLinkedChunkId::Room(room_id);
         self.items.entry(linked_chunk_id).or_default().insert(id, item);
     }
+
+    /// Remove a single item of a room from the relational linked chunk,
+    /// unless it's still part of the room's linked chunk.
+    pub fn remove_item(&mut self, room_id: &RoomId, item_id: &ItemId) {
+        let linked_chunk_id = OwnedLinkedChunkId::Room(room_id.to_owned());
+
+        if self.item_position(linked_chunk_id.as_ref(), item_id).is_some() {
+            return;
+        }
+
+        if let Some(items) = self.items.get_mut(&linked_chunk_id) {
+            items.remove(item_id);
+        }
+    }
 }
 
 impl<ItemId, Item, Gap> RelationalLinkedChunk<ItemId, Item, Gap>
@@ -1405,6 +1490,84 @@ mod tests {
         assert!(events.next().is_none());
     }
 
+    #[test]
+    fn test_item_position() {
+        let room_id = room_id!("!r0:matrix.org");
+        let linked_chunk_id = OwnedLinkedChunkId::Room(room_id.to_owned());
+
+        let other_room_id = room_id!("!r1:matrix.org");
+        let other_linked_chunk_id = OwnedLinkedChunkId::Room(other_room_id.to_owned());
+
+        let mut relational_linked_chunk = RelationalLinkedChunk::<_, char, ()>::new();
+
+        relational_linked_chunk.apply_updates(
+            linked_chunk_id.as_ref(),
+            vec![
+                Update::NewItemsChunk { previous: None, new: CId::new(0), next: None },
+                Update::PushItems { at: Position::new(CId::new(0), 0), items: vec!['a', 'b', 'c'] },
+                Update::NewItemsChunk { previous: Some(CId::new(0)), new: CId::new(1), next: None },
+                Update::PushItems { at: Position::new(CId::new(1), 0), items: vec!['d', 'e', 'f'] },
+            ],
+        );
+
+        relational_linked_chunk.apply_updates(
+            other_linked_chunk_id.as_ref(),
+            vec![
+                Update::NewItemsChunk { previous: None, new: CId::new(0), next: None },
+                Update::PushItems { at: Position::new(CId::new(0), 0), items: vec!['x'] },
+            ],
+        );
+
+        let position =
+            |item| relational_linked_chunk.item_position(linked_chunk_id.as_ref(), &item);
+
+        assert_eq!(position('a'), Some(Position::new(CId::new(0), 0)));
+        assert_eq!(position('e'), Some(Position::new(CId::new(1), 1)));
+        // Items of other linked chunks aren't found.
+        assert_eq!(position('x'), None);
+
+        relational_linked_chunk.apply_updates(
+            linked_chunk_id.as_ref(),
+            vec![
+                // Removing an item shifts the next ones.
+                Update::RemoveItem { at: Position::new(CId::new(0), 0) },
+                // Replacing an item indexes the new one instead.
+                Update::ReplaceItem { at: Position::new(CId::new(1), 0), item: 'g' },
+                // Detaching items un-indexes them.
+                Update::DetachLastItems { at: Position::new(CId::new(1), 2) },
+            ],
+        );
+
+        let position =
+            |item| relational_linked_chunk.item_position(linked_chunk_id.as_ref(), &item);
+
+        assert_eq!(position('a'), None);
+        assert_eq!(position('b'), Some(Position::new(CId::new(0), 0)));
+        assert_eq!(position('c'), Some(Position::new(CId::new(0), 1)));
+        assert_eq!(position('d'), None);
+        assert_eq!(position('g'), Some(Position::new(CId::new(1), 0)));
+        assert_eq!(position('f'), None);
+
+        // Removing a chunk un-indexes its items.
+        relational_linked_chunk
+            .apply_updates(linked_chunk_id.as_ref(), vec![Update::RemoveChunk(CId::new(0))]);
+
+        assert_eq!(relational_linked_chunk.item_position(linked_chunk_id.as_ref(), &'b'), None);
+        assert_eq!(
+            relational_linked_chunk.item_position(linked_chunk_id.as_ref(), &'g'),
+            Some(Position::new(CId::new(1), 0))
+        );
+
+        // Clearing the linked chunk un-indexes everything.
+        relational_linked_chunk.apply_updates(linked_chunk_id.as_ref(), vec![Update::Clear]);
+
+        assert_eq!(relational_linked_chunk.item_position(linked_chunk_id.as_ref(), &'g'), None);
+        assert_eq!(
+            relational_linked_chunk.item_position(other_linked_chunk_id.as_ref(), &'x'),
+            Some(Position::new(CId::new(0), 0))
+        );
+    }
+
     #[test]
     fn test_load_last_chunk() {
         let room_id = room_id!("!r0:matrix.org");
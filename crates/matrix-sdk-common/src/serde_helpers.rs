@@ -88,7 +88,11 @@ pub fn extract_bundled_thread_summary(
                 bundled_thread.latest_event.get_field::<OwnedEventId>("event_id").ok().flatten();
 
             (
-                ThreadSummaryStatus::Some(ThreadSummary { num_replies: count, latest_reply }),
+                ThreadSummaryStatus::Some(ThreadSummary {
+                    num_replies: count,
+                    latest_reply,
+                    current_user_participated: bundled_thread.current_user_participated,
+                }),
                 Some(bundled_thread.latest_event),
             )
         }
@@ -47,7 +47,7 @@ use std::{
     time::Duration,
 };
 
-use tokio::sync::Mutex;
+use tokio::sync::{watch, Mutex};
 use tracing::{debug, error, instrument, trace};
 
 use crate::{
@@ -121,6 +121,12 @@ pub struct CrossProcessStoreLock<S: BackingStore + Clone + SendOutsideWasm + 'st
     /// Current renew task spawned by `try_lock_once`.
     renew_task: Arc<Mutex<Option<JoinHandle<()>>>>,
 
+    /// Whether this process currently has a lease on the lock in the store.
+    ///
+    /// It's set when the lock is acquired, and unset once the renew task has
+    /// given the lease back to the store.
+    is_leased: Arc<watch::Sender<bool>>,
+
     /// The key used in the key/value mapping for the lock entry.
     lock_key: String,
 
@@ -165,6 +171,7 @@ impl<S: BackingStore + Clone + SendOutsideWasm + 'static> CrossProcessStoreLock<
             num_holders: Arc::new(0.into()),
             locking_attempt: Arc::new(Mutex::new(())),
             renew_task: Default::default(),
+            is_leased: Arc::new(watch::Sender::new(false)),
         }
     }
 
@@ -233,6 +240,8 @@ impl<S: BackingStore + Clone + SendOutsideWasm + 'static> CrossProcessStoreLock<
             }
         }
 
+        self.is_leased.send_replace(true);
+
         // Restart a new one.
         *renew_task = Some(spawn(async move {
             loop {
@@ -257,6 +266,8 @@ impl<S: BackingStore + Clone + SendOutsideWasm + 'static> CrossProcessStoreLock<
                         let fut = this.store.try_lock(0, &this.lock_key, &this.lock_holder);
                         let _ = fut.await;
 
+                        this.is_leased.send_replace(false);
+
                         // Exit the loop.
                         break;
                     }
@@ -267,6 +278,10 @@ impl<S: BackingStore + Clone + SendOutsideWasm + 'static> CrossProcessStoreLock<
                 let fut = this.store.try_lock(LEASE_DURATION_MS, &this.lock_key, &this.lock_holder);
                 if let Err(err) = fut.await {
                     error!("error when extending lock lease: {err:#}");
+
+                    // The lease won't be extended anymore, and will expire on its own.
+                    this.is_leased.send_replace(false);
+
                     // Exit the loop.
                     break;
                 }
@@ -279,6 +294,18 @@ impl<S: BackingStore + Clone + SendOutsideWasm + 'static> CrossProcessStoreLock<
         Ok(Some(guard))
     }
 
+    /// Wait until this process doesn't hold the lock anymore, and its lease has
+    /// been given back to the store.
+    ///
+    /// This doesn't return as long as a [`CrossProcessStoreLockGuard`] is
+    /// alive, so callers will likely want to wrap it in a timeout.
+    pub async fn wait_for_release(&self) {
+        // The lease is renewed as long as there's a holder, and given back by the
+        // renew task once they've all been dropped.
+        let mut is_leased = self.is_leased.subscribe();
+        let _ = is_leased.wait_for(|is_leased| !is_leased).await;
+    }
+
     /// Attempt to take the lock, with exponential backoff if the lock has
     /// already been taken before.
     ///
@@ -479,6 +506,29 @@ mod tests {
         Ok(())
     }
 
+    #[async_test]
+    async fn test_wait_for_release() -> TestResult {
+        let store = TestStore::default();
+        let lock1 = CrossProcessStoreLock::new(store.clone(), "key".to_owned(), "first".to_owned());
+        let lock2 = CrossProcessStoreLock::new(store, "key".to_owned(), "second".to_owned());
+
+        // Nothing to wait for when the lock has never been taken.
+        lock1.wait_for_release().await;
+
+        let acquired1 = lock1.try_lock_once().await?;
+        assert!(acquired1.is_some());
+
+        // Once the guard is dropped and the lock has been released...
+        drop(acquired1);
+        lock1.wait_for_release().await;
+
+        // ...another process can take it immediately.
+        let acquired2 = lock2.try_lock_once().await?;
+        assert!(acquired2.is_some());
+
+        Ok(())
+    }
+
     #[async_test]
     async fn test_multiple_processes() -> TestResult {
         let store = TestStore::default();
@@ -107,7 +107,7 @@ use crate::{
     utilities::timestamp_to_iso8601,
     verification::{Verification, VerificationMachine, VerificationRequest},
     CollectStrategy, CryptoStoreError, DecryptionSettings, DeviceData, LocalTrust,
-    RoomEventDecryptionResult, SignatureError, TrustRequirement,
+    OlmSessionHealth, RoomEventDecryptionResult, SignatureError, TrustRequirement,
 };
 
 /// State machine implementation of the Olm/Megolm encryption protocol used for
@@ -784,6 +784,52 @@ impl OlmMachine {
         self.inner.session_manager.get_missing_sessions(users).await
     }
 
+    /// Get the health of the Olm sessions shared with a device: the known
+    /// sessions, and whether the current one is wedged.
+    ///
+    /// This is useful to diagnose why a device can't decrypt our messages, or
+    /// why we can't decrypt its messages.
+    ///
+    /// Returns `None` if the device is unknown.
+    pub async fn olm_session_health(
+        &self,
+        user_id: &UserId,
+        device_id: &DeviceId,
+    ) -> StoreResult<Option<OlmSessionHealth>> {
+        let Some(device) = self.store().get_device_data(user_id, device_id).await? else {
+            return Ok(None);
+        };
+
+        Ok(Some(self.inner.session_manager.olm_session_health(&device).await?))
+    }
+
+    /// Force the creation of a new Olm session with a device, to resolve the
+    /// case where the device can't decrypt our messages anymore.
+    ///
+    /// A one-time key of the device will be claimed by the next request
+    /// returned by [`OlmMachine::get_missing_sessions`], and once the new
+    /// session has been created, an `m.dummy` message will be sent to the
+    /// device with it, so the device starts using the new session too.
+    ///
+    /// Returns `false` if the device is unknown, or doesn't support Olm.
+    pub async fn reestablish_olm_session(
+        &self,
+        user_id: &UserId,
+        device_id: &DeviceId,
+    ) -> StoreResult<bool> {
+        let Some(device) = self.store().get_device_data(user_id, device_id).await? else {
+            return Ok(false);
+        };
+
+        if !device.supports_olm() || device.curve25519_key().is_none() {
+            return Ok(false);
+        }
+
+        self.inner.session_manager.reestablish_session(&device);
+
+        Ok(true)
+    }
+
     /// Receive a successful `/keys/query` response.
     ///
     /// Returns a list of newly discovered devices and devices that changed.
@@ -18,3 +18,4 @@ mod sessions;
 pub use group_sessions::CollectStrategy;
 pub(crate) use group_sessions::{GroupSessionCache, GroupSessionManager};
 pub(crate) use sessions::SessionManager;
+pub use sessions::{OlmSessionHealth, OlmSessionInfo};
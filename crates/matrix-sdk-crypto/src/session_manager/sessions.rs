@@ -40,9 +40,47 @@ use crate::{
         requests::{OutgoingRequest, ToDeviceRequest},
         EventEncryptionAlgorithm,
     },
-    DeviceData,
+    DeviceData, Session,
 };
 
+/// Diagnostic information about an Olm session shared with a device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OlmSessionInfo {
+    /// The unique ID of the session.
+    pub session_id: String,
+
+    /// When the session was created.
+    pub creation_time: SecondsSinceUnixEpoch,
+
+    /// When the session was last used to encrypt or decrypt a message.
+    pub last_use_time: SecondsSinceUnixEpoch,
+}
+
+impl From<&Session> for OlmSessionInfo {
+    fn from(session: &Session) -> Self {
+        Self {
+            session_id: session.session_id().to_owned(),
+            creation_time: session.creation_time,
+            last_use_time: session.last_use_time,
+        }
+    }
+}
+
+/// The health of the Olm sessions shared with a device, see
+/// [`OlmMachine::olm_session_health`](crate::OlmMachine::olm_session_health).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OlmSessionHealth {
+    /// The Olm sessions shared with the device, from the most recently created
+    /// one, which is used to encrypt messages to the device, to the oldest
+    /// one.
+    pub sessions: Vec<OlmSessionInfo>,
+
+    /// Whether the Olm session with the device is wedged, i.e. a message from
+    /// the device couldn't be decrypted, and a new session will be created
+    /// with the next `/keys/claim` request.
+    pub is_wedged: bool,
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct SessionManager {
     store: Store,
@@ -119,16 +157,7 @@ impl SessionManager {
                     .unwrap_or(true);
 
                 if should_unwedge {
-                    self.users_for_key_claim
-                        .write()
-                        .entry(device.user_id().to_owned())
-                        .or_default()
-                        .insert(device.device_id().into());
-                    self.wedged_devices
-                        .write()
-                        .entry(device.user_id().to_owned())
-                        .or_default()
-                        .insert(device.device_id().into());
+                    self.mark_device_for_unwedging(&device);
                 }
             }
         }
@@ -136,7 +165,33 @@ impl SessionManager {
         Ok(())
     }
 
-    #[allow(dead_code)]
+    /// Force the creation of a new Olm session with the given device, even if
+    /// the current session was created recently.
+    pub fn reestablish_session(&self, device: &DeviceData) {
+        info!(
+            user_id = ?device.user_id(),
+            device_id = ?device.device_id(),
+            "Marking session to be re-established"
+        );
+        self.mark_device_for_unwedging(device);
+    }
+
+    /// Claim a new one-time key for the device with the next `/keys/claim`
+    /// request, and send it a dummy message once the new session has been
+    /// created.
+    fn mark_device_for_unwedging(&self, device: &DeviceData) {
+        self.users_for_key_claim
+            .write()
+            .entry(device.user_id().to_owned())
+            .or_default()
+            .insert(device.device_id().into());
+        self.wedged_devices
+            .write()
+            .entry(device.user_id().to_owned())
+            .or_default()
+            .insert(device.device_id().into());
+    }
+
     pub fn is_device_wedged(&self, device: &DeviceData) -> bool {
         self.wedged_devices
             .read()
@@ -144,6 +199,21 @@ impl SessionManager {
             .is_some_and(|d| d.contains(device.device_id()))
     }
 
+    /// Get the health of the Olm sessions shared with the given device.
+    pub async fn olm_session_health(&self, device: &DeviceData) -> StoreResult<OlmSessionHealth> {
+        let mut sessions: Vec<OlmSessionInfo> = match device.curve25519_key() {
+            Some(sender_key) => match self.store.get_sessions(&sender_key.to_base64()).await? {
+                Some(sessions) => sessions.lock().await.iter().map(Into::into).collect(),
+                None => Vec::new(),
+            },
+            None => Vec::new(),
+        };
+
+        sessions.sort_by(|a, b| b.creation_time.cmp(&a.creation_time));
+
+        Ok(OlmSessionHealth { sessions, is_wedged: self.is_device_wedged(device) })
+    }
+
     /// Check if the session was created to unwedge a Device.
     ///
     /// If the device was wedged this will queue up a dummy to-device message.
@@ -600,7 +670,7 @@ mod tests {
     use tokio::sync::Mutex;
     use tracing::info;
 
-    use super::SessionManager;
+    use super::{OlmSessionInfo, SessionManager};
     use crate::{
         gossiping::GossipMachine,
         identities::{DeviceData, IdentityManager},
@@ -884,6 +954,43 @@ mod tests {
         assert!(!manager.outgoing_to_device_requests.read().is_empty())
     }
 
+    #[async_test]
+    async fn test_session_reestablishment() {
+        let (manager, _identity_manager) = session_manager_test_helper().await;
+        let mut bob = bob_account();
+
+        let (_, session) = manager
+            .store
+            .with_transaction(|mut tr| async {
+                let manager_account = tr.account().await.unwrap();
+                let res = bob.create_session_for_test_helper(manager_account).await;
+                Ok((tr, res))
+            })
+            .await
+            .unwrap();
+
+        let bob_device = DeviceData::from_account(&bob);
+        manager.store.save_device_data(std::slice::from_ref(&bob_device)).await.unwrap();
+        manager.store.save_sessions(&[session.clone()]).await.unwrap();
+
+        // The session is healthy.
+        let health = manager.olm_session_health(&bob_device).await.unwrap();
+        assert_eq!(health.sessions, vec![OlmSessionInfo::from(&session)]);
+        assert!(!health.is_wedged);
+        assert!(manager.get_missing_sessions(iter::once(bob.user_id())).await.unwrap().is_none());
+
+        // Even if the session has just been created, it can be re-established.
+        manager.reestablish_session(&bob_device);
+
+        let health = manager.olm_session_health(&bob_device).await.unwrap();
+        assert_eq!(health.sessions.len(), 1);
+        assert!(health.is_wedged);
+
+        let (_, request) =
+            manager.get_missing_sessions(iter::once(bob.user_id())).await.unwrap().unwrap();
+        assert!(request.one_time_keys[bob.user_id()].contains_key(bob.device_id()));
+    }
+
     #[async_test]
     async fn test_failure_handling() {
         let alice = user_id!("@alice:example.org");
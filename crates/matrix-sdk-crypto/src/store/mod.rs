@@ -1088,6 +1088,59 @@ impl Store {
         self.set_value("only_allow_trusted_devices", &block_untrusted_devices).await
     }
 
+    /// Check whether the given room has been marked as only sharing its room
+    /// keys with trusted devices.
+    ///
+    /// Unlike [`types::RoomSettings::only_allow_trusted_devices`], this flag
+    /// can be changed at any time by the user.
+    pub async fn get_room_only_allow_trusted_devices(&self, room_id: &RoomId) -> Result<bool> {
+        let key = format!("only_allow_trusted_devices:{room_id}");
+        let value = self.get_value(&key).await?.unwrap_or_default();
+        Ok(value)
+    }
+
+    /// Mark the given room as only sharing its room keys with trusted devices,
+    /// or as sharing them with every device of its members.
+    pub async fn set_room_only_allow_trusted_devices(
+        &self,
+        room_id: &RoomId,
+        block_untrusted_devices: bool,
+    ) -> Result<()> {
+        let key = format!("only_allow_trusted_devices:{room_id}");
+        self.set_value(&key, &block_untrusted_devices).await
+    }
+
+    /// Get the untrusted devices which have been confirmed to receive the room
+    /// keys of the given room, grouped by their owner.
+    ///
+    /// The confirmation only applies to this room: the trust state of the
+    /// devices isn't changed.
+    pub async fn get_room_confirmed_untrusted_devices(
+        &self,
+        room_id: &RoomId,
+    ) -> Result<BTreeMap<OwnedUserId, BTreeSet<OwnedDeviceId>>> {
+        let key = format!("confirmed_untrusted_devices:{room_id}");
+        let value = self.get_value(&key).await?.unwrap_or_default();
+        Ok(value)
+    }
+
+    /// Confirm that the given untrusted devices can receive the room keys of
+    /// the given room, in addition to the ones already confirmed.
+    pub async fn confirm_room_untrusted_devices(
+        &self,
+        room_id: &RoomId,
+        devices: impl IntoIterator<Item = (OwnedUserId, OwnedDeviceId)>,
+    ) -> Result<()> {
+        let mut confirmed = self.get_room_confirmed_untrusted_devices(room_id).await?;
+
+        for (user_id, device_id) in devices {
+            confirmed.entry(user_id).or_default().insert(device_id);
+        }
+
+        let key = format!("confirmed_untrusted_devices:{room_id}");
+        self.set_value(&key, &confirmed).await
+    }
+
     /// Get custom stored value associated with a key
     pub async fn get_value<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
         let Some(value) = self.get_custom_value(key).await? else {
@@ -1701,12 +1754,12 @@ impl matrix_sdk_common::store_locks::BackingStore for LockableCryptoStore {
 
 #[cfg(test)]
 mod tests {
-    use std::pin::pin;
+    use std::{collections::BTreeSet, pin::pin};
 
     use futures_util::StreamExt;
     use insta::{_macro_support::Content, assert_json_snapshot, internals::ContentPath};
     use matrix_sdk_test::async_test;
-    use ruma::{device_id, room_id, user_id, RoomId};
+    use ruma::{device_id, owned_device_id, owned_user_id, room_id, user_id, RoomId};
     use vodozemac::megolm::SessionKey;
 
     use crate::{
@@ -1741,6 +1794,71 @@ mod tests {
         assert_eq!(room_keys[0].room_id, "!room1:localhost");
     }
 
+    #[async_test]
+    async fn test_room_only_allow_trusted_devices() {
+        let (alice, _, _) = get_machine_pair(user_id!("@a:s.co"), user_id!("@b:s.co"), false).await;
+        let room1_id = room_id!("!room1:localhost");
+        let room2_id = room_id!("!room2:localhost");
+
+        // Rooms share their keys with every device by default.
+        assert!(!alice.store().get_room_only_allow_trusted_devices(room1_id).await.unwrap());
+
+        alice.store().set_room_only_allow_trusted_devices(room1_id, true).await.unwrap();
+        assert!(alice.store().get_room_only_allow_trusted_devices(room1_id).await.unwrap());
+        assert!(!alice.store().get_room_only_allow_trusted_devices(room2_id).await.unwrap());
+
+        // The flag can be unset again.
+        alice.store().set_room_only_allow_trusted_devices(room1_id, false).await.unwrap();
+        assert!(!alice.store().get_room_only_allow_trusted_devices(room1_id).await.unwrap());
+    }
+
+    #[async_test]
+    async fn test_room_confirmed_untrusted_devices() {
+        let (alice, _, _) = get_machine_pair(user_id!("@a:s.co"), user_id!("@b:s.co"), false).await;
+        let room1_id = room_id!("!room1:localhost");
+        let room2_id = room_id!("!room2:localhost");
+        let bob = owned_user_id!("@b:s.co");
+
+        // No device is confirmed by default.
+        assert!(alice
+            .store()
+            .get_room_confirmed_untrusted_devices(room1_id)
+            .await
+            .unwrap()
+            .is_empty());
+
+        alice
+            .store()
+            .confirm_room_untrusted_devices(
+                room1_id,
+                [(bob.clone(), owned_device_id!("BOBDEVICE1"))],
+            )
+            .await
+            .unwrap();
+        alice
+            .store()
+            .confirm_room_untrusted_devices(
+                room1_id,
+                [(bob.clone(), owned_device_id!("BOBDEVICE2"))],
+            )
+            .await
+            .unwrap();
+
+        // The confirmations add up, in the room they've been made for only.
+        let confirmed = alice.store().get_room_confirmed_untrusted_devices(room1_id).await.unwrap();
+        assert_eq!(confirmed.len(), 1);
+        assert_eq!(
+            confirmed[&bob],
+            BTreeSet::from([owned_device_id!("BOBDEVICE1"), owned_device_id!("BOBDEVICE2")])
+        );
+        assert!(alice
+            .store()
+            .get_room_confirmed_untrusted_devices(room2_id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
     #[async_test]
     async fn test_export_room_keys_provides_selected_keys() {
         // Given an OlmMachine with room keys in it
@@ -1131,6 +1131,40 @@ impl EventCacheStore for SqliteEventCacheStore {
             .await
     }
 
+    async fn position_of_event(
+        &self,
+        linked_chunk_id: LinkedChunkId<'_>,
+        event_id: &EventId,
+    ) -> Result<Option<Position>, Self::Error> {
+        let hashed_linked_chunk_id =
+            self.encode_key(keys::LINKED_CHUNKS, linked_chunk_id.storage_key());
+        let event_id = event_id.to_owned();
+
+        self.acquire()
+            .await?
+            .with_transaction(move |txn| -> Result<_> {
+                // The event ID is the primary key of `event_chunks`, so this is an indexed
+                // lookup.
+                let position = txn
+                    .prepare(
+                        r#"
+                            SELECT chunk_id, position
+                            FROM event_chunks
+                            WHERE linked_chunk_id = ? AND event_id = ?
+                        "#,
+                    )?
+                    .query_row((hashed_linked_chunk_id, event_id.as_str()), |row| {
+                        Ok((row.get::<_, u64>(0)?, row.get::<_, usize>(1)?))
+                    })
+                    .optional()?;
+
+                Ok(position.map(|(chunk_identifier, index)| {
+                    Position::new(ChunkIdentifier::new(chunk_identifier), index)
+                }))
+            })
+            .await
+    }
+
     async fn find_event(
         &self,
         room_id: &RoomId,
@@ -1232,6 +1266,37 @@ impl EventCacheStore for SqliteEventCacheStore {
             .await
     }
 
+    async fn remove_events(
+        &self,
+        room_id: &RoomId,
+        event_ids: Vec<OwnedEventId>,
+    ) -> Result<(), Self::Error> {
+        if event_ids.is_empty() {
+            return Ok(());
+        }
+
+        let hashed_room_id = self.encode_key(keys::LINKED_CHUNKS, room_id);
+
+        self.acquire()
+            .await?
+            .with_transaction(move |txn| -> Result<_> {
+                for event_id in event_ids {
+                    // Keep the events which are still part of a linked chunk.
+                    txn.execute(
+                        r#"
+                            DELETE FROM events
+                            WHERE room_id = ? AND event_id = ?
+                            AND NOT EXISTS (SELECT 1 FROM event_chunks WHERE event_id = events.event_id)
+                        "#,
+                        (&hashed_room_id, event_id.as_str()),
+                    )?;
+                }
+
+                Ok(())
+            })
+            .await
+    }
+
     async fn add_media_content(
         &self,
         request: &MediaRequestParameters,
@@ -0,0 +1,495 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Management of several accounts, i.e. several [`Client`]s, in the same
+//! application.
+//!
+//! The [`AccountManager`] owns the clients of all the accounts of the
+//! application, along with the [`SyncService`] of each of them. It syncs all
+//! the accounts together, merges their rooms and their unread notification
+//! counts, and finds the client to use for an action on a given account.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    pin::pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use async_stream::stream;
+use eyeball::{ObservableWriteGuard, SharedObservable};
+use eyeball_im::Vector;
+use futures_core::Stream;
+use futures_util::{
+    future::{join_all, select, Either},
+    stream::{select_all, SelectAll},
+    StreamExt as _,
+};
+use matrix_sdk::{Client, Room, RoomState};
+use matrix_sdk_base::sync::UnreadNotificationsCount;
+use ruma::{OwnedRoomId, OwnedUserId, RoomId, UserId};
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+use crate::sync_service::{self, SyncService};
+
+/// An error when managing the accounts of an [`AccountManager`].
+#[derive(Debug, thiserror::Error)]
+pub enum AccountManagerError {
+    /// The client isn't logged in, so it doesn't belong to any account.
+    #[error("the client isn't logged in")]
+    NotLoggedIn,
+
+    /// A client for the same account has already been added.
+    #[error("the account {0} has already been added")]
+    AlreadyAdded(OwnedUserId),
+
+    /// No client has been added for the account.
+    #[error("the account {0} is unknown")]
+    UnknownAccount(OwnedUserId),
+
+    /// The sync service of the account couldn't be created.
+    #[error(transparent)]
+    SyncService(#[from] sync_service::Error),
+
+    /// The client of the account failed to perform the action.
+    #[error(transparent)]
+    Sdk(#[from] matrix_sdk::Error),
+}
+
+/// An account of an [`AccountManager`].
+#[derive(Clone)]
+struct Account {
+    client: Client,
+    sync_service: Arc<SyncService>,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Account").field("user_id", &self.client.user_id()).finish_non_exhaustive()
+    }
+}
+
+/// The clients of all the accounts of an application.
+///
+/// Each account is identified by the user ID of its client, and an account
+/// can only be added once. The accounts are kept in the order they were
+/// added.
+///
+/// A [`SyncService`] is created for each account. All the accounts are synced
+/// together with [`AccountManager::start_sync`] and
+/// [`AccountManager::stop_sync`], and the sync of an account is stopped when
+/// it's removed.
+///
+/// Cloning an `AccountManager` is cheap, and all the clones share the same
+/// accounts.
+#[derive(Debug, Clone, Default)]
+pub struct AccountManager {
+    accounts: SharedObservable<Vec<Account>>,
+
+    /// Whether the accounts must be synced, i.e. whether
+    /// [`AccountManager::start_sync`] has been called since the last call to
+    /// [`AccountManager::stop_sync`].
+    is_syncing: Arc<AtomicBool>,
+}
+
+impl AccountManager {
+    /// Create a new `AccountManager`, without any account.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the account of the given logged-in client.
+    ///
+    /// A [`SyncService`] is created for the account. It's started right away
+    /// if the accounts are being synced.
+    pub async fn add_account(&self, client: Client) -> Result<(), AccountManagerError> {
+        let user_id = client.user_id().ok_or(AccountManagerError::NotLoggedIn)?.to_owned();
+
+        if self.account(&user_id).is_some() {
+            return Err(AccountManagerError::AlreadyAdded(user_id));
+        }
+
+        let sync_service = Arc::new(SyncService::builder(client.clone()).build().await?);
+
+        {
+            let mut accounts = self.accounts.write();
+
+            // The account might have been added while the sync service was created.
+            if accounts.iter().any(|account| account.client.user_id() == Some(&user_id)) {
+                return Err(AccountManagerError::AlreadyAdded(user_id));
+            }
+
+            let mut updated = accounts.clone();
+            updated.push(Account { client, sync_service: sync_service.clone() });
+            ObservableWriteGuard::set(&mut accounts, updated);
+        }
+
+        if self.is_syncing.load(Ordering::SeqCst) {
+            sync_service.start().await;
+        }
+
+        Ok(())
+    }
+
+    /// Remove the account with the given user ID.
+    ///
+    /// The sync service of the account is stopped, and the client of the
+    /// account is returned, if the account was known. The session isn't logged
+    /// out, see [`AccountManager::logout`] for that.
+    pub async fn remove_account(&self, user_id: &UserId) -> Option<Client> {
+        let account = {
+            let mut accounts = self.accounts.write();
+
+            let index =
+                accounts.iter().position(|account| account.client.user_id() == Some(user_id))?;
+
+            let mut updated = accounts.clone();
+            let account = updated.remove(index);
+            ObservableWriteGuard::set(&mut accounts, updated);
+
+            account
+        };
+
+        account.sync_service.stop().await;
+
+        Some(account.client)
+    }
+
+    /// Log out the account with the given user ID, and remove it.
+    ///
+    /// The sync of the account is stopped before logging out. The account is
+    /// kept if logging out fails, so that it can be retried, and its sync is
+    /// started again if the accounts are being synced.
+    pub async fn logout(&self, user_id: &UserId) -> Result<(), AccountManagerError> {
+        let account = self
+            .find_account(user_id)
+            .ok_or_else(|| AccountManagerError::UnknownAccount(user_id.to_owned()))?;
+
+        account.sync_service.stop().await;
+
+        if let Err(error) = account.client.logout().await {
+            if self.is_syncing.load(Ordering::SeqCst) {
+                account.sync_service.start().await;
+            }
+
+            return Err(error.into());
+        }
+
+        self.remove_account(user_id).await;
+
+        Ok(())
+    }
+
+    /// Start syncing all the accounts, and the accounts added later.
+    ///
+    /// This can be called multiple times safely, see [`SyncService::start`].
+    pub async fn start_sync(&self) {
+        self.is_syncing.store(true, Ordering::SeqCst);
+
+        join_all(self.sync_services().iter().map(|sync_service| sync_service.start())).await;
+    }
+
+    /// Stop syncing all the accounts, see [`SyncService::stop`].
+    pub async fn stop_sync(&self) {
+        self.is_syncing.store(false, Ordering::SeqCst);
+
+        join_all(self.sync_services().iter().map(|sync_service| sync_service.stop())).await;
+    }
+
+    /// Get the clients of all the accounts, in the order they were added.
+    pub fn accounts(&self) -> Vec<Client> {
+        self.accounts.read().iter().map(|account| account.client.clone()).collect()
+    }
+
+    /// Subscribe to the accounts.
+    ///
+    /// The current accounts are emitted immediately, then again every time an
+    /// account is added or removed.
+    pub fn subscribe_to_accounts(&self) -> impl Stream<Item = Vec<Client>> {
+        let mut accounts = self.accounts.subscribe();
+
+        stream! {
+            yield account_clients(&accounts.get());
+
+            while let Some(accounts) = accounts.next().await {
+                yield account_clients(&accounts);
+            }
+        }
+    }
+
+    /// Get the client of the account with the given user ID, to perform an
+    /// action with this account.
+    pub fn account(&self, user_id: &UserId) -> Option<Client> {
+        self.find_account(user_id).map(|account| account.client)
+    }
+
+    /// Get the sync service of the account with the given user ID.
+    pub fn sync_service(&self, user_id: &UserId) -> Option<Arc<SyncService>> {
+        self.find_account(user_id).map(|account| account.sync_service)
+    }
+
+    /// Get the room with the given ID, as seen by the account with the given
+    /// user ID.
+    pub fn room(&self, user_id: &UserId, room_id: &RoomId) -> Option<Room> {
+        self.account(user_id)?.get_room(room_id)
+    }
+
+    /// Get the rooms of all the accounts.
+    ///
+    /// The rooms are grouped by account, in the order the accounts were
+    /// added, and are in the order they're known by the client within an
+    /// account. A room shared by several accounts appears once for each of
+    /// them; [`Room::client`] is the client of the account the room belongs
+    /// to, so actions on the room are performed with the right account.
+    pub fn rooms(&self) -> Vec<Room> {
+        self.accounts.read().iter().flat_map(|account| account.client.rooms()).collect()
+    }
+
+    /// Subscribe to the rooms of all the accounts, see
+    /// [`AccountManager::rooms`].
+    ///
+    /// The current rooms are emitted immediately, then again every time an
+    /// account is added or removed, or a room is added to or removed from an
+    /// account. Only the rooms of the account which changed are updated.
+    pub fn subscribe_to_rooms(&self) -> impl Stream<Item = Vec<Room>> {
+        let mut accounts = self.accounts.subscribe();
+
+        stream! {
+            let mut clients = account_clients(&accounts.get());
+
+            loop {
+                // The rooms of each account, updated with the changes of the room list
+                // of the account.
+                let mut rooms = Vec::with_capacity(clients.len());
+                let mut room_updates = SelectAll::new();
+
+                for (index, client) in clients.iter().enumerate() {
+                    let (account_rooms, updates) = client.rooms_stream();
+                    rooms.push(account_rooms);
+                    room_updates.push(updates.map(move |diffs| (index, diffs)).boxed());
+                }
+
+                yield combined_rooms(&rooms);
+
+                let new_clients = loop {
+                    let update = match select(pin!(accounts.next()), room_updates.next()).await {
+                        Either::Left((accounts, _)) => Either::Left(accounts),
+                        Either::Right((update, _)) => Either::Right(update),
+                    };
+
+                    match update {
+                        // An account was added or removed, let's listen to the new ones.
+                        Either::Left(Some(accounts)) => break account_clients(&accounts),
+                        // The accounts can't change anymore.
+                        Either::Left(None) => return,
+                        Either::Right(Some((index, diffs))) => {
+                            for diff in diffs {
+                                diff.apply(&mut rooms[index]);
+                            }
+
+                            yield combined_rooms(&rooms);
+                        }
+                        // There's no account to listen to.
+                        Either::Right(None) => {
+                            let Some(accounts) = accounts.next().await else {
+                                return;
+                            };
+                            break account_clients(&accounts);
+                        }
+                    }
+                };
+
+                drop(room_updates);
+                clients = new_clients;
+            }
+        }
+    }
+
+    /// Get the sum of the unread notification counts of the joined rooms of
+    /// all the accounts.
+    pub fn unread_notification_counts(&self) -> UnreadNotificationsCount {
+        let mut counts = UnreadNotificationCounts::default();
+
+        for client in account_clients(&self.accounts.read()) {
+            counts.reset_account(&client);
+        }
+
+        counts.total
+    }
+
+    /// Subscribe to the sum of the unread notification counts of the joined
+    /// rooms of all the accounts, see
+    /// [`AccountManager::unread_notification_counts`].
+    ///
+    /// The current counts are emitted immediately, then again every time they
+    /// change. Only the counts of the rooms which changed are computed again.
+    pub fn subscribe_to_unread_notification_counts(
+        &self,
+    ) -> impl Stream<Item = UnreadNotificationsCount> {
+        let mut accounts = self.accounts.subscribe();
+
+        stream! {
+            let mut clients = account_clients(&accounts.get());
+            let mut previous_total = None;
+
+            loop {
+                // Listen to the changes before computing the counts, so that no change is
+                // missed.
+                let mut room_info_updates = select_all(clients.iter().enumerate().map(
+                    |(index, client)| {
+                        BroadcastStream::new(client.room_info_notable_update_receiver())
+                            .map(move |update| (index, update))
+                    },
+                ));
+
+                let mut counts = UnreadNotificationCounts::default();
+
+                for client in &clients {
+                    counts.reset_account(client);
+                }
+
+                if previous_total != Some(counts.total) {
+                    previous_total = Some(counts.total);
+                    yield counts.total;
+                }
+
+                let new_clients = loop {
+                    let update =
+                        match select(pin!(accounts.next()), room_info_updates.next()).await {
+                            Either::Left((accounts, _)) => Either::Left(accounts),
+                            Either::Right((update, _)) => Either::Right(update),
+                        };
+
+                    match update {
+                        // An account was added or removed, let's listen to the new ones.
+                        Either::Left(Some(accounts)) => break account_clients(&accounts),
+                        // The accounts can't change anymore.
+                        Either::Left(None) => return,
+                        Either::Right(Some((index, update))) => {
+                            let client = &clients[index];
+
+                            match update {
+                                Ok(update) => counts.update_room(client, &update.room_id),
+                                // Some updates have been missed, let's compute all the counts of
+                                // the account again.
+                                Err(BroadcastStreamRecvError::Lagged(_)) => {
+                                    counts.reset_account(client)
+                                }
+                            }
+
+                            if previous_total != Some(counts.total) {
+                                previous_total = Some(counts.total);
+                                yield counts.total;
+                            }
+                        }
+                        // There's no account to listen to.
+                        Either::Right(None) => {
+                            let Some(accounts) = accounts.next().await else {
+                                return;
+                            };
+                            break account_clients(&accounts);
+                        }
+                    }
+                };
+
+                clients = new_clients;
+            }
+        }
+    }
+
+    /// Find the account with the given user ID.
+    fn find_account(&self, user_id: &UserId) -> Option<Account> {
+        self.accounts
+            .read()
+            .iter()
+            .find(|account| account.client.user_id() == Some(user_id))
+            .cloned()
+    }
+
+    /// Get the sync services of all the accounts.
+    fn sync_services(&self) -> Vec<Arc<SyncService>> {
+        self.accounts.read().iter().map(|account| account.sync_service.clone()).collect()
+    }
+}
+
+/// The unread notification counts of the joined rooms of several accounts,
+/// and their sum.
+#[derive(Default)]
+struct UnreadNotificationCounts {
+    rooms: HashMap<(OwnedUserId, OwnedRoomId), UnreadNotificationsCount>,
+    total: UnreadNotificationsCount,
+}
+
+impl UnreadNotificationCounts {
+    /// Compute again the counts of all the rooms of the given account.
+    fn reset_account(&mut self, client: &Client) {
+        let Some(user_id) = client.user_id() else {
+            return;
+        };
+
+        let room_ids = self
+            .rooms
+            .keys()
+            .filter(|(room_user_id, _)| room_user_id == user_id)
+            .map(|(_, room_id)| room_id.clone())
+            .chain(client.joined_rooms().into_iter().map(|room| room.room_id().to_owned()))
+            .collect::<Vec<_>>();
+
+        for room_id in room_ids {
+            self.update_room(client, &room_id);
+        }
+    }
+
+    /// Compute again the counts of the given room of the given account.
+    fn update_room(&mut self, client: &Client, room_id: &RoomId) {
+        let Some(user_id) = client.user_id() else {
+            return;
+        };
+
+        let counts = client
+            .get_room(room_id)
+            .filter(|room| room.state() == RoomState::Joined)
+            .map(|room| room.unread_notification_counts())
+            .unwrap_or_default();
+
+        let key = (user_id.to_owned(), room_id.to_owned());
+        let previous = if counts == UnreadNotificationsCount::default() {
+            self.rooms.remove(&key)
+        } else {
+            self.rooms.insert(key, counts)
+        }
+        .unwrap_or_default();
+
+        self.total = UnreadNotificationsCount {
+            highlight_count: self.total.highlight_count - previous.highlight_count
+                + counts.highlight_count,
+            notification_count: self.total.notification_count - previous.notification_count
+                + counts.notification_count,
+        };
+    }
+}
+
+/// The clients of the given accounts.
+fn account_clients(accounts: &[Account]) -> Vec<Client> {
+    accounts.iter().map(|account| account.client.clone()).collect()
+}
+
+/// The rooms of all the accounts, grouped by account.
+fn combined_rooms(rooms: &[Vector<Room>]) -> Vec<Room> {
+    rooms.iter().flatten().cloned().collect()
+}
@@ -18,6 +18,7 @@
 pub use eyeball_im;
 use ruma::html::HtmlSanitizerMode;
 
+pub mod account_manager;
 pub mod encryption_sync_service;
 pub mod notification_client;
 pub mod room_list_service;
@@ -25,7 +26,9 @@ pub mod sync_service;
 pub mod timeline;
 pub mod unable_to_decrypt_hook;
 
-pub use self::{room_list_service::RoomListService, timeline::Timeline};
+pub use self::{
+    account_manager::AccountManager, room_list_service::RoomListService, timeline::Timeline,
+};
 
 /// The default sanitizer mode used when sanitizing HTML.
 const DEFAULT_SANITIZER_MODE: HtmlSanitizerMode = HtmlSanitizerMode::Compat;
@@ -23,18 +23,28 @@ use std::{
 
 use futures_util::{pin_mut, StreamExt as _};
 use matrix_sdk::{
-    room::Room, sleep::sleep, Client, ClientBuildError, SlidingSyncList, SlidingSyncMode,
+    room::{
+        reply::{EnforceThread, Reply, ReplyError},
+        Receipts, Room,
+    },
+    send_queue::RoomSendQueueError,
+    sleep::sleep,
+    Client, ClientBuildError, SlidingSyncList, SlidingSyncMode,
+};
+use matrix_sdk_base::{
+    deserialized_responses::{TimelineEvent, TimelineEventKind},
+    RoomState, StoreError,
 };
-use matrix_sdk_base::{deserialized_responses::TimelineEvent, RoomState, StoreError};
 use ruma::{
     api::client::sync::sync_events::v5 as http,
     assign,
     directory::RoomTypeFilter,
     events::{
+        relation::InReplyTo,
         room::{
             join_rules::JoinRule,
             member::{MembershipState, StrippedRoomMemberEvent},
-            message::{Relation, SyncRoomMessageEvent},
+            message::{Relation, RoomMessageEventContentWithoutRelation, SyncRoomMessageEvent},
         },
         AnyFullStateEventContent, AnyMessageLikeEventContent, AnyStateEvent,
         AnySyncMessageLikeEvent, AnySyncTimelineEvent, FullStateEventContent, StateEventType,
@@ -211,6 +221,81 @@ impl NotificationClient {
         Ok(notification_items)
     }
 
+    /// Send a quick reply to the event of a notification, without having to
+    /// start a sync.
+    ///
+    /// The reply is pushed to the send queue of the room, which persists it
+    /// in the store shared with the app before trying to send it, and
+    /// encrypts it if the room is encrypted. If it can't be sent before the
+    /// notification process is suspended, e.g. because the device is offline,
+    /// it will be sent by the send queue of the app the next time it's
+    /// started.
+    ///
+    /// If the replied-to event isn't in the event cache and can't be fetched
+    /// from the homeserver either, the reply only refers to it by its ID, so
+    /// it's queued nonetheless.
+    ///
+    /// Returns once the reply has been queued.
+    #[instrument(skip(self, body))]
+    pub async fn send_quick_reply(
+        &self,
+        room_id: &RoomId,
+        reply_to: &EventId,
+        body: String,
+    ) -> Result<(), Error> {
+        let room = self.parent_client.get_room(room_id).ok_or(Error::UnknownRoom)?;
+        let content = RoomMessageEventContentWithoutRelation::text_plain(body);
+
+        let content = match room
+            .make_reply_event(
+                content.clone(),
+                Reply {
+                    event_id: reply_to.to_owned(),
+                    enforce_thread: EnforceThread::MaybeThreaded,
+                },
+            )
+            .await
+        {
+            Ok(content) => content,
+            Err(ReplyError::Fetch(error)) => {
+                warn!("Couldn't load the replied-to event, replying without its details: {error}");
+                content.with_relation(Some(Relation::Reply {
+                    in_reply_to: InReplyTo::new(reply_to.to_owned()),
+                }))
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        room.send_queue().send(content.into()).await?;
+
+        Ok(())
+    }
+
+    /// Mark the room of a notification as read, up to the given event,
+    /// without having to start a sync.
+    ///
+    /// This sends a public read receipt and moves the fully read marker to
+    /// the event, and removes the unread flag of the room if it was set.
+    /// Contrary to [`Self::send_quick_reply()`], this isn't queued, so it
+    /// fails if the homeserver can't be reached.
+    #[instrument(skip(self))]
+    pub async fn mark_as_read(&self, room_id: &RoomId, event_id: &EventId) -> Result<(), Error> {
+        let room = self.parent_client.get_room(room_id).ok_or(Error::UnknownRoom)?;
+
+        room.send_multiple_receipts(
+            Receipts::new()
+                .fully_read_marker(event_id.to_owned())
+                .public_read_receipt(event_id.to_owned()),
+        )
+        .await?;
+
+        if room.is_marked_unread() {
+            room.set_unread_flag(false).await?;
+        }
+
+        Ok(())
+    }
+
     /// Run an encryption sync loop, in case an event is still encrypted.
     ///
     /// Will return true if and only:
@@ -354,6 +439,35 @@ impl NotificationClient {
         }
     }
 
+    /// Save an event fetched for a notification in the event cache, when
+    /// running in a dedicated process, so that the main process can display it
+    /// as soon as it loads the room, without fetching it again.
+    ///
+    /// Events which couldn't be decrypted aren't saved: the main process will
+    /// get them from its own sync, and is more likely to decrypt them.
+    async fn save_for_main_process(&self, room_id: &RoomId, event: TimelineEvent) {
+        if !matches!(self.process_setup, NotificationProcessSetup::MultipleProcesses) {
+            return;
+        }
+
+        let is_encrypted = event
+            .raw()
+            .get_field::<TimelineEventType>("type")
+            .ok()
+            .flatten()
+            .is_some_and(is_event_encrypted);
+
+        if is_encrypted || matches!(event.kind, TimelineEventKind::UnableToDecrypt { .. }) {
+            return;
+        }
+
+        if let Err(err) =
+            self.parent_client.event_cache().save_notification_event(room_id, event).await
+        {
+            warn!("Couldn't save the notification event for the main process: {err}");
+        }
+    }
+
     /// Try to run a sliding sync (without encryption) to retrieve the events
     /// from the notification.
     ///
@@ -635,6 +749,8 @@ impl NotificationClient {
                         // Timeline events may be encrypted, so make sure they get decrypted first.
                         match self.retry_decryption(&room, timeline_event).await {
                             Ok(Some(timeline_event)) => {
+                                self.save_for_main_process(&room_id, timeline_event.clone()).await;
+
                                 let push_actions =
                                     timeline_event.push_actions().map(ToOwned::to_owned);
                                 (
@@ -643,6 +759,12 @@ impl NotificationClient {
                                 )
                             }
                             Ok(None) => {
+                                self.save_for_main_process(
+                                    &room_id,
+                                    TimelineEvent::from_plaintext(timeline_event.clone()),
+                                )
+                                .await;
+
                                 match room.event_push_actions(timeline_event).await {
                                     Ok(push_actions) => (raw_event.clone(), push_actions),
                                     Err(error) => {
@@ -755,6 +877,8 @@ impl NotificationClient {
             timeline_event = decrypted_event;
         }
 
+        self.save_for_main_process(room_id, timeline_event.clone()).await;
+
         if let Some(actions) = timeline_event.push_actions() {
             if !actions.iter().any(|a| a.should_notify()) {
                 return Ok(None);
@@ -1061,6 +1185,14 @@ pub enum Error {
     #[error(transparent)]
     SdkError(#[from] matrix_sdk::Error),
 
+    /// The reply to a notification couldn't be created.
+    #[error(transparent)]
+    ReplyError(#[from] ReplyError),
+
+    /// The reply to a notification couldn't be queued.
+    #[error(transparent)]
+    SendQueueError(#[from] RoomSendQueueError),
+
     /// An error forwarded from the underlying state store.
     #[error(transparent)]
     StoreError(#[from] StoreError),
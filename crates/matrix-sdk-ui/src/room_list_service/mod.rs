@@ -54,24 +54,34 @@
 pub mod filters;
 mod room_list;
 pub mod sorters;
+mod space_unread_counts;
+mod stale_rooms;
 mod state;
 
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::Duration,
+};
 
 use async_stream::stream;
 use eyeball::Subscriber;
 use futures_util::{pin_mut, Stream, StreamExt};
 use matrix_sdk::{
     event_cache::EventCacheError, timeout::timeout, Client, Error as SlidingSyncError, Room,
-    SlidingSync, SlidingSyncList, SlidingSyncMode,
+    SlidingSync, SlidingSyncExtension, SlidingSyncList, SlidingSyncMode,
 };
 pub use room_list::*;
 use ruma::{
     api::client::sync::sync_events::v5 as http, assign, directory::RoomTypeFilter,
     events::StateEventType, OwnedRoomId, RoomId, UInt,
 };
+use space_unread_counts::SpaceHierarchy;
+pub use space_unread_counts::SpaceUnreadCounts;
+pub use stale_rooms::*;
 pub use state::*;
 use thiserror::Error;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
 use tracing::debug;
 
 /// The default `required_state` constant value for sliding sync lists and
@@ -103,6 +113,14 @@ const DEFAULT_REQUIRED_STATE: &[(StateEventType, &str)] = &[
 const DEFAULT_ROOM_SUBSCRIPTION_EXTRA_REQUIRED_STATE: &[(StateEventType, &str)] =
     &[(StateEventType::RoomPinnedEvents, "")];
 
+/// The `required_state` constant value for sliding sync space subscriptions
+/// that must be added to `DEFAULT_REQUIRED_STATE`.
+///
+/// The children of a space are required to compute its
+/// [`SpaceUnreadCounts`].
+const SPACE_SUBSCRIPTION_EXTRA_REQUIRED_STATE: &[(StateEventType, &str)] =
+    &[(StateEventType::SpaceChild, "*")];
+
 /// The default `timeline_limit` value when used with room subscriptions.
 const DEFAULT_ROOM_SUBSCRIPTION_TIMELINE_LIMIT: u32 = 20;
 
@@ -365,6 +383,71 @@ impl RoomListService {
         }
     }
 
+    /// Get a [`Stream`] of the [`SpaceUnreadCounts`] of all the joined
+    /// spaces, by space ID.
+    ///
+    /// The counts of a space roll up the counts of all the joined rooms in its
+    /// hierarchy, including the rooms of its subspaces. They are updated every
+    /// time the information of a room changes, and a new map is yielded only
+    /// if a count has changed.
+    ///
+    /// Spaces are excluded from the room lists, so only the spaces the
+    /// [`Client`] knows about are considered. Use
+    /// [`RoomListService::subscribe_to_spaces`] to receive the changes of
+    /// their children.
+    pub fn space_unread_counts(
+        &self,
+    ) -> impl Stream<Item = BTreeMap<OwnedRoomId, SpaceUnreadCounts>> {
+        let client = self.client.clone();
+        let mut room_info_updates = client.room_info_notable_update_receiver();
+
+        stream! {
+            let mut hierarchy = SpaceHierarchy::load(&client).await;
+            let mut counts = hierarchy.counts();
+            yield counts.clone();
+
+            loop {
+                let mut updated_rooms = BTreeSet::new();
+                let mut lagged = false;
+
+                match room_info_updates.recv().await {
+                    Ok(update) => {
+                        updated_rooms.insert(update.room_id);
+                    }
+                    Err(RecvError::Lagged(_)) => lagged = true,
+                    Err(RecvError::Closed) => break,
+                }
+
+                // Handle all the updates received in the meantime at once.
+                loop {
+                    match room_info_updates.try_recv() {
+                        Ok(update) => {
+                            updated_rooms.insert(update.room_id);
+                        }
+                        Err(TryRecvError::Lagged(_)) => lagged = true,
+                        Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+                    }
+                }
+
+                if lagged {
+                    // Some updates have been missed, so everything must be loaded again.
+                    hierarchy = SpaceHierarchy::load(&client).await;
+                } else {
+                    for room_id in updated_rooms {
+                        hierarchy.update_room_with_id(&client, &room_id).await;
+                    }
+                }
+
+                let new_counts = hierarchy.counts();
+
+                if new_counts != counts {
+                    counts = new_counts;
+                    yield counts.clone();
+                }
+            }
+        }
+    }
+
     /// Get the [`Client`] that has been used to create [`Self`].
     pub fn client(&self) -> &Client {
         &self.client
@@ -389,21 +472,88 @@ impl RoomListService {
         self.client.get_room(room_id).ok_or_else(|| Error::RoomNotFound(room_id.to_owned()))
     }
 
+    /// Archive the stale left rooms, according to the given
+    /// [`StaleRoomsGcPolicy`].
+    ///
+    /// The archived rooms are removed from the room lists and from the rooms
+    /// the [`Client`] knows about, but their state is kept in the store. This
+    /// keeps old accounts with many left rooms fast, and is best called right
+    /// after the [`RoomListService`] has been created. An archived room can
+    /// be brought back with [`RoomListService::resurrect_room`].
+    ///
+    /// The archived rooms stay archived across restarts, until they're
+    /// resurrected.
+    ///
+    /// Returns the IDs of the archived rooms.
+    pub async fn archive_stale_rooms(
+        &self,
+        policy: &StaleRoomsGcPolicy,
+    ) -> Result<Vec<OwnedRoomId>, Error> {
+        let stale_rooms = policy.stale_rooms(&self.client.rooms());
+
+        debug!(count = stale_rooms.len(), "Archiving stale rooms");
+
+        let mut archived_rooms = Vec::with_capacity(stale_rooms.len());
+
+        for room_id in stale_rooms {
+            if self.client.archive_room(&room_id).await.map_err(Error::SlidingSync)? {
+                archived_rooms.push(room_id);
+            }
+        }
+
+        Ok(archived_rooms)
+    }
+
+    /// Bring back a room archived by [`RoomListService::archive_stale_rooms`].
+    ///
+    /// The room is loaded from the store and appears in the room lists again.
+    /// If the room hasn't been archived, it's returned as is.
+    pub async fn resurrect_room(&self, room_id: &RoomId) -> Result<Room, Error> {
+        self.client
+            .restore_archived_room(room_id)
+            .await
+            .map_err(Error::SlidingSync)?
+            .ok_or_else(|| Error::RoomNotFound(room_id.to_owned()))
+    }
+
     /// Subscribe to rooms.
     ///
     /// It means that all events from these rooms will be received every time,
     /// no matter how the `RoomList` is configured.
     pub fn subscribe_to_rooms(&self, room_ids: &[&RoomId]) {
+        self.subscribe_with_extra_required_state(
+            room_ids,
+            DEFAULT_ROOM_SUBSCRIPTION_EXTRA_REQUIRED_STATE,
+        );
+    }
+
+    /// Subscribe to spaces.
+    ///
+    /// Like [`RoomListService::subscribe_to_rooms`], but the children of the
+    /// spaces are received too, so that their
+    /// [`RoomListService::space_unread_counts`] are kept up to date.
+    pub fn subscribe_to_spaces(&self, space_ids: &[&RoomId]) {
+        self.subscribe_with_extra_required_state(
+            space_ids,
+            &[
+                DEFAULT_ROOM_SUBSCRIPTION_EXTRA_REQUIRED_STATE,
+                SPACE_SUBSCRIPTION_EXTRA_REQUIRED_STATE,
+            ]
+            .concat(),
+        );
+    }
+
+    fn subscribe_with_extra_required_state(
+        &self,
+        room_ids: &[&RoomId],
+        extra_required_state: &[(StateEventType, &str)],
+    ) {
         let settings = assign!(http::request::RoomSubscription::default(), {
-            required_state: DEFAULT_REQUIRED_STATE.iter().map(|(state_event, value)| {
-                (state_event.clone(), (*value).to_owned())
-            })
-            .chain(
-                DEFAULT_ROOM_SUBSCRIPTION_EXTRA_REQUIRED_STATE.iter().map(|(state_event, value)| {
-                    (state_event.clone(), (*value).to_owned())
-                })
-            )
-            .collect(),
+            required_state: DEFAULT_REQUIRED_STATE
+                .iter()
+                .chain(extra_required_state)
+                .map(|(state_event, value)| (state_event.clone(), (*value).to_owned()))
+                .collect(),
             timeline_limit: UInt::from(DEFAULT_ROOM_SUBSCRIPTION_TIMELINE_LIMIT),
         });
 
@@ -417,6 +567,31 @@ impl RoomListService {
         self.sliding_sync.subscribe_to_rooms(room_ids, Some(settings), cancel_in_flight_request)
     }
 
+    /// Enable or disable a sliding sync extension, e.g. to stop receiving the
+    /// typing notifications and the read receipts to save battery.
+    ///
+    /// The change applies to the next sync request, without restarting the
+    /// sync. See [`SlidingSync::set_extension_enabled`] for the details.
+    ///
+    /// The [`SlidingSyncExtension::E2ee`] and
+    /// [`SlidingSyncExtension::ToDevice`] extensions are refused: they're
+    /// handled by the encryption sync, see
+    /// [`EncryptionSyncService`](crate::encryption_sync_service::EncryptionSyncService).
+    pub async fn set_extension_enabled(
+        &self,
+        extension: SlidingSyncExtension,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        if matches!(extension, SlidingSyncExtension::E2ee | SlidingSyncExtension::ToDevice) {
+            return Err(Error::UnsupportedExtension(extension));
+        }
+
+        self.sliding_sync
+            .set_extension_enabled(extension, enabled)
+            .await
+            .map_err(Error::SlidingSync)
+    }
+
     #[cfg(test)]
     pub fn sliding_sync(&self) -> &SlidingSync {
         &self.sliding_sync
@@ -438,6 +613,10 @@ pub enum Error {
     #[error("Room `{0}` not found")]
     RoomNotFound(OwnedRoomId),
 
+    /// The extension can't be enabled or disabled on the room list sync.
+    #[error("The `{0:?}` extension isn't handled by the room list sync")]
+    UnsupportedExtension(SlidingSyncExtension),
+
     #[error(transparent)]
     EventCache(#[from] EventCacheError),
 }
@@ -468,9 +647,11 @@ pub enum SyncIndicator {
 mod tests {
     use std::future::ready;
 
+    use assert_matches::assert_matches;
     use futures_util::{pin_mut, StreamExt};
     use matrix_sdk::{
-        config::RequestConfig, test_utils::client::mock_matrix_session, Client, SlidingSyncMode,
+        config::RequestConfig, test_utils::client::mock_matrix_session, Client,
+        SlidingSyncExtension, SlidingSyncMode,
     };
     use matrix_sdk_test::async_test;
     use ruma::api::MatrixVersion;
@@ -529,6 +710,25 @@ mod tests {
         Ok(())
     }
 
+    #[async_test]
+    async fn test_set_extension_enabled() -> Result<(), Error> {
+        let room_list = new_room_list().await?;
+
+        room_list.set_extension_enabled(SlidingSyncExtension::Typing, false).await?;
+        assert!(!room_list.sliding_sync().is_extension_enabled(SlidingSyncExtension::Typing));
+
+        // The encryption extensions are handled by the encryption sync.
+        for extension in [SlidingSyncExtension::E2ee, SlidingSyncExtension::ToDevice] {
+            assert_matches!(
+                room_list.set_extension_enabled(extension, true).await,
+                Err(Error::UnsupportedExtension(_))
+            );
+            assert!(!room_list.sliding_sync().is_extension_enabled(extension));
+        }
+
+        Ok(())
+    }
+
     #[async_test]
     async fn test_expire_sliding_sync_session_manually() -> Result<(), Error> {
         let (client, server) = new_client().await;
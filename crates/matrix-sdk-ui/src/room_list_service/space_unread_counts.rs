@@ -0,0 +1,309 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use matrix_sdk::{deserialized_responses::SyncOrStrippedState, Client, Room, RoomState};
+use ruma::{
+    events::{space::child::SpaceChildEventContent, SyncStateEvent},
+    OwnedRoomId, RoomId,
+};
+use tracing::warn;
+
+/// The unread counts of all the rooms of a space, including the rooms of its
+/// subspaces.
+///
+/// A room that appears several times in the hierarchy of a space is counted
+/// once.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SpaceUnreadCounts {
+    /// The number of unread messages, see [`Room::num_unread_messages`].
+    pub num_unread_messages: u64,
+
+    /// The number of unread notifications, see
+    /// [`Room::num_unread_notifications`].
+    pub num_unread_notifications: u64,
+
+    /// The number of unread mentions, see [`Room::num_unread_mentions`].
+    pub num_unread_mentions: u64,
+}
+
+impl SpaceUnreadCounts {
+    fn for_room(room: &Room) -> Self {
+        Self {
+            num_unread_messages: room.num_unread_messages(),
+            num_unread_notifications: room.num_unread_notifications(),
+            num_unread_mentions: room.num_unread_mentions(),
+        }
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.num_unread_messages += other.num_unread_messages;
+        self.num_unread_notifications += other.num_unread_notifications;
+        self.num_unread_mentions += other.num_unread_mentions;
+    }
+}
+
+/// The hierarchy of the joined spaces of the client, with the counts of the
+/// joined rooms which aren't spaces.
+///
+/// It's kept up to date room by room, so that an update of a room doesn't
+/// require to load all the spaces again.
+#[derive(Debug, Default)]
+pub(super) struct SpaceHierarchy {
+    /// The children of every joined space.
+    space_children: BTreeMap<OwnedRoomId, Vec<OwnedRoomId>>,
+
+    /// The counts of every joined room which isn't a space.
+    room_counts: BTreeMap<OwnedRoomId, SpaceUnreadCounts>,
+}
+
+impl SpaceHierarchy {
+    /// Load the hierarchy of all the joined spaces of the client.
+    pub(super) async fn load(client: &Client) -> Self {
+        let mut this = Self::default();
+
+        for room in client.joined_rooms() {
+            this.update_room(&room).await;
+        }
+
+        this
+    }
+
+    /// Update the hierarchy with the room with the given ID, after it has
+    /// changed.
+    pub(super) async fn update_room_with_id(&mut self, client: &Client, room_id: &RoomId) {
+        match client.get_room(room_id) {
+            Some(room) if room.state() == RoomState::Joined => self.update_room(&room).await,
+            _ => self.remove_room(room_id),
+        }
+    }
+
+    /// Update the hierarchy with the given joined room.
+    async fn update_room(&mut self, room: &Room) {
+        if room.is_space() {
+            let children = space_children_of(room).await;
+            self.set_space_children(room.room_id().to_owned(), children);
+        } else {
+            self.set_room_counts(room.room_id().to_owned(), SpaceUnreadCounts::for_room(room));
+        }
+    }
+
+    fn set_space_children(&mut self, space_id: OwnedRoomId, children: Vec<OwnedRoomId>) {
+        self.room_counts.remove(&space_id);
+        self.space_children.insert(space_id, children);
+    }
+
+    fn set_room_counts(&mut self, room_id: OwnedRoomId, counts: SpaceUnreadCounts) {
+        self.space_children.remove(&room_id);
+        self.room_counts.insert(room_id, counts);
+    }
+
+    fn remove_room(&mut self, room_id: &RoomId) {
+        self.space_children.remove(room_id);
+        self.room_counts.remove(room_id);
+    }
+
+    /// Compute the [`SpaceUnreadCounts`] of every space of the hierarchy.
+    pub(super) fn counts(&self) -> BTreeMap<OwnedRoomId, SpaceUnreadCounts> {
+        roll_up(&self.space_children, &self.room_counts)
+    }
+}
+
+/// Get the valid `m.space.child` of a space.
+async fn space_children_of(space: &Room) -> Vec<OwnedRoomId> {
+    let events = match space.get_state_events_static::<SpaceChildEventContent>().await {
+        Ok(events) => events,
+        Err(error) => {
+            warn!(room_id = ?space.room_id(), "Couldn't load the children of a space: {error}");
+            return Vec::new();
+        }
+    };
+
+    events
+        .into_iter()
+        .filter_map(|event| match event.deserialize() {
+            // A child without `via` isn't part of the space anymore.
+            Ok(SyncOrStrippedState::Sync(SyncStateEvent::Original(event)))
+                if !event.content.via.is_empty() =>
+            {
+                Some(event.state_key)
+            }
+            Ok(_) => None,
+            Err(error) => {
+                warn!(room_id = ?space.room_id(), "Couldn't deserialize m.space.child: {error}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Sum the counts of the rooms of every space, recursively.
+///
+/// `space_children` maps every space to its children, and `room_counts` maps
+/// every room which isn't a space to its counts. Children which are neither
+/// known spaces nor known rooms are ignored.
+fn roll_up(
+    space_children: &BTreeMap<OwnedRoomId, Vec<OwnedRoomId>>,
+    room_counts: &BTreeMap<OwnedRoomId, SpaceUnreadCounts>,
+) -> BTreeMap<OwnedRoomId, SpaceUnreadCounts> {
+    space_children
+        .keys()
+        .map(|space_id| {
+            let mut counts = SpaceUnreadCounts::default();
+
+            for room_id in descendant_rooms(space_id, space_children) {
+                if let Some(room_counts) = room_counts.get(room_id) {
+                    counts.add(room_counts);
+                }
+            }
+
+            (space_id.clone(), counts)
+        })
+        .collect()
+}
+
+/// Get the rooms in the hierarchy of a space, without duplicates, and without
+/// being stuck in cycles.
+fn descendant_rooms<'a>(
+    space_id: &'a RoomId,
+    space_children: &'a BTreeMap<OwnedRoomId, Vec<OwnedRoomId>>,
+) -> BTreeSet<&'a RoomId> {
+    let mut visited_spaces = BTreeSet::from([space_id]);
+    let mut rooms = BTreeSet::new();
+    let mut to_visit = vec![space_id];
+
+    while let Some(space_id) = to_visit.pop() {
+        let Some(children) = space_children.get(space_id) else {
+            continue;
+        };
+
+        for child in children {
+            if space_children.contains_key(child) {
+                if visited_spaces.insert(child) {
+                    to_visit.push(child);
+                }
+            } else {
+                rooms.insert(child.as_ref());
+            }
+        }
+    }
+
+    rooms
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use ruma::{owned_room_id, OwnedRoomId};
+
+    use super::{roll_up, SpaceHierarchy, SpaceUnreadCounts};
+
+    fn counts(num_unread_messages: u64, num_unread_mentions: u64) -> SpaceUnreadCounts {
+        SpaceUnreadCounts {
+            num_unread_messages,
+            num_unread_notifications: num_unread_messages,
+            num_unread_mentions,
+        }
+    }
+
+    #[test]
+    fn test_roll_up_subspaces() {
+        let space_children: BTreeMap<OwnedRoomId, _> = BTreeMap::from([
+            (
+                owned_room_id!("!parent:b.c"),
+                vec![owned_room_id!("!a:b.c"), owned_room_id!("!sub:b.c")],
+            ),
+            (owned_room_id!("!sub:b.c"), vec![owned_room_id!("!b:b.c")]),
+        ]);
+        let room_counts = BTreeMap::from([
+            (owned_room_id!("!a:b.c"), counts(1, 0)),
+            (owned_room_id!("!b:b.c"), counts(2, 1)),
+            (owned_room_id!("!other:b.c"), counts(4, 4)),
+        ]);
+
+        let rolled_up = roll_up(&space_children, &room_counts);
+
+        assert_eq!(rolled_up.len(), 2);
+        assert_eq!(rolled_up[&owned_room_id!("!parent:b.c")], counts(3, 1));
+        assert_eq!(rolled_up[&owned_room_id!("!sub:b.c")], counts(2, 1));
+    }
+
+    #[test]
+    fn test_roll_up_deduplicates_rooms() {
+        // `!a` is both a direct child of the parent space, and a child of its subspace.
+        let space_children: BTreeMap<OwnedRoomId, _> = BTreeMap::from([
+            (
+                owned_room_id!("!parent:b.c"),
+                vec![owned_room_id!("!a:b.c"), owned_room_id!("!sub:b.c")],
+            ),
+            (
+                owned_room_id!("!sub:b.c"),
+                vec![owned_room_id!("!a:b.c"), owned_room_id!("!unknown:b.c")],
+            ),
+        ]);
+        let room_counts = BTreeMap::from([(owned_room_id!("!a:b.c"), counts(1, 1))]);
+
+        let rolled_up = roll_up(&space_children, &room_counts);
+
+        assert_eq!(rolled_up[&owned_room_id!("!parent:b.c")], counts(1, 1));
+        assert_eq!(rolled_up[&owned_room_id!("!sub:b.c")], counts(1, 1));
+    }
+
+    #[test]
+    fn test_roll_up_with_cycles() {
+        let space_children: BTreeMap<OwnedRoomId, _> = BTreeMap::from([
+            (owned_room_id!("!x:b.c"), vec![owned_room_id!("!y:b.c"), owned_room_id!("!a:b.c")]),
+            (owned_room_id!("!y:b.c"), vec![owned_room_id!("!x:b.c"), owned_room_id!("!b:b.c")]),
+        ]);
+        let room_counts = BTreeMap::from([
+            (owned_room_id!("!a:b.c"), counts(1, 0)),
+            (owned_room_id!("!b:b.c"), counts(2, 0)),
+        ]);
+
+        let rolled_up = roll_up(&space_children, &room_counts);
+
+        assert_eq!(rolled_up[&owned_room_id!("!x:b.c")], counts(3, 0));
+        assert_eq!(rolled_up[&owned_room_id!("!y:b.c")], counts(3, 0));
+    }
+
+    #[test]
+    fn test_hierarchy_is_updated_room_by_room() {
+        let mut hierarchy = SpaceHierarchy::default();
+        hierarchy.set_space_children(
+            owned_room_id!("!space:b.c"),
+            vec![owned_room_id!("!a:b.c"), owned_room_id!("!b:b.c")],
+        );
+        hierarchy.set_room_counts(owned_room_id!("!a:b.c"), counts(1, 0));
+
+        assert_eq!(hierarchy.counts()[&owned_room_id!("!space:b.c")], counts(1, 0));
+
+        // A room of the space gets new messages.
+        hierarchy.set_room_counts(owned_room_id!("!b:b.c"), counts(2, 1));
+        assert_eq!(hierarchy.counts()[&owned_room_id!("!space:b.c")], counts(3, 1));
+
+        // A room of the space is left.
+        hierarchy.remove_room(owned_room_id!("!a:b.c").as_ref());
+        assert_eq!(hierarchy.counts()[&owned_room_id!("!space:b.c")], counts(2, 1));
+
+        // The children of the space change.
+        hierarchy.set_space_children(owned_room_id!("!space:b.c"), vec![]);
+        assert_eq!(hierarchy.counts()[&owned_room_id!("!space:b.c")], counts(0, 0));
+
+        // The space is left.
+        hierarchy.remove_room(owned_room_id!("!space:b.c").as_ref());
+        assert!(hierarchy.counts().is_empty());
+    }
+}
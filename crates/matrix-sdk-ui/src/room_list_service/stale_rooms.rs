@@ -0,0 +1,144 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use matrix_sdk::{Room, RoomState};
+use ruma::{MilliSecondsSinceUnixEpoch, OwnedRoomId};
+
+/// A policy describing which left rooms are stale, and should be archived out
+/// of the room list by [`RoomListService::archive_stale_rooms`].
+///
+/// The age of a room is computed from the timestamp of its latest event. Left
+/// rooms without a latest event are considered older than any other room.
+///
+/// Both limits can be combined; a left room is stale as soon as it exceeds
+/// one of them. The default policy considers no room as stale.
+///
+/// [`RoomListService::archive_stale_rooms`]: super::RoomListService::archive_stale_rooms
+#[derive(Clone, Debug, Default)]
+pub struct StaleRoomsGcPolicy {
+    /// Left rooms whose latest event is older than this are stale.
+    pub max_age: Option<Duration>,
+
+    /// The maximum number of left rooms to keep; the oldest left rooms beyond
+    /// this count are stale.
+    pub max_count: Option<usize>,
+}
+
+impl StaleRoomsGcPolicy {
+    /// Find the stale rooms among `rooms`, according to this policy.
+    pub(super) fn stale_rooms(&self, rooms: &[Room]) -> Vec<OwnedRoomId> {
+        let left_rooms = rooms
+            .iter()
+            .filter(|room| room.state() == RoomState::Left)
+            .map(|room| {
+                let latest_event_ts = room.latest_event().and_then(|latest_event| {
+                    latest_event.event().raw().get_field("origin_server_ts").ok().flatten()
+                });
+
+                (room.room_id().to_owned(), latest_event_ts)
+            })
+            .collect();
+
+        self.stale_rooms_at(left_rooms, MilliSecondsSinceUnixEpoch::now())
+    }
+
+    /// Find the stale rooms among the given left rooms and the timestamps of
+    /// their latest event, at the given point in time.
+    fn stale_rooms_at(
+        &self,
+        mut left_rooms: Vec<(OwnedRoomId, Option<MilliSecondsSinceUnixEpoch>)>,
+        now: MilliSecondsSinceUnixEpoch,
+    ) -> Vec<OwnedRoomId> {
+        // Sort from the most recent to the oldest room; `None` sorts first, so the
+        // order is reversed.
+        left_rooms.sort_by(|(_, left), (_, right)| right.cmp(left));
+
+        let now = now.as_secs();
+
+        left_rooms
+            .into_iter()
+            .enumerate()
+            .filter(|(nth, (_, latest_event_ts))| {
+                let too_many = self.max_count.is_some_and(|max_count| *nth >= max_count);
+                let too_old = self.max_age.is_some_and(|max_age| match latest_event_ts {
+                    Some(latest_event_ts) => {
+                        u64::from(now.saturating_sub(latest_event_ts.as_secs())) > max_age.as_secs()
+                    }
+                    None => true,
+                });
+
+                too_many || too_old
+            })
+            .map(|(_, (room_id, _))| room_id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ruma::{owned_room_id, MilliSecondsSinceUnixEpoch, UInt};
+
+    use super::StaleRoomsGcPolicy;
+
+    fn ts(secs: u32) -> Option<MilliSecondsSinceUnixEpoch> {
+        Some(MilliSecondsSinceUnixEpoch(UInt::from(secs) * UInt::from(1000u32)))
+    }
+
+    #[test]
+    fn test_stale_rooms() {
+        let now = ts(1_000).unwrap();
+        let left_rooms = vec![
+            (owned_room_id!("!r0:bar.org"), ts(900)),
+            (owned_room_id!("!r1:bar.org"), ts(100)),
+            (owned_room_id!("!r2:bar.org"), None),
+            (owned_room_id!("!r3:bar.org"), ts(500)),
+        ];
+
+        // The default policy keeps everything.
+        let policy = StaleRoomsGcPolicy::default();
+        assert!(policy.stale_rooms_at(left_rooms.clone(), now).is_empty());
+
+        // Rooms older than the max age are stale, as well as rooms without a latest
+        // event.
+        let policy =
+            StaleRoomsGcPolicy { max_age: Some(Duration::from_secs(600)), max_count: None };
+        assert_eq!(
+            policy.stale_rooms_at(left_rooms.clone(), now),
+            vec![owned_room_id!("!r1:bar.org"), owned_room_id!("!r2:bar.org")]
+        );
+
+        // The oldest rooms beyond the max count are stale.
+        let policy = StaleRoomsGcPolicy { max_age: None, max_count: Some(2) };
+        assert_eq!(
+            policy.stale_rooms_at(left_rooms.clone(), now),
+            vec![owned_room_id!("!r1:bar.org"), owned_room_id!("!r2:bar.org")]
+        );
+
+        // Both limits are combined.
+        let policy =
+            StaleRoomsGcPolicy { max_age: Some(Duration::from_secs(200)), max_count: Some(3) };
+        assert_eq!(
+            policy.stale_rooms_at(left_rooms, now),
+            vec![
+                owned_room_id!("!r3:bar.org"),
+                owned_room_id!("!r1:bar.org"),
+                owned_room_id!("!r2:bar.org")
+            ]
+        );
+    }
+}
@@ -36,7 +36,7 @@ use tracing::{info_span, instrument, trace, warn, Instrument, Span};
 use super::{
     controller::{TimelineController, TimelineSettings},
     to_device::{handle_forwarded_room_key_event, handle_room_key_event},
-    DateDividerMode, Error, Timeline, TimelineDropHandle, TimelineFocus,
+    DateDividerMode, Error, ProfileResolution, Timeline, TimelineDropHandle, TimelineFocus,
 };
 use crate::{timeline::event_item::RemoteEventOrigin, unable_to_decrypt_hook::UtdHookManager};
 
@@ -55,6 +55,10 @@ pub struct TimelineBuilder {
 
     /// An optional prefix for internal IDs.
     internal_id_prefix: Option<String>,
+
+    /// An optional maximum size for the cache of decrypted media content, in
+    /// bytes.
+    media_cache_max_size: Option<usize>,
 }
 
 impl TimelineBuilder {
@@ -65,6 +69,7 @@ impl TimelineBuilder {
             unable_to_decrypt_hook: None,
             focus: TimelineFocus::Live { hide_threaded_events: false },
             internal_id_prefix: None,
+            media_cache_max_size: None,
         }
     }
 
@@ -94,6 +99,15 @@ impl TimelineBuilder {
         self
     }
 
+    /// Sets the maximum size, in bytes, of the cache of decrypted media
+    /// content used by [`Timeline::fetch_media_content`].
+    ///
+    /// Defaults to 32 MiB. Setting it to 0 disables the cache.
+    pub fn with_media_cache_max_size(mut self, max_size: usize) -> Self {
+        self.media_cache_max_size = Some(max_size);
+        self
+    }
+
     /// Chose when to insert the date separators, either in between each day
     /// or each month.
     pub fn with_date_divider_mode(mut self, mode: DateDividerMode) -> Self {
@@ -101,6 +115,27 @@ impl TimelineBuilder {
         self
     }
 
+    /// Stamp the local echoes with the time of the homeserver rather than the
+    /// time of the local clock.
+    ///
+    /// The skew between both clocks is estimated from the age of the events
+    /// received from sync. This avoids misplacing the local echoes and their
+    /// date dividers on devices with a wrong clock.
+    ///
+    /// Disabled by default.
+    pub fn with_clock_skew_correction(mut self, enabled: bool) -> Self {
+        self.settings.correct_clock_skew = enabled;
+        self
+    }
+
+    /// Choose how the profiles of the senders are resolved.
+    ///
+    /// Defaults to [`ProfileResolution::RoomMembership`].
+    pub fn with_profile_resolution(mut self, resolution: ProfileResolution) -> Self {
+        self.settings.profile_resolution = resolution;
+        self
+    }
+
     /// Enable tracking of the fully-read marker and the read receipts on the
     /// timeline.
     pub fn track_read_marker_and_receipts(mut self) -> Self {
@@ -161,7 +196,14 @@ impl TimelineBuilder {
         )
     )]
     pub async fn build(self) -> Result<Timeline, Error> {
-        let Self { room, settings, unable_to_decrypt_hook, focus, internal_id_prefix } = self;
+        let Self {
+            room,
+            settings,
+            unable_to_decrypt_hook,
+            focus,
+            internal_id_prefix,
+            media_cache_max_size,
+        } = self;
 
         let client = room.client();
         let event_cache = client.event_cache();
@@ -179,6 +221,8 @@ impl TimelineBuilder {
             .ok()
             .unwrap_or_default();
 
+        let profile_resolution = settings.profile_resolution;
+
         let controller = TimelineController::new(
             room.clone(),
             focus.clone(),
@@ -188,6 +232,10 @@ impl TimelineBuilder {
         )
         .with_settings(settings);
 
+        if let Some(max_size) = media_cache_max_size {
+            controller.media_cache().await.set_max_size(max_size);
+        }
+
         let has_events = controller.init_focus(&room_event_cache).await?;
 
         let pinned_events_join_handle = if matches!(focus, TimelineFocus::PinnedEvents { .. }) {
@@ -196,6 +244,9 @@ impl TimelineBuilder {
             None
         };
 
+        let global_profiles_join_handle = (profile_resolution == ProfileResolution::GlobalProfile)
+            .then(|| spawn(global_profiles_task(controller.clone())));
+
         let encryption_changes_handle = spawn({
             let inner = controller.clone();
             async move {
@@ -293,6 +344,7 @@ impl TimelineBuilder {
                 event_handler_handles: event_handlers,
                 room_update_join_handle,
                 pinned_events_join_handle,
+                global_profiles_join_handle,
                 room_key_from_backups_join_handle,
                 room_key_backup_enabled_join_handle,
                 room_keys_received_join_handle,
@@ -352,6 +404,20 @@ where
     }
 }
 
+/// The task that fetches the global profiles of the senders, when the profiles
+/// are resolved with [`ProfileResolution::GlobalProfile`].
+///
+/// It runs separately from the other tasks, so the requests to the homeserver
+/// don't hold back the updates of the timeline.
+async fn global_profiles_task(timeline_controller: TimelineController) {
+    loop {
+        // Fetch the global profiles of the senders seen so far, e.g. of the initial
+        // events, then wait for new ones.
+        timeline_controller.fetch_missing_global_profiles().await;
+        timeline_controller.wait_for_missing_global_profiles().await;
+    }
+}
+
 /// The task that handles the [`RoomEventCacheUpdate`]s.
 async fn room_event_cache_updates_task(
     room_event_cache: RoomEventCache,
@@ -397,7 +463,7 @@ async fn room_event_cache_updates_task(
                 let origin = match origin {
                     EventsOrigin::Sync => RemoteEventOrigin::Sync,
                     EventsOrigin::Pagination => RemoteEventOrigin::Pagination,
-                    EventsOrigin::Cache => RemoteEventOrigin::Cache,
+                    EventsOrigin::Cache | EventsOrigin::Import => RemoteEventOrigin::Cache,
                 };
 
                 let has_diffs = !diffs.is_empty();
@@ -0,0 +1,177 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Estimation of the skew between the local clock and the clock of the
+//! homeserver.
+//!
+//! Devices with a wrong clock give wrong timestamps to the local echoes, which
+//! are then misplaced relative to the remote events, and scramble the date
+//! dividers. The homeserver tells how long ago it received each event in the
+//! `unsigned.age` field of the events received from sync, so we can estimate
+//! what time it is on the homeserver when we receive them.
+
+use std::collections::VecDeque;
+
+use ruma::{events::AnySyncTimelineEvent, serde::Raw, Int, MilliSecondsSinceUnixEpoch, UInt};
+use serde::Deserialize;
+
+/// The maximum number of samples used to estimate the skew.
+const MAX_SAMPLES: usize = 20;
+
+/// Estimates the skew between the local clock and the clock of the homeserver,
+/// from the age of the events received from sync.
+#[derive(Clone, Debug, Default)]
+pub(super) struct ClockSkewEstimator {
+    /// The most recent offsets between the clock of the homeserver and the
+    /// local clock, in milliseconds, from the oldest to the most recent.
+    samples: VecDeque<i64>,
+}
+
+impl ClockSkewEstimator {
+    /// Record a sample from an event received from sync at `received_at`,
+    /// according to the local clock.
+    ///
+    /// Events without an `unsigned.age` field are ignored.
+    pub fn record(
+        &mut self,
+        raw: &Raw<AnySyncTimelineEvent>,
+        origin_server_ts: MilliSecondsSinceUnixEpoch,
+        received_at: MilliSecondsSinceUnixEpoch,
+    ) {
+        #[derive(Deserialize)]
+        struct Unsigned {
+            age: Option<Int>,
+        }
+
+        let Some(age) = raw.get_field::<Unsigned>("unsigned").ok().flatten().and_then(|u| u.age)
+        else {
+            return;
+        };
+
+        // The homeserver received the event `age` milliseconds ago, so this is what
+        // time it is on the homeserver.
+        let server_now = millis(origin_server_ts) + i64::from(age);
+        self.push_sample(server_now - millis(received_at));
+    }
+
+    fn push_sample(&mut self, offset: i64) {
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(offset);
+    }
+
+    /// The estimated offset between the clock of the homeserver and the local
+    /// clock, in milliseconds, or `None` if no sample has been recorded yet.
+    ///
+    /// It is positive if the local clock is late.
+    ///
+    /// The median of the recent samples is used, so that a few events with a
+    /// bogus age don't skew the estimation.
+    pub fn offset(&self) -> Option<i64> {
+        let mut samples = self.samples.iter().copied().collect::<Vec<_>>();
+        samples.sort_unstable();
+        samples.get(samples.len() / 2).copied()
+    }
+
+    /// The current time, as seen by the homeserver.
+    ///
+    /// Falls back to the local clock if no sample has been recorded yet.
+    pub fn now(&self) -> MilliSecondsSinceUnixEpoch {
+        self.correct(MilliSecondsSinceUnixEpoch::now())
+    }
+
+    /// Correct a timestamp from the local clock with the estimated offset.
+    fn correct(&self, local: MilliSecondsSinceUnixEpoch) -> MilliSecondsSinceUnixEpoch {
+        let Some(offset) = self.offset() else {
+            return local;
+        };
+
+        let corrected = (millis(local) + offset).max(0);
+        MilliSecondsSinceUnixEpoch(UInt::try_from(corrected).unwrap_or(local.0))
+    }
+}
+
+fn millis(ts: MilliSecondsSinceUnixEpoch) -> i64 {
+    i64::from(ts.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::{serde::Raw, uint, MilliSecondsSinceUnixEpoch};
+    use serde_json::json;
+
+    use super::ClockSkewEstimator;
+
+    fn event(origin_server_ts: u64, age: Option<i64>) -> Raw<ruma::events::AnySyncTimelineEvent> {
+        let mut event = json!({
+            "type": "m.room.message",
+            "event_id": "$event",
+            "sender": "@alice:example.org",
+            "origin_server_ts": origin_server_ts,
+            "content": { "msgtype": "m.text", "body": "hello" },
+        });
+        if let Some(age) = age {
+            event["unsigned"] = json!({ "age": age });
+        }
+        Raw::new(&event).unwrap().cast()
+    }
+
+    fn ts(millis: u64) -> MilliSecondsSinceUnixEpoch {
+        MilliSecondsSinceUnixEpoch(millis.try_into().unwrap())
+    }
+
+    #[test]
+    fn test_no_correction_without_samples() {
+        let mut estimator = ClockSkewEstimator::default();
+        assert_eq!(estimator.offset(), None);
+        assert_eq!(estimator.correct(ts(1_000)), ts(1_000));
+
+        // Events without an age don't help.
+        estimator.record(&event(5_000, None), ts(5_000), ts(1_000));
+        assert_eq!(estimator.offset(), None);
+    }
+
+    #[test]
+    fn test_local_clock_is_late() {
+        let mut estimator = ClockSkewEstimator::default();
+
+        // The event was sent 2 seconds ago at 10s on the homeserver, so it's 12s on
+        // the homeserver, but only 2s locally.
+        estimator.record(&event(10_000, Some(2_000)), ts(10_000), ts(2_000));
+        assert_eq!(estimator.offset(), Some(10_000));
+        assert_eq!(estimator.correct(ts(3_000)), ts(13_000));
+    }
+
+    #[test]
+    fn test_local_clock_is_early() {
+        let mut estimator = ClockSkewEstimator::default();
+
+        estimator.record(&event(10_000, Some(0)), ts(10_000), ts(70_000));
+        assert_eq!(estimator.offset(), Some(-60_000));
+        assert_eq!(estimator.correct(ts(71_000)).0, uint!(11_000));
+    }
+
+    #[test]
+    fn test_outliers_are_ignored() {
+        let mut estimator = ClockSkewEstimator::default();
+
+        estimator.record(&event(10_000, Some(0)), ts(10_000), ts(5_000));
+        estimator.record(&event(11_000, Some(0)), ts(11_000), ts(6_000));
+        // A bogus age.
+        estimator.record(&event(12_000, Some(1_000_000)), ts(12_000), ts(7_000));
+
+        assert_eq!(estimator.offset(), Some(5_000));
+    }
+}
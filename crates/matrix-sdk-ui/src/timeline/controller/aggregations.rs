@@ -54,8 +54,9 @@ use tracing::{info, trace, warn};
 
 use super::{rfind_event_by_item_id, ObservableItemsTransaction};
 use crate::timeline::{
-    EventTimelineItem, MsgLikeContent, MsgLikeKind, PollState, ReactionInfo, ReactionStatus,
-    TimelineEventItemId, TimelineItem, TimelineItemContent,
+    event_item::extract_edit_new_content, EventTimelineItem, MsgLikeContent, MsgLikeKind,
+    PollState, ReactionInfo, ReactionStatus, TimelineEventItemId, TimelineItem,
+    TimelineItemContent,
 };
 
 #[derive(Clone)]
@@ -686,6 +687,10 @@ fn edit_item(item: &mut Cow<'_, EventTimelineItem>, edit: PendingEdit) -> bool {
             let mut new_msg = msg.clone();
             new_msg.apply_edit(replacement.new_content);
 
+            if let Some(new_content) = edit_json.as_ref().and_then(extract_edit_new_content) {
+                new_msg.set_additional_content(new_content);
+            }
+
             let new_item = item.with_content_and_latest_edit(
                 TimelineItemContent::MsgLike(content.with_kind(MsgLikeKind::Message(new_msg))),
                 edit_json,
@@ -26,8 +26,9 @@ use ruma::{
         AnySyncMessageLikeEvent, AnySyncTimelineEvent, BundledMessageLikeRelations,
     },
     serde::Raw,
-    EventId, OwnedEventId, OwnedUserId, RoomVersionId,
+    EventId, OwnedEventId, OwnedUserId, RoomVersionId, UserId,
 };
+use tokio::sync::Notify;
 use tracing::trace;
 
 use super::{
@@ -38,12 +39,15 @@ use super::{
 };
 use crate::{
     timeline::{
+        clock_skew::ClockSkewEstimator,
         controller::TimelineFocusKind,
         event_item::{
             extract_bundled_edit_event_json, extract_poll_edit_content,
             extract_room_msg_edit_content,
         },
-        InReplyToDetails, TimelineEventItemId,
+        media_cache::DecryptedMediaCache,
+        traits::RoomDataProvider,
+        InReplyToDetails, Profile, ProfileResolution, TimelineEventItemId,
     },
     unable_to_decrypt_hook::UtdHookManager,
 };
@@ -89,6 +93,12 @@ pub(in crate::timeline) struct TimelineMetadata {
     /// The own [`OwnedUserId`] of the client who opened the timeline.
     pub(crate) own_user_id: OwnedUserId,
 
+    /// The cache of decrypted media content for the events of this timeline.
+    ///
+    /// This value is constant over the lifetime of the metadata, and shared
+    /// with the [`Timeline`](crate::timeline::Timeline).
+    pub media_cache: DecryptedMediaCache,
+
     // **** DYNAMIC FIELDS ****
     /// The next internal identifier for timeline items, used for both local and
     /// remote echoes.
@@ -125,6 +135,32 @@ pub(in crate::timeline) struct TimelineMetadata {
     ///
     /// TODO: move this over to the event cache (see also #3058).
     pub(super) read_receipts: ReadReceipts,
+
+    /// The estimated skew between the local clock and the clock of the
+    /// homeserver.
+    ///
+    /// This is never cleared, since it doesn't depend on the events of the
+    /// timeline.
+    pub(super) clock_skew: ClockSkewEstimator,
+
+    /// The profiles of the senders that were resolved outside of the room
+    /// memberships, i.e. global profiles, or profiles requested on demand.
+    ///
+    /// This isn't cleared with the timeline, since it doesn't depend on its
+    /// events, but the profile of a sender is invalidated when their
+    /// membership changes, see [`Self::invalidate_sender_profile`].
+    pub(super) sender_profiles: HashMap<OwnedUserId, Profile>,
+
+    /// The senders whose global profile has been requested, so that it's
+    /// fetched at most once per sender, unless the fetch fails.
+    requested_global_profiles: BTreeSet<OwnedUserId>,
+
+    /// The senders whose global profile has been requested, but not fetched
+    /// yet.
+    pub(super) missing_global_profiles: BTreeSet<OwnedUserId>,
+
+    /// Notified when a sender is added to [`Self::missing_global_profiles`].
+    pub(super) missing_global_profiles_notify: Arc<Notify>,
 }
 
 impl TimelineMetadata {
@@ -150,9 +186,61 @@ impl TimelineMetadata {
             unable_to_decrypt_hook,
             internal_id_prefix,
             is_room_encrypted,
+            media_cache: Default::default(),
+            clock_skew: Default::default(),
+            sender_profiles: Default::default(),
+            requested_global_profiles: Default::default(),
+            missing_global_profiles: Default::default(),
+            missing_global_profiles_notify: Default::default(),
+        }
+    }
+
+    /// Resolve the profile of the sender of an event, according to the given
+    /// [`ProfileResolution`].
+    ///
+    /// A profile that was resolved on demand, or a global profile that was
+    /// already fetched, is always returned. Otherwise, with
+    /// [`ProfileResolution::GlobalProfile`], the sender is recorded so that
+    /// their global profile is fetched later, and `None` is returned.
+    pub(super) async fn resolve_sender_profile<P: RoomDataProvider>(
+        &mut self,
+        room_data_provider: &P,
+        resolution: ProfileResolution,
+        sender: &UserId,
+    ) -> Option<Profile> {
+        if let Some(profile) = self.sender_profiles.get(sender) {
+            return Some(profile.clone());
+        }
+
+        match resolution {
+            ProfileResolution::RoomMembership => {
+                room_data_provider.profile_from_user_id(sender).await
+            }
+            ProfileResolution::GlobalProfile => {
+                if self.requested_global_profiles.insert(sender.to_owned()) {
+                    self.missing_global_profiles.insert(sender.to_owned());
+                    self.missing_global_profiles_notify.notify_one();
+                }
+                None
+            }
+            ProfileResolution::None => None,
         }
     }
 
+    /// Forget the profile of a sender resolved outside of the room
+    /// memberships, e.g. because their membership changed, so that it's
+    /// resolved again the next time it's needed.
+    pub(super) fn invalidate_sender_profile(&mut self, sender: &UserId) {
+        self.sender_profiles.remove(sender);
+        self.requested_global_profiles.remove(sender);
+    }
+
+    /// Record that the global profile of a sender couldn't be fetched, so that
+    /// it's requested again the next time it's needed.
+    pub(super) fn global_profile_fetch_failed(&mut self, sender: &UserId) {
+        self.requested_global_profiles.remove(sender);
+    }
+
     pub(super) fn clear(&mut self) {
         // Note: we don't clear the next internal id to avoid bad cases of stale unique
         // ids across timeline clears.
@@ -163,6 +251,9 @@ impl TimelineMetadata {
         // before attempting to update it for each new timeline item.
         self.has_up_to_date_read_marker_item = true;
         self.read_receipts.clear();
+        // Redactions received while the timeline is cleared wouldn't invalidate the
+        // cached media, so don't keep any.
+        self.media_cache.clear();
     }
 
     /// Get the relative positions of two events in the timeline.
@@ -12,7 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::BTreeSet, fmt, sync::Arc};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    sync::Arc,
+};
 
 use as_variant::as_variant;
 use decryption_retry_task::DecryptionRetryTask;
@@ -43,8 +47,8 @@ use ruma::{
         AnySyncTimelineEvent, MessageLikeEventType,
     },
     serde::Raw,
-    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedTransactionId, RoomVersionId,
-    TransactionId, UserId,
+    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedTransactionId, OwnedUserId,
+    RoomVersionId, TransactionId, UserId,
 };
 #[cfg(test)]
 use ruma::{events::receipt::ReceiptEventContent, OwnedRoomId, RoomId};
@@ -67,14 +71,15 @@ use super::{
     subscriber::TimelineSubscriber,
     traits::{Decryptor, RoomDataProvider},
     DateDividerMode, EmbeddedEvent, Error, EventSendState, EventTimelineItem, InReplyToDetails,
-    PaginationError, Profile, TimelineDetails, TimelineEventItemId, TimelineFocus, TimelineItem,
-    TimelineItemContent, TimelineItemKind, VirtualTimelineItem,
+    PaginationError, Profile, ProfileResolution, TimelineDetails, TimelineEventItemId,
+    TimelineFocus, TimelineItem, TimelineItemContent, TimelineItemKind, VirtualTimelineItem,
 };
 use crate::{
     timeline::{
         algorithms::rfind_event_by_item_id,
         date_dividers::DateDividerAdjuster,
         event_item::EventTimelineItemKind,
+        media_cache::DecryptedMediaCache,
         pinned_events_loader::{PinnedEventsLoader, PinnedEventsLoaderError},
         MsgLikeContent, MsgLikeKind, TimelineEventFilterFn,
     },
@@ -155,6 +160,14 @@ pub(super) struct TimelineSettings {
 
     /// Should the timeline items be grouped by day or month?
     pub(super) date_divider_mode: DateDividerMode,
+
+    /// Should the local echoes be stamped with the time of the homeserver,
+    /// estimated from the age of the events received from sync, rather than
+    /// the time of the local clock?
+    pub(super) correct_clock_skew: bool,
+
+    /// Where are the profiles of the senders resolved from?
+    pub(super) profile_resolution: ProfileResolution,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -163,6 +176,8 @@ impl fmt::Debug for TimelineSettings {
         f.debug_struct("TimelineSettings")
             .field("track_read_receipts", &self.track_read_receipts)
             .field("add_failed_to_parse", &self.add_failed_to_parse)
+            .field("correct_clock_skew", &self.correct_clock_skew)
+            .field("profile_resolution", &self.profile_resolution)
             .finish_non_exhaustive()
     }
 }
@@ -174,6 +189,8 @@ impl Default for TimelineSettings {
             event_filter: Arc::new(default_event_filter),
             add_failed_to_parse: true,
             date_divider_mode: DateDividerMode::Daily,
+            correct_clock_skew: false,
+            profile_resolution: ProfileResolution::RoomMembership,
         }
     }
 }
@@ -578,6 +595,11 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
         self.state.read().await.items.clone_items()
     }
 
+    /// Get a handle to the cache of decrypted media content of this timeline.
+    pub(super) async fn media_cache(&self) -> DecryptedMediaCache {
+        self.state.read().await.meta.media_cache.clone()
+    }
+
     #[cfg(test)]
     pub(super) async fn subscribe_raw(
         &self,
@@ -865,16 +887,45 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
         send_handle: Option<SendHandle>,
     ) {
         let sender = self.room_data_provider.own_user_id().to_owned();
-        let profile = self.room_data_provider.profile_from_user_id(&sender).await;
-
         let date_divider_mode = self.settings.date_divider_mode.clone();
 
         let mut state = self.state.write().await;
+        let profile = state
+            .meta
+            .resolve_sender_profile(
+                &self.room_data_provider,
+                self.settings.profile_resolution,
+                &sender,
+            )
+            .await;
+        let timestamp = self.now_with(&state.meta);
         state
-            .handle_local_event(sender, profile, date_divider_mode, txn_id, send_handle, content)
+            .handle_local_event(
+                sender,
+                profile,
+                date_divider_mode,
+                timestamp,
+                txn_id,
+                send_handle,
+                content,
+            )
             .await;
     }
 
+    /// The current time, corrected from the estimated clock skew if enabled in
+    /// the settings.
+    pub(super) async fn now(&self) -> MilliSecondsSinceUnixEpoch {
+        self.now_with(&self.state.read().await.meta)
+    }
+
+    fn now_with(&self, meta: &TimelineMetadata) -> MilliSecondsSinceUnixEpoch {
+        if self.settings.correct_clock_skew {
+            meta.clock_skew.now()
+        } else {
+            MilliSecondsSinceUnixEpoch::now()
+        }
+    }
+
     /// Update the send state of a local event represented by a transaction ID.
     ///
     /// If the corresponding local timeline item is missing, a warning is
@@ -1112,6 +1163,7 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
         trace!("Updating missing sender profiles");
 
         let mut state = self.state.write().await;
+        let state = &mut *state;
         let mut entries = state.items.entries();
         while let Some(mut entry) = entries.next() {
             let Some(event_item) = entry.as_event() else { continue };
@@ -1123,7 +1175,15 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
                 continue;
             }
 
-            match self.room_data_provider.profile_from_user_id(event_item.sender()).await {
+            match state
+                .meta
+                .resolve_sender_profile(
+                    &self.room_data_provider,
+                    self.settings.profile_resolution,
+                    event_item.sender(),
+                )
+                .await
+            {
                 Some(profile) => {
                     trace!(event_id, transaction_id, "Adding profile");
                     let updated_item =
@@ -1153,6 +1213,13 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
         trace!("Forcing update of sender profiles: {sender_ids:?}");
 
         let mut state = self.state.write().await;
+        let state = &mut *state;
+
+        // The cached profiles of these senders may be outdated.
+        for sender_id in sender_ids {
+            state.meta.invalidate_sender_profile(sender_id);
+        }
+
         let mut entries = state.items.entries();
         while let Some(mut entry) = entries.next() {
             let Some(event_item) = entry.as_event() else { continue };
@@ -1163,7 +1230,15 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
             let event_id = event_item.event_id().map(debug);
             let transaction_id = event_item.transaction_id().map(debug);
 
-            match self.room_data_provider.profile_from_user_id(event_item.sender()).await {
+            match state
+                .meta
+                .resolve_sender_profile(
+                    &self.room_data_provider,
+                    self.settings.profile_resolution,
+                    event_item.sender(),
+                )
+                .await
+            {
                 Some(profile) => {
                     if matches!(event_item.sender_profile(), TimelineDetails::Ready(old_profile) if *old_profile == profile)
                     {
@@ -1193,6 +1268,92 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
         trace!("Done forcing update of sender profiles");
     }
 
+    /// Fetch the global profiles of the senders that were recorded as missing
+    /// while resolving the profiles with [`ProfileResolution::GlobalProfile`],
+    /// and update the items they sent.
+    ///
+    /// The senders whose global profile couldn't be fetched are requested
+    /// again the next time their profile is resolved.
+    pub(super) async fn fetch_missing_global_profiles(&self) {
+        let missing = std::mem::take(&mut self.state.write().await.meta.missing_global_profiles);
+        if missing.is_empty() {
+            return;
+        }
+
+        trace!("Fetching {} missing global profiles", missing.len());
+
+        let mut profiles = HashMap::new();
+        let mut failed = Vec::new();
+        for user_id in missing {
+            match self.room_data_provider.fetch_global_profile(&user_id).await {
+                Some(profile) => {
+                    profiles.insert(user_id, profile);
+                }
+                None => failed.push(user_id),
+            }
+        }
+
+        if !failed.is_empty() {
+            let mut state = self.state.write().await;
+            for user_id in &failed {
+                state.meta.global_profile_fetch_failed(user_id);
+            }
+        }
+
+        self.set_sender_profiles(profiles).await;
+    }
+
+    /// Wait until the global profile of a sender must be fetched with
+    /// [`Self::fetch_missing_global_profiles`].
+    pub(super) async fn wait_for_missing_global_profiles(&self) {
+        let notify = self.state.read().await.meta.missing_global_profiles_notify.clone();
+        notify.notified().await;
+    }
+
+    /// Resolve the profile of the given sender on demand, whatever the
+    /// [`ProfileResolution`] of the timeline, and update the items they sent.
+    ///
+    /// The profile is resolved from the room memberships first, and from the
+    /// global profile of the sender otherwise. It's remembered until the
+    /// membership of the sender changes.
+    pub(super) async fn fetch_sender_profile(&self, user_id: &UserId) -> Option<Profile> {
+        let profile = match self.room_data_provider.profile_from_user_id(user_id).await {
+            Some(profile) => profile,
+            None => self.room_data_provider.fetch_global_profile(user_id).await?,
+        };
+
+        self.set_sender_profiles(HashMap::from([(user_id.to_owned(), profile.clone())])).await;
+
+        Some(profile)
+    }
+
+    /// Remember the given sender profiles, and update the items they sent.
+    async fn set_sender_profiles(&self, profiles: HashMap<OwnedUserId, Profile>) {
+        if profiles.is_empty() {
+            return;
+        }
+
+        let mut state = self.state.write().await;
+        let state = &mut *state;
+        let mut entries = state.items.entries();
+        while let Some(mut entry) = entries.next() {
+            let Some(event_item) = entry.as_event() else { continue };
+            let Some(profile) = profiles.get(event_item.sender()) else { continue };
+
+            if matches!(event_item.sender_profile(), TimelineDetails::Ready(old_profile) if old_profile == profile)
+            {
+                continue;
+            }
+
+            let updated_item =
+                event_item.with_sender_profile(TimelineDetails::Ready(profile.clone()));
+            let new_item = entry.with_kind(updated_item);
+            ObservableItemsEntry::replace(&mut entry, new_item);
+        }
+
+        state.meta.sender_profiles.extend(profiles);
+    }
+
     #[cfg(test)]
     pub(super) async fn handle_read_receipts(&self, receipt_event_content: ReceiptEventContent) {
         let own_user_id = self.room_data_provider.own_user_id();
@@ -1278,7 +1439,7 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
             AggregationKind::Reaction {
                 key: reaction_key.clone(),
                 sender: self.room_data_provider.own_user_id().to_owned(),
-                timestamp: MilliSecondsSinceUnixEpoch::now(),
+                timestamp: self.now_with(&tr.meta),
                 reaction_status,
             },
         );
@@ -1357,6 +1518,17 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
         txn.commit();
     }
 
+    /// Insert a history unavailable item at the beginning of the room, if
+    /// it's missing, replacing the timeline start item if there's one.
+    pub async fn insert_history_unavailable_if_missing(&self) {
+        let mut state = self.state.write().await;
+        let mut txn = state.transaction();
+        txn.items.push_history_unavailable_if_missing(
+            txn.meta.new_timeline_item(VirtualTimelineItem::HistoryUnavailable),
+        );
+        txn.commit();
+    }
+
     /// Create a [`EmbeddedEvent`] from an arbitrary event, be it in the
     /// timeline or not.
     ///
@@ -39,7 +39,8 @@ use super::{metadata::EventMeta, TimelineItem};
 /// The `ObservableItems` holds all the invariants about the _position_ of the
 /// items. It defines three regions where items can live:
 ///
-/// 1. the _start_ region, which can only contain a single [`TimelineStart`],
+/// 1. the _start_ region, which can only contain a single [`TimelineStart`] or
+///    [`HistoryUnavailable`],
 /// 2. the _remotes_ region, which can only contain many [`Remote`] timeline
 ///    items with their decorations (only [`DateDivider`]s and [`ReadMarker`]s),
 /// 3. the _locals_ region, which can only contain many [`Local`] timeline items
@@ -55,6 +56,7 @@ use super::{metadata::EventMeta, TimelineItem};
 /// in the correct region, and check a couple of invariants.
 ///
 /// [`TimelineStart`]: super::VirtualTimelineItem::TimelineStart
+/// [`HistoryUnavailable`]: super::VirtualTimelineItem::HistoryUnavailable
 /// [`DateDivider`]: super::VirtualTimelineItem::DateDivider
 /// [`ReadMarker`]: super::VirtualTimelineItem::ReadMarker
 /// [`Remote`]: super::EventTimelineItemKind::Remote
@@ -385,7 +387,7 @@ impl<'observable_items> ObservableItemsTransaction<'observable_items> {
         // We are not inserting in the start region.
         if timeline_item_index == 0 && !self.items.is_empty() {
             assert!(
-                matches!(self.items.get(timeline_item_index), Some(timeline_item) if !timeline_item.is_timeline_start())
+                matches!(self.items.get(timeline_item_index), Some(timeline_item) if !timeline_item.is_start_region_item())
             );
         }
 
@@ -404,11 +406,15 @@ impl<'observable_items> ObservableItemsTransaction<'observable_items> {
     ///
     /// A [`TimelineStart`] is always the first item if present.
     ///
+    /// Nothing happens if a [`HistoryUnavailable`] is present, since it
+    /// already marks the start of the timeline.
+    ///
     /// # Panics
     ///
     /// It panics if the provided `timeline_item` is not a [`TimelineStart`].
     ///
     /// [`TimelineStart`]: super::VirtualTimelineItem::TimelineStart
+    /// [`HistoryUnavailable`]: super::VirtualTimelineItem::HistoryUnavailable
     pub fn push_timeline_start_if_missing(&mut self, timeline_item: Arc<TimelineItem>) {
         assert!(
             timeline_item.is_timeline_start(),
@@ -416,13 +422,44 @@ impl<'observable_items> ObservableItemsTransaction<'observable_items> {
         );
 
         // The timeline start virtual item is necessarily the first item.
-        if self.get(0).is_some_and(|item| item.is_timeline_start()) {
+        if self.get(0).is_some_and(|item| item.is_start_region_item()) {
             return;
         }
 
         self.push_front(timeline_item, None);
     }
 
+    /// Push a new [`HistoryUnavailable`] virtual timeline item.
+    ///
+    /// # Invariant
+    ///
+    /// A [`HistoryUnavailable`] is always the first item if present. It
+    /// replaces a [`TimelineStart`] if there's one.
+    ///
+    /// # Panics
+    ///
+    /// It panics if the provided `timeline_item` is not a
+    /// [`HistoryUnavailable`].
+    ///
+    /// [`TimelineStart`]: super::VirtualTimelineItem::TimelineStart
+    /// [`HistoryUnavailable`]: super::VirtualTimelineItem::HistoryUnavailable
+    pub fn push_history_unavailable_if_missing(&mut self, timeline_item: Arc<TimelineItem>) {
+        assert!(
+            timeline_item.is_history_unavailable(),
+            "The provided `timeline_item` is not a `HistoryUnavailable`"
+        );
+
+        match self.get(0) {
+            Some(item) if item.is_history_unavailable() => {}
+            Some(item) if item.is_timeline_start() => {
+                self.replace(0, timeline_item);
+            }
+            _ => {
+                self.push_front(timeline_item, None);
+            }
+        }
+    }
+
     /// Clear all timeline items and all remote events.
     pub fn clear(&mut self) {
         self.items.clear();
@@ -450,7 +487,7 @@ impl<'observable_items> ObservableItemsTransaction<'observable_items> {
     /// Return the index where to insert the first remote timeline
     /// item.
     pub fn first_remotes_region_index(&self) -> usize {
-        if self.items.get(0).is_some_and(|item| item.is_timeline_start()) {
+        if self.items.get(0).is_some_and(|item| item.is_start_region_item()) {
             1
         } else {
             0
@@ -502,9 +539,11 @@ impl<'observable_items> ObservableItemsTransaction<'observable_items> {
 
 bitflags! {
     struct Regions: u8 {
-        /// The _start_ region can only contain a single [`TimelineStart`].
+        /// The _start_ region can only contain a single [`TimelineStart`] or
+        /// [`HistoryUnavailable`].
         ///
         /// [`TimelineStart`]: super::VirtualTimelineItem::TimelineStart
+        /// [`HistoryUnavailable`]: super::VirtualTimelineItem::HistoryUnavailable
         const START = 0b0000_0001;
 
         /// The _remotes_ region can only contain many [`Remote`] timeline items
@@ -563,7 +602,7 @@ impl<'e> ObservableItemsTransactionIterBuilder<'e> {
         // Calculate the size of the _start_ region.
         let size_of_start_region = if matches!(
             self.items.get(0),
-            Some(first_timeline_item) if first_timeline_item.is_timeline_start()
+            Some(first_timeline_item) if first_timeline_item.is_start_region_item()
         ) {
             1
         } else {
@@ -743,6 +782,7 @@ mod observable_items_tests {
                         msgtype: MessageType::Text(TextMessageEventContent::plain("hello")),
                         edited: false,
                         mentions: None,
+                        additional_content: Default::default(),
                     }),
                     reactions: Default::default(),
                     thread_root: None,
@@ -777,6 +817,7 @@ mod observable_items_tests {
                         msgtype: MessageType::Text(TextMessageEventContent::plain("hello")),
                         edited: false,
                         mentions: None,
+                        additional_content: Default::default(),
                     }),
                     reactions: Default::default(),
                     thread_root: None,
@@ -1690,6 +1731,52 @@ mod observable_items_tests {
         assert_matches!(entries.next(), None);
     }
 
+    #[test]
+    fn test_transaction_push_history_unavailable_if_missing() {
+        let mut items = ObservableItems::new();
+
+        let mut transaction = items.transaction();
+
+        // Push an item and the timeline start.
+        transaction.push_back(item("$ev0"), None);
+        transaction.push_timeline_start_if_missing(TimelineItem::new(
+            VirtualTimelineItem::TimelineStart,
+            TimelineUniqueId("__id_start".to_owned()),
+        ));
+
+        // Push the history unavailable item: it replaces the timeline start.
+        transaction.push_history_unavailable_if_missing(TimelineItem::new(
+            VirtualTimelineItem::HistoryUnavailable,
+            TimelineUniqueId("__id_history_unavailable".to_owned()),
+        ));
+
+        // Neither a timeline start nor another history unavailable item are pushed
+        // afterwards.
+        transaction.push_timeline_start_if_missing(TimelineItem::new(
+            VirtualTimelineItem::TimelineStart,
+            TimelineUniqueId("__id_start_again".to_owned()),
+        ));
+        transaction.push_history_unavailable_if_missing(TimelineItem::new(
+            VirtualTimelineItem::HistoryUnavailable,
+            TimelineUniqueId("__id_history_unavailable_again".to_owned()),
+        ));
+
+        assert_eq!(transaction.first_remotes_region_index(), 1);
+
+        transaction.commit();
+
+        let mut entries = items.entries();
+
+        assert_matches!(entries.next(), Some(entry) => {
+            assert!(entry.is_history_unavailable());
+            assert_eq!(entry.unique_id().0, "__id_history_unavailable");
+        });
+        assert_matches!(entries.next(), Some(entry) => {
+            assert_event_id!(entry, "$ev0");
+        });
+        assert_matches!(entries.next(), None);
+    }
+
     #[test]
     fn test_transaction_iter_all_regions() {
         let mut items = ObservableItems::new();
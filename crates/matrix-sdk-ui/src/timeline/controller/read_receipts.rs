@@ -28,7 +28,7 @@ use super::{
     rfind_event_by_id, AllRemoteEvents, ObservableItemsTransaction, RelativePosition,
     RoomDataProvider, TimelineMetadata, TimelineState,
 };
-use crate::timeline::{controller::TimelineStateTransaction, TimelineItem};
+use crate::timeline::{controller::TimelineStateTransaction, TimelineItem, TimelineItemContent};
 
 /// In-memory caches for read receipts.
 #[derive(Clone, Debug, Default)]
@@ -511,11 +511,19 @@ impl TimelineStateTransaction<'_> {
                 }
 
                 for (user_id, receipt) in receipts {
+                    let is_own_user_id = user_id == own_user_id;
+
+                    if let ReceiptThread::Thread(thread_root) = &receipt.thread {
+                        if is_own_user_id {
+                            self.mark_thread_as_read(thread_root, &event_id);
+                        }
+                        continue;
+                    }
+
                     if !matches!(receipt.thread, ReceiptThread::Unthreaded | ReceiptThread::Main) {
                         continue;
                     }
 
-                    let is_own_user_id = user_id == own_user_id;
                     let full_receipt = FullReceipt {
                         event_id: &event_id,
                         user_id: &user_id,
@@ -533,6 +541,33 @@ impl TimelineStateTransaction<'_> {
         }
     }
 
+    /// Mark the thread starting at `thread_root` as read, if `event_id` is its
+    /// latest event.
+    fn mark_thread_as_read(&mut self, thread_root: &EventId, event_id: &EventId) {
+        let Some((index, item)) = rfind_event_by_id(&self.items, thread_root) else {
+            return;
+        };
+
+        let TimelineItemContent::MsgLike(mut msglike) = item.content().clone() else {
+            return;
+        };
+
+        let Some(summary) = &mut msglike.thread_summary else {
+            return;
+        };
+
+        if !summary.is_unread || summary.latest_event_id.as_deref() != Some(event_id) {
+            return;
+        }
+
+        trace!(%thread_root, "marking thread as read");
+        summary.is_unread = false;
+
+        let new_item = item.with_content(TimelineItemContent::MsgLike(msglike));
+        let internal_id = item.internal_id.to_owned();
+        self.items.replace(index, TimelineItem::new(new_item, internal_id));
+    }
+
     /// Load the read receipts from the store for the given event ID.
     ///
     /// Populates the read receipts in-memory caches.
@@ -156,6 +156,7 @@ impl TimelineState {
         own_user_id: OwnedUserId,
         own_profile: Option<Profile>,
         date_divider_mode: DateDividerMode,
+        timestamp: MilliSecondsSinceUnixEpoch,
         txn_id: OwnedTransactionId,
         send_handle: Option<SendHandle>,
         content: AnyMessageLikeEventContent,
@@ -185,7 +186,7 @@ impl TimelineState {
         let ctx = TimelineEventContext {
             sender: own_user_id,
             sender_profile: own_profile,
-            timestamp: MilliSecondsSinceUnixEpoch::now(),
+            timestamp,
             read_receipts: Default::default(),
             // An event sent by ourselves is never matched against push rules.
             is_highlighted: false,
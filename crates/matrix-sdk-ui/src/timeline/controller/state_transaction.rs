@@ -20,8 +20,13 @@ use matrix_sdk::deserialized_responses::{
     ThreadSummaryStatus, TimelineEvent, TimelineEventKind, UnsignedEventLocation,
 };
 use ruma::{
-    events::AnySyncTimelineEvent, push::Action, serde::Raw, EventId, MilliSecondsSinceUnixEpoch,
-    OwnedEventId, OwnedTransactionId, OwnedUserId, UserId,
+    events::{
+        receipt::{ReceiptThread, ReceiptType},
+        AnySyncStateEvent, AnySyncTimelineEvent,
+    },
+    push::Action,
+    serde::Raw,
+    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedTransactionId, OwnedUserId, UserId,
 };
 use tracing::{debug, instrument, warn};
 
@@ -187,6 +192,7 @@ impl<'a> TimelineStateTransaction<'a> {
         event: TimelineEvent,
         position: TimelineItemPosition,
         room_data_provider: &RoomData,
+        settings: &TimelineSettings,
         date_divider_adjuster: &mut DateDividerAdjuster,
     ) where
         RoomData: RoomDataProvider,
@@ -218,7 +224,10 @@ impl<'a> TimelineStateTransaction<'a> {
         {
             let encryption_info = event.kind.encryption_info().cloned();
 
-            let sender_profile = room_data_provider.profile_from_user_id(&sender).await;
+            let sender_profile = self
+                .meta
+                .resolve_sender_profile(room_data_provider, settings.profile_resolution, &sender)
+                .await;
 
             let ctx = TimelineEventContext {
                 sender,
@@ -268,6 +277,7 @@ impl<'a> TimelineStateTransaction<'a> {
                             event,
                             TimelineItemPosition::End { origin },
                             room_data_provider,
+                            settings,
                             &mut date_divider_adjuster,
                         )
                         .await;
@@ -279,6 +289,7 @@ impl<'a> TimelineStateTransaction<'a> {
                         event,
                         TimelineItemPosition::Start { origin },
                         room_data_provider,
+                        settings,
                         &mut date_divider_adjuster,
                     )
                     .await;
@@ -289,6 +300,7 @@ impl<'a> TimelineStateTransaction<'a> {
                         event,
                         TimelineItemPosition::End { origin },
                         room_data_provider,
+                        settings,
                         &mut date_divider_adjuster,
                     )
                     .await;
@@ -299,6 +311,7 @@ impl<'a> TimelineStateTransaction<'a> {
                         event,
                         TimelineItemPosition::At { event_index, origin },
                         room_data_provider,
+                        settings,
                         &mut date_divider_adjuster,
                     )
                     .await;
@@ -315,6 +328,7 @@ impl<'a> TimelineStateTransaction<'a> {
                             event,
                             TimelineItemPosition::UpdateAt { timeline_item_index },
                             room_data_provider,
+                            settings,
                             &mut date_divider_adjuster,
                         )
                         .await;
@@ -596,15 +610,33 @@ impl<'a> TimelineStateTransaction<'a> {
         let is_highlighted =
             event.push_actions().is_some_and(|actions| actions.iter().any(Action::is_highlight));
 
+        let thread_root = event.event_id();
         let thread_summary = if let ThreadSummaryStatus::Some(summary) = event.thread_summary {
-            let latest_reply_item = if let Some(latest_reply) = summary.latest_reply {
-                self.fetch_latest_thread_reply(&latest_reply, room_data_provider).await
+            let latest_reply_item = if let Some(latest_reply) = &summary.latest_reply {
+                self.fetch_latest_thread_reply(latest_reply, room_data_provider).await
             } else {
                 None
             };
+
+            let is_unread = match (&thread_root, &summary.latest_reply) {
+                (Some(thread_root), Some(latest_reply)) => {
+                    is_thread_unread(
+                        thread_root,
+                        latest_reply,
+                        latest_reply_item.as_deref(),
+                        room_data_provider,
+                    )
+                    .await
+                }
+                _ => false,
+            };
+
             Some(ThreadSummary {
                 latest_event: TimelineDetails::from_initial_value(latest_reply_item),
                 num_replies: summary.num_replies,
+                latest_event_id: summary.latest_reply,
+                current_user_participated: summary.current_user_participated,
+                is_unread,
             })
         } else {
             None
@@ -626,6 +658,24 @@ impl<'a> TimelineStateTransaction<'a> {
         {
             // Classical path: the event is valid, can be deserialized, everything is alright.
             Ok(event) => {
+                // Events fresh from sync tell how long ago the homeserver received them,
+                // which helps estimating the clock skew.
+                if matches!(position, TimelineItemPosition::End { origin: RemoteEventOrigin::Sync })
+                {
+                    self.meta.clock_skew.record(
+                        &raw,
+                        event.origin_server_ts(),
+                        MilliSecondsSinceUnixEpoch::now(),
+                    );
+
+                    // A new membership may come with a new profile.
+                    if let AnySyncTimelineEvent::State(AnySyncStateEvent::RoomMember(member)) =
+                        &event
+                    {
+                        self.meta.invalidate_sender_profile(member.state_key());
+                    }
+                }
+
                 let (in_reply_to, thread_root) = self.meta.process_event_relations(
                     &event,
                     &raw,
@@ -688,7 +738,10 @@ impl<'a> TimelineStateTransaction<'a> {
 
         // Handle the event to create or update a timeline item.
         if let Some(timeline_action) = timeline_action {
-            let sender_profile = room_data_provider.profile_from_user_id(&sender).await;
+            let sender_profile = self
+                .meta
+                .resolve_sender_profile(room_data_provider, settings.profile_resolution, &sender)
+                .await;
 
             let ctx = TimelineEventContext {
                 sender,
@@ -763,9 +816,9 @@ impl<'a> TimelineStateTransaction<'a> {
                 if entry.is_remote_event()
                     || entry.as_virtual().is_some_and(|vitem| match vitem {
                         VirtualTimelineItem::DateDivider(_) => false,
-                        VirtualTimelineItem::ReadMarker | VirtualTimelineItem::TimelineStart => {
-                            true
-                        }
+                        VirtualTimelineItem::ReadMarker
+                        | VirtualTimelineItem::TimelineStart
+                        | VirtualTimelineItem::HistoryUnavailable => true,
                     })
                 {
                     ObservableItemsTransactionEntry::remove(entry);
@@ -906,3 +959,35 @@ impl<'a> TimelineStateTransaction<'a> {
         }
     }
 }
+
+/// Whether the current user hasn't read the latest reply of the thread starting
+/// at `thread_root`, according to their threaded read receipts.
+async fn is_thread_unread<P: RoomDataProvider>(
+    thread_root: &EventId,
+    latest_reply: &EventId,
+    latest_reply_item: Option<&EmbeddedEvent>,
+    room_data_provider: &P,
+) -> bool {
+    let own_user_id = room_data_provider.own_user_id();
+
+    // Our own replies are always read.
+    if latest_reply_item.is_some_and(|item| item.sender == own_user_id) {
+        return false;
+    }
+
+    for receipt_type in [ReceiptType::Read, ReceiptType::ReadPrivate] {
+        let receipt = room_data_provider
+            .load_user_receipt(
+                receipt_type,
+                ReceiptThread::Thread(thread_root.to_owned()),
+                own_user_id,
+            )
+            .await;
+
+        if receipt.is_some_and(|(event_id, _)| event_id == latest_reply) {
+            return false;
+        }
+    }
+
+    true
+}
@@ -26,11 +26,11 @@ use super::{
     DateDividerMode, TimelineItem, TimelineItemKind, VirtualTimelineItem,
 };
 
-#[derive(Debug, PartialEq)]
-struct Date {
-    year: i32,
-    month: u32,
-    day: u32,
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) struct Date {
+    pub(super) year: i32,
+    pub(super) month: u32,
+    pub(super) day: u32,
 }
 
 impl Date {
@@ -40,7 +40,7 @@ impl Date {
 }
 
 /// Converts a timestamp since Unix Epoch to a year, month and day.
-fn timestamp_to_date(ts: MilliSecondsSinceUnixEpoch) -> Date {
+pub(super) fn timestamp_to_date(ts: MilliSecondsSinceUnixEpoch) -> Date {
     let datetime = Local
         .timestamp_millis_opt(ts.0.into())
         // Only returns `None` if date is after Dec 31, 262143 BCE.
@@ -154,7 +154,8 @@ impl DateDividerAdjuster {
                 }
 
                 TimelineItemKind::Virtual(VirtualTimelineItem::ReadMarker)
-                | TimelineItemKind::Virtual(VirtualTimelineItem::TimelineStart) => {
+                | TimelineItemKind::Virtual(VirtualTimelineItem::TimelineStart)
+                | TimelineItemKind::Virtual(VirtualTimelineItem::HistoryUnavailable) => {
                     // Nothing to do.
                 }
             }
@@ -251,7 +252,8 @@ impl DateDividerAdjuster {
             }
 
             TimelineItemKind::Virtual(VirtualTimelineItem::ReadMarker)
-            | TimelineItemKind::Virtual(VirtualTimelineItem::TimelineStart) => {
+            | TimelineItemKind::Virtual(VirtualTimelineItem::TimelineStart)
+            | TimelineItemKind::Virtual(VirtualTimelineItem::HistoryUnavailable) => {
                 // Nothing to do.
             }
         }
@@ -317,7 +319,8 @@ impl DateDividerAdjuster {
             }
 
             TimelineItemKind::Virtual(VirtualTimelineItem::ReadMarker)
-            | TimelineItemKind::Virtual(VirtualTimelineItem::TimelineStart) => {
+            | TimelineItemKind::Virtual(VirtualTimelineItem::TimelineStart)
+            | TimelineItemKind::Virtual(VirtualTimelineItem::HistoryUnavailable) => {
                 // Nothing to do.
             }
         }
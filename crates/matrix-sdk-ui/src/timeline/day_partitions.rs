@@ -0,0 +1,278 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Partitioning of the events of a room by local calendar day, so that clients
+//! can build date pickers showing which days have messages.
+
+use std::collections::BTreeMap;
+
+use async_stream::stream;
+use chrono::{Local, TimeZone};
+use futures_core::Stream;
+use matrix_sdk::{
+    event_cache::{EventCacheError, RoomEventCacheUpdate},
+    Result,
+};
+use matrix_sdk_base::deserialized_responses::TimelineEvent;
+use ruma::{
+    api::Direction, events::TimelineEventType, MilliSecondsSinceUnixEpoch, OwnedEventId, UInt,
+};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{instrument, warn};
+
+use super::{
+    date_dividers::{timestamp_to_date, Date},
+    Error,
+};
+
+/// The number of events to back-paginate at once, when paginating by days.
+const DAY_PARTITIONS_PAGINATION_BATCH_SIZE: u16 = 50;
+
+/// The messages of a room sent on the same local calendar day.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DayPartition {
+    /// The year of the day.
+    pub year: i32,
+
+    /// The month of the day, starting from 1.
+    pub month: u32,
+
+    /// The day of the month, starting from 1.
+    pub day: u32,
+
+    /// The number of loaded messages sent on that day.
+    pub num_events: usize,
+
+    /// The ID of the first loaded message sent on that day.
+    pub first_event_id: OwnedEventId,
+}
+
+impl super::Timeline {
+    /// Get the messages of the room loaded in the event cache, grouped by the
+    /// local calendar day they were sent on, from the oldest day to the most
+    /// recent one.
+    pub async fn day_partitions(&self) -> Vec<DayPartition> {
+        partition_by_day(&self.event_cache.events().await)
+    }
+
+    /// Subscribe to the [`DayPartition`]s of the room, as returned by
+    /// [`Self::day_partitions()`].
+    ///
+    /// The stream yields the new partitions every time they change, e.g.
+    /// because of new messages from the sync, or of a back-pagination.
+    pub async fn subscribe_to_day_partitions(
+        &self,
+    ) -> (Vec<DayPartition>, impl Stream<Item = Vec<DayPartition>>) {
+        let (events, mut subscriber) = self.event_cache.subscribe().await;
+        let initial = partition_by_day(&events);
+
+        let event_cache = self.event_cache.clone();
+        let mut previous = initial.clone();
+
+        let stream = Box::pin(stream! {
+            loop {
+                match subscriber.recv().await {
+                    Ok(RoomEventCacheUpdate::UpdateTimelineEvents { .. })
+                    | Err(RecvError::Lagged(_)) => {}
+                    Ok(_) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+
+                let partitions = partition_by_day(&event_cache.events().await);
+
+                if partitions != previous {
+                    previous = partitions.clone();
+                    yield partitions;
+                }
+            }
+        });
+
+        (initial, stream)
+    }
+
+    /// Back-paginate the room until the messages of `num_days` more days have
+    /// been loaded, or the start of the room has been reached.
+    ///
+    /// The new days are visible through [`Self::day_partitions()`] and
+    /// [`Self::subscribe_to_day_partitions()`]. Since the loaded events of the
+    /// oldest day may not be complete, it might only be complete after the
+    /// next call.
+    ///
+    /// Returns whether we hit the start of the room.
+    #[instrument(skip_all, fields(room_id = ?self.room().room_id()))]
+    pub async fn paginate_day_partitions_backwards(&self, num_days: u16) -> Result<bool, Error> {
+        let target_num_days = self.day_partitions().await.len() + usize::from(num_days);
+
+        loop {
+            let outcome = match self
+                .event_cache
+                .pagination()
+                .run_backwards_once(DAY_PARTITIONS_PAGINATION_BATCH_SIZE)
+                .await
+            {
+                Ok(outcome) => outcome,
+
+                Err(EventCacheError::AlreadyBackpaginating) => {
+                    // Let the caller retry later, as with the regular back-pagination.
+                    warn!("Another pagination request is already happening, returning early");
+                    return Ok(false);
+                }
+
+                Err(err) => return Err(err.into()),
+            };
+
+            if outcome.reached_start {
+                return Ok(true);
+            }
+
+            if self.day_partitions().await.len() >= target_num_days {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Find the first event of the room sent on the given local calendar day,
+    /// using the `/timestamp_to_event` endpoint, so that the timeline can be
+    /// focused on it even if it's not loaded yet.
+    ///
+    /// Returns `None` if the date is invalid, or if there's no event on that
+    /// day.
+    pub async fn first_event_id_of_day(
+        &self,
+        year: i32,
+        month: u32,
+        day: u32,
+    ) -> Result<Option<OwnedEventId>> {
+        let Some(midnight) = Local.with_ymd_and_hms(year, month, day, 0, 0, 0).earliest() else {
+            return Ok(None);
+        };
+
+        let Ok(millis) = UInt::try_from(midnight.timestamp_millis()) else {
+            return Ok(None);
+        };
+
+        let Some((event_id, origin_server_ts)) = self
+            .room()
+            .event_for_timestamp(MilliSecondsSinceUnixEpoch(millis), Direction::Forward)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        // The closest event might have been sent on a later day.
+        let date = timestamp_to_date(origin_server_ts);
+        Ok((date == Date { year, month, day }).then_some(event_id))
+    }
+}
+
+/// Whether the event is a message worth counting in a [`DayPartition`].
+fn is_counted_message(event: &TimelineEvent) -> bool {
+    let raw = event.raw();
+
+    if raw.get_field::<serde_json::Value>("state_key").ok().flatten().is_some() {
+        return false;
+    }
+
+    !matches!(
+        raw.get_field::<TimelineEventType>("type").ok().flatten(),
+        None | Some(TimelineEventType::Reaction | TimelineEventType::RoomRedaction)
+    )
+}
+
+/// Group the given messages by the local calendar day they were sent on.
+fn partition_by_day(events: &[TimelineEvent]) -> Vec<DayPartition> {
+    let mut partitions = BTreeMap::<Date, DayPartition>::new();
+
+    for event in events.iter().filter(|event| is_counted_message(event)) {
+        let Some(event_id) = event.event_id() else {
+            continue;
+        };
+
+        let Some(ts) =
+            event.raw().get_field::<MilliSecondsSinceUnixEpoch>("origin_server_ts").ok().flatten()
+        else {
+            continue;
+        };
+
+        let date = timestamp_to_date(ts);
+
+        partitions
+            .entry(date)
+            .or_insert_with(|| DayPartition {
+                year: date.year,
+                month: date.month,
+                day: date.day,
+                num_events: 0,
+                first_event_id: event_id,
+            })
+            .num_events += 1;
+    }
+
+    partitions.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+    use matrix_sdk_test::{event_factory::EventFactory, ALICE};
+    use ruma::{event_id, owned_event_id, room_id, MilliSecondsSinceUnixEpoch, UInt};
+
+    use super::{partition_by_day, DayPartition};
+
+    fn local_ts(year: i32, month: u32, day: u32, hour: u32) -> MilliSecondsSinceUnixEpoch {
+        let millis = Local
+            .with_ymd_and_hms(year, month, day, hour, 0, 0)
+            .single()
+            .unwrap()
+            .timestamp_millis();
+        MilliSecondsSinceUnixEpoch(UInt::try_from(millis).unwrap())
+    }
+
+    #[test]
+    fn test_partition_by_day() {
+        let f = EventFactory::new().room(room_id!("!r:example.org")).sender(*ALICE);
+
+        let events = vec![
+            f.text_msg("a").event_id(event_id!("$a")).server_ts(local_ts(2025, 3, 1, 10)).into(),
+            f.reaction(event_id!("$a"), "👍")
+                .event_id(event_id!("$r"))
+                .server_ts(local_ts(2025, 3, 1, 11))
+                .into(),
+            f.text_msg("b").event_id(event_id!("$b")).server_ts(local_ts(2025, 3, 1, 12)).into(),
+            f.text_msg("c").event_id(event_id!("$c")).server_ts(local_ts(2025, 3, 4, 9)).into(),
+        ];
+
+        assert_eq!(
+            partition_by_day(&events),
+            vec![
+                DayPartition {
+                    year: 2025,
+                    month: 3,
+                    day: 1,
+                    num_events: 2,
+                    first_event_id: owned_event_id!("$a"),
+                },
+                DayPartition {
+                    year: 2025,
+                    month: 3,
+                    day: 4,
+                    num_events: 1,
+                    first_event_id: owned_event_id!("$c"),
+                },
+            ]
+        );
+
+        assert!(partition_by_day(&[]).is_empty());
+    }
+}
@@ -44,6 +44,10 @@ pub enum Error {
     #[error("Failed sending attachment")]
     FailedSendingAttachment,
 
+    /// The media content of an event could not be fetched.
+    #[error("Failed fetching media content")]
+    FailedFetchingMedia(#[source] matrix_sdk::Error),
+
     /// The reaction could not be toggled.
     #[error("Failed toggling reaction")]
     FailedToToggleReaction,
@@ -48,9 +48,9 @@ use super::{
     },
     date_dividers::DateDividerAdjuster,
     event_item::{
-        AnyOtherFullStateEventContent, EventSendState, EventTimelineItemKind,
-        LocalEventTimelineItem, PollState, Profile, RemoteEventOrigin, RemoteEventTimelineItem,
-        TimelineEventItemId,
+        extract_bundled_edit_event_json, extract_edit_new_content, AnyOtherFullStateEventContent,
+        EventSendState, EventTimelineItemKind, LocalEventTimelineItem, PollState, Profile,
+        RemoteEventOrigin, RemoteEventTimelineItem, TimelineEventItemId,
     },
     traits::RoomDataProvider,
     EmbeddedEvent, EncryptedMessage, EventTimelineItem, InReplyToDetails, MsgLikeContent,
@@ -251,7 +251,31 @@ impl TimelineAction {
                 }
 
                 Some(content) => {
-                    return Self::from_content(content, in_reply_to, thread_root, thread_summary);
+                    let mut action =
+                        Self::from_content(content, in_reply_to, thread_root, thread_summary)?;
+
+                    // Keep the fields of a message that the SDK doesn't handle, so that clients
+                    // don't have to parse the raw event again to use them.
+                    if let Self::AddItem {
+                        content:
+                            TimelineItemContent::MsgLike(MsgLikeContent {
+                                kind: MsgLikeKind::Message(message),
+                                ..
+                            }),
+                    } = &mut action
+                    {
+                        // The content of an edited message is the one of its edit.
+                        let content = match extract_bundled_edit_event_json(raw_event) {
+                            Some(edit_json) => extract_edit_new_content(&edit_json),
+                            None => raw_event.get_field("content").ok().flatten(),
+                        };
+
+                        if let Some(content) = content {
+                            message.set_additional_content(content);
+                        }
+                    }
+
+                    return Some(action);
                 }
 
                 None => Self::add_item(redacted_message_or_none(ev.event_type())?),
@@ -678,6 +702,9 @@ impl<'a, 'o> TimelineEventHandler<'a, 'o> {
         // TODO: Apply local redaction of PollResponse and PollEnd events.
         // https://github.com/matrix-org/matrix-rust-sdk/pull/2381#issuecomment-1689647825
 
+        // The media of a redacted event must not be served from the cache anymore.
+        self.meta.media_cache.invalidate(&redacted);
+
         // If it's an aggregation that's being redacted, handle it here.
         if self.handle_aggregation_redaction(redacted.clone()) {
             // When we have raw timeline items, we should not return here anymore, as we
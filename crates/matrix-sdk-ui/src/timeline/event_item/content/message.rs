@@ -28,7 +28,7 @@ use ruma::{
         AnySyncMessageLikeEvent, AnySyncTimelineEvent, BundledMessageLikeRelations, Mentions,
     },
     html::RemoveReplyFallback,
-    serde::Raw,
+    serde::{JsonObject, Raw},
     OwnedEventId,
 };
 use tracing::{error, trace};
@@ -41,6 +41,7 @@ pub struct Message {
     pub(in crate::timeline) msgtype: MessageType,
     pub(in crate::timeline) edited: bool,
     pub(in crate::timeline) mentions: Option<Mentions>,
+    pub(in crate::timeline) additional_content: JsonObject,
 }
 
 impl Message {
@@ -53,7 +54,8 @@ impl Message {
     ) -> Self {
         msgtype.sanitize(DEFAULT_SANITIZER_MODE, remove_reply_fallback);
 
-        let mut ret = Self { msgtype, edited: false, mentions };
+        let mut ret =
+            Self { msgtype, edited: false, mentions, additional_content: JsonObject::new() };
 
         if let Some(edit) = edit {
             ret.apply_edit(edit);
@@ -70,6 +72,23 @@ impl Message {
         self.msgtype = new_content.msgtype;
         self.mentions = new_content.mentions;
         self.edited = true;
+        // The edit replaces the whole content, and its other fields aren't known here.
+        self.additional_content.clear();
+    }
+
+    /// Set the fields of the content of this message which aren't handled by
+    /// the SDK, from the full content of the event.
+    pub(in crate::timeline) fn set_additional_content(&mut self, mut content: JsonObject) {
+        // The fields which are handled outside of the `msgtype`.
+        for field in ["msgtype", "body", "m.mentions", "m.relates_to", "m.new_content"] {
+            content.remove(field);
+        }
+
+        if let Ok(serde_json::Value::Object(msgtype_fields)) = serde_json::to_value(&self.msgtype) {
+            content.retain(|field, _| !msgtype_fields.contains_key(field));
+        }
+
+        self.additional_content = content;
     }
 
     /// Get the `msgtype`-specific data of this message.
@@ -94,6 +113,68 @@ impl Message {
     pub fn mentions(&self) -> Option<&Mentions> {
         self.mentions.as_ref()
     }
+
+    /// Get the fields of the content of this message which aren't part of
+    /// its `msgtype`, nor handled otherwise by the SDK.
+    ///
+    /// This contains the custom metadata added to messages by some clients.
+    /// The fields of a custom `msgtype` are available with
+    /// [`MessageType::data()`] instead. It's empty for local echoes, and for
+    /// edited messages if the edit event isn't available.
+    pub fn additional_content(&self) -> &JsonObject {
+        &self.additional_content
+    }
+
+    /// Get the visual effect to render along with this message, if any.
+    ///
+    /// The effects are defined by the custom `msgtype`s sent by Element
+    /// clients.
+    pub fn effect(&self) -> Option<MessageEffect> {
+        MessageEffect::from_msgtype(self.msgtype.msgtype())
+    }
+}
+
+/// A visual effect to render along with a message, like confetti.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MessageEffect {
+    /// Confetti, with the `nic.custom.confetti` msgtype.
+    Confetti,
+
+    /// Fireworks, with the `nic.custom.fireworks` msgtype.
+    Fireworks,
+
+    /// Hearts, with the `io.element.effect.hearts` msgtype.
+    Hearts,
+
+    /// Rain, with the `io.element.effect.rainfall` msgtype.
+    Rainfall,
+
+    /// Snow, with the `io.element.effect.snowfall` msgtype.
+    Snowfall,
+
+    /// Space invaders, with the `io.element.effects.space_invaders` msgtype.
+    SpaceInvaders,
+}
+
+impl MessageEffect {
+    /// Get the effect defined by the given `msgtype`, if any.
+    fn from_msgtype(msgtype: &str) -> Option<Self> {
+        Some(match msgtype {
+            "nic.custom.confetti" => Self::Confetti,
+            "nic.custom.fireworks" => Self::Fireworks,
+            "io.element.effect.hearts" => Self::Hearts,
+            "io.element.effect.rainfall" => Self::Rainfall,
+            "io.element.effect.snowfall" => Self::Snowfall,
+            "io.element.effects.space_invaders" => Self::SpaceInvaders,
+            _ => return None,
+        })
+    }
+}
+
+/// Extracts the full new content of an edit event, if any.
+pub(crate) fn extract_edit_new_content(raw: &Raw<AnySyncTimelineEvent>) -> Option<JsonObject> {
+    let content: JsonObject = raw.get_field("content").ok()??;
+    serde_json::from_value(content.get("m.new_content")?.clone()).ok()
 }
 
 /// Extracts the raw json of the edit event part of bundled relations.
@@ -170,7 +251,7 @@ pub(crate) fn extract_poll_edit_content(
 #[cfg(not(tarpaulin_include))]
 impl fmt::Debug for Message {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let Self { msgtype: _, edited, mentions: _ } = self;
+        let Self { msgtype: _, edited, mentions: _, additional_content: _ } = self;
         // since timeline items are logged, don't include all fields here so
         // people don't leak personal data in bug reports
         f.debug_struct("Message").field("edited", edited).finish_non_exhaustive()
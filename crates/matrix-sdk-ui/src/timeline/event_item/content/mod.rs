@@ -67,10 +67,11 @@ mod reply;
 pub use pinned_events::RoomPinnedEventsChange;
 
 pub(in crate::timeline) use self::message::{
-    extract_bundled_edit_event_json, extract_poll_edit_content, extract_room_msg_edit_content,
+    extract_bundled_edit_event_json, extract_edit_new_content, extract_poll_edit_content,
+    extract_room_msg_edit_content,
 };
 pub use self::{
-    message::Message,
+    message::{Message, MessageEffect},
     msg_like::{MsgLikeContent, MsgLikeKind, ThreadSummary},
     polls::{PollResult, PollState},
     reply::{EmbeddedEvent, InReplyToDetails},
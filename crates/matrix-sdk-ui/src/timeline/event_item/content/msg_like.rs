@@ -48,6 +48,18 @@ pub struct ThreadSummary {
     /// thread-focused timeline with the same timeline filter may result in
     /// *fewer* events than this number.
     pub num_replies: u32,
+
+    /// The ID of the latest event in the thread, if known.
+    pub latest_event_id: Option<OwnedEventId>,
+
+    /// Whether the current user has sent an event in the thread.
+    pub current_user_participated: bool,
+
+    /// Whether the latest event in the thread hasn't been read by the current
+    /// user yet, according to their threaded read receipts.
+    ///
+    /// The events sent by the current user are always considered read.
+    pub is_unread: bool,
 }
 
 /// A special kind of [`super::TimelineItemContent`] that groups together
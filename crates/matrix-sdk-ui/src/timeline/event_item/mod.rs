@@ -44,7 +44,8 @@ mod remote;
 
 pub(super) use self::{
     content::{
-        extract_bundled_edit_event_json, extract_poll_edit_content, extract_room_msg_edit_content,
+        extract_bundled_edit_event_json, extract_edit_new_content, extract_poll_edit_content,
+        extract_room_msg_edit_content,
     },
     local::LocalEventTimelineItem,
     remote::{RemoteEventOrigin, RemoteEventTimelineItem},
@@ -52,8 +53,8 @@ pub(super) use self::{
 pub use self::{
     content::{
         AnyOtherFullStateEventContent, EmbeddedEvent, EncryptedMessage, InReplyToDetails,
-        MemberProfileChange, MembershipChange, Message, MsgLikeContent, MsgLikeKind, OtherState,
-        PollResult, PollState, RoomMembershipChange, RoomPinnedEventsChange, Sticker,
+        MemberProfileChange, MembershipChange, Message, MessageEffect, MsgLikeContent, MsgLikeKind,
+        OtherState, PollResult, PollState, RoomMembershipChange, RoomPinnedEventsChange, Sticker,
         ThreadSummary, TimelineItemContent,
     },
     local::EventSendState,
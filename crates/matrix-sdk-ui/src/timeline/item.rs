@@ -119,6 +119,17 @@ impl TimelineItem {
     pub fn is_timeline_start(&self) -> bool {
         matches!(self.kind, TimelineItemKind::Virtual(VirtualTimelineItem::TimelineStart))
     }
+
+    /// Check whether this item is a (virtual) history unavailable item.
+    pub fn is_history_unavailable(&self) -> bool {
+        matches!(self.kind, TimelineItemKind::Virtual(VirtualTimelineItem::HistoryUnavailable))
+    }
+
+    /// Check whether this item lives in the _start_ region of the timeline,
+    /// i.e. it's either a timeline start or a history unavailable item.
+    pub(crate) fn is_start_region_item(&self) -> bool {
+        self.is_timeline_start() || self.is_history_unavailable()
+    }
 }
 
 impl Deref for TimelineItem {
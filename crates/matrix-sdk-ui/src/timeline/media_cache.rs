@@ -0,0 +1,330 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An in-memory cache of decrypted media content, keyed by event.
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use indexmap::IndexMap;
+use matrix_sdk::media::{MediaRequestParameters, UniqueKey};
+use ruma::{EventId, OwnedEventId};
+use tracing::trace;
+
+/// The default maximum size of the [`DecryptedMediaCache`], in bytes.
+const DEFAULT_MEDIA_CACHE_MAX_SIZE: usize = 32 * 1024 * 1024;
+
+/// A bounded cache of decrypted media content, keyed by the ID of the event
+/// that references the media.
+///
+/// It avoids re-downloading and re-decrypting the media of an event that is
+/// rendered multiple times. Entries are evicted in least-recently-used order
+/// once the total size of the cached content exceeds the maximum size.
+///
+/// Each entry remembers the media request it was created for, so that an edit
+/// changing the media source of an event doesn't return stale content. The
+/// entries of an event are dropped when the event is redacted.
+///
+/// This is cheap to clone, and all clones share the same underlying storage.
+#[derive(Clone)]
+pub(super) struct DecryptedMediaCache {
+    inner: Arc<Mutex<DecryptedMediaCacheInner>>,
+}
+
+struct DecryptedMediaCacheInner {
+    /// The cached media, ordered from the least recently used to the most
+    /// recently used event.
+    entries: IndexMap<OwnedEventId, Vec<CachedMedia>>,
+
+    /// The total size of the cached media content, in bytes.
+    size: usize,
+
+    /// The maximum size of the cached media content, in bytes.
+    max_size: usize,
+}
+
+struct CachedMedia {
+    /// The unique key of the media source used to get the content.
+    source_key: String,
+
+    /// The unique key of the media format used to get the content.
+    format_key: String,
+
+    /// The decrypted media content.
+    content: Arc<Vec<u8>>,
+}
+
+impl DecryptedMediaCache {
+    /// Create a new empty cache, that can hold up to `max_size` bytes of
+    /// media content.
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(DecryptedMediaCacheInner {
+                entries: IndexMap::new(),
+                size: 0,
+                max_size,
+            })),
+        }
+    }
+
+    /// Change the maximum size of the cache, in bytes.
+    ///
+    /// If the cache is already bigger than this, the least recently used
+    /// entries are evicted.
+    pub fn set_max_size(&self, max_size: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.max_size = max_size;
+        inner.evict();
+    }
+
+    /// Get the cached content for the media of the given event, if any.
+    ///
+    /// An entry for the same event and format but a different media source is
+    /// considered stale (e.g. the event was edited to reference a new media),
+    /// and is removed from the cache.
+    pub fn get(
+        &self,
+        event_id: &EventId,
+        request: &MediaRequestParameters,
+    ) -> Option<Arc<Vec<u8>>> {
+        let mut inner = self.inner.lock().unwrap();
+
+        let source_key = request.source.unique_key();
+        let format_key = request.format.unique_key();
+
+        let index = inner.entries.get_index_of(event_id)?;
+        let last = inner.entries.len() - 1;
+
+        let (_, medias) = inner.entries.get_index_mut(index)?;
+
+        let mut content = None;
+        let mut removed_size = 0;
+
+        medias.retain(|media| {
+            if media.format_key != format_key {
+                return true;
+            }
+
+            if media.source_key == source_key {
+                content = Some(media.content.clone());
+                true
+            } else {
+                trace!(%event_id, "removing stale media from the cache");
+                removed_size += media.content.len();
+                false
+            }
+        });
+
+        let is_empty = medias.is_empty();
+
+        inner.size -= removed_size;
+
+        if is_empty {
+            inner.entries.shift_remove_index(index);
+        } else {
+            // Mark the event as the most recently used.
+            inner.entries.move_index(index, last);
+        }
+
+        content
+    }
+
+    /// Insert the decrypted content for the media of the given event.
+    pub fn insert(&self, event_id: &EventId, request: &MediaRequestParameters, content: Vec<u8>) {
+        let mut inner = self.inner.lock().unwrap();
+
+        let content_len = content.len();
+
+        if content_len > inner.max_size {
+            trace!(%event_id, content_len, "media content is too big to be cached");
+            return;
+        }
+
+        let source_key = request.source.unique_key();
+        let format_key = request.format.unique_key();
+
+        let mut removed_size = 0;
+        let medias = inner.entries.entry(event_id.to_owned()).or_default();
+
+        // Replace any previous media for the same format.
+        medias.retain(|media| {
+            let keep = media.format_key != format_key;
+            if !keep {
+                removed_size += media.content.len();
+            }
+            keep
+        });
+        medias.push(CachedMedia { source_key, format_key, content: Arc::new(content) });
+
+        if let Some(index) = inner.entries.get_index_of(event_id) {
+            let last = inner.entries.len() - 1;
+            inner.entries.move_index(index, last);
+        }
+
+        inner.size = inner.size - removed_size + content_len;
+        inner.evict();
+    }
+
+    /// Remove all the cached media of the given event.
+    pub fn invalidate(&self, event_id: &EventId) {
+        let mut inner = self.inner.lock().unwrap();
+
+        if let Some(medias) = inner.entries.shift_remove(event_id) {
+            trace!(%event_id, "invalidating cached media");
+            inner.size -= medias.iter().map(|media| media.content.len()).sum::<usize>();
+        }
+    }
+
+    /// Remove all the cached media.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.clear();
+        inner.size = 0;
+    }
+
+    /// The total size of the cached media content, in bytes.
+    #[cfg(test)]
+    pub fn size(&self) -> usize {
+        self.inner.lock().unwrap().size
+    }
+}
+
+impl Default for DecryptedMediaCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEDIA_CACHE_MAX_SIZE)
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for DecryptedMediaCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.lock().unwrap();
+        f.debug_struct("DecryptedMediaCache")
+            .field("num_events", &inner.entries.len())
+            .field("size", &inner.size)
+            .field("max_size", &inner.max_size)
+            .finish()
+    }
+}
+
+impl DecryptedMediaCacheInner {
+    /// Evict the least recently used entries until the cache fits in its
+    /// maximum size.
+    fn evict(&mut self) {
+        while self.size > self.max_size {
+            let Some((event_id, medias)) = self.entries.shift_remove_index(0) else {
+                break;
+            };
+
+            trace!(%event_id, "evicting media from the cache");
+            self.size -= medias.iter().map(|media| media.content.len()).sum::<usize>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::media::{MediaFormat, MediaRequestParameters, MediaThumbnailSettings};
+    use ruma::{event_id, events::room::MediaSource, uint};
+
+    use super::DecryptedMediaCache;
+
+    fn file_request(uri: &str) -> MediaRequestParameters {
+        MediaRequestParameters { source: MediaSource::Plain(uri.into()), format: MediaFormat::File }
+    }
+
+    #[test]
+    fn test_get_and_invalidate() {
+        let cache = DecryptedMediaCache::new(100);
+        let event_id = event_id!("$ev");
+        let request = file_request("mxc://localhost/media");
+
+        assert!(cache.get(event_id, &request).is_none());
+
+        cache.insert(event_id, &request, vec![1, 2, 3]);
+        assert_eq!(cache.get(event_id, &request).unwrap().as_slice(), &[1, 2, 3]);
+        assert_eq!(cache.size(), 3);
+
+        cache.invalidate(event_id);
+        assert!(cache.get(event_id, &request).is_none());
+        assert_eq!(cache.size(), 0);
+    }
+
+    #[test]
+    fn test_clear() {
+        let cache = DecryptedMediaCache::new(100);
+        let request = file_request("mxc://localhost/media");
+
+        cache.insert(event_id!("$a"), &request, vec![1, 2]);
+        cache.insert(event_id!("$b"), &request, vec![3]);
+        assert_eq!(cache.size(), 3);
+
+        cache.clear();
+        assert!(cache.get(event_id!("$a"), &request).is_none());
+        assert!(cache.get(event_id!("$b"), &request).is_none());
+        assert_eq!(cache.size(), 0);
+    }
+
+    #[test]
+    fn test_new_source_invalidates_previous_content() {
+        let cache = DecryptedMediaCache::new(100);
+        let event_id = event_id!("$ev");
+        let old_request = file_request("mxc://localhost/old");
+        let new_request = file_request("mxc://localhost/new");
+        let thumbnail_request = MediaRequestParameters {
+            source: MediaSource::Plain("mxc://localhost/old".into()),
+            format: MediaFormat::Thumbnail(MediaThumbnailSettings::new(uint!(16), uint!(16))),
+        };
+
+        cache.insert(event_id, &old_request, vec![1, 2, 3]);
+        cache.insert(event_id, &thumbnail_request, vec![4]);
+
+        // The event was edited to point to a new media, the old file is stale.
+        assert!(cache.get(event_id, &new_request).is_none());
+        assert!(cache.get(event_id, &old_request).is_none());
+
+        // But the thumbnail is kept, since it's for another format.
+        assert_eq!(cache.get(event_id, &thumbnail_request).unwrap().as_slice(), &[4]);
+        assert_eq!(cache.size(), 1);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let cache = DecryptedMediaCache::new(5);
+        let request = file_request("mxc://localhost/media");
+
+        cache.insert(event_id!("$a"), &request, vec![0; 2]);
+        cache.insert(event_id!("$b"), &request, vec![0; 2]);
+
+        // Touch `$a` so `$b` becomes the least recently used.
+        assert!(cache.get(event_id!("$a"), &request).is_some());
+
+        cache.insert(event_id!("$c"), &request, vec![0; 2]);
+
+        assert!(cache.get(event_id!("$a"), &request).is_some());
+        assert!(cache.get(event_id!("$b"), &request).is_none());
+        assert!(cache.get(event_id!("$c"), &request).is_some());
+        assert_eq!(cache.size(), 4);
+
+        // Content bigger than the cache is never stored.
+        cache.insert(event_id!("$d"), &request, vec![0; 6]);
+        assert!(cache.get(event_id!("$d"), &request).is_none());
+
+        cache.set_max_size(2);
+        assert_eq!(cache.size(), 2);
+        assert!(cache.get(event_id!("$c"), &request).is_some());
+    }
+}
@@ -33,6 +33,7 @@ use matrix_sdk::{
     event_cache::{EventCacheDropHandles, RoomEventCache},
     event_handler::EventHandlerHandle,
     executor::JoinHandle,
+    media::{MediaEventContent, MediaFormat, MediaRequestParameters},
     room::{edit::EditedContent, reply::Reply, Receipts, Room},
     send_queue::{RoomSendQueueError, SendHandle},
     Client, Result,
@@ -45,12 +46,14 @@ use ruma::{
         poll::unstable_start::{NewUnstablePollStartEventContent, UnstablePollStartEventContent},
         receipt::{Receipt, ReceiptThread},
         room::{
-            message::RoomMessageEventContentWithoutRelation,
+            message::{MessageType, RoomMessageEventContentWithoutRelation},
             pinned_events::RoomPinnedEventsEventContent,
+            MediaSource,
         },
         AnyMessageLikeEventContent, AnySyncTimelineEvent,
     },
-    EventId, OwnedEventId, RoomVersionId, UserId,
+    serde::Raw,
+    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, RoomVersionId, UserId,
 };
 #[cfg(feature = "unstable-msc4274")]
 use ruma::{
@@ -67,14 +70,17 @@ use self::{
 
 mod algorithms;
 mod builder;
+mod clock_skew;
 mod controller;
 mod date_dividers;
+mod day_partitions;
 mod error;
 mod event_handler;
 mod event_item;
 pub mod event_type_filter;
 pub mod futures;
 mod item;
+mod media_cache;
 mod pagination;
 mod pinned_events_loader;
 mod subscriber;
@@ -87,12 +93,13 @@ mod virtual_item;
 pub use self::{
     builder::TimelineBuilder,
     controller::default_event_filter,
+    day_partitions::DayPartition,
     error::*,
     event_item::{
         AnyOtherFullStateEventContent, EmbeddedEvent, EncryptedMessage, EventItemOrigin,
         EventSendState, EventTimelineItem, InReplyToDetails, MemberProfileChange, MembershipChange,
-        Message, MsgLikeContent, MsgLikeKind, OtherState, PollResult, PollState, Profile,
-        ReactionInfo, ReactionStatus, ReactionsByKeyBySender, RoomMembershipChange,
+        Message, MessageEffect, MsgLikeContent, MsgLikeKind, OtherState, PollResult, PollState,
+        Profile, ReactionInfo, ReactionStatus, ReactionsByKeyBySender, RoomMembershipChange,
         RoomPinnedEventsChange, Sticker, ThreadSummary, TimelineDetails, TimelineEventItemId,
         TimelineItemContent,
     },
@@ -174,6 +181,28 @@ pub enum DateDividerMode {
     Monthly,
 }
 
+/// How the timeline resolves the profiles of the senders of its events.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProfileResolution {
+    /// Resolve the profiles from the memberships of the room.
+    #[default]
+    RoomMembership,
+
+    /// Resolve the profiles from the global profiles of the senders.
+    ///
+    /// The global profiles are fetched from the homeserver in the background,
+    /// and cached for the lifetime of the timeline. A profile is fetched again
+    /// if the fetch failed, or if the membership of the sender changed.
+    GlobalProfile,
+
+    /// Don't resolve the profiles, only the IDs of the senders are available.
+    ///
+    /// This saves memory, at the cost of displaying less information. The
+    /// profile of a specific sender can still be resolved on demand, with
+    /// [`Timeline::fetch_sender_profile`].
+    None,
+}
+
 impl Timeline {
     /// Returns the room for this timeline.
     pub fn room(&self) -> &Room {
@@ -246,6 +275,17 @@ impl Timeline {
         }
     }
 
+    /// Get the current time, corrected from the estimated skew between the
+    /// local clock and the clock of the homeserver if
+    /// [`TimelineBuilder::with_clock_skew_correction`] is enabled.
+    ///
+    /// This is the time the local echoes are stamped with, so it should be
+    /// used to compute relative times like "X minutes ago" for the items of
+    /// the timeline.
+    pub async fn now(&self) -> MilliSecondsSinceUnixEpoch {
+        self.controller.now().await
+    }
+
     /// Get the current timeline items, along with a stream of updates of
     /// timeline items.
     ///
@@ -464,6 +504,71 @@ impl Timeline {
         SendGallery::new(self, gallery)
     }
 
+    /// Get the decrypted content of the media attached to the event with the
+    /// given ID.
+    ///
+    /// The content is kept in a bounded in-memory cache keyed by event ID, so
+    /// that rendering the same media again doesn't require downloading and
+    /// decrypting it again. Cached content is invalidated when the event is
+    /// redacted, or when it's edited to point to another media.
+    ///
+    /// # Arguments
+    ///
+    /// * `event_id` - The ID of the event holding the media.
+    ///
+    /// * `format` - The format of the media, i.e. the file itself or a
+    ///   thumbnail. Requesting a thumbnail uses the thumbnail source of the
+    ///   media, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the event is not in the timeline, if it doesn't
+    /// have a media in the requested format, or if fetching the media failed.
+    #[instrument(skip(self, format), fields(room_id = ?self.room().room_id()))]
+    pub async fn fetch_media_content(
+        &self,
+        event_id: &EventId,
+        format: MediaFormat,
+    ) -> Result<Vec<u8>, Error> {
+        let media_cache = self.controller.media_cache().await;
+
+        let Some(item) = self.item_by_event_id(event_id).await else {
+            return Err(Error::EventNotInTimeline(TimelineEventItemId::EventId(
+                event_id.to_owned(),
+            )));
+        };
+
+        let source = match &format {
+            MediaFormat::File => media_source(item.content()),
+            MediaFormat::Thumbnail(_) => thumbnail_source(item.content()),
+        };
+
+        let Some(source) = source else {
+            // The event may have been redacted in the meantime.
+            media_cache.invalidate(event_id);
+            return Err(Error::UnsupportedEvent);
+        };
+
+        let request = MediaRequestParameters { source, format };
+
+        if let Some(content) = media_cache.get(event_id, &request) {
+            trace!("using cached media content");
+            return Ok(content.as_ref().clone());
+        }
+
+        let content = self
+            .room()
+            .client()
+            .media()
+            .get_media_content(&request, true)
+            .await
+            .map_err(Error::FailedFetchingMedia)?;
+
+        media_cache.insert(event_id, &request, content.clone());
+
+        Ok(content)
+    }
+
     /// Redact an event given its [`TimelineEventItemId`] and an optional
     /// reason.
     pub async fn redact(
@@ -534,6 +639,41 @@ impl Timeline {
         }
     }
 
+    /// Resolve the profile of a single sender on demand, e.g. when the user
+    /// wants to see details about them.
+    ///
+    /// This works whatever the [`ProfileResolution`] of the timeline: the
+    /// profile is resolved from the room memberships first, and from the
+    /// global profile of the sender otherwise. It's then used for all the
+    /// items of this sender, including the ones received later, until their
+    /// membership changes.
+    ///
+    /// Returns `None` if the profile couldn't be resolved.
+    #[instrument(skip(self))]
+    pub async fn fetch_sender_profile(&self, user_id: &UserId) -> Option<Profile> {
+        self.controller.fetch_sender_profile(user_id).await
+    }
+
+    /// Insert an event obtained out of band, i.e. not received from the
+    /// homeserver, e.g. from a bridge or a parallel transport during a
+    /// migration period.
+    ///
+    /// The event is inserted in the room's event cache, so it shows up in all
+    /// the live timelines of the room. It's ordered according to its
+    /// `origin_server_ts` among the loaded events, and it's replaced by the
+    /// event with the same ID if the homeserver later sends it. See
+    /// [`RoomEventCache::insert_out_of_band_event`] for more details.
+    ///
+    /// Returns `false`, without inserting anything, if an event with the same
+    /// ID is already known.
+    pub async fn insert_out_of_band_event(
+        &self,
+        event: Raw<AnySyncTimelineEvent>,
+    ) -> Result<bool, Error> {
+        let event = TimelineEvent::from_plaintext(event);
+        Ok(self.event_cache.insert_out_of_band_event(event).await?)
+    }
+
     /// Get the latest read receipt for the given user.
     ///
     /// Contrary to [`Room::load_user_receipt()`] that only keeps track of read
@@ -774,6 +914,7 @@ struct TimelineDropHandle {
     event_handler_handles: Vec<EventHandlerHandle>,
     room_update_join_handle: JoinHandle<()>,
     pinned_events_join_handle: Option<JoinHandle<()>>,
+    global_profiles_join_handle: Option<JoinHandle<()>>,
     room_key_from_backups_join_handle: JoinHandle<()>,
     room_keys_received_join_handle: JoinHandle<()>,
     room_key_backup_enabled_join_handle: JoinHandle<()>,
@@ -792,6 +933,10 @@ impl Drop for TimelineDropHandle {
             handle.abort()
         };
 
+        if let Some(handle) = self.global_profiles_join_handle.take() {
+            handle.abort()
+        };
+
         self.local_echo_listener_handle.abort();
         self.room_update_join_handle.abort();
         self.room_key_from_backups_join_handle.abort();
@@ -801,6 +946,36 @@ impl Drop for TimelineDropHandle {
     }
 }
 
+/// Get the source of the media attached to the given content, if any.
+fn media_source(content: &TimelineItemContent) -> Option<MediaSource> {
+    match &content.as_msglike()?.kind {
+        MsgLikeKind::Message(message) => match message.msgtype() {
+            MessageType::Audio(content) => content.source(),
+            MessageType::File(content) => content.source(),
+            MessageType::Image(content) => content.source(),
+            MessageType::Video(content) => content.source(),
+            _ => None,
+        },
+        MsgLikeKind::Sticker(sticker) => sticker.content().source(),
+        _ => None,
+    }
+}
+
+/// Get the source of the thumbnail of the media attached to the given content,
+/// if any.
+fn thumbnail_source(content: &TimelineItemContent) -> Option<MediaSource> {
+    match &content.as_msglike()?.kind {
+        MsgLikeKind::Message(message) => match message.msgtype() {
+            MessageType::File(content) => content.thumbnail_source(),
+            MessageType::Image(content) => content.thumbnail_source(),
+            MessageType::Video(content) => content.thumbnail_source(),
+            _ => None,
+        },
+        MsgLikeKind::Sticker(sticker) => sticker.content().thumbnail_source(),
+        _ => None,
+    }
+}
+
 #[cfg(not(target_family = "wasm"))]
 pub type TimelineEventFilterFn =
     dyn Fn(&AnySyncTimelineEvent, &RoomVersionId) -> bool + Send + Sync;
@@ -75,7 +75,9 @@ impl super::Timeline {
                     // As an exceptional contract, restart the back-pagination if we received an
                     // empty chunk.
                     if outcome.reached_start || !outcome.events.is_empty() {
-                        if outcome.reached_start {
+                        if outcome.history_unavailable {
+                            self.controller.insert_history_unavailable_if_missing().await;
+                        } else if outcome.reached_start {
                             self.controller.insert_timeline_start_if_missing().await;
                         }
                         return Ok(outcome.reached_start);
@@ -12,15 +12,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeSet;
+
 use assert_matches::assert_matches;
 use assert_matches2::assert_let;
 use eyeball_im::VectorDiff;
 use futures_util::StreamExt;
 use imbl::vector;
+use matrix_sdk::deserialized_responses::TimelineEvent;
 use matrix_sdk_test::{
     async_test,
     event_factory::{EventFactory, PreviousMembership},
-    ALICE, BOB, CAROL,
+    sync_timeline_event, ALICE, BOB, CAROL,
 };
 use ruma::{
     event_id,
@@ -43,8 +46,8 @@ use crate::timeline::{
     controller::TimelineSettings,
     event_item::{AnyOtherFullStateEventContent, RemoteEventOrigin},
     tests::{ReadReceiptMap, TestRoomDataProvider, TestTimelineBuilder},
-    MembershipChange, MsgLikeContent, MsgLikeKind, TimelineDetails, TimelineItemContent,
-    TimelineItemKind, VirtualTimelineItem,
+    MembershipChange, MessageEffect, MsgLikeContent, MsgLikeKind, Profile, ProfileResolution,
+    TimelineDetails, TimelineItemContent, TimelineItemKind, VirtualTimelineItem,
 };
 
 #[async_test]
@@ -157,6 +160,112 @@ async fn test_sticker() {
     assert_eq!(details.event_id, event_id!("$in_reply_to").to_owned())
 }
 
+#[async_test]
+async fn test_message_additional_content_and_effect() {
+    let timeline = TestTimeline::new();
+    let mut stream = timeline.subscribe_events().await;
+
+    // A message with an effect defined by its `msgtype`.
+    timeline
+        .handle_live_event(TimelineEvent::from_plaintext(sync_timeline_event!({
+            "content": {
+                "msgtype": "nic.custom.confetti",
+                "body": "🎉",
+            },
+            "event_id": "$confetti",
+            "origin_server_ts": 10,
+            "sender": "@alice:example.org",
+            "type": "m.room.message",
+        })))
+        .await;
+
+    let item = assert_next_matches!(stream, VectorDiff::PushBack { value } => value);
+    let message = item.content().as_message().unwrap();
+    assert_eq!(message.effect(), Some(MessageEffect::Confetti));
+
+    // A text message with custom fields.
+    timeline
+        .handle_live_event(TimelineEvent::from_plaintext(sync_timeline_event!({
+            "content": {
+                "msgtype": "m.text",
+                "body": "Let it snow",
+                "io.element.effect": "io.element.effect.snowfall",
+                "com.example.metadata": { "answer": 42 },
+                "m.mentions": {},
+            },
+            "event_id": "$snow",
+            "origin_server_ts": 20,
+            "sender": "@alice:example.org",
+            "type": "m.room.message",
+        })))
+        .await;
+
+    let item = assert_next_matches!(stream, VectorDiff::PushBack { value } => value);
+    let message = item.content().as_message().unwrap();
+    assert_eq!(message.body(), "Let it snow");
+    // The effects are only defined by their `msgtype`.
+    assert_eq!(message.effect(), None);
+
+    let additional_content = message.additional_content();
+    assert_eq!(additional_content.len(), 2);
+    assert_eq!(additional_content["com.example.metadata"]["answer"], 42);
+
+    // A message with a bundled edit has the additional content of the edit.
+    timeline
+        .handle_live_event(TimelineEvent::from_plaintext(sync_timeline_event!({
+            "content": {
+                "msgtype": "m.text",
+                "body": "original",
+                "com.example.metadata": { "version": 1 },
+                "com.example.original_only": true,
+            },
+            "event_id": "$original",
+            "origin_server_ts": 30,
+            "sender": "@alice:example.org",
+            "type": "m.room.message",
+            "unsigned": {
+                "m.relations": {
+                    "m.replace": {
+                        "content": {
+                            "msgtype": "m.text",
+                            "body": "* edited",
+                            "m.new_content": {
+                                "msgtype": "m.text",
+                                "body": "edited",
+                                "com.example.metadata": { "version": 2 },
+                            },
+                            "m.relates_to": {
+                                "rel_type": "m.replace",
+                                "event_id": "$original",
+                            },
+                        },
+                        "event_id": "$edit",
+                        "origin_server_ts": 40,
+                        "sender": "@alice:example.org",
+                        "type": "m.room.message",
+                    },
+                },
+            },
+        })))
+        .await;
+
+    let item = assert_next_matches!(stream, VectorDiff::PushBack { value } => value);
+    let message = item.content().as_message().unwrap();
+    assert_eq!(message.body(), "edited");
+
+    let additional_content = message.additional_content();
+    assert_eq!(additional_content.len(), 1);
+    assert_eq!(additional_content["com.example.metadata"]["version"], 2);
+
+    // A plain message has no effect, nor additional content.
+    timeline.handle_live_event(timeline.factory.text_msg("hi").sender(&ALICE)).await;
+
+    let item = assert_next_matches!(stream, VectorDiff::PushBack { value } => value);
+    let message = item.content().as_message().unwrap();
+    assert_eq!(message.effect(), None);
+    assert!(message.additional_content().is_empty());
+}
+
 #[async_test]
 async fn test_room_member() {
     let timeline = TestTimeline::new();
@@ -507,3 +616,183 @@ async fn test_replace_with_initial_events_when_batched() {
         assert_matches!(value.as_virtual(), Some(VirtualTimelineItem::DateDivider(_)));
     });
 }
+
+#[async_test]
+async fn test_profile_resolution_none_with_lazy_upgrade() {
+    let bob_profile = Profile { display_name: Some("Bob".to_owned()), ..Default::default() };
+    let timeline = TestTimelineBuilder::new()
+        .provider(TestRoomDataProvider::default().with_member_profile(&BOB, bob_profile.clone()))
+        .settings(TimelineSettings {
+            profile_resolution: ProfileResolution::None,
+            ..Default::default()
+        })
+        .build();
+
+    let f = &timeline.factory;
+    timeline.handle_live_event(f.text_msg("hi").sender(&BOB)).await;
+
+    // The profile isn't resolved, even though the member is known.
+    let items = timeline.controller.items().await;
+    assert_eq!(items.len(), 2);
+    assert_matches!(items[1].as_event().unwrap().sender_profile(), TimelineDetails::Unavailable);
+
+    // Until it's requested explicitly.
+    assert_eq!(timeline.controller.fetch_sender_profile(&BOB).await, Some(bob_profile.clone()));
+
+    let items = timeline.controller.items().await;
+    assert_let!(TimelineDetails::Ready(profile) = items[1].as_event().unwrap().sender_profile());
+    assert_eq!(*profile, bob_profile);
+
+    // The profile is then used for the new events of that sender.
+    timeline.handle_live_event(f.text_msg("hello again").sender(&BOB)).await;
+
+    let items = timeline.controller.items().await;
+    assert_eq!(items.len(), 3);
+    assert_let!(TimelineDetails::Ready(profile) = items[2].as_event().unwrap().sender_profile());
+    assert_eq!(*profile, bob_profile);
+}
+
+#[async_test]
+async fn test_profile_resolution_global_profile() {
+    let member_profile =
+        Profile { display_name: Some("Bob in the room".to_owned()), ..Default::default() };
+    let global_profile = Profile { display_name: Some("Bob".to_owned()), ..Default::default() };
+    let timeline = TestTimelineBuilder::new()
+        .provider(
+            TestRoomDataProvider::default()
+                .with_member_profile(&BOB, member_profile)
+                .with_global_profile(&BOB, global_profile.clone()),
+        )
+        .settings(TimelineSettings {
+            profile_resolution: ProfileResolution::GlobalProfile,
+            ..Default::default()
+        })
+        .build();
+
+    let f = &timeline.factory;
+    timeline.handle_live_event(f.text_msg("hi").sender(&BOB)).await;
+    timeline.handle_live_event(f.text_msg("hi").sender(&CAROL)).await;
+
+    // The global profiles haven't been fetched yet.
+    let items = timeline.controller.items().await;
+    assert_eq!(items.len(), 3);
+    assert_matches!(items[1].as_event().unwrap().sender_profile(), TimelineDetails::Unavailable);
+    assert_matches!(items[2].as_event().unwrap().sender_profile(), TimelineDetails::Unavailable);
+
+    timeline.controller.fetch_missing_global_profiles().await;
+
+    // The global profile is used rather than the member profile, and Carol has
+    // no global profile.
+    let items = timeline.controller.items().await;
+    assert_let!(TimelineDetails::Ready(profile) = items[1].as_event().unwrap().sender_profile());
+    assert_eq!(*profile, global_profile);
+    assert_matches!(items[2].as_event().unwrap().sender_profile(), TimelineDetails::Unavailable);
+
+    // The cached global profile is used for the new events.
+    timeline.handle_live_event(f.text_msg("hello again").sender(&BOB)).await;
+
+    let items = timeline.controller.items().await;
+    assert_let!(TimelineDetails::Ready(profile) = items[3].as_event().unwrap().sender_profile());
+    assert_eq!(*profile, global_profile);
+}
+
+#[async_test]
+async fn test_forced_profile_update_ignores_cached_profile() {
+    let bob_profile = Profile { display_name: Some("Bob".to_owned()), ..Default::default() };
+    let provider = TestRoomDataProvider::default().with_member_profile(&BOB, bob_profile.clone());
+    let timeline = TestTimelineBuilder::new().provider(provider.clone()).build();
+
+    let f = &timeline.factory;
+    timeline.handle_live_event(f.text_msg("hi").sender(&BOB)).await;
+
+    // The profile is resolved on demand, and cached.
+    assert_eq!(timeline.controller.fetch_sender_profile(&BOB).await, Some(bob_profile));
+
+    // When the member profile changes, forcing the update uses the new profile
+    // rather than the cached one.
+    let new_profile = Profile { display_name: Some("Bobby".to_owned()), ..Default::default() };
+    provider.member_profiles.write().await.insert(BOB.to_owned(), new_profile.clone());
+
+    timeline.controller.force_update_sender_profiles(&BTreeSet::from([*BOB])).await;
+
+    let items = timeline.controller.items().await;
+    assert_let!(TimelineDetails::Ready(profile) = items[1].as_event().unwrap().sender_profile());
+    assert_eq!(*profile, new_profile);
+}
+
+#[async_test]
+async fn test_global_profile_is_fetched_again_after_membership_change() {
+    let global_profile = Profile { display_name: Some("Bob".to_owned()), ..Default::default() };
+    let provider =
+        TestRoomDataProvider::default().with_global_profile(&BOB, global_profile.clone());
+    let timeline = TestTimelineBuilder::new()
+        .provider(provider.clone())
+        .settings(TimelineSettings {
+            profile_resolution: ProfileResolution::GlobalProfile,
+            ..Default::default()
+        })
+        .build();
+
+    let f = &timeline.factory;
+    timeline.handle_live_event(f.text_msg("hi").sender(&BOB)).await;
+    timeline.controller.fetch_missing_global_profiles().await;
+
+    let items = timeline.controller.items().await;
+    assert_let!(TimelineDetails::Ready(profile) = items[1].as_event().unwrap().sender_profile());
+    assert_eq!(*profile, global_profile);
+
+    // When Bob changes their profile, the new global profile is fetched, and used
+    // for all their items.
+    let new_profile = Profile { display_name: Some("Bobby".to_owned()), ..Default::default() };
+    provider.global_profiles.write().await.insert(BOB.to_owned(), new_profile.clone());
+
+    timeline.handle_live_event(f.member(&BOB).display_name("Bobby")).await;
+    timeline.controller.fetch_missing_global_profiles().await;
+
+    let items = timeline.controller.items().await;
+    let bob_items = items
+        .iter()
+        .filter_map(|item| item.as_event())
+        .filter(|event| event.sender() == *BOB)
+        .collect::<Vec<_>>();
+    assert_eq!(bob_items.len(), 2);
+    for item in bob_items {
+        assert_let!(TimelineDetails::Ready(profile) = item.sender_profile());
+        assert_eq!(*profile, new_profile);
+    }
+}
+
+#[async_test]
+async fn test_global_profile_is_fetched_again_after_failure() {
+    let provider = TestRoomDataProvider::default();
+    let timeline = TestTimelineBuilder::new()
+        .provider(provider.clone())
+        .settings(TimelineSettings {
+            profile_resolution: ProfileResolution::GlobalProfile,
+            ..Default::default()
+        })
+        .build();
+
+    let f = &timeline.factory;
+    timeline.handle_live_event(f.text_msg("hi").sender(&BOB)).await;
+
+    // The global profile can't be fetched.
+    timeline.controller.fetch_missing_global_profiles().await;
+
+    let items = timeline.controller.items().await;
+    assert_matches!(items[1].as_event().unwrap().sender_profile(), TimelineDetails::Unavailable);
+
+    // It's requested again with the next event of that sender.
+    let global_profile = Profile { display_name: Some("Bob".to_owned()), ..Default::default() };
+    provider.global_profiles.write().await.insert(BOB.to_owned(), global_profile.clone());
+
+    timeline.handle_live_event(f.text_msg("hello again").sender(&BOB)).await;
+    timeline.controller.fetch_missing_global_profiles().await;
+
+    let items = timeline.controller.items().await;
+    assert_eq!(items.len(), 3);
+    for item in &items[1..] {
+        assert_let!(TimelineDetails::Ready(profile) = item.as_event().unwrap().sender_profile());
+        assert_eq!(*profile, global_profile);
+    }
+}
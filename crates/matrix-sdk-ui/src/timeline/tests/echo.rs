@@ -17,13 +17,15 @@ use std::sync::Arc;
 use assert_matches::assert_matches;
 use eyeball_im::VectorDiff;
 use matrix_sdk::{assert_next_matches_with_timeout, send_queue::RoomSendQueueUpdate};
-use matrix_sdk_base::store::QueueWedgeError;
+use matrix_sdk_base::{deserialized_responses::TimelineEvent, store::QueueWedgeError};
 use matrix_sdk_test::{async_test, ALICE, BOB};
 use ruma::{
     event_id,
     events::{room::message::RoomMessageEventContent, AnyMessageLikeEventContent},
+    serde::Raw,
     user_id, MilliSecondsSinceUnixEpoch,
 };
+use serde_json::json;
 use stream_assert::{assert_next_matches, assert_pending};
 
 use super::TestTimeline;
@@ -366,3 +368,35 @@ async fn test_no_reuse_of_counters() {
     // The remote id still isn't the same as the local id.
     assert_ne!(local_id, remote_id);
 }
+
+#[async_test]
+async fn test_local_echo_timestamp_corrects_clock_skew() {
+    let timeline = TestTimelineBuilder::new()
+        .settings(TimelineSettings { correct_clock_skew: true, ..Default::default() })
+        .build();
+
+    // The clock of the homeserver is 10 days ahead of the local clock.
+    let ten_days = 10 * 24 * 60 * 60 * 1000;
+    let server_ts = u64::from(MilliSecondsSinceUnixEpoch::now().0) + ten_days;
+
+    let event = json!({
+        "type": "m.room.message",
+        "event_id": "$remote",
+        "sender": "@a:b.c",
+        "origin_server_ts": server_ts,
+        "content": { "msgtype": "m.text", "body": "remote echo" },
+        "unsigned": { "age": 0 },
+    });
+    timeline
+        .handle_live_event(TimelineEvent::from_plaintext(Raw::new(&event).unwrap().cast()))
+        .await;
+
+    timeline.handle_local_event(RoomMessageEventContent::text_plain("local echo").into()).await;
+
+    // The local echo is stamped with the time of the homeserver, not with the time
+    // of the local clock, so it's after the remote event.
+    let items = timeline.controller.items().await;
+    let local_echo = items.last().unwrap().as_event().unwrap();
+    assert!(local_echo.is_local_echo());
+    assert!(u64::from(local_echo.timestamp().0) >= server_ts);
+}
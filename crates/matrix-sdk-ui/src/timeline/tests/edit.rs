@@ -23,7 +23,12 @@ use matrix_sdk_base::deserialized_responses::{DecryptedRoomEvent, TimelineEvent}
 use matrix_sdk_test::{async_test, ALICE, BOB};
 use ruma::{
     event_id,
-    events::room::message::{MessageType, RedactedRoomMessageEventContent},
+    events::{
+        room::message::{
+            MessageType, RedactedRoomMessageEventContent, RoomMessageEventContentWithoutRelation,
+        },
+        Mentions,
+    },
     room_id,
 };
 use stream_assert::{assert_next_matches, assert_pending};
@@ -101,6 +106,48 @@ async fn test_live_sanitized() {
     assert_eq!(text.formatted.as_ref().unwrap().body, " <strong>better</strong> message");
 }
 
+#[async_test]
+async fn test_live_edit_of_emote_with_mentions() {
+    let timeline = TestTimeline::new();
+    let mut stream = timeline.subscribe().await;
+
+    let f = &timeline.factory;
+    timeline.handle_live_event(f.emote("waves").sender(&ALICE)).await;
+
+    let item = assert_next_matches!(stream, VectorDiff::PushBack { value } => value);
+    let first_event_id = item.as_event().unwrap().event_id().unwrap().to_owned();
+    assert!(item.as_event().unwrap().content().as_message().unwrap().mentions().is_none());
+
+    let date_divider = assert_next_matches!(stream, VectorDiff::PushFront { value } => value);
+    assert!(date_divider.is_date_divider());
+
+    let mut new_content = RoomMessageEventContentWithoutRelation::new(MessageType::emote_html(
+        "waves at Bob",
+        "waves at <a href=\"https://matrix.to/#/@bob:other.server\">Bob</a>",
+    ));
+    new_content.mentions = Some(Mentions::with_user_ids([BOB.to_owned()]));
+
+    timeline
+        .handle_live_event(
+            f.emote("* waves at Bob").sender(&ALICE).edit(&first_event_id, new_content),
+        )
+        .await;
+
+    // The edit is applied with its formatted body and its mentions.
+    let item = assert_next_matches!(stream, VectorDiff::Set { index: 1, value } => value);
+    assert_let!(Some(message) = item.as_event().unwrap().content().as_message());
+    assert!(message.is_edited());
+    assert_let!(MessageType::Emote(emote) = message.msgtype());
+    assert_eq!(emote.body, "waves at Bob");
+    assert_eq!(
+        emote.formatted.as_ref().unwrap().body,
+        "waves at <a href=\"https://matrix.to/#/@bob:other.server\">Bob</a>"
+    );
+    assert_let!(Some(mentions) = message.mentions());
+    assert_eq!(mentions.user_ids.len(), 1);
+    assert!(mentions.user_ids.contains(*BOB));
+}
+
 #[async_test]
 async fn test_aggregated_sanitized() {
     let timeline = TestTimeline::new();
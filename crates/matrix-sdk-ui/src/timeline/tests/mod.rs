@@ -265,6 +265,12 @@ struct TestRoomDataProvider {
     /// The [`EncryptionInfo`] describing the Megolm sessions that were used to
     /// encrypt events.
     pub encryption_info: HashMap<String, Arc<EncryptionInfo>>,
+
+    /// The profiles of the room members.
+    member_profiles: Arc<RwLock<HashMap<OwnedUserId, Profile>>>,
+
+    /// The global profiles of the users.
+    global_profiles: Arc<RwLock<HashMap<OwnedUserId, Profile>>>,
 }
 
 impl TestRoomDataProvider {
@@ -286,6 +292,16 @@ impl TestRoomDataProvider {
         self.encryption_info.insert(session_id.to_owned(), encryption_info);
         self
     }
+
+    fn with_member_profile(self, user_id: &UserId, profile: Profile) -> Self {
+        self.member_profiles.try_write().unwrap().insert(user_id.to_owned(), profile);
+        self
+    }
+
+    fn with_global_profile(self, user_id: &UserId, profile: Profile) -> Self {
+        self.global_profiles.try_write().unwrap().insert(user_id.to_owned(), profile);
+        self
+    }
 }
 
 impl PaginableRoom for TestRoomDataProvider {
@@ -360,14 +376,18 @@ impl RoomDataProvider for TestRoomDataProvider {
         }
     }
 
-    async fn profile_from_user_id<'a>(&'a self, _user_id: &'a UserId) -> Option<Profile> {
-        None
+    async fn profile_from_user_id<'a>(&'a self, user_id: &'a UserId) -> Option<Profile> {
+        self.member_profiles.read().await.get(user_id).cloned()
     }
 
     fn profile_from_latest_event(&self, _latest_event: &LatestEvent) -> Option<Profile> {
         None
     }
 
+    async fn fetch_global_profile<'a>(&'a self, user_id: &'a UserId) -> Option<Profile> {
+        self.global_profiles.read().await.get(user_id).cloned()
+    }
+
     async fn load_user_receipt<'a>(
         &'a self,
         receipt_type: ReceiptType,
@@ -16,13 +16,16 @@ use assert_matches::assert_matches;
 use assert_matches2::assert_let;
 use eyeball_im::VectorDiff;
 use imbl::vector;
+use matrix_sdk::media::{MediaFormat, MediaRequestParameters};
 use matrix_sdk_test::{async_test, ALICE, BOB};
 use ruma::{
     event_id,
     events::{
-        reaction::RedactedReactionEventContent, room::message::OriginalSyncRoomMessageEvent,
+        reaction::RedactedReactionEventContent,
+        room::{message::OriginalSyncRoomMessageEvent, MediaSource},
         FullStateEventContent,
     },
+    owned_mxc_uri,
 };
 use stream_assert::{assert_next_matches, assert_pending};
 
@@ -203,3 +206,29 @@ async fn test_reaction_redaction_timeline_filter() {
     assert_eq!(item.content().reactions().cloned().unwrap_or_default().len(), 0);
     assert_eq!(timeline.controller.items().await.len(), 2);
 }
+
+#[async_test]
+async fn test_redaction_invalidates_cached_media() {
+    let timeline = TestTimeline::new();
+    let mut stream = timeline.subscribe_events().await;
+
+    let f = &timeline.factory;
+
+    let mxc = owned_mxc_uri!("mxc://localhost/image");
+    timeline.handle_live_event(f.image("cat.png".to_owned(), mxc.clone()).sender(&ALICE)).await;
+
+    let item = assert_next_matches!(stream, VectorDiff::PushBack { value } => value);
+    let event_id = item.event_id().unwrap().to_owned();
+
+    let request =
+        MediaRequestParameters { source: MediaSource::Plain(mxc), format: MediaFormat::File };
+    let media_cache = timeline.controller.media_cache().await;
+    media_cache.insert(&event_id, &request, vec![1, 2, 3]);
+    assert!(media_cache.get(&event_id, &request).is_some());
+
+    timeline.handle_live_event(f.redaction(&event_id).sender(&ALICE)).await;
+
+    let item = assert_next_matches!(stream, VectorDiff::Set { index: 0, value } => value);
+    assert!(item.content().is_redacted());
+    assert!(media_cache.get(&event_id, &request).is_none());
+}
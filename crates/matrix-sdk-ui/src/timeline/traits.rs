@@ -35,7 +35,7 @@ use ruma::{
     serde::Raw,
     EventId, OwnedEventId, OwnedTransactionId, OwnedUserId, RoomVersionId, UserId,
 };
-use tracing::error;
+use tracing::{error, warn};
 
 use super::{EventTimelineItem, Profile, RedactError, TimelineBuilder};
 use crate::timeline::{self, pinned_events_loader::PinnedEventsRoom, Timeline};
@@ -101,6 +101,12 @@ pub(super) trait RoomDataProvider:
     ) -> impl Future<Output = Option<Profile>> + SendOutsideWasm + 'a;
     fn profile_from_latest_event(&self, latest_event: &LatestEvent) -> Option<Profile>;
 
+    /// Fetch the global profile of a user from the homeserver.
+    fn fetch_global_profile<'a>(
+        &'a self,
+        user_id: &'a UserId,
+    ) -> impl Future<Output = Option<Profile>> + SendOutsideWasm + 'a;
+
     /// Loads a user receipt from the storage backend.
     fn load_user_receipt<'a>(
         &'a self,
@@ -180,6 +186,20 @@ impl RoomDataProvider for Room {
         }
     }
 
+    async fn fetch_global_profile<'a>(&'a self, user_id: &'a UserId) -> Option<Profile> {
+        match self.client().account().fetch_user_profile_of(user_id).await {
+            Ok(response) => Some(Profile {
+                display_name: response.displayname,
+                display_name_ambiguous: false,
+                avatar_url: response.avatar_url,
+            }),
+            Err(e) => {
+                warn!(%user_id, "Failed to fetch global profile: {e}");
+                None
+            }
+        }
+    }
+
     fn profile_from_latest_event(&self, latest_event: &LatestEvent) -> Option<Profile> {
         if !latest_event.has_sender_profile() {
             return None;
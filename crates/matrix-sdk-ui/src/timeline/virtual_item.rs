@@ -30,4 +30,12 @@ pub enum VirtualTimelineItem {
     /// The timeline start, that is, an indication that we've seen all the
     /// events for that timeline.
     TimelineStart,
+
+    /// The start of the readable history, that is, an indication that the
+    /// server refused to return older events for that timeline.
+    ///
+    /// This happens when older events have been purged by the server, or when
+    /// the history visibility of the room prevents the user from seeing them.
+    /// It replaces the [`Self::TimelineStart`] item in that case.
+    HistoryUnavailable,
 }
@@ -0,0 +1,207 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use assert_matches2::assert_matches;
+use futures_util::pin_mut;
+use matrix_sdk::{assert_next_with_timeout, test_utils::mocks::MatrixMockServer};
+use matrix_sdk_test::{async_test, JoinedRoomBuilder};
+use matrix_sdk_ui::{account_manager::AccountManagerError, sync_service::State, AccountManager};
+use ruma::{owned_device_id, room_id, user_id};
+use serde_json::json;
+use wiremock::{Mock, Request, ResponseTemplate};
+
+use crate::sliding_sync::{PartialSlidingSyncRequest, SlidingSyncMatcher};
+
+#[async_test]
+async fn test_add_and_remove_accounts() {
+    let server = MatrixMockServer::new().await;
+    let alice = server.client_builder().build().await;
+    let bob = server
+        .client_builder()
+        .logged_in_with_token(
+            "bob_token".to_owned(),
+            user_id!("@bob:localhost").to_owned(),
+            owned_device_id!("BOB"),
+        )
+        .build()
+        .await;
+
+    let manager = AccountManager::new();
+    manager.add_account(alice.clone()).await.unwrap();
+    manager.add_account(bob).await.unwrap();
+
+    // An account can only be added once.
+    assert_matches!(
+        manager.add_account(alice).await,
+        Err(AccountManagerError::AlreadyAdded(user_id))
+    );
+    assert_eq!(user_id, "@example:localhost");
+
+    // A client needs to be logged in to be added.
+    let unlogged = server.client_builder().unlogged().build().await;
+    assert_matches!(manager.add_account(unlogged).await, Err(AccountManagerError::NotLoggedIn));
+
+    let accounts = manager.accounts();
+    assert_eq!(accounts.len(), 2);
+    assert_eq!(accounts[0].user_id().unwrap(), "@example:localhost");
+    assert_eq!(accounts[1].user_id().unwrap(), "@bob:localhost");
+
+    // Actions are routed to the client of the account.
+    let bob = manager.account(user_id!("@bob:localhost")).unwrap();
+    assert_eq!(bob.user_id().unwrap(), "@bob:localhost");
+
+    let removed = manager.remove_account(user_id!("@example:localhost")).await.unwrap();
+    assert_eq!(removed.user_id().unwrap(), "@example:localhost");
+    assert!(manager.account(user_id!("@example:localhost")).is_none());
+    assert!(manager.remove_account(user_id!("@example:localhost")).await.is_none());
+    assert_eq!(manager.accounts().len(), 1);
+}
+
+#[async_test]
+async fn test_combined_rooms_and_notification_counts() {
+    let server = MatrixMockServer::new().await;
+    let alice = server.client_builder().build().await;
+    let bob = server
+        .client_builder()
+        .logged_in_with_token(
+            "bob_token".to_owned(),
+            user_id!("@bob:localhost").to_owned(),
+            owned_device_id!("BOB"),
+        )
+        .build()
+        .await;
+
+    let manager = AccountManager::new();
+    manager.add_account(alice.clone()).await.unwrap();
+    manager.add_account(bob.clone()).await.unwrap();
+
+    let rooms = manager.subscribe_to_rooms();
+    pin_mut!(rooms);
+    let counts = manager.subscribe_to_unread_notification_counts();
+    pin_mut!(counts);
+
+    // No account has any room yet.
+    assert!(assert_next_with_timeout!(rooms, 1000).is_empty());
+    let initial_counts = assert_next_with_timeout!(counts, 1000);
+    assert_eq!(initial_counts.notification_count, 0);
+
+    // Alice joins a room.
+    let shared_room_id = room_id!("!shared:localhost");
+    server
+        .sync_room(
+            &alice,
+            JoinedRoomBuilder::new(shared_room_id).set_unread_notifications_count(json!({
+                "highlight_count": 1,
+                "notification_count": 2,
+            })),
+        )
+        .await;
+
+    let combined = assert_next_with_timeout!(rooms, 1000);
+    assert_eq!(combined.len(), 1);
+    assert_eq!(combined[0].own_user_id(), "@example:localhost");
+
+    let combined_counts = assert_next_with_timeout!(counts, 1000);
+    assert_eq!(combined_counts.highlight_count, 1);
+    assert_eq!(combined_counts.notification_count, 2);
+
+    // Bob joins the same room, which appears once for each account.
+    server
+        .sync_room(
+            &bob,
+            JoinedRoomBuilder::new(shared_room_id).set_unread_notifications_count(json!({
+                "highlight_count": 0,
+                "notification_count": 3,
+            })),
+        )
+        .await;
+
+    let combined = assert_next_with_timeout!(rooms, 1000);
+    assert_eq!(combined.len(), 2);
+    assert_eq!(combined[0].own_user_id(), "@example:localhost");
+    assert_eq!(combined[1].own_user_id(), "@bob:localhost");
+    assert_eq!(combined[1].room_id(), shared_room_id);
+
+    let combined_counts = assert_next_with_timeout!(counts, 1000);
+    assert_eq!(combined_counts.highlight_count, 1);
+    assert_eq!(combined_counts.notification_count, 5);
+    assert_eq!(manager.unread_notification_counts(), combined_counts);
+
+    // The rooms of a removed account aren't combined anymore.
+    manager.remove_account(user_id!("@bob:localhost")).await;
+
+    let combined = assert_next_with_timeout!(rooms, 1000);
+    assert_eq!(combined.len(), 1);
+    assert_eq!(combined[0].own_user_id(), "@example:localhost");
+
+    let combined_counts = assert_next_with_timeout!(counts, 1000);
+    assert_eq!(combined_counts.notification_count, 2);
+}
+
+#[async_test]
+async fn test_sync_accounts() {
+    let server = MatrixMockServer::new().await;
+
+    // Answer the sliding sync requests of all the accounts.
+    Mock::given(SlidingSyncMatcher)
+        .respond_with(|request: &Request| {
+            let partial_request: PartialSlidingSyncRequest = request.body_json().unwrap();
+
+            ResponseTemplate::new(200)
+                .set_body_json(json!({
+                    "txn_id": partial_request.txn_id,
+                    "pos": "0",
+                }))
+                .set_delay(Duration::from_millis(50))
+        })
+        .mount(server.server())
+        .await;
+
+    let alice = server.client_builder().build().await;
+    let bob = server
+        .client_builder()
+        .logged_in_with_token(
+            "bob_token".to_owned(),
+            user_id!("@bob:localhost").to_owned(),
+            owned_device_id!("BOB"),
+        )
+        .build()
+        .await;
+
+    let manager = AccountManager::new();
+    manager.add_account(alice).await.unwrap();
+
+    let alice_sync_service = manager.sync_service(user_id!("@example:localhost")).unwrap();
+    assert_eq!(alice_sync_service.state().get(), State::Idle);
+
+    // Syncing starts the sync service of every account.
+    manager.start_sync().await;
+    assert_eq!(alice_sync_service.state().get(), State::Running);
+
+    // An account added while syncing is synced too.
+    manager.add_account(bob).await.unwrap();
+    let bob_sync_service = manager.sync_service(user_id!("@bob:localhost")).unwrap();
+    assert_eq!(bob_sync_service.state().get(), State::Running);
+
+    // Removing an account stops its sync.
+    manager.remove_account(user_id!("@bob:localhost")).await.unwrap();
+    assert_eq!(bob_sync_service.state().get(), State::Idle);
+    assert_eq!(alice_sync_service.state().get(), State::Running);
+
+    // Stopping the sync stops the sync service of every account.
+    manager.stop_sync().await;
+    assert_eq!(alice_sync_service.state().get(), State::Idle);
+}
@@ -22,6 +22,7 @@ use wiremock::{
     Mock, MockServer, ResponseTemplate,
 };
 
+mod account_manager;
 mod encryption_sync_service;
 mod notification_client;
 mod room_list_service;
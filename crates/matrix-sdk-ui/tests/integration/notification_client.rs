@@ -5,8 +5,11 @@ use std::{
 };
 
 use assert_matches::assert_matches;
+use assert_matches2::assert_let;
 use matrix_sdk::{
+    assert_recv_with_timeout,
     config::SyncSettings,
+    send_queue::{LocalEchoContent, RoomSendQueueUpdate},
     test_utils::{logged_in_client_with_server, mocks::MatrixMockServer},
 };
 use matrix_sdk_test::{
@@ -15,14 +18,17 @@ use matrix_sdk_test::{
 };
 use matrix_sdk_ui::{
     notification_client::{
-        NotificationClient, NotificationEvent, NotificationItemsRequest, NotificationProcessSetup,
-        NotificationStatus,
+        Error as NotificationClientError, NotificationClient, NotificationEvent,
+        NotificationItemsRequest, NotificationProcessSetup, NotificationStatus,
     },
     sync_service::SyncService,
 };
 use ruma::{
     event_id,
-    events::{room::member::MembershipState, AnyStateEvent, TimelineEventType},
+    events::{
+        room::{member::MembershipState, message::Relation},
+        AnyMessageLikeEventContent, AnyStateEvent, MessageLikeEventType, TimelineEventType,
+    },
     mxc_uri, room_id, user_id,
 };
 use serde_json::json;
@@ -945,3 +951,159 @@ async fn test_notification_client_context_filters_out_events_from_ignored_users(
 
     assert!(result.is_none());
 }
+
+#[async_test]
+async fn test_notification_client_send_quick_reply() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let event_id = event_id!("$example_event_id");
+    let sender = user_id!("@user:example.org");
+
+    let event =
+        EventFactory::new().room(room_id).sender(sender).text_msg("Heya").event_id(event_id);
+
+    let room = server.sync_joined_room(&client, room_id).await;
+    server.mock_room_state_encryption().plain().mount().await;
+    server.mock_room_event().match_event_id().ok(event.into_event()).mock_once().mount().await;
+    server.mock_room_send().ok(event_id!("$reply")).mock_once().mount().await;
+
+    let (_, mut updates) = room.send_queue().subscribe().await.unwrap();
+
+    let notification_client =
+        NotificationClient::new(client, NotificationProcessSetup::MultipleProcesses).await.unwrap();
+
+    notification_client.send_quick_reply(room_id, event_id, "On my way!".to_owned()).await.unwrap();
+
+    // The reply is queued first…
+    assert_let!(
+        RoomSendQueueUpdate::NewLocalEvent(local_echo) = assert_recv_with_timeout!(updates, 1000)
+    );
+    assert_let!(LocalEchoContent::Event { serialized_event, .. } = local_echo.content);
+    let content = serialized_event.deserialize().unwrap();
+    assert_let!(AnyMessageLikeEventContent::RoomMessage(content) = content);
+    assert_eq!(content.body(), "On my way!");
+    assert_let!(Some(Relation::Reply { in_reply_to }) = content.relates_to);
+    assert_eq!(in_reply_to.event_id, event_id);
+
+    // …then sent.
+    assert_let!(
+        RoomSendQueueUpdate::SentEvent { event_id: sent_event_id, .. } =
+            assert_recv_with_timeout!(updates, 1000)
+    );
+    assert_eq!(sent_event_id, event_id!("$reply"));
+}
+
+#[async_test]
+async fn test_notification_client_send_quick_reply_in_encrypted_room() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let event_id = event_id!("$example_event_id");
+    let sender = user_id!("@user:example.org");
+
+    let event =
+        EventFactory::new().room(room_id).sender(sender).text_msg("Heya").event_id(event_id);
+
+    let room = server.sync_joined_room(&client, room_id).await;
+    server.mock_room_state_encryption().encrypted().mount().await;
+    // Needed for the reply to be encrypted.
+    server.mock_get_members().ok(Vec::new()).mock_once().mount().await;
+    server.mock_room_event().match_event_id().ok(event.into_event()).mock_once().mount().await;
+    server
+        .mock_room_send()
+        .for_type(MessageLikeEventType::RoomEncrypted)
+        .body_matches_partial_json(json!({ "algorithm": "m.megolm.v1.aes-sha2" }))
+        .ok(event_id!("$reply"))
+        .mock_once()
+        .mount()
+        .await;
+
+    let (_, mut updates) = room.send_queue().subscribe().await.unwrap();
+
+    let notification_client =
+        NotificationClient::new(client, NotificationProcessSetup::MultipleProcesses).await.unwrap();
+
+    notification_client.send_quick_reply(room_id, event_id, "On my way!".to_owned()).await.unwrap();
+
+    // The local echo has the clear content of the reply…
+    assert_let!(
+        RoomSendQueueUpdate::NewLocalEvent(local_echo) = assert_recv_with_timeout!(updates, 1000)
+    );
+    assert_let!(LocalEchoContent::Event { serialized_event, .. } = local_echo.content);
+    assert_let!(
+        AnyMessageLikeEventContent::RoomMessage(content) = serialized_event.deserialize().unwrap()
+    );
+    assert_eq!(content.body(), "On my way!");
+
+    // …and it's sent encrypted.
+    assert_let!(
+        RoomSendQueueUpdate::SentEvent { event_id: sent_event_id, .. } =
+            assert_recv_with_timeout!(updates, 1000)
+    );
+    assert_eq!(sent_event_id, event_id!("$reply"));
+}
+
+#[async_test]
+async fn test_notification_client_send_quick_reply_to_unknown_event() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let event_id = event_id!("$example_event_id");
+
+    let room = server.sync_joined_room(&client, room_id).await;
+    server.mock_room_state_encryption().plain().mount().await;
+    // The replied-to event isn't mocked, so it can't be fetched.
+    server.mock_room_send().ok(event_id!("$reply")).mock_once().mount().await;
+
+    let (_, mut updates) = room.send_queue().subscribe().await.unwrap();
+
+    let notification_client =
+        NotificationClient::new(client, NotificationProcessSetup::MultipleProcesses).await.unwrap();
+
+    notification_client.send_quick_reply(room_id, event_id, "On my way!".to_owned()).await.unwrap();
+
+    // The reply is queued nonetheless, and refers to the event by its ID.
+    assert_let!(
+        RoomSendQueueUpdate::NewLocalEvent(local_echo) = assert_recv_with_timeout!(updates, 1000)
+    );
+    assert_let!(LocalEchoContent::Event { serialized_event, .. } = local_echo.content);
+    assert_let!(
+        AnyMessageLikeEventContent::RoomMessage(content) = serialized_event.deserialize().unwrap()
+    );
+    assert_eq!(content.body(), "On my way!");
+    assert_let!(Some(Relation::Reply { in_reply_to }) = content.relates_to);
+    assert_eq!(in_reply_to.event_id, event_id);
+
+    assert_let!(
+        RoomSendQueueUpdate::SentEvent { event_id: sent_event_id, .. } =
+            assert_recv_with_timeout!(updates, 1000)
+    );
+    assert_eq!(sent_event_id, event_id!("$reply"));
+}
+
+#[async_test]
+async fn test_notification_client_mark_as_read() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let event_id = event_id!("$example_event_id");
+
+    server.sync_joined_room(&client, room_id).await;
+    server.mock_send_read_markers().ok().mock_once().mount().await;
+
+    let notification_client =
+        NotificationClient::new(client, NotificationProcessSetup::MultipleProcesses).await.unwrap();
+
+    notification_client.mark_as_read(room_id, event_id).await.unwrap();
+
+    // An unknown room can't be marked as read.
+    assert_matches!(
+        notification_client.mark_as_read(room_id!("!unknown:example.org"), event_id).await,
+        Err(NotificationClientError::UnknownRoom)
+    );
+}
@@ -19,6 +19,7 @@ use assert_matches2::assert_let;
 use eyeball_im::VectorDiff;
 use futures_util::StreamExt;
 use matrix_sdk::{
+    assert_next_matches_with_timeout,
     linked_chunk::{ChunkIdentifier, LinkedChunkId, Position, Update},
     test_utils::mocks::MatrixMockServer,
 };
@@ -534,6 +535,73 @@ async fn test_duplicate_maintains_correct_order() {
     assert_eq!(content, "C");
 }
 
+#[async_test]
+async fn test_out_of_band_event_is_ordered_and_deduplicated() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    server.mock_room_state_encryption().plain().mount().await;
+
+    let timeline = room.timeline().await.unwrap();
+
+    let f = EventFactory::new().sender(user_id!("@a:b.c"));
+
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(
+                    f.text_msg("A").event_id(event_id!("$a")).server_ts(1000).into_raw_sync(),
+                )
+                .add_timeline_event(
+                    f.text_msg("C").event_id(event_id!("$c")).server_ts(3000).into_raw_sync(),
+                ),
+        )
+        .await;
+
+    let (items, mut stream) = timeline.subscribe().await;
+    assert_eq!(items.len(), 3);
+
+    // An event received out of band is inserted according to its timestamp.
+    let bridged = f.text_msg("B (bridged)").event_id(event_id!("$b")).server_ts(2000);
+    assert!(timeline.insert_out_of_band_event(bridged.into_raw_sync()).await.unwrap());
+
+    assert_next_matches_with_timeout!(stream, 250, diffs => {
+        assert_eq!(diffs.len(), 1);
+        assert_matches!(&diffs[0], VectorDiff::Insert { index: 2, value } => {
+            let body = value.as_event().unwrap().content().as_message().unwrap().body();
+            assert_eq!(body, "B (bridged)");
+        });
+    });
+
+    // When the homeserver sends the same event, it replaces the out-of-band one.
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).add_timeline_event(
+                f.text_msg("B").event_id(event_id!("$b")).server_ts(2000).into_raw_sync(),
+            ),
+        )
+        .await;
+
+    assert_next_matches_with_timeout!(stream, 250, _diffs => {});
+
+    let bodies = timeline
+        .items()
+        .await
+        .iter()
+        .filter_map(|item| Some(item.as_event()?.content().as_message()?.body().to_owned()))
+        .collect::<Vec<_>>();
+    assert_eq!(bodies, ["A", "C", "B"]);
+
+    // Inserting an event which is already known doesn't do anything.
+    let bridged = f.text_msg("B (bridged)").event_id(event_id!("$b")).server_ts(2000);
+    assert!(!timeline.insert_out_of_band_event(bridged.into_raw_sync()).await.unwrap());
+}
+
 #[async_test]
 async fn test_pin_event_is_sent_successfully() {
     let mut setup = PinningTestSetup::new().await;
@@ -1430,3 +1430,51 @@ async fn test_timeline_start_properly_inserted_when_created() {
     assert!(items[0].is_timeline_start());
     assert_pending!(stream2);
 }
+
+#[async_test]
+async fn test_history_unavailable_inserted_on_forbidden_pagination() {
+    let room_id = room_id!("!foo:bar.baz");
+
+    let mock_server = MatrixMockServer::new().await;
+    let client = mock_server.client_builder().build().await;
+
+    client.event_cache().subscribe().unwrap();
+
+    let room = mock_server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).set_timeline_prev_batch("previous-batch"),
+        )
+        .await;
+
+    let timeline = room.timeline().await.unwrap();
+    let (initial_items, mut stream) = timeline.subscribe().await;
+
+    assert!(initial_items.is_empty());
+    assert_pending!(stream);
+
+    // The server refuses to return older events, because they have been purged.
+    mock_server
+        .mock_room_messages()
+        .match_from("previous-batch")
+        .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+            "errcode": "M_FORBIDDEN",
+            "error": "History is unavailable",
+        })))
+        .mock_once()
+        .mount()
+        .await;
+
+    // The pagination doesn't fail, and reports that the start has been reached.
+    let hit_end_of_timeline = timeline.paginate_backwards(5).await.unwrap();
+    assert!(hit_end_of_timeline);
+
+    // A history unavailable item is inserted, instead of the timeline start.
+    assert_let_timeout!(Some(timeline_updates) = stream.next());
+    assert_eq!(timeline_updates.len(), 1);
+    assert_let!(VectorDiff::PushFront { value } = &timeline_updates[0]);
+    assert!(value.is_history_unavailable());
+    assert!(value.is_timeline_start().not());
+
+    assert_pending!(stream);
+}
@@ -23,7 +23,16 @@ use matrix_sdk::{
 };
 use matrix_sdk_test::{async_test, event_factory::EventFactory, JoinedRoomBuilder, ALICE, BOB};
 use matrix_sdk_ui::timeline::{RoomExt as _, TimelineBuilder, TimelineDetails, TimelineFocus};
-use ruma::{event_id, events::AnyTimelineEvent, owned_event_id, room_id, serde::Raw, user_id};
+use ruma::{
+    event_id,
+    events::{
+        receipt::{ReceiptThread, ReceiptType},
+        AnyTimelineEvent,
+    },
+    owned_event_id, room_id,
+    serde::Raw,
+    user_id,
+};
 use stream_assert::assert_pending;
 
 #[async_test]
@@ -279,6 +288,75 @@ async fn test_extract_bundled_thread_summary() {
     assert!(value.is_date_divider());
 }
 
+#[async_test]
+async fn test_thread_summary_participation_and_unread_state() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let own_user_id = client.user_id().unwrap().to_owned();
+
+    let room_id = room_id!("!a:b.c");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    let timeline = room.timeline().await.unwrap();
+
+    let (initial_items, mut stream) = timeline.subscribe().await;
+    assert!(initial_items.is_empty());
+
+    let f = EventFactory::new().room(room_id).sender(&ALICE);
+    let thread_event_id = event_id!("$thread_root");
+    let latest_event_id = event_id!("$latest_event");
+
+    // The server tells us we've participated in the thread, but the latest reply
+    // is from Alice, and we haven't read it yet.
+    let event = f
+        .text_msg("thready thread mcthreadface")
+        .with_bundled_thread_summary(
+            f.text_msg("the last one!").event_id(latest_event_id).into_raw(),
+            2,
+            true,
+        )
+        .event_id(thread_event_id);
+
+    server.sync_room(&client, JoinedRoomBuilder::new(room_id).add_timeline_event(event)).await;
+
+    assert_let_timeout!(Some(timeline_updates) = stream.next());
+    assert_eq!(timeline_updates.len(), 2);
+
+    assert_let!(VectorDiff::PushBack { value } = &timeline_updates[0]);
+    assert_let!(Some(summary) = value.as_event().unwrap().content().thread_summary());
+    assert_eq!(summary.num_replies, 2);
+    assert_eq!(summary.latest_event_id.as_deref(), Some(latest_event_id));
+    assert!(summary.current_user_participated);
+    assert!(summary.is_unread);
+
+    assert_let!(VectorDiff::PushFront { value } = &timeline_updates[1]);
+    assert!(value.is_date_divider());
+
+    // When we send a threaded read receipt on the latest reply, the thread is
+    // marked as read.
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).add_receipt(f.read_receipts().add(
+                latest_event_id,
+                &own_user_id,
+                ReceiptType::Read,
+                ReceiptThread::Thread(thread_event_id.to_owned()),
+            )),
+        )
+        .await;
+
+    assert_let_timeout!(Some(timeline_updates) = stream.next());
+    assert_eq!(timeline_updates.len(), 1);
+
+    assert_let!(VectorDiff::Set { index: 1, value } = &timeline_updates[0]);
+    assert_let!(Some(summary) = value.as_event().unwrap().content().thread_summary());
+    assert!(summary.current_user_participated);
+    assert!(summary.is_unread.not());
+
+    assert_pending!(stream);
+}
+
 #[async_test]
 async fn test_new_thread_reply_causes_thread_summary() {
     let server = MatrixMockServer::new().await;
@@ -37,6 +37,7 @@ use ruma::{
     },
     assign,
     events::{
+        direct::DirectEventContent,
         ignored_user_list::{IgnoredUser, IgnoredUserListEventContent},
         media_preview_config::{
             InviteAvatars, MediaPreviewConfigEventContent, MediaPreviews,
@@ -53,7 +54,7 @@ use ruma::{
     ClientSecret, MxcUri, OwnedMxcUri, OwnedRoomId, OwnedUserId, RoomId, SessionId, UInt, UserId,
 };
 use serde::Deserialize;
-use tracing::error;
+use tracing::{error, warn};
 
 use crate::{config::RequestConfig, Client, Error, Result};
 
@@ -71,6 +72,10 @@ impl Account {
     /// store.
     const VISITED_ROOMS_LIMIT: usize = 20;
 
+    /// The maximum number of times we upload the `m.direct` account data when
+    /// it keeps being modified concurrently.
+    const MAX_DIRECT_ROOMS_UPDATE_ATTEMPTS: usize = 3;
+
     pub(crate) fn new(client: Client) -> Self {
         Self { client }
     }
@@ -844,8 +849,6 @@ impl Account {
     /// * `user_ids` - The user IDs to be associated with this direct message
     ///   room.
     pub async fn mark_as_dm(&self, room_id: &RoomId, user_ids: &[OwnedUserId]) -> Result<()> {
-        use ruma::events::direct::DirectEventContent;
-
         // This function does a read/update/store of an account data event stored on the
         // homeserver. We first fetch the existing account data event, the event
         // contains a map which gets updated by this method, finally we upload the
@@ -888,6 +891,64 @@ impl Account {
         Ok(())
     }
 
+    /// Updates the `m.direct` account data with the given `update` function,
+    /// which returns whether it changed the content.
+    ///
+    /// The account data can be modified by another device between the moment
+    /// we fetch it and the moment we upload the updated content, in which case
+    /// our update would be lost. So the content is fetched again after the
+    /// upload, and the update is retried if it doesn't hold anymore.
+    pub(crate) async fn update_direct_rooms(
+        &self,
+        update: impl Fn(&mut DirectEventContent) -> bool,
+    ) -> Result<()> {
+        // Share the lock of `Self::mark_as_dm()`, so that updates from this client
+        // don't trample on each other.
+        let _guard = self.client.locks().mark_as_dm_lock.lock().await;
+
+        let mut num_attempts = 0;
+
+        loop {
+            // We are fetching the content from the server because we currently can't rely
+            // on `/sync` giving us the correct data in a timely manner.
+            let mut content = self.fetch_direct_rooms().await?;
+
+            if !update(&mut content) {
+                // Either there was nothing to do, or our previous upload stuck.
+                return Ok(());
+            }
+
+            if num_attempts == Self::MAX_DIRECT_ROOMS_UPDATE_ATTEMPTS {
+                error!("the m.direct account data keeps being modified concurrently, giving up");
+                return Err(Error::AccountDataConflict);
+            }
+
+            if num_attempts > 0 {
+                warn!("the m.direct account data has been modified concurrently, retrying");
+            }
+
+            num_attempts += 1;
+            self.set_account_data(content).await?;
+        }
+    }
+
+    /// Fetches the `m.direct` account data from the homeserver, or a default
+    /// one if there's none.
+    async fn fetch_direct_rooms(&self) -> Result<DirectEventContent> {
+        let Some(raw_content) = self.fetch_account_data(GlobalAccountDataEventType::Direct).await?
+        else {
+            // If there was no m.direct event server-side, create a default one.
+            return Ok(Default::default());
+        };
+
+        // Log the error and pass it upwards if we fail to deserialize the m.direct
+        // event.
+        Ok(raw_content.deserialize_as::<DirectEventContent>().map_err(|err| {
+            error!("unable to deserialize m.direct event content; aborting request: {err}");
+            err
+        })?)
+    }
+
     /// Adds the given user ID to the account's ignore list.
     pub async fn ignore_user(&self, user_id: &UserId) -> Result<()> {
         let own_user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
@@ -14,8 +14,12 @@
 
 //! Types and traits for attachments.
 
+#[cfg(feature = "image-proc")]
+use std::io::Cursor;
 use std::time::Duration;
 
+#[cfg(feature = "image-proc")]
+use image::{imageops::FilterType, DynamicImage, ImageDecoder as _, ImageFormat, ImageReader};
 use ruma::{
     assign,
     events::{
@@ -191,6 +195,8 @@ pub struct AttachmentConfig {
     pub(crate) formatted_caption: Option<FormattedBody>,
     pub(crate) mentions: Option<Mentions>,
     pub(crate) reply: Option<Reply>,
+    #[cfg(feature = "image-proc")]
+    pub(crate) processing: Option<AttachmentProcessing>,
 }
 
 impl AttachmentConfig {
@@ -275,6 +281,210 @@ impl AttachmentConfig {
         self.reply = reply;
         self
     }
+
+    /// Set the processing to apply to the attachment before uploading it.
+    ///
+    /// # Arguments
+    ///
+    /// * `processing` - The processing to apply. If the attachment isn't an
+    ///   image supported by the image backend, it is ignored.
+    #[cfg(feature = "image-proc")]
+    #[must_use]
+    pub fn processing(mut self, processing: AttachmentProcessing) -> Self {
+        self.processing = Some(processing);
+        self
+    }
+
+    /// Make sure the images of this attachment are downscaled to fit within
+    /// the given dimension, on top of the processing already set, if any.
+    #[cfg(feature = "image-proc")]
+    pub(crate) fn cap_image_dimension(&mut self, max_dimension: u32) {
+        let processing = self.processing.get_or_insert_with(Default::default);
+        processing.max_dimension =
+            Some(processing.max_dimension.map_or(max_dimension, |max| max.min(max_dimension)));
+    }
+
+    /// Apply the processing of this configuration, if any, to the given
+    /// attachment and to the thumbnail of this configuration.
+    ///
+    /// Returns the content type and data to upload, and updates the image
+    /// metadata and the thumbnail of this configuration to match the processed
+    /// images.
+    ///
+    /// Decoding and encoding images is CPU-intensive, so it happens on a
+    /// blocking thread, where available.
+    #[cfg(feature = "image-proc")]
+    pub(crate) async fn apply_processing(
+        &mut self,
+        content_type: mime::Mime,
+        data: Vec<u8>,
+    ) -> Result<(mime::Mime, Vec<u8>), image::ImageError> {
+        let Some(processing) = self.processing.take() else {
+            return Ok((content_type, data));
+        };
+
+        let thumbnail = self.thumbnail.take();
+
+        let process = move || {
+            let processed = processing.process(&content_type, &data)?;
+            let thumbnail =
+                thumbnail.map(|thumbnail| processing.process_thumbnail(thumbnail)).transpose()?;
+
+            Ok::<_, image::ImageError>((content_type, data, processed, thumbnail))
+        };
+
+        #[cfg(not(target_family = "wasm"))]
+        let result = tokio::task::spawn_blocking(process).await.expect("Task join error");
+        #[cfg(target_family = "wasm")]
+        let result = process();
+
+        let (content_type, data, processed, thumbnail) = result?;
+
+        self.thumbnail = thumbnail;
+
+        let Some(processed) = processed else {
+            return Ok((content_type, data));
+        };
+
+        if let Some(AttachmentInfo::Image(info)) = &mut self.info {
+            info.width = Some(processed.width.into());
+            info.height = Some(processed.height.into());
+            info.size = UInt::new(processed.data.len() as u64);
+        }
+
+        Ok((processed.content_type, processed.data))
+    }
+}
+
+/// Privacy-preserving processing to apply to an image attachment, before it's
+/// uploaded.
+///
+/// Only images in a format supported by the image backend are processed;
+/// other attachments, including videos and animated GIFs, are sent as is.
+///
+/// Re-encoding an image always drops its metadata, so downscaling or
+/// transcoding an image also strips its metadata. The file name isn't changed
+/// when transcoding, so it may have to be adjusted by the caller.
+#[cfg(feature = "image-proc")]
+#[derive(Debug, Clone, Default)]
+pub struct AttachmentProcessing {
+    /// Whether to strip the metadata of the image, like the EXIF data which
+    /// may contain the location where a picture was taken.
+    ///
+    /// The orientation found in the metadata is applied to the image first, so
+    /// it's still displayed the right way up.
+    pub strip_metadata: bool,
+
+    /// The maximum width and height of the image, in pixels.
+    ///
+    /// Larger images are downscaled to fit, preserving their aspect ratio.
+    pub max_dimension: Option<u32>,
+
+    /// The format to transcode the image to.
+    ///
+    /// If `None`, the image keeps its original format, when it's possible to
+    /// encode it; otherwise, it's transcoded to PNG.
+    pub output_format: Option<ImageOutputFormat>,
+}
+
+/// The format an image attachment can be transcoded to, see
+/// [`AttachmentProcessing::output_format`].
+#[cfg(feature = "image-proc")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageOutputFormat {
+    /// The JPEG format, which doesn't support transparency.
+    Jpeg,
+    /// The PNG format.
+    Png,
+}
+
+/// An image attachment, after it's been processed.
+#[cfg(feature = "image-proc")]
+#[derive(Debug)]
+struct ProcessedImage {
+    data: Vec<u8>,
+    content_type: mime::Mime,
+    width: u32,
+    height: u32,
+}
+
+#[cfg(feature = "image-proc")]
+impl AttachmentProcessing {
+    /// Whether this processing would leave any attachment untouched.
+    fn is_noop(&self) -> bool {
+        !self.strip_metadata && self.max_dimension.is_none() && self.output_format.is_none()
+    }
+
+    /// Process the given attachment.
+    ///
+    /// Returns `None` if the attachment doesn't need to be processed, or can't
+    /// be.
+    fn process(
+        &self,
+        content_type: &mime::Mime,
+        data: &[u8],
+    ) -> Result<Option<ProcessedImage>, image::ImageError> {
+        if self.is_noop() {
+            return Ok(None);
+        }
+
+        let Some(format) = ImageFormat::from_mime_type(content_type.essence_str()) else {
+            return Ok(None);
+        };
+
+        // Decoding an animated GIF only keeps its first frame.
+        if format == ImageFormat::Gif || !format.reading_enabled() {
+            return Ok(None);
+        }
+
+        let (output_format, output_content_type) = match self.output_format {
+            Some(ImageOutputFormat::Jpeg) => (ImageFormat::Jpeg, mime::IMAGE_JPEG),
+            Some(ImageOutputFormat::Png) => (ImageFormat::Png, mime::IMAGE_PNG),
+            None if format.writing_enabled() => (format, content_type.clone()),
+            None => (ImageFormat::Png, mime::IMAGE_PNG),
+        };
+
+        let mut decoder = ImageReader::with_format(Cursor::new(data), format).into_decoder()?;
+        let orientation = decoder.orientation()?;
+
+        let mut image = DynamicImage::from_decoder(decoder)?;
+        image.apply_orientation(orientation);
+
+        if let Some(max_dimension) = self.max_dimension {
+            if image.width() > max_dimension || image.height() > max_dimension {
+                image = image.resize(max_dimension, max_dimension, FilterType::Lanczos3);
+            }
+        }
+
+        if output_format == ImageFormat::Jpeg && image.color().has_alpha() {
+            image = DynamicImage::ImageRgb8(image.into_rgb8());
+        }
+
+        let mut output = Vec::new();
+        image.write_to(&mut Cursor::new(&mut output), output_format)?;
+
+        Ok(Some(ProcessedImage {
+            data: output,
+            content_type: output_content_type,
+            width: image.width(),
+            height: image.height(),
+        }))
+    }
+
+    /// Process the given thumbnail, like the attachment itself.
+    fn process_thumbnail(&self, thumbnail: Thumbnail) -> Result<Thumbnail, image::ImageError> {
+        let Some(processed) = self.process(&thumbnail.content_type, &thumbnail.data)? else {
+            return Ok(thumbnail);
+        };
+
+        Ok(Thumbnail {
+            size: UInt::new(processed.data.len() as u64).unwrap_or(thumbnail.size),
+            data: processed.data,
+            content_type: processed.content_type,
+            height: processed.height.into(),
+            width: processed.width.into(),
+        })
+    }
 }
 
 /// Configuration for sending a gallery.
@@ -390,3 +600,152 @@ pub struct GalleryItemInfo {
     /// The thumbnail.
     pub thumbnail: Option<Thumbnail>,
 }
+
+#[cfg(all(test, feature = "image-proc"))]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, ImageFormat, RgbaImage};
+    use matrix_sdk_test::async_test;
+    use ruma::{uint, UInt};
+
+    use super::{
+        AttachmentConfig, AttachmentInfo, AttachmentProcessing, BaseImageInfo, ImageOutputFormat,
+        Thumbnail,
+    };
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = DynamicImage::ImageRgba8(RgbaImage::new(width, height));
+        let mut data = Vec::new();
+        image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png).unwrap();
+        data
+    }
+
+    #[async_test]
+    async fn test_no_processing_keeps_attachment() {
+        let data = png(40, 20);
+
+        let mut config = AttachmentConfig::new();
+        let (content_type, processed) =
+            config.apply_processing(mime::IMAGE_PNG, data.clone()).await.unwrap();
+
+        assert_eq!(content_type, mime::IMAGE_PNG);
+        assert_eq!(processed, data);
+
+        // A processing which does nothing keeps the attachment too.
+        let mut config = AttachmentConfig::new().processing(AttachmentProcessing::default());
+        let (_, processed) = config.apply_processing(mime::IMAGE_PNG, data.clone()).await.unwrap();
+        assert_eq!(processed, data);
+    }
+
+    #[async_test]
+    async fn test_non_image_attachments_are_not_processed() {
+        let data = b"not an image".to_vec();
+
+        let mut config = AttachmentConfig::new().processing(AttachmentProcessing {
+            strip_metadata: true,
+            max_dimension: Some(10),
+            output_format: Some(ImageOutputFormat::Jpeg),
+        });
+        let (content_type, processed) =
+            config.apply_processing(mime::TEXT_PLAIN, data.clone()).await.unwrap();
+
+        assert_eq!(content_type, mime::TEXT_PLAIN);
+        assert_eq!(processed, data);
+    }
+
+    #[async_test]
+    async fn test_downscale_and_transcode() {
+        let mut config = AttachmentConfig::new()
+            .info(AttachmentInfo::Image(BaseImageInfo {
+                width: Some(uint!(40)),
+                height: Some(uint!(20)),
+                ..Default::default()
+            }))
+            .processing(AttachmentProcessing {
+                strip_metadata: true,
+                max_dimension: Some(10),
+                output_format: Some(ImageOutputFormat::Jpeg),
+            });
+
+        let (content_type, data) =
+            config.apply_processing(mime::IMAGE_PNG, png(40, 20)).await.unwrap();
+
+        assert_eq!(content_type, mime::IMAGE_JPEG);
+
+        let image = image::load_from_memory_with_format(&data, ImageFormat::Jpeg).unwrap();
+        assert_eq!(image.width(), 10);
+        assert_eq!(image.height(), 5);
+
+        // The image metadata have been updated to match the processed image.
+        let Some(AttachmentInfo::Image(info)) = config.info else {
+            panic!("the attachment info should still be an image info");
+        };
+        assert_eq!(info.width, Some(uint!(10)));
+        assert_eq!(info.height, Some(uint!(5)));
+        assert_eq!(info.size, Some((data.len() as u32).into()));
+    }
+
+    #[async_test]
+    async fn test_thumbnail_is_processed() {
+        let thumbnail = png(20, 20);
+        let mut config = AttachmentConfig::new()
+            .thumbnail(Some(Thumbnail {
+                size: UInt::new(thumbnail.len() as u64).unwrap(),
+                data: thumbnail,
+                content_type: mime::IMAGE_PNG,
+                height: uint!(20),
+                width: uint!(20),
+            }))
+            .processing(AttachmentProcessing {
+                strip_metadata: true,
+                max_dimension: Some(10),
+                output_format: Some(ImageOutputFormat::Jpeg),
+            });
+
+        config.apply_processing(mime::IMAGE_PNG, png(40, 20)).await.unwrap();
+
+        let thumbnail = config.thumbnail.expect("the thumbnail should still be set");
+        assert_eq!(thumbnail.content_type, mime::IMAGE_JPEG);
+        assert_eq!(thumbnail.width, uint!(10));
+        assert_eq!(thumbnail.height, uint!(10));
+        assert_eq!(thumbnail.size, UInt::new(thumbnail.data.len() as u64).unwrap());
+
+        let image =
+            image::load_from_memory_with_format(&thumbnail.data, ImageFormat::Jpeg).unwrap();
+        assert_eq!(image.width(), 10);
+    }
+
+    #[async_test]
+    async fn test_cap_image_dimension() {
+        // Capping the dimension of an attachment without processing downscales it.
+        let mut config = AttachmentConfig::new();
+        config.cap_image_dimension(20);
+
+        let (content_type, data) =
+            config.apply_processing(mime::IMAGE_PNG, png(40, 20)).await.unwrap();
+
+        assert_eq!(content_type, mime::IMAGE_PNG);
+        let image = image::load_from_memory_with_format(&data, ImageFormat::Png).unwrap();
+        assert_eq!(image.width(), 20);
+        assert_eq!(image.height(), 10);
+
+        // A smaller maximum dimension set by the caller is kept.
+        let mut config = AttachmentConfig::new()
+            .processing(AttachmentProcessing { max_dimension: Some(10), ..Default::default() });
+        config.cap_image_dimension(20);
+
+        let (_, data) = config.apply_processing(mime::IMAGE_PNG, png(40, 20)).await.unwrap();
+        let image = image::load_from_memory_with_format(&data, ImageFormat::Png).unwrap();
+        assert_eq!(image.width(), 10);
+
+        // A larger one is capped.
+        let mut config = AttachmentConfig::new()
+            .processing(AttachmentProcessing { max_dimension: Some(30), ..Default::default() });
+        config.cap_image_dimension(20);
+
+        let (_, data) = config.apply_processing(mime::IMAGE_PNG, png(40, 20)).await.unwrap();
+        let image = image::load_from_memory_with_format(&data, ImageFormat::Png).unwrap();
+        assert_eq!(image.width(), 20);
+    }
+}
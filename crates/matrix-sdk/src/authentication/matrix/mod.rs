@@ -24,7 +24,8 @@ use matrix_sdk_base::{store::RoomLoadSettings, SessionMeta};
 use ruma::{
     api::{
         client::{
-            account::register,
+            account::{get_username_availability, register, request_registration_token_via_email},
+            error::ErrorKind,
             session::{
                 get_login_types, login, logout, refresh_token, sso_login, sso_login_with_provider,
             },
@@ -33,6 +34,7 @@ use ruma::{
         OutgoingRequest, SendAccessToken,
     },
     serde::JsonObject,
+    ClientSecret, UInt,
 };
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -47,10 +49,11 @@ use crate::{
 };
 
 mod login_builder;
+mod register_builder;
 
-pub use self::login_builder::LoginBuilder;
 #[cfg(feature = "sso-login")]
 pub use self::login_builder::SsoLoginBuilder;
+pub use self::{login_builder::LoginBuilder, register_builder::RegisterBuilder};
 use super::SessionTokens;
 
 /// A high-level API to interact with the native Matrix authentication API.
@@ -608,17 +611,122 @@ impl MatrixAuth {
 
         let response = self.client.send(request).await?;
         if let Some(session) = MatrixSession::from_register_response(&response) {
-            let _ = self
-                .set_session(
-                    session,
-                    RoomLoadSettings::default(),
-                    #[cfg(feature = "e2e-encryption")]
-                    login_info,
-                )
-                .await;
+            self.set_session(
+                session,
+                RoomLoadSettings::default(),
+                #[cfg(feature = "e2e-encryption")]
+                login_info,
+            )
+            .await?;
         }
         Ok(response)
     }
+
+    /// Register a new account with the given username and password.
+    ///
+    /// Returns a [`RegisterBuilder`] that allows to configure the request and
+    /// to go through the stages of the user-interactive authentication
+    /// required by the homeserver, see its documentation for more details.
+    ///
+    /// If the registration succeeds, the client is logged in with the new
+    /// account.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use matrix_sdk::{
+    ///     ruma::{api::client::uiaa, assign},
+    ///     Client,
+    /// };
+    /// # use url::Url;
+    /// # let homeserver = Url::parse("http://example.com").unwrap();
+    /// # async {
+    /// let client = Client::new(homeserver).await?;
+    /// let auth = client.matrix_auth();
+    ///
+    /// if !auth.is_username_available("user").await? {
+    ///     println!("This username is already taken");
+    ///     return Ok(());
+    /// }
+    ///
+    /// // The first request tells us which stages need to be completed.
+    /// let error = auth
+    ///     .register_username("user", "password")
+    ///     .await
+    ///     .expect_err("the homeserver requires a registration token");
+    /// let session =
+    ///     error.as_uiaa_response().and_then(|info| info.session.clone());
+    ///
+    /// let token =
+    ///     assign!(uiaa::RegistrationToken::new("token".to_owned()), { session });
+    /// auth.register_username("user", "password")
+    ///     .auth(uiaa::AuthData::RegistrationToken(token))
+    ///     .initial_device_display_name("My app")
+    ///     .await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub fn register_username(&self, username: &str, password: &str) -> RegisterBuilder {
+        RegisterBuilder::new(self.clone(), username.to_owned(), password.to_owned())
+    }
+
+    /// Checks whether the given username is still available on the
+    /// homeserver, before registering an account with it.
+    ///
+    /// Returns:
+    /// - `Ok(true)` if the username is available.
+    /// - `Ok(false)` if it's already taken (the homeserver responded with
+    ///   `M_USER_IN_USE`).
+    /// - An `Err` otherwise, for example if the username is invalid or in a
+    ///   namespace reserved for an application service.
+    pub async fn is_username_available(&self, username: &str) -> HttpResult<bool> {
+        let request = get_username_availability::v3::Request::new(username.to_owned());
+
+        match self.client.send(request).await {
+            Ok(response) => Ok(response.available),
+            Err(error) => match error.client_api_error_kind() {
+                Some(ErrorKind::UserInUse) => Ok(false),
+                _ => Err(error),
+            },
+        }
+    }
+
+    /// Ask the homeserver to send a validation token to the given email
+    /// address, to complete the `m.login.email.identity` stage of the
+    /// registration.
+    ///
+    /// The `sid` of the response and the `client_secret` must then be used in
+    /// the [`uiaa::ThirdpartyIdCredentials`] of the
+    /// [`uiaa::AuthData::EmailIdentity`] sent with
+    /// [`RegisterBuilder::auth()`], once the user has validated their email
+    /// address.
+    ///
+    /// # Arguments
+    ///
+    /// * `email` - The email address to validate.
+    ///
+    /// * `client_secret` - A secret generated by the client, unique to this
+    ///   validation attempt.
+    ///
+    /// * `send_attempt` - The number of the attempt. The homeserver only sends
+    ///   a new email if it's greater than the one of the previous request with
+    ///   the same `client_secret`.
+    ///
+    /// [`uiaa::ThirdpartyIdCredentials`]: ruma::api::client::uiaa::ThirdpartyIdCredentials
+    /// [`uiaa::AuthData::EmailIdentity`]: ruma::api::client::uiaa::AuthData::EmailIdentity
+    pub async fn request_registration_email_token(
+        &self,
+        email: &str,
+        client_secret: &ClientSecret,
+        send_attempt: UInt,
+    ) -> HttpResult<request_registration_token_via_email::v3::Response> {
+        let request = request_registration_token_via_email::v3::Request::new(
+            client_secret.to_owned(),
+            email.to_owned(),
+            send_attempt,
+        );
+        self.client.send(request).await
+    }
+
     /// Log out the current user.
     pub async fn logout(&self) -> HttpResult<logout::v3::Response> {
         let request = logout::v3::Request::new();
@@ -0,0 +1,143 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#![cfg_attr(not(target_family = "wasm"), deny(clippy::future_not_send))]
+
+use std::future::IntoFuture;
+
+use matrix_sdk_common::boxed_into_future;
+use ruma::{
+    api::client::{account::register, uiaa::AuthData},
+    assign,
+};
+
+use super::MatrixAuth;
+use crate::Result;
+
+/// Builder type used to configure optional settings for registering a new
+/// account with a username and a password.
+///
+/// Created with [`MatrixAuth::register_username`]. Finalized with
+/// [`.send()`](Self::send).
+///
+/// Registration uses the [User-Interactive Authentication API][uiaa]: the
+/// first request is usually sent without [`auth`](Self::auth), and fails with
+/// the stages the homeserver wants the user to complete, that can be retrieved
+/// with [`Error::as_uiaa_response()`](crate::Error::as_uiaa_response). The
+/// request must then be sent again for each stage, with the matching
+/// [`AuthData`], until it succeeds:
+///
+/// - `m.login.registration_token` with [`AuthData::RegistrationToken`],
+/// - `m.login.email.identity` with [`AuthData::EmailIdentity`], once the email
+///   address has been validated with a token requested with
+///   [`MatrixAuth::request_registration_email_token()`],
+/// - `m.login.recaptcha` with [`AuthData::ReCaptcha`], with the response of the
+///   ReCAPTCHA challenge displayed by the application, using the public key
+///   found in the parameters of the stage,
+/// - `m.login.dummy` with [`AuthData::Dummy`].
+///
+/// [uiaa]: https://spec.matrix.org/v1.14/client-server-api/#user-interactive-authentication-api
+#[allow(missing_debug_implementations)]
+pub struct RegisterBuilder {
+    auth: MatrixAuth,
+    username: String,
+    password: String,
+    uiaa_auth: Option<AuthData>,
+    device_id: Option<String>,
+    initial_device_display_name: Option<String>,
+    request_refresh_token: bool,
+}
+
+impl RegisterBuilder {
+    pub(super) fn new(auth: MatrixAuth, username: String, password: String) -> Self {
+        Self {
+            auth,
+            username,
+            password,
+            uiaa_auth: None,
+            device_id: None,
+            initial_device_display_name: None,
+            request_refresh_token: false,
+        }
+    }
+
+    /// Set the authentication data for the current stage of the
+    /// user-interactive authentication.
+    ///
+    /// The `session` of the authentication data must be set to the one
+    /// returned by the homeserver in the previous response.
+    pub fn auth(mut self, value: AuthData) -> Self {
+        self.uiaa_auth = Some(value);
+        self
+    }
+
+    /// Set the device ID.
+    ///
+    /// The device ID is a unique ID that will be associated with the session
+    /// created after the registration. If not set, the homeserver will create
+    /// one.
+    pub fn device_id(mut self, value: &str) -> Self {
+        self.device_id = Some(value.to_owned());
+        self
+    }
+
+    /// Set the initial device display name.
+    ///
+    /// The device display name is the public name that will be associated with
+    /// the device ID. It can be changed later.
+    pub fn initial_device_display_name(mut self, value: &str) -> Self {
+        self.initial_device_display_name = Some(value.to_owned());
+        self
+    }
+
+    /// Advertise support for [refreshing access tokens].
+    ///
+    /// See [`LoginBuilder::request_refresh_token()`] for more details.
+    ///
+    /// [refreshing access tokens]: https://spec.matrix.org/v1.3/client-server-api/#refreshing-access-tokens
+    /// [`LoginBuilder::request_refresh_token()`]: super::LoginBuilder::request_refresh_token
+    pub fn request_refresh_token(mut self) -> Self {
+        self.request_refresh_token = true;
+        self
+    }
+
+    /// Send the registration request.
+    ///
+    /// If the registration succeeds, the client is logged in with the new
+    /// account and its device is set up, like after a call to
+    /// [`MatrixAuth::login_username()`].
+    ///
+    /// Instead of calling this function and `.await`ing its return value, you
+    /// can also `.await` the `RegisterBuilder` directly.
+    pub async fn send(self) -> Result<register::v3::Response> {
+        let request = assign!(register::v3::Request::new(), {
+            username: Some(self.username),
+            password: Some(self.password),
+            auth: self.uiaa_auth,
+            device_id: self.device_id.map(Into::into),
+            initial_device_display_name: self.initial_device_display_name,
+            refresh_token: self.request_refresh_token,
+        });
+
+        self.auth.register(request).await
+    }
+}
+
+impl IntoFuture for RegisterBuilder {
+    type Output = Result<register::v3::Response>;
+    boxed_into_future!();
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.send())
+    }
+}
@@ -437,6 +437,9 @@ impl OAuth {
     ///             LoginProgress::WaitingForToken { user_code } => {
     ///                 println!("Please use your other device to confirm the log in {user_code}")
     ///             },
+    ///             LoginProgress::SyncingSecrets => {
+    ///                 println!("Waiting for the secrets from the other device")
+    ///             },
     ///             LoginProgress::Done => break,
     ///         }
     ///     }
@@ -75,6 +75,9 @@ pub enum LoginProgress {
         /// enter this code.
         user_code: String,
     },
+    /// We're logged in, and we're waiting for the other device to send us the
+    /// secrets bundle, to set up end-to-end encryption.
+    SyncingSecrets,
     /// The login process has completed.
     Done,
 }
@@ -222,6 +225,8 @@ impl<'a> IntoFuture for LoginWithQrCode<'a> {
             // Let's wait for the secrets bundle to be sent to us, otherwise we won't be a
             // fully E2EE enabled device.
             trace!("Waiting for the secrets bundle.");
+            self.state.set(LoginProgress::SyncingSecrets);
+
             let bundle = match channel.receive_json().await? {
                 QrAuthMessage::LoginSecrets(bundle) => bundle,
                 QrAuthMessage::LoginFailure { reason, homeserver } => {
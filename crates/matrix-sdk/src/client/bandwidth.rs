@@ -0,0 +1,95 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// The set of behaviors that depend on whether the low-bandwidth mode of a
+/// [`Client`] is enabled.
+///
+/// All the behaviors are derived from the mode when they're used, so toggling
+/// the mode with [`Client::set_low_bandwidth_mode`] switches all of them at
+/// once.
+///
+/// The SDK applies the sync-related behaviors itself, and downscales the
+/// images sent through the send queue when the `image-proc` feature is
+/// enabled. It doesn't fetch media on its own, so whether to prefetch media or
+/// to download only their thumbnails is meant to be honored by the callers of
+/// the media APIs.
+///
+/// [`Client`]: crate::Client
+/// [`Client::set_low_bandwidth_mode`]: crate::Client::set_low_bandwidth_mode
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BandwidthProfile {
+    /// The maximum number of timeline events to request for a room, when
+    /// syncing.
+    ///
+    /// `None` means the limits configured by the callers are used as is.
+    pub max_sync_timeline_limit: Option<u32>,
+
+    /// The factor applied to the network timeout of the sync requests, on top
+    /// of their long-polling timeout.
+    pub sync_network_timeout_factor: u32,
+
+    /// Whether media, like avatars or images in the timeline, should be
+    /// fetched before being displayed.
+    pub prefetch_media: bool,
+
+    /// Whether only the thumbnails of the media should be downloaded
+    /// automatically; the full media is then only downloaded when the user
+    /// explicitly asks for it.
+    pub thumbnails_only: bool,
+
+    /// The maximum width or height, in pixels, of the images sent through the
+    /// send queue; larger images are downscaled before being uploaded.
+    ///
+    /// `None` means images are sent as is.
+    pub max_image_dimension: Option<u32>,
+}
+
+impl BandwidthProfile {
+    /// The profile used when the low-bandwidth mode is disabled.
+    pub fn normal() -> Self {
+        Self {
+            max_sync_timeline_limit: None,
+            sync_network_timeout_factor: 1,
+            prefetch_media: true,
+            thumbnails_only: false,
+            max_image_dimension: None,
+        }
+    }
+
+    /// The profile used when the low-bandwidth mode is enabled.
+    pub fn low_bandwidth() -> Self {
+        Self {
+            max_sync_timeline_limit: Some(5),
+            sync_network_timeout_factor: 3,
+            prefetch_media: false,
+            thumbnails_only: true,
+            max_image_dimension: Some(1280),
+        }
+    }
+
+    /// Get the profile matching the given state of the low-bandwidth mode.
+    pub fn for_low_bandwidth_mode(enabled: bool) -> Self {
+        if enabled {
+            Self::low_bandwidth()
+        } else {
+            Self::normal()
+        }
+    }
+
+    /// Cap the given timeline limit of a sync request according to this
+    /// profile.
+    pub fn cap_sync_timeline_limit(&self, limit: u32) -> u32 {
+        self.max_sync_timeline_limit.map_or(limit, |max| limit.min(max))
+    }
+}
@@ -22,7 +22,7 @@ use std::{fmt, sync::Arc};
 use homeserver_config::*;
 #[cfg(feature = "e2e-encryption")]
 use matrix_sdk_base::crypto::DecryptionSettings;
-use matrix_sdk_base::{store::StoreConfig, BaseClient};
+use matrix_sdk_base::{event_cache::store::media::MediaBlobStore, store::StoreConfig, BaseClient};
 #[cfg(feature = "sqlite")]
 use matrix_sdk_sqlite::SqliteStoreConfig;
 use ruma::{
@@ -113,6 +113,7 @@ pub struct ClientBuilder {
     #[cfg(feature = "e2e-encryption")]
     enable_share_history_on_invite: bool,
     cross_process_store_locks_holder_name: String,
+    media_blob_store: Option<Arc<dyn MediaBlobStore>>,
 }
 
 impl ClientBuilder {
@@ -143,6 +144,7 @@ impl ClientBuilder {
             enable_share_history_on_invite: false,
             cross_process_store_locks_holder_name:
                 Self::DEFAULT_CROSS_PROCESS_STORE_LOCKS_HOLDER_NAME.to_owned(),
+            media_blob_store: None,
         }
     }
 
@@ -297,6 +299,20 @@ impl ClientBuilder {
         self
     }
 
+    /// Store the content of the downloaded media in the given
+    /// [`MediaBlobStore`], e.g. in a location managed by the operating system,
+    /// while the event cache store only keeps their metadata.
+    ///
+    /// This applies to the SQLite event cache store set up by this builder. To
+    /// use it with a custom [`StoreConfig`], wrap its event cache store in a
+    /// [`MediaBlobEventCacheStore`] instead.
+    ///
+    /// [`MediaBlobEventCacheStore`]: matrix_sdk_base::event_cache::store::media::MediaBlobEventCacheStore
+    pub fn media_blob_store(mut self, store: Arc<dyn MediaBlobStore>) -> Self {
+        self.media_blob_store = Some(store);
+        self
+    }
+
     /// Update the client's homeserver URL with the discovery information
     /// present in the login response, if any.
     pub fn respect_login_well_known(mut self, value: bool) -> Self {
@@ -513,8 +529,12 @@ impl ClientBuilder {
         } else {
             #[allow(unused_mut)]
             let mut client = BaseClient::new(
-                build_store_config(self.store_config, &self.cross_process_store_locks_holder_name)
-                    .await?,
+                build_store_config(
+                    self.store_config,
+                    &self.cross_process_store_locks_holder_name,
+                    self.media_blob_store,
+                )
+                .await?,
             );
 
             #[cfg(feature = "e2e-encryption")]
@@ -612,6 +632,7 @@ pub fn sanitize_server_name(s: &str) -> crate::Result<OwnedServerName, IdParseEr
 async fn build_store_config(
     builder_config: BuilderStoreConfig,
     cross_process_store_locks_holder_name: &str,
+    media_blob_store: Option<Arc<dyn MediaBlobStore>>,
 ) -> Result<StoreConfig, ClientBuildError> {
     #[allow(clippy::infallible_destructuring_match)]
     let store_config = match builder_config {
@@ -620,16 +641,28 @@ async fn build_store_config(
             let store_config = StoreConfig::new(cross_process_store_locks_holder_name.to_owned())
                 .state_store(
                     matrix_sdk_sqlite::SqliteStateStore::open_with_config(config.clone()).await?,
-                )
-                .event_cache_store({
-                    let mut config = config.clone();
+                );
 
-                    if let Some(cache_path) = cache_path {
-                        config = config.path(cache_path);
-                    }
+            let event_cache_store = {
+                let mut config = config.clone();
 
-                    matrix_sdk_sqlite::SqliteEventCacheStore::open_with_config(config).await?
-                });
+                if let Some(cache_path) = cache_path {
+                    config = config.path(cache_path);
+                }
+
+                matrix_sdk_sqlite::SqliteEventCacheStore::open_with_config(config).await?
+            };
+
+            let store_config = if let Some(media_blob_store) = media_blob_store {
+                store_config.event_cache_store(
+                    matrix_sdk_base::event_cache::store::media::MediaBlobEventCacheStore::new(
+                        event_cache_store,
+                        media_blob_store,
+                    ),
+                )
+            } else {
+                store_config.event_cache_store(event_cache_store)
+            };
 
             #[cfg(feature = "e2e-encryption")]
             let store_config = store_config.crypto_store(
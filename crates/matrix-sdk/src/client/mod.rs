@@ -20,6 +20,7 @@ use std::{
     future::{ready, Future},
     pin::Pin,
     sync::{Arc, Mutex as StdMutex, RwLock as StdRwLock, Weak},
+    time::Duration,
 };
 
 use caches::ClientCaches;
@@ -36,7 +37,7 @@ use matrix_sdk_base::{
     BaseClient, RoomInfoNotableUpdate, RoomState, RoomStateFilter, SendOutsideWasm, SessionMeta,
     StateStoreDataKey, StateStoreDataValue, SyncOutsideWasm,
 };
-use matrix_sdk_common::ttl_cache::TtlCache;
+use matrix_sdk_common::{timeout::ElapsedError, ttl_cache::TtlCache};
 #[cfg(feature = "e2e-encryption")]
 use ruma::events::{room::encryption::RoomEncryptionEventContent, InitialStateEvent};
 use ruma::{
@@ -103,15 +104,25 @@ use crate::{
 };
 #[cfg(feature = "e2e-encryption")]
 use crate::{
-    encryption::{Encryption, EncryptionData, EncryptionSettings, VerificationState},
+    encryption::{
+        DetailedVerificationState, Encryption, EncryptionData, EncryptionSettings,
+        VerificationState,
+    },
     store_locks::CrossProcessStoreLock,
 };
 
+mod bandwidth;
 mod builder;
 pub(crate) mod caches;
 pub(crate) mod futures;
+mod shutdown;
+mod well_known;
 
-pub use self::builder::{sanitize_server_name, ClientBuildError, ClientBuilder};
+pub(crate) use self::shutdown::ShutdownTracker;
+pub use self::{
+    bandwidth::BandwidthProfile,
+    builder::{sanitize_server_name, ClientBuildError, ClientBuilder},
+};
 
 #[cfg(not(target_family = "wasm"))]
 type NotificationHandlerFut = Pin<Box<dyn Future<Output = ()> + Send>>;
@@ -329,6 +340,11 @@ pub(crate) struct ClientInner {
     #[cfg(feature = "e2e-encryption")]
     pub(crate) verification_state: SharedObservable<VerificationState>,
 
+    /// The verification state of our own device, with the reason why it's
+    /// unverified.
+    #[cfg(feature = "e2e-encryption")]
+    pub(crate) detailed_verification_state: SharedObservable<DetailedVerificationState>,
+
     /// Whether to enable the experimental support for sending and receiving
     /// encrypted room history on invite, per [MSC4268].
     ///
@@ -344,6 +360,14 @@ pub(crate) struct ClientInner {
     /// The `max_upload_size` value of the homeserver, it contains the max
     /// request size you can send.
     pub(crate) server_max_upload_size: Mutex<OnceCell<UInt>>,
+
+    /// Whether the low-bandwidth mode is enabled.
+    ///
+    /// See [`BandwidthProfile`] for the behaviors it controls.
+    low_bandwidth_mode: SharedObservable<bool>,
+
+    /// The background work that's stopped by [`Client::shutdown`].
+    pub(crate) shutdown: ShutdownTracker,
 }
 
 impl ClientInner {
@@ -399,8 +423,12 @@ impl ClientInner {
             #[cfg(feature = "e2e-encryption")]
             verification_state: SharedObservable::new(VerificationState::Unknown),
             #[cfg(feature = "e2e-encryption")]
+            detailed_verification_state: SharedObservable::new(DetailedVerificationState::Unknown),
+            #[cfg(feature = "e2e-encryption")]
             enable_share_history_on_invite,
             server_max_upload_size: Mutex::new(OnceCell::new()),
+            low_bandwidth_mode: SharedObservable::new(false),
+            shutdown: ShutdownTracker::default(),
         };
 
         #[allow(clippy::let_and_return)]
@@ -526,6 +554,30 @@ impl Client {
         self.inner.http_client.request_config
     }
 
+    /// Enable or disable the low-bandwidth mode.
+    ///
+    /// All the behaviors described by [`BandwidthProfile`] switch at once, the
+    /// next time they're used.
+    pub fn set_low_bandwidth_mode(&self, enabled: bool) {
+        self.inner.low_bandwidth_mode.set_if_not_eq(enabled);
+    }
+
+    /// Whether the low-bandwidth mode is enabled.
+    pub fn is_low_bandwidth_mode_enabled(&self) -> bool {
+        self.inner.low_bandwidth_mode.get()
+    }
+
+    /// Subscribe to the changes of the low-bandwidth mode.
+    pub fn subscribe_to_low_bandwidth_mode(&self) -> Subscriber<bool> {
+        self.inner.low_bandwidth_mode.subscribe()
+    }
+
+    /// Get the [`BandwidthProfile`] matching the current state of the
+    /// low-bandwidth mode.
+    pub fn bandwidth_profile(&self) -> BandwidthProfile {
+        BandwidthProfile::for_low_bandwidth_mode(self.is_low_bandwidth_mode_enabled())
+    }
+
     /// Check whether the client has been activated.
     ///
     /// A client is considered active when:
@@ -1182,6 +1234,20 @@ impl Client {
             .collect()
     }
 
+    /// Returns the joined and invited rooms that are DMs with the given user,
+    /// according to [`Room::is_direct_with`].
+    pub async fn direct_rooms_with(&self, user_id: &UserId) -> Result<Vec<Room>> {
+        let mut rooms = Vec::new();
+
+        for room in self.rooms_filtered(RoomStateFilter::JOINED | RoomStateFilter::INVITED) {
+            if room.is_direct_with(user_id).await? {
+                rooms.push(room);
+            }
+        }
+
+        Ok(rooms)
+    }
+
     /// Get a room with the given room id.
     ///
     /// # Arguments
@@ -1191,6 +1257,36 @@ impl Client {
         self.base_client().get_room(room_id).map(|room| Room::new(self.clone(), room))
     }
 
+    /// Archive the room with the given room id.
+    ///
+    /// The room is dropped from the rooms the client knows about, so it won't
+    /// be returned by [`Client::get_room`] or [`Client::rooms`] anymore, but
+    /// its state is kept in the store. It can be restored on demand with
+    /// [`Client::restore_archived_room`]. The room stays archived across
+    /// restarts.
+    ///
+    /// This is meant for rooms which aren't relevant anymore, like rooms the
+    /// user left a long time ago, to keep them out of the hot path. It
+    /// shouldn't be used for rooms which still receive updates from the sync,
+    /// since an archived room is created anew when it's updated.
+    ///
+    /// Returns `true` if the room was known and has been archived.
+    pub async fn archive_room(&self, room_id: &RoomId) -> Result<bool> {
+        Ok(self.base_client().archive_room(room_id).await?)
+    }
+
+    /// Restore a room which has been archived with [`Client::archive_room`].
+    ///
+    /// If the room isn't archived, it's returned as is. Returns `None` if the
+    /// room is unknown.
+    pub async fn restore_archived_room(&self, room_id: &RoomId) -> Result<Option<Room>> {
+        Ok(self
+            .base_client()
+            .restore_archived_room(room_id)
+            .await?
+            .map(|room| Room::new(self.clone(), room)))
+    }
+
     /// Gets the preview of a room, whether the current user has joined it or
     /// not.
     pub async fn get_room_preview(
@@ -1853,6 +1949,41 @@ impl Client {
         }
     }
 
+    /// Fetches client well-known from network, keeping its raw JSON content
+    /// alongside the typed one so that custom fields aren't lost; no caching.
+    async fn fetch_client_well_known_with_raw(&self) -> Option<WellKnownResponse> {
+        let server_url_string = self
+            .server()
+            .unwrap_or(
+                // Sometimes people configure their well-known directly on the homeserver so use
+                // this as a fallback when the server name is unknown.
+                &self.homeserver(),
+            )
+            .to_string();
+
+        let well_known = self
+            .inner
+            .http_client
+            .send(
+                well_known::Request,
+                Some(RequestConfig::short_retry()),
+                server_url_string,
+                None,
+                &[MatrixVersion::V1_0],
+                Default::default(),
+            )
+            .await;
+
+        match well_known {
+            Ok(well_known::Response(well_known)) => Some(well_known),
+            Err(http_error) => {
+                // It is perfectly valid to not have a well-known file.
+                warn!("Failed to fetch client well-known: {http_error}");
+                None
+            }
+        }
+    }
+
     /// Load server info from storage, or fetch them from network and cache
     /// them.
     async fn load_or_fetch_server_info(&self) -> HttpResult<ServerInfo> {
@@ -1874,11 +2005,11 @@ impl Client {
         }
 
         let server_versions = self.fetch_server_versions(None).await?;
-        let well_known = self.fetch_client_well_known().await;
+        let well_known = self.fetch_client_well_known_with_raw().await;
         let server_info = ServerInfo::new(
             server_versions.versions.clone(),
             server_versions.unstable_features.clone(),
-            well_known.map(Into::into),
+            well_known,
         );
 
         // Attempt to cache the result in storage.
@@ -2005,7 +2136,43 @@ impl Client {
         Ok(well_known.map(|well_known| well_known.rtc_foci).unwrap_or_default())
     }
 
-    /// Empty the server version and unstable features cache.
+    /// Get the content of the homeserver's client well-known file, by fetching
+    /// it from the server or the cache.
+    ///
+    /// The returned [`WellKnownResponse`] contains both the fields known to the
+    /// SDK and the raw JSON content of the file, so that custom fields, like
+    /// `io.element.e2ee`, can be read with [`WellKnownResponse::field`].
+    ///
+    /// Returns `None` if the server doesn't have a client well-known file.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let mut client = Client::new(homeserver).await?;
+    /// if let Some(well_known) = client.client_well_known().await? {
+    ///     let e2ee_config = well_known.field("io.element.e2ee");
+    ///     println!("E2EE configuration: {e2ee_config:?}");
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn client_well_known(&self) -> HttpResult<Option<WellKnownResponse>> {
+        self.get_or_load_and_cache_server_info(|server_info| server_info.well_known.clone()).await
+    }
+
+    /// Reset the cached server info and fetch the client well-known file from
+    /// the server again.
+    ///
+    /// See [`Client::client_well_known`] for details about the returned value.
+    pub async fn refresh_client_well_known(&self) -> Result<Option<WellKnownResponse>> {
+        self.reset_server_info().await?;
+        Ok(self.client_well_known().await?)
+    }
+
+    /// Empty the server version, unstable features and well-known cache.
     ///
     /// Since the SDK caches server info (versions, unstable features,
     /// well-known etc), it's possible to have a stale entry in the cache. This
@@ -2015,6 +2182,7 @@ impl Client {
         let mut guard = self.inner.caches.server_info.write().await;
         guard.server_versions = CachedValue::NotSet;
         guard.unstable_features = CachedValue::NotSet;
+        guard.well_known = CachedValue::NotSet;
 
         // Empty the store cache.
         Ok(self.state_store().remove_kv_data(StateStoreDataKey::ServerInfo).await?)
@@ -2234,6 +2402,10 @@ impl Client {
         &self,
         sync_settings: crate::config::SyncSettings,
     ) -> Result<SyncResponse> {
+        if self.inner.shutdown.is_shut_down() {
+            return Err(Error::ClientShutDown);
+        }
+
         // The sync might not return for quite a while due to the timeout.
         // We'll see if there's anything crypto related to send out before we
         // sync, i.e. if we closed our client after a sync but before the
@@ -2253,11 +2425,19 @@ impl Client {
             timeout: sync_settings.timeout,
         });
         let mut request_config = self.request_config();
+        request_config.timeout *= self.bandwidth_profile().sync_network_timeout_factor;
         if let Some(timeout) = sync_settings.timeout {
             request_config.timeout += timeout;
         }
 
-        let response = self.send(request).with_request_config(request_config).await?;
+        // Only the request can be given up on when shutting down: the response must be
+        // processed entirely once it's been received.
+        let response = self
+            .inner
+            .shutdown
+            .run_until_shut_down(self.send(request).with_request_config(request_config))
+            .await
+            .ok_or(Error::ClientShutDown)??;
         let next_batch = response.next_batch.clone();
         let response = self.process_sync(response).await?;
 
@@ -2484,10 +2664,20 @@ impl Client {
             sync_settings.token = self.sync_token().await;
         }
 
+        let Some(_shutdown_guard) = self.inner.shutdown.enter() else {
+            trace!("The client has been shut down, not starting the sync loop");
+            return Ok(());
+        };
+
         loop {
             trace!("Syncing");
             let result = self.sync_loop_helper(&mut sync_settings).await;
 
+            if self.inner.shutdown.is_shut_down() {
+                trace!("The client is shutting down, stopping the sync loop");
+                break;
+            }
+
             trace!("Running callback");
             if callback(result).await? == LoopCtrl::Break {
                 trace!("Callback told us to stop");
@@ -2495,7 +2685,16 @@ impl Client {
             }
             trace!("Done running callback");
 
-            Client::delay_sync(&mut last_sync_time).await
+            if self
+                .inner
+                .shutdown
+                .run_until_shut_down(Client::delay_sync(&mut last_sync_time))
+                .await
+                .is_none()
+            {
+                trace!("The client is shutting down, stopping the sync loop");
+                break;
+            }
         }
 
         Ok(())
@@ -2555,16 +2754,83 @@ impl Client {
         }
 
         let parent_span = Span::current();
+        let shutdown_guard = self.inner.shutdown.enter();
 
         async_stream::stream! {
+            let Some(_shutdown_guard) = shutdown_guard else {
+                trace!("The client has been shut down, not starting the sync stream");
+                return;
+            };
+
             loop {
-                yield self.sync_loop_helper(&mut sync_settings).instrument(parent_span.clone()).await;
+                let result = self.sync_loop_helper(&mut sync_settings).instrument(parent_span.clone()).await;
+
+                if self.inner.shutdown.is_shut_down() {
+                    trace!("The client is shutting down, stopping the sync stream");
+                    break;
+                }
 
-                Client::delay_sync(&mut last_sync_time).await
+                yield result;
+
+                if self.inner.shutdown.run_until_shut_down(Client::delay_sync(&mut last_sync_time)).await.is_none() {
+                    trace!("The client is shutting down, stopping the sync stream");
+                    break;
+                }
             }
         }
     }
 
+    /// Shut down the background work of this client, so that it can be
+    /// dropped or the process exited without losing data.
+    ///
+    /// This will, in order:
+    ///
+    /// 1. cancel the loops started by [`Client::sync`] and its variants, the
+    ///    [`Client::sync_stream`]s and the sliding sync loops (and thus the
+    ///    `SyncService` running on top of them), giving up on their in-flight
+    ///    requests,
+    /// 2. cancel the sending tasks of the [`SendQueue`]; the requests being
+    ///    sent are kept in the queue and will be sent again by the next client
+    ///    using the same store,
+    /// 3. cancel the background tasks of the [`EventCache`], and wait for the
+    ///    pending updates of its rooms to be written to the store,
+    /// 4. wait for the cross-process locks of the crypto store and of the event
+    ///    cache store to be released.
+    ///
+    /// Once this returns successfully, none of this work is running anymore.
+    ///
+    /// This is final: sync loops and send queues can't be started anymore on
+    /// this client afterwards, and a new client must be built instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The maximum duration to wait for; an error is returned if
+    ///   it's elapsed before everything has been shut down.
+    ///
+    /// [`SendQueue`]: crate::send_queue::SendQueue
+    #[instrument(skip(self))]
+    pub async fn shutdown(&self, timeout: Duration) -> Result<(), ElapsedError> {
+        matrix_sdk_common::timeout::timeout(
+            async {
+                self.inner.shutdown.shut_down().await;
+                trace!("The sync loops and background tasks have stopped");
+
+                self.event_cache().flush().await;
+                trace!("The event cache has been flushed");
+
+                #[cfg(feature = "e2e-encryption")]
+                if let Some(lock) = self.locks().cross_process_crypto_store_lock.get() {
+                    lock.wait_for_release().await;
+                }
+
+                self.event_cache_store().wait_for_release().await;
+                trace!("The cross-process store locks have been released");
+            },
+            timeout,
+        )
+        .await
+    }
+
     /// Get the current, if any, sync token of the client.
     /// This will be None if the client didn't sync at least once.
     pub(crate) async fn sync_token(&self) -> Option<String> {
@@ -2835,6 +3101,7 @@ pub(crate) mod tests {
         events::{
             ignored_user_list::IgnoredUserListEventContent,
             media_preview_config::{InviteAvatars, MediaPreviewConfigEventContent, MediaPreviews},
+            room::message::RoomMessageEventContent,
         },
         owned_room_id, room_alias_id, room_id, RoomId, ServerName, UserId,
     };
@@ -3391,6 +3658,64 @@ pub(crate) mod tests {
         assert_eq!(client.rtc_foci().await.unwrap(), rtc_foci);
     }
 
+    #[async_test]
+    async fn test_client_well_known_custom_fields() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/_matrix/client/versions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::VERSIONS))
+            .mount(&server)
+            .await;
+
+        let well_known_mock = Mock::given(method("GET"))
+            .and(path("/.well-known/matrix/client"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "m.homeserver": { "base_url": server.uri() },
+                "io.element.e2ee": { "default": false },
+            })))
+            .named("first well known mock")
+            .expect(1)
+            .mount_as_scoped(&server)
+            .await;
+
+        let client = Client::builder()
+            .homeserver_url(server.uri()) // Configure this client directly so as to not hit the discovery endpoint.
+            .build()
+            .await
+            .unwrap();
+
+        let well_known = client.client_well_known().await.unwrap().unwrap();
+        assert_eq!(well_known.homeserver.base_url, server.uri());
+        assert_eq!(well_known.field("io.element.e2ee"), Some(&json!({ "default": false })));
+        assert!(well_known.field("im.vector.riot.jitsi").is_none());
+
+        // This call hits the in-memory cache.
+        assert_eq!(client.client_well_known().await.unwrap(), Some(well_known));
+
+        drop(well_known_mock);
+
+        Mock::given(method("GET"))
+            .and(path("/.well-known/matrix/client"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "m.homeserver": { "base_url": server.uri() },
+                "io.element.e2ee": { "default": true },
+                "im.vector.riot.jitsi": { "preferredDomain": "jitsi.example.com" },
+            })))
+            .named("second well known mock")
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Refreshing hits the network again.
+        let well_known = client.refresh_client_well_known().await.unwrap().unwrap();
+        assert_eq!(well_known.field("io.element.e2ee"), Some(&json!({ "default": true })));
+        assert_eq!(
+            well_known.field("im.vector.riot.jitsi"),
+            Some(&json!({ "preferredDomain": "jitsi.example.com" }))
+        );
+    }
+
     #[async_test]
     async fn test_no_network_doesnt_cause_infinite_retries() {
         // Note: not `no_retry_test_client` or `logged_in_client` which uses the former,
@@ -3805,4 +4130,56 @@ pub(crate) mod tests {
         assert_eq!(max, uint!(1));
         assert_eq!(current, UInt::new_wrapping(data.len() as u64));
     }
+
+    #[async_test]
+    async fn test_shutdown_stops_background_work() {
+        let (client_builder, server) = test_client_builder_with_server().await;
+        let client = client_builder.request_config(RequestConfig::new()).build().await.unwrap();
+        set_client_session(&client).await;
+
+        // The sync request never resolves before the shutdown.
+        Mock::given(method("GET"))
+            .and(path("/_matrix/client/r0/sync"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(SyncResponseBuilder::new().build_json_sync_response())
+                    .set_delay(Duration::from_secs(30)),
+            )
+            .mount(&server)
+            .await;
+
+        let room_id = room_id!("!galette:saucisse.bzh");
+        client.base_client().get_or_create_room(room_id, RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+
+        // Start the sending task of the room's send queue.
+        let send_queue = room.send_queue();
+
+        let sync_task = spawn({
+            let client = client.clone();
+            async move { client.sync(SyncSettings::default()).await }
+        });
+
+        // Let the sync request be sent.
+        sleep(Duration::from_millis(100)).await;
+        assert!(!sync_task.is_finished());
+
+        // The in-flight sync request is given up on, instead of being waited for.
+        client.shutdown(Duration::from_secs(1)).await.unwrap();
+        timeout(Duration::from_millis(100), sync_task).await.unwrap().unwrap().unwrap();
+
+        let num_requests = server.received_requests().await.unwrap().len();
+
+        // Nothing is started anymore after the shutdown.
+        client.sync(SyncSettings::default()).await.unwrap();
+        assert_matches!(
+            client.sync_once(SyncSettings::default()).await,
+            Err(Error::ClientShutDown)
+        );
+        send_queue.send(RoomMessageEventContent::text_plain("hello").into()).await.unwrap();
+
+        sleep(Duration::from_millis(200)).await;
+
+        assert_eq!(server.received_requests().await.unwrap().len(), num_requests);
+    }
 }
@@ -0,0 +1,93 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tracking of the background work that [`Client::shutdown`] must stop.
+//!
+//! [`Client::shutdown`]: super::Client::shutdown
+
+use std::{future::Future, sync::Arc};
+
+use tokio::sync::{OwnedRwLockReadGuard, RwLock};
+use tokio_util::sync::CancellationToken;
+
+/// Keeps track of the background work of a client (sync loops, send queue
+/// and event cache tasks…), so that it can be cancelled and waited for when
+/// the client shuts down.
+///
+/// Every piece of work holds a [`ShutdownGuard`] as long as it's running, and
+/// must stop at its next safe point once [`Self::is_shut_down`] is true.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ShutdownTracker {
+    /// Cancelled when the client starts shutting down.
+    token: CancellationToken,
+
+    /// Read-locked by every running piece of work; write-locking it waits for
+    /// all of them to have stopped.
+    running: Arc<RwLock<()>>,
+}
+
+/// A guard held by a piece of work tracked by a [`ShutdownTracker`], for as
+/// long as it's running.
+#[derive(Debug)]
+pub(crate) struct ShutdownGuard {
+    _running: OwnedRwLockReadGuard<()>,
+}
+
+impl ShutdownTracker {
+    /// Register a new piece of running work.
+    ///
+    /// Returns `None` if the client is shutting down, in which case the work
+    /// must not start.
+    pub(crate) fn enter(&self) -> Option<ShutdownGuard> {
+        if self.is_shut_down() {
+            return None;
+        }
+
+        let running = self.running.clone().try_read_owned().ok()?;
+
+        Some(ShutdownGuard { _running: running })
+    }
+
+    /// Whether the client has started shutting down.
+    pub(crate) fn is_shut_down(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Wait until the client starts shutting down.
+    pub(crate) async fn wait_for_shut_down(&self) {
+        self.token.cancelled().await
+    }
+
+    /// Run the given future, unless the client starts shutting down in the
+    /// meantime, in which case it's dropped and `None` is returned.
+    ///
+    /// Only futures that can safely be cancelled at any point, like waiting
+    /// for a notification or for a network response, should be run this way.
+    pub(crate) async fn run_until_shut_down<F: Future>(&self, future: F) -> Option<F::Output> {
+        tokio::select! {
+            biased;
+
+            _ = self.token.cancelled() => None,
+            output = future => Some(output),
+        }
+    }
+
+    /// Ask all the tracked work to stop, and wait until it has.
+    pub(super) async fn shut_down(&self) {
+        self.token.cancel();
+
+        // All the work has stopped once every guard has been dropped.
+        drop(self.running.write().await);
+    }
+}
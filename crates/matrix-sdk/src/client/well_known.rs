@@ -0,0 +1,78 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The client well-known endpoint, keeping the raw JSON content of the
+//! well-known file alongside the typed one, so that custom fields aren't lost.
+
+use bytes::BufMut;
+use matrix_sdk_base::store::WellKnownResponse;
+use ruma::{
+    api::{
+        client::{discovery::discover_homeserver, Error as ClientApiError},
+        error::{DeserializationError, FromHttpResponseError, IntoHttpError},
+        EndpointError, IncomingResponse, MatrixVersion, Metadata, OutgoingRequest, SendAccessToken,
+    },
+    serde::JsonObject,
+};
+
+/// A request for the client well-known file, like
+/// [`discover_homeserver::Request`].
+#[derive(Clone, Debug, Default)]
+pub(super) struct Request;
+
+/// The client well-known file, with its raw JSON content.
+#[derive(Debug)]
+pub(super) struct Response(pub(super) WellKnownResponse);
+
+impl OutgoingRequest for Request {
+    type EndpointError = ClientApiError;
+    type IncomingResponse = Response;
+
+    const METADATA: Metadata = discover_homeserver::Request::METADATA;
+
+    fn try_into_http_request<T: Default + BufMut>(
+        self,
+        base_url: &str,
+        access_token: SendAccessToken<'_>,
+        considering_versions: &[MatrixVersion],
+    ) -> Result<http::Request<T>, IntoHttpError> {
+        discover_homeserver::Request::new().try_into_http_request(
+            base_url,
+            access_token,
+            considering_versions,
+        )
+    }
+}
+
+impl IncomingResponse for Response {
+    type EndpointError = ClientApiError;
+
+    fn try_from_http_response<T: AsRef<[u8]>>(
+        response: http::Response<T>,
+    ) -> Result<Self, FromHttpResponseError<Self::EndpointError>> {
+        if response.status().as_u16() >= 400 {
+            return Err(FromHttpResponseError::Server(ClientApiError::from_http_response(
+                response,
+            )));
+        }
+
+        let raw =
+            serde_json::from_slice::<JsonObject>(response.body().as_ref()).map_err(|error| {
+                FromHttpResponseError::Deserialization(DeserializationError::from(error))
+            })?;
+        let response = discover_homeserver::Response::try_from_http_response(response)?;
+
+        Ok(Self(WellKnownResponse::with_raw(response, raw)))
+    }
+}
@@ -18,13 +18,19 @@ use std::{future::IntoFuture, time::Duration};
 
 use futures_core::Stream;
 use futures_util::StreamExt;
+use matrix_sdk_base::crypto::{store::types::BackupDecryptionKey, types::RoomKeyBackupInfo};
 use matrix_sdk_common::boxed_into_future;
+use ruma::events::secret::request::SecretName;
 use thiserror::Error;
 use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
-use tracing::trace;
+use tracing::{info, trace};
+use zeroize::Zeroize;
 
-use super::{Backups, UploadState};
-use crate::utils::ChannelObservable;
+use super::{Backups, UploadState, PREVIOUS_BACKUP_RECOVERY_KEY_SECRET_NAME};
+use crate::{
+    encryption::secret_storage::{SecretStorageError, SecretStore},
+    utils::ChannelObservable,
+};
 
 /// Error describing the ways that waiting for the backup upload to settle down
 /// can fail.
@@ -157,3 +163,115 @@ impl<'a> IntoFuture for WaitForSteadyState<'a> {
         })
     }
 }
+
+/// Error describing the ways that upgrading the backup to the most recent
+/// backup algorithm can fail.
+#[derive(Debug, Error)]
+pub enum UpgradeAlgorithmError {
+    /// A typical SDK error, e.g. when no backup exists on the homeserver or
+    /// when the new backup version couldn't be created.
+    #[error(transparent)]
+    Sdk(#[from] crate::Error),
+
+    /// The room keys couldn't all be uploaded to the new backup version.
+    ///
+    /// The new backup version is kept, its backup recovery key is already in
+    /// the secret storage, and the room keys will keep being uploaded to it in
+    /// the background.
+    #[error(transparent)]
+    Upload(#[from] SteadyStateError),
+
+    /// The backup recovery keys couldn't be read from or stored in the secret
+    /// storage.
+    ///
+    /// The new backup version is only created once its backup recovery key
+    /// has been stored, so the backup is left as it was.
+    #[error(transparent)]
+    SecretStorage(#[from] SecretStorageError),
+}
+
+/// Named future for the [`Backups::upgrade_algorithm()`] method.
+#[derive(Debug)]
+pub struct UpgradeAlgorithm<'a> {
+    pub(super) backups: &'a Backups,
+    pub(super) secret_store: &'a SecretStore,
+    pub(super) progress: ChannelObservable<UploadState>,
+}
+
+impl UpgradeAlgorithm<'_> {
+    /// Subscribe to the progress of the upload of the room keys to the new
+    /// backup version.
+    pub fn subscribe_to_progress(
+        &self,
+    ) -> impl Stream<Item = Result<UploadState, BroadcastStreamRecvError>> {
+        self.progress.subscribe()
+    }
+}
+
+impl<'a> IntoFuture for UpgradeAlgorithm<'a> {
+    type Output = Result<bool, UpgradeAlgorithmError>;
+    boxed_into_future!(extra_bounds: 'a);
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move {
+            let Self { backups, secret_store, .. } = self;
+
+            let Some(old_version) = backups.get_current_version().await? else {
+                return Err(crate::Error::BackupNotEnabled.into());
+            };
+
+            let backup_info: RoomKeyBackupInfo =
+                old_version.algorithm.deserialize_as().map_err(crate::Error::from)?;
+
+            if Backups::uses_latest_algorithm(&backup_info) {
+                trace!("The backup already uses the most recent algorithm");
+                return Ok(false);
+            }
+
+            info!(old_version = old_version.version, "Upgrading the backup algorithm");
+
+            // The old backup version isn't deleted: it may contain room keys that this
+            // device doesn't have, and which can't be imported since the SDK doesn't
+            // support its algorithm. Keep its key in the secret storage, under another
+            // secret name, so it stays readable by the clients supporting it.
+            //
+            // If the key has already been moved by an interrupted upgrade, the current
+            // secret may be the key of a backup version which has never been created, so
+            // it mustn't replace the moved key.
+            if secret_store.get_secret(PREVIOUS_BACKUP_RECOVERY_KEY_SECRET_NAME).await?.is_none() {
+                if let Some(mut old_key) = secret_store.get_secret(SecretName::RecoveryKey).await? {
+                    secret_store
+                        .put_secret(PREVIOUS_BACKUP_RECOVERY_KEY_SECRET_NAME, &old_key)
+                        .await?;
+                    old_key.zeroize();
+                }
+            }
+
+            // Store the key of the new backup version in the secret storage before
+            // creating it, so other devices can read the backup as soon as it becomes
+            // the current one.
+            let decryption_key = BackupDecryptionKey::new().expect(
+                "We should be able to generate enough randomness to create a new backup recovery key",
+            );
+
+            let mut new_key = decryption_key.to_base64();
+            secret_store.put_secret(SecretName::RecoveryKey, &new_key).await?;
+            new_key.zeroize();
+
+            // Creating a new backup version resets the `backed_up` flags of the room keys,
+            // so they're all uploaded again, this time under the new algorithm.
+            backups.create_with_decryption_key(decryption_key).await?;
+
+            // If the upload fails, the new backup version stays the current one, and the
+            // room keys keep being uploaded to it in the background.
+            backups.wait_for_steady_state().await?;
+
+            info!(
+                old_version = old_version.version,
+                "The backup algorithm has been upgraded, the old backup version is kept"
+            );
+
+            Ok(true)
+        })
+    }
+}
@@ -45,19 +45,42 @@ use ruma::{
     serde::Raw,
     OwnedRoomId, RoomId, TransactionId,
 };
+use serde::{Deserialize, Serialize};
 use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
 use tracing::{error, info, instrument, trace, warn, Span};
 
 pub mod futures;
 pub(crate) mod types;
 
-pub use types::{BackupState, UploadState};
+pub use types::{BackupDownloadSettings, BackupState, UploadState};
 
-use self::futures::WaitForSteadyState;
+use self::futures::{UpgradeAlgorithm, WaitForSteadyState};
 use crate::{
-    crypto::olm::ExportedRoomKey, encryption::BackupDownloadStrategy, Client, Error, Room,
+    crypto::olm::ExportedRoomKey,
+    encryption::{secret_storage::SecretStore, BackupDownloadStrategy},
+    Client, Error, Room, RoomStateFilter,
 };
 
+/// The name of the secret under which [`Backups::upgrade_algorithm()`] keeps
+/// the backup recovery key of the old backup version, in the secret storage.
+pub const PREVIOUS_BACKUP_RECOVERY_KEY_SECRET_NAME: &str =
+    "org.matrix.rust_sdk.previous_megolm_backup";
+
+/// The key under which the progress of
+/// [`Backups::download_all_room_keys_in_batches()`] is persisted in the crypto
+/// store.
+const DOWNLOAD_PROGRESS_KEY: &str = "backups.download_progress";
+
+/// The persisted progress of [`Backups::download_all_room_keys_in_batches()`].
+#[derive(Debug, Serialize, Deserialize)]
+struct DownloadProgress {
+    /// The version of the backup the room keys have been downloaded from.
+    version: String,
+
+    /// The rooms whose room keys have already been downloaded.
+    downloaded_rooms: BTreeSet<OwnedRoomId>,
+}
+
 /// The backups manager for the [`Client`].
 #[derive(Debug, Clone)]
 pub struct Backups {
@@ -90,6 +113,20 @@ impl Backups {
     /// # anyhow::Ok(()) };
     /// ```
     pub async fn create(&self) -> Result<(), Error> {
+        // Create a new backup recovery key.
+        let decryption_key = BackupDecryptionKey::new().expect(
+            "We should be able to generate enough randomness to create a new backup recovery key",
+        );
+
+        self.create_with_decryption_key(decryption_key).await
+    }
+
+    /// Create a new backup version, encrypted with the given backup recovery
+    /// key, see [`Backups::create()`].
+    pub(crate) async fn create_with_decryption_key(
+        &self,
+        decryption_key: BackupDecryptionKey,
+    ) -> Result<(), Error> {
         self.client.inner.e2ee.backup_state.clear_backup_exists_on_server();
         let _guard = self.client.locks().backup_modify_lock.lock().await;
 
@@ -101,12 +138,6 @@ impl Backups {
             let olm_machine = self.client.olm_machine().await;
             let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
 
-            // Create a new backup recovery key.
-            let decryption_key = BackupDecryptionKey::new().expect(
-                "We should be able to generate enough randomness to create a new backup recovery \
-                 key",
-            );
-
             // Get the info about the new backup key, this needs to be uploaded to the
             // homeserver[1].
             //
@@ -328,6 +359,74 @@ impl Backups {
         }
     }
 
+    /// Upgrade the backup on the homeserver to the most recent backup
+    /// algorithm supported by the SDK, currently
+    /// `m.megolm_backup.v1.curve25519-aes-sha2`.
+    ///
+    /// If the current backup uses another algorithm, a new backup version is
+    /// created with a new backup recovery key, and all the room keys known by
+    /// this device are uploaded to it.
+    ///
+    /// The new backup recovery key is stored in the given secret store before
+    /// the new backup version is created, so other devices can read the
+    /// backup as soon as it becomes the current one, even if the upload of
+    /// the room keys is interrupted.
+    ///
+    /// The old backup version is kept on the homeserver, since it may contain
+    /// room keys that this device doesn't have, and which can't be moved to
+    /// the new backup version because the SDK doesn't support its algorithm.
+    /// Its backup recovery key is moved to the
+    /// [`PREVIOUS_BACKUP_RECOVERY_KEY_SECRET_NAME`] secret, so it stays
+    /// readable by the clients supporting its algorithm.
+    ///
+    /// The future resolves to `false` if the backup already uses the most
+    /// recent algorithm, and `true` once it has been upgraded.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::{Client, encryption::backups::UploadState};
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://example.com")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// use futures_util::StreamExt;
+    ///
+    /// let secret_store = client
+    ///     .encryption()
+    ///     .secret_storage()
+    ///     .open_secret_store("It's a secret to everybody")
+    ///     .await?;
+    ///
+    /// let backups = client.encryption().backups();
+    /// let upgrade = backups.upgrade_algorithm(&secret_store);
+    ///
+    /// let mut progress_stream = upgrade.subscribe_to_progress();
+    ///
+    /// tokio::spawn(async move {
+    ///     while let Some(Ok(UploadState::Uploading(counts))) =
+    ///         progress_stream.next().await
+    ///     {
+    ///         println!(
+    ///             "Uploaded {} out of {} room keys.",
+    ///             counts.backed_up, counts.total
+    ///         );
+    ///     }
+    /// });
+    ///
+    /// if upgrade.await? {
+    ///     println!("The backup has been upgraded");
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub fn upgrade_algorithm<'a>(&'a self, secret_store: &'a SecretStore) -> UpgradeAlgorithm<'a> {
+        UpgradeAlgorithm {
+            backups: self,
+            secret_store,
+            progress: self.client.inner.e2ee.backup_state.upload_progress.clone(),
+        }
+    }
+
     /// Get a stream of updates to the [`BackupState`].
     ///
     /// This method will send out the current state as the first update.
@@ -452,17 +551,7 @@ impl Backups {
 
         if let Some(decryption_key) = backup_keys.decryption_key {
             if let Some(version) = backup_keys.backup_version {
-                let request =
-                    get_backup_keys_for_room::v3::Request::new(version.clone(), room_id.to_owned());
-                let response = self.client.send(request).await?;
-
-                // Transform response to standard format (map of room ID -> room key).
-                let response = get_backup_keys::v3::Response::new(BTreeMap::from([(
-                    room_id.to_owned(),
-                    RoomKeyBackup::new(response.sessions),
-                )]));
-
-                self.handle_downloaded_room_keys(response, decryption_key, &version, olm_machine)
+                self.download_room_keys_for_room_with_key(room_id, decryption_key, &version)
                     .await?;
             }
         }
@@ -470,6 +559,30 @@ impl Backups {
         Ok(())
     }
 
+    /// Download all the room keys of a room from the given backup version, and
+    /// import them with the given backup recovery key.
+    async fn download_room_keys_for_room_with_key(
+        &self,
+        room_id: &RoomId,
+        decryption_key: BackupDecryptionKey,
+        version: &str,
+    ) -> Result<(), Error> {
+        let request =
+            get_backup_keys_for_room::v3::Request::new(version.to_owned(), room_id.to_owned());
+        let response = self.client.send(request).await?;
+
+        // Transform response to standard format (map of room ID -> room key).
+        let response = get_backup_keys::v3::Response::new(BTreeMap::from([(
+            room_id.to_owned(),
+            RoomKeyBackup::new(response.sessions),
+        )]));
+
+        let olm_machine = self.client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
+
+        self.handle_downloaded_room_keys(response, decryption_key, version, olm_machine).await
+    }
+
     /// Download a single room key from the server-side key backup.
     ///
     /// Returns `true` if we managed to download a room key, `false` or an error
@@ -516,6 +629,141 @@ impl Backups {
         }
     }
 
+    /// Download the room keys of all the rooms known by the client from the
+    /// server-side key backup, in batches.
+    ///
+    /// Unlike [`BackupDownloadStrategy::OneShot`], which downloads the whole
+    /// backup in a single request, the room keys are downloaded room by room,
+    /// [`BackupDownloadSettings::batch_size`] rooms at a time, waiting for
+    /// [`BackupDownloadSettings::batch_delay`] between two batches.
+    ///
+    /// The joined, invited and left rooms are all included. The room keys of
+    /// the rooms the client doesn't know about, e.g. rooms left before the
+    /// client was logged in, aren't downloaded.
+    ///
+    /// The progress is persisted after each batch. If the download is
+    /// interrupted, e.g. because the app has been closed, calling this method
+    /// again will only download the room keys of the rooms that haven't been
+    /// handled yet. The progress is reset when the backup version changes, and
+    /// once the download completes.
+    ///
+    /// The download can be paused and resumed between two batches with
+    /// [`Backups::pause_download()`] and [`Backups::resume_download()`].
+    #[instrument(skip(self))]
+    pub async fn download_all_room_keys_in_batches(
+        &self,
+        settings: BackupDownloadSettings,
+    ) -> Result<(), Error> {
+        let (decryption_key, version, mut progress) = {
+            let olm_machine = self.client.olm_machine().await;
+            let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
+
+            let backup_keys = olm_machine.store().load_backup_keys().await?;
+
+            let (Some(decryption_key), Some(version)) =
+                (backup_keys.decryption_key, backup_keys.backup_version)
+            else {
+                return Ok(());
+            };
+
+            let progress = match olm_machine
+                .store()
+                .get_value::<DownloadProgress>(DOWNLOAD_PROGRESS_KEY)
+                .await?
+            {
+                Some(progress) if progress.version == version => progress,
+                _ => {
+                    DownloadProgress { version: version.clone(), downloaded_rooms: BTreeSet::new() }
+                }
+            };
+
+            (decryption_key, version, progress)
+        };
+
+        let room_ids: Vec<_> = self
+            .client
+            .rooms_filtered(
+                RoomStateFilter::JOINED | RoomStateFilter::INVITED | RoomStateFilter::LEFT,
+            )
+            .into_iter()
+            .map(|room| room.room_id().to_owned())
+            .filter(|room_id| !progress.downloaded_rooms.contains(room_id))
+            .collect();
+
+        info!(num_rooms = room_ids.len(), "Downloading room keys from the backup in batches");
+
+        for (index, batch) in room_ids.chunks(settings.batch_size.max(1)).enumerate() {
+            if index > 0 {
+                crate::sleep::sleep(settings.batch_delay).await;
+            }
+
+            self.wait_until_download_is_resumed().await;
+
+            for room_id in batch {
+                self.download_room_keys_for_room_with_key(
+                    room_id,
+                    decryption_key.clone(),
+                    &version,
+                )
+                .await?;
+
+                progress.downloaded_rooms.insert(room_id.to_owned());
+            }
+
+            // Persist the progress, so an interrupted download resumes from this point.
+            let olm_machine = self.client.olm_machine().await;
+            let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
+            olm_machine.store().set_value(DOWNLOAD_PROGRESS_KEY, &progress).await?;
+
+            trace!(
+                downloaded_rooms = progress.downloaded_rooms.len(),
+                "Downloaded a batch of room keys"
+            );
+        }
+
+        // The download is complete, forget about the progress so the next download
+        // starts from scratch and picks up the room keys uploaded in the meantime.
+        let olm_machine = self.client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
+        olm_machine.store().remove_custom_value(DOWNLOAD_PROGRESS_KEY).await?;
+
+        Ok(())
+    }
+
+    /// Pause the download of room keys started with
+    /// [`Backups::download_all_room_keys_in_batches()`].
+    ///
+    /// The download stops before starting the next batch, until
+    /// [`Backups::resume_download()`] is called.
+    pub fn pause_download(&self) {
+        self.client.inner.e2ee.backup_state.download_paused.set(true);
+    }
+
+    /// Resume the download of room keys paused with
+    /// [`Backups::pause_download()`].
+    pub fn resume_download(&self) {
+        self.client.inner.e2ee.backup_state.download_paused.set(false);
+    }
+
+    /// Wait until the download of room keys isn't paused anymore.
+    async fn wait_until_download_is_resumed(&self) {
+        let mut paused = self.client.inner.e2ee.backup_state.download_paused.subscribe();
+
+        while paused.get() {
+            trace!("The download of room keys is paused, waiting");
+
+            if paused.next().await.is_none() {
+                break;
+            }
+        }
+    }
+
+    /// Whether the given backup uses the most recent backup algorithm supported
+    /// by the SDK.
+    fn uses_latest_algorithm(backup_info: &RoomKeyBackupInfo) -> bool {
+        matches!(backup_info, RoomKeyBackupInfo::MegolmBackupV1Curve25519AesSha2(_))
+    }
+
     /// Set the state of the backup.
     fn set_state(&self, new_state: BackupState) {
         let old_state = self.client.inner.e2ee.backup_state.global_state.set(new_state);
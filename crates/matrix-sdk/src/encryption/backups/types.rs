@@ -17,6 +17,7 @@ use std::{
     time::Duration,
 };
 
+use eyeball::SharedObservable;
 use matrix_sdk_base::crypto::{store::types::RoomKeyCounts, RoomKeyImportResult};
 use tokio::sync::broadcast;
 
@@ -48,6 +49,27 @@ pub enum UploadState {
     Done,
 }
 
+/// Settings to throttle the download of room keys from the backup, see
+/// [`Backups::download_all_room_keys_in_batches()`].
+#[derive(Clone, Copy, Debug)]
+pub struct BackupDownloadSettings {
+    /// The number of rooms whose room keys are downloaded in a single batch.
+    ///
+    /// Defaults to 10.
+    pub batch_size: usize,
+
+    /// How long to wait between two batches.
+    ///
+    /// Defaults to 100 milliseconds.
+    pub batch_delay: Duration,
+}
+
+impl Default for BackupDownloadSettings {
+    fn default() -> Self {
+        Self { batch_size: 10, batch_delay: Duration::from_millis(100) }
+    }
+}
+
 pub(crate) struct BackupClientState {
     pub(super) upload_delay: Arc<RwLock<Duration>>,
     pub(crate) upload_progress: ChannelObservable<UploadState>,
@@ -61,6 +83,9 @@ pub(crate) struct BackupClientState {
     /// on the server was changed by some other client, we will have a old
     /// value.
     pub(super) backup_exists_on_server: RwLock<Option<bool>>,
+
+    /// Whether the download of room keys in batches has been paused.
+    pub(super) download_paused: SharedObservable<bool>,
 }
 
 impl BackupClientState {
@@ -96,6 +121,7 @@ impl Default for BackupClientState {
             global_state: Default::default(),
             room_keys_broadcaster: broadcast::Sender::new(100),
             backup_exists_on_server: RwLock::new(None),
+            download_paused: SharedObservable::new(false),
         }
     }
 }
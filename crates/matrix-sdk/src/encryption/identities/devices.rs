@@ -12,10 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::BTreeMap, ops::Deref};
+use std::{collections::BTreeMap, iter, ops::Deref};
 
 use matrix_sdk_base::crypto::{
-    store::CryptoStoreError, Device as BaseDevice, DeviceData, LocalTrust,
+    store::CryptoStoreError, Device as BaseDevice, DeviceData, LocalTrust, OlmSessionHealth,
     UserDevices as BaseUserDevices,
 };
 use ruma::{events::key::verification::VerificationMethod, DeviceId, OwnedDeviceId, OwnedUserId};
@@ -24,7 +24,7 @@ use super::ManualVerifyError;
 use crate::{
     encryption::verification::{SasVerification, VerificationRequest},
     error::Result,
-    Client,
+    Client, Error,
 };
 
 /// Updates about [`Device`]s which got received over the `/keys/query`
@@ -553,6 +553,46 @@ impl Device {
     pub fn is_cross_signed_by_owner(&self) -> bool {
         self.inner.is_cross_signed_by_owner()
     }
+
+    /// Get the health of the Olm sessions shared with this device: the known
+    /// sessions, and whether the current one is wedged.
+    ///
+    /// This is useful to diagnose why this device can't decrypt our messages,
+    /// or why we can't decrypt its messages.
+    pub async fn olm_session_health(&self) -> Result<OlmSessionHealth> {
+        let olm = self.client.olm_machine().await;
+        let olm = olm.as_ref().ok_or(Error::NoOlmMachine)?;
+
+        Ok(olm
+            .olm_session_health(self.user_id(), self.device_id())
+            .await?
+            .unwrap_or(OlmSessionHealth { sessions: Vec::new(), is_wedged: false }))
+    }
+
+    /// Create a new Olm session with this device, and send it a dummy message
+    /// with the new session, so it starts using it too.
+    ///
+    /// This is useful to resolve the case where this device can't decrypt our
+    /// messages anymore, because it lost the Olm session we use to send it
+    /// room keys.
+    ///
+    /// Returns `false` if a new session can't be created with this device,
+    /// because it doesn't support Olm.
+    pub async fn reestablish_olm_session(&self) -> Result<bool> {
+        {
+            let olm = self.client.olm_machine().await;
+            let olm = olm.as_ref().ok_or(Error::NoOlmMachine)?;
+
+            if !olm.reestablish_olm_session(self.user_id(), self.device_id()).await? {
+                return Ok(false);
+            }
+        }
+
+        self.client.claim_one_time_keys(iter::once(self.user_id())).await?;
+        self.client.send_outgoing_requests().await?;
+
+        Ok(true)
+    }
 }
 
 /// The collection of all the [`Device`]s a user has.
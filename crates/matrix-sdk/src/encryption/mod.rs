@@ -24,6 +24,7 @@ use std::{
     iter,
     path::PathBuf,
     sync::Arc,
+    time::Duration,
 };
 
 use eyeball::{SharedObservable, Subscriber};
@@ -39,7 +40,7 @@ use matrix_sdk_base::crypto::{
     },
     CrossSigningBootstrapRequests, OlmMachine,
 };
-use matrix_sdk_common::{executor::spawn, locks::Mutex as StdMutex};
+use matrix_sdk_common::{executor::spawn, locks::Mutex as StdMutex, sleep::sleep};
 use ruma::{
     api::client::{
         keys::{
@@ -224,6 +225,50 @@ pub enum VerificationState {
     Unverified,
 }
 
+/// The verification state of our own device, with the reason why it's
+/// unverified.
+///
+/// This is a more detailed version of [`VerificationState`], to explain to the
+/// user what needs to be done to verify the device.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DetailedVerificationState {
+    /// The verification state is unknown for now.
+    Unknown,
+    /// The device is considered to be verified, it has been signed by its user
+    /// identity.
+    Verified,
+    /// The device is unverified.
+    Unverified {
+        /// Why the device is unverified.
+        reason: UnverifiedReason,
+    },
+}
+
+/// Why our own device is unverified, see
+/// [`DetailedVerificationState::Unverified`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UnverifiedReason {
+    /// The account has no user identity, i.e. cross-signing hasn't been set
+    /// up yet.
+    MissingIdentity,
+    /// The user identity of the account has been replaced since it was
+    /// verified, e.g. because it has been reset from another device, and the
+    /// new identity hasn't signed this device.
+    NewIdentity,
+    /// The user identity of the account hasn't signed this device.
+    MissingSignature,
+}
+
+impl From<DetailedVerificationState> for VerificationState {
+    fn from(state: DetailedVerificationState) -> Self {
+        match state {
+            DetailedVerificationState::Unknown => Self::Unknown,
+            DetailedVerificationState::Verified => Self::Verified,
+            DetailedVerificationState::Unverified { .. } => Self::Unverified,
+        }
+    }
+}
+
 /// Wraps together a `CrossProcessLockStoreGuard` and a generation number.
 #[derive(Debug)]
 pub struct CrossProcessLockStoreGuardWithGeneration {
@@ -238,6 +283,15 @@ impl CrossProcessLockStoreGuardWithGeneration {
     }
 }
 
+/// The delay between two attempts to upload the cross-signing keys, while
+/// waiting for the additional authentication to be completed.
+const CROSS_SIGNING_RESET_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// The maximum number of attempts to upload the cross-signing keys, while
+/// waiting for the additional authentication to be completed; that's about 5
+/// minutes, with [`CROSS_SIGNING_RESET_RETRY_DELAY`].
+const CROSS_SIGNING_RESET_MAX_ATTEMPTS: usize = 300;
+
 /// A stateful struct remembering the cross-signing keys we need to upload.
 ///
 /// Since the `/_matrix/client/v3/keys/device_signing/upload` might require
@@ -283,10 +337,16 @@ impl CrossSigningResetHandle {
     /// Continue the cross-signing reset by either waiting for the
     /// authentication to be done on the side of the OAuth 2.0 server or by
     /// providing additional [`AuthData`] the homeserver requires.
+    ///
+    /// While the authentication hasn't been completed, the upload is retried
+    /// every second, for about 5 minutes; the last error is returned if it
+    /// still hasn't been completed by then.
     pub async fn auth(&self, auth: Option<AuthData>) -> Result<()> {
         let mut upload_request = self.upload_request.clone();
         upload_request.auth = auth;
 
+        let mut attempts = 0;
+
         while let Err(e) = self.client.send(upload_request.clone()).await {
             if *self.is_cancelled.lock().await {
                 return Ok(());
@@ -300,6 +360,18 @@ impl CrossSigningResetHandle {
                 }
                 None => return Err(e.into()),
             }
+
+            attempts += 1;
+            if attempts >= CROSS_SIGNING_RESET_MAX_ATTEMPTS {
+                warn!("The additional authentication wasn't completed in time, giving up");
+                return Err(e.into());
+            }
+
+            sleep(CROSS_SIGNING_RESET_RETRY_DELAY).await;
+
+            if *self.is_cancelled.lock().await {
+                return Ok(());
+            }
         }
 
         self.client.send(self.signatures_request.clone()).await?;
@@ -782,6 +854,17 @@ impl Encryption {
         self.client.inner.verification_state.subscribe_reset()
     }
 
+    /// Get a [`Subscriber`] for the [`DetailedVerificationState`] of our own
+    /// device.
+    ///
+    /// It's updated at the same time as
+    /// [`Encryption::verification_state()`], and also tells why the device is
+    /// unverified, e.g. to show a different message when the user identity
+    /// has been reset from another device.
+    pub fn detailed_verification_state(&self) -> Subscriber<DetailedVerificationState> {
+        self.client.inner.detailed_verification_state.subscribe_reset()
+    }
+
     /// Get a verification object with the given flow id.
     pub async fn get_verification(&self, user_id: &UserId, flow_id: &str) -> Option<Verification> {
         let olm = self.client.olm_machine().await;
@@ -1419,6 +1502,27 @@ impl Encryption {
         &self,
         path: PathBuf,
         passphrase: &str,
+    ) -> Result<RoomKeyImportResult, RoomKeyImportError> {
+        self.import_room_keys_with_progress(path, passphrase, |_, _| {}).await
+    }
+
+    /// Import E2EE keys from the given file path, reporting the progress of
+    /// the import.
+    ///
+    /// This is the same as [`Encryption::import_room_keys`], but the
+    /// `progress_listener` is called with the number of room keys processed so
+    /// far and the total number of room keys found in the key export, which
+    /// is useful for exports containing a large number of keys.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if it isn't run on a Tokio runtime.
+    #[cfg(not(target_family = "wasm"))]
+    pub async fn import_room_keys_with_progress(
+        &self,
+        path: PathBuf,
+        passphrase: &str,
+        progress_listener: impl Fn(usize, usize),
     ) -> Result<RoomKeyImportResult, RoomKeyImportError> {
         let olm = self.client.olm_machine().await;
         let olm = olm.as_ref().ok_or(RoomKeyImportError::StoreClosed)?;
@@ -1432,7 +1536,7 @@ impl Encryption {
         let task = tokio::task::spawn_blocking(decrypt);
         let import = task.await.expect("Task join error")?;
 
-        let ret = olm.store().import_exported_room_keys(import, |_, _| {}).await?;
+        let ret = olm.store().import_exported_room_keys(import, progress_listener).await?;
 
         self.backups().maybe_trigger_backup();
 
@@ -1758,26 +1862,42 @@ impl Encryption {
     }
 
     async fn update_verification_state(&self) {
-        match self.get_own_device().await {
-            Ok(device) => {
-                if let Some(device) = device {
-                    let is_verified = device.is_cross_signed_by_owner();
-
-                    if is_verified {
-                        self.client.inner.verification_state.set(VerificationState::Verified);
-                    } else {
-                        self.client.inner.verification_state.set(VerificationState::Unverified);
-                    }
-                } else {
-                    warn!("Couldn't find out own device in the store.");
-                    self.client.inner.verification_state.set(VerificationState::Unknown);
-                }
+        let state = self.compute_detailed_verification_state().await;
+
+        self.client.inner.verification_state.set(state.into());
+        self.client.inner.detailed_verification_state.set(state);
+    }
+
+    async fn compute_detailed_verification_state(&self) -> DetailedVerificationState {
+        let device = match self.get_own_device().await {
+            Ok(Some(device)) => device,
+            Ok(None) => {
+                warn!("Couldn't find out own device in the store.");
+                return DetailedVerificationState::Unknown;
             }
             Err(error) => {
                 warn!("Failed retrieving own device: {error}");
-                self.client.inner.verification_state.set(VerificationState::Unknown);
+                return DetailedVerificationState::Unknown;
             }
+        };
+
+        if device.is_cross_signed_by_owner() {
+            return DetailedVerificationState::Verified;
         }
+
+        let reason = match self.get_user_identity(device.user_id()).await {
+            Ok(Some(identity)) if identity.was_previously_verified() => {
+                UnverifiedReason::NewIdentity
+            }
+            Ok(Some(_)) => UnverifiedReason::MissingSignature,
+            Ok(None) => UnverifiedReason::MissingIdentity,
+            Err(error) => {
+                warn!("Failed retrieving own user identity: {error}");
+                UnverifiedReason::MissingSignature
+            }
+        };
+
+        DetailedVerificationState::Unverified { reason }
     }
 
     /// Encrypts then send the given content via the `/sendToDevice` end-point
@@ -1886,7 +2006,10 @@ mod tests {
     use crate::{
         assert_next_matches_with_timeout,
         config::RequestConfig,
-        encryption::{OAuthCrossSigningResetInfo, VerificationState},
+        encryption::{
+            DetailedVerificationState, OAuthCrossSigningResetInfo, UnverifiedReason,
+            VerificationState,
+        },
         test_utils::{
             client::mock_matrix_session, logged_in_client, no_retry_test_client, set_client_session,
         },
@@ -2234,6 +2357,23 @@ mod tests {
         assert_next_matches_with_timeout!(verification_state, VerificationState::Unverified);
     }
 
+    #[async_test]
+    async fn test_detailed_verification_state_without_identity() {
+        let client = no_retry_test_client(None).await;
+
+        let mut detailed_state = client.encryption().detailed_verification_state();
+        assert_next_matches_with_timeout!(detailed_state, DetailedVerificationState::Unknown);
+
+        set_client_session(&client).await;
+
+        // Cross-signing hasn't been set up, so there is no identity to sign the device.
+        assert_next_matches_with_timeout!(
+            detailed_state,
+            DetailedVerificationState::Unverified { reason: UnverifiedReason::MissingIdentity }
+        );
+        assert_eq!(client.encryption().verification_state().get(), VerificationState::Unverified);
+    }
+
     #[test]
     fn test_oauth_reset_info_from_uiaa_info() {
         let auth_info = json!({
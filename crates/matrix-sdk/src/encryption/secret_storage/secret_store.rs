@@ -420,13 +420,26 @@ impl SecretStore {
     }
 
     pub(super) async fn export_secrets(&self) -> Result<()> {
-        let olm_machine = self.client.olm_machine().await;
-        let olm_machine = olm_machine.as_ref().ok_or(crate::Error::NoOlmMachine)?;
+        let cross_signing_keys = {
+            let olm_machine = self.client.olm_machine().await;
+            let olm_machine = olm_machine.as_ref().ok_or(crate::Error::NoOlmMachine)?;
 
-        if let Some(cross_signing_keys) = olm_machine.export_cross_signing_keys().await? {
+            olm_machine.export_cross_signing_keys().await?
+        };
+
+        if let Some(cross_signing_keys) = cross_signing_keys {
             self.put_cross_signing_keys(cross_signing_keys).await?;
         }
 
+        self.put_backup_recovery_key().await
+    }
+
+    /// Store the backup recovery key of the currently active backup in the
+    /// secret store, replacing the previous one.
+    pub(crate) async fn put_backup_recovery_key(&self) -> Result<()> {
+        let olm_machine = self.client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(crate::Error::NoOlmMachine)?;
+
         let backup_keys = olm_machine.backup_machine().get_backup_keys().await?;
 
         if let Some(backup_recovery_key) = backup_keys.decryption_key {
@@ -45,6 +45,8 @@ use serde_json::Error as JsonError;
 use thiserror::Error;
 use url::ParseError as UrlParseError;
 
+#[cfg(feature = "e2e-encryption")]
+use crate::room::UntrustedDevicesReport;
 use crate::{
     authentication::oauth::OAuthError, event_cache::EventCacheError, media::MediaError,
     room::reply::ReplyError, sliding_sync::Error as SlidingSyncError, store_locks::LockStoreError,
@@ -300,6 +302,14 @@ pub enum Error {
     #[error("The olm machine isn't yet available")]
     NoOlmMachine,
 
+    /// The room only shares its keys with trusted devices, and some devices of
+    /// its members are neither trusted nor have been confirmed by the user.
+    ///
+    /// See [`Room::confirm_untrusted_devices`](crate::Room::confirm_untrusted_devices).
+    #[cfg(feature = "e2e-encryption")]
+    #[error("the room has untrusted devices which haven't been confirmed")]
+    UntrustedDevices(Box<UntrustedDevicesReport>),
+
     /// An error de/serializing type for the `StateStore`
     #[error(transparent)]
     SerdeJson(#[from] JsonError),
@@ -379,6 +389,28 @@ pub enum Error {
     #[error("a concurrent request failed; see logs for details")]
     ConcurrentRequestFailed,
 
+    /// The account data kept being modified concurrently, e.g. by another
+    /// device, so it couldn't be updated.
+    #[error("the account data kept being modified concurrently")]
+    AccountDataConflict,
+
+    /// The power levels of a room have been modified since the ones the
+    /// changes were based on, e.g. by another admin of the room.
+    #[error("the power levels of the room have been modified concurrently")]
+    PowerLevelsConflict,
+
+    /// The client has been shut down with [`Client::shutdown`], so the request
+    /// has been given up on.
+    ///
+    /// [`Client::shutdown`]: crate::Client::shutdown
+    #[error("the client has been shut down")]
+    ClientShutDown,
+
+    /// An attachment couldn't be processed before being uploaded.
+    #[cfg(feature = "image-proc")]
+    #[error(transparent)]
+    ImageProcessing(#[from] image::ImageError),
+
     /// An other error was raised.
     ///
     /// This might happen because encryption was enabled on the base-crate
@@ -0,0 +1,90 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Machine-readable representation of the linked chunk of a room, for
+//! debugging purposes.
+
+use matrix_sdk_base::deserialized_responses::TimelineEventKind;
+use matrix_sdk_common::linked_chunk::ChunkIdentifier;
+use ruma::OwnedEventId;
+
+use super::room::events::Event;
+
+/// A chunk of the linked chunk of a room, as returned by
+/// [`RoomEventCache::debug_chunks`](super::RoomEventCache::debug_chunks).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugChunk {
+    /// The identifier of the chunk.
+    pub identifier: ChunkIdentifier,
+
+    /// The identifier of the previous chunk, if this is the first chunk
+    /// loaded in memory and there are more chunks in the store.
+    pub lazy_previous: Option<ChunkIdentifier>,
+
+    /// The content of the chunk.
+    pub content: DebugChunkContent,
+}
+
+/// The content of a [`DebugChunk`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugChunkContent {
+    /// A gap, i.e. a hole in the history which can be filled by paginating
+    /// backwards.
+    Gap {
+        /// The token to use to paginate backwards from this gap.
+        prev_token: String,
+    },
+
+    /// Some events.
+    Events(Vec<DebugEvent>),
+}
+
+/// An event of a [`DebugChunk`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugEvent {
+    /// The event ID, if the event has one.
+    pub event_id: Option<OwnedEventId>,
+
+    /// The position of the event in the ordering of all the events of the
+    /// room, including those which haven't been loaded in memory, if known.
+    pub order: Option<usize>,
+
+    /// Where the content of the event comes from.
+    pub origin: DebugEventOrigin,
+}
+
+impl DebugEvent {
+    pub(super) fn new(event: &Event, order: Option<usize>) -> Self {
+        let origin = match &event.kind {
+            TimelineEventKind::PlainText { .. } => DebugEventOrigin::PlainText,
+            TimelineEventKind::Decrypted(_) => DebugEventOrigin::Decrypted,
+            TimelineEventKind::UnableToDecrypt { .. } => DebugEventOrigin::UnableToDecrypt,
+        };
+
+        Self { event_id: event.event_id(), order, origin }
+    }
+}
+
+/// Where the content of a [`DebugEvent`] comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugEventOrigin {
+    /// The event was received unencrypted.
+    PlainText,
+
+    /// The event was encrypted, and has been decrypted.
+    Decrypted,
+
+    /// The event is encrypted, and couldn't be decrypted.
+    UnableToDecrypt,
+}
@@ -18,10 +18,13 @@
 use std::collections::BTreeSet;
 
 use matrix_sdk_base::{
+    deserialized_responses::{ThreadSummaryStatus, TimelineEventKind},
     event_cache::store::EventCacheStoreLock,
     linked_chunk::{LinkedChunkId, Position},
 };
-use ruma::OwnedEventId;
+use ruma::{events::AnySyncTimelineEvent, serde::Raw, CanonicalJsonValue, OwnedEventId};
+use serde_json::Value as JsonValue;
+use sha2::{Digest as _, Sha256};
 
 use super::{
     room::events::{Event, RoomEvents},
@@ -154,6 +157,107 @@ pub(super) struct DeduplicationOutcome {
     pub non_empty_all_duplicates: bool,
 }
 
+/// Merge a new copy of an event with the copy already known by the event
+/// cache.
+///
+/// The same event can be received several times with different bundled
+/// aggregations, e.g. from a sync and then from a back-pagination, so the
+/// richest information of both copies is kept:
+///
+/// - a copy which couldn't be decrypted doesn't override a decrypted one,
+/// - the bundled relations (`unsigned.m.relations`) of the known copy are
+///   preserved, unless the new copy has a more recent version of them,
+/// - the thread summary of the known copy is preserved, unless the new copy has
+///   one.
+///
+/// Returns `None` if the merged event is identical to the known copy, i.e.
+/// there's nothing to update.
+pub(super) fn merge_duplicate_event(existing: &Event, mut new: Event) -> Option<Event> {
+    if matches!(new.kind, TimelineEventKind::UnableToDecrypt { .. })
+        && !matches!(existing.kind, TimelineEventKind::UnableToDecrypt { .. })
+    {
+        new.kind = existing.kind.clone();
+    }
+
+    if let Some(raw) = merge_bundled_relations(existing.raw(), new.raw()) {
+        replace_raw_event(&mut new, raw);
+    }
+
+    if matches!(new.thread_summary, ThreadSummaryStatus::Unknown) {
+        new.thread_summary = existing.thread_summary.clone();
+    }
+
+    if new.bundled_latest_thread_event.is_none() {
+        new.bundled_latest_thread_event = existing.bundled_latest_thread_event.clone();
+    }
+
+    let unchanged = content_hash(existing.raw()) == content_hash(new.raw())
+        && existing.thread_summary == new.thread_summary;
+
+    (!unchanged).then_some(new)
+}
+
+/// Hash the content of an event, to cheaply find whether two copies of an
+/// event are identical.
+///
+/// The canonical JSON form is hashed, so that the order of the fields or the
+/// whitespace don't matter. SHA-256 is used rather than the standard library's
+/// hasher, whose output isn't stable across Rust releases.
+fn content_hash(raw: &Raw<AnySyncTimelineEvent>) -> [u8; 32] {
+    let hasher = match raw.deserialize_as::<CanonicalJsonValue>() {
+        Ok(value) => Sha256::new().chain_update(value.to_string()),
+        Err(_) => Sha256::new().chain_update(raw.json().get()),
+    };
+
+    hasher.finalize().into()
+}
+
+/// Add the bundled relations of `existing` which are missing from `new` to
+/// `new`.
+///
+/// Returns `None` if no relation is missing from `new`.
+fn merge_bundled_relations(
+    existing: &Raw<AnySyncTimelineEvent>,
+    new: &Raw<AnySyncTimelineEvent>,
+) -> Option<Raw<AnySyncTimelineEvent>> {
+    let existing: JsonValue = existing.deserialize_as().ok()?;
+    let existing_relations = existing.get("unsigned")?.get("m.relations")?.as_object()?;
+
+    let mut new: JsonValue = new.deserialize_as().ok()?;
+    let relations = new
+        .as_object_mut()?
+        .entry("unsigned")
+        .or_insert_with(|| JsonValue::Object(Default::default()))
+        .as_object_mut()?
+        .entry("m.relations")
+        .or_insert_with(|| JsonValue::Object(Default::default()))
+        .as_object_mut()?;
+
+    let mut has_changed = false;
+
+    for (relation_type, aggregation) in existing_relations {
+        if !relations.contains_key(relation_type) {
+            relations.insert(relation_type.clone(), aggregation.clone());
+            has_changed = true;
+        }
+    }
+
+    if !has_changed {
+        return None;
+    }
+
+    Raw::new(&new).ok().map(Raw::cast)
+}
+
+/// Replace the raw JSON of an event, whatever its kind.
+fn replace_raw_event(event: &mut Event, raw: Raw<AnySyncTimelineEvent>) {
+    match &mut event.kind {
+        TimelineEventKind::Decrypted(decrypted) => decrypted.event = raw.cast(),
+        TimelineEventKind::UnableToDecrypt { event, .. }
+        | TimelineEventKind::PlainText { event } => *event = raw,
+    }
+}
+
 #[cfg(test)]
 #[cfg(not(target_family = "wasm"))] // These tests uses the cross-process lock, so need time support.
 mod tests {
@@ -161,7 +265,10 @@ mod tests {
 
     use matrix_sdk_base::{deserialized_responses::TimelineEvent, linked_chunk::ChunkIdentifier};
     use matrix_sdk_test::{async_test, event_factory::EventFactory};
-    use ruma::{owned_event_id, serde::Raw, user_id, EventId};
+    use ruma::{
+        event_id, events::room::message::RoomMessageEventContentWithoutRelation, owned_event_id,
+        room_id, serde::Raw, user_id, EventId,
+    };
 
     use super::*;
 
@@ -396,4 +503,34 @@ mod tests {
             (eid2.to_owned(), Position::new(ChunkIdentifier::new(43), 0))
         );
     }
+
+    #[test]
+    fn test_merge_duplicate_event() {
+        let f = EventFactory::new().room(room_id!("!r:x.y")).sender(user_id!("@mnt_io:matrix.org"));
+        let event_id = owned_event_id!("$ev0");
+
+        let edit = f
+            .text_msg("* hello")
+            .edit(&event_id, RoomMessageEventContentWithoutRelation::text_plain("hello"))
+            .event_id(event_id!("$edit"))
+            .into_raw_sync();
+
+        let plain = f.text_msg("hallo").event_id(&event_id).server_ts(1000).into_event();
+        let with_edit = f
+            .text_msg("hallo")
+            .event_id(&event_id)
+            .server_ts(1000)
+            .with_bundled_edit(edit)
+            .into_event();
+
+        // Identical copies, nothing to merge.
+        assert!(merge_duplicate_event(&plain, plain.clone()).is_none());
+
+        // The new copy has a bundled edit, it's richer.
+        let merged = merge_duplicate_event(&plain, with_edit.clone()).unwrap();
+        assert!(merged.raw().get_field::<JsonValue>("unsigned").unwrap().is_some());
+
+        // The new copy lost the bundled edit: the known copy is already the richest.
+        assert!(merge_duplicate_event(&with_edit, plain).is_none());
+    }
 }
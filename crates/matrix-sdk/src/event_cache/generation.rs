@@ -0,0 +1,127 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tracking of the generations of the event cache, i.e. a cheap way to know
+//! whether new events have been received in some rooms since a given point in
+//! time.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex as StdMutex},
+};
+
+use ruma::{OwnedRoomId, RoomId};
+
+/// An opaque token representing the state of the [`EventCache`] at a given
+/// point in time.
+///
+/// It can be obtained with [`EventCache::generation`], and later passed to
+/// [`EventCache::has_new_events_since`] to know whether new events have been
+/// received since then.
+///
+/// Tokens are only meaningful for the [`EventCache`] instance which created
+/// them; they are neither persisted nor shared across processes.
+///
+/// [`EventCache`]: super::EventCache
+/// [`EventCache::generation`]: super::EventCache::generation
+/// [`EventCache::has_new_events_since`]: super::EventCache::has_new_events_since
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EventCacheGeneration(u64);
+
+/// A tracker of the generation at which each room has last received new
+/// events, shared by all the [`RoomEventCache`]s.
+///
+/// [`RoomEventCache`]: super::RoomEventCache
+#[derive(Clone, Debug, Default)]
+pub(super) struct GenerationTracker {
+    inner: Arc<StdMutex<GenerationTrackerInner>>,
+}
+
+#[derive(Debug, Default)]
+struct GenerationTrackerInner {
+    /// The current generation, incremented every time a room receives new
+    /// events.
+    current: u64,
+
+    /// The generation at which each room has last received new events.
+    by_room: HashMap<OwnedRoomId, u64>,
+}
+
+impl GenerationTracker {
+    /// Get the current generation.
+    pub fn current(&self) -> EventCacheGeneration {
+        EventCacheGeneration(self.inner.lock().unwrap().current)
+    }
+
+    /// Record that new events have been received in the given room.
+    pub fn bump(&self, room_id: &RoomId) {
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.current += 1;
+        let current = inner.current;
+        inner.by_room.insert(room_id.to_owned(), current);
+    }
+
+    /// Whether any of the given rooms has received new events since the given
+    /// generation.
+    pub fn has_new_events_since<'a>(
+        &self,
+        since: EventCacheGeneration,
+        room_ids: impl IntoIterator<Item = &'a RoomId>,
+    ) -> bool {
+        let inner = self.inner.lock().unwrap();
+
+        // Fast path: nothing happened at all.
+        if inner.current <= since.0 {
+            return false;
+        }
+
+        room_ids.into_iter().any(|room_id| {
+            inner.by_room.get(room_id).is_some_and(|generation| *generation > since.0)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::room_id;
+
+    use super::GenerationTracker;
+
+    #[test]
+    fn test_has_new_events_since() {
+        let tracker = GenerationTracker::default();
+        let room_id_0 = room_id!("!r0:matrix.org");
+        let room_id_1 = room_id!("!r1:matrix.org");
+
+        let initial = tracker.current();
+        assert!(!tracker.has_new_events_since(initial, [room_id_0, room_id_1]));
+
+        tracker.bump(room_id_0);
+
+        assert!(tracker.current() > initial);
+        assert!(tracker.has_new_events_since(initial, [room_id_0]));
+        assert!(tracker.has_new_events_since(initial, [room_id_0, room_id_1]));
+        assert!(!tracker.has_new_events_since(initial, [room_id_1]));
+
+        // Once a new token has been obtained, older events aren't reported anymore.
+        let after_room_0 = tracker.current();
+        assert!(!tracker.has_new_events_since(after_room_0, [room_id_0, room_id_1]));
+
+        tracker.bump(room_id_1);
+
+        assert!(!tracker.has_new_events_since(after_room_0, [room_id_0]));
+        assert!(tracker.has_new_events_since(after_room_0, [room_id_1]));
+    }
+}
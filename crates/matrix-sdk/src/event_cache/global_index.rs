@@ -0,0 +1,340 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A global index of the events known by the event cache, across all the
+//! rooms.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, RwLock as StdRwLock},
+};
+
+use matrix_sdk_base::{
+    event_cache::{Event, Gap},
+    linked_chunk::Update,
+};
+use ruma::{EventId, OwnedEventId, OwnedRoomId, RoomId};
+use tracing::trace;
+
+/// The maximum number of events the [`GlobalEventIndex`] keeps track of.
+///
+/// When it's reached, the events that have been indexed first are evicted.
+const MAX_INDEXED_EVENTS: usize = 100_000;
+
+/// An index mapping the ID of an event to the room it belongs to, shared by
+/// all the [`RoomEventCache`]s.
+///
+/// It is fed with the linked chunk updates of every room, so it knows about
+/// all the events which have been added to a room's [`RoomEvents`] since the
+/// [`EventCache`] has been created. Events which have only been persisted in a
+/// previous session are indexed once they're loaded again.
+///
+/// The index only stores the room of an event, not its position, since the
+/// position changes whenever events are inserted or removed before it; the
+/// event itself is looked up in its room's event cache.
+///
+/// The index is bounded: once it contains [`MAX_INDEXED_EVENTS`] events, the
+/// oldest indexed ones are evicted first, and can't be found through the
+/// index anymore.
+///
+/// [`RoomEventCache`]: super::RoomEventCache
+/// [`RoomEvents`]: super::room::events::RoomEvents
+/// [`EventCache`]: super::EventCache
+#[derive(Clone, Debug)]
+pub(super) struct GlobalEventIndex {
+    inner: Arc<StdRwLock<GlobalEventIndexInner>>,
+}
+
+impl Default for GlobalEventIndex {
+    fn default() -> Self {
+        Self::with_capacity(MAX_INDEXED_EVENTS)
+    }
+}
+
+/// An indexed event.
+#[derive(Debug)]
+struct IndexedEvent {
+    /// The room the event belongs to.
+    room_id: OwnedRoomId,
+
+    /// When the event has been indexed, to match it with its entry in
+    /// [`GlobalEventIndexInner::insertion_order`].
+    sequence_number: u64,
+}
+
+#[derive(Debug)]
+struct GlobalEventIndexInner {
+    /// The maximum number of indexed events.
+    capacity: usize,
+
+    /// The room of each known event.
+    rooms_by_event: HashMap<OwnedEventId, IndexedEvent>,
+
+    /// The known events of each room, so they can all be removed when the
+    /// room is cleared.
+    events_by_room: HashMap<OwnedRoomId, HashSet<OwnedEventId>>,
+
+    /// The indexed events, oldest first, with their sequence number.
+    ///
+    /// An entry is stale if its sequence number doesn't match the one in
+    /// [`Self::rooms_by_event`] anymore, i.e. the event has been removed or
+    /// indexed again since then.
+    insertion_order: VecDeque<(u64, OwnedEventId)>,
+
+    /// The sequence number of the next indexed event.
+    next_sequence_number: u64,
+}
+
+impl GlobalEventIndexInner {
+    fn insert(&mut self, room_id: &RoomId, event_id: OwnedEventId) {
+        if let Some(indexed) = self.rooms_by_event.get(&event_id) {
+            if indexed.room_id == room_id {
+                // Already indexed.
+                return;
+            }
+
+            let previous_room_id = indexed.room_id.clone();
+            if let Some(events) = self.events_by_room.get_mut(&previous_room_id) {
+                events.remove(&event_id);
+            }
+        }
+
+        let sequence_number = self.next_sequence_number;
+        self.next_sequence_number += 1;
+
+        self.rooms_by_event.insert(
+            event_id.clone(),
+            IndexedEvent { room_id: room_id.to_owned(), sequence_number },
+        );
+        self.events_by_room.entry(room_id.to_owned()).or_default().insert(event_id.clone());
+        self.insertion_order.push_back((sequence_number, event_id));
+
+        self.evict_over_capacity();
+    }
+
+    fn remove(&mut self, event_id: &EventId) {
+        if let Some(indexed) = self.rooms_by_event.remove(event_id) {
+            if let Some(events) = self.events_by_room.get_mut(&indexed.room_id) {
+                events.remove(event_id);
+
+                if events.is_empty() {
+                    self.events_by_room.remove(&indexed.room_id);
+                }
+            }
+        }
+    }
+
+    fn clear_room(&mut self, room_id: &RoomId) {
+        for event_id in self.events_by_room.remove(room_id).unwrap_or_default() {
+            self.rooms_by_event.remove(&event_id);
+        }
+    }
+
+    fn is_stale(&self, sequence_number: u64, event_id: &EventId) -> bool {
+        self.rooms_by_event
+            .get(event_id)
+            .is_none_or(|indexed| indexed.sequence_number != sequence_number)
+    }
+
+    /// Evict the oldest indexed events until the index fits in its capacity.
+    fn evict_over_capacity(&mut self) {
+        let mut num_evicted = 0;
+
+        while self.rooms_by_event.len() > self.capacity {
+            let Some((sequence_number, event_id)) = self.insertion_order.pop_front() else {
+                break;
+            };
+
+            if !self.is_stale(sequence_number, &event_id) {
+                self.remove(&event_id);
+                num_evicted += 1;
+            }
+        }
+
+        if num_evicted > 0 {
+            trace!(num_evicted, "evicted events from the global event index");
+        }
+
+        // Removed and re-indexed events leave stale entries behind; get rid of them
+        // once they outnumber the live ones, so the queue stays bounded too.
+        if self.insertion_order.len() > 2 * self.capacity.max(1) {
+            let mut insertion_order = std::mem::take(&mut self.insertion_order);
+            insertion_order
+                .retain(|(sequence_number, event_id)| !self.is_stale(*sequence_number, event_id));
+            self.insertion_order = insertion_order;
+        }
+    }
+}
+
+impl GlobalEventIndex {
+    /// Create an empty index, which keeps track of at most `capacity` events.
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(StdRwLock::new(GlobalEventIndexInner {
+                capacity,
+                rooms_by_event: HashMap::new(),
+                events_by_room: HashMap::new(),
+                insertion_order: VecDeque::new(),
+                next_sequence_number: 0,
+            })),
+        }
+    }
+
+    /// Index the given events as belonging to the given room.
+    pub fn add_events<'a>(&self, room_id: &RoomId, events: impl IntoIterator<Item = &'a Event>) {
+        let mut inner = self.inner.write().unwrap();
+
+        for event_id in events.into_iter().filter_map(|event| event.event_id()) {
+            inner.insert(room_id, event_id);
+        }
+    }
+
+    /// Update the index with the linked chunk updates of a room.
+    pub fn handle_updates(&self, room_id: &RoomId, updates: &[Update<Event, Gap>]) {
+        let mut inner = self.inner.write().unwrap();
+
+        for update in updates {
+            match update {
+                Update::PushItems { items, .. } => {
+                    for event_id in items.iter().filter_map(|event| event.event_id()) {
+                        inner.insert(room_id, event_id);
+                    }
+                }
+
+                Update::ReplaceItem { item, .. } => {
+                    if let Some(event_id) = item.event_id() {
+                        inner.insert(room_id, event_id);
+                    }
+                }
+
+                Update::Clear => inner.clear_room(room_id),
+
+                // Events are only removed from a linked chunk when they're deduplicated, in
+                // which case they're pushed again in the same room; and removed chunks are
+                // either empty or still in the store. So there's nothing to unindex.
+                Update::NewItemsChunk { .. }
+                | Update::NewGapChunk { .. }
+                | Update::RemoveChunk(_)
+                | Update::RemoveItem { .. }
+                | Update::DetachLastItems { .. }
+                | Update::StartReattachItems
+                | Update::EndReattachItems => {}
+            }
+        }
+    }
+
+    /// Get the room an event belongs to, if it's been indexed.
+    pub fn room_for_event(&self, event_id: &EventId) -> Option<OwnedRoomId> {
+        self.inner
+            .read()
+            .unwrap()
+            .rooms_by_event
+            .get(event_id)
+            .map(|indexed| indexed.room_id.clone())
+    }
+
+    /// Remove an event from the index, because it turned out to be stale.
+    pub fn remove_event(&self, event_id: &EventId) {
+        self.inner.write().unwrap().remove(event_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk_base::linked_chunk::{ChunkIdentifier, Position, Update};
+    use matrix_sdk_test::event_factory::EventFactory;
+    use ruma::{event_id, room_id, user_id};
+
+    use super::GlobalEventIndex;
+
+    #[test]
+    fn test_handle_updates() {
+        let room_id1 = room_id!("!galette:saucisse.bzh");
+        let room_id2 = room_id!("!crepe:saucisse.bzh");
+        let f = EventFactory::new().sender(user_id!("@ben:saucisse.bzh"));
+
+        let index = GlobalEventIndex::default();
+
+        index.handle_updates(
+            room_id1,
+            &[Update::PushItems {
+                at: Position::new(ChunkIdentifier::new(0), 0),
+                items: vec![
+                    f.text_msg("hey").event_id(event_id!("$1")).into_event(),
+                    f.text_msg("you").event_id(event_id!("$2")).into_event(),
+                ],
+            }],
+        );
+        index.add_events(room_id2, &[f.text_msg("bjr").event_id(event_id!("$3")).into_event()]);
+
+        assert_eq!(index.room_for_event(event_id!("$1")).as_deref(), Some(room_id1));
+        assert_eq!(index.room_for_event(event_id!("$2")).as_deref(), Some(room_id1));
+        assert_eq!(index.room_for_event(event_id!("$3")).as_deref(), Some(room_id2));
+        assert!(index.room_for_event(event_id!("$4")).is_none());
+
+        // Removing an item doesn't unindex it, since removed events are pushed again.
+        index.handle_updates(
+            room_id1,
+            &[Update::RemoveItem { at: Position::new(ChunkIdentifier::new(0), 0) }],
+        );
+        assert_eq!(index.room_for_event(event_id!("$1")).as_deref(), Some(room_id1));
+
+        // Removing a stale event works.
+        index.remove_event(event_id!("$1"));
+        assert!(index.room_for_event(event_id!("$1")).is_none());
+
+        // Clearing a room unindexes all its events, but not the ones of other rooms.
+        index.handle_updates(room_id1, &[Update::Clear]);
+        assert!(index.room_for_event(event_id!("$2")).is_none());
+        assert_eq!(index.room_for_event(event_id!("$3")).as_deref(), Some(room_id2));
+    }
+
+    #[test]
+    fn test_evict_oldest_events_over_capacity() {
+        let room_id1 = room_id!("!galette:saucisse.bzh");
+        let room_id2 = room_id!("!crepe:saucisse.bzh");
+        let f = EventFactory::new().sender(user_id!("@ben:saucisse.bzh"));
+
+        let index = GlobalEventIndex::with_capacity(2);
+
+        index.add_events(
+            room_id1,
+            &[
+                f.text_msg("hey").event_id(event_id!("$1")).into_event(),
+                f.text_msg("you").event_id(event_id!("$2")).into_event(),
+            ],
+        );
+
+        // Indexing an event again doesn't count twice.
+        index.add_events(room_id1, &[f.text_msg("hey").event_id(event_id!("$1")).into_event()]);
+        assert_eq!(index.room_for_event(event_id!("$1")).as_deref(), Some(room_id1));
+        assert_eq!(index.room_for_event(event_id!("$2")).as_deref(), Some(room_id1));
+
+        // Going over the capacity evicts the oldest event.
+        index.add_events(room_id2, &[f.text_msg("bjr").event_id(event_id!("$3")).into_event()]);
+        assert!(index.room_for_event(event_id!("$1")).is_none());
+        assert_eq!(index.room_for_event(event_id!("$2")).as_deref(), Some(room_id1));
+        assert_eq!(index.room_for_event(event_id!("$3")).as_deref(), Some(room_id2));
+
+        // Removed events make room for new ones, without evicting anything.
+        index.remove_event(event_id!("$2"));
+        index.add_events(room_id2, &[f.text_msg("slt").event_id(event_id!("$4")).into_event()]);
+        assert_eq!(index.room_for_event(event_id!("$3")).as_deref(), Some(room_id2));
+        assert_eq!(index.room_for_event(event_id!("$4")).as_deref(), Some(room_id2));
+
+        let inner = index.inner.read().unwrap();
+        assert_eq!(inner.rooms_by_event.len(), 2);
+        assert!(!inner.events_by_room.contains_key(room_id1));
+    }
+}
@@ -0,0 +1,64 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Types to import the history of a room from an export file into the event
+//! cache.
+
+use ruma::{events::AnySyncTimelineEvent, serde::Raw, OwnedUserId};
+use serde::Deserialize;
+
+use super::{EventCacheError, Result};
+
+/// The history of a room, as exported in the JSON format of the "export chat"
+/// feature of Matrix clients.
+///
+/// It can be imported in the event cache with
+/// [`RoomEventCache::import_history`](super::RoomEventCache::import_history).
+#[derive(Debug, Deserialize)]
+pub struct RoomHistoryExport {
+    /// The name of the room at the time of the export.
+    #[serde(default)]
+    pub room_name: Option<String>,
+
+    /// The topic of the room at the time of the export.
+    #[serde(default)]
+    pub topic: Option<String>,
+
+    /// The user who exported the history.
+    #[serde(default)]
+    pub exported_by: Option<OwnedUserId>,
+
+    /// The exported events, from the oldest to the most recent.
+    pub messages: Vec<Raw<AnySyncTimelineEvent>>,
+}
+
+impl RoomHistoryExport {
+    /// Parse an export from its JSON representation.
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(EventCacheError::InvalidHistoryExport)
+    }
+}
+
+/// The outcome of [`RoomEventCache::import_history`].
+///
+/// [`RoomEventCache::import_history`]: super::RoomEventCache::import_history
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HistoryImportSummary {
+    /// The number of events which have been inserted in the event cache.
+    pub imported: usize,
+
+    /// The number of events which have been ignored, because they were
+    /// already known, they had no event ID, or they belonged to another room.
+    pub skipped: usize,
+}
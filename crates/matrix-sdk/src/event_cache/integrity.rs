@@ -0,0 +1,124 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Types to check the ordering of the events of a room in the event cache
+//! against the homeserver.
+
+use std::collections::BTreeSet;
+
+use ruma::{EventId, OwnedEventId};
+
+/// The outcome of
+/// [`RoomEventCache::verify_and_heal_ordering`](super::RoomEventCache::verify_and_heal_ordering).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OrderingIntegrityReport {
+    /// The number of events of the homeserver that have been compared with
+    /// the event cache, i.e. the ones between the oldest and the most recent
+    /// events known by both sides.
+    pub checked: usize,
+
+    /// The events returned by the homeserver which were missing from the event
+    /// cache, and have been inserted, from the oldest to the most recent.
+    pub inserted: Vec<OwnedEventId>,
+
+    /// The events of the event cache, in the checked range, which the
+    /// homeserver didn't return. They're kept, since they may still exist,
+    /// e.g. if they have been inserted out of band.
+    pub unconfirmed: Vec<OwnedEventId>,
+
+    /// Whether the events known by both the event cache and the homeserver
+    /// are in the same order. This isn't repaired.
+    pub same_order: bool,
+}
+
+impl OrderingIntegrityReport {
+    /// Whether the event cache was consistent with the homeserver, i.e.
+    /// nothing had to be repaired, all the checked events were confirmed and
+    /// the ordering is the same.
+    pub fn is_consistent(&self) -> bool {
+        self.inserted.is_empty() && self.unconfirmed.is_empty() && self.same_order
+    }
+}
+
+/// The differences between the events of the event cache and the events of
+/// the homeserver, over the same range.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct OrderingDrift {
+    /// The events of the homeserver which aren't in the event cache.
+    pub missing: Vec<OwnedEventId>,
+
+    /// The events of the event cache which aren't on the homeserver.
+    pub phantoms: Vec<OwnedEventId>,
+
+    /// Whether the events present on both sides are in the same order.
+    pub same_order: bool,
+}
+
+/// Compare the events of the event cache with the events of the homeserver,
+/// both ordered from the oldest to the most recent.
+pub(super) fn compare_orderings(cached: &[OwnedEventId], server: &[OwnedEventId]) -> OrderingDrift {
+    let cached_ids: BTreeSet<&EventId> = cached.iter().map(AsRef::as_ref).collect();
+    let server_ids: BTreeSet<&EventId> = server.iter().map(AsRef::as_ref).collect();
+
+    let missing =
+        server.iter().filter(|event_id| !cached_ids.contains(event_id.as_ref())).cloned().collect();
+    let phantoms =
+        cached.iter().filter(|event_id| !server_ids.contains(event_id.as_ref())).cloned().collect();
+
+    let same_order = cached
+        .iter()
+        .filter(|event_id| server_ids.contains(event_id.as_ref()))
+        .eq(server.iter().filter(|event_id| cached_ids.contains(event_id.as_ref())));
+
+    OrderingDrift { missing, phantoms, same_order }
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::{owned_event_id, OwnedEventId};
+
+    use super::{compare_orderings, OrderingDrift};
+
+    fn ids(ids: &[&str]) -> Vec<OwnedEventId> {
+        ids.iter().map(|id| OwnedEventId::try_from(*id).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_compare_same_orderings() {
+        let events = ids(&["$a", "$b", "$c"]);
+
+        assert_eq!(
+            compare_orderings(&events, &events),
+            OrderingDrift { missing: vec![], phantoms: vec![], same_order: true }
+        );
+    }
+
+    #[test]
+    fn test_compare_orderings_with_missing_and_phantom_events() {
+        let drift = compare_orderings(&ids(&["$a", "$phantom", "$c"]), &ids(&["$a", "$b", "$c"]));
+
+        assert_eq!(drift.missing, vec![owned_event_id!("$b")]);
+        assert_eq!(drift.phantoms, vec![owned_event_id!("$phantom")]);
+        assert!(drift.same_order);
+    }
+
+    #[test]
+    fn test_compare_different_orderings() {
+        let drift = compare_orderings(&ids(&["$a", "$c", "$b"]), &ids(&["$a", "$b", "$c"]));
+
+        assert!(drift.missing.is_empty());
+        assert!(drift.phantoms.is_empty());
+        assert!(!drift.same_order);
+    }
+}
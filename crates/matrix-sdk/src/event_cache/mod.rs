@@ -27,40 +27,72 @@
 
 #![forbid(missing_docs)]
 
+#[cfg(feature = "e2e-encryption")]
+use std::collections::BTreeSet;
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap},
     fmt::Debug,
-    sync::{Arc, OnceLock},
+    sync::{Arc, OnceLock, RwLock as StdRwLock, RwLockWriteGuard as StdRwLockWriteGuard},
+    time::Duration,
 };
 
 use eyeball::{SharedObservable, Subscriber};
 use eyeball_im::VectorDiff;
 use futures_util::future::{join_all, try_join_all};
+#[cfg(feature = "e2e-encryption")]
+use futures_util::{pin_mut, StreamExt as _};
 use matrix_sdk_base::{
     deserialized_responses::{AmbiguityChange, TimelineEvent},
-    event_cache::store::{EventCacheStoreError, EventCacheStoreLock},
+    event_cache::store::EventCacheStoreLock,
     linked_chunk::lazy_loader::LazyLoaderError,
     store_locks::LockStoreError,
     sync::RoomUpdates,
     ROOM_VERSION_FALLBACK,
 };
-use matrix_sdk_common::executor::{spawn, JoinHandle};
+use matrix_sdk_common::{
+    executor::{spawn, JoinHandle},
+    sleep::sleep,
+};
 use room::RoomEventCacheState;
-use ruma::{events::AnySyncEphemeralRoomEvent, serde::Raw, OwnedEventId, OwnedRoomId, RoomId};
+use ruma::{
+    events::{room::MediaSource, AnySyncEphemeralRoomEvent},
+    serde::Raw,
+    EventId, OwnedEventId, OwnedRoomId, RoomId,
+};
 use tokio::sync::{
     broadcast::{channel, error::RecvError, Receiver, Sender},
     mpsc, Mutex, RwLock,
 };
+#[cfg(feature = "e2e-encryption")]
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
 use tracing::{debug, error, info, info_span, instrument, trace, warn, Instrument as _, Span};
 
-use crate::{client::WeakClient, Client};
+use self::{generation::GenerationTracker, global_index::GlobalEventIndex};
+use crate::{
+    client::{ShutdownTracker, WeakClient},
+    Client,
+};
 
+mod debug;
 mod deduplicator;
+mod generation;
+mod global_index;
+mod import;
+mod integrity;
+mod out_of_band;
 mod pagination;
+mod priming;
 mod room;
+mod snapshot;
 
+pub use debug::{DebugChunk, DebugChunkContent, DebugEvent, DebugEventOrigin};
+pub use generation::EventCacheGeneration;
+pub use import::{HistoryImportSummary, RoomHistoryExport};
+pub use integrity::OrderingIntegrityReport;
+pub use matrix_sdk_base::event_cache::store::EventCacheStoreError;
 pub use pagination::{RoomPagination, RoomPaginationStatus};
-pub use room::{RoomEventCache, RoomEventCacheSubscriber};
+pub use room::{OutOfBandPlacement, RoomEventCache, RoomEventCacheSubscriber};
+pub use snapshot::RoomEventsSnapshot;
 
 /// An error observed in the [`EventCache`].
 #[derive(thiserror::Error, Debug)]
@@ -109,6 +141,19 @@ pub enum EventCacheError {
         /// A string containing details about the error.
         details: String,
     },
+
+    /// An event without an event ID has been given, where one was required.
+    #[error("the event has no event ID")]
+    MissingEventId,
+
+    /// A room history export couldn't be parsed.
+    #[error("the room history export is invalid: {0}")]
+    InvalidHistoryExport(serde_json::Error),
+
+    /// An error happening when interacting with the state store, which keeps
+    /// track of the events saved by a notification process.
+    #[error(transparent)]
+    StateStore(#[from] matrix_sdk_base::StoreError),
 }
 
 /// A result using the [`EventCacheError`].
@@ -124,6 +169,10 @@ pub struct EventCacheDropHandles {
 
     /// The task used to automatically shrink the linked chunks.
     auto_shrink_linked_chunk_task: JoinHandle<()>,
+
+    /// Task that retries decrypting events when room keys are received.
+    #[cfg(feature = "e2e-encryption")]
+    redecryption_task: JoinHandle<()>,
 }
 
 impl Debug for EventCacheDropHandles {
@@ -137,9 +186,110 @@ impl Drop for EventCacheDropHandles {
         self.listen_updates_task.abort();
         self.ignore_user_list_update_task.abort();
         self.auto_shrink_linked_chunk_task.abort();
+        #[cfg(feature = "e2e-encryption")]
+        self.redecryption_task.abort();
     }
 }
 
+/// Configuration of the [`EventCache`].
+///
+/// It can be changed at any time with [`EventCache::config_mut`].
+#[derive(Clone, Debug, Default)]
+pub struct EventCacheConfig {
+    /// The maximum amount of events a single room may keep in memory.
+    ///
+    /// When a room goes over this limit after a sync, its oldest chunks of
+    /// events are unloaded from memory. They're still kept in the event cache
+    /// store, and they're lazily reloaded when back-paginating. Rooms with
+    /// live subscribers are never unloaded.
+    ///
+    /// Defaults to `None`, i.e. rooms keep all their loaded events in memory.
+    pub room_memory_limit: Option<RoomMemoryLimit>,
+
+    /// The policy deciding which events are pruned from the rooms, in memory
+    /// and in the event cache store.
+    ///
+    /// Defaults to `None`, i.e. events are kept forever.
+    pub retention_policy: Option<RetentionPolicy>,
+
+    /// Whether to back-paginate a room automatically, as the user scrolls
+    /// towards its oldest loaded events.
+    ///
+    /// Defaults to `None`, i.e. rooms are only back-paginated on demand.
+    pub prefetch: Option<PrefetchConfig>,
+
+    /// The minimum number of events to load in memory, from the event cache
+    /// store, when a room gets its first [`RoomEventCacheSubscriber`].
+    ///
+    /// Only the store is used: loading stops at the first gap, or at the start
+    /// of the room.
+    ///
+    /// Defaults to `None`, i.e. the first subscriber only gets the events
+    /// already in memory.
+    pub subscription_preload: Option<usize>,
+
+    /// How long to wait, after the last [`RoomEventCacheSubscriber`] of a room
+    /// is dropped, before unloading the events of the room from memory.
+    ///
+    /// If the room gets a new subscriber in the meantime, its events are kept
+    /// in memory.
+    ///
+    /// Defaults to `None`, i.e. the events are unloaded immediately.
+    pub unload_grace_period: Option<Duration>,
+}
+
+/// A configuration to back-paginate a room automatically, before the user
+/// reaches its oldest loaded event.
+///
+/// Every time a back-pagination requested with [`RoomPagination`] succeeds
+/// without reaching the start of the timeline, the next batch of events is
+/// back-paginated in the background, so that it's ready when the user scrolls
+/// further.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrefetchConfig {
+    /// The number of events to request for each prefetch.
+    pub batch_size: u16,
+}
+
+impl Default for PrefetchConfig {
+    fn default() -> Self {
+        Self { batch_size: 20 }
+    }
+}
+
+/// A policy deciding which events of a room are pruned from the event cache.
+///
+/// The policy is applied to a room when it becomes idle, i.e. when its last
+/// [`RoomEventCacheSubscriber`] is dropped. Events are pruned by whole chunks,
+/// starting from the oldest ones, and the most recent chunk is never pruned.
+///
+/// Only the events before a gap are pruned, and the gap is kept, so that they
+/// can still be back-paginated from the server. The events of a room without
+/// any gap are never pruned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Prune the events older than this duration, as measured by their
+    /// `origin_server_ts`.
+    pub max_age: Option<Duration>,
+
+    /// Prune the events beyond this number of the most recent events of a
+    /// room.
+    pub max_events: Option<usize>,
+}
+
+/// A limit on the amount of events a room keeps in memory.
+///
+/// See [`EventCacheConfig::room_memory_limit`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoomMemoryLimit {
+    /// Keep at most this number of events in memory.
+    MaxEvents(usize),
+
+    /// Keep at most this number of bytes of events in memory, as measured by
+    /// the size of their JSON serialization.
+    MaxBytes(usize),
+}
+
 /// An event cache, providing lots of useful functionality for clients.
 ///
 /// Cloning is shallow, and thus is cheap to do.
@@ -161,20 +311,33 @@ impl EventCache {
     /// Create a new [`EventCache`] for the given client.
     pub(crate) fn new(client: WeakClient, event_cache_store: EventCacheStoreLock) -> Self {
         let (room_event_cache_generic_update_sender, _) = channel(32);
+        let (redacted_media_sender, _) = channel(32);
 
         Self {
             inner: Arc::new(EventCacheInner {
                 client,
                 store: event_cache_store,
                 multiple_room_updates_lock: Default::default(),
+                notification_events_lock: Default::default(),
                 by_room: Default::default(),
                 drop_handles: Default::default(),
                 auto_shrink_sender: Default::default(),
                 room_event_cache_generic_update_sender,
+                redacted_media_sender,
+                config: Default::default(),
+                global_index: Default::default(),
+                generations: Default::default(),
             }),
         }
     }
 
+    /// Get a mutable reference to the configuration of the event cache.
+    ///
+    /// Changes apply to all the rooms, the next time they handle a sync.
+    pub fn config_mut(&self) -> StdRwLockWriteGuard<'_, EventCacheConfig> {
+        self.inner.config.write().unwrap()
+    }
+
     /// Starts subscribing the [`EventCache`] to sync responses, if not done
     /// before.
     ///
@@ -189,11 +352,13 @@ impl EventCache {
             let listen_updates_task = spawn(Self::listen_task(
                 self.inner.clone(),
                 client.subscribe_to_all_room_updates(),
+                client.inner.shutdown.clone(),
             ));
 
             let ignore_user_list_update_task = spawn(Self::ignore_user_list_update_task(
                 self.inner.clone(),
                 client.subscribe_to_ignore_user_list_changes(),
+                client.inner.shutdown.clone(),
             ));
 
             let (auto_shrink_sender, auto_shrink_receiver) = mpsc::channel(32);
@@ -204,12 +369,19 @@ impl EventCache {
             let auto_shrink_linked_chunk_task = spawn(Self::auto_shrink_linked_chunk_task(
                 self.inner.clone(),
                 auto_shrink_receiver,
+                client.inner.shutdown.clone(),
             ));
 
+            #[cfg(feature = "e2e-encryption")]
+            let redecryption_task =
+                spawn(Self::redecryption_task(self.inner.clone(), client.inner.shutdown.clone()));
+
             Arc::new(EventCacheDropHandles {
                 listen_updates_task,
                 ignore_user_list_update_task,
                 auto_shrink_linked_chunk_task,
+                #[cfg(feature = "e2e-encryption")]
+                redecryption_task,
             })
         });
 
@@ -220,12 +392,19 @@ impl EventCache {
     async fn ignore_user_list_update_task(
         inner: Arc<EventCacheInner>,
         mut ignore_user_list_stream: Subscriber<Vec<String>>,
+        shutdown: ShutdownTracker,
     ) {
         let span = info_span!(parent: Span::none(), "ignore_user_list_update_task");
         span.follows_from(Span::current());
 
         async move {
-            while ignore_user_list_stream.next().await.is_some() {
+            let Some(_shutdown_guard) = shutdown.enter() else {
+                return;
+            };
+
+            while let Some(Some(_)) =
+                shutdown.run_until_shut_down(ignore_user_list_stream.next()).await
+            {
                 info!("Received an ignore user list change");
                 if let Err(err) = inner.clear_all_rooms().await {
                     error!("when clearing room storage after ignore user list change: {err}");
@@ -241,10 +420,21 @@ impl EventCache {
     async fn listen_task(
         inner: Arc<EventCacheInner>,
         mut room_updates_feed: Receiver<RoomUpdates>,
+        shutdown: ShutdownTracker,
     ) {
         trace!("Spawning the listen task");
+
+        let Some(_shutdown_guard) = shutdown.enter() else {
+            return;
+        };
+
         loop {
-            match room_updates_feed.recv().await {
+            let Some(updates) = shutdown.run_until_shut_down(room_updates_feed.recv()).await else {
+                info!("Closing the event cache global listen task because the client is shutting down");
+                break;
+            };
+
+            match updates {
                 Ok(updates) => {
                     if let Err(err) = inner.handle_room_updates(updates).await {
                         match err {
@@ -294,50 +484,195 @@ impl EventCache {
     ///   to such notifications that a room may be shrunk. It will attempt an
     ///   auto-shrink, by letting the inner state decide whether this is a good
     ///   time to do so (new subscribers might have spawned in the meanwhile).
+    ///   If there's an [`EventCacheConfig::unload_grace_period`], the attempt
+    ///   is delayed by it, so that a room which is quickly subscribed to again
+    ///   keeps its events in memory. The grace period starts again each time
+    ///   the last subscriber of the room is dropped.
     #[instrument(skip_all)]
     async fn auto_shrink_linked_chunk_task(
         inner: Arc<EventCacheInner>,
         mut rx: mpsc::Receiver<AutoShrinkChannelPayload>,
+        shutdown: ShutdownTracker,
     ) {
-        while let Some(room_id) = rx.recv().await {
+        let Some(_shutdown_guard) = shutdown.enter() else {
+            return;
+        };
+
+        // The pending attempts to shrink a room after its grace period, which are
+        // aborted when this task stops.
+        let mut pending_shrinks = PendingShrinks::default();
+
+        while let Some(Some(room_id)) = shutdown.run_until_shut_down(rx.recv()).await {
             trace!(for_room = %room_id, "received notification to shrink");
 
-            let room = match inner.for_room(&room_id).await {
-                Ok(room) => room,
-                Err(err) => {
-                    warn!(for_room = %room_id, "error when getting a RoomEventCache: {err}");
-                    continue;
+            let grace_period = inner.config.read().unwrap().unload_grace_period;
+
+            match grace_period {
+                Some(grace_period) => {
+                    // Don't hold the event cache alive while waiting: it may be dropped in the
+                    // meantime.
+                    let weak_inner = Arc::downgrade(&inner);
+                    let Some(shutdown_guard) = shutdown.enter() else {
+                        break;
+                    };
+                    let shutdown = shutdown.clone();
+                    let shrunk_room_id = room_id.clone();
+
+                    let task = spawn(async move {
+                        let _shutdown_guard = shutdown_guard;
+
+                        if shutdown.run_until_shut_down(sleep(grace_period)).await.is_none() {
+                            return;
+                        }
+
+                        if let Some(inner) = weak_inner.upgrade() {
+                            Self::auto_shrink_room(&inner, &shrunk_room_id).await;
+                        }
+                    });
+
+                    // The room has been subscribed to, and dropped again, since the previous
+                    // attempt was scheduled: its grace period starts over.
+                    pending_shrinks.replace(room_id, task);
                 }
-            };
 
-            trace!("waiting for state lock…");
-            let mut state = room.inner.state.write().await;
-
-            match state.auto_shrink_if_no_subscribers().await {
-                Ok(diffs) => {
-                    if let Some(diffs) = diffs {
-                        // Hey, fun stuff: we shrunk the linked chunk, so there shouldn't be any
-                        // subscribers, right? RIGHT? Especially because the state is guarded behind
-                        // a lock.
-                        //
-                        // However, better safe than sorry, and it's cheap to send an update here,
-                        // so let's do it!
-                        if !diffs.is_empty() {
-                            let _ = room.inner.sender.send(
-                                RoomEventCacheUpdate::UpdateTimelineEvents {
-                                    diffs,
-                                    origin: EventsOrigin::Cache,
-                                },
-                            );
+                None => Self::auto_shrink_room(&inner, &room_id).await,
+            }
+        }
+    }
+
+    /// Shrink the given room if it doesn't have any subscriber anymore, after
+    /// having applied the retention policy to it.
+    async fn auto_shrink_room(inner: &Arc<EventCacheInner>, room_id: &RoomId) {
+        let room = match inner.for_room(room_id).await {
+            Ok(room) => room,
+            Err(err) => {
+                warn!(for_room = %room_id, "error when getting a RoomEventCache: {err}");
+                return;
+            }
+        };
+
+        match room.inner.apply_retention_policy().await {
+            Ok(true) => debug!(for_room = %room_id, "pruned events per the retention policy"),
+            Ok(false) => {}
+            Err(err) => {
+                warn!(for_room = %room_id, "error when applying the retention policy: {err}");
+            }
+        }
+
+        trace!("waiting for state lock…");
+        let mut state = room.inner.state.write().await;
+
+        match state.auto_shrink_if_no_subscribers().await {
+            Ok(diffs) => {
+                if let Some(diffs) = diffs {
+                    // Hey, fun stuff: we shrunk the linked chunk, so there shouldn't be any
+                    // subscribers, right? RIGHT? Especially because the state is guarded behind
+                    // a lock.
+                    //
+                    // However, better safe than sorry, and it's cheap to send an update here,
+                    // so let's do it!
+                    if !diffs.is_empty() {
+                        let _ =
+                            room.inner.sender.send(RoomEventCacheUpdate::UpdateTimelineEvents {
+                                diffs,
+                                origin: EventsOrigin::Cache,
+                            });
+                    }
+                } else {
+                    debug!("auto-shrinking didn't happen");
+                }
+            }
+
+            Err(err) => {
+                // There's not much we can do here, unfortunately.
+                warn!(for_room = %room_id, "error when attempting to shrink linked chunk: {err}");
+            }
+        }
+
+        room.inner.semantic_updates.flush();
+    }
+
+    /// Spawns the task that will retry decrypting the events of the loaded
+    /// rooms, whenever room keys are received.
+    #[cfg(feature = "e2e-encryption")]
+    #[instrument(skip_all)]
+    async fn redecryption_task(inner: Arc<EventCacheInner>, shutdown: ShutdownTracker) {
+        let Some(_shutdown_guard) = shutdown.enter() else {
+            return;
+        };
+
+        let room_keys_stream = match inner.client() {
+            Ok(client) => client.encryption().room_keys_received_stream().await,
+            Err(_) => return,
+        };
+
+        let Some(room_keys_stream) = room_keys_stream else {
+            debug!("no olm machine, not listening to the received room keys");
+            return;
+        };
+
+        pin_mut!(room_keys_stream);
+
+        while let Some(Some(room_keys)) =
+            shutdown.run_until_shut_down(room_keys_stream.next()).await
+        {
+            let room_keys = match room_keys {
+                Ok(room_keys) => room_keys,
+                Err(BroadcastStreamRecvError::Lagged(num_skipped)) => {
+                    // The sessions of the missed room keys are unknown, so retry decrypting all
+                    // the events of the loaded rooms.
+                    warn!(num_skipped, "lagged behind the received room keys, retrying all events");
+
+                    let rooms = inner
+                        .by_room
+                        .read()
+                        .await
+                        .iter()
+                        .map(|(room_id, room)| (room_id.clone(), room.clone()))
+                        .collect::<Vec<_>>();
+
+                    for (room_id, room) in rooms {
+                        match room.retry_decryption_of_all_events().await {
+                            Ok(0) => {}
+                            Ok(num_decrypted) => {
+                                debug!(for_room = %room_id, num_decrypted, "decrypted events");
+                            }
+                            Err(err) => {
+                                warn!(
+                                    for_room = %room_id,
+                                    "error when retrying to decrypt events: {err}"
+                                );
+                            }
                         }
-                    } else {
-                        debug!("auto-shrinking didn't happen");
                     }
+
+                    continue;
                 }
+            };
+
+            let mut session_ids_by_room = BTreeMap::<OwnedRoomId, BTreeSet<String>>::new();
+
+            for room_key in room_keys {
+                session_ids_by_room
+                    .entry(room_key.room_id)
+                    .or_default()
+                    .insert(room_key.session_id);
+            }
+
+            for (room_id, session_ids) in session_ids_by_room {
+                // Only the rooms that have been loaded may have events to decrypt.
+                let Some(room) = inner.by_room.read().await.get(&room_id).cloned() else {
+                    continue;
+                };
 
-                Err(err) => {
-                    // There's not much we can do here, unfortunately.
-                    warn!(for_room = %room_id, "error when attempting to shrink linked chunk: {err}");
+                match room.retry_decryption(&session_ids).await {
+                    Ok(0) => {}
+                    Ok(num_decrypted) => {
+                        debug!(for_room = %room_id, num_decrypted, "decrypted events");
+                    }
+                    Err(err) => {
+                        warn!(for_room = %room_id, "error when retrying to decrypt events: {err}");
+                    }
                 }
             }
         }
@@ -357,6 +692,68 @@ impl EventCache {
         Ok((room, drop_handles))
     }
 
+    /// Try to find an event by its ID, in any room.
+    ///
+    /// This relies on a bounded index of the most recent events the event
+    /// cache has seen since it's been created, so it doesn't hit the server,
+    /// nor does it need to know the room of the event beforehand. This is
+    /// useful to resolve links to an event, for instance.
+    ///
+    /// Returns the ID of the room the event belongs to, along with the event,
+    /// or `None` if the event isn't known to the event cache.
+    pub async fn find_event_anywhere(
+        &self,
+        event_id: &EventId,
+    ) -> Result<Option<(OwnedRoomId, TimelineEvent)>> {
+        let Some(room_id) = self.inner.global_index.room_for_event(event_id) else {
+            return Ok(None);
+        };
+
+        let (room_event_cache, _drop_handles) = self.for_room(&room_id).await?;
+
+        let Some(event) = room_event_cache.event(event_id).await else {
+            // The event isn't in its room anymore, it must have been removed in the
+            // meanwhile.
+            self.inner.global_index.remove_event(event_id);
+            return Ok(None);
+        };
+
+        Ok(Some((room_id, event)))
+    }
+
+    /// Save an event fetched by a notification process, e.g. an iOS
+    /// Notification Service Extension, so that the event cache of the main
+    /// process can be primed with it.
+    ///
+    /// The event is saved in the event cache store, which must be shared by
+    /// both processes. The next time the main process loads the room, the
+    /// event is inserted among the room's events, according to its
+    /// `origin_server_ts`, like an event [obtained out of
+    /// band](RoomEventCache::insert_out_of_band_event): the room can then be
+    /// displayed with the event right away, e.g. when the notification is
+    /// tapped, before the next sync. The copy of the event is replaced once
+    /// the sync brings the event, along with the gap before it, if any.
+    ///
+    /// This doesn't need [`EventCache::subscribe`] to have been called, so it
+    /// can be used by a notification process which doesn't sync.
+    pub async fn save_notification_event(
+        &self,
+        room_id: &RoomId,
+        event: TimelineEvent,
+    ) -> Result<()> {
+        let event_id = event.event_id().ok_or(EventCacheError::MissingEventId)?;
+        let client = self.inner.client()?;
+
+        let _notification_events_guard = self.inner.notification_events_lock.lock().await;
+
+        // Keep the cross-process lock while updating the list, so the main process
+        // doesn't take it in the meantime.
+        let store = self.inner.store.lock().await?;
+        store.save_event(room_id, event).await?;
+
+        priming::record_notification_event(client.state_store(), room_id, event_id).await
+    }
+
     /// Cleanly clear all the rooms' event caches.
     ///
     /// This will notify any live observers that the room has been cleared.
@@ -364,6 +761,21 @@ impl EventCache {
         self.inner.clear_all_rooms().await
     }
 
+    /// Wait for all the pending updates of the rooms' event caches to be
+    /// written to the store.
+    ///
+    /// Every update is persisted while holding the state lock of its room, so
+    /// taking each of them once is enough to make sure none is in flight.
+    pub(crate) async fn flush(&self) {
+        let _multiple_room_updates_guard = self.inner.multiple_room_updates_lock.lock().await;
+
+        let rooms = self.inner.by_room.read().await.values().cloned().collect::<Vec<_>>();
+
+        for room in rooms {
+            drop(room.inner.state.write().await);
+        }
+    }
+
     /// Subscribe to room _generic_ updates.
     ///
     /// If one wants to listen what has changed in a specific room, the
@@ -378,6 +790,40 @@ impl EventCache {
     pub fn subscribe_to_room_generic_updates(&self) -> Receiver<RoomEventCacheGenericUpdate> {
         self.inner.room_event_cache_generic_update_sender.subscribe()
     }
+
+    /// Subscribe to the media of the events redacted in any room.
+    ///
+    /// When the event cache applies a redaction to an event with a media, the
+    /// media and its thumbnail are removed from the media cache of the client,
+    /// and a [`RedactedMedia`] is sent, once per redacted event, so the
+    /// copies of the media kept outside of the SDK can be removed too.
+    pub fn subscribe_to_redacted_media(&self) -> Receiver<RedactedMedia> {
+        self.inner.redacted_media_sender.subscribe()
+    }
+
+    /// Get a token representing the current state of the event cache.
+    ///
+    /// It can later be passed to [`EventCache::has_new_events_since`] to know
+    /// whether new events have been received since then.
+    pub fn generation(&self) -> EventCacheGeneration {
+        self.inner.generations.current()
+    }
+
+    /// Whether any of the given rooms has received new events from the sync,
+    /// since the given token has been obtained with
+    /// [`EventCache::generation`].
+    ///
+    /// This is cheap: it doesn't load any event nor compute any diff, and is
+    /// thus suited for background tasks deciding whether they have work to do.
+    /// Events loaded from the storage or from back-paginations aren't
+    /// considered new.
+    pub fn has_new_events_since<'a>(
+        &self,
+        since: EventCacheGeneration,
+        room_ids: impl IntoIterator<Item = &'a RoomId>,
+    ) -> bool {
+        self.inner.generations.has_new_events_since(since, room_ids)
+    }
 }
 
 struct EventCacheInner {
@@ -396,6 +842,14 @@ struct EventCacheInner {
     // TODO: that's the place to add a cross-process lock!
     multiple_room_updates_lock: Mutex<()>,
 
+    /// A lock used when updating the list of the events saved by a notification
+    /// process, see [`EventCache::save_notification_event`].
+    ///
+    /// The list is shared by the processes, so it's updated while holding the
+    /// cross-process lock of the store too. This one serializes the updates
+    /// within this process, since the cross-process lock is reentrant.
+    notification_events_lock: Mutex<()>,
+
     /// Lazily-filled cache of live [`RoomEventCache`], once per room.
     by_room: RwLock<BTreeMap<OwnedRoomId, RoomEventCache>>,
 
@@ -415,10 +869,55 @@ struct EventCacheInner {
     /// See doc comment of [`RoomEventCacheGenericUpdate`] and
     /// [`EventCache::subscribe_to_room_generic_updates`].
     room_event_cache_generic_update_sender: Sender<RoomEventCacheGenericUpdate>,
+
+    /// A sender for the media of the redacted events, shared with each
+    /// [`RoomEventCache`].
+    ///
+    /// See [`EventCache::subscribe_to_redacted_media`].
+    redacted_media_sender: Sender<RedactedMedia>,
+
+    /// The configuration of the event cache, shared with each
+    /// [`RoomEventCache`].
+    config: Arc<StdRwLock<EventCacheConfig>>,
+
+    /// The index of all the events known to the event cache, across rooms,
+    /// shared with each [`RoomEventCache`].
+    global_index: GlobalEventIndex,
+
+    /// The tracker of the generations at which rooms have received new events,
+    /// shared with each [`RoomEventCache`].
+    generations: GenerationTracker,
 }
 
 type AutoShrinkChannelPayload = OwnedRoomId;
 
+/// The attempts to shrink rooms once their grace period is over, by room.
+///
+/// They're aborted when they're replaced, or when this is dropped.
+#[derive(Default)]
+struct PendingShrinks(HashMap<OwnedRoomId, JoinHandle<()>>);
+
+impl PendingShrinks {
+    /// Replace the pending attempt to shrink the given room, aborting the
+    /// previous one, if any.
+    fn replace(&mut self, room_id: OwnedRoomId, task: JoinHandle<()>) {
+        // Forget about the attempts which are over.
+        self.0.retain(|_, task| !task.is_finished());
+
+        if let Some(previous_task) = self.0.insert(room_id, task) {
+            previous_task.abort();
+        }
+    }
+}
+
+impl Drop for PendingShrinks {
+    fn drop(&mut self) {
+        for task in self.0.values() {
+            task.abort();
+        }
+    }
+}
+
 impl EventCacheInner {
     fn client(&self) -> Result<Client> {
         self.client.get().ok_or(EventCacheError::ClientDropped)
@@ -486,6 +985,7 @@ impl EventCacheInner {
                 diffs: updates_as_vector_diffs,
                 origin: EventsOrigin::Cache,
             });
+            room.inner.semantic_updates.flush();
             Ok::<_, EventCacheError>(())
         }))
         .await?;
@@ -563,14 +1063,47 @@ impl EventCacheInner {
                         ROOM_VERSION_FALLBACK
                     });
 
-                let room_state = RoomEventCacheState::new(
+                let own_user_id =
+                    self.client.get().and_then(|client| client.user_id().map(ToOwned::to_owned));
+
+                let mut room_state = RoomEventCacheState::new(
+                    self.client.clone(),
                     room_id.to_owned(),
                     room_version,
+                    own_user_id,
                     self.store.clone(),
                     pagination_status.clone(),
+                    self.config.clone(),
+                    self.global_index.clone(),
+                    self.redacted_media_sender.clone(),
                 )
                 .await?;
 
+                // Prime the room with the events saved by a notification process, if any.
+                if let Some(client) = self.client.get() {
+                    let event_ids = {
+                        // Hold the same locks as the notification process when it adds an event.
+                        let _notification_events_guard = self.notification_events_lock.lock().await;
+                        let _store_guard = self.store.lock().await?;
+
+                        priming::take_notification_events(client.state_store(), room_id).await
+                    };
+
+                    match event_ids {
+                        Ok(event_ids) => {
+                            let primed = room_state.prime_with_events(event_ids).await?;
+                            if primed > 0 {
+                                debug!(%room_id, primed, "primed with notification events");
+                            }
+                        }
+                        Err(err) => {
+                            warn!(%room_id, "couldn't read the notification events: {err}");
+                        }
+                    }
+                }
+
+                room_state.restore_pending_out_of_band_events().await?;
+
                 let timeline_is_not_empty = room_state.events().revents().next().is_some();
 
                 // SAFETY: we must have subscribed before reaching this code, otherwise
@@ -587,6 +1120,7 @@ impl EventCacheInner {
                     room_id.to_owned(),
                     auto_shrink_sender,
                     self.room_event_cache_generic_update_sender.clone(),
+                    self.generations.clone(),
                 );
 
                 by_room_guard.insert(room_id.to_owned(), room_event_cache.clone());
@@ -613,6 +1147,15 @@ pub struct BackPaginationOutcome {
     /// Did the back-pagination reach the start of the timeline?
     pub reached_start: bool,
 
+    /// Did the back-pagination stop because the server refused to return
+    /// older events?
+    ///
+    /// This happens when the server has purged older history, or when the
+    /// history visibility settings of the room prevent the user from reading
+    /// events from before they joined. In this case, `reached_start` is also
+    /// set to `true`, as back-paginating further wouldn't have any effect.
+    pub history_unavailable: bool,
+
     /// All the events that have been returned in the back-pagination
     /// request.
     ///
@@ -622,6 +1165,23 @@ pub struct BackPaginationOutcome {
     pub events: Vec<TimelineEvent>,
 }
 
+/// The result of a single back-pagination request in a thread.
+///
+/// See [`RoomEventCache::paginate_thread_backwards`].
+#[derive(Debug)]
+pub struct ThreadBackPaginationOutcome {
+    /// The token to continue back-paginating the thread from, or `None` if the
+    /// start of the thread has been reached.
+    pub prev_token: Option<String>,
+
+    /// All the in-thread events that have been returned in the
+    /// back-pagination request.
+    ///
+    /// Events are presented in reverse order: the first element of the vec,
+    /// if present, is the most "recent" event of the thread.
+    pub events: Vec<TimelineEvent>,
+}
+
 /// Represents an update of a room. It hides the details of
 /// [`RoomEventCacheUpdate`] by being more generic.
 ///
@@ -637,6 +1197,20 @@ pub enum RoomEventCacheGenericUpdate {
     },
 }
 
+/// The media of an event which has been redacted, as sent to the subscribers
+/// of [`EventCache::subscribe_to_redacted_media`].
+#[derive(Clone, Debug)]
+pub struct RedactedMedia {
+    /// The ID of the room of the redacted event.
+    pub room_id: OwnedRoomId,
+
+    /// The ID of the redacted event.
+    pub event_id: OwnedEventId,
+
+    /// The sources of the media of the event, and of its thumbnail, if any.
+    pub sources: Vec<MediaSource>,
+}
+
 /// An update related to events happened in a room.
 #[derive(Debug, Clone)]
 pub enum RoomEventCacheUpdate {
@@ -672,6 +1246,60 @@ pub enum RoomEventCacheUpdate {
     },
 }
 
+/// A semantic description of a change to the events of a [`RoomEventCache`].
+///
+/// [`RoomEventCacheUpdate::UpdateTimelineEvents`] describes *how* the events
+/// have changed, as a list of [`VectorDiff`]s; this describes *why* they
+/// changed, so that observers can, for instance, animate the new events
+/// differently when they've been back-paginated or received from a sync.
+///
+/// Those updates are sent right after the matching [`VectorDiff`]s. See
+/// [`RoomEventCache::updates_as_semantic_events`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum RoomEventCacheSemanticUpdate {
+    /// New events have been received from a sync, and appended at the end of
+    /// the timeline.
+    LiveAppended {
+        /// The number of events that have been appended.
+        count: usize,
+    },
+
+    /// Older events have been back-paginated, from the network or the store,
+    /// and inserted at the start of the timeline.
+    PaginatedBackwards {
+        /// The number of events that have been inserted.
+        count: usize,
+    },
+
+    /// An event of the timeline has been redacted.
+    Redacted {
+        /// The ID of the redacted event.
+        event_id: OwnedEventId,
+    },
+
+    /// An event of the timeline has been replaced in place, e.g. because its
+    /// thread summary has changed.
+    Replaced {
+        /// The ID of the replaced event.
+        event_id: OwnedEventId,
+    },
+
+    /// An event of the timeline which couldn't be decrypted has been replaced
+    /// in place by its decrypted form, after its room key has been received.
+    Decrypted {
+        /// The ID of the decrypted event.
+        event_id: OwnedEventId,
+    },
+
+    /// The oldest events have been unloaded from memory; they can be
+    /// back-paginated again.
+    Unloaded,
+
+    /// The timeline has been reset: it has been either cleared, or shrunk to
+    /// its most recent events.
+    Reset,
+}
+
 /// Indicate where events are coming from.
 #[derive(Debug, Clone)]
 pub enum EventsOrigin {
@@ -683,6 +1311,10 @@ pub enum EventsOrigin {
 
     /// The cause of the change is purely internal to the cache.
     Cache,
+
+    /// Events have been imported from a room history export; they haven't been
+    /// received from the homeserver.
+    Import,
 }
 
 #[cfg(test)]
@@ -700,7 +1332,10 @@ mod tests {
     use ruma::{event_id, room_id, serde::Raw, user_id};
     use serde_json::json;
 
-    use super::{EventCacheError, RoomEventCacheGenericUpdate, RoomEventCacheUpdate};
+    use super::{
+        priming, room, DebugChunkContent, EventCacheError, RoomEventCacheGenericUpdate,
+        RoomEventCacheUpdate,
+    };
     use crate::test_utils::{assert_event_matches_msg, logged_in_client};
 
     #[async_test]
@@ -826,6 +1461,65 @@ mod tests {
         assert!(room_event_cache.event(eid3).await.is_none());
     }
 
+    #[async_test]
+    async fn test_find_event_anywhere() {
+        let client = logged_in_client(None).await;
+        let room_id1 = room_id!("!galette:saucisse.bzh");
+        let room_id2 = room_id!("!crepe:saucisse.bzh");
+
+        let event_cache = client.event_cache();
+        event_cache.subscribe().unwrap();
+
+        let f = EventFactory::new().sender(user_id!("@ben:saucisse.bzh"));
+
+        let eid1 = event_id!("$1");
+        let eid2 = event_id!("$2");
+        let eid3 = event_id!("$3");
+
+        let joined_room_update1 = JoinedRoomUpdate {
+            timeline: Timeline {
+                events: vec![f.text_msg("hey").room(room_id1).event_id(eid1).into()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let joined_room_update2 = JoinedRoomUpdate {
+            timeline: Timeline {
+                events: vec![f.text_msg("bjr").room(room_id2).event_id(eid2).into()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut updates = RoomUpdates::default();
+        updates.joined.insert(room_id1.to_owned(), joined_room_update1);
+        updates.joined.insert(room_id2.to_owned(), joined_room_update2);
+
+        event_cache.inner.handle_room_updates(updates).await.unwrap();
+
+        // The events are found without knowing their room.
+        let (room_id, event) = event_cache.find_event_anywhere(eid1).await.unwrap().unwrap();
+        assert_eq!(room_id, room_id1);
+        assert_event_matches_msg(&event, "hey");
+
+        let (room_id, event) = event_cache.find_event_anywhere(eid2).await.unwrap().unwrap();
+        assert_eq!(room_id, room_id2);
+        assert_event_matches_msg(&event, "bjr");
+
+        // An unknown event isn't found.
+        assert!(event_cache.find_event_anywhere(eid3).await.unwrap().is_none());
+
+        // Once a room is cleared, its events can't be found anymore.
+        client.base_client().get_or_create_room(room_id1, RoomState::Joined);
+        let room1 = client.get_room(room_id1).unwrap();
+        let (room_event_cache, _drop_handles) = room1.event_cache().await.unwrap();
+        room_event_cache.clear().await.unwrap();
+
+        assert!(event_cache.find_event_anywhere(eid1).await.unwrap().is_none());
+        assert!(event_cache.find_event_anywhere(eid2).await.unwrap().is_some());
+    }
+
     #[async_test]
     async fn test_save_event() {
         let client = logged_in_client(None).await;
@@ -847,6 +1541,48 @@ mod tests {
         assert!(room_event_cache.event(event_id).await.is_some());
     }
 
+    #[async_test]
+    async fn test_prime_with_notification_events() {
+        let client = logged_in_client(None).await;
+        let room_id = room_id!("!galette:saucisse.bzh");
+
+        let event_cache = client.event_cache();
+        let f = EventFactory::new().room(room_id).sender(user_id!("@ben:saucisse.bzh"));
+
+        // A notification process saves an event, without subscribing the event cache.
+        event_cache
+            .save_notification_event(
+                room_id,
+                f.text_msg("hey there").event_id(event_id!("$1")).into(),
+            )
+            .await
+            .unwrap();
+
+        // The room is primed with the event when the main process loads it.
+        event_cache.subscribe().unwrap();
+
+        client.base_client().get_or_create_room(room_id, RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+        let events = room_event_cache.events().await;
+        assert_eq!(events.len(), 1);
+        assert_event_matches_msg(&events[0], "hey there");
+
+        // The event is preceded by a gap, since the history before it is unknown.
+        let chunks = room_event_cache.debug_chunks().await;
+        assert_eq!(chunks.len(), 2);
+        assert_matches!(&chunks[0].content, DebugChunkContent::Gap { prev_token } => {
+            assert!(room::is_primed_gap_token(prev_token));
+        });
+        assert_matches!(&chunks[1].content, DebugChunkContent::Events(_));
+
+        // The room isn't primed again.
+        let event_ids =
+            priming::take_notification_events(client.state_store(), room_id).await.unwrap();
+        assert!(event_ids.is_empty());
+    }
+
     #[async_test]
     async fn test_generic_update_when_loading_rooms() {
         // Create 2 rooms. One of them has data in the event cache storage.
@@ -0,0 +1,85 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bookkeeping of the out-of-band events whose position isn't known yet, see
+//! [`RoomEventCache::place_out_of_band_event`].
+//!
+//! The events themselves are saved in the event cache store. Their IDs are
+//! listed, per room, in a custom value of the state store, so the pending
+//! events are restored when the room is loaded again.
+//!
+//! [`RoomEventCache::place_out_of_band_event`]: super::RoomEventCache::place_out_of_band_event
+
+use std::collections::BTreeMap;
+
+use matrix_sdk_base::store::DynStateStore;
+use ruma::{OwnedEventId, OwnedRoomId, RoomId};
+
+use super::Result;
+
+/// The key of the custom value of the state store listing the pending
+/// out-of-band events.
+const PENDING_EVENTS_KEY: &[u8] = b"event_cache.pending_out_of_band_events";
+
+/// The maximum number of pending out-of-band events of a single room.
+///
+/// The oldest pending events are dropped beyond that, so the list doesn't grow
+/// forever if their position is never confirmed.
+pub(super) const MAX_PENDING_EVENTS_PER_ROOM: usize = 50;
+
+type PendingEvents = BTreeMap<OwnedRoomId, Vec<OwnedEventId>>;
+
+async fn load(store: &DynStateStore) -> Result<PendingEvents> {
+    let Some(value) = store.get_custom_value(PENDING_EVENTS_KEY).await? else {
+        return Ok(PendingEvents::new());
+    };
+
+    // The list is only a hint, so it's dropped if it can't be read.
+    Ok(serde_json::from_slice(&value).unwrap_or_default())
+}
+
+/// Get the IDs of the pending out-of-band events of the given room, in the
+/// order they were placed.
+pub(super) async fn load_pending_events(
+    store: &DynStateStore,
+    room_id: &RoomId,
+) -> Result<Vec<OwnedEventId>> {
+    Ok(load(store).await?.remove(room_id).unwrap_or_default())
+}
+
+/// Replace the IDs of the pending out-of-band events of the given room.
+pub(super) async fn save_pending_events(
+    store: &DynStateStore,
+    room_id: &RoomId,
+    event_ids: Vec<OwnedEventId>,
+) -> Result<()> {
+    let mut events = load(store).await?;
+
+    if event_ids.is_empty() {
+        if events.remove(room_id).is_none() {
+            return Ok(());
+        }
+    } else {
+        events.insert(room_id.to_owned(), event_ids);
+    }
+
+    if events.is_empty() {
+        store.remove_custom_value(PENDING_EVENTS_KEY).await?;
+    } else {
+        let value = serde_json::to_vec(&events).expect("the event IDs can always be serialized");
+        store.set_custom_value(PENDING_EVENTS_KEY, value).await?;
+    }
+
+    Ok(())
+}
@@ -17,18 +17,30 @@
 use std::{sync::Arc, time::Duration};
 
 use eyeball::{SharedObservable, Subscriber};
+use http::StatusCode;
 use matrix_sdk_base::timeout::timeout;
-use matrix_sdk_common::linked_chunk::ChunkContent;
-use ruma::api::Direction;
+use matrix_sdk_common::{executor::spawn, linked_chunk::ChunkContent};
+use ruma::{
+    api::{
+        client::error::{ErrorBody, ErrorKind},
+        Direction,
+    },
+    uint, OwnedEventId,
+};
 use tracing::{debug, instrument, trace};
 
+#[cfg(doc)]
+use super::EventCacheConfig;
 use super::{
-    room::{events::Gap, LoadMoreEventsBackwardsOutcome, RoomEventCacheInner},
+    room::{
+        events::{Gap, RoomEvents},
+        is_primed_gap_token, LoadMoreEventsBackwardsOutcome, RoomEventCacheInner,
+    },
     BackPaginationOutcome, EventsOrigin, Result, RoomEventCacheUpdate,
 };
 use crate::{
     event_cache::{EventCacheError, RoomEventCacheGenericUpdate},
-    room::MessagesOptions,
+    room::{MessagesOptions, Room},
 };
 
 /// Status for the back-pagination on a room event cache.
@@ -99,8 +111,13 @@ impl RoomPagination {
             if let Some(outcome) = self.run_backwards_impl(num_requested_events).await? {
                 events.extend(outcome.events);
                 if outcome.reached_start || events.len() >= num_requested_events as usize {
+                    if !outcome.reached_start {
+                        self.prefetch().await;
+                    }
+
                     return Ok(BackPaginationOutcome {
                         reached_start: outcome.reached_start,
+                        history_unavailable: outcome.history_unavailable,
                         events,
                     });
                 }
@@ -122,6 +139,10 @@ impl RoomPagination {
     pub async fn run_backwards_once(&self, batch_size: u16) -> Result<BackPaginationOutcome> {
         loop {
             if let Some(outcome) = self.run_backwards_impl(batch_size).await? {
+                if !outcome.reached_start {
+                    self.prefetch().await;
+                }
+
                 return Ok(outcome);
             }
             debug!("restarting back-pagination because of a timeline reset.");
@@ -140,6 +161,21 @@ impl RoomPagination {
             return Err(EventCacheError::AlreadyBackpaginating);
         }
 
+        self.run_backwards_with_status(batch_size, prev_status).await
+    }
+
+    /// Paginate from either the storage or the network, once the pagination
+    /// status has been set to [`RoomPaginationStatus::Paginating`], and let
+    /// pagination status observers know about updates.
+    ///
+    /// `prev_status` is the status to restore if the back-pagination fails.
+    async fn run_backwards_with_status(
+        &self,
+        batch_size: u16,
+        prev_status: RoomPaginationStatus,
+    ) -> Result<Option<BackPaginationOutcome>> {
+        let status_observable = &self.inner.pagination_status;
+
         let reset_status_on_drop_guard = ResetStatusOnDrop {
             prev_status: Some(prev_status),
             pagination_status: status_observable.clone(),
@@ -226,7 +262,11 @@ impl RoomPagination {
                 }
 
                 LoadMoreEventsBackwardsOutcome::StartOfTimeline => {
-                    return Ok(Some(BackPaginationOutcome { reached_start: true, events: vec![] }));
+                    return Ok(Some(BackPaginationOutcome {
+                        reached_start: true,
+                        history_unavailable: false,
+                        events: vec![],
+                    }));
                 }
 
                 LoadMoreEventsBackwardsOutcome::Events {
@@ -241,9 +281,11 @@ impl RoomPagination {
                                 origin: EventsOrigin::Cache,
                             });
                     }
+                    self.inner.semantic_updates.flush();
 
                     return Ok(Some(BackPaginationOutcome {
                         reached_start,
+                        history_unavailable: false,
                         // This is a backwards pagination. `BackPaginationOutcome` expects events to
                         // be in “reverse order”.
                         events: events.into_iter().rev().collect(),
@@ -269,17 +311,86 @@ impl RoomPagination {
                 // The client is shutting down, return an empty default response.
                 return Ok(Some(BackPaginationOutcome {
                     reached_start: false,
+                    history_unavailable: false,
                     events: Default::default(),
                 }));
             };
 
-            let mut options = MessagesOptions::new(Direction::Backward).from(prev_token.as_deref());
-            options.limit = batch_size.into();
+            let mut from = prev_token.clone();
+            let mut reanchored = false;
+
+            if let Some(token) = prev_token.as_deref().filter(|token| is_primed_gap_token(token)) {
+                // The gap has been inserted before a primed event, and the homeserver doesn't
+                // know its token: get one from the event after it right away.
+                from = self.reanchor_gap(&room, token).await;
+                reanchored = true;
+
+                if from.is_none() {
+                    debug!("couldn't get a token for the gap of a primed event, stopping back-pagination");
+                    return Ok(Some(BackPaginationOutcome {
+                        reached_start: true,
+                        history_unavailable: true,
+                        events: Vec::new(),
+                    }));
+                }
+            }
+
+            let response = loop {
+                let mut options = MessagesOptions::new(Direction::Backward).from(from.as_deref());
+                options.limit = batch_size.into();
+
+                match room.messages(options).await {
+                    Ok(response) => break response,
+
+                    Err(err) if is_history_unavailable_error(&err) => {
+                        // The server refuses to give us older events, either because they have
+                        // been purged or because of the room's history visibility. There's
+                        // nothing more to back-paginate: consider we've hit the start of the
+                        // readable history.
+                        //
+                        // The gap is kept in the linked chunk, so that a later back-pagination
+                        // may succeed, if the server's retention policy changes for instance.
+                        debug!("history is unavailable, stopping back-pagination: {err}");
+                        return Ok(Some(BackPaginationOutcome {
+                            reached_start: true,
+                            history_unavailable: true,
+                            events: Vec::new(),
+                        }));
+                    }
+
+                    Err(err) if is_rejected_token_error(&err) => {
+                        let Some(token) = prev_token.as_deref() else {
+                            return Err(EventCacheError::BackpaginationError(Box::new(err)));
+                        };
+
+                        // The server doesn't know about the token anymore, e.g. because it has
+                        // expired: ask for a new one, from the event right after the gap.
+                        let new_token = if reanchored {
+                            None
+                        } else {
+                            debug!("the previous-batch token has been rejected, re-anchoring the gap: {err}");
+                            self.reanchor_gap(&room, token).await
+                        };
+
+                        let Some(new_token) = new_token else {
+                            // The older history can't be reached from this gap. It's kept in the
+                            // linked chunk, so the older events aren't considered missing for
+                            // good.
+                            debug!("couldn't re-anchor the gap, stopping back-pagination");
+                            return Ok(Some(BackPaginationOutcome {
+                                reached_start: true,
+                                history_unavailable: true,
+                                events: Vec::new(),
+                            }));
+                        };
+
+                        from = Some(new_token);
+                        reanchored = true;
+                    }
 
-            let response = room
-                .messages(options)
-                .await
-                .map_err(|err| EventCacheError::BackpaginationError(Box::new(err)))?;
+                    Err(err) => return Err(EventCacheError::BackpaginationError(Box::new(err))),
+                }
+            };
 
             let new_gap = response.end.map(|prev_token| Gap { prev_token });
 
@@ -315,19 +426,159 @@ impl RoomPagination {
         let (outcome, timeline_event_diffs) =
             state.handle_backpagination(events, new_gap, prev_gap_chunk_id).await?;
 
+        // Interrupted back-paginations may have left adjacent gaps behind; keep the
+        // linked chunk compact.
+        state.coalesce_gaps().await?;
+
         if !timeline_event_diffs.is_empty() {
             let _ = self.inner.sender.send(RoomEventCacheUpdate::UpdateTimelineEvents {
                 diffs: timeline_event_diffs,
                 origin: EventsOrigin::Pagination,
             });
         }
+        self.inner.semantic_updates.flush();
 
         Ok(Some(outcome))
     }
 
+    /// Get a new previous-batch token for the gap with the given token, from a
+    /// `/context` request on the event right after the gap.
+    ///
+    /// Returns `None` if the gap isn't followed by a loaded event, or if the
+    /// request failed.
+    async fn reanchor_gap(&self, room: &Room, prev_token: &str) -> Option<String> {
+        let event_id =
+            first_event_id_after_gap(self.inner.state.read().await.events(), prev_token)?;
+
+        match room.event_with_context(&event_id, false, uint!(0), None).await {
+            Ok(response) => response.prev_batch_token,
+            Err(err) => {
+                debug!("couldn't get a new previous-batch token from {event_id}: {err}");
+                None
+            }
+        }
+    }
+
+    /// Back-paginate the next batch of events in the background, if
+    /// [`EventCacheConfig::prefetch`] is set, so that they're ready when the
+    /// user reaches them.
+    ///
+    /// Nothing happens if a back-pagination is already running, or if the
+    /// start of the timeline has been reached.
+    async fn prefetch(&self) {
+        let Some(prefetch) = self.inner.state.read().await.prefetch_config() else {
+            return;
+        };
+
+        // Check and set the status at once, so that a concurrent back-pagination
+        // can't start in between.
+        let mut started = false;
+        self.inner.pagination_status.update_if(|status| {
+            started = matches!(status, RoomPaginationStatus::Idle { hit_timeline_start: false });
+
+            if started {
+                *status = RoomPaginationStatus::Paginating;
+            }
+
+            started
+        });
+
+        if !started {
+            return;
+        }
+
+        let prev_status = RoomPaginationStatus::Idle { hit_timeline_start: false };
+
+        // The back-pagination must be waited for when shutting down the client.
+        let Some(shutdown_guard) =
+            self.inner.weak_room.get().and_then(|room| room.client().inner.shutdown.enter())
+        else {
+            self.inner.pagination_status.set(prev_status);
+            return;
+        };
+
+        trace!("prefetching the next batch of events");
+
+        let pagination = self.clone();
+        spawn(async move {
+            let _shutdown_guard = shutdown_guard;
+
+            if let Err(err) =
+                pagination.run_backwards_with_status(prefetch.batch_size, prev_status).await
+            {
+                debug!("couldn't prefetch events: {err}");
+            }
+        });
+    }
+
     /// Returns a subscriber to the pagination status used for the
     /// back-pagination integrated to the event cache.
     pub fn status(&self) -> Subscriber<RoomPaginationStatus> {
         self.inner.pagination_status.subscribe()
     }
 }
+
+/// Get the ID of the first loaded event after the gap with the given
+/// previous-batch token, if it directly follows the gap.
+fn first_event_id_after_gap(events: &RoomEvents, prev_token: &str) -> Option<OwnedEventId> {
+    let mut chunks = events.chunks().skip_while(
+        |chunk| !matches!(chunk.content(), ChunkContent::Gap(gap) if gap.prev_token == prev_token),
+    );
+
+    // Skip the gap itself.
+    chunks.next()?;
+
+    // Find the first event after it, skipping the empty items chunks.
+    chunks
+        .map_while(|chunk| match chunk.content() {
+            ChunkContent::Items(events) => Some(events.first()),
+            ChunkContent::Gap(_) => None,
+        })
+        .flatten()
+        .next()?
+        .event_id()
+}
+
+/// Whether the error returned by a `/messages` request means that the older
+/// history of the room can't be read by the user.
+///
+/// Servers respond with a 403 `M_FORBIDDEN` or a 404 `M_NOT_FOUND` when the
+/// requested events have been purged by a data retention policy, or when the
+/// room's history visibility doesn't allow the user to see them. Other errors
+/// with the same status codes, like an `M_UNRECOGNIZED` endpoint, aren't about
+/// the history.
+fn is_history_unavailable_error(err: &crate::Error) -> bool {
+    let Some(api_error) = err.as_client_api_error() else {
+        return false;
+    };
+
+    match err.client_api_error_kind() {
+        Some(ErrorKind::Forbidden { .. }) => api_error.status_code == StatusCode::FORBIDDEN,
+        Some(ErrorKind::NotFound) => api_error.status_code == StatusCode::NOT_FOUND,
+        _ => false,
+    }
+}
+
+/// Whether the error returned by a `/messages` request means that the server
+/// rejected the `from` token, e.g. because it has expired.
+///
+/// Servers respond with a 400 `M_INVALID_PARAM`, or `M_UNKNOWN` for Synapse,
+/// with a message about the token. Other errors with the same codes are about
+/// the other parameters of the request.
+fn is_rejected_token_error(err: &crate::Error) -> bool {
+    let Some(api_error) = err.as_client_api_error() else {
+        return false;
+    };
+
+    if api_error.status_code != StatusCode::BAD_REQUEST {
+        return false;
+    }
+
+    let ErrorBody::Standard { kind: ErrorKind::InvalidParam | ErrorKind::Unknown, message } =
+        &api_error.body
+    else {
+        return false;
+    };
+
+    message.to_lowercase().contains("token")
+}
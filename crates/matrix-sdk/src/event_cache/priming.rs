@@ -0,0 +1,108 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bookkeeping of the events saved by a notification process, to prime the
+//! event cache of the main process with them.
+//!
+//! The events themselves are saved in the event cache store, which is shared
+//! by both processes. Their IDs are listed, per room, in a custom value of the
+//! state store, so the main process knows which events to look up when it
+//! loads a room.
+//!
+//! Both processes read, modify and write back the list, so they must hold the
+//! cross-process lock of the event cache store while doing it, otherwise
+//! concurrent updates would be lost.
+
+use std::collections::BTreeMap;
+
+use matrix_sdk_base::store::DynStateStore;
+use ruma::{OwnedEventId, OwnedRoomId, RoomId};
+
+use super::Result;
+
+/// The key of the custom value of the state store listing the events saved by
+/// a notification process.
+const NOTIFICATION_EVENTS_KEY: &[u8] = b"event_cache.notification_events";
+
+/// The maximum number of events listed for a single room.
+///
+/// The oldest events are dropped beyond that, so the list doesn't grow
+/// forever if a room is never loaded by the main process.
+const MAX_NOTIFICATION_EVENTS_PER_ROOM: usize = 50;
+
+type NotificationEvents = BTreeMap<OwnedRoomId, Vec<OwnedEventId>>;
+
+async fn load(store: &DynStateStore) -> Result<NotificationEvents> {
+    let Some(value) = store.get_custom_value(NOTIFICATION_EVENTS_KEY).await? else {
+        return Ok(NotificationEvents::new());
+    };
+
+    // The list is only a hint, so it's dropped if it can't be read.
+    Ok(serde_json::from_slice(&value).unwrap_or_default())
+}
+
+async fn save(store: &DynStateStore, events: &NotificationEvents) -> Result<()> {
+    if events.is_empty() {
+        store.remove_custom_value(NOTIFICATION_EVENTS_KEY).await?;
+    } else {
+        let value = serde_json::to_vec(events).expect("the event IDs can always be serialized");
+        store.set_custom_value(NOTIFICATION_EVENTS_KEY, value).await?;
+    }
+
+    Ok(())
+}
+
+/// Add an event saved by a notification process to the list of its room.
+///
+/// The cross-process lock of the event cache store must be held.
+pub(super) async fn record_notification_event(
+    store: &DynStateStore,
+    room_id: &RoomId,
+    event_id: OwnedEventId,
+) -> Result<()> {
+    let mut events = load(store).await?;
+    let room_events = events.entry(room_id.to_owned()).or_default();
+
+    if room_events.contains(&event_id) {
+        return Ok(());
+    }
+
+    room_events.push(event_id);
+
+    if room_events.len() > MAX_NOTIFICATION_EVENTS_PER_ROOM {
+        let excess = room_events.len() - MAX_NOTIFICATION_EVENTS_PER_ROOM;
+        room_events.drain(..excess);
+    }
+
+    save(store, &events).await
+}
+
+/// Take the list of the events saved by a notification process for the given
+/// room, in the order they were saved.
+///
+/// The cross-process lock of the event cache store must be held.
+pub(super) async fn take_notification_events(
+    store: &DynStateStore,
+    room_id: &RoomId,
+) -> Result<Vec<OwnedEventId>> {
+    let mut events = load(store).await?;
+
+    let Some(room_events) = events.remove(room_id) else {
+        return Ok(Vec::new());
+    };
+
+    save(store, &events).await?;
+
+    Ok(room_events)
+}
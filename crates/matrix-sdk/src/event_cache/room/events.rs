@@ -12,8 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+
 use as_variant::as_variant;
 use eyeball_im::VectorDiff;
+use imbl::Vector;
 pub use matrix_sdk_base::event_cache::{Event, Gap};
 use matrix_sdk_base::{
     event_cache::store::DEFAULT_CHUNK_CAPACITY,
@@ -21,11 +28,15 @@ use matrix_sdk_base::{
         lazy_loader::{self, LazyLoaderError},
         ChunkContent, ChunkIdentifierGenerator, ChunkMetadata, OrderTracker, RawChunk,
     },
+    serde_helpers::extract_thread_root,
 };
 use matrix_sdk_common::linked_chunk::{
-    AsVector, Chunk, ChunkIdentifier, Error, Iter, IterBackward, LinkedChunk, ObservableUpdates,
-    Position,
+    AsVector, Chunk, ChunkIdentifier, Error, ItemPositions, Iter, IterBackward, LinkedChunk,
+    ObservableUpdates, Position,
 };
+use ruma::{EventId, OwnedEventId};
+
+use crate::event_cache::{DebugChunk, DebugChunkContent, DebugEvent, RoomEventsSnapshot};
 
 /// This type represents all events of a single room.
 #[derive(Debug)]
@@ -40,6 +51,20 @@ pub struct RoomEvents {
 
     /// Tracker of the events ordering in this room.
     pub order_tracker: OrderTracker<Event, Gap>,
+
+    /// Index of the positions of the in-memory events, by event ID.
+    ///
+    /// It's behind a mutex so it can be brought up to date lazily, when it's
+    /// queried.
+    event_positions: Mutex<ItemPositions<Event, Gap, OwnedEventId>>,
+
+    /// Index of the in-memory events which are part of a thread.
+    thread_index: ThreadIndex,
+
+    /// The last snapshot of the events, shared until they change.
+    ///
+    /// It's behind a mutex so it can be built lazily, when it's requested.
+    snapshot: Mutex<Option<RoomEventsSnapshot>>,
 }
 
 impl Default for RoomEvents {
@@ -71,7 +96,21 @@ impl RoomEvents {
             .order_tracker(full_linked_chunk_metadata)
             .expect("`LinkedChunk` must have been built with `new_with_update_history`");
 
-        Self { chunks: linked_chunk, chunks_updates_as_vectordiffs, order_tracker }
+        let event_positions = linked_chunk
+            .item_positions(|event| event.event_id())
+            .expect("`LinkedChunk` must have been built with `new_with_update_history`");
+
+        let mut thread_index = ThreadIndex::default();
+        thread_index.add_events(linked_chunk.items().map(|(_position, event)| event));
+
+        Self {
+            chunks: linked_chunk,
+            chunks_updates_as_vectordiffs,
+            order_tracker,
+            event_positions: Mutex::new(event_positions),
+            thread_index,
+            snapshot: Mutex::new(None),
+        }
     }
 
     /// Clear all events.
@@ -79,7 +118,9 @@ impl RoomEvents {
     /// All events, all gaps, everything is dropped, move into the void, into
     /// the ether, forever.
     pub fn reset(&mut self) {
+        self.invalidate_snapshot();
         self.chunks.clear();
+        self.thread_index.clear();
     }
 
     /// Push events after all events or gaps.
@@ -90,11 +131,14 @@ impl RoomEvents {
         I: IntoIterator<Item = Event>,
         I::IntoIter: ExactSizeIterator,
     {
-        self.chunks.push_items_back(events);
+        self.invalidate_snapshot();
+        let thread_index = &mut self.thread_index;
+        self.chunks.push_items_back(events.into_iter().inspect(|event| thread_index.add(event)));
     }
 
     /// Push a gap after all events or gaps.
     pub fn push_gap(&mut self, gap: Gap) {
+        self.invalidate_snapshot();
         self.chunks.push_gap_back(gap);
     }
 
@@ -104,12 +148,16 @@ impl RoomEvents {
         events: Vec<Event>,
         position: Position,
     ) -> Result<(), Error> {
+        let thread_entries = ThreadIndex::entries(&events);
+        self.invalidate_snapshot();
         self.chunks.insert_items_at(events, position)?;
+        self.thread_index.add_entries(thread_entries);
         Ok(())
     }
 
     /// Insert a gap at a specified position.
     pub fn insert_gap_at(&mut self, gap: Gap, position: Position) -> Result<(), Error> {
+        self.invalidate_snapshot();
         self.chunks.insert_gap_at(gap, position)
     }
 
@@ -124,6 +172,7 @@ impl RoomEvents {
         &mut self,
         gap: ChunkIdentifier,
     ) -> Result<Option<Position>, Error> {
+        self.invalidate_snapshot();
         self.chunks.remove_empty_chunk_at(gap)
     }
 
@@ -156,6 +205,9 @@ impl RoomEvents {
             it.next().is_none()
         };
 
+        let thread_entries = ThreadIndex::entries(&events);
+
+        self.invalidate_snapshot();
         let next_pos = if events.is_empty() && !has_only_one_chunk {
             // There are no new events, so there's no need to create a new empty items
             // chunk; instead, remove the gap.
@@ -165,17 +217,69 @@ impl RoomEvents {
             Some(self.chunks.replace_gap_at(events, gap_identifier)?.first_position())
         };
 
+        self.thread_index.add_entries(thread_entries);
+
         Ok(next_pos)
     }
 
+    /// Remove the redundant gaps, and return how many were removed.
+    ///
+    /// Adjacent gaps, possibly separated by empty items chunks, cover the
+    /// same missing events, so only the most recent one of them is kept: a
+    /// back-pagination from its token resolves the others too. The empty items
+    /// chunks between them are removed as well.
+    pub fn coalesce_gaps(&mut self) -> usize {
+        let mut redundant_chunks = Vec::new();
+        let mut num_redundant_gaps = 0;
+
+        // The chunks since the last gap, if they're all empty.
+        let mut run = Vec::new();
+
+        for chunk in self.chunks.chunks() {
+            match chunk.content() {
+                ChunkContent::Gap(_) => {
+                    // The previous gap of the run, if any, is redundant with this one.
+                    if !run.is_empty() {
+                        num_redundant_gaps += 1;
+                    }
+
+                    redundant_chunks.append(&mut run);
+                    run.push(chunk.identifier());
+                }
+
+                ChunkContent::Items(events) if events.is_empty() => {
+                    if !run.is_empty() {
+                        run.push(chunk.identifier());
+                    }
+                }
+
+                ChunkContent::Items(_) => run.clear(),
+            }
+        }
+
+        if !redundant_chunks.is_empty() {
+            self.invalidate_snapshot();
+        }
+
+        for chunk_identifier in redundant_chunks {
+            self.chunks
+                .remove_empty_chunk_at(chunk_identifier)
+                .expect("the chunk is empty, and is followed by a gap");
+        }
+
+        num_redundant_gaps
+    }
+
     /// Remove some events from the linked chunk.
     ///
     /// If a chunk becomes empty, it's going to be removed.
     pub fn remove_events_by_position(&mut self, mut positions: Vec<Position>) -> Result<(), Error> {
         sort_positions_descending(&mut positions);
+        self.invalidate_snapshot();
 
         for position in positions {
-            self.chunks.remove_item_at(position)?;
+            let event = self.chunks.remove_item_at(position)?;
+            self.thread_index.remove(&event);
         }
 
         Ok(())
@@ -186,7 +290,25 @@ impl RoomEvents {
     /// `position` must point to a valid item, otherwise the method returns an
     /// error.
     pub fn replace_event_at(&mut self, position: Position, event: Event) -> Result<(), Error> {
-        self.chunks.replace_item_at(position, event)
+        let previous_entry = self
+            .chunks
+            .items_from(position)?
+            .next()
+            .filter(|(pos, _)| *pos == position)
+            .and_then(|(_, previous_event)| ThreadIndex::entry(previous_event));
+        let new_entry = ThreadIndex::entry(&event);
+
+        // Only update the index once the event has been replaced, so it doesn't get out
+        // of sync if that fails.
+        self.invalidate_snapshot();
+        self.chunks.replace_item_at(position, event)?;
+
+        if let Some(previous_entry) = previous_entry {
+            self.thread_index.remove_entry(previous_entry);
+        }
+        self.thread_index.add_entries(new_entry);
+
+        Ok(())
     }
 
     /// Search for a chunk, and return its identifier.
@@ -225,6 +347,53 @@ impl RoomEvents {
         self.chunks.items()
     }
 
+    /// Iterate over the in-memory events of the thread starting at
+    /// `thread_root`, forward.
+    ///
+    /// The thread root comes first, if it's loaded, followed by the in-thread
+    /// events, the oldest first. Nothing is returned if no in-thread event is
+    /// loaded.
+    pub fn events_in_thread<'a>(
+        &'a self,
+        thread_root: &EventId,
+    ) -> impl Iterator<Item = (Position, &'a Event)> + 'a {
+        let mut positions = Vec::new();
+
+        if let Some(in_thread) = self.thread_index.get(thread_root) {
+            let mut event_positions = self.event_positions.lock().unwrap();
+            event_positions.flush_updates();
+
+            positions.extend(
+                std::iter::once(thread_root)
+                    .chain(in_thread.iter().map(|event_id| &**event_id))
+                    .filter_map(|event_id| event_positions.position(event_id)),
+            );
+        }
+
+        // Resolve the positions in a single pass over the chunks, not over the events.
+        let chunks = if positions.is_empty() {
+            HashMap::new()
+        } else {
+            self.chunks
+                .chunks()
+                .enumerate()
+                .map(|(nth, chunk)| (chunk.identifier(), (nth, chunk)))
+                .collect::<HashMap<_, _>>()
+        };
+
+        positions.sort_by_key(|position| {
+            (chunks.get(&position.chunk_identifier()).map(|(nth, _)| *nth), position.index())
+        });
+
+        positions.into_iter().filter_map(move |position| {
+            let (_, chunk) = chunks.get(&position.chunk_identifier())?;
+            let event = as_variant!(chunk.content(), ChunkContent::Items(events) => events)?
+                .get(position.index())?;
+
+            Some((position, event))
+        })
+    }
+
     /// Return the order of an event in the room linked chunk.
     ///
     /// Can return `None` if the event can't be found in the linked chunk.
@@ -232,6 +401,37 @@ impl RoomEvents {
         self.order_tracker.ordering(event_pos)
     }
 
+    /// Return the event at the given position, if it's loaded in memory.
+    pub fn event_at(&self, position: Position) -> Option<&Event> {
+        let chunk =
+            self.chunks.chunks().find(|chunk| chunk.identifier() == position.chunk_identifier())?;
+
+        match chunk.content() {
+            ChunkContent::Items(events) => events.get(position.index()),
+            ChunkContent::Gap(_) => None,
+        }
+    }
+
+    /// Return the position of an event in the in-memory linked chunk.
+    pub fn event_position(&self, event_id: &EventId) -> Option<Position> {
+        let mut event_positions = self.event_positions.lock().unwrap();
+        event_positions.flush_updates();
+        event_positions.position(event_id)
+    }
+
+    /// Compare the positions of two events in the room linked chunk.
+    ///
+    /// Returns [`Ordering::Less`] if the event `a` comes before the event `b`,
+    /// i.e. if it's older in the timeline.
+    ///
+    /// Returns `None` if one of the events isn't loaded in memory.
+    pub fn compare_events_positions(&self, a: &EventId, b: &EventId) -> Option<Ordering> {
+        let a = self.event_order(self.event_position(a)?)?;
+        let b = self.event_order(self.event_position(b)?)?;
+
+        Some(a.cmp(&b))
+    }
+
     #[cfg(any(test, debug_assertions))]
     fn assert_event_ordering(&self) {
         let mut iter = self.chunks.items().enumerate();
@@ -265,6 +465,7 @@ impl RoomEvents {
         let updates = self.chunks_updates_as_vectordiffs.take();
 
         self.order_tracker.flush_updates(false);
+        self.event_positions.get_mut().unwrap().flush_updates();
 
         if cfg!(any(test, debug_assertions)) {
             // Assert that the orderings are fully correct for all the events present in the
@@ -307,6 +508,67 @@ impl RoomEvents {
         result
     }
 
+    /// Return a machine-readable representation of the linked chunk of events
+    /// for this room, for debugging purposes.
+    pub fn debug_chunks(&self) -> Vec<DebugChunk> {
+        self.chunks
+            .chunks()
+            .map(|chunk| {
+                let content = match chunk.content() {
+                    ChunkContent::Gap(Gap { prev_token }) => {
+                        DebugChunkContent::Gap { prev_token: prev_token.clone() }
+                    }
+                    ChunkContent::Items(events) => DebugChunkContent::Events(
+                        events
+                            .iter()
+                            .enumerate()
+                            .map(|(index, event)| {
+                                let position = Position::new(chunk.identifier(), index);
+                                DebugEvent::new(event, self.order_tracker.ordering(position))
+                            })
+                            .collect(),
+                    ),
+                };
+
+                DebugChunk {
+                    identifier: chunk.identifier(),
+                    lazy_previous: chunk.lazy_previous(),
+                    content,
+                }
+            })
+            .collect()
+    }
+
+    /// Return an immutable view of the events loaded in memory, along with the
+    /// gaps between them.
+    ///
+    /// The snapshot is shared until the events change, so taking it again is
+    /// cheap.
+    pub fn snapshot(&self) -> RoomEventsSnapshot {
+        self.snapshot
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| {
+                let mut events = Vector::new();
+                let mut gaps = Vec::new();
+
+                for chunk in self.chunks() {
+                    match chunk.content() {
+                        ChunkContent::Gap(gap) => gaps.push((events.len(), gap.clone())),
+                        ChunkContent::Items(items) => events.extend(items.iter().cloned()),
+                    }
+                }
+
+                RoomEventsSnapshot::new(events, gaps)
+            })
+            .clone()
+    }
+
+    /// Drop the last snapshot of the events, because they're about to change.
+    fn invalidate_snapshot(&mut self) {
+        *self.snapshot.get_mut().unwrap() = None;
+    }
+
     /// Return the latest gap, if any.
     ///
     /// Latest means "closest to the end", or, since events are ordered
@@ -331,6 +593,9 @@ impl RoomEvents {
         // Start by flushing previous pending updates to the chunk ordering, if any.
         self.order_tracker.flush_updates(false);
 
+        // The function changes the events loaded in memory.
+        self.invalidate_snapshot();
+
         // Call the function.
         let r = f(self);
 
@@ -354,8 +619,37 @@ impl RoomEvents {
     ) -> Result<(), LazyLoaderError> {
         // Since `replace_with` is used only to unload some chunks, we don't want it to
         // affect the chunk ordering.
-        self.inhibit_updates_to_ordering_tracker(move |this| {
+        let result = self.inhibit_updates_to_ordering_tracker(move |this| {
             lazy_loader::replace_with(&mut this.chunks, last_chunk, chunk_identifier_generator)
+        });
+
+        self.thread_index.clear();
+        self.thread_index.add_events(self.chunks.items().map(|(_position, event)| event));
+
+        result
+    }
+
+    /// Unload the first chunk from memory, keeping it in the persisted storage.
+    ///
+    /// Happens only when the in-memory linked chunk exceeds its memory limit.
+    ///
+    /// Returns `None` if there's a single chunk, since the last chunk is never
+    /// unloaded.
+    pub(super) fn unload_first_chunk(&mut self) -> Option<ChunkIdentifier> {
+        // The last chunk is never unloaded, so only remove the events of the first
+        // chunk from the thread index if there's another one.
+        let mut chunks = self.chunks.chunks();
+        if let (Some(first_chunk), Some(_)) = (chunks.next(), chunks.next()) {
+            if let ChunkContent::Items(events) = first_chunk.content() {
+                for event in events {
+                    self.thread_index.remove(event);
+                }
+            }
+        }
+
+        // The chunk is still in the store, so the chunk ordering doesn't change.
+        self.inhibit_updates_to_ordering_tracker(|this| {
+            lazy_loader::unload_first_chunk(&mut this.chunks)
         })
     }
 
@@ -364,11 +658,94 @@ impl RoomEvents {
         &mut self,
         raw_new_first_chunk: RawChunk<Event, Gap>,
     ) -> Result<(), LazyLoaderError> {
+        let thread_entries = match &raw_new_first_chunk.content {
+            ChunkContent::Items(events) => ThreadIndex::entries(events),
+            ChunkContent::Gap(_) => Vec::new(),
+        };
+
         // This is only used when reinserting a chunk that was in persisted storage, so
         // we don't need to touch the chunk ordering for this.
         self.inhibit_updates_to_ordering_tracker(move |this| {
             lazy_loader::insert_new_first_chunk(&mut this.chunks, raw_new_first_chunk)
-        })
+        })?;
+
+        self.thread_index.add_entries(thread_entries);
+
+        Ok(())
+    }
+}
+
+/// An index of the in-memory events of a room which are part of a thread,
+/// grouped by thread root.
+///
+/// Only event IDs are stored, not positions, since the latter change whenever
+/// events are inserted or removed before them; positions are resolved when
+/// iterating over a thread's events, see [`RoomEvents::events_in_thread`].
+#[derive(Debug, Default)]
+struct ThreadIndex {
+    /// The in-thread events, by thread root.
+    events_by_root: HashMap<OwnedEventId, HashSet<OwnedEventId>>,
+}
+
+/// An in-thread event, as `(thread root, event ID)`.
+type ThreadEntry = (OwnedEventId, OwnedEventId);
+
+impl ThreadIndex {
+    /// Return the in-thread events for the given thread root, if any.
+    fn get(&self, thread_root: &EventId) -> Option<&HashSet<OwnedEventId>> {
+        self.events_by_root.get(thread_root)
+    }
+
+    /// Return the entry of an event in the index, if it's part of a thread.
+    fn entry(event: &Event) -> Option<ThreadEntry> {
+        extract_thread_root(event.raw()).zip(event.event_id())
+    }
+
+    /// Return the entries of the events which are part of a thread.
+    fn entries<'a>(events: impl IntoIterator<Item = &'a Event>) -> Vec<ThreadEntry> {
+        events.into_iter().filter_map(Self::entry).collect()
+    }
+
+    /// Index a new event, if it's part of a thread.
+    fn add(&mut self, event: &Event) {
+        self.add_entries(Self::entry(event));
+    }
+
+    /// Index multiple new events.
+    fn add_events<'a>(&mut self, events: impl IntoIterator<Item = &'a Event>) {
+        for event in events {
+            self.add(event);
+        }
+    }
+
+    /// Add entries to the index.
+    fn add_entries(&mut self, entries: impl IntoIterator<Item = ThreadEntry>) {
+        for (thread_root, event_id) in entries {
+            self.events_by_root.entry(thread_root).or_default().insert(event_id);
+        }
+    }
+
+    /// Remove an event from the index, if it was part of a thread.
+    fn remove(&mut self, event: &Event) {
+        if let Some(entry) = Self::entry(event) {
+            self.remove_entry(entry);
+        }
+    }
+
+    /// Remove an entry from the index.
+    fn remove_entry(&mut self, (thread_root, event_id): ThreadEntry) {
+        if let Some(in_thread) = self.events_by_root.get_mut(&thread_root) {
+            in_thread.remove(&event_id);
+
+            if in_thread.is_empty() {
+                self.events_by_root.remove(&thread_root);
+            }
+        }
+    }
+
+    /// Remove all the events from the index.
+    fn clear(&mut self) {
+        self.events_by_root.clear();
     }
 }
 
@@ -428,9 +805,10 @@ mod tests {
     use assert_matches::assert_matches;
     use assert_matches2::assert_let;
     use matrix_sdk_test::{event_factory::EventFactory, ALICE, DEFAULT_TEST_ROOM_ID};
-    use ruma::{event_id, user_id, EventId, OwnedEventId};
+    use ruma::{event_id, owned_event_id, user_id, EventId, OwnedEventId};
 
     use super::*;
+    use crate::event_cache::DebugEventOrigin;
 
     macro_rules! assert_events_eq {
         ( $events_iterator:expr, [ $( ( $event_id:ident at ( $chunk_identifier:literal, $index:literal ) ) ),* $(,)? ] ) => {
@@ -523,6 +901,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_snapshot_is_shared_until_events_change() {
+        let (event_id_0, event_0) = new_event("$ev0");
+        let (event_id_1, event_1) = new_event("$ev1");
+
+        let mut room_events = RoomEvents::new();
+        room_events.push_events([event_0]);
+        room_events.push_gap(Gap { prev_token: "hello".to_owned() });
+
+        let snapshot = room_events.snapshot();
+        assert_eq!(snapshot.events().len(), 1);
+        assert_eq!(snapshot.events()[0].event_id(), Some(event_id_0));
+        assert_eq!(snapshot.gaps().len(), 1);
+        assert_eq!(snapshot.gaps()[0].0, 1);
+
+        // Taking the snapshot again shares it.
+        assert!(std::ptr::eq(room_events.snapshot().events(), snapshot.events()));
+
+        // Once the events change, a new snapshot is taken.
+        room_events.push_events([event_1]);
+
+        let new_snapshot = room_events.snapshot();
+        assert!(!std::ptr::eq(new_snapshot.events(), snapshot.events()));
+        assert_eq!(new_snapshot.events().len(), 2);
+        assert_eq!(new_snapshot.events()[1].event_id(), Some(event_id_1));
+
+        // The previous snapshot is left untouched.
+        assert_eq!(snapshot.events().len(), 1);
+    }
+
     #[test]
     fn test_insert_events_at() {
         let (event_id_0, event_0) = new_event("$ev0");
@@ -670,6 +1078,42 @@ mod tests {
         assert!(pos.is_none());
     }
 
+    #[test]
+    fn test_coalesce_gaps() {
+        let (event_id_0, event_0) = new_event("$ev0");
+        let (event_id_1, event_1) = new_event("$ev1");
+
+        let mut room_events = RoomEvents::new();
+
+        room_events.push_gap(Gap { prev_token: "oldest".to_owned() });
+        room_events.push_gap(Gap { prev_token: "old".to_owned() });
+        room_events.push_gap(Gap { prev_token: "recent".to_owned() });
+        room_events.push_events([event_0]);
+        room_events.push_gap(Gap { prev_token: "alone".to_owned() });
+        room_events.push_events([event_1]);
+
+        assert_eq!(room_events.coalesce_gaps(), 2);
+
+        // Only the most recent gap of the adjacent ones is kept.
+        let gaps = room_events
+            .chunks()
+            .filter_map(|chunk| as_variant!(chunk.content(), ChunkContent::Gap(gap) => gap))
+            .map(|gap| gap.prev_token.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(gaps, ["recent", "alone"]);
+
+        assert_events_eq!(
+            room_events.events(),
+            [
+                (event_id_0 at (4, 0)),
+                (event_id_1 at (6, 0)),
+            ]
+        );
+
+        // There's nothing left to coalesce.
+        assert_eq!(room_events.coalesce_gaps(), 0);
+    }
+
     #[test]
     fn test_remove_events() {
         let (event_id_0, event_0) = new_event("$ev0");
@@ -820,6 +1264,47 @@ mod tests {
         assert_eq!(&output[1], "chunk #1: gap['raclette']");
     }
 
+    #[test]
+    fn test_debug_chunks() {
+        let event_factory = EventFactory::new().room(&DEFAULT_TEST_ROOM_ID).sender(*ALICE);
+
+        let mut room_events = RoomEvents::new();
+        room_events.push_events(vec![
+            event_factory.text_msg("hey").event_id(event_id!("$1")).into_event(),
+            event_factory.text_msg("you").event_id(event_id!("$2")).into_event(),
+        ]);
+        room_events.push_gap(Gap { prev_token: "raclette".to_owned() });
+
+        // Flush updates to the order tracker.
+        let _ = room_events.updates_as_vector_diffs();
+
+        let chunks = room_events.debug_chunks();
+
+        assert_eq!(chunks.len(), 2);
+
+        assert_eq!(chunks[0].identifier, ChunkIdentifier::new(0));
+        assert!(chunks[0].lazy_previous.is_none());
+        assert_let!(DebugChunkContent::Events(events) = &chunks[0].content);
+        assert_eq!(
+            events,
+            &[
+                DebugEvent {
+                    event_id: Some(owned_event_id!("$1")),
+                    order: Some(0),
+                    origin: DebugEventOrigin::PlainText,
+                },
+                DebugEvent {
+                    event_id: Some(owned_event_id!("$2")),
+                    order: Some(1),
+                    origin: DebugEventOrigin::PlainText,
+                },
+            ]
+        );
+
+        assert_eq!(chunks[1].identifier, ChunkIdentifier::new(1));
+        assert_eq!(chunks[1].content, DebugChunkContent::Gap { prev_token: "raclette".to_owned() });
+    }
+
     #[test]
     fn test_sort_positions_descending() {
         let mut positions = vec![
@@ -843,4 +1328,153 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_events_in_thread() {
+        let f = EventFactory::new().room(&DEFAULT_TEST_ROOM_ID).sender(*ALICE);
+
+        let root_id = event_id!("$root");
+        let reply_0_id = event_id!("$reply0");
+        let reply_1_id = event_id!("$reply1");
+        let reply_2_id = event_id!("$reply2");
+        let (other_id, other) = new_event("$other");
+
+        let root = f.text_msg("root").event_id(root_id).into_event();
+        let reply_0 =
+            f.text_msg("r0").in_thread(root_id, root_id).event_id(reply_0_id).into_event();
+        let reply_1 =
+            f.text_msg("r1").in_thread(root_id, reply_0_id).event_id(reply_1_id).into_event();
+        let reply_2 =
+            f.text_msg("r2").in_thread(root_id, reply_1_id).event_id(reply_2_id).into_event();
+
+        let mut room_events = RoomEvents::new();
+
+        // No thread when there's only a root.
+        room_events.push_events([root]);
+        assert_events_eq!(room_events.events_in_thread(root_id), []);
+
+        room_events.push_events([reply_0.clone(), other, reply_2]);
+        room_events
+            .insert_events_at(vec![reply_1], Position::new(ChunkIdentifier::new(0), 2))
+            .unwrap();
+
+        // The root and its in-thread events are returned in order, with their current
+        // positions.
+        assert_events_eq!(
+            room_events.events_in_thread(root_id),
+            [
+                (root_id at (0, 0)),
+                (reply_0_id at (0, 1)),
+                (reply_1_id at (0, 2)),
+                (reply_2_id at (0, 4)),
+            ]
+        );
+        assert_events_eq!(room_events.events_in_thread(&other_id), []);
+
+        // Removing an in-thread event updates the index.
+        room_events
+            .remove_events_by_position(vec![Position::new(ChunkIdentifier::new(0), 2)])
+            .unwrap();
+
+        assert_events_eq!(
+            room_events.events_in_thread(root_id),
+            [
+                (root_id at (0, 0)),
+                (reply_0_id at (0, 1)),
+                (reply_2_id at (0, 3)),
+            ]
+        );
+
+        // Replacing an in-thread event by an event which isn't in the thread updates
+        // the index too.
+        let edited = f.text_msg("r2, edited").event_id(reply_2_id).into_event();
+        room_events.replace_event_at(Position::new(ChunkIdentifier::new(0), 3), edited).unwrap();
+
+        assert_events_eq!(
+            room_events.events_in_thread(root_id),
+            [
+                (root_id at (0, 0)),
+                (reply_0_id at (0, 1)),
+            ]
+        );
+
+        // Failing to replace an event leaves the index untouched.
+        let reply_3 = f
+            .text_msg("r3")
+            .in_thread(root_id, reply_0_id)
+            .event_id(event_id!("$reply3"))
+            .into_event();
+        room_events
+            .replace_event_at(Position::new(ChunkIdentifier::new(0), 42), reply_3)
+            .unwrap_err();
+
+        assert_events_eq!(
+            room_events.events_in_thread(root_id),
+            [
+                (root_id at (0, 0)),
+                (reply_0_id at (0, 1)),
+            ]
+        );
+
+        // Resetting clears the thread index.
+        room_events.reset();
+        assert_events_eq!(room_events.events_in_thread(root_id), []);
+
+        // The thread is known again if an in-thread event is pushed, even without its
+        // root.
+        room_events.push_events([reply_0]);
+        assert_events_eq!(room_events.events_in_thread(root_id), [(reply_0_id at (0, 0))]);
+    }
+
+    #[test]
+    fn test_compare_events_positions() {
+        let (event_id_0, event_0) = new_event("$ev0");
+        let (event_id_1, event_1) = new_event("$ev1");
+        let (event_id_2, event_2) = new_event("$ev2");
+
+        let mut room_events = RoomEvents::new();
+        room_events.push_events([event_0, event_1]);
+        room_events.push_gap(Gap { prev_token: "middle".to_owned() });
+        room_events.push_events([event_2]);
+
+        // The ordering tracker must be up to date.
+        let _ = room_events.updates_as_vector_diffs();
+
+        assert_eq!(
+            room_events.compare_events_positions(&event_id_0, &event_id_1),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            room_events.compare_events_positions(&event_id_2, &event_id_1),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            room_events.compare_events_positions(&event_id_2, &event_id_2),
+            Some(Ordering::Equal)
+        );
+
+        // An unknown event can't be compared.
+        assert_eq!(room_events.compare_events_positions(&event_id_0, event_id!("$unknown")), None);
+    }
+
+    #[test]
+    fn test_event_at() {
+        let (event_id_0, event_0) = new_event("$ev0");
+        let (event_id_1, event_1) = new_event("$ev1");
+
+        let mut room_events = RoomEvents::new();
+        room_events.push_events([event_0]);
+        room_events.push_gap(Gap { prev_token: "middle".to_owned() });
+        room_events.push_events([event_1]);
+
+        let position_0 = room_events.event_position(&event_id_0).unwrap();
+        let position_1 = room_events.event_position(&event_id_1).unwrap();
+
+        assert_eq!(room_events.event_at(position_0).unwrap().event_id().unwrap(), event_id_0);
+        assert_eq!(room_events.event_at(position_1).unwrap().event_id().unwrap(), event_id_1);
+
+        // There's no event past the end of a chunk.
+        let past_the_end = Position::new(position_1.chunk_identifier(), position_1.index() + 1);
+        assert!(room_events.event_at(past_the_end).is_none());
+    }
 }
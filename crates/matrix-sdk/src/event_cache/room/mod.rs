@@ -14,44 +14,78 @@
 
 //! All event cache types for a single room.
 
+#[cfg(feature = "e2e-encryption")]
+use std::collections::BTreeSet;
 use std::{
+    cmp,
     collections::BTreeMap,
     fmt,
     ops::{Deref, DerefMut},
     sync::{
         atomic::{AtomicUsize, Ordering},
-        Arc,
+        Arc, Mutex as StdMutex,
     },
 };
 
 use events::sort_positions_descending;
 use eyeball::SharedObservable;
 use eyeball_im::VectorDiff;
+#[cfg(feature = "e2e-encryption")]
+use matrix_sdk_base::deserialized_responses::TimelineEventKind;
 use matrix_sdk_base::{
     deserialized_responses::AmbiguityChange,
     event_cache::Event,
     linked_chunk::Position,
+    media::MediaEventContent,
     sync::{JoinedRoomUpdate, LeftRoomUpdate, Timeline},
 };
+#[cfg(feature = "e2e-encryption")]
+use ruma::events::room::encrypted::OriginalSyncRoomEncryptedEvent;
 use ruma::{
-    events::{relation::RelationType, AnyRoomAccountDataEvent, AnySyncEphemeralRoomEvent},
+    api::Direction,
+    events::{
+        relation::RelationType,
+        room::{message::MessageType, MediaSource},
+        AnyRoomAccountDataEvent, AnySyncEphemeralRoomEvent, AnySyncMessageLikeEvent,
+        SyncMessageLikeEvent,
+    },
     serde::Raw,
-    EventId, OwnedEventId, OwnedRoomId,
+    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId,
 };
 use tokio::sync::{
     broadcast::{Receiver, Sender},
-    mpsc, Notify, RwLock,
+    mpsc, Notify, RwLock, RwLockWriteGuard,
 };
-use tracing::{instrument, trace, warn};
+use tracing::{debug, instrument, trace, warn};
 
 use super::{
-    AutoShrinkChannelPayload, EventsOrigin, Result, RoomEventCacheGenericUpdate,
-    RoomEventCacheUpdate, RoomPagination, RoomPaginationStatus,
+    generation::GenerationTracker, snapshot::diff_events, AutoShrinkChannelPayload, DebugChunk,
+    EventCacheError, EventsOrigin, HistoryImportSummary, OrderingIntegrityReport, Result,
+    RoomEventCacheGenericUpdate, RoomEventCacheSemanticUpdate, RoomEventCacheUpdate,
+    RoomEventsSnapshot, RoomHistoryExport, RoomPagination, RoomPaginationStatus,
+    ThreadBackPaginationOutcome,
+};
+use crate::{
+    client::WeakClient,
+    room::{IncludeRelations, MessagesOptions, RelationsOptions, WeakRoom},
 };
-use crate::{client::WeakClient, room::WeakRoom};
 
 pub(super) mod events;
 
+/// The prefix of the previous-batch token of the gaps inserted before the
+/// events primed from a notification process, followed by the ID of the
+/// primed event so the gaps can be told apart.
+///
+/// The homeserver doesn't know these tokens: a new one is obtained from the
+/// event following the gap when back-paginating from it.
+const PRIMED_GAP_TOKEN_PREFIX: &str = "primed:";
+
+/// Whether the previous-batch token of a gap is the one of a gap inserted
+/// before a primed event, see [`PRIMED_GAP_TOKEN_PREFIX`].
+pub(super) fn is_primed_gap_token(token: &str) -> bool {
+    token.starts_with(PRIMED_GAP_TOKEN_PREFIX)
+}
+
 /// A subset of an event cache, for a room.
 ///
 /// Cloning is shallow, and thus is cheap to do.
@@ -66,6 +100,21 @@ impl fmt::Debug for RoomEventCache {
     }
 }
 
+/// What happened to an event given to
+/// [`RoomEventCache::place_out_of_band_event`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutOfBandPlacement {
+    /// The event has been inserted between its known neighbours.
+    Inserted,
+
+    /// The position of the event isn't known yet, so it's been kept aside
+    /// until a sync or a back-pagination confirms it.
+    Pending,
+
+    /// An event with the same ID is already known, nothing has been done.
+    AlreadyKnown,
+}
+
 /// Thin wrapper for a room event cache subscriber, so as to trigger
 /// side-effects when all subscribers are gone.
 ///
@@ -160,6 +209,7 @@ impl RoomEventCache {
         room_id: OwnedRoomId,
         auto_shrink_sender: mpsc::Sender<AutoShrinkChannelPayload>,
         generic_update_sender: Sender<RoomEventCacheGenericUpdate>,
+        generations: GenerationTracker,
     ) -> Self {
         Self {
             inner: Arc::new(RoomEventCacheInner::new(
@@ -169,6 +219,7 @@ impl RoomEventCache {
                 room_id,
                 auto_shrink_sender,
                 generic_update_sender,
+                generations,
             )),
         }
     }
@@ -189,22 +240,79 @@ impl RoomEventCache {
     /// Use [`RoomEventCache::events`] to get all current events without the
     /// subscriber. Creating, and especially dropping, a
     /// [`RoomEventCacheSubscriber`] isn't free, as it triggers side-effects.
+    ///
+    /// If this is the first subscriber of the room, more events may be loaded
+    /// from the store first, per
+    /// [`EventCacheConfig::subscription_preload`](super::EventCacheConfig::subscription_preload).
     pub async fn subscribe(&self) -> (Vec<Event>, RoomEventCacheSubscriber) {
-        let state = self.inner.state.read().await;
+        let state = self.inner.lock_state_for_new_subscriber().await;
         let events = state.events().events().map(|(_position, item)| item.clone()).collect();
 
+        (events, self.new_subscriber(&state))
+    }
+
+    /// Take a snapshot of the events of this room currently loaded in memory.
+    ///
+    /// The snapshot can later be passed to [`Self::restore_from_snapshot`],
+    /// e.g. when switching back to this room, to get the updates which
+    /// happened in the meantime.
+    pub async fn snapshot(&self) -> RoomEventsSnapshot {
+        self.inner.state.read().await.events().snapshot()
+    }
+
+    /// Subscribe to this room updates, after getting the updates to apply to
+    /// the events of the given snapshot to get the current events.
+    ///
+    /// This is an alternative to [`Self::subscribe`] for UI layers which have
+    /// kept the state they rendered from a [`RoomEventsSnapshot`]: only what
+    /// changed since then must be processed, instead of all the events. The
+    /// updates contain a single [`VectorDiff::Reset`] if they can't be
+    /// computed cheaply, e.g. because the events have been reloaded from the
+    /// store since the snapshot was taken.
+    pub async fn restore_from_snapshot(
+        &self,
+        snapshot: &RoomEventsSnapshot,
+    ) -> (Vec<VectorDiff<Event>>, RoomEventCacheSubscriber) {
+        let state = self.inner.lock_state_for_new_subscriber().await;
+        let current = state.events().snapshot();
+
+        let diffs = diff_events(snapshot.events(), current.events());
+
+        (diffs, self.new_subscriber(&state))
+    }
+
+    /// The number of active [`RoomEventCacheSubscriber`]s of this room.
+    ///
+    /// The events of a room without subscribers may be unloaded from memory.
+    pub async fn subscriber_count(&self) -> usize {
+        self.inner.state.read().await.subscriber_count.load(Ordering::SeqCst)
+    }
+
+    /// Create a new subscriber to this room updates.
+    fn new_subscriber(&self, state: &RoomEventCacheState) -> RoomEventCacheSubscriber {
         let previous_subscriber_count = state.subscriber_count.fetch_add(1, Ordering::SeqCst);
         trace!("added a room event cache subscriber; new count: {}", previous_subscriber_count + 1);
 
         let recv = self.inner.sender.subscribe();
-        let subscriber = RoomEventCacheSubscriber {
+
+        RoomEventCacheSubscriber {
             recv,
             room_id: self.inner.room_id.clone(),
             auto_shrink_sender: self.inner.auto_shrink_sender.clone(),
             subscriber_count: state.subscriber_count.clone(),
-        };
+        }
+    }
 
-        (events, subscriber)
+    /// Subscribe to the semantic updates of the events of this room, i.e. why
+    /// they have changed.
+    ///
+    /// This stream is parallel to the [`RoomEventCacheUpdate`]s received with
+    /// [`Self::subscribe`], and doesn't replace it: the events themselves are
+    /// only available through the latter. Unlike [`Self::subscribe`], this
+    /// doesn't prevent the events from being unloaded from memory when
+    /// there are no more subscribers.
+    pub async fn updates_as_semantic_events(&self) -> Receiver<RoomEventCacheSemanticUpdate> {
+        self.inner.semantic_updates.subscribe()
     }
 
     /// Return a [`RoomPagination`] API object useful for running
@@ -226,6 +334,22 @@ impl RoomEventCache {
             .map(|(_loc, event)| event)
     }
 
+    /// Compare the positions of two events in this room.
+    ///
+    /// Returns [`cmp::Ordering::Less`] if the event `a` comes before the event
+    /// `b`, i.e. if it's older in the timeline. The events don't need to be
+    /// loaded in memory, as long as they're in the store.
+    ///
+    /// Returns `None` if one of the events can't be found in the cache, and an
+    /// error if the store couldn't be read.
+    pub async fn compare_events_positions(
+        &self,
+        a: &EventId,
+        b: &EventId,
+    ) -> Result<Option<cmp::Ordering>> {
+        self.inner.state.read().await.compare_events_positions(a, b).await
+    }
+
     /// Try to find an event by id in this room, along with its related events.
     ///
     /// You can filter which types of related events to retrieve using
@@ -246,6 +370,59 @@ impl RoomEventCache {
             .flatten()
     }
 
+    /// Read all the in-memory events of the thread starting at `thread_root`.
+    ///
+    /// The thread root comes first, if it's loaded, followed by the in-thread
+    /// events, the oldest first. Use
+    /// [`RoomEventCache::paginate_thread_backwards`] to fetch older in-thread
+    /// events from the server.
+    pub async fn thread_events(&self, thread_root: &EventId) -> Vec<Event> {
+        let state = self.inner.state.read().await;
+
+        state.events().events_in_thread(thread_root).map(|(_position, item)| item.clone()).collect()
+    }
+
+    /// Back-paginate the thread starting at `thread_root`, fetching at most
+    /// `num_events` in-thread events from the server.
+    ///
+    /// `from` must be `None` for the first request, and then the
+    /// [`ThreadBackPaginationOutcome::prev_token`] returned by the previous
+    /// request.
+    ///
+    /// The fetched events are saved in the event cache, so they can later be
+    /// retrieved with [`RoomEventCache::event`] or
+    /// [`RoomEventCache::event_with_relations`], but they're not inserted in
+    /// the room's timeline since their position in it is unknown.
+    pub async fn paginate_thread_backwards(
+        &self,
+        thread_root: OwnedEventId,
+        from: Option<String>,
+        num_events: u16,
+    ) -> Result<ThreadBackPaginationOutcome> {
+        let room = self.inner.weak_room.get().ok_or(EventCacheError::ClientDropped)?;
+
+        let relations = room
+            .relations(
+                thread_root,
+                RelationsOptions {
+                    from,
+                    dir: Direction::Backward,
+                    limit: Some(num_events.into()),
+                    include_relations: IncludeRelations::RelationsOfType(RelationType::Thread),
+                    recurse: false,
+                },
+            )
+            .await
+            .map_err(|err| EventCacheError::BackpaginationError(Box::new(err)))?;
+
+        self.inner.state.read().await.save_event(relations.chunk.iter().cloned()).await?;
+
+        Ok(ThreadBackPaginationOutcome {
+            prev_token: relations.next_batch_token,
+            events: relations.chunk,
+        })
+    }
+
     /// Clear all the storage for this [`RoomEventCache`].
     ///
     /// This will get rid of all the events from the linked chunk and persisted
@@ -259,6 +436,7 @@ impl RoomEventCache {
             diffs: updates_as_vector_diffs,
             origin: EventsOrigin::Cache,
         });
+        self.inner.semantic_updates.flush();
 
         Ok(())
     }
@@ -271,11 +449,295 @@ impl RoomEventCache {
         }
     }
 
+    /// Insert an event obtained out of band, i.e. not received from the
+    /// homeserver, in the room's events.
+    ///
+    /// This is useful to display events received through another transport,
+    /// e.g. from a bridge during a migration period. The event is inserted
+    /// among the loaded events according to its `origin_server_ts`, right
+    /// after the events which aren't more recent than it. If the homeserver
+    /// later sends an event with the same ID, it replaces the out-of-band one,
+    /// like any other duplicated event.
+    ///
+    /// Returns `false`, without inserting anything, if an event with the same
+    /// ID is already part of the room's events.
+    pub async fn insert_out_of_band_event(&self, event: Event) -> Result<bool> {
+        let Some(diffs) = self.inner.state.write().await.insert_out_of_band_event(event).await?
+        else {
+            return Ok(false);
+        };
+
+        self.notify_out_of_band_diffs(diffs, EventsOrigin::Cache);
+
+        Ok(true)
+    }
+
+    /// Place an event obtained out of band, e.g. fetched with `/event` when
+    /// handling a push notification, in the room's events.
+    ///
+    /// The event is inserted with [`Self::insert_out_of_band_event`], but only
+    /// if its position is known for sure, i.e. if the loaded events right
+    /// before and right after it, according to their `origin_server_ts`, are
+    /// not separated from it by a gap. Otherwise, the event is kept in a
+    /// pending section, which isn't part of the room's events, until a sync or
+    /// a back-pagination brings its neighbours, or the event itself, in which
+    /// case the pending copy is dropped. A pending event can still be found
+    /// with [`Self::event`].
+    ///
+    /// The pending section is persisted, and bounded: the oldest pending
+    /// events are dropped from it when there are too many of them.
+    ///
+    /// Returns [`OutOfBandPlacement::AlreadyKnown`] if the event is already
+    /// part of the room's events, or pending. An event which has only been
+    /// saved in the cache, e.g. by [`Room::event`](crate::Room::event), can
+    /// still be placed.
+    pub async fn place_out_of_band_event(&self, event: Event) -> Result<OutOfBandPlacement> {
+        let (placement, diffs) =
+            self.inner.state.write().await.place_out_of_band_event(event).await?;
+
+        if !diffs.is_empty() {
+            self.notify_out_of_band_diffs(diffs, EventsOrigin::Cache);
+        }
+
+        Ok(placement)
+    }
+
+    /// Retry decrypting the events which couldn't be decrypted because the
+    /// room keys of their megolm sessions were missing, now that the keys of
+    /// the given sessions are available.
+    ///
+    /// The events which can now be decrypted are replaced in place by their
+    /// decrypted form, and observers are notified about it. This includes the
+    /// events which are only in the store, and not loaded in memory.
+    ///
+    /// This happens automatically when room keys are received, once the
+    /// [`EventCache`](super::EventCache) has been subscribed to.
+    ///
+    /// Returns the number of events which have been decrypted.
+    #[cfg(feature = "e2e-encryption")]
+    pub async fn retry_decryption(&self, session_ids: &BTreeSet<String>) -> Result<usize> {
+        self.retry_decryption_impl(Some(session_ids)).await
+    }
+
+    /// Retry decrypting all the events which couldn't be decrypted, whatever
+    /// their megolm session.
+    ///
+    /// This is used when it's unknown which room keys have been received.
+    #[cfg(feature = "e2e-encryption")]
+    pub(super) async fn retry_decryption_of_all_events(&self) -> Result<usize> {
+        self.retry_decryption_impl(None).await
+    }
+
+    #[cfg(feature = "e2e-encryption")]
+    async fn retry_decryption_impl(&self, session_ids: Option<&BTreeSet<String>>) -> Result<usize> {
+        let room = self.inner.weak_room.get().ok_or(EventCacheError::ClientDropped)?;
+
+        // Only collect the events to decrypt with the state locked: decrypting many
+        // events may take a while, and mustn't block the sync and the pagination.
+        let utds = self.inner.state.read().await.find_utd_events(session_ids).await?;
+
+        if utds.is_empty() {
+            return Ok(0);
+        }
+
+        let push_ctx = room.push_context().await.ok().flatten();
+        let mut decrypted_events = Vec::new();
+
+        for utd in utds {
+            let raw = utd.raw().cast_ref::<OriginalSyncRoomEncryptedEvent>();
+
+            match room.decrypt_event(raw, push_ctx.as_ref()).await {
+                Ok(event) if matches!(event.kind, TimelineEventKind::Decrypted(_)) => {
+                    decrypted_events.push(event);
+                }
+                Ok(_) => {
+                    trace!(event_id = ?utd.event_id(), "event still can't be decrypted");
+                }
+                Err(err) => {
+                    warn!(
+                        event_id = ?utd.event_id(),
+                        "error when retrying to decrypt an event: {err}"
+                    );
+                }
+            }
+        }
+
+        // The events may have moved, or been removed, while they were being decrypted,
+        // so they're looked up again.
+        let mut state = self.inner.state.write().await;
+        let (num_decrypted, diffs) = state.replace_decrypted_events(decrypted_events).await?;
+        drop(state);
+
+        self.notify_out_of_band_diffs(diffs, EventsOrigin::Cache);
+
+        Ok(num_decrypted)
+    }
+
+    /// The events given to [`Self::place_out_of_band_event`] whose position
+    /// isn't known yet.
+    pub async fn pending_out_of_band_events(&self) -> Vec<Event> {
+        self.inner.state.read().await.pending_out_of_band_events.clone()
+    }
+
+    /// Import the history of the room from an export, e.g. to read the
+    /// history of a migrated room offline.
+    ///
+    /// The imported events are inserted like events obtained [out of
+    /// band](Self::insert_out_of_band_event): they are sorted among the events
+    /// of the room according to their `origin_server_ts`, including the ones
+    /// which aren't loaded, and they are replaced by the events received from
+    /// the homeserver with the same IDs, if any. They never create or fill a
+    /// gap, so they don't interfere with the pagination, and the updates they
+    /// cause are reported with [`EventsOrigin::Import`]. They are marked as
+    /// [imported](Event::imported), also in the store, so they can always be
+    /// told apart from the events received from the homeserver.
+    ///
+    /// Events which are already known, which have no event ID, or which
+    /// belong to another room, are skipped.
+    pub async fn import_history(&self, export: RoomHistoryExport) -> Result<HistoryImportSummary> {
+        let num_messages = export.messages.len();
+
+        let events = export
+            .messages
+            .into_iter()
+            .filter(|raw| {
+                let room_id = raw.get_field::<OwnedRoomId>("room_id").ok().flatten();
+                room_id.is_none_or(|room_id| room_id == self.inner.room_id)
+            })
+            .map(Event::from_plaintext)
+            .filter(|event| event.event_id().is_some())
+            .collect();
+
+        let (imported, diffs) = self.inner.state.write().await.import_events(events).await?;
+
+        self.notify_out_of_band_diffs(diffs, EventsOrigin::Import);
+
+        Ok(HistoryImportSummary { imported, skipped: num_messages - imported })
+    }
+
+    /// Check the ordering of the most recent events of the room against the
+    /// homeserver, and repair the drift.
+    ///
+    /// The `sample_size` most recent events are fetched with `/messages`, and
+    /// compared with the loaded events between the oldest and the most recent
+    /// events known by both sides. The events missed by the event cache in
+    /// that range, e.g. because of a misbehaving bridge, are inserted. The
+    /// loaded events the homeserver didn't return are only reported: they may
+    /// have been purged, but they may as well be out-of-band or imported
+    /// events, so they're kept.
+    ///
+    /// This isn't run automatically; it can be run in the background, e.g.
+    /// when opening a room whose history looks wrong. The updates it causes
+    /// are reported with [`EventsOrigin::Cache`].
+    pub async fn verify_and_heal_ordering(
+        &self,
+        sample_size: u16,
+    ) -> Result<OrderingIntegrityReport> {
+        let Some(room) = self.inner.weak_room.get() else {
+            return Err(EventCacheError::ClientDropped);
+        };
+
+        let mut options = MessagesOptions::new(Direction::Backward);
+        options.limit = sample_size.into();
+
+        let response = room
+            .messages(options)
+            .await
+            .map_err(|err| EventCacheError::BackpaginationError(Box::new(err)))?;
+
+        // The events are returned from the most recent to the oldest.
+        let mut server_events = response.chunk;
+        server_events.reverse();
+
+        let (report, diffs) = self.inner.state.write().await.heal_ordering(server_events).await?;
+
+        if !report.is_consistent() {
+            debug!(
+                inserted = report.inserted.len(),
+                unconfirmed = report.unconfirmed.len(),
+                same_order = report.same_order,
+                "healed the ordering of the event cache"
+            );
+        }
+
+        self.notify_out_of_band_diffs(diffs, EventsOrigin::Cache);
+
+        Ok(report)
+    }
+
+    /// Notify the subscribers about events inserted out of band.
+    fn notify_out_of_band_diffs(&self, diffs: Vec<VectorDiff<Event>>, origin: EventsOrigin) {
+        if diffs.is_empty() {
+            self.inner.semantic_updates.flush();
+            return;
+        }
+
+        self.inner.generations.bump(&self.inner.room_id);
+
+        let _ =
+            self.inner.sender.send(RoomEventCacheUpdate::UpdateTimelineEvents { diffs, origin });
+        self.inner.semantic_updates.flush();
+        let _ =
+            self.inner.generic_update_sender.send(RoomEventCacheGenericUpdate::TimelineUpdated {
+                room_id: self.inner.room_id.clone(),
+            });
+    }
+
     /// Return a nice debug string (a vector of lines) for the linked chunk of
     /// events for this room.
     pub async fn debug_string(&self) -> Vec<String> {
         self.inner.state.read().await.events().debug_string()
     }
+
+    /// Return a machine-readable representation of the linked chunk of events
+    /// for this room, for debugging purposes.
+    ///
+    /// Only the chunks loaded in memory are returned.
+    pub async fn debug_chunks(&self) -> Vec<DebugChunk> {
+        self.inner.state.read().await.events().debug_chunks()
+    }
+}
+
+/// The semantic updates of the events of a room, waiting to be sent.
+///
+/// The [`RoomEventCacheState`] collects them while it changes the events, and
+/// they're only sent with [`Self::flush`], once the matching
+/// [`RoomEventCacheUpdate::UpdateTimelineEvents`] have been sent, so that the
+/// observers never learn about changes they can't see yet.
+#[derive(Clone)]
+pub(super) struct SemanticUpdates {
+    sender: Sender<RoomEventCacheSemanticUpdate>,
+    pending: Arc<StdMutex<Vec<RoomEventCacheSemanticUpdate>>>,
+}
+
+impl SemanticUpdates {
+    pub(super) fn new() -> Self {
+        Self { sender: Sender::new(32), pending: Default::default() }
+    }
+
+    fn subscribe(&self) -> Receiver<RoomEventCacheSemanticUpdate> {
+        self.sender.subscribe()
+    }
+
+    /// Collect an update, to be sent with the next [`Self::flush`].
+    pub(super) fn push(&self, update: RoomEventCacheSemanticUpdate) {
+        self.pending.lock().unwrap().push(update);
+    }
+
+    /// Send the collected updates to the observers, if there are any.
+    pub(super) fn flush(&self) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+
+        for update in pending {
+            let _ = self.sender.send(update);
+        }
+    }
+
+    /// Drop the collected updates, because the changes they describe haven't
+    /// been sent to the subscribers.
+    pub(super) fn discard(&self) {
+        self.pending.lock().unwrap().clear();
+    }
 }
 
 /// The (non-cloneable) details of the `RoomEventCache`.
@@ -308,6 +770,14 @@ pub(super) struct RoomEventCacheInner {
     /// the storage, it doesn't handle the update from pagination. Having a
     /// clone here allows to access it from [`RoomPagination`].
     pub(super) generic_update_sender: Sender<RoomEventCacheGenericUpdate>,
+
+    /// A clone of [`EventCacheInner::generations`], to record that this room
+    /// has received new events from the sync.
+    generations: GenerationTracker,
+
+    /// A clone of the semantic updates collected by the state, to send them
+    /// once the lock on the state has been released.
+    pub(super) semantic_updates: SemanticUpdates,
 }
 
 impl RoomEventCacheInner {
@@ -320,9 +790,11 @@ impl RoomEventCacheInner {
         room_id: OwnedRoomId,
         auto_shrink_sender: mpsc::Sender<AutoShrinkChannelPayload>,
         generic_update_sender: Sender<RoomEventCacheGenericUpdate>,
+        generations: GenerationTracker,
     ) -> Self {
         let sender = Sender::new(32);
         let weak_room = WeakRoom::new(client, room_id);
+        let semantic_updates = state.semantic_updates.clone();
         Self {
             room_id: weak_room.room_id().to_owned(),
             weak_room,
@@ -332,7 +804,59 @@ impl RoomEventCacheInner {
             auto_shrink_sender,
             pagination_status,
             generic_update_sender,
+            generations,
+            semantic_updates,
+        }
+    }
+
+    /// Lock the state to create a new subscriber with it.
+    ///
+    /// If the room doesn't have any subscriber yet, events are first loaded
+    /// from the store, per
+    /// [`EventCacheConfig::subscription_preload`](super::EventCacheConfig::subscription_preload).
+    async fn lock_state_for_new_subscriber(&self) -> RwLockWriteGuard<'_, RoomEventCacheState> {
+        let mut state = self.state.write().await;
+
+        if state.subscriber_count.load(Ordering::SeqCst) == 0 {
+            if let Some(min_events) = state.subscription_preload() {
+                if let Err(err) = state.preload_events(min_events).await {
+                    warn!("couldn't preload events for the first subscriber: {err}");
+                }
+
+                // The preloaded events are part of the initial events of the subscriber, not
+                // an update.
+                self.semantic_updates.discard();
+            }
+        }
+
+        state
+    }
+
+    /// Apply the [`RetentionPolicy`] of the event cache to this room, if any
+    /// and if the room doesn't have any subscriber.
+    ///
+    /// Returns whether some events have been pruned.
+    pub(super) async fn apply_retention_policy(&self) -> Result<bool> {
+        let mut state = self.state.write().await;
+
+        let Some(policy) = state.retention_policy() else {
+            return Ok(false);
+        };
+
+        if state.subscriber_count.load(Ordering::SeqCst) > 0 {
+            return Ok(false);
         }
+
+        let Some(plan) = state.plan_retention(&policy).await? else {
+            return Ok(false);
+        };
+
+        state.apply_retention_plan(plan).await?;
+        drop(state);
+
+        self.semantic_updates.flush();
+
+        Ok(true)
     }
 
     fn handle_account_data(&self, account_data: Vec<Raw<AnyRoomAccountDataEvent>>) {
@@ -410,6 +934,8 @@ impl RoomEventCacheInner {
             return Ok(());
         }
 
+        let has_new_events = !timeline.events.is_empty();
+
         // Add all the events to the backend.
         trace!("adding new events");
 
@@ -427,6 +953,10 @@ impl RoomEventCacheInner {
         // The order matters here: first send the timeline event diffs, then only the
         // related events (read receipts, etc.).
         if !timeline_event_diffs.is_empty() {
+            if has_new_events {
+                self.generations.bump(&self.room_id);
+            }
+
             let _ = self.sender.send(RoomEventCacheUpdate::UpdateTimelineEvents {
                 diffs: timeline_event_diffs,
                 origin: EventsOrigin::Sync,
@@ -434,6 +964,8 @@ impl RoomEventCacheInner {
             update_has_been_sent = true;
         }
 
+        self.semantic_updates.flush();
+
         if !ephemeral_events.is_empty() {
             let _ = self
                 .sender
@@ -479,9 +1011,12 @@ pub(super) enum LoadMoreEventsBackwardsOutcome {
 
 // Use a private module to hide `events` to this parent module.
 mod private {
+    #[cfg(feature = "e2e-encryption")]
+    use std::collections::BTreeSet;
     use std::{
+        cmp::Ordering,
         collections::{HashMap, HashSet},
-        sync::{atomic::AtomicUsize, Arc},
+        sync::{atomic::AtomicUsize, Arc, RwLock as StdRwLock},
     };
 
     use eyeball::SharedObservable;
@@ -504,34 +1039,63 @@ mod private {
     use matrix_sdk_common::executor::spawn;
     use ruma::{
         events::{
-            relation::RelationType, room::redaction::SyncRoomRedactionEvent, AnySyncTimelineEvent,
-            MessageLikeEventType,
+            relation::RelationType,
+            room::{redaction::SyncRoomRedactionEvent, MediaSource},
+            AnySyncTimelineEvent, MessageLikeEventType,
         },
         serde::Raw,
-        EventId, OwnedEventId, OwnedRoomId, RoomVersionId,
+        EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId, OwnedUserId, RoomVersionId,
     };
+    use tokio::sync::broadcast::Sender;
     use tracing::{debug, error, instrument, trace, warn};
 
     use super::{
         super::{deduplicator::DeduplicationOutcome, EventCacheError},
         events::RoomEvents,
-        sort_positions_descending, EventLocation, LoadMoreEventsBackwardsOutcome,
+        media_sources, origin_server_ts, replace_with_merged_events, sort_positions_descending,
+        EventLocation, LoadMoreEventsBackwardsOutcome, OutOfBandPlacement, SemanticUpdates,
+        PRIMED_GAP_TOKEN_PREFIX,
     };
-    use crate::event_cache::{
-        deduplicator::filter_duplicate_events, BackPaginationOutcome, RoomPaginationStatus,
+    use crate::{
+        client::WeakClient,
+        event_cache::{
+            deduplicator::{filter_duplicate_events, merge_duplicate_event},
+            global_index::GlobalEventIndex,
+            integrity::{compare_orderings, OrderingIntegrityReport},
+            out_of_band, BackPaginationOutcome, EventCacheConfig, PrefetchConfig, RedactedMedia,
+            RetentionPolicy, RoomEventCacheSemanticUpdate, RoomMemoryLimit, RoomPaginationStatus,
+        },
     };
 
+    /// The chunks of a room to prune, according to a [`RetentionPolicy`].
+    #[derive(Debug, PartialEq)]
+    pub struct RetentionPlan {
+        /// The chunks to prune, from the most recent to the oldest.
+        pruned_chunks: Vec<ChunkIdentifier>,
+
+        /// The IDs of the events in the chunks to prune.
+        pruned_event_ids: Vec<OwnedEventId>,
+    }
+
     /// State for a single room's event cache.
     ///
     /// This contains all the inner mutable states that ought to be updated at
     /// the same time.
     pub struct RoomEventCacheState {
+        /// A weak reference to the client, to persist the pending out-of-band
+        /// events in its state store.
+        client: WeakClient,
+
         /// The room this state relates to.
         room: OwnedRoomId,
 
         /// The room version for this room.
         room_version: RoomVersionId,
 
+        /// The ID of the current user, if known, used to find out whether they
+        /// participated in a thread.
+        own_user_id: Option<OwnedUserId>,
+
         /// Reference to the underlying backing store.
         store: EventCacheStoreLock,
 
@@ -549,6 +1113,28 @@ mod private {
         /// An atomic count of the current number of subscriber of the
         /// [`super::RoomEventCache`].
         pub(super) subscriber_count: Arc<AtomicUsize>,
+
+        /// The configuration of the event cache, shared with all the rooms.
+        config: Arc<StdRwLock<EventCacheConfig>>,
+
+        /// The index of the events of all the rooms, kept up to date with the
+        /// updates of this room.
+        global_index: GlobalEventIndex,
+
+        /// The semantic updates of the events of this room, sent by the
+        /// [`super::RoomEventCacheInner`] after the matching diffs.
+        pub(super) semantic_updates: SemanticUpdates,
+
+        /// The events placed out of band whose position isn't known yet, see
+        /// [`Self::place_out_of_band_event`], in the order they were placed.
+        ///
+        /// Their IDs are persisted in the state store, and there are at most
+        /// [`out_of_band::MAX_PENDING_EVENTS_PER_ROOM`] of them.
+        pub(super) pending_out_of_band_events: Vec<Event>,
+
+        /// The sender of the media of the redacted events, see
+        /// [`crate::event_cache::EventCache::subscribe_to_redacted_media`].
+        redacted_media_sender: Sender<RedactedMedia>,
     }
 
     impl RoomEventCacheState {
@@ -561,11 +1147,17 @@ mod private {
         /// [`Self::load_more_events_backwards`].
         ///
         /// [`LinkedChunk`]: matrix_sdk_common::linked_chunk::LinkedChunk
+        #[allow(clippy::too_many_arguments)]
         pub async fn new(
+            client: WeakClient,
             room_id: OwnedRoomId,
             room_version: RoomVersionId,
+            own_user_id: Option<OwnedUserId>,
             store: EventCacheStoreLock,
             pagination_status: SharedObservable<RoomPaginationStatus>,
+            config: Arc<StdRwLock<EventCacheConfig>>,
+            global_index: GlobalEventIndex,
+            redacted_media_sender: Sender<RedactedMedia>,
         ) -> Result<Self, EventCacheError> {
             let store_lock = store.lock().await?;
 
@@ -606,17 +1198,32 @@ mod private {
             let events =
                 RoomEvents::with_initial_linked_chunk(linked_chunk, full_linked_chunk_metadata);
 
+            global_index.add_events(&room_id, events.events().map(|(_position, event)| event));
+
             Ok(Self {
+                client,
                 room: room_id,
                 room_version,
+                own_user_id,
                 store,
                 events,
                 waited_for_initial_prev_token: false,
                 subscriber_count: Default::default(),
                 pagination_status,
+                config,
+                global_index,
+                semantic_updates: SemanticUpdates::new(),
+                pending_out_of_band_events: Vec::new(),
+                redacted_media_sender,
             })
         }
 
+        /// Collect a semantic update, to be sent to the observers after the
+        /// matching diffs.
+        fn send_semantic_update(&self, update: RoomEventCacheSemanticUpdate) {
+            self.semantic_updates.push(update);
+        }
+
         /// Load a linked chunk's full metadata, making sure the chunks are
         /// according to their their links.
         ///
@@ -775,14 +1382,15 @@ mod private {
             let first_chunk_identifier =
                 self.events.chunks().next().expect("a linked chunk is never empty").identifier();
 
-            let store = self.store.lock().await?;
-
             // The first chunk is not a gap, we can load its previous chunk.
-            let linked_chunk_id = LinkedChunkId::Room(&self.room);
-            let new_first_chunk = match store
-                .load_previous_chunk(linked_chunk_id, first_chunk_identifier)
-                .await
-            {
+            let loaded = self
+                .store
+                .lock()
+                .await?
+                .load_previous_chunk(LinkedChunkId::Room(&self.room), first_chunk_identifier)
+                .await;
+
+            let new_first_chunk = match loaded {
                 Ok(Some(new_first_chunk)) => {
                     // All good, let's continue with this chunk.
                     new_first_chunk
@@ -795,12 +1403,7 @@ mod private {
 
                 Err(err) => {
                     error!("error when loading the previous chunk of a linked chunk: {err}");
-
-                    // Clear storage for this room.
-                    store.handle_linked_chunk_updates(linked_chunk_id, vec![Update::Clear]).await?;
-
-                    // Return the error.
-                    return Err(err.into());
+                    return self.recover_from_lazy_loading_failure().await;
                 }
             };
 
@@ -815,17 +1418,13 @@ mod private {
 
             if let Err(err) = self.events.insert_new_chunk_as_first(new_first_chunk) {
                 error!("error when inserting the previous chunk into its linked chunk: {err}");
-
-                // Clear storage for this room.
-                store.handle_linked_chunk_updates(linked_chunk_id, vec![Update::Clear]).await?;
-
-                // Return the error.
-                return Err(err.into());
+                return self.recover_from_lazy_loading_failure().await;
             };
 
             // ⚠️ Let's not propagate the updates to the store! We already have these data
-            // in the store! Let's drain them.
-            let _ = self.events.store_updates().take();
+            // in the store! Let's drain them, but still index the loaded events.
+            let updates = self.events.store_updates().take();
+            self.global_index.handle_updates(&self.room, &updates);
 
             // However, we want to get updates as `VectorDiff`s.
             let timeline_event_diffs = self.events.updates_as_vector_diffs();
@@ -838,6 +1437,11 @@ mod private {
 
                 ChunkContent::Items(events) => {
                     trace!(?reached_start, "reloaded chunk from disk ({} items)", events.len());
+
+                    self.send_semantic_update(RoomEventCacheSemanticUpdate::PaginatedBackwards {
+                        count: events.len(),
+                    });
+
                     LoadMoreEventsBackwardsOutcome::Events {
                         events,
                         timeline_event_diffs,
@@ -847,6 +1451,25 @@ mod private {
             })
         }
 
+        /// Recover from a failure to lazy-load a chunk from the store, which
+        /// means the persisted linked chunk of this room is corrupted.
+        ///
+        /// Only this room is affected: its linked chunk is reset, both in
+        /// memory and in the store, so that its events can be back-paginated
+        /// again from the network. The returned outcome contains no events,
+        /// and a diff update clearing the events of the observers.
+        async fn recover_from_lazy_loading_failure(
+            &mut self,
+        ) -> Result<LoadMoreEventsBackwardsOutcome, EventCacheError> {
+            let timeline_event_diffs = self.reset().await?;
+
+            Ok(LoadMoreEventsBackwardsOutcome::Events {
+                events: Vec::new(),
+                timeline_event_diffs,
+                reached_start: false,
+            })
+        }
+
         /// If storage is enabled, unload all the chunks, then reloads only the
         /// last one.
         ///
@@ -896,6 +1519,8 @@ mod private {
             // representation that we're doing this. Let's drain those store updates.
             let _ = self.events.store_updates().take();
 
+            self.send_semantic_update(RoomEventCacheSemanticUpdate::Reset);
+
             Ok(())
         }
 
@@ -919,37 +1544,350 @@ mod private {
             }
         }
 
-        #[cfg(test)]
-        pub(crate) async fn force_shrink_to_last_chunk(
+        /// Load chunks from the store until at least the given number of events
+        /// are in memory.
+        ///
+        /// Only the store is used: loading stops at the first gap, or at the
+        /// start of the timeline. The loaded events aren't sent to the
+        /// subscribers, so this must only be called when there are none.
+        pub(super) async fn preload_events(
             &mut self,
-        ) -> Result<Vec<VectorDiff<Event>>, EventCacheError> {
-            self.shrink_to_last_chunk().await?;
-            Ok(self.events.updates_as_vector_diffs())
-        }
+            min_events: usize,
+        ) -> Result<(), EventCacheError> {
+            let mut num_loaded_events = 0;
 
-        #[cfg(test)]
-        pub(crate) fn room_event_order(&self, event_pos: Position) -> Option<usize> {
-            self.events.event_order(event_pos)
+            while self.events.events().count() < min_events {
+                match self.load_more_events_backwards().await? {
+                    LoadMoreEventsBackwardsOutcome::Events { events, reached_start, .. } => {
+                        num_loaded_events += events.len();
+
+                        if reached_start {
+                            self.pagination_status
+                                .set(RoomPaginationStatus::Idle { hit_timeline_start: true });
+                            break;
+                        }
+
+                        if events.is_empty() {
+                            break;
+                        }
+                    }
+
+                    LoadMoreEventsBackwardsOutcome::StartOfTimeline => {
+                        self.pagination_status
+                            .set(RoomPaginationStatus::Idle { hit_timeline_start: true });
+                        break;
+                    }
+
+                    LoadMoreEventsBackwardsOutcome::Gap { .. }
+                    | LoadMoreEventsBackwardsOutcome::WaitForInitialPrevToken => break,
+                }
+            }
+
+            trace!(num_loaded_events, "preloaded events from the store");
+
+            Ok(())
         }
 
-        /// Removes the bundled relations from an event, if they were present.
+        /// Unload the oldest chunks from memory, until the in-memory events fit
+        /// in the [`EventCacheConfig::room_memory_limit`], if any.
         ///
-        /// Only replaces the present if it contained bundled relations.
-        fn strip_relations_if_present<T>(event: &mut Raw<T>) {
-            // We're going to get rid of the `unsigned`/`m.relations` field, if it's
-            // present.
-            // Use a closure that returns an option so we can quickly short-circuit.
-            let mut closure = || -> Option<()> {
-                let mut val: serde_json::Value = event.deserialize_as().ok()?;
-                let unsigned = val.get_mut("unsigned")?;
-                let unsigned_obj = unsigned.as_object_mut()?;
-                if unsigned_obj.remove("m.relations").is_some() {
-                    *event = Raw::new(&val).ok()?.cast();
-                }
-                None
+        /// The unloaded chunks are kept in the store, and they're lazily
+        /// reloaded by [`Self::load_more_events_backwards`]. The last chunk is
+        /// never unloaded.
+        ///
+        /// Nothing is unloaded while the room has subscribers, since they may
+        /// still be displaying the events of those chunks; like
+        /// [`Self::auto_shrink_if_no_subscribers`], this only applies to rooms
+        /// nobody is listening to.
+        async fn unload_chunks_over_memory_limit(&mut self) -> Result<(), EventCacheError> {
+            let Some(limit) = self.config.read().unwrap().room_memory_limit else {
+                return Ok(());
             };
-            let _ = closure();
-        }
+
+            let subscriber_count = self.subscriber_count.load(std::sync::atomic::Ordering::SeqCst);
+            if subscriber_count > 0 {
+                trace!(subscriber_count, "not unloading chunks, the room has subscribers");
+                return Ok(());
+            }
+
+            let chunk_size = |content: &ChunkContent<Event, Gap>| match content {
+                ChunkContent::Gap(_) => 0,
+                ChunkContent::Items(events) => match limit {
+                    RoomMemoryLimit::MaxEvents(_) => events.len(),
+                    RoomMemoryLimit::MaxBytes(_) => {
+                        events.iter().map(|event| event.raw().json().get().len()).sum()
+                    }
+                },
+            };
+
+            let max_size = match limit {
+                RoomMemoryLimit::MaxEvents(max) | RoomMemoryLimit::MaxBytes(max) => max,
+            };
+
+            let mut size: usize =
+                self.events.chunks().map(|chunk| chunk_size(chunk.content())).sum();
+
+            if size <= max_size {
+                return Ok(());
+            }
+
+            // Make sure the store knows about all the chunks before unloading any of them.
+            self.propagate_changes().await?;
+
+            let mut num_unloaded_chunks = 0;
+
+            while size > max_size {
+                let first_chunk_size = chunk_size(
+                    self.events.chunks().next().expect("a linked chunk is never empty").content(),
+                );
+
+                if self.events.unload_first_chunk().is_none() {
+                    // Only the last chunk remains.
+                    break;
+                }
+
+                size -= first_chunk_size;
+                num_unloaded_chunks += 1;
+            }
+
+            debug!(num_unloaded_chunks, "unloaded chunks over the memory limit");
+
+            // Don't propagate those updates to the store; the chunks are only unloaded from
+            // memory.
+            let _ = self.events.store_updates().take();
+
+            if num_unloaded_chunks > 0 {
+                self.send_semantic_update(RoomEventCacheSemanticUpdate::Unloaded);
+            }
+
+            // The start of the timeline isn't in memory anymore.
+            if num_unloaded_chunks > 0
+                && self.pagination_status.get()
+                    == (RoomPaginationStatus::Idle { hit_timeline_start: true })
+            {
+                self.pagination_status
+                    .set(RoomPaginationStatus::Idle { hit_timeline_start: false });
+            }
+
+            Ok(())
+        }
+
+        /// The retention policy of the event cache, if any.
+        pub(super) fn retention_policy(&self) -> Option<RetentionPolicy> {
+            self.config.read().unwrap().retention_policy
+        }
+
+        /// The minimum number of events to load for the first subscriber of the
+        /// room, if any.
+        pub(super) fn subscription_preload(&self) -> Option<usize> {
+            self.config.read().unwrap().subscription_preload
+        }
+
+        /// The prefetch configuration of the event cache, if any.
+        pub fn prefetch_config(&self) -> Option<PrefetchConfig> {
+            self.config.read().unwrap().prefetch
+        }
+
+        /// Find the chunks to prune according to the given retention policy,
+        /// by walking the linked chunk in the store from its most recent chunk.
+        ///
+        /// Only the chunks before a gap are pruned, and the gap is kept, so
+        /// that the pruned events can be back-paginated again without asking
+        /// the server for a new pagination token.
+        ///
+        /// Returns `None` if there's nothing to prune.
+        pub(super) async fn plan_retention(
+            &mut self,
+            policy: &RetentionPolicy,
+        ) -> Result<Option<RetentionPlan>, EventCacheError> {
+            // Make sure the store knows about all the chunks.
+            self.propagate_changes().await?;
+
+            let store = self.store.lock().await?;
+            let linked_chunk_id = LinkedChunkId::Room(&self.room);
+
+            let Some(mut kept_chunk) = store.load_last_chunk(linked_chunk_id).await?.0 else {
+                return Ok(None);
+            };
+
+            let cutoff = policy.max_age.map(|max_age| {
+                let now: u64 = MilliSecondsSinceUnixEpoch::now().get().into();
+                now.saturating_sub(max_age.as_millis().try_into().unwrap_or(u64::MAX))
+            });
+
+            let is_expired = |event: &Event| {
+                event
+                    .raw()
+                    .get_field::<MilliSecondsSinceUnixEpoch>("origin_server_ts")
+                    .ok()
+                    .flatten()
+                    .zip(cutoff)
+                    .is_some_and(|(ts, cutoff)| u64::from(ts.get()) < cutoff)
+            };
+
+            // Walk the chunks backwards, until finding a gap right after a chunk to prune.
+            // The most recent chunk is always kept.
+            let mut num_kept_events = 0;
+
+            let mut chunk = loop {
+                if let ChunkContent::Items(events) = &kept_chunk.content {
+                    num_kept_events += events.len();
+                }
+
+                let Some(previous) =
+                    store.load_previous_chunk(linked_chunk_id, kept_chunk.identifier).await?
+                else {
+                    // We've reached the start of the linked chunk without finding anything
+                    // to prune.
+                    return Ok(None);
+                };
+
+                let is_prunable = match &previous.content {
+                    ChunkContent::Gap(_) => false,
+                    ChunkContent::Items(events) => {
+                        policy.max_events.is_some_and(|max_events| num_kept_events >= max_events)
+                            || (!events.is_empty() && events.iter().all(&is_expired))
+                    }
+                };
+
+                if is_prunable && matches!(kept_chunk.content, ChunkContent::Gap(_)) {
+                    break previous;
+                }
+
+                kept_chunk = previous;
+            };
+
+            // Everything before the kept gap is pruned.
+            let mut pruned_chunks = Vec::new();
+            let mut pruned_event_ids = Vec::new();
+
+            loop {
+                if let ChunkContent::Items(events) = &chunk.content {
+                    pruned_event_ids.extend(events.iter().filter_map(|event| event.event_id()));
+                }
+
+                pruned_chunks.push(chunk.identifier);
+
+                match store.load_previous_chunk(linked_chunk_id, chunk.identifier).await? {
+                    Some(previous) => chunk = previous,
+                    None => break,
+                }
+            }
+
+            if pruned_event_ids.is_empty() {
+                return Ok(None);
+            }
+
+            Ok(Some(RetentionPlan { pruned_chunks, pruned_event_ids }))
+        }
+
+        /// Prune the chunks of the given [`RetentionPlan`], along with their
+        /// events, from the store, and shrink the room to its last chunk.
+        pub(super) async fn apply_retention_plan(
+            &mut self,
+            plan: RetentionPlan,
+        ) -> Result<(), EventCacheError> {
+            // The pruned chunks come before a gap which is kept, so they're not loaded
+            // anymore once the room only has its last chunk in memory.
+            self.shrink_to_last_chunk().await?;
+
+            self.apply_store_only_updates(
+                plan.pruned_chunks.into_iter().map(Update::RemoveChunk).collect(),
+            )
+            .await?;
+
+            // Removing the chunks doesn't remove their events.
+            self.store
+                .lock()
+                .await?
+                .remove_events(&self.room, plan.pruned_event_ids.clone())
+                .await?;
+
+            for event_id in &plan.pruned_event_ids {
+                self.global_index.remove_event(event_id);
+            }
+
+            Ok(())
+        }
+
+        #[cfg(test)]
+        pub(crate) async fn force_shrink_to_last_chunk(
+            &mut self,
+        ) -> Result<Vec<VectorDiff<Event>>, EventCacheError> {
+            self.shrink_to_last_chunk().await?;
+            Ok(self.events.updates_as_vector_diffs())
+        }
+
+        #[cfg(test)]
+        pub(crate) fn room_event_order(&self, event_pos: Position) -> Option<usize> {
+            self.events.event_order(event_pos)
+        }
+
+        /// Compare the positions of two events in the room linked chunk.
+        ///
+        /// The events are looked up in memory first, then in the store, so
+        /// that the events which aren't loaded yet can be compared too.
+        pub async fn compare_events_positions(
+            &self,
+            a: &EventId,
+            b: &EventId,
+        ) -> Result<Option<Ordering>, EventCacheError> {
+            if let Some(ordering) = self.events.compare_events_positions(a, b) {
+                return Ok(Some(ordering));
+            }
+
+            let (Some(a), Some(b)) =
+                (self.event_order_by_id(a).await?, self.event_order_by_id(b).await?)
+            else {
+                return Ok(None);
+            };
+
+            Ok(Some(a.cmp(&b)))
+        }
+
+        /// Return the order of an event in the room linked chunk, whether it's
+        /// loaded in memory or only in the store.
+        async fn event_order_by_id(
+            &self,
+            event_id: &EventId,
+        ) -> Result<Option<usize>, EventCacheError> {
+            let position = match self.events.event_position(event_id) {
+                Some(position) => Some(position),
+                None => {
+                    let store = self.store.lock().await?;
+                    store
+                        .filter_duplicated_events(
+                            LinkedChunkId::Room(&self.room),
+                            vec![event_id.to_owned()],
+                        )
+                        .await?
+                        .into_iter()
+                        .next()
+                        .map(|(_event_id, position)| position)
+                }
+            };
+
+            Ok(position.and_then(|position| self.events.event_order(position)))
+        }
+
+        /// Removes the bundled relations from an event, if they were present.
+        ///
+        /// Only replaces the present if it contained bundled relations.
+        fn strip_relations_if_present<T>(event: &mut Raw<T>) {
+            // We're going to get rid of the `unsigned`/`m.relations` field, if it's
+            // present.
+            // Use a closure that returns an option so we can quickly short-circuit.
+            let mut closure = || -> Option<()> {
+                let mut val: serde_json::Value = event.deserialize_as().ok()?;
+                let unsigned = val.get_mut("unsigned")?;
+                let unsigned_obj = unsigned.as_object_mut()?;
+                if unsigned_obj.remove("m.relations").is_some() {
+                    *event = Raw::new(&val).ok()?.cast();
+                }
+                None
+            };
+            let _ = closure();
+        }
 
         fn strip_relations_from_event(ev: &mut Event) {
             match &mut ev.kind {
@@ -1020,6 +1958,57 @@ mod private {
             self.propagate_changes().await
         }
 
+        /// Merge the new copies of duplicated events with the copies already
+        /// known by the event cache, see [`merge_duplicate_event`].
+        ///
+        /// Returns the location of the known copy along with the merged event,
+        /// for each event whose new copy brings something new.
+        async fn merge_duplicated_events<'a>(
+            &self,
+            events: &[Event],
+            duplicated_event_ids: impl IntoIterator<Item = &'a OwnedEventId>,
+        ) -> Result<Vec<(EventLocation, Event)>, EventCacheError> {
+            let mut merged_events = Vec::new();
+
+            for event_id in duplicated_event_ids {
+                let Some(new) =
+                    events.iter().find(|event| event.event_id().as_ref() == Some(event_id))
+                else {
+                    continue;
+                };
+
+                let Some((location, existing)) = self.find_indexed_event(event_id).await? else {
+                    continue;
+                };
+
+                if let Some(merged) = merge_duplicate_event(&existing, new.clone()) {
+                    merged_events.push((location, merged));
+                }
+            }
+
+            Ok(merged_events)
+        }
+
+        /// Replace in place the known copies of duplicated events by their
+        /// merged version, as returned by [`Self::merge_duplicated_events`].
+        async fn replace_duplicated_events(
+            &mut self,
+            merged_events: Vec<(EventLocation, Event)>,
+        ) -> Result<(), EventCacheError> {
+            for (location, event) in merged_events {
+                let event_id = event.event_id();
+                let is_in_memory = matches!(location, EventLocation::Memory(_));
+
+                self.replace_event_at(location, event).await?;
+
+                if let Some(event_id) = event_id.filter(|_| is_in_memory) {
+                    self.send_semantic_update(RoomEventCacheSemanticUpdate::Replaced { event_id });
+                }
+            }
+
+            Ok(())
+        }
+
         /// Propagate changes to the underlying storage.
         async fn propagate_changes(&mut self) -> Result<(), EventCacheError> {
             let updates = self.events.store_updates().take();
@@ -1048,6 +2037,8 @@ mod private {
                 return Ok(());
             }
 
+            self.global_index.handle_updates(&self.room, &updates);
+
             // Strip relations from updates which insert or replace items.
             for update in updates.iter_mut() {
                 match update {
@@ -1120,6 +2111,8 @@ mod private {
             // TODO: likely must cancel any ongoing back-paginations too
             self.pagination_status.set(RoomPaginationStatus::Idle { hit_timeline_start: false });
 
+            self.send_semantic_update(RoomEventCacheSemanticUpdate::Reset);
+
             Ok(())
         }
 
@@ -1152,6 +2145,37 @@ mod private {
                 .map(|event| (EventLocation::Store, event)))
         }
 
+        /// Find an event of the room's linked chunk, using the position
+        /// indexed by the store rather than scanning the loaded events.
+        ///
+        /// Unlike [`Self::find_event`], the events only saved in the store,
+        /// outside of the linked chunk, aren't found.
+        async fn find_indexed_event(
+            &self,
+            event_id: &EventId,
+        ) -> Result<Option<(EventLocation, Event)>, EventCacheError> {
+            let store = self.store.lock().await?;
+
+            let Some(position) =
+                store.position_of_event(LinkedChunkId::Room(&self.room), event_id).await?
+            else {
+                return Ok(None);
+            };
+
+            if let Some(event) = self
+                .events
+                .event_at(position)
+                .filter(|event| event.event_id().as_deref() == Some(event_id))
+            {
+                return Ok(Some((EventLocation::Memory(position), event.clone())));
+            }
+
+            Ok(store
+                .find_event(&self.room, event_id)
+                .await?
+                .map(|event| (EventLocation::Store, event)))
+        }
+
         /// Find an event and all its relations in the persisted storage.
         ///
         /// This goes straight to the database, as a simplification; we don't
@@ -1256,15 +2280,28 @@ mod private {
             // that field can only be present on room messages, we don't have to
             // worry about filtering out aggregation events (like
             // reactions/edits/etc.). Pretty neat, huh?
-            let num_replies = {
+            let (num_replies, sent_by_own_user) = {
                 let store_guard = &*self.store.lock().await?;
                 let related_thread_events = store_guard
                     .find_event_relations(&self.room, &thread_root, Some(&[RelationType::Thread]))
                     .await?;
-                related_thread_events.len().try_into().unwrap_or(u32::MAX)
+
+                let sent_by_own_user = self.own_user_id.as_deref().is_some_and(|own_user_id| {
+                    related_thread_events.iter().any(|event| {
+                        event.raw().get_field::<OwnedUserId>("sender").ok().flatten().as_deref()
+                            == Some(own_user_id)
+                    })
+                });
+
+                (related_thread_events.len().try_into().unwrap_or(u32::MAX), sent_by_own_user)
             };
 
             let prev_summary = target_event.thread_summary.summary();
+
+            // The bundled summary may know about in-thread events we haven't seen yet, so
+            // keep its participation flag.
+            let current_user_participated = sent_by_own_user
+                || prev_summary.is_some_and(|summary| summary.current_user_participated);
             let mut latest_reply =
                 prev_summary.as_ref().and_then(|summary| summary.latest_reply.clone());
 
@@ -1287,7 +2324,8 @@ mod private {
                 latest_reply = event.event_id();
             }
 
-            let new_summary = ThreadSummary { num_replies, latest_reply };
+            let new_summary =
+                ThreadSummary { num_replies, latest_reply, current_user_participated };
 
             if prev_summary == Some(&new_summary) {
                 trace!("thread summary is already up-to-date");
@@ -1296,8 +2334,15 @@ mod private {
 
             // Cause an update to observers.
             target_event.thread_summary = ThreadSummaryStatus::Some(new_summary);
+            let is_in_memory = matches!(location, EventLocation::Memory(_));
             self.replace_event_at(location, target_event).await?;
 
+            if is_in_memory {
+                self.send_semantic_update(RoomEventCacheSemanticUpdate::Replaced {
+                    event_id: thread_root,
+                });
+            }
+
             Ok(())
         }
 
@@ -1329,6 +2374,66 @@ mod private {
             Ok(())
         }
 
+        /// Find the events of the room which couldn't be decrypted, in memory
+        /// or in the store.
+        ///
+        /// If `session_ids` is set, only the events encrypted with one of these
+        /// megolm sessions are returned.
+        #[cfg(feature = "e2e-encryption")]
+        pub async fn find_utd_events(
+            &self,
+            session_ids: Option<&BTreeSet<String>>,
+        ) -> Result<Vec<Event>, EventCacheError> {
+            let store = self.store.lock().await?;
+
+            let utds = store.find_utd_events(LinkedChunkId::Room(&self.room), session_ids).await?;
+
+            Ok(utds.into_iter().map(|(_position, event)| event).collect())
+        }
+
+        /// Replace in place the events which couldn't be decrypted by their
+        /// decrypted form.
+        ///
+        /// The events are looked up by their ID; the ones which aren't in the
+        /// room anymore, or which aren't undecryptable anymore, e.g. because
+        /// they've been redacted in the meantime, are skipped.
+        ///
+        /// Returns the number of replaced events, and the updates to propagate.
+        #[cfg(feature = "e2e-encryption")]
+        #[must_use = "Propagate `VectorDiff` updates via `RoomEventCacheUpdate`"]
+        pub(crate) async fn replace_decrypted_events(
+            &mut self,
+            decrypted_events: Vec<Event>,
+        ) -> Result<(usize, Vec<VectorDiff<Event>>), EventCacheError> {
+            let mut num_replaced = 0;
+
+            for mut event in decrypted_events {
+                let Some(event_id) = event.event_id() else {
+                    continue;
+                };
+
+                let Some((location, utd)) = self.find_event(&event_id).await? else {
+                    trace!(%event_id, "decrypted event isn't in the room anymore");
+                    continue;
+                };
+
+                if !matches!(utd.kind, TimelineEventKind::UnableToDecrypt { .. }) {
+                    trace!(%event_id, "event isn't undecryptable anymore");
+                    continue;
+                }
+
+                // Keep what the event cache knows about the thread of the event.
+                event.thread_summary = utd.thread_summary;
+
+                self.replace_event_at(location, event).await?;
+                num_replaced += 1;
+
+                self.send_semantic_update(RoomEventCacheSemanticUpdate::Decrypted { event_id });
+            }
+
+            Ok((num_replaced, self.events.updates_as_vector_diffs()))
+        }
+
         /// If the given event is a redaction, try to retrieve the
         /// to-be-redacted event in the chunk, and replace it by the
         /// redacted form.
@@ -1367,21 +2472,23 @@ mod private {
                 return Ok(());
             };
 
-            // Don't redact already redacted events.
-            if let Ok(deserialized) = target_event.raw().deserialize() {
-                match deserialized {
-                    AnySyncTimelineEvent::MessageLike(ev) => {
-                        if ev.is_redacted() {
-                            return Ok(());
-                        }
+            // Don't redact already redacted events, and remember the media of the event, to
+            // remove it once it's redacted.
+            let media_sources = match target_event.raw().deserialize() {
+                Ok(AnySyncTimelineEvent::MessageLike(ev)) => {
+                    if ev.is_redacted() {
+                        return Ok(());
                     }
-                    AnySyncTimelineEvent::State(ev) => {
-                        if ev.is_redacted() {
-                            return Ok(());
-                        }
+                    media_sources(&ev)
+                }
+                Ok(AnySyncTimelineEvent::State(ev)) => {
+                    if ev.is_redacted() {
+                        return Ok(());
                     }
+                    Vec::new()
                 }
-            }
+                Err(_) => Vec::new(),
+            };
 
             if let Some(redacted_event) = apply_redaction(
                 target_event.raw(),
@@ -1394,12 +2501,54 @@ mod private {
                 // - or it wasn't, and it's a plain `AnySyncTimelineEvent` in this case.
                 target_event.replace_raw(redacted_event.cast());
 
+                let is_in_memory = matches!(location, EventLocation::Memory(_));
                 self.replace_event_at(location, target_event).await?;
+
+                if is_in_memory {
+                    self.send_semantic_update(RoomEventCacheSemanticUpdate::Redacted {
+                        event_id: event_id.to_owned(),
+                    });
+                }
+
+                if !media_sources.is_empty() {
+                    self.remove_redacted_media(event_id, media_sources).await;
+                }
             }
 
             Ok(())
         }
 
+        /// Remove the media of a redacted event from the media cache, and
+        /// notify the subscribers to the redacted media.
+        async fn remove_redacted_media(&self, event_id: &EventId, sources: Vec<MediaSource>) {
+            trace!(%event_id, "removing the media of a redacted event");
+
+            match self.store.lock().await {
+                Ok(store) => {
+                    for source in &sources {
+                        let uri = match source {
+                            MediaSource::Plain(uri) => uri,
+                            MediaSource::Encrypted(file) => &file.url,
+                        };
+
+                        if let Err(err) = store.remove_media_content_for_uri(uri).await {
+                            warn!(%event_id, "failed to remove the media of a redacted event: {err}");
+                        }
+                    }
+                }
+                Err(err) => {
+                    warn!(%event_id, "couldn't lock the store to remove redacted media: {err}");
+                }
+            }
+
+            // Ignore the error, which only happens when there are no subscribers.
+            let _ = self.redacted_media_sender.send(RedactedMedia {
+                room_id: self.room.clone(),
+                event_id: event_id.to_owned(),
+                sources,
+            });
+        }
+
         /// Save a single event into the database, without notifying observers.
         ///
         /// Note: if the event was already saved as part of a linked chunk, and
@@ -1414,6 +2563,8 @@ mod private {
             let room_id = self.room.clone();
             let events = events.into_iter().collect::<Vec<_>>();
 
+            self.global_index.add_events(&room_id, &events);
+
             // Spawn a task so the save is uninterrupted by task cancellation.
             spawn(async move {
                 let store = store.lock().await?;
@@ -1443,7 +2594,7 @@ mod private {
             let mut prev_batch = timeline.prev_batch.take();
 
             let DeduplicationOutcome {
-                all_events: events,
+                all_events: mut events,
                 in_memory_duplicated_event_ids,
                 in_store_duplicated_event_ids,
                 non_empty_all_duplicates: all_duplicates,
@@ -1455,6 +2606,16 @@ mod private {
             )
             .await?;
 
+            let merged_duplicates = self
+                .merge_duplicated_events(
+                    &events,
+                    in_memory_duplicated_event_ids
+                        .iter()
+                        .chain(&in_store_duplicated_event_ids)
+                        .map(|(event_id, _)| event_id),
+                )
+                .await?;
+
             // If the timeline isn't limited, and we already knew about some past events,
             // then this definitely knows what the timeline head is (either we know
             // about all the events persisted in storage, or we have a gap
@@ -1473,10 +2634,16 @@ mod private {
 
             if all_duplicates {
                 // No new events and no gap (per the previous check), thus no need to change the
-                // room state. We're done!
-                return Ok((false, Vec::new()));
+                // room state, except for updating in place the events we received a richer
+                // copy of. We're done!
+                self.replace_duplicated_events(merged_duplicates).await?;
+                return Ok((false, self.events.updates_as_vector_diffs()));
             }
 
+            // The duplicated events are moved to the end of the timeline, make sure they
+            // don't lose anything the known copy had.
+            replace_with_merged_events(&mut events, merged_duplicates);
+
             // Remove the old duplicated events.
             //
             // We don't have to worry the removals can change the position of the existing
@@ -1510,6 +2677,12 @@ mod private {
 
             self.events.push_events(events.clone());
 
+            if !events.is_empty() {
+                self.send_semantic_update(RoomEventCacheSemanticUpdate::LiveAppended {
+                    count: events.len(),
+                });
+            }
+
             self.post_process_new_events(events, true).await?;
 
             if timeline.limited && prev_batch.is_some() {
@@ -1520,15 +2693,659 @@ mod private {
                 // We must do this *after* the above call to `.with_events_mut`, so the new
                 // events and gaps are properly persisted to storage.
                 self.shrink_to_last_chunk().await?;
+            } else {
+                self.unload_chunks_over_memory_limit().await?;
             }
 
+            self.resolve_pending_out_of_band_events().await?;
+
             let timeline_event_diffs = self.events.updates_as_vector_diffs();
 
             Ok((prev_batch.is_some(), timeline_event_diffs))
         }
 
+        /// Insert an event obtained out of band, among the loaded events,
+        /// according to its `origin_server_ts`.
+        ///
+        /// Returns `None` if an event with the same ID is already part of the
+        /// room's events, in which case nothing has been inserted.
         #[must_use = "Propagate `VectorDiff` updates via `RoomEventCacheUpdate`"]
-        pub async fn handle_backpagination(
+        pub async fn insert_out_of_band_event(
+            &mut self,
+            event: Event,
+        ) -> Result<Option<Vec<VectorDiff<Event>>>, EventCacheError> {
+            if !self.insert_out_of_band_event_without_diffs(event).await? {
+                return Ok(None);
+            }
+
+            Ok(Some(self.events.updates_as_vector_diffs()))
+        }
+
+        /// Same as [`Self::insert_out_of_band_event`], but leaves the updates
+        /// to the caller.
+        ///
+        /// Returns whether the event has been inserted.
+        async fn insert_out_of_band_event_without_diffs(
+            &mut self,
+            event: Event,
+        ) -> Result<bool, EventCacheError> {
+            let event_id = event.event_id().ok_or(EventCacheError::MissingEventId)?;
+
+            // The event received from the homeserver always wins, and this event may have
+            // been inserted before already. It may also have been saved after being
+            // fetched with `/event`, in which case it can still be inserted.
+            if self.is_in_room_events(&event_id).await? {
+                return Ok(false);
+            }
+
+            self.insert_by_timestamp(event).await?;
+
+            Ok(true)
+        }
+
+        /// Import events from a room history export, ordered from the oldest
+        /// to the most recent, among the room's events according to their
+        /// `origin_server_ts`.
+        ///
+        /// The events are marked as [imported](Event::imported). Those which
+        /// are already part of the room's events, loaded or not, are skipped.
+        /// The previous chunks are loaded from the store first, as far as the
+        /// oldest imported event, so that the events are sorted among all the
+        /// events of the room, and not only the loaded ones.
+        ///
+        /// Returns the number of imported events.
+        #[must_use = "Propagate `VectorDiff` updates via `RoomEventCacheUpdate`"]
+        pub async fn import_events(
+            &mut self,
+            events: Vec<Event>,
+        ) -> Result<(usize, Vec<VectorDiff<Event>>), EventCacheError> {
+            // Look up, in a single query, which events are already part of the room's
+            // events; an export may contain the same event more than once too.
+            let event_ids = events.iter().filter_map(Event::event_id).collect::<Vec<_>>();
+
+            let mut known_event_ids = self
+                .store
+                .lock()
+                .await?
+                .filter_duplicated_events(LinkedChunkId::Room(&self.room), event_ids)
+                .await?
+                .into_iter()
+                .map(|(event_id, _)| event_id)
+                .collect::<HashSet<_>>();
+
+            let mut events = events
+                .into_iter()
+                .filter(|event| {
+                    event.event_id().is_some_and(|event_id| known_event_ids.insert(event_id))
+                })
+                .map(|mut event| {
+                    event.imported = true;
+                    event
+                })
+                .collect::<Vec<_>>();
+
+            if events.is_empty() {
+                return Ok((0, Vec::new()));
+            }
+
+            if let Some(oldest_ts) = events.iter().filter_map(origin_server_ts).min() {
+                self.load_chunks_until(oldest_ts).await?;
+            }
+
+            // The events without a timestamp are the most recent ones.
+            events.sort_by_cached_key(|event| {
+                let ts = origin_server_ts(event);
+                (ts.is_none(), ts)
+            });
+
+            // Find where to insert the events, in a single pass over the loaded events,
+            // from the most recent to the oldest. Each event goes right after
+            // the most recent event which isn't more recent than it. `None`
+            // means the end of the room.
+            let mut insertions = Vec::<(Option<Position>, Vec<Event>)>::new();
+
+            {
+                let mut loaded_events = self.events.revents().peekable();
+                let mut more_recent_position = None;
+
+                for event in events.iter().rev() {
+                    let ts = origin_server_ts(event);
+
+                    while let Some((position, existing)) = loaded_events.peek() {
+                        let is_older = match (origin_server_ts(existing), ts) {
+                            (Some(existing_ts), Some(ts)) => existing_ts <= ts,
+                            (_, None) => true,
+                            (None, Some(_)) => false,
+                        };
+
+                        if is_older {
+                            break;
+                        }
+
+                        more_recent_position = Some(*position);
+                        loaded_events.next();
+                    }
+
+                    let position = match (loaded_events.peek(), more_recent_position) {
+                        // The event is the most recent one.
+                        (_, None) => None,
+                        // The event goes right after an older event.
+                        (Some((older_position, _)), Some(_)) => Some(Position::new(
+                            older_position.chunk_identifier(),
+                            older_position.index() + 1,
+                        )),
+                        // The event is older than all the loaded events.
+                        (None, Some(oldest_position)) => Some(oldest_position),
+                    };
+
+                    match insertions.last_mut() {
+                        Some((last_position, group)) if *last_position == position => {
+                            group.push(event.clone())
+                        }
+                        _ => insertions.push((position, vec![event.clone()])),
+                    }
+                }
+            }
+
+            // The insertion positions are sorted from the most recent to the oldest, so
+            // inserting events never moves the positions of the next insertions.
+            for (position, mut group) in insertions {
+                group.reverse();
+
+                match position {
+                    Some(position) => self
+                        .events
+                        .insert_events_at(group, position)
+                        .expect("the position is right next to a loaded event"),
+                    None => self.events.push_events(group),
+                }
+            }
+
+            let num_imported = events.len();
+            self.post_process_new_events(events, false).await?;
+
+            // Loading the previous chunks may have exceeded the memory limit.
+            self.unload_chunks_over_memory_limit().await?;
+
+            Ok((num_imported, self.events.updates_as_vector_diffs()))
+        }
+
+        /// Load the previous chunks from the store, until the loaded events
+        /// include one which isn't more recent than the given timestamp, or
+        /// until the start of the linked chunk.
+        ///
+        /// Like when paginating from the store, the loaded chunks aren't
+        /// propagated back to the store.
+        async fn load_chunks_until(
+            &mut self,
+            ts: MilliSecondsSinceUnixEpoch,
+        ) -> Result<(), EventCacheError> {
+            let is_older =
+                |event: &Event| origin_server_ts(event).is_some_and(|event_ts| event_ts <= ts);
+
+            if self.events.revents().any(|(_, event)| is_older(event)) {
+                return Ok(());
+            }
+
+            loop {
+                let first_chunk_identifier = self
+                    .events
+                    .chunks()
+                    .next()
+                    .expect("a linked chunk is never empty")
+                    .identifier();
+
+                let Some(previous_chunk) = self
+                    .store
+                    .lock()
+                    .await?
+                    .load_previous_chunk(LinkedChunkId::Room(&self.room), first_chunk_identifier)
+                    .await?
+                else {
+                    return Ok(());
+                };
+
+                let covered = matches!(
+                    &previous_chunk.content,
+                    ChunkContent::Items(events) if events.iter().any(is_older)
+                );
+
+                self.events.insert_new_chunk_as_first(previous_chunk)?;
+
+                let updates = self.events.store_updates().take();
+                self.global_index.handle_updates(&self.room, &updates);
+
+                if covered {
+                    return Ok(());
+                }
+            }
+        }
+
+        /// Place an event obtained out of band among the loaded events, with
+        /// [`Self::insert_out_of_band_event`], if both its older and more
+        /// recent neighbours are loaded, or keep it aside until its position
+        /// is known.
+        #[must_use = "Propagate `VectorDiff` updates via `RoomEventCacheUpdate`"]
+        pub async fn place_out_of_band_event(
+            &mut self,
+            event: Event,
+        ) -> Result<(OutOfBandPlacement, Vec<VectorDiff<Event>>), EventCacheError> {
+            let event_id = event.event_id().ok_or(EventCacheError::MissingEventId)?;
+
+            if self.is_pending_out_of_band_event(&event_id) {
+                return Ok((OutOfBandPlacement::AlreadyKnown, Vec::new()));
+            }
+
+            if self.has_contiguous_neighbours(&event) {
+                return Ok(match self.insert_out_of_band_event(event).await? {
+                    Some(diffs) => (OutOfBandPlacement::Inserted, diffs),
+                    None => (OutOfBandPlacement::AlreadyKnown, Vec::new()),
+                });
+            }
+
+            if self.is_in_room_events(&event_id).await? {
+                return Ok((OutOfBandPlacement::AlreadyKnown, Vec::new()));
+            }
+
+            // Save it, so it can be found by its ID in the meantime, and restored later.
+            self.save_event([event.clone()]).await?;
+            self.pending_out_of_band_events.push(event);
+
+            if self.pending_out_of_band_events.len() > out_of_band::MAX_PENDING_EVENTS_PER_ROOM {
+                let excess = self.pending_out_of_band_events.len()
+                    - out_of_band::MAX_PENDING_EVENTS_PER_ROOM;
+                self.pending_out_of_band_events.drain(..excess);
+            }
+
+            self.save_pending_out_of_band_events().await;
+
+            Ok((OutOfBandPlacement::Pending, Vec::new()))
+        }
+
+        /// Restore the pending out-of-band events persisted in the state
+        /// store, when the room is loaded.
+        ///
+        /// The events which have become part of the room's events in the
+        /// meantime, or which can't be found in the store anymore, are
+        /// dropped.
+        pub async fn restore_pending_out_of_band_events(&mut self) -> Result<(), EventCacheError> {
+            let Some(client) = self.client.get() else {
+                return Ok(());
+            };
+
+            let event_ids =
+                match out_of_band::load_pending_events(client.state_store(), &self.room).await {
+                    Ok(event_ids) => event_ids,
+                    Err(err) => {
+                        warn!("couldn't read the pending out-of-band events: {err}");
+                        return Ok(());
+                    }
+                };
+
+            let num_event_ids = event_ids.len();
+
+            for event_id in event_ids {
+                if self.is_in_room_events(&event_id).await? {
+                    continue;
+                }
+
+                if let Some(event) =
+                    self.store.lock().await?.find_event(&self.room, &event_id).await?
+                {
+                    self.pending_out_of_band_events.push(event);
+                }
+            }
+
+            if self.pending_out_of_band_events.len() != num_event_ids {
+                self.save_pending_out_of_band_events().await;
+            }
+
+            Ok(())
+        }
+
+        /// Persist the IDs of the pending out-of-band events in the state
+        /// store.
+        async fn save_pending_out_of_band_events(&self) {
+            let Some(client) = self.client.get() else {
+                return;
+            };
+
+            let event_ids =
+                self.pending_out_of_band_events.iter().filter_map(Event::event_id).collect();
+
+            if let Err(err) =
+                out_of_band::save_pending_events(client.state_store(), &self.room, event_ids).await
+            {
+                warn!("couldn't save the pending out-of-band events: {err}");
+            }
+        }
+
+        /// Insert the events with the given IDs, which have been saved in the
+        /// store by a notification process, among the loaded events according
+        /// to their `origin_server_ts`.
+        ///
+        /// Each event is preceded by a gap, since the history between the event
+        /// and the previous loaded one is unknown. The gap has no actual
+        /// token, see [`PRIMED_GAP_TOKEN_PREFIX`].
+        ///
+        /// The events which are already part of the room's events, or which
+        /// can't be found in the store, are skipped. This must be called before
+        /// the room has any subscriber, since the insertions aren't reported as
+        /// updates: the primed events are part of the initial events of the
+        /// room.
+        ///
+        /// Returns the number of events which have been inserted.
+        pub async fn prime_with_events(
+            &mut self,
+            event_ids: Vec<OwnedEventId>,
+        ) -> Result<usize, EventCacheError> {
+            let mut primed = 0;
+
+            for event_id in event_ids {
+                if self.is_in_room_events(&event_id).await? {
+                    continue;
+                }
+
+                let Some(event) =
+                    self.store.lock().await?.find_event(&self.room, &event_id).await?
+                else {
+                    continue;
+                };
+
+                self.insert_by_timestamp(event).await?;
+
+                let position = self
+                    .events
+                    .event_position(&event_id)
+                    .expect("the event has just been inserted");
+                let prev_token = format!("{PRIMED_GAP_TOKEN_PREFIX}{event_id}");
+                self.events
+                    .insert_gap_at(Gap { prev_token }, position)
+                    .expect("the position is the one of a loaded event");
+
+                primed += 1;
+            }
+
+            if primed > 0 {
+                self.propagate_changes().await?;
+            }
+
+            // Nobody has seen the events of the room yet.
+            let _ = self.events.updates_as_vector_diffs();
+
+            Ok(primed)
+        }
+
+        /// Whether the event is part of the room's events, loaded or not, as
+        /// opposed to only being saved in the store.
+        async fn is_in_room_events(&self, event_id: &EventId) -> Result<bool, EventCacheError> {
+            if self.events.revents().any(|(_, event)| event.event_id().as_deref() == Some(event_id))
+            {
+                return Ok(true);
+            }
+
+            let store = self.store.lock().await?;
+
+            Ok(store.position_of_event(LinkedChunkId::Room(&self.room), event_id).await?.is_some())
+        }
+
+        fn is_pending_out_of_band_event(&self, event_id: &EventId) -> bool {
+            self.pending_out_of_band_events
+                .iter()
+                .any(|event| event.event_id().as_deref() == Some(event_id))
+        }
+
+        /// Whether the events right before and right after the place where
+        /// the given event would be inserted, according to their
+        /// `origin_server_ts`, are both loaded, with no gap in between.
+        ///
+        /// If there's a gap, the event may belong to the missing part of the
+        /// history, so its position isn't known for sure.
+        fn has_contiguous_neighbours(&self, event: &Event) -> bool {
+            let Some(ts) = origin_server_ts(event) else {
+                return false;
+            };
+
+            // Whether there's a more recent event right after the place of the new one.
+            let mut has_more_recent = false;
+
+            for chunk in self.events.rchunks() {
+                match chunk.content() {
+                    // The more recent events seen so far are separated from the place of the new
+                    // one by a gap.
+                    ChunkContent::Gap(_) => has_more_recent = false,
+
+                    ChunkContent::Items(events) => {
+                        for existing in events.iter().rev() {
+                            if origin_server_ts(existing)
+                                .is_some_and(|existing_ts| existing_ts <= ts)
+                            {
+                                return has_more_recent;
+                            }
+
+                            has_more_recent = true;
+                        }
+                    }
+                }
+            }
+
+            false
+        }
+
+        /// Insert the pending out-of-band events whose neighbours are now
+        /// loaded, and drop those which have been received from the
+        /// homeserver in the meantime.
+        async fn resolve_pending_out_of_band_events(&mut self) -> Result<(), EventCacheError> {
+            if self.pending_out_of_band_events.is_empty() {
+                return Ok(());
+            }
+
+            let pending = std::mem::take(&mut self.pending_out_of_band_events);
+            let num_pending = pending.len();
+
+            for event in pending {
+                let Some(event_id) = event.event_id() else {
+                    continue;
+                };
+
+                if self.has_contiguous_neighbours(&event) {
+                    if self.insert_out_of_band_event_without_diffs(event).await? {
+                        trace!(%event_id, "inserted pending out-of-band event");
+                    } else {
+                        trace!(%event_id, "pending out-of-band event received from the homeserver");
+                    }
+                } else if self.is_in_room_events(&event_id).await? {
+                    trace!(%event_id, "pending out-of-band event received from the homeserver");
+                } else {
+                    self.pending_out_of_band_events.push(event);
+                }
+            }
+
+            if self.pending_out_of_band_events.len() != num_pending {
+                self.save_pending_out_of_band_events().await;
+            }
+
+            Ok(())
+        }
+
+        /// Insert an event among the loaded events, right after the most
+        /// recent event which isn't more recent than it, according to their
+        /// `origin_server_ts`.
+        async fn insert_by_timestamp(&mut self, event: Event) -> Result<(), EventCacheError> {
+            // Find the most recent event which isn't more recent than the new one, to
+            // insert the new one right after it. If all the loaded events are more recent,
+            // the new one goes before the oldest of them. `None` means the new event is the
+            // most recent one, and it's pushed at the end.
+            let mut position = None;
+
+            if let Some(new_ts) = origin_server_ts(&event) {
+                for (nth, (existing_position, existing)) in self.events.revents().enumerate() {
+                    if origin_server_ts(existing).is_some_and(|ts| ts <= new_ts) {
+                        position = (nth > 0).then(|| {
+                            Position::new(
+                                existing_position.chunk_identifier(),
+                                existing_position.index() + 1,
+                            )
+                        });
+                        break;
+                    }
+
+                    position = Some(existing_position);
+                }
+            }
+
+            match position {
+                Some(position) => self
+                    .events
+                    .insert_events_at(vec![event.clone()], position)
+                    .expect("the position is right next to a loaded event"),
+                None => self.events.push_events([event.clone()]),
+            }
+
+            self.post_process_new_events(vec![event], false).await
+        }
+
+        /// Compare the most recent loaded events with the given events of the
+        /// homeserver, ordered from the oldest to the most recent, and repair
+        /// the drift.
+        ///
+        /// The comparison is anchored on the events known by both sides: only
+        /// the range between the oldest and the most recent of them is
+        /// checked. The homeserver's events missing from that range are
+        /// inserted right before the event following them on the homeserver.
+        /// The loaded events the homeserver didn't return are reported, but
+        /// never removed, since a single page of events isn't enough to tell
+        /// they don't exist.
+        #[must_use = "Propagate `VectorDiff` updates via `RoomEventCacheUpdate`"]
+        pub async fn heal_ordering(
+            &mut self,
+            server_events: Vec<Event>,
+        ) -> Result<(OrderingIntegrityReport, Vec<VectorDiff<Event>>), EventCacheError> {
+            let server_events: Vec<_> = server_events
+                .into_iter()
+                .filter_map(|event| Some((event.event_id()?, event)))
+                .collect();
+            let server_ids: HashSet<_> =
+                server_events.iter().map(|(event_id, _)| event_id.clone()).collect();
+            let oldest_ts = server_events.first().and_then(|(_, event)| origin_server_ts(event));
+
+            // Collect the loaded events from the most recent one known by the homeserver,
+            // down to the oldest one known by the homeserver.
+            let mut cached_ids = Vec::new();
+            let mut num_anchors = 0;
+
+            for (_, event) in self.events.revents() {
+                let Some(event_id) = event.event_id() else {
+                    continue;
+                };
+
+                let is_anchor = server_ids.contains(&event_id);
+
+                if is_anchor {
+                    num_anchors += 1;
+                }
+
+                if num_anchors > 0 {
+                    cached_ids.push((event_id, is_anchor));
+                }
+
+                // All the anchors have been found, or the remaining events are older than
+                // all the homeserver's events.
+                if num_anchors == server_ids.len()
+                    || origin_server_ts(event)
+                        .zip(oldest_ts)
+                        .is_some_and(|(ts, oldest)| ts < oldest)
+                {
+                    break;
+                }
+            }
+
+            // Drop the events older than the oldest anchor.
+            while cached_ids.last().is_some_and(|(_, is_anchor)| !is_anchor) {
+                cached_ids.pop();
+            }
+
+            cached_ids.reverse();
+            let cached_ids: Vec<_> = cached_ids.into_iter().map(|(event_id, _)| event_id).collect();
+
+            // The homeserver's events in the same range.
+            let anchors: HashSet<_> = cached_ids.iter().cloned().collect();
+            let first_anchor =
+                server_events.iter().position(|(event_id, _)| anchors.contains(event_id));
+            let last_anchor =
+                server_events.iter().rposition(|(event_id, _)| anchors.contains(event_id));
+
+            let (Some(first_anchor), Some(last_anchor)) = (first_anchor, last_anchor) else {
+                // Nothing is known by both sides, so nothing can be compared.
+                return Ok((
+                    OrderingIntegrityReport { same_order: true, ..Default::default() },
+                    Vec::new(),
+                ));
+            };
+
+            let server_events = &server_events[first_anchor..=last_anchor];
+            let server_ids: Vec<_> =
+                server_events.iter().map(|(event_id, _)| event_id.clone()).collect();
+
+            let drift = compare_orderings(&cached_ids, &server_ids);
+
+            let mut report = OrderingIntegrityReport {
+                checked: server_ids.len(),
+                unconfirmed: drift.phantoms,
+                same_order: drift.same_order,
+                ..Default::default()
+            };
+
+            // Insert the missing events from the most recent one, so that the event
+            // following each of them on the homeserver is always loaded.
+            let mut inserted_events = Vec::new();
+            let mut next_event_id = None;
+
+            for (event_id, event) in server_events.iter().rev() {
+                if drift.missing.contains(event_id) && !self.is_in_room_events(event_id).await? {
+                    let position = next_event_id
+                        .and_then(|next_event_id| self.events.event_position(next_event_id))
+                        .expect("the following event is an anchor or has just been inserted");
+
+                    self.events
+                        .insert_events_at(vec![event.clone()], position)
+                        .expect("the position is the one of a loaded event");
+
+                    report.inserted.push(event_id.clone());
+                    inserted_events.push(event.clone());
+                }
+
+                next_event_id = Some(event_id);
+            }
+
+            report.inserted.reverse();
+            inserted_events.reverse();
+
+            if !inserted_events.is_empty() {
+                self.post_process_new_events(inserted_events, false).await?;
+            }
+
+            Ok((report, self.events.updates_as_vector_diffs()))
+        }
+
+        /// Remove the redundant gaps of the loaded chunks, and return how many
+        /// were removed, see [`RoomEvents::coalesce_gaps`].
+        ///
+        /// Gaps aren't visible to the subscribers, so this doesn't cause any
+        /// `VectorDiff` update.
+        pub async fn coalesce_gaps(&mut self) -> Result<usize, EventCacheError> {
+            let num_removed = self.events.coalesce_gaps();
+
+            if num_removed > 0 {
+                trace!(num_removed, "coalesced adjacent gaps");
+                self.propagate_changes().await?;
+            }
+
+            Ok(num_removed)
+        }
+
+        #[must_use = "Propagate `VectorDiff` updates via `RoomEventCacheUpdate`"]
+        pub async fn handle_backpagination(
             &mut self,
             events: Vec<Event>,
             mut new_gap: Option<Gap>,
@@ -1551,6 +3368,16 @@ mod private {
             )
             .await?;
 
+            let merged_duplicates = self
+                .merge_duplicated_events(
+                    &events,
+                    in_memory_duplicated_event_ids
+                        .iter()
+                        .chain(&in_store_duplicated_event_ids)
+                        .map(|(event_id, _)| event_id),
+                )
+                .await?;
+
             // If not all the events have been back-paginated, we need to remove the
             // previous ones, otherwise we can end up with misordered events.
             //
@@ -1565,11 +3392,15 @@ mod private {
             // all the events, in case this happens (see also #4746).
 
             if !all_duplicates {
-                // Let's forget all the previous events.
+                // Let's forget all the previous events, the new copies keep anything the known
+                // copies had.
+                replace_with_merged_events(&mut events, merged_duplicates);
                 self.remove_events(in_memory_duplicated_event_ids, in_store_duplicated_event_ids)
                     .await?;
             } else {
-                // All new events are duplicated, they can all be ignored.
+                // All new events are duplicated, they can all be ignored, once the known copies
+                // have been updated in place with anything new they bring.
+                self.replace_duplicated_events(merged_duplicates).await?;
                 events.clear();
                 // The gap can be ditched too, as it won't be useful to backpaginate any
                 // further.
@@ -1633,6 +3464,12 @@ mod private {
                 }
             }
 
+            if !reversed_events.is_empty() {
+                self.send_semantic_update(RoomEventCacheSemanticUpdate::PaginatedBackwards {
+                    count: reversed_events.len(),
+                });
+            }
+
             self.post_process_new_events(reversed_events, false).await?;
 
             // There could be an inconsistency between the network (which thinks we hit the
@@ -1661,14 +3498,57 @@ mod private {
                 reached_start
             };
 
+            self.resolve_pending_out_of_band_events().await?;
+
             let event_diffs = self.events.updates_as_vector_diffs();
-            let backpagination_outcome = BackPaginationOutcome { events, reached_start };
+            let backpagination_outcome =
+                BackPaginationOutcome { events, reached_start, history_unavailable: false };
 
             Ok((backpagination_outcome, event_diffs))
         }
     }
 }
 
+/// Replace the events which have been merged with a known copy by their merged
+/// version, see [`RoomEventCacheState::merge_duplicated_events`].
+fn replace_with_merged_events(events: &mut [Event], merged_events: Vec<(EventLocation, Event)>) {
+    for (_, merged) in merged_events {
+        let merged_event_id = merged.event_id();
+        if let Some(event) = events.iter_mut().find(|event| event.event_id() == merged_event_id) {
+            *event = merged;
+        }
+    }
+}
+
+/// Get the `origin_server_ts` of an event, if it's valid.
+fn origin_server_ts(event: &Event) -> Option<MilliSecondsSinceUnixEpoch> {
+    event.raw().get_field::<MilliSecondsSinceUnixEpoch>("origin_server_ts").ok().flatten()
+}
+
+/// Get the sources of the media attached to an event, and of its thumbnail, if
+/// any.
+fn media_sources(event: &AnySyncMessageLikeEvent) -> Vec<MediaSource> {
+    fn sources(content: &impl MediaEventContent) -> Vec<MediaSource> {
+        content.source().into_iter().chain(content.thumbnail_source()).collect()
+    }
+
+    match event {
+        AnySyncMessageLikeEvent::RoomMessage(SyncMessageLikeEvent::Original(ev)) => {
+            match &ev.content.msgtype {
+                MessageType::Audio(content) => sources(content),
+                MessageType::File(content) => sources(content),
+                MessageType::Image(content) => sources(content),
+                MessageType::Video(content) => sources(content),
+                _ => Vec::new(),
+            }
+        }
+        AnySyncMessageLikeEvent::Sticker(SyncMessageLikeEvent::Original(ev)) => {
+            sources(&ev.content)
+        }
+        _ => Vec::new(),
+    }
+}
+
 /// An enum representing where an event has been found.
 pub(super) enum EventLocation {
     /// Event lives in memory (and likely in the store!).
@@ -1940,7 +3820,11 @@ mod tests {
 
 #[cfg(all(test, not(target_family = "wasm")))] // This uses the cross-process lock, so needs time support.
 mod timed_tests {
-    use std::sync::Arc;
+    use std::{
+        cmp::Ordering::{Equal, Greater, Less},
+        sync::Arc,
+        time::Duration,
+    };
 
     use assert_matches::assert_matches;
     use assert_matches2::assert_let;
@@ -1949,7 +3833,7 @@ mod timed_tests {
     use matrix_sdk_base::{
         event_cache::{
             store::{EventCacheStore as _, MemoryStore},
-            Gap,
+            Event, Gap,
         },
         linked_chunk::{
             lazy_loader::from_all_chunks, ChunkContent, ChunkIdentifier, LinkedChunkId, Position,
@@ -1961,16 +3845,25 @@ mod timed_tests {
     use matrix_sdk_test::{async_test, event_factory::EventFactory, ALICE, BOB};
     use ruma::{
         event_id,
-        events::{AnySyncMessageLikeEvent, AnySyncTimelineEvent},
-        room_id, user_id,
+        events::{
+            room::message::RoomMessageEventContentWithoutRelation, AnySyncMessageLikeEvent,
+            AnySyncTimelineEvent,
+        },
+        room_id, user_id, EventId, MilliSecondsSinceUnixEpoch, RoomId,
     };
-    use tokio::task::yield_now;
+    use serde_json::json;
+    use tokio::{task::yield_now, time::sleep};
 
     use super::RoomEventCacheGenericUpdate;
     use crate::{
         assert_let_timeout,
-        event_cache::{room::LoadMoreEventsBackwardsOutcome, RoomEventCacheUpdate},
-        test_utils::client::MockClientBuilder,
+        event_cache::{
+            out_of_band, room::LoadMoreEventsBackwardsOutcome, EventCacheError, EventsOrigin,
+            HistoryImportSummary, OutOfBandPlacement, RetentionPolicy, RoomEventCacheUpdate,
+            RoomHistoryExport, RoomMemoryLimit, RoomPaginationStatus,
+        },
+        test_utils::client::MockClientBuilder,
+        Client,
     };
 
     #[async_test]
@@ -2275,193 +4168,86 @@ mod timed_tests {
     }
 
     #[async_test]
-    async fn test_load_from_storage() {
+    async fn test_insert_out_of_band_event() {
         let room_id = room_id!("!galette:saucisse.bzh");
-        let f = EventFactory::new().room(room_id).sender(user_id!("@ben:saucisse.bzh"));
-
-        let event_cache_store = Arc::new(MemoryStore::new());
-
-        let event_id1 = event_id!("$1");
-        let event_id2 = event_id!("$2");
-
-        let ev1 = f.text_msg("hello world").sender(*ALICE).event_id(event_id1).into_event();
-        let ev2 = f.text_msg("how's it going").sender(*BOB).event_id(event_id2).into_event();
-
-        // Prefill the store with some data.
-        event_cache_store
-            .handle_linked_chunk_updates(
-                LinkedChunkId::Room(room_id),
-                vec![
-                    // An empty items chunk.
-                    Update::NewItemsChunk {
-                        previous: None,
-                        new: ChunkIdentifier::new(0),
-                        next: None,
-                    },
-                    // A gap chunk.
-                    Update::NewGapChunk {
-                        previous: Some(ChunkIdentifier::new(0)),
-                        // Chunk IDs aren't supposed to be ordered, so use a random value here.
-                        new: ChunkIdentifier::new(42),
-                        next: None,
-                        gap: Gap { prev_token: "cheddar".to_owned() },
-                    },
-                    // Another items chunk, non-empty this time.
-                    Update::NewItemsChunk {
-                        previous: Some(ChunkIdentifier::new(42)),
-                        new: ChunkIdentifier::new(1),
-                        next: None,
-                    },
-                    Update::PushItems {
-                        at: Position::new(ChunkIdentifier::new(1), 0),
-                        items: vec![ev1.clone()],
-                    },
-                    // And another items chunk, non-empty again.
-                    Update::NewItemsChunk {
-                        previous: Some(ChunkIdentifier::new(1)),
-                        new: ChunkIdentifier::new(2),
-                        next: None,
-                    },
-                    Update::PushItems {
-                        at: Position::new(ChunkIdentifier::new(2), 0),
-                        items: vec![ev2.clone()],
-                    },
-                ],
-            )
-            .await
-            .unwrap();
+        let f = EventFactory::new().room(room_id).sender(*ALICE);
 
-        let client = MockClientBuilder::new("http://localhost".to_owned())
-            .store_config(
-                StoreConfig::new("hodlor".to_owned()).event_cache_store(event_cache_store.clone()),
-            )
-            .build()
-            .await;
+        let client = MockClientBuilder::new("http://localhost".to_owned()).build().await;
 
         let event_cache = client.event_cache();
-
-        // Don't forget to subscribe and like^W enable storage!
         event_cache.subscribe().unwrap();
 
-        // Let's check whether the generic updates are received for the initialisation.
-        let mut generic_stream = event_cache.subscribe_to_room_generic_updates();
-
         client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
         let room = client.get_room(room_id).unwrap();
 
         let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
 
-        // The room event cache has been loaded. A generic update must have been
-        // triggered.
-        assert_matches!(
-            generic_stream.recv().await,
-            Ok(RoomEventCacheGenericUpdate::TimelineUpdated { room_id: expected_room_id }) => {
-                assert_eq!(room_id, expected_room_id);
-            }
-        );
-
-        let (items, mut stream) = room_event_cache.subscribe().await;
-
-        // The initial items contain one event because only the last chunk is loaded by
-        // default.
-        assert_eq!(items.len(), 1);
-        assert_eq!(items[0].event_id().unwrap(), event_id2);
-        assert!(stream.is_empty());
+        // Receive two events from sync.
+        room_event_cache
+            .inner
+            .handle_joined_room_update(JoinedRoomUpdate {
+                timeline: Timeline {
+                    limited: false,
+                    prev_batch: None,
+                    events: vec![
+                        f.text_msg("hello").event_id(event_id!("$1")).server_ts(1000).into_event(),
+                        f.text_msg("world").event_id(event_id!("$3")).server_ts(3000).into_event(),
+                    ],
+                },
+                ..Default::default()
+            })
+            .await
+            .unwrap();
 
-        // The event cache knows only all events though, even if they aren't loaded.
-        assert!(room_event_cache.event(event_id1).await.is_some());
-        assert!(room_event_cache.event(event_id2).await.is_some());
+        let (_, mut stream) = room_event_cache.subscribe().await;
 
-        // Let's paginate to load more events.
-        room_event_cache.pagination().run_backwards_once(20).await.unwrap();
+        // An out-of-band event is inserted according to its timestamp.
+        let oob = f.text_msg("bridged").event_id(event_id!("$2")).server_ts(2000).into_event();
+        assert!(room_event_cache.insert_out_of_band_event(oob.clone()).await.unwrap());
 
         assert_let_timeout!(
-            Ok(RoomEventCacheUpdate::UpdateTimelineEvents { diffs, .. }) = stream.recv()
+            Ok(RoomEventCacheUpdate::UpdateTimelineEvents { diffs, origin: EventsOrigin::Cache }) =
+                stream.recv()
         );
         assert_eq!(diffs.len(), 1);
-        assert_matches!(&diffs[0], VectorDiff::Insert { index: 0, value: event } => {
-            assert_eq!(event.event_id().unwrap(), event_id1);
+        assert_matches!(&diffs[0], VectorDiff::Insert { index: 1, value: event } => {
+            assert_eq!(event.event_id().as_deref(), Some(event_id!("$2")));
         });
 
+        // Inserting it again is a no-op.
+        assert!(!room_event_cache.insert_out_of_band_event(oob).await.unwrap());
         assert!(stream.is_empty());
 
-        // A generic update is triggered too.
-        assert_matches!(
-            generic_stream.recv().await,
-            Ok(RoomEventCacheGenericUpdate::TimelineUpdated { room_id: expected_room_id }) => {
-                assert_eq!(expected_room_id, room_id);
-            }
-        );
-
-        // A new update with one of these events leads to deduplication.
-        let timeline = Timeline { limited: false, prev_batch: None, events: vec![ev2] };
+        // Events older or more recent than all the others go at the start or the end.
+        let oldest = f.text_msg("oldest").event_id(event_id!("$0")).server_ts(500).into_event();
+        assert!(room_event_cache.insert_out_of_band_event(oldest).await.unwrap());
+        let newest = f.text_msg("newest").event_id(event_id!("$4")).server_ts(5000).into_event();
+        assert!(room_event_cache.insert_out_of_band_event(newest).await.unwrap());
 
-        room_event_cache
-            .inner
-            .handle_joined_room_update(JoinedRoomUpdate { timeline, ..Default::default() })
+        let event_ids = room_event_cache
+            .events()
             .await
-            .unwrap();
-
-        // Just checking the generic update is correct. There is a duplicate event, so
-        // no generic changes whatsoever!
-        assert!(generic_stream.recv().now_or_never().is_none());
+            .into_iter()
+            .map(|event| event.event_id().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(event_ids, ["$0", "$1", "$2", "$3", "$4"]);
 
-        // The stream doesn't report these changes *yet*. Use the items vector given
-        // when subscribing, to check that the items correspond to their new
-        // positions. The duplicated item is removed (so it's not the first
-        // element anymore), and it's added to the back of the list.
-        let items = room_event_cache.events().await;
-        assert_eq!(items.len(), 2);
-        assert_eq!(items[0].event_id().unwrap(), event_id1);
-        assert_eq!(items[1].event_id().unwrap(), event_id2);
+        // An event without an ID can't be inserted.
+        let no_id = f.text_msg("who am I").no_event_id().into_event();
+        assert_matches!(
+            room_event_cache.insert_out_of_band_event(no_id).await,
+            Err(EventCacheError::MissingEventId)
+        );
     }
 
     #[async_test]
-    async fn test_load_from_storage_resilient_to_failure() {
-        let room_id = room_id!("!fondue:patate.ch");
-        let event_cache_store = Arc::new(MemoryStore::new());
-
-        let event = EventFactory::new()
-            .room(room_id)
-            .sender(user_id!("@ben:saucisse.bzh"))
-            .text_msg("foo")
-            .event_id(event_id!("$42"))
-            .into_event();
-
-        // Prefill the store with invalid data: two chunks that form a cycle.
-        event_cache_store
-            .handle_linked_chunk_updates(
-                LinkedChunkId::Room(room_id),
-                vec![
-                    Update::NewItemsChunk {
-                        previous: None,
-                        new: ChunkIdentifier::new(0),
-                        next: None,
-                    },
-                    Update::PushItems {
-                        at: Position::new(ChunkIdentifier::new(0), 0),
-                        items: vec![event],
-                    },
-                    Update::NewItemsChunk {
-                        previous: Some(ChunkIdentifier::new(0)),
-                        new: ChunkIdentifier::new(1),
-                        next: Some(ChunkIdentifier::new(0)),
-                    },
-                ],
-            )
-            .await
-            .unwrap();
+    async fn test_place_out_of_band_event() {
+        let room_id = room_id!("!galette:saucisse.bzh");
+        let f = EventFactory::new().room(room_id).sender(*ALICE);
 
-        let client = MockClientBuilder::new("http://localhost".to_owned())
-            .store_config(
-                StoreConfig::new("holder".to_owned()).event_cache_store(event_cache_store.clone()),
-            )
-            .build()
-            .await;
+        let client = MockClientBuilder::new("http://localhost".to_owned()).build().await;
 
         let event_cache = client.event_cache();
-
-        // Don't forget to subscribe and like^W enable storage!
         event_cache.subscribe().unwrap();
 
         client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
@@ -2469,22 +4255,95 @@ mod timed_tests {
 
         let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
 
-        let items = room_event_cache.events().await;
+        let sync_events = |events: Vec<Event>| JoinedRoomUpdate {
+            timeline: Timeline { limited: false, prev_batch: None, events },
+            ..Default::default()
+        };
+        let event_ids = || async {
+            room_event_cache
+                .events()
+                .await
+                .into_iter()
+                .map(|event| event.event_id().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
 
-        // Because the persisted content was invalid, the room store is reset: there are
-        // no events in the cache.
-        assert!(items.is_empty());
+        // Receive two events from sync.
+        room_event_cache
+            .inner
+            .handle_joined_room_update(sync_events(vec![
+                f.text_msg("hello").event_id(event_id!("$1")).server_ts(1000).into_event(),
+                f.text_msg("world").event_id(event_id!("$3")).server_ts(3000).into_event(),
+            ]))
+            .await
+            .unwrap();
 
-        // Storage doesn't contain anything. It would also be valid that it contains a
-        // single initial empty items chunk.
-        let raw_chunks =
-            event_cache_store.load_all_chunks(LinkedChunkId::Room(room_id)).await.unwrap();
-        assert!(raw_chunks.is_empty());
+        // An event between two loaded events is inserted right away.
+        let between = f.text_msg("between").event_id(event_id!("$2")).server_ts(2000).into_event();
+        assert_eq!(
+            room_event_cache.place_out_of_band_event(between).await.unwrap(),
+            OutOfBandPlacement::Inserted
+        );
+        assert_eq!(event_ids().await, ["$1", "$2", "$3"]);
+
+        // An event more recent than all the loaded events is kept aside, but can be
+        // found by its ID.
+        let newer = f.text_msg("newer").event_id(event_id!("$4")).server_ts(4000).into_event();
+        assert_eq!(
+            room_event_cache.place_out_of_band_event(newer.clone()).await.unwrap(),
+            OutOfBandPlacement::Pending
+        );
+        assert_eq!(event_ids().await, ["$1", "$2", "$3"]);
+        assert_eq!(room_event_cache.pending_out_of_band_events().await.len(), 1);
+        assert!(room_event_cache.event(event_id!("$4")).await.is_some());
+
+        // It's persisted, to be restored when the room is loaded again.
+        assert_eq!(
+            out_of_band::load_pending_events(client.state_store(), room_id).await.unwrap(),
+            [event_id!("$4").to_owned()]
+        );
+
+        assert_eq!(
+            room_event_cache.place_out_of_band_event(newer).await.unwrap(),
+            OutOfBandPlacement::AlreadyKnown
+        );
+
+        // Once a more recent event is received from sync, the pending event is inserted
+        // before it.
+        room_event_cache
+            .inner
+            .handle_joined_room_update(sync_events(vec![f
+                .text_msg("latest")
+                .event_id(event_id!("$5"))
+                .server_ts(5000)
+                .into_event()]))
+            .await
+            .unwrap();
+
+        assert_eq!(event_ids().await, ["$1", "$2", "$3", "$4", "$5"]);
+        assert!(room_event_cache.pending_out_of_band_events().await.is_empty());
+        assert!(out_of_band::load_pending_events(client.state_store(), room_id)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // When sync brings the pending event itself, the pending copy is dropped.
+        let pending = f.text_msg("pending").event_id(event_id!("$6")).server_ts(6000).into_event();
+        assert_eq!(
+            room_event_cache.place_out_of_band_event(pending.clone()).await.unwrap(),
+            OutOfBandPlacement::Pending
+        );
+
+        room_event_cache.inner.handle_joined_room_update(sync_events(vec![pending])).await.unwrap();
+
+        assert_eq!(event_ids().await, ["$1", "$2", "$3", "$4", "$5", "$6"]);
+        assert!(room_event_cache.pending_out_of_band_events().await.is_empty());
     }
 
     #[async_test]
-    async fn test_no_useless_gaps() {
+    async fn test_place_out_of_band_event_next_to_gap() {
         let room_id = room_id!("!galette:saucisse.bzh");
+        let f = EventFactory::new().room(room_id).sender(*ALICE);
 
         let client = MockClientBuilder::new("http://localhost".to_owned()).build().await;
 
@@ -2493,132 +4352,1326 @@ mod timed_tests {
 
         client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
         let room = client.get_room(room_id).unwrap();
-        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
-        let mut generic_stream = event_cache.subscribe_to_room_generic_updates();
 
-        let f = EventFactory::new().room(room_id).sender(*ALICE);
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
 
-        // Propagate an update including a limited timeline with one message and a
-        // prev-batch token.
+        // Receive an event, then another one after a gap.
         room_event_cache
             .inner
             .handle_joined_room_update(JoinedRoomUpdate {
                 timeline: Timeline {
-                    limited: true,
+                    limited: false,
+                    prev_batch: None,
+                    events: vec![f
+                        .text_msg("hello")
+                        .event_id(event_id!("$1"))
+                        .server_ts(1000)
+                        .into_event()],
+                },
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        room_event_cache
+            .inner
+            .handle_joined_room_update(JoinedRoomUpdate {
+                timeline: Timeline {
+                    limited: false,
                     prev_batch: Some("raclette".to_owned()),
-                    events: vec![f.text_msg("hey yo").into_event()],
+                    events: vec![f
+                        .text_msg("world")
+                        .event_id(event_id!("$3"))
+                        .server_ts(3000)
+                        .into_event()],
                 },
                 ..Default::default()
             })
             .await
             .unwrap();
 
-        // Just checking the generic update is correct.
-        assert_matches!(
-            generic_stream.recv().await,
-            Ok(RoomEventCacheGenericUpdate::TimelineUpdated { room_id: expected_room_id }) => {
-                assert_eq!(expected_room_id, room_id);
-            }
+        // An event between the two loaded events may belong to the gap, so it's kept
+        // aside.
+        let between = f.text_msg("between").event_id(event_id!("$2")).server_ts(2000).into_event();
+        assert_eq!(
+            room_event_cache.place_out_of_band_event(between).await.unwrap(),
+            OutOfBandPlacement::Pending
         );
+        assert_eq!(room_event_cache.events().await.len(), 2);
+
+        // The pending events are bounded: the oldest ones are dropped.
+        for i in 0..out_of_band::MAX_PENDING_EVENTS_PER_ROOM {
+            let event = f
+                .text_msg("newer")
+                .event_id(&EventId::parse(format!("$newer{i}")).unwrap())
+                .server_ts(4000 + i as u64)
+                .into_event();
+            assert_eq!(
+                room_event_cache.place_out_of_band_event(event).await.unwrap(),
+                OutOfBandPlacement::Pending
+            );
+        }
 
-        {
-            let mut state = room_event_cache.inner.state.write().await;
+        let pending = room_event_cache.pending_out_of_band_events().await;
+        assert_eq!(pending.len(), out_of_band::MAX_PENDING_EVENTS_PER_ROOM);
+        assert_eq!(pending[0].event_id().as_deref(), Some(event_id!("$newer0")));
 
-            let mut num_gaps = 0;
-            let mut num_events = 0;
+        let persisted =
+            out_of_band::load_pending_events(client.state_store(), room_id).await.unwrap();
+        assert_eq!(persisted.len(), out_of_band::MAX_PENDING_EVENTS_PER_ROOM);
+        assert!(!persisted.contains(&event_id!("$2").to_owned()));
+    }
 
-            for c in state.events().chunks() {
-                match c.content() {
-                    ChunkContent::Items(items) => num_events += items.len(),
-                    ChunkContent::Gap(_) => num_gaps += 1,
-                }
-            }
+    #[async_test]
+    async fn test_duplicated_events_are_replaced_in_place() {
+        let room_id = room_id!("!galette:saucisse.bzh");
+        let f = EventFactory::new().room(room_id).sender(*ALICE);
 
-            // The limited sync unloads the chunk, so it will appear as if there are only
-            // the events.
-            assert_eq!(num_gaps, 0);
-            assert_eq!(num_events, 1);
+        let client = MockClientBuilder::new("http://localhost".to_owned()).build().await;
 
-            // But if I manually reload more of the chunk, the gap will be present.
-            assert_matches!(
-                state.load_more_events_backwards().await.unwrap(),
-                LoadMoreEventsBackwardsOutcome::Gap { .. }
-            );
+        let event_cache = client.event_cache();
+        event_cache.subscribe().unwrap();
 
-            num_gaps = 0;
-            num_events = 0;
-            for c in state.events().chunks() {
-                match c.content() {
-                    ChunkContent::Items(items) => num_events += items.len(),
-                    ChunkContent::Gap(_) => num_gaps += 1,
-                }
-            }
+        client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
 
-            // The gap must have been stored.
-            assert_eq!(num_gaps, 1);
-            assert_eq!(num_events, 1);
-        }
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
 
-        // Now, propagate an update for another message, but the timeline isn't limited
-        // this time.
+        // Receive two events from sync.
+        let timeline = |events| JoinedRoomUpdate {
+            timeline: Timeline { limited: false, prev_batch: None, events },
+            ..Default::default()
+        };
+
+        room_event_cache
+            .inner
+            .handle_joined_room_update(timeline(vec![
+                f.text_msg("hello").event_id(event_id!("$1")).server_ts(1000).into_event(),
+                f.text_msg("world").event_id(event_id!("$2")).into_event(),
+            ]))
+            .await
+            .unwrap();
+
+        let (_, mut stream) = room_event_cache.subscribe().await;
+
+        // Receiving the exact same event again doesn't change anything.
+        room_event_cache
+            .inner
+            .handle_joined_room_update(timeline(vec![f
+                .text_msg("hello")
+                .event_id(event_id!("$1"))
+                .server_ts(1000)
+                .into_event()]))
+            .await
+            .unwrap();
+        assert!(stream.is_empty());
+
+        // Receiving a richer copy of the first event replaces it in place, instead of
+        // moving it to the end of the timeline.
+        let edit = f
+            .text_msg("* hallo")
+            .edit(event_id!("$1"), RoomMessageEventContentWithoutRelation::text_plain("hallo"))
+            .event_id(event_id!("$edit"))
+            .into_raw_sync();
+
+        room_event_cache
+            .inner
+            .handle_joined_room_update(timeline(vec![f
+                .text_msg("hello")
+                .event_id(event_id!("$1"))
+                .server_ts(1000)
+                .with_bundled_edit(edit)
+                .into_event()]))
+            .await
+            .unwrap();
+
+        assert_let_timeout!(
+            Ok(RoomEventCacheUpdate::UpdateTimelineEvents { diffs, .. }) = stream.recv()
+        );
+        assert_eq!(diffs.len(), 1);
+        assert_matches!(&diffs[0], VectorDiff::Set { index: 0, value: event } => {
+            assert_eq!(event.event_id().as_deref(), Some(event_id!("$1")));
+            assert!(event.raw().get_field::<serde_json::Value>("unsigned").unwrap().is_some());
+        });
+
+        // Receiving the poorer copy again keeps the bundled edit.
+        room_event_cache
+            .inner
+            .handle_joined_room_update(timeline(vec![f
+                .text_msg("hello")
+                .event_id(event_id!("$1"))
+                .server_ts(1000)
+                .into_event()]))
+            .await
+            .unwrap();
+        assert!(stream.is_empty());
+
+        let event_ids = room_event_cache
+            .events()
+            .await
+            .into_iter()
+            .map(|event| event.event_id().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(event_ids, ["$1", "$2"]);
+    }
+
+    #[async_test]
+    async fn test_import_history() {
+        let room_id = room_id!("!galette:saucisse.bzh");
+        let f = EventFactory::new().room(room_id).sender(*ALICE);
+
+        let client = MockClientBuilder::new("http://localhost".to_owned()).build().await;
+
+        let event_cache = client.event_cache();
+        event_cache.subscribe().unwrap();
+
+        client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+        // Receive two events from sync.
         room_event_cache
             .inner
             .handle_joined_room_update(JoinedRoomUpdate {
                 timeline: Timeline {
                     limited: false,
-                    prev_batch: Some("fondue".to_owned()),
-                    events: vec![f.text_msg("sup").into_event()],
+                    prev_batch: None,
+                    events: vec![
+                        f.text_msg("hello").event_id(event_id!("$1")).server_ts(1000).into_event(),
+                        f.text_msg("world").event_id(event_id!("$3")).server_ts(3000).into_event(),
+                    ],
                 },
                 ..Default::default()
             })
             .await
             .unwrap();
 
-        // Just checking the generic update is correct.
+        let (_, mut stream) = room_event_cache.subscribe().await;
+
+        let message = |event_id: Option<&str>, room_id: &str, ts: u64| {
+            let mut event = json!({
+                "type": "m.room.message",
+                "sender": *ALICE,
+                "room_id": room_id,
+                "origin_server_ts": ts,
+                "content": { "msgtype": "m.text", "body": "imported" },
+            });
+            if let Some(event_id) = event_id {
+                event["event_id"] = event_id.into();
+            }
+            event
+        };
+
+        let export = RoomHistoryExport::from_json(
+            &json!({
+                "room_name": "Galette",
+                "exported_by": *ALICE,
+                "messages": [
+                    message(Some("$0"), room_id.as_str(), 500),
+                    // Already known.
+                    message(Some("$1"), room_id.as_str(), 1000),
+                    message(Some("$2"), room_id.as_str(), 2000),
+                    // From another room.
+                    message(Some("$other"), "!crepe:saucisse.bzh", 2500),
+                    // Without an event ID.
+                    message(None, room_id.as_str(), 2600),
+                ],
+            })
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(export.room_name.as_deref(), Some("Galette"));
+
+        let summary = room_event_cache.import_history(export).await.unwrap();
+        assert_eq!(summary, HistoryImportSummary { imported: 2, skipped: 3 });
+
+        // The subscribers are notified once, with the import origin.
+        assert_let_timeout!(
+            Ok(RoomEventCacheUpdate::UpdateTimelineEvents {
+                diffs,
+                origin: EventsOrigin::Import
+            }) = stream.recv()
+        );
+        assert_eq!(diffs.len(), 2);
+        assert!(stream.is_empty());
+
+        // The imported events are sorted among the other ones.
+        let event_ids = room_event_cache
+            .events()
+            .await
+            .into_iter()
+            .map(|event| event.event_id().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(event_ids, ["$0", "$1", "$2", "$3"]);
+
+        // Only the imported events are marked as such.
+        let imported = room_event_cache
+            .events()
+            .await
+            .into_iter()
+            .map(|event| event.imported)
+            .collect::<Vec<_>>();
+        assert_eq!(imported, [true, false, true, false]);
+
+        // An invalid export is rejected.
+        assert_matches!(
+            RoomHistoryExport::from_json("{}"),
+            Err(EventCacheError::InvalidHistoryExport(_))
+        );
+    }
+
+    #[async_test]
+    async fn test_import_history_among_unloaded_events() {
+        let room_id = room_id!("!galette:saucisse.bzh");
+        let f = EventFactory::new().room(room_id).sender(*ALICE);
+
+        let event_cache_store = Arc::new(MemoryStore::new());
+
+        // Prefill the store with two chunks; only the last one is loaded at first.
+        event_cache_store
+            .handle_linked_chunk_updates(
+                LinkedChunkId::Room(room_id),
+                vec![
+                    Update::NewItemsChunk {
+                        previous: None,
+                        new: ChunkIdentifier::new(0),
+                        next: None,
+                    },
+                    Update::PushItems {
+                        at: Position::new(ChunkIdentifier::new(0), 0),
+                        items: vec![
+                            f.text_msg("a").event_id(event_id!("$1")).server_ts(1000).into_event(),
+                            f.text_msg("b").event_id(event_id!("$3")).server_ts(3000).into_event(),
+                        ],
+                    },
+                    Update::NewItemsChunk {
+                        previous: Some(ChunkIdentifier::new(0)),
+                        new: ChunkIdentifier::new(1),
+                        next: None,
+                    },
+                    Update::PushItems {
+                        at: Position::new(ChunkIdentifier::new(1), 0),
+                        items: vec![f
+                            .text_msg("c")
+                            .event_id(event_id!("$5"))
+                            .server_ts(5000)
+                            .into_event()],
+                    },
+                ],
+            )
+            .await
+            .unwrap();
+
+        let client = MockClientBuilder::new("http://localhost".to_owned())
+            .store_config(
+                StoreConfig::new("hodlor".to_owned()).event_cache_store(event_cache_store.clone()),
+            )
+            .build()
+            .await;
+
+        let event_cache = client.event_cache();
+        event_cache.subscribe().unwrap();
+
+        client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+        assert_eq!(room_event_cache.events().await.len(), 1);
+
+        let message = |event_id: &str, ts: u64| {
+            json!({
+                "type": "m.room.message",
+                "event_id": event_id,
+                "sender": *ALICE,
+                "room_id": room_id,
+                "origin_server_ts": ts,
+                "content": { "msgtype": "m.text", "body": "imported" },
+            })
+        };
+
+        let export = RoomHistoryExport::from_json(
+            &json!({
+                "messages": [
+                    message("$2", 2000),
+                    // Known, in the chunk which isn't loaded.
+                    message("$3", 3000),
+                    message("$4", 4000),
+                    // Twice in the export.
+                    message("$4", 4000),
+                    message("$6", 6000),
+                ],
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let summary = room_event_cache.import_history(export).await.unwrap();
+        assert_eq!(summary, HistoryImportSummary { imported: 3, skipped: 2 });
+
+        // The previous chunk has been loaded, so the imported events are sorted among
+        // all the events of the room.
+        let events = room_event_cache.events().await;
+        let event_ids =
+            events.iter().map(|event| event.event_id().unwrap().to_string()).collect::<Vec<_>>();
+        assert_eq!(event_ids, ["$1", "$2", "$3", "$4", "$5", "$6"]);
+
+        // The imported events are marked as such in the store too.
+        let stored = event_cache_store.find_event(room_id, event_id!("$4")).await.unwrap().unwrap();
+        assert!(stored.imported);
+        let stored = event_cache_store.find_event(room_id, event_id!("$3")).await.unwrap().unwrap();
+        assert!(!stored.imported);
+    }
+
+    #[async_test]
+    async fn test_load_from_storage() {
+        let room_id = room_id!("!galette:saucisse.bzh");
+        let f = EventFactory::new().room(room_id).sender(user_id!("@ben:saucisse.bzh"));
+
+        let event_cache_store = Arc::new(MemoryStore::new());
+
+        let event_id1 = event_id!("$1");
+        let event_id2 = event_id!("$2");
+
+        let ev1 = f.text_msg("hello world").sender(*ALICE).event_id(event_id1).into_event();
+        let ev2 = f.text_msg("how's it going").sender(*BOB).event_id(event_id2).into_event();
+
+        // Prefill the store with some data.
+        event_cache_store
+            .handle_linked_chunk_updates(
+                LinkedChunkId::Room(room_id),
+                vec![
+                    // An empty items chunk.
+                    Update::NewItemsChunk {
+                        previous: None,
+                        new: ChunkIdentifier::new(0),
+                        next: None,
+                    },
+                    // A gap chunk.
+                    Update::NewGapChunk {
+                        previous: Some(ChunkIdentifier::new(0)),
+                        // Chunk IDs aren't supposed to be ordered, so use a random value here.
+                        new: ChunkIdentifier::new(42),
+                        next: None,
+                        gap: Gap { prev_token: "cheddar".to_owned() },
+                    },
+                    // Another items chunk, non-empty this time.
+                    Update::NewItemsChunk {
+                        previous: Some(ChunkIdentifier::new(42)),
+                        new: ChunkIdentifier::new(1),
+                        next: None,
+                    },
+                    Update::PushItems {
+                        at: Position::new(ChunkIdentifier::new(1), 0),
+                        items: vec![ev1.clone()],
+                    },
+                    // And another items chunk, non-empty again.
+                    Update::NewItemsChunk {
+                        previous: Some(ChunkIdentifier::new(1)),
+                        new: ChunkIdentifier::new(2),
+                        next: None,
+                    },
+                    Update::PushItems {
+                        at: Position::new(ChunkIdentifier::new(2), 0),
+                        items: vec![ev2.clone()],
+                    },
+                ],
+            )
+            .await
+            .unwrap();
+
+        let client = MockClientBuilder::new("http://localhost".to_owned())
+            .store_config(
+                StoreConfig::new("hodlor".to_owned()).event_cache_store(event_cache_store.clone()),
+            )
+            .build()
+            .await;
+
+        let event_cache = client.event_cache();
+
+        // Don't forget to subscribe and like^W enable storage!
+        event_cache.subscribe().unwrap();
+
+        // Let's check whether the generic updates are received for the initialisation.
+        let mut generic_stream = event_cache.subscribe_to_room_generic_updates();
+
+        client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+        // The room event cache has been loaded. A generic update must have been
+        // triggered.
         assert_matches!(
             generic_stream.recv().await,
             Ok(RoomEventCacheGenericUpdate::TimelineUpdated { room_id: expected_room_id }) => {
-                assert_eq!(expected_room_id, room_id);
+                assert_eq!(room_id, expected_room_id);
             }
         );
 
-        {
-            let state = room_event_cache.inner.state.read().await;
+        let (items, mut stream) = room_event_cache.subscribe().await;
+
+        // The initial items contain one event because only the last chunk is loaded by
+        // default.
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].event_id().unwrap(), event_id2);
+        assert!(stream.is_empty());
+
+        // The event cache knows only all events though, even if they aren't loaded.
+        assert!(room_event_cache.event(event_id1).await.is_some());
+        assert!(room_event_cache.event(event_id2).await.is_some());
+
+        // Let's paginate to load more events.
+        room_event_cache.pagination().run_backwards_once(20).await.unwrap();
+
+        assert_let_timeout!(
+            Ok(RoomEventCacheUpdate::UpdateTimelineEvents { diffs, .. }) = stream.recv()
+        );
+        assert_eq!(diffs.len(), 1);
+        assert_matches!(&diffs[0], VectorDiff::Insert { index: 0, value: event } => {
+            assert_eq!(event.event_id().unwrap(), event_id1);
+        });
+
+        assert!(stream.is_empty());
+
+        // A generic update is triggered too.
+        assert_matches!(
+            generic_stream.recv().await,
+            Ok(RoomEventCacheGenericUpdate::TimelineUpdated { room_id: expected_room_id }) => {
+                assert_eq!(expected_room_id, room_id);
+            }
+        );
+
+        // A new update with one of these events leads to deduplication.
+        let timeline = Timeline { limited: false, prev_batch: None, events: vec![ev2] };
+
+        room_event_cache
+            .inner
+            .handle_joined_room_update(JoinedRoomUpdate { timeline, ..Default::default() })
+            .await
+            .unwrap();
+
+        // Just checking the generic update is correct. There is a duplicate event, so
+        // no generic changes whatsoever!
+        assert!(generic_stream.recv().now_or_never().is_none());
+
+        // The stream doesn't report these changes *yet*. Use the items vector given
+        // when subscribing, to check that the items correspond to their new
+        // positions. The duplicated item is removed (so it's not the first
+        // element anymore), and it's added to the back of the list.
+        let items = room_event_cache.events().await;
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].event_id().unwrap(), event_id1);
+        assert_eq!(items[1].event_id().unwrap(), event_id2);
+    }
+
+    #[async_test]
+    async fn test_load_from_storage_resilient_to_failure() {
+        let room_id = room_id!("!fondue:patate.ch");
+        let event_cache_store = Arc::new(MemoryStore::new());
+
+        let event = EventFactory::new()
+            .room(room_id)
+            .sender(user_id!("@ben:saucisse.bzh"))
+            .text_msg("foo")
+            .event_id(event_id!("$42"))
+            .into_event();
+
+        // Prefill the store with invalid data: two chunks that form a cycle.
+        event_cache_store
+            .handle_linked_chunk_updates(
+                LinkedChunkId::Room(room_id),
+                vec![
+                    Update::NewItemsChunk {
+                        previous: None,
+                        new: ChunkIdentifier::new(0),
+                        next: None,
+                    },
+                    Update::PushItems {
+                        at: Position::new(ChunkIdentifier::new(0), 0),
+                        items: vec![event],
+                    },
+                    Update::NewItemsChunk {
+                        previous: Some(ChunkIdentifier::new(0)),
+                        new: ChunkIdentifier::new(1),
+                        next: Some(ChunkIdentifier::new(0)),
+                    },
+                ],
+            )
+            .await
+            .unwrap();
+
+        let client = MockClientBuilder::new("http://localhost".to_owned())
+            .store_config(
+                StoreConfig::new("holder".to_owned()).event_cache_store(event_cache_store.clone()),
+            )
+            .build()
+            .await;
+
+        let event_cache = client.event_cache();
+
+        // Don't forget to subscribe and like^W enable storage!
+        event_cache.subscribe().unwrap();
+
+        client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+        let items = room_event_cache.events().await;
+
+        // Because the persisted content was invalid, the room store is reset: there are
+        // no events in the cache.
+        assert!(items.is_empty());
+
+        // Storage doesn't contain anything. It would also be valid that it contains a
+        // single initial empty items chunk.
+        let raw_chunks =
+            event_cache_store.load_all_chunks(LinkedChunkId::Room(room_id)).await.unwrap();
+        assert!(raw_chunks.is_empty());
+    }
+
+    #[async_test]
+    async fn test_no_useless_gaps() {
+        let room_id = room_id!("!galette:saucisse.bzh");
+
+        let client = MockClientBuilder::new("http://localhost".to_owned()).build().await;
+
+        let event_cache = client.event_cache();
+        event_cache.subscribe().unwrap();
+
+        client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+        let mut generic_stream = event_cache.subscribe_to_room_generic_updates();
+
+        let f = EventFactory::new().room(room_id).sender(*ALICE);
+
+        // Propagate an update including a limited timeline with one message and a
+        // prev-batch token.
+        room_event_cache
+            .inner
+            .handle_joined_room_update(JoinedRoomUpdate {
+                timeline: Timeline {
+                    limited: true,
+                    prev_batch: Some("raclette".to_owned()),
+                    events: vec![f.text_msg("hey yo").into_event()],
+                },
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        // Just checking the generic update is correct.
+        assert_matches!(
+            generic_stream.recv().await,
+            Ok(RoomEventCacheGenericUpdate::TimelineUpdated { room_id: expected_room_id }) => {
+                assert_eq!(expected_room_id, room_id);
+            }
+        );
+
+        {
+            let mut state = room_event_cache.inner.state.write().await;
+
+            let mut num_gaps = 0;
+            let mut num_events = 0;
+
+            for c in state.events().chunks() {
+                match c.content() {
+                    ChunkContent::Items(items) => num_events += items.len(),
+                    ChunkContent::Gap(_) => num_gaps += 1,
+                }
+            }
+
+            // The limited sync unloads the chunk, so it will appear as if there are only
+            // the events.
+            assert_eq!(num_gaps, 0);
+            assert_eq!(num_events, 1);
+
+            // But if I manually reload more of the chunk, the gap will be present.
+            assert_matches!(
+                state.load_more_events_backwards().await.unwrap(),
+                LoadMoreEventsBackwardsOutcome::Gap { .. }
+            );
+
+            num_gaps = 0;
+            num_events = 0;
+            for c in state.events().chunks() {
+                match c.content() {
+                    ChunkContent::Items(items) => num_events += items.len(),
+                    ChunkContent::Gap(_) => num_gaps += 1,
+                }
+            }
+
+            // The gap must have been stored.
+            assert_eq!(num_gaps, 1);
+            assert_eq!(num_events, 1);
+        }
+
+        // Now, propagate an update for another message, but the timeline isn't limited
+        // this time.
+        room_event_cache
+            .inner
+            .handle_joined_room_update(JoinedRoomUpdate {
+                timeline: Timeline {
+                    limited: false,
+                    prev_batch: Some("fondue".to_owned()),
+                    events: vec![f.text_msg("sup").into_event()],
+                },
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        // Just checking the generic update is correct.
+        assert_matches!(
+            generic_stream.recv().await,
+            Ok(RoomEventCacheGenericUpdate::TimelineUpdated { room_id: expected_room_id }) => {
+                assert_eq!(expected_room_id, room_id);
+            }
+        );
+
+        {
+            let state = room_event_cache.inner.state.read().await;
+
+            let mut num_gaps = 0;
+            let mut num_events = 0;
+
+            for c in state.events().chunks() {
+                match c.content() {
+                    ChunkContent::Items(items) => num_events += items.len(),
+                    ChunkContent::Gap(gap) => {
+                        assert_eq!(gap.prev_token, "raclette");
+                        num_gaps += 1;
+                    }
+                }
+            }
+
+            // There's only the previous gap, no new ones.
+            assert_eq!(num_gaps, 1);
+            assert_eq!(num_events, 2);
+        }
+    }
+
+    #[async_test]
+    async fn test_lazy_loading_recovers_from_corrupted_chunk() {
+        let room_id = room_id!("!fondue:patate.ch");
+        let other_room_id = room_id!("!raclette:patate.ch");
+        let event_cache_store = Arc::new(MemoryStore::new());
+
+        let f = EventFactory::new().room(room_id).sender(user_id!("@ben:saucisse.bzh"));
+
+        // Prefill the store with two valid chunks for the room, and one for another
+        // room.
+        event_cache_store
+            .handle_linked_chunk_updates(
+                LinkedChunkId::Room(room_id),
+                vec![
+                    Update::NewItemsChunk {
+                        previous: None,
+                        new: ChunkIdentifier::new(0),
+                        next: None,
+                    },
+                    Update::PushItems {
+                        at: Position::new(ChunkIdentifier::new(0), 0),
+                        items: vec![f.text_msg("hello").into_event()],
+                    },
+                    Update::NewItemsChunk {
+                        previous: Some(ChunkIdentifier::new(0)),
+                        new: ChunkIdentifier::new(1),
+                        next: None,
+                    },
+                    Update::PushItems {
+                        at: Position::new(ChunkIdentifier::new(1), 0),
+                        items: vec![f.text_msg("world").into_event()],
+                    },
+                ],
+            )
+            .await
+            .unwrap();
+
+        event_cache_store
+            .handle_linked_chunk_updates(
+                LinkedChunkId::Room(other_room_id),
+                vec![
+                    Update::NewItemsChunk {
+                        previous: None,
+                        new: ChunkIdentifier::new(0),
+                        next: None,
+                    },
+                    Update::PushItems {
+                        at: Position::new(ChunkIdentifier::new(0), 0),
+                        items: vec![f.text_msg("bonjour").room(other_room_id).into_event()],
+                    },
+                ],
+            )
+            .await
+            .unwrap();
+
+        let client = MockClientBuilder::new("http://localhost".to_owned())
+            .store_config(
+                StoreConfig::new("holder".to_owned()).event_cache_store(event_cache_store.clone()),
+            )
+            .build()
+            .await;
+
+        let event_cache = client.event_cache();
+        event_cache.subscribe().unwrap();
+
+        client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+        // Only the last chunk has been loaded.
+        assert_eq!(room_event_cache.events().await.len(), 1);
+
+        // Corrupt the first chunk, now that the room has been loaded: it contains more
+        // items than a chunk can hold.
+        event_cache_store
+            .handle_linked_chunk_updates(
+                LinkedChunkId::Room(room_id),
+                vec![Update::PushItems {
+                    at: Position::new(ChunkIdentifier::new(0), 1),
+                    items: (0..200).map(|i| f.text_msg(format!("{i}")).into_event()).collect(),
+                }],
+            )
+            .await
+            .unwrap();
+
+        // Lazy-loading the previous chunk fails, but this isn't reported as an error:
+        // the room is reset instead.
+        {
+            let mut state = room_event_cache.inner.state.write().await;
+
+            assert_let!(
+                LoadMoreEventsBackwardsOutcome::Events {
+                    events,
+                    timeline_event_diffs,
+                    reached_start: false,
+                } = state.load_more_events_backwards().await.unwrap()
+            );
+            assert!(events.is_empty());
+            assert_eq!(timeline_event_diffs.len(), 1);
+            assert_matches!(&timeline_event_diffs[0], VectorDiff::Clear);
+
+            assert_eq!(state.events().events().count(), 0);
+        }
+
+        // The persisted linked chunk of the room has been cleared…
+        let raw_chunks =
+            event_cache_store.load_all_chunks(LinkedChunkId::Room(room_id)).await.unwrap();
+        assert!(raw_chunks.iter().all(|chunk| match &chunk.content {
+            ChunkContent::Items(items) => items.is_empty(),
+            ChunkContent::Gap(_) => false,
+        }));
+
+        // …but not the one of the other room.
+        let raw_chunks =
+            event_cache_store.load_all_chunks(LinkedChunkId::Room(other_room_id)).await.unwrap();
+        assert_eq!(raw_chunks.len(), 1);
+        assert_let!(ChunkContent::Items(items) = &raw_chunks[0].content);
+        assert_eq!(items.len(), 1);
+    }
+
+    #[async_test]
+    async fn test_shrink_to_last_chunk() {
+        let room_id = room_id!("!galette:saucisse.bzh");
+
+        let client = MockClientBuilder::new("http://localhost".to_owned()).build().await;
+
+        let f = EventFactory::new().room(room_id);
+
+        let evid1 = event_id!("$1");
+        let evid2 = event_id!("$2");
+
+        let ev1 = f.text_msg("hello world").sender(*ALICE).event_id(evid1).into_event();
+        let ev2 = f.text_msg("howdy").sender(*BOB).event_id(evid2).into_event();
+
+        // Fill the event cache store with an initial linked chunk with 2 events chunks.
+        {
+            let store = client.event_cache_store();
+            let store = store.lock().await.unwrap();
+            store
+                .handle_linked_chunk_updates(
+                    LinkedChunkId::Room(room_id),
+                    vec![
+                        Update::NewItemsChunk {
+                            previous: None,
+                            new: ChunkIdentifier::new(0),
+                            next: None,
+                        },
+                        Update::PushItems {
+                            at: Position::new(ChunkIdentifier::new(0), 0),
+                            items: vec![ev1],
+                        },
+                        Update::NewItemsChunk {
+                            previous: Some(ChunkIdentifier::new(0)),
+                            new: ChunkIdentifier::new(1),
+                            next: None,
+                        },
+                        Update::PushItems {
+                            at: Position::new(ChunkIdentifier::new(1), 0),
+                            items: vec![ev2],
+                        },
+                    ],
+                )
+                .await
+                .unwrap();
+        }
+
+        let event_cache = client.event_cache();
+        event_cache.subscribe().unwrap();
+
+        client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+        // Sanity check: lazily loaded, so only includes one item at start.
+        let (events, mut stream) = room_event_cache.subscribe().await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_id().as_deref(), Some(evid2));
+        assert!(stream.is_empty());
+
+        // Force loading the full linked chunk by back-paginating.
+        let outcome = room_event_cache.pagination().run_backwards_once(20).await.unwrap();
+        assert_eq!(outcome.events.len(), 1);
+        assert_eq!(outcome.events[0].event_id().as_deref(), Some(evid1));
+        assert!(outcome.reached_start);
+
+        // We also get an update about the loading from the store.
+        assert_let_timeout!(
+            Ok(RoomEventCacheUpdate::UpdateTimelineEvents { diffs, .. }) = stream.recv()
+        );
+        assert_eq!(diffs.len(), 1);
+        assert_matches!(&diffs[0], VectorDiff::Insert { index: 0, value } => {
+            assert_eq!(value.event_id().as_deref(), Some(evid1));
+        });
+
+        assert!(stream.is_empty());
+
+        // Shrink the linked chunk to the last chunk.
+        let diffs = room_event_cache
+            .inner
+            .state
+            .write()
+            .await
+            .force_shrink_to_last_chunk()
+            .await
+            .expect("shrinking should succeed");
+
+        // We receive updates about the changes to the linked chunk.
+        assert_eq!(diffs.len(), 2);
+        assert_matches!(&diffs[0], VectorDiff::Clear);
+        assert_matches!(&diffs[1], VectorDiff::Append { values} => {
+            assert_eq!(values.len(), 1);
+            assert_eq!(values[0].event_id().as_deref(), Some(evid2));
+        });
+
+        assert!(stream.is_empty());
+
+        // When reading the events, we do get only the last one.
+        let events = room_event_cache.events().await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_id().as_deref(), Some(evid2));
+
+        // But if we back-paginate, we don't need access to network to find out about
+        // the previous event.
+        let outcome = room_event_cache.pagination().run_backwards_once(20).await.unwrap();
+        assert_eq!(outcome.events.len(), 1);
+        assert_eq!(outcome.events[0].event_id().as_deref(), Some(evid1));
+        assert!(outcome.reached_start);
+    }
+
+    #[async_test]
+    async fn test_unload_chunks_over_memory_limit() {
+        let room_id = room_id!("!galette:saucisse.bzh");
+
+        let client = MockClientBuilder::new("http://localhost".to_owned()).build().await;
+
+        let f = EventFactory::new().room(room_id);
+
+        let evid1 = event_id!("$1");
+        let evid2 = event_id!("$2");
+        let evid3 = event_id!("$3");
+
+        let ev1 = f.text_msg("hello world").sender(*ALICE).event_id(evid1).into_event();
+        let ev2 = f.text_msg("howdy").sender(*BOB).event_id(evid2).into_event();
+        let ev3 = f.text_msg("hey").sender(*ALICE).event_id(evid3).into_event();
+
+        // Fill the event cache store with an initial linked chunk with 2 events chunks.
+        {
+            let store = client.event_cache_store();
+            let store = store.lock().await.unwrap();
+            store
+                .handle_linked_chunk_updates(
+                    LinkedChunkId::Room(room_id),
+                    vec![
+                        Update::NewItemsChunk {
+                            previous: None,
+                            new: ChunkIdentifier::new(0),
+                            next: None,
+                        },
+                        Update::PushItems {
+                            at: Position::new(ChunkIdentifier::new(0), 0),
+                            items: vec![ev1],
+                        },
+                        Update::NewItemsChunk {
+                            previous: Some(ChunkIdentifier::new(0)),
+                            new: ChunkIdentifier::new(1),
+                            next: None,
+                        },
+                        Update::PushItems {
+                            at: Position::new(ChunkIdentifier::new(1), 0),
+                            items: vec![ev2],
+                        },
+                    ],
+                )
+                .await
+                .unwrap();
+        }
+
+        let event_cache = client.event_cache();
+        event_cache.subscribe().unwrap();
+        event_cache.config_mut().room_memory_limit = Some(RoomMemoryLimit::MaxEvents(2));
+
+        client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+        // Load the full linked chunk by back-paginating.
+        let outcome = room_event_cache.pagination().run_backwards_once(20).await.unwrap();
+        assert_eq!(outcome.events.len(), 1);
+        assert!(outcome.reached_start);
+        assert_eq!(room_event_cache.events().await.len(), 2);
+
+        let pagination_status = room_event_cache.pagination().status();
+        assert_eq!(
+            pagination_status.get(),
+            RoomPaginationStatus::Idle { hit_timeline_start: true }
+        );
+
+        // A new event from sync makes the room go over its memory limit.
+        room_event_cache
+            .inner
+            .handle_joined_room_update(JoinedRoomUpdate {
+                timeline: Timeline { limited: false, prev_batch: None, events: vec![ev3] },
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        // The oldest chunk has been unloaded from memory.
+        let events = room_event_cache.events().await;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event_id().as_deref(), Some(evid2));
+        assert_eq!(events[1].event_id().as_deref(), Some(evid3));
+
+        // The start of the timeline isn't in memory anymore.
+        assert_eq!(
+            pagination_status.get(),
+            RoomPaginationStatus::Idle { hit_timeline_start: false }
+        );
+
+        // But it's still in the store, so back-paginating reloads it without
+        // reaching the network.
+        let outcome = room_event_cache.pagination().run_backwards_once(20).await.unwrap();
+        assert_eq!(outcome.events.len(), 1);
+        assert_eq!(outcome.events[0].event_id().as_deref(), Some(evid1));
+        assert!(outcome.reached_start);
+    }
+
+    #[async_test]
+    async fn test_no_unload_over_memory_limit_with_subscribers() {
+        let room_id = room_id!("!galette:saucisse.bzh");
+
+        let client = MockClientBuilder::new("http://localhost".to_owned()).build().await;
+
+        let f = EventFactory::new().room(room_id);
+
+        let evid1 = event_id!("$1");
+        let evid2 = event_id!("$2");
+        let evid3 = event_id!("$3");
+
+        let ev1 = f.text_msg("hello world").sender(*ALICE).event_id(evid1).into_event();
+        let ev2 = f.text_msg("howdy").sender(*BOB).event_id(evid2).into_event();
+        let ev3 = f.text_msg("hey").sender(*ALICE).event_id(evid3).into_event();
+
+        // Fill the event cache store with an initial linked chunk with 2 events chunks.
+        {
+            let store = client.event_cache_store();
+            let store = store.lock().await.unwrap();
+            store
+                .handle_linked_chunk_updates(
+                    LinkedChunkId::Room(room_id),
+                    vec![
+                        Update::NewItemsChunk {
+                            previous: None,
+                            new: ChunkIdentifier::new(0),
+                            next: None,
+                        },
+                        Update::PushItems {
+                            at: Position::new(ChunkIdentifier::new(0), 0),
+                            items: vec![ev1],
+                        },
+                        Update::NewItemsChunk {
+                            previous: Some(ChunkIdentifier::new(0)),
+                            new: ChunkIdentifier::new(1),
+                            next: None,
+                        },
+                        Update::PushItems {
+                            at: Position::new(ChunkIdentifier::new(1), 0),
+                            items: vec![ev2],
+                        },
+                    ],
+                )
+                .await
+                .unwrap();
+        }
+
+        let event_cache = client.event_cache();
+        event_cache.subscribe().unwrap();
+        event_cache.config_mut().room_memory_limit = Some(RoomMemoryLimit::MaxEvents(2));
+
+        client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+        // Someone is displaying the room.
+        let (_, _stream) = room_event_cache.subscribe().await;
+
+        // Load the full linked chunk by back-paginating.
+        let outcome = room_event_cache.pagination().run_backwards_once(20).await.unwrap();
+        assert_eq!(outcome.events.len(), 1);
+        assert!(outcome.reached_start);
+        assert_eq!(room_event_cache.events().await.len(), 2);
+
+        // A new event from sync makes the room go over its memory limit.
+        room_event_cache
+            .inner
+            .handle_joined_room_update(JoinedRoomUpdate {
+                timeline: Timeline { limited: false, prev_batch: None, events: vec![ev3] },
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        // Nothing has been unloaded, since the room has a subscriber.
+        let events = room_event_cache.events().await;
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].event_id().as_deref(), Some(evid1));
+        assert_eq!(events[1].event_id().as_deref(), Some(evid2));
+        assert_eq!(events[2].event_id().as_deref(), Some(evid3));
+
+        assert_eq!(
+            room_event_cache.pagination().status().get(),
+            RoomPaginationStatus::Idle { hit_timeline_start: true }
+        );
+    }
+
+    #[async_test]
+    async fn test_retention_policy_prunes_oldest_chunks() {
+        let room_id = room_id!("!galette:saucisse.bzh");
+
+        let client = MockClientBuilder::new("http://localhost".to_owned()).build().await;
+
+        let f = EventFactory::new().room(room_id);
+
+        let evid1 = event_id!("$1");
+        let evid2 = event_id!("$2");
+
+        let ev1 = f.text_msg("hello world").sender(*ALICE).event_id(evid1).into_event();
+        let ev2 = f.text_msg("howdy").sender(*BOB).event_id(evid2).into_event();
+
+        // Fill the event cache store with an initial linked chunk with an events chunk,
+        // a gap, and another events chunk.
+        {
+            let store = client.event_cache_store();
+            let store = store.lock().await.unwrap();
+            store
+                .handle_linked_chunk_updates(
+                    LinkedChunkId::Room(room_id),
+                    vec![
+                        Update::NewItemsChunk {
+                            previous: None,
+                            new: ChunkIdentifier::new(0),
+                            next: None,
+                        },
+                        Update::PushItems {
+                            at: Position::new(ChunkIdentifier::new(0), 0),
+                            items: vec![ev1],
+                        },
+                        Update::NewGapChunk {
+                            previous: Some(ChunkIdentifier::new(0)),
+                            new: ChunkIdentifier::new(1),
+                            next: None,
+                            gap: Gap { prev_token: "middle".to_owned() },
+                        },
+                        Update::NewItemsChunk {
+                            previous: Some(ChunkIdentifier::new(1)),
+                            new: ChunkIdentifier::new(2),
+                            next: None,
+                        },
+                        Update::PushItems {
+                            at: Position::new(ChunkIdentifier::new(2), 0),
+                            items: vec![ev2],
+                        },
+                    ],
+                )
+                .await
+                .unwrap();
+        }
+
+        let event_cache = client.event_cache();
+        event_cache.subscribe().unwrap();
+        event_cache.config_mut().retention_policy =
+            Some(RetentionPolicy { max_age: None, max_events: Some(1) });
+
+        client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+        // The oldest events chunk is over the limit, and is followed by a gap, so it's
+        // pruned without any network request.
+        assert!(room_event_cache.inner.apply_retention_policy().await.unwrap());
+
+        let event_cache_store = client.event_cache_store().lock().await.unwrap();
+        let linked_chunk = from_all_chunks::<3, _, _>(
+            event_cache_store.load_all_chunks(LinkedChunkId::Room(room_id)).await.unwrap(),
+        )
+        .unwrap()
+        .unwrap();
+
+        let mut chunks = linked_chunk.chunks();
+
+        assert_matches!(chunks.next().unwrap().content(), ChunkContent::Gap(gap) => {
+            assert_eq!(gap.prev_token, "middle");
+        });
+        assert_matches!(chunks.next().unwrap().content(), ChunkContent::Items(events) => {
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].event_id().as_deref(), Some(evid2));
+        });
+        assert!(chunks.next().is_none());
+
+        drop(event_cache_store);
+
+        // The most recent chunk is never pruned, so there's nothing left to prune.
+        assert!(!room_event_cache.inner.apply_retention_policy().await.unwrap());
+
+        // The pruned event is gone from the cache.
+        assert!(room_event_cache.event(evid1).await.is_none());
+    }
+
+    #[async_test]
+    async fn test_retention_policy_prunes_expired_chunks() {
+        let room_id = room_id!("!galette:saucisse.bzh");
+
+        let client = MockClientBuilder::new("http://localhost".to_owned()).build().await;
+
+        let f = EventFactory::new().room(room_id).sender(*ALICE);
+
+        let evid1 = event_id!("$1");
+        let evid2 = event_id!("$2");
+
+        // The first event is from a long time ago, the second one is recent.
+        let ev1 = f.text_msg("hello world").event_id(evid1).server_ts(1_000).into_event();
+        let ev2 = f
+            .text_msg("howdy")
+            .event_id(evid2)
+            .server_ts(MilliSecondsSinceUnixEpoch::now())
+            .into_event();
+
+        // Fill the event cache store with an initial linked chunk with an events chunk,
+        // a gap, and another events chunk.
+        {
+            let store = client.event_cache_store();
+            let store = store.lock().await.unwrap();
+            store
+                .handle_linked_chunk_updates(
+                    LinkedChunkId::Room(room_id),
+                    vec![
+                        Update::NewItemsChunk {
+                            previous: None,
+                            new: ChunkIdentifier::new(0),
+                            next: None,
+                        },
+                        Update::PushItems {
+                            at: Position::new(ChunkIdentifier::new(0), 0),
+                            items: vec![ev1],
+                        },
+                        Update::NewGapChunk {
+                            previous: Some(ChunkIdentifier::new(0)),
+                            new: ChunkIdentifier::new(1),
+                            next: None,
+                            gap: Gap { prev_token: "middle".to_owned() },
+                        },
+                        Update::NewItemsChunk {
+                            previous: Some(ChunkIdentifier::new(1)),
+                            new: ChunkIdentifier::new(2),
+                            next: None,
+                        },
+                        Update::PushItems {
+                            at: Position::new(ChunkIdentifier::new(2), 0),
+                            items: vec![ev2],
+                        },
+                    ],
+                )
+                .await
+                .unwrap();
+        }
+
+        let event_cache = client.event_cache();
+        event_cache.subscribe().unwrap();
+        event_cache.config_mut().retention_policy = Some(RetentionPolicy {
+            max_age: Some(Duration::from_secs(24 * 60 * 60)),
+            max_events: None,
+        });
+
+        client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+        // The oldest events chunk has expired, and is pruned.
+        assert!(room_event_cache.inner.apply_retention_policy().await.unwrap());
 
-            let mut num_gaps = 0;
-            let mut num_events = 0;
+        let event_cache_store = client.event_cache_store().lock().await.unwrap();
+        let linked_chunk = from_all_chunks::<3, _, _>(
+            event_cache_store.load_all_chunks(LinkedChunkId::Room(room_id)).await.unwrap(),
+        )
+        .unwrap()
+        .unwrap();
 
-            for c in state.events().chunks() {
-                match c.content() {
-                    ChunkContent::Items(items) => num_events += items.len(),
-                    ChunkContent::Gap(gap) => {
-                        assert_eq!(gap.prev_token, "raclette");
-                        num_gaps += 1;
-                    }
-                }
-            }
+        let mut chunks = linked_chunk.chunks();
 
-            // There's only the previous gap, no new ones.
-            assert_eq!(num_gaps, 1);
-            assert_eq!(num_events, 2);
-        }
+        // The gap is kept, to back-paginate the pruned event again.
+        assert_matches!(chunks.next().unwrap().content(), ChunkContent::Gap(gap) => {
+            assert_eq!(gap.prev_token, "middle");
+        });
+        assert_matches!(chunks.next().unwrap().content(), ChunkContent::Items(events) => {
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].event_id().as_deref(), Some(evid2));
+        });
+        assert!(chunks.next().is_none());
+
+        // The pruned event has been removed from the store too.
+        assert!(event_cache_store.find_event(room_id, evid1).await.unwrap().is_none());
+        assert!(event_cache_store.find_event(room_id, evid2).await.unwrap().is_some());
     }
 
     #[async_test]
-    async fn test_shrink_to_last_chunk() {
+    async fn test_retention_policy_keeps_chunks_without_gap() {
         let room_id = room_id!("!galette:saucisse.bzh");
 
         let client = MockClientBuilder::new("http://localhost".to_owned()).build().await;
 
-        let f = EventFactory::new().room(room_id);
+        let f = EventFactory::new().room(room_id).sender(*ALICE);
 
         let evid1 = event_id!("$1");
         let evid2 = event_id!("$2");
 
-        let ev1 = f.text_msg("hello world").sender(*ALICE).event_id(evid1).into_event();
-        let ev2 = f.text_msg("howdy").sender(*BOB).event_id(evid2).into_event();
+        let ev1 = f.text_msg("hello world").event_id(evid1).into_event();
+        let ev2 = f.text_msg("howdy").event_id(evid2).into_event();
 
-        // Fill the event cache store with an initial linked chunk with 2 events chunks.
+        // Fill the event cache store with an initial linked chunk with 2 events chunks,
+        // and no gap.
         {
             let store = client.event_cache_store();
             let store = store.lock().await.unwrap();
@@ -2652,65 +5705,22 @@ mod timed_tests {
 
         let event_cache = client.event_cache();
         event_cache.subscribe().unwrap();
+        event_cache.config_mut().retention_policy =
+            Some(RetentionPolicy { max_age: None, max_events: Some(1) });
 
         client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
         let room = client.get_room(room_id).unwrap();
         let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
 
-        // Sanity check: lazily loaded, so only includes one item at start.
-        let (events, mut stream) = room_event_cache.subscribe().await;
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].event_id().as_deref(), Some(evid2));
-        assert!(stream.is_empty());
-
-        // Force loading the full linked chunk by back-paginating.
-        let outcome = room_event_cache.pagination().run_backwards_once(20).await.unwrap();
-        assert_eq!(outcome.events.len(), 1);
-        assert_eq!(outcome.events[0].event_id().as_deref(), Some(evid1));
-        assert!(outcome.reached_start);
-
-        // We also get an update about the loading from the store.
-        assert_let_timeout!(
-            Ok(RoomEventCacheUpdate::UpdateTimelineEvents { diffs, .. }) = stream.recv()
-        );
-        assert_eq!(diffs.len(), 1);
-        assert_matches!(&diffs[0], VectorDiff::Insert { index: 0, value } => {
-            assert_eq!(value.event_id().as_deref(), Some(evid1));
-        });
-
-        assert!(stream.is_empty());
-
-        // Shrink the linked chunk to the last chunk.
-        let diffs = room_event_cache
-            .inner
-            .state
-            .write()
-            .await
-            .force_shrink_to_last_chunk()
-            .await
-            .expect("shrinking should succeed");
-
-        // We receive updates about the changes to the linked chunk.
-        assert_eq!(diffs.len(), 2);
-        assert_matches!(&diffs[0], VectorDiff::Clear);
-        assert_matches!(&diffs[1], VectorDiff::Append { values} => {
-            assert_eq!(values.len(), 1);
-            assert_eq!(values[0].event_id().as_deref(), Some(evid2));
-        });
-
-        assert!(stream.is_empty());
+        // The oldest events chunk is over the limit, but without a gap after it, it
+        // couldn't be back-paginated again, so it's kept.
+        assert!(!room_event_cache.inner.apply_retention_policy().await.unwrap());
 
-        // When reading the events, we do get only the last one.
-        let events = room_event_cache.events().await;
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].event_id().as_deref(), Some(evid2));
+        let event_cache_store = client.event_cache_store().lock().await.unwrap();
+        let chunks = event_cache_store.load_all_chunks(LinkedChunkId::Room(room_id)).await.unwrap();
+        assert_eq!(chunks.len(), 2);
 
-        // But if we back-paginate, we don't need access to network to find out about
-        // the previous event.
-        let outcome = room_event_cache.pagination().run_backwards_once(20).await.unwrap();
-        assert_eq!(outcome.events.len(), 1);
-        assert_eq!(outcome.events[0].event_id().as_deref(), Some(evid1));
-        assert!(outcome.reached_start);
+        assert!(event_cache_store.find_event(room_id, evid1).await.unwrap().is_some());
     }
 
     #[async_test]
@@ -2848,6 +5858,85 @@ mod timed_tests {
         }
     }
 
+    #[async_test]
+    async fn test_compare_events_positions() {
+        let room_id = room_id!("!galette:saucisse.bzh");
+
+        let client = MockClientBuilder::new("http://localhost".to_owned()).build().await;
+
+        let f = EventFactory::new().room(room_id).sender(*ALICE);
+
+        let evid1 = event_id!("$1");
+        let evid2 = event_id!("$2");
+        let evid3 = event_id!("$3");
+
+        // Fill the event cache store with an initial linked chunk with 2 events chunks.
+        {
+            let store = client.event_cache_store();
+            let store = store.lock().await.unwrap();
+            store
+                .handle_linked_chunk_updates(
+                    LinkedChunkId::Room(room_id),
+                    vec![
+                        Update::NewItemsChunk {
+                            previous: None,
+                            new: ChunkIdentifier::new(0),
+                            next: None,
+                        },
+                        Update::PushItems {
+                            at: Position::new(ChunkIdentifier::new(0), 0),
+                            items: vec![
+                                f.text_msg("hello world").event_id(evid1).into_event(),
+                                f.text_msg("howdy").event_id(evid2).into_event(),
+                            ],
+                        },
+                        Update::NewItemsChunk {
+                            previous: Some(ChunkIdentifier::new(0)),
+                            new: ChunkIdentifier::new(1),
+                            next: None,
+                        },
+                        Update::PushItems {
+                            at: Position::new(ChunkIdentifier::new(1), 0),
+                            items: vec![f.text_msg("yo").event_id(evid3).into_event()],
+                        },
+                    ],
+                )
+                .await
+                .unwrap();
+        }
+
+        let event_cache = client.event_cache();
+        event_cache.subscribe().unwrap();
+
+        client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+        // Only ev3 is loaded in memory, but the other events can be compared too.
+        assert_eq!(
+            room_event_cache.compare_events_positions(evid1, evid3).await.unwrap(),
+            Some(Less)
+        );
+        assert_eq!(
+            room_event_cache.compare_events_positions(evid3, evid2).await.unwrap(),
+            Some(Greater)
+        );
+        assert_eq!(
+            room_event_cache.compare_events_positions(evid2, evid1).await.unwrap(),
+            Some(Greater)
+        );
+        assert_eq!(
+            room_event_cache.compare_events_positions(evid2, evid2).await.unwrap(),
+            Some(Equal)
+        );
+
+        // An unknown event can't be compared.
+        assert_eq!(
+            room_event_cache.compare_events_positions(evid1, event_id!("$unknown")).await.unwrap(),
+            None
+        );
+    }
+
     #[async_test]
     async fn test_auto_shrink_after_all_subscribers_are_gone() {
         let room_id = room_id!("!galette:saucisse.bzh");
@@ -2958,4 +6047,173 @@ mod timed_tests {
         assert_eq!(events3.len(), 1);
         assert_eq!(events3[0].event_id().as_deref(), Some(evid2));
     }
+
+    /// Fill the event cache store with an initial linked chunk of the given
+    /// room, with 2 events chunks containing one of the given events each.
+    async fn fill_store_with_two_chunks(client: &Client, room_id: &RoomId, ev1: Event, ev2: Event) {
+        let store = client.event_cache_store();
+        let store = store.lock().await.unwrap();
+        store
+            .handle_linked_chunk_updates(
+                LinkedChunkId::Room(room_id),
+                vec![
+                    Update::NewItemsChunk {
+                        previous: None,
+                        new: ChunkIdentifier::new(0),
+                        next: None,
+                    },
+                    Update::PushItems {
+                        at: Position::new(ChunkIdentifier::new(0), 0),
+                        items: vec![ev1],
+                    },
+                    Update::NewItemsChunk {
+                        previous: Some(ChunkIdentifier::new(0)),
+                        new: ChunkIdentifier::new(1),
+                        next: None,
+                    },
+                    Update::PushItems {
+                        at: Position::new(ChunkIdentifier::new(1), 0),
+                        items: vec![ev2],
+                    },
+                ],
+            )
+            .await
+            .unwrap();
+    }
+
+    #[async_test]
+    async fn test_preload_events_for_first_subscriber() {
+        let room_id = room_id!("!galette:saucisse.bzh");
+
+        let client = MockClientBuilder::new("http://localhost".to_owned()).build().await;
+
+        let f = EventFactory::new().room(room_id);
+
+        let evid1 = event_id!("$1");
+        let evid2 = event_id!("$2");
+
+        let ev1 = f.text_msg("hello world").sender(*ALICE).event_id(evid1).into_event();
+        let ev2 = f.text_msg("howdy").sender(*BOB).event_id(evid2).into_event();
+
+        fill_store_with_two_chunks(&client, room_id, ev1, ev2).await;
+
+        let event_cache = client.event_cache();
+        event_cache.subscribe().unwrap();
+        event_cache.config_mut().subscription_preload = Some(2);
+
+        client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+        // Only the last chunk is loaded at start.
+        assert_eq!(room_event_cache.events().await.len(), 1);
+        assert_eq!(room_event_cache.subscriber_count().await, 0);
+
+        // The first subscriber gets the preloaded events.
+        let (events, stream) = room_event_cache.subscribe().await;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event_id().as_deref(), Some(evid1));
+        assert_eq!(events[1].event_id().as_deref(), Some(evid2));
+        assert!(stream.is_empty());
+        assert_eq!(room_event_cache.subscriber_count().await, 1);
+
+        // The whole room has been loaded.
+        assert_eq!(
+            room_event_cache.pagination().status().get(),
+            RoomPaginationStatus::Idle { hit_timeline_start: true }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_auto_shrink_after_grace_period() {
+        let room_id = room_id!("!galette:saucisse.bzh");
+
+        let client = MockClientBuilder::new("http://localhost".to_owned()).build().await;
+
+        let f = EventFactory::new().room(room_id);
+
+        let evid1 = event_id!("$1");
+        let evid2 = event_id!("$2");
+
+        let ev1 = f.text_msg("hello world").sender(*ALICE).event_id(evid1).into_event();
+        let ev2 = f.text_msg("howdy").sender(*BOB).event_id(evid2).into_event();
+
+        fill_store_with_two_chunks(&client, room_id, ev1, ev2).await;
+
+        let event_cache = client.event_cache();
+        event_cache.subscribe().unwrap();
+        event_cache.config_mut().unload_grace_period = Some(Duration::from_secs(10));
+
+        client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+        // Load the full linked chunk by back-paginating.
+        let (_, stream) = room_event_cache.subscribe().await;
+        room_event_cache.pagination().run_backwards_once(20).await.unwrap();
+        assert_eq!(room_event_cache.events().await.len(), 2);
+
+        // Drop the only subscriber.
+        drop(stream);
+        yield_now().await;
+        assert_eq!(room_event_cache.subscriber_count().await, 0);
+
+        // The events are still in memory during the grace period.
+        sleep(Duration::from_secs(6)).await;
+        assert_eq!(room_event_cache.events().await.len(), 2);
+
+        // The room is subscribed to again, and dropped again: the grace period starts
+        // over.
+        let (_, stream) = room_event_cache.subscribe().await;
+        drop(stream);
+        yield_now().await;
+
+        // The events are still in memory once the first grace period is over.
+        sleep(Duration::from_secs(6)).await;
+        assert_eq!(room_event_cache.events().await.len(), 2);
+
+        // Once the second grace period is over, the linked chunk is shrunk.
+        sleep(Duration::from_secs(6)).await;
+
+        let events = room_event_cache.events().await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_id().as_deref(), Some(evid2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_no_auto_shrink_when_subscribed_again_during_grace_period() {
+        let room_id = room_id!("!galette:saucisse.bzh");
+
+        let client = MockClientBuilder::new("http://localhost".to_owned()).build().await;
+
+        let f = EventFactory::new().room(room_id);
+
+        let ev1 = f.text_msg("hello world").sender(*ALICE).event_id(event_id!("$1")).into_event();
+        let ev2 = f.text_msg("howdy").sender(*BOB).event_id(event_id!("$2")).into_event();
+
+        fill_store_with_two_chunks(&client, room_id, ev1, ev2).await;
+
+        let event_cache = client.event_cache();
+        event_cache.subscribe().unwrap();
+        event_cache.config_mut().unload_grace_period = Some(Duration::from_secs(10));
+
+        client.base_client().get_or_create_room(room_id, matrix_sdk_base::RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+        let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+        let (_, stream) = room_event_cache.subscribe().await;
+        room_event_cache.pagination().run_backwards_once(20).await.unwrap();
+        assert_eq!(room_event_cache.events().await.len(), 2);
+
+        drop(stream);
+        yield_now().await;
+
+        // The room is subscribed to again during the grace period, and kept.
+        sleep(Duration::from_secs(5)).await;
+        let (_, _stream) = room_event_cache.subscribe().await;
+
+        // The events stay in memory after the grace period.
+        sleep(Duration::from_secs(20)).await;
+        assert_eq!(room_event_cache.events().await.len(), 2);
+    }
 }
@@ -0,0 +1,224 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Snapshots of the events of a room, to quickly restore the state of a UI
+//! layer when switching back to a room.
+
+use std::sync::Arc;
+
+use eyeball_im::VectorDiff;
+use imbl::Vector;
+
+use super::room::events::{Event, Gap};
+
+/// An immutable view of the events of a room loaded in memory, at a given
+/// point in time.
+///
+/// It can be obtained with
+/// [`RoomEventCache::snapshot`](super::RoomEventCache::snapshot), kept by a UI
+/// layer along with the state it has rendered, and later passed to
+/// [`RoomEventCache::restore_from_snapshot`](super::RoomEventCache::restore_from_snapshot)
+/// to get the updates which happened in the meantime, instead of rebuilding
+/// everything from scratch.
+///
+/// Cloning is cheap, since the snapshot is shared.
+#[derive(Clone, Debug, Default)]
+pub struct RoomEventsSnapshot {
+    inner: Arc<SnapshotInner>,
+}
+
+#[derive(Debug, Default)]
+struct SnapshotInner {
+    events: Vector<Event>,
+    gaps: Vec<(usize, Gap)>,
+}
+
+impl RoomEventsSnapshot {
+    pub(super) fn new(events: Vector<Event>, gaps: Vec<(usize, Gap)>) -> Self {
+        Self { inner: Arc::new(SnapshotInner { events, gaps }) }
+    }
+
+    /// The events, from the oldest to the most recent.
+    pub fn events(&self) -> &Vector<Event> {
+        &self.inner.events
+    }
+
+    /// The gaps between the events, along with the index in
+    /// [`Self::events`] of the event following each of them.
+    pub fn gaps(&self) -> &[(usize, Gap)] {
+        &self.inner.gaps
+    }
+}
+
+/// Compute the updates to apply to the `old` events to get the `new` ones.
+///
+/// This only detects the most common changes happening while a room isn't
+/// displayed: events added at the start (back-pagination) or at the end
+/// (sync), and events replaced in place (e.g. decrypted). Any other change
+/// results in a [`VectorDiff::Reset`].
+pub(super) fn diff_events(old: &Vector<Event>, new: &Vector<Event>) -> Vec<VectorDiff<Event>> {
+    if old.is_empty() {
+        return if new.is_empty() {
+            Vec::new()
+        } else {
+            vec![VectorDiff::Append { values: new.clone() }]
+        };
+    }
+
+    let reset = || vec![VectorDiff::Reset { values: new.clone() }];
+
+    // Find where the old events start in the new events.
+    let Some(first_event_id) = old[0].event_id() else {
+        return reset();
+    };
+
+    let Some(offset) =
+        new.iter().position(|event| event.event_id().as_ref() == Some(&first_event_id))
+    else {
+        return reset();
+    };
+
+    // All the old events must still be there, in the same order.
+    if offset + old.len() > new.len()
+        || !old
+            .iter()
+            .zip(new.iter().skip(offset))
+            .all(|(old, new)| old.event_id().is_some() && old.event_id() == new.event_id())
+    {
+        return reset();
+    }
+
+    let mut diffs = Vec::new();
+
+    // Events added at the start.
+    for (index, event) in new.iter().take(offset).enumerate() {
+        diffs.push(VectorDiff::Insert { index, value: event.clone() });
+    }
+
+    // Events replaced in place.
+    for (index, (old, new)) in old.iter().zip(new.iter().skip(offset)).enumerate() {
+        if !has_same_content(old, new) {
+            diffs.push(VectorDiff::Set { index: offset + index, value: new.clone() });
+        }
+    }
+
+    // Events added at the end.
+    let appended = new.clone().split_off(offset + old.len());
+
+    if !appended.is_empty() {
+        diffs.push(VectorDiff::Append { values: appended });
+    }
+
+    diffs
+}
+
+/// Whether two events with the same ID have the same content.
+fn has_same_content(old: &Event, new: &Event) -> bool {
+    old.raw().json().get() == new.raw().json().get()
+        && old.encryption_info().is_some() == new.encryption_info().is_some()
+        && old.thread_summary == new.thread_summary
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches2::{assert_let, assert_matches};
+    use eyeball_im::VectorDiff;
+    use imbl::{vector, Vector};
+    use matrix_sdk_test::{event_factory::EventFactory, ALICE, DEFAULT_TEST_ROOM_ID};
+    use ruma::{event_id, EventId};
+
+    use super::{diff_events, Event};
+
+    fn event(event_id: &str, body: &str) -> Event {
+        EventFactory::new()
+            .room(&DEFAULT_TEST_ROOM_ID)
+            .sender(*ALICE)
+            .text_msg(body)
+            .event_id(&EventId::parse(event_id).unwrap())
+            .into_event()
+    }
+
+    fn event_ids(events: &Vector<Event>) -> Vec<String> {
+        events.iter().map(|event| event.event_id().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_diff_unchanged() {
+        let events = vector![event("$a", "a"), event("$b", "b")];
+
+        assert!(diff_events(&events, &events).is_empty());
+        assert!(diff_events(&Vector::new(), &Vector::new()).is_empty());
+    }
+
+    #[test]
+    fn test_diff_from_empty_snapshot() {
+        let new = vector![event("$a", "a")];
+
+        let diffs = diff_events(&Vector::new(), &new);
+
+        assert_eq!(diffs.len(), 1);
+        assert_let!(VectorDiff::Append { values } = &diffs[0]);
+        assert_eq!(event_ids(values), ["$a"]);
+    }
+
+    #[test]
+    fn test_diff_events_added_at_both_ends() {
+        let old = vector![event("$b", "b"), event("$c", "c")];
+        let new = vector![
+            event("$0", "0"),
+            event("$a", "a"),
+            event("$b", "b"),
+            event("$c", "c"),
+            event("$d", "d")
+        ];
+
+        let diffs = diff_events(&old, &new);
+
+        assert_eq!(diffs.len(), 3);
+        assert_let!(VectorDiff::Insert { index: 0, value } = &diffs[0]);
+        assert_eq!(value.event_id().as_deref(), Some(event_id!("$0")));
+        assert_let!(VectorDiff::Insert { index: 1, value } = &diffs[1]);
+        assert_eq!(value.event_id().as_deref(), Some(event_id!("$a")));
+        assert_let!(VectorDiff::Append { values } = &diffs[2]);
+        assert_eq!(event_ids(values), ["$d"]);
+    }
+
+    #[test]
+    fn test_diff_event_replaced() {
+        let old = vector![event("$a", "a"), event("$b", "b")];
+        let new = vector![event("$a", "a"), event("$b", "b, edited")];
+
+        let diffs = diff_events(&old, &new);
+
+        assert_eq!(diffs.len(), 1);
+        assert_let!(VectorDiff::Set { index: 1, value } = &diffs[0]);
+        assert_eq!(value.event_id().as_deref(), Some(event_id!("$b")));
+    }
+
+    #[test]
+    fn test_diff_falls_back_to_reset() {
+        let old = vector![event("$a", "a"), event("$b", "b")];
+
+        // An event has been removed.
+        let new = vector![event("$a", "a"), event("$c", "c")];
+        assert_matches!(&diff_events(&old, &new)[..], [VectorDiff::Reset { .. }]);
+
+        // The first event is gone.
+        let new = vector![event("$b", "b")];
+        assert_matches!(&diff_events(&old, &new)[..], [VectorDiff::Reset { .. }]);
+
+        // Everything is gone.
+        assert_matches!(&diff_events(&old, &Vector::new())[..], [VectorDiff::Reset { .. }]);
+    }
+}
@@ -16,14 +16,20 @@
 //!
 //! Live location sharing allows users to share their real-time location with
 //! others in a room via [MSC3489](https://github.com/matrix-org/matrix-spec-proposals/pull/3489).
+use std::{collections::BTreeMap, time::Duration};
+
 use async_stream::stream;
-use futures_util::Stream;
+use futures_util::{pin_mut, stream, Stream, StreamExt as _};
+use matrix_sdk_base::deserialized_responses::SyncOrStrippedState;
+use matrix_sdk_common::timeout::timeout;
 use ruma::{
     events::{
-        beacon::OriginalSyncBeaconEvent, beacon_info::BeaconInfoEventContent,
+        beacon::OriginalSyncBeaconEvent,
+        beacon_info::{BeaconInfoEventContent, OriginalSyncBeaconInfoEvent},
         location::LocationContent,
+        AnySyncMessageLikeEvent, AnySyncTimelineEvent, SyncMessageLikeEvent, SyncStateEvent,
     },
-    MilliSecondsSinceUnixEpoch, OwnedUserId, RoomId,
+    MilliSecondsSinceUnixEpoch, OwnedRoomId, OwnedUserId, RoomId,
 };
 
 use crate::{event_handler::ObservableEventHandler, Client, Room};
@@ -31,13 +37,21 @@ use crate::{event_handler::ObservableEventHandler, Client, Room};
 /// An observable live location.
 #[derive(Debug)]
 pub struct ObservableLiveLocation {
+    client: Client,
+    room_id: OwnedRoomId,
     observable_room_events: ObservableEventHandler<(OriginalSyncBeaconEvent, Room)>,
+    observable_beacon_infos: ObservableEventHandler<(OriginalSyncBeaconInfoEvent, Room)>,
 }
 
 impl ObservableLiveLocation {
     /// Create a new `ObservableLiveLocation` for a particular room.
     pub fn new(client: &Client, room_id: &RoomId) -> Self {
-        Self { observable_room_events: client.observe_room_events(room_id) }
+        Self {
+            client: client.clone(),
+            room_id: room_id.to_owned(),
+            observable_room_events: client.observe_room_events(room_id),
+            observable_beacon_infos: client.observe_room_events(room_id),
+        }
     }
 
     /// Get a stream of [`LiveLocationShare`].
@@ -63,6 +77,170 @@ impl ObservableLiveLocation {
             }
         }
     }
+
+    /// Get a stream of the active live location shares of the other users of
+    /// the room, with their last known location.
+    ///
+    /// The shares which are already active are emitted first, with the last
+    /// location found in the event cache of the room. Then the whole list is
+    /// emitted each time a location beacon is received, a live location share
+    /// of the list is updated, or a share expires. Shares which have been
+    /// stopped, or have expired, are removed from the list.
+    pub fn subscribe_to_active_shares(&self) -> impl Stream<Item = Vec<LiveLocationShare>> {
+        let room = self.client.get_room(&self.room_id);
+        let beacons = self.subscribe().map(LiveLocationUpdate::Beacon);
+        let beacon_infos = self
+            .observable_beacon_infos
+            .subscribe()
+            .map(|(event, _room)| LiveLocationUpdate::BeaconInfo(event));
+        let updates = stream::select(beacons, beacon_infos);
+
+        stream! {
+            let mut shares = match &room {
+                Some(room) => active_shares(room).await,
+                None => BTreeMap::new(),
+            };
+
+            yield shares.values().cloned().collect();
+
+            pin_mut!(updates);
+
+            loop {
+                let update = match next_expiry(&shares) {
+                    Some(delay) => match timeout(updates.next(), delay).await {
+                        Ok(update) => update,
+                        // A share has expired, the list must be checked again.
+                        Err(_) => Some(LiveLocationUpdate::Expiry),
+                    },
+                    None => updates.next().await,
+                };
+
+                let Some(update) = update else {
+                    break;
+                };
+
+                let is_expiry = matches!(update, LiveLocationUpdate::Expiry);
+
+                match update {
+                    LiveLocationUpdate::Beacon(share) => {
+                        shares.insert(share.user_id.clone(), share);
+                    }
+
+                    LiveLocationUpdate::BeaconInfo(event) => {
+                        // Only the shares we have a location for are listed.
+                        let Some(share) = shares.get_mut(&event.state_key) else {
+                            continue;
+                        };
+
+                        share.beacon_info = Some(event.content);
+                    }
+
+                    LiveLocationUpdate::Expiry => {}
+                }
+
+                let num_shares = shares.len();
+
+                shares.retain(|_, share| {
+                    share.beacon_info.as_ref().is_some_and(|beacon_info| beacon_info.is_live())
+                });
+
+                if is_expiry && shares.len() == num_shares {
+                    // The clocks may differ slightly, nothing has expired yet.
+                    continue;
+                }
+
+                yield shares.values().cloned().collect();
+            }
+        }
+    }
+}
+
+/// Get the active live location shares of the other users of the room, which
+/// have a known location in the event cache of the room.
+async fn active_shares(room: &Room) -> BTreeMap<OwnedUserId, LiveLocationShare> {
+    let mut shares = BTreeMap::new();
+
+    let Ok(raw_beacon_infos) = room.get_state_events_static::<BeaconInfoEventContent>().await
+    else {
+        return shares;
+    };
+
+    let beacon_infos = raw_beacon_infos
+        .into_iter()
+        .filter_map(|raw| match raw.deserialize().ok()? {
+            SyncOrStrippedState::Sync(SyncStateEvent::Original(event)) => Some(event),
+            _ => None,
+        })
+        .filter(|event| event.state_key != room.own_user_id() && event.content.is_live())
+        .collect::<Vec<_>>();
+
+    if beacon_infos.is_empty() {
+        return shares;
+    }
+
+    let Ok((room_event_cache, _drop_handles)) = room.event_cache().await else {
+        return shares;
+    };
+
+    // Look for the last location of each share, starting from the most recent
+    // events.
+    for event in room_event_cache.events().await.into_iter().rev() {
+        if shares.len() == beacon_infos.len() {
+            break;
+        }
+
+        let Ok(AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::Beacon(
+            SyncMessageLikeEvent::Original(beacon),
+        ))) = event.raw().deserialize()
+        else {
+            continue;
+        };
+
+        let Some(beacon_info) = beacon_infos.iter().find(|beacon_info| {
+            beacon_info.event_id == beacon.content.relates_to.event_id
+                && beacon_info.state_key == beacon.sender
+        }) else {
+            continue;
+        };
+
+        shares.entry(beacon.sender.clone()).or_insert_with(|| LiveLocationShare {
+            last_location: LastLocation {
+                location: beacon.content.location,
+                ts: beacon.origin_server_ts,
+            },
+            beacon_info: Some(beacon_info.content.clone()),
+            user_id: beacon.sender,
+        });
+    }
+
+    shares
+}
+
+/// Get the delay until the first of the given shares expires, if any.
+fn next_expiry(shares: &BTreeMap<OwnedUserId, LiveLocationShare>) -> Option<Duration> {
+    let now = u64::from(MilliSecondsSinceUnixEpoch::now().get());
+
+    shares
+        .values()
+        .filter_map(|share| share.beacon_info.as_ref())
+        .map(|beacon_info| {
+            let timeout = u64::try_from(beacon_info.timeout.as_millis()).unwrap_or(u64::MAX);
+            u64::from(beacon_info.ts.get()).saturating_add(timeout)
+        })
+        .min()
+        .map(|expiry| Duration::from_millis(expiry.saturating_sub(now)))
+}
+
+/// An update to the live location shares of a room.
+enum LiveLocationUpdate {
+    /// A location beacon has been received.
+    Beacon(LiveLocationShare),
+
+    /// A live location share has been started, or stopped.
+    BeaconInfo(OriginalSyncBeaconInfoEvent),
+
+    /// A live location share has expired.
+    Expiry,
 }
 
 /// Details of the last known location beacon.
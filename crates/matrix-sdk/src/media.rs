@@ -24,7 +24,10 @@ use std::{fmt, fs::File, path::Path};
 use eyeball::SharedObservable;
 use futures_util::future::try_join;
 use matrix_sdk_base::event_cache::store::media::IgnoreMediaRetentionPolicy;
-pub use matrix_sdk_base::{event_cache::store::media::MediaRetentionPolicy, media::*};
+pub use matrix_sdk_base::{
+    event_cache::store::media::{MediaBlobEventCacheStore, MediaBlobStore, MediaRetentionPolicy},
+    media::*,
+};
 use mime::Mime;
 use ruma::{
     api::{
@@ -452,6 +452,59 @@ mod tests {
         assert_eq!(repl.new_content.msgtype.body(), "the edit");
     }
 
+    #[async_test]
+    async fn test_make_edit_event_of_emote_with_mentions() {
+        let event_id = event_id!("$1");
+        let own_user_id = user_id!("@me:saucisse.bzh");
+        let mentioned_user_id = owned_user_id!("@alice:saucisse.bzh");
+
+        let mut cache = TestEventCache::default();
+        let f = EventFactory::new();
+        cache.events.insert(
+            event_id.to_owned(),
+            f.emote("waves").event_id(event_id).sender(own_user_id).into(),
+        );
+
+        let room_id = room_id!("!galette:saucisse.bzh");
+        let mut new_content = RoomMessageEventContentWithoutRelation::new(MessageType::emote_html(
+            "waves at Alice",
+            "waves at <a href=\"https://matrix.to/#/@alice:saucisse.bzh\">Alice</a>",
+        ));
+        new_content.mentions = Some(Mentions::with_user_ids([mentioned_user_id.clone()]));
+
+        let edit_event = make_edit_event(
+            cache,
+            room_id,
+            own_user_id,
+            event_id,
+            EditedContent::RoomMessage(new_content),
+        )
+        .await
+        .unwrap();
+
+        assert_let!(AnyMessageLikeEventContent::RoomMessage(msg) = edit_event);
+
+        // The fallback is still an emote, with its formatted body.
+        assert_let!(MessageType::Emote(emote) = &msg.msgtype);
+        assert_eq!(emote.body, "* waves at Alice");
+        assert!(emote
+            .formatted
+            .as_ref()
+            .unwrap()
+            .body
+            .ends_with("waves at <a href=\"https://matrix.to/#/@alice:saucisse.bzh\">Alice</a>"));
+
+        // The newly mentioned user is notified.
+        assert_let!(Some(mentions) = msg.mentions);
+        assert_eq!(mentions.user_ids.into_iter().collect::<Vec<_>>(), [mentioned_user_id.clone()]);
+
+        // And the new content keeps the emote and its mentions.
+        assert_let!(Some(Relation::Replacement(repl)) = msg.relates_to);
+        assert_matches!(repl.new_content.msgtype, MessageType::Emote(_));
+        assert_let!(Some(mentions) = repl.new_content.mentions);
+        assert_eq!(mentions.user_ids.into_iter().collect::<Vec<_>>(), [mentioned_user_id]);
+    }
+
     #[async_test]
     async fn test_make_edit_caption_for_non_media_room_message() {
         let event_id = event_id!("$1");
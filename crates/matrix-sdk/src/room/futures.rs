@@ -201,6 +201,7 @@ impl<'a> IntoFuture for SendRawMessageLikeEvent<'a> {
                     // could have not query their keys ever.
                     room.query_keys_for_untracked_or_dirty_users().await?;
 
+                    room.ensure_untrusted_devices_confirmed().await?;
                     room.preshare_room_key().await?;
 
                     let olm = room.client.olm_machine().await;
@@ -61,24 +61,27 @@ use ruma::events::{
     SyncMessageLikeEvent,
 };
 use ruma::{
-    api::client::{
-        config::{set_global_account_data, set_room_account_data},
-        context,
-        error::ErrorKind,
-        filter::LazyLoadOptions,
-        membership::{
-            ban_user, forget_room, get_member_events,
-            invite_user::{self, v3::InvitationRecipient},
-            kick_user, leave_room, unban_user, Invite3pid,
+    api::{
+        client::{
+            config::{set_global_account_data, set_room_account_data},
+            context,
+            error::ErrorKind,
+            filter::LazyLoadOptions,
+            membership::{
+                ban_user, forget_room, get_member_events,
+                invite_user::{self, v3::InvitationRecipient},
+                kick_user, leave_room, unban_user, Invite3pid,
+            },
+            message::send_message_event,
+            read_marker::set_read_marker,
+            receipt::create_receipt,
+            redact::redact_event,
+            room::{get_event_by_timestamp, get_room_event, report_content, report_room},
+            state::{get_state_events_for_key, send_state_event},
+            tag::{create_tag, delete_tag},
+            typing::create_typing_event::{self, v3::Typing},
         },
-        message::send_message_event,
-        read_marker::set_read_marker,
-        receipt::create_receipt,
-        redact::redact_event,
-        room::{get_room_event, report_content, report_room},
-        state::{get_state_events_for_key, send_state_event},
-        tag::{create_tag, delete_tag},
-        typing::create_typing_event::{self, v3::Typing},
+        Direction,
     },
     assign,
     events::{
@@ -92,7 +95,7 @@ use ruma::{
             avatar::{self, RoomAvatarEventContent},
             encryption::RoomEncryptionEventContent,
             history_visibility::HistoryVisibility,
-            member::{MembershipChange, SyncRoomMemberEvent},
+            member::{MembershipChange, MembershipState, SyncRoomMemberEvent},
             message::{
                 AudioInfo, AudioMessageEventContent, FileInfo, FileMessageEventContent,
                 FormattedBody, ImageMessageEventContent, MessageType, RoomMessageEventContent,
@@ -118,14 +121,17 @@ use ruma::{
     push::{Action, PushConditionRoomCtx, Ruleset},
     serde::Raw,
     time::Instant,
-    EventId, Int, MatrixToUri, MatrixUri, MxcUri, OwnedEventId, OwnedRoomId, OwnedServerName,
-    OwnedTransactionId, OwnedUserId, RoomId, TransactionId, UInt, UserId,
+    DirectUserIdentifier, EventId, Int, MatrixToUri, MatrixUri, MilliSecondsSinceUnixEpoch, MxcUri,
+    OwnedEventId, OwnedRoomId, OwnedServerName, OwnedTransactionId, OwnedUserId, RoomId,
+    SpaceChildOrder, TransactionId, UInt, UserId,
 };
 use serde::de::DeserializeOwned;
 use thiserror::Error;
 use tokio::{join, sync::broadcast};
 use tokio_stream::StreamExt;
 use tracing::{debug, error, info, instrument, trace, warn};
+#[cfg(feature = "e2e-encryption")]
+pub use untrusted_devices::{UntrustedDevice, UntrustedDeviceReason, UntrustedDevicesReport};
 
 use self::futures::{SendAttachment, SendMessageLikeEvent, SendRawMessageLikeEvent};
 pub use self::{
@@ -134,6 +140,10 @@ pub use self::{
         EventWithContextResponse, IncludeRelations, ListThreadsOptions, Messages, MessagesOptions,
         Relations, RelationsOptions, ThreadRoots,
     },
+    state_events_batch::{
+        BatchedStateEvent, PreviousState, StateEventOutcome, StateEventsBatch,
+        StateEventsBatchReport,
+    },
 };
 #[cfg(doc)]
 use crate::event_cache::EventCache;
@@ -151,6 +161,7 @@ use crate::{
         knock_requests::{KnockRequest, KnockRequestMemberInfo},
         power_levels::{RoomPowerLevelChanges, RoomPowerLevelsExt},
         privacy_settings::RoomPrivacySettings,
+        server_acl::{ServerAcl, ServerAclError},
     },
     sync::RoomUpdate,
     utils::{IntoRawMessageLikeEventContent, IntoRawStateEventContent},
@@ -168,12 +179,16 @@ mod member;
 mod messages;
 pub mod power_levels;
 pub mod reply;
+pub mod server_acl;
+pub mod state_events_batch;
 
 /// Contains all the functionality for modifying the privacy settings in a room.
 pub mod privacy_settings;
 
 #[cfg(feature = "e2e-encryption")]
 pub(crate) mod shared_room_history;
+mod space_child_order;
+pub mod untrusted_devices;
 
 /// A struct containing methods that are common for Joined, Invited and Left
 /// Rooms
@@ -314,23 +329,47 @@ impl Room {
     /// If the room was in [`RoomState::Invited`] state, it'll also be forgotten
     /// automatically.
     ///
-    /// Only invited and joined rooms can be left.
-    #[doc(alias = "reject_invitation")]
-    #[instrument(skip_all, fields(room_id = ?self.inner.room_id()))]
+    /// If the room was in [`RoomState::Knocked`] state, this withdraws the
+    /// request to join the room.
+    ///
+    /// Only invited, knocked and joined rooms can be left.
+    #[doc(alias = "reject_invitation", alias = "withdraw_knock")]
     pub async fn leave(&self) -> Result<()> {
+        self.leave_with_options(LeaveOptions::default()).await?;
+        Ok(())
+    }
+
+    /// Leave this room, with the given options.
+    ///
+    /// Like [`Room::leave()`], this rejects the invite of an invited room,
+    /// which is then forgotten automatically, and withdraws the request to
+    /// join a knocked room.
+    ///
+    /// Returns what leaving the room meant, depending on its previous state.
+    ///
+    /// Only invited, knocked and joined rooms can be left.
+    #[instrument(skip_all, fields(room_id = ?self.inner.room_id()))]
+    pub async fn leave_with_options(&self, options: LeaveOptions) -> Result<LeaveOutcome> {
         let state = self.state();
-        if state == RoomState::Left {
-            return Err(Error::WrongRoomState(Box::new(WrongRoomState::new(
-                "Joined or Invited",
-                state,
-            ))));
-        }
+        let outcome = match state {
+            RoomState::Joined | RoomState::Banned => LeaveOutcome::Left,
+            RoomState::Invited => LeaveOutcome::InviteRejected,
+            RoomState::Knocked => LeaveOutcome::KnockWithdrawn,
+            RoomState::Left => {
+                return Err(Error::WrongRoomState(Box::new(WrongRoomState::new(
+                    "Joined, Invited or Knocked",
+                    state,
+                ))));
+            }
+        };
 
         // If the room was in Invited state we should also forget it when declining the
         // invite.
-        let should_forget = matches!(self.state(), RoomState::Invited);
+        let should_forget = outcome == LeaveOutcome::InviteRejected || options.forget;
 
-        let request = leave_room::v3::Request::new(self.inner.room_id().to_owned());
+        let request = assign!(leave_room::v3::Request::new(self.inner.room_id().to_owned()), {
+            reason: options.reason,
+        });
         let response = self.client.send(request).await;
 
         // The server can return with an error that is acceptable to ignore. Let's find
@@ -362,10 +401,56 @@ impl Room {
 
             if let Err(error) = self.forget().await {
                 error!(?error, "Failed to forget the room");
+
+                // Rejected invites are forgotten on a best-effort basis, but the caller
+                // explicitly asked to forget this room.
+                if options.forget {
+                    return Err(error);
+                }
             }
         }
 
-        Ok(())
+        Ok(outcome)
+    }
+
+    /// Leave the rooms of this space the current user is in, then this space
+    /// itself, with the given options.
+    ///
+    /// Only the direct children of this space are left, not the rooms of its
+    /// subspaces. Failing to leave a room doesn't prevent leaving the other
+    /// ones: the result of leaving each room is returned, in the order the
+    /// rooms have been left.
+    pub async fn leave_space(
+        &self,
+        options: LeaveOptions,
+    ) -> Result<Vec<(OwnedRoomId, Result<LeaveOutcome>)>> {
+        let children = self.get_state_events_static::<SpaceChildEventContent>().await?;
+
+        let mut rooms = children
+            .into_iter()
+            .filter_map(|raw| match raw.deserialize() {
+                // A child without `via` isn't a child of the space anymore, and doesn't
+                // deserialize.
+                Ok(SyncOrStrippedState::Sync(SyncStateEvent::Original(event))) => {
+                    self.client.get_room(&event.state_key)
+                }
+                _ => None,
+            })
+            .filter(|room| {
+                matches!(room.state(), RoomState::Joined | RoomState::Invited | RoomState::Knocked)
+            })
+            .collect::<Vec<_>>();
+
+        rooms.push(self.clone());
+
+        let mut results = Vec::with_capacity(rooms.len());
+
+        for room in rooms {
+            let result = room.leave_with_options(options.clone()).await;
+            results.push((room.room_id().to_owned(), result));
+        }
+
+        Ok(results)
     }
 
     /// Join this room.
@@ -710,6 +795,29 @@ impl Room {
         })
     }
 
+    /// Find the event closest to the given timestamp in this room, in the given
+    /// direction, using the `/timestamp_to_event` endpoint.
+    ///
+    /// Returns the ID of the event along with its `origin_server_ts`, or `None`
+    /// if the server couldn't find any event in that direction.
+    pub async fn event_for_timestamp(
+        &self,
+        timestamp: MilliSecondsSinceUnixEpoch,
+        direction: Direction,
+    ) -> Result<Option<(OwnedEventId, MilliSecondsSinceUnixEpoch)>> {
+        let request = get_event_by_timestamp::v1::Request::new(
+            self.room_id().to_owned(),
+            timestamp,
+            direction,
+        );
+
+        match self.client.send(request).await {
+            Ok(response) => Ok(Some((response.event_id, response.origin_server_ts))),
+            Err(err) if err.client_api_error_kind() == Some(&ErrorKind::NotFound) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
     pub(crate) async fn request_members(&self) -> Result<()> {
         self.client
             .locks()
@@ -1162,6 +1270,141 @@ impl Room {
             .collect::<FuturesUnordered<_>>())
     }
 
+    /// Add the given room as a child of this space.
+    ///
+    /// This sends an `m.space.child` state event to this room and, if the
+    /// current user is allowed to, an `m.space.parent` state event to the
+    /// child room, so the relationship is advertised in both directions.
+    ///
+    /// # Arguments
+    ///
+    /// * `child_room_id` - The ID of the room to add to this space.
+    ///
+    /// * `order` - The string used to sort the children of this space, if any.
+    ///
+    /// * `suggested` - Whether the child room is suggested to the members of
+    ///   this space.
+    pub async fn add_space_child(
+        &self,
+        child_room_id: &RoomId,
+        order: Option<SpaceChildOrder>,
+        suggested: bool,
+    ) -> Result<()> {
+        let via = vec![self.own_user_id().server_name().to_owned()];
+
+        let content = assign!(SpaceChildEventContent::new(via.clone()), { order, suggested });
+        self.send_state_event_for_key(child_room_id, content).await?;
+
+        if let Some(child_room) = self.child_room_with_parent_permission(child_room_id).await {
+            child_room
+                .send_state_event_for_key(self.room_id(), SpaceParentEventContent::new(via))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove the given room from the children of this space.
+    ///
+    /// This empties the `m.space.child` state event of this room for the child
+    /// room and, if the current user is allowed to, the `m.space.parent` state
+    /// event of the child room for this space, if any.
+    pub async fn remove_space_child(&self, child_room_id: &RoomId) -> Result<()> {
+        // An `m.space.child` event without `via` doesn't advertise a child anymore.
+        self.send_state_event_raw(
+            StateEventType::SpaceChild.to_string().as_str(),
+            child_room_id.as_str(),
+            serde_json::json!({}),
+        )
+        .await?;
+
+        if let Some(child_room) = self.child_room_with_parent_permission(child_room_id).await {
+            let has_parent = child_room
+                .get_state_event_static_for_key::<SpaceParentEventContent, _>(self.room_id())
+                .await?
+                .is_some();
+
+            if has_parent {
+                child_room
+                    .send_state_event_raw(
+                        StateEventType::SpaceParent.to_string().as_str(),
+                        self.room_id().as_str(),
+                        serde_json::json!({}),
+                    )
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compute the `order` placing a child of this space between the two
+    /// given children.
+    ///
+    /// This allows to sort the children of a space without having to build
+    /// the `order` strings by hand. The result can be passed to
+    /// [`Self::add_space_child`].
+    ///
+    /// # Arguments
+    ///
+    /// * `previous_child` - The child after which the new child is sorted, or
+    ///   `None` to sort it first.
+    ///
+    /// * `next_child` - The child before which the new child is sorted, or
+    ///   `None` to sort it last.
+    ///
+    /// Returns `None` if no `order` sorts a child between them. Notably, the
+    /// children without `order` are sorted after all the others, so a child
+    /// can only be sorted after one of them by not having an `order` either.
+    pub async fn space_child_order_between(
+        &self,
+        previous_child: Option<&RoomId>,
+        next_child: Option<&RoomId>,
+    ) -> Result<Option<SpaceChildOrder>> {
+        let previous = match previous_child {
+            Some(previous_child) => match self.space_child_order(previous_child).await? {
+                Some(order) => Some(order),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+
+        // A child without order is sorted after all the ones with an order.
+        let next = match next_child {
+            Some(next_child) => self.space_child_order(next_child).await?,
+            None => None,
+        };
+
+        Ok(space_child_order::order_between(previous.as_ref(), next.as_ref()))
+    }
+
+    /// Get the `order` of the given child of this space, if any.
+    async fn space_child_order(&self, child_room_id: &RoomId) -> Result<Option<SpaceChildOrder>> {
+        let Some(event) =
+            self.get_state_event_static_for_key::<SpaceChildEventContent, _>(child_room_id).await?
+        else {
+            return Ok(None);
+        };
+
+        Ok(event.deserialize()?.original_content().and_then(|content| content.order.clone()))
+    }
+
+    /// Get the given joined room, if the current user is allowed to send an
+    /// `m.space.parent` state event to it.
+    async fn child_room_with_parent_permission(&self, child_room_id: &RoomId) -> Option<Room> {
+        let child_room = self.client.get_room(child_room_id)?;
+
+        if child_room.state() != RoomState::Joined {
+            return None;
+        }
+
+        let power_levels = child_room.power_levels().await.ok()?;
+
+        power_levels
+            .user_can_send_state(self.own_user_id(), StateEventType::SpaceParent)
+            .then_some(child_room)
+    }
+
     /// Read account data in this room, from storage.
     pub async fn account_data(
         &self,
@@ -1456,6 +1699,115 @@ impl Room {
         Ok(())
     }
 
+    /// Marks this room as a DM with each of the given users, in the `m.direct`
+    /// account data.
+    ///
+    /// Contrary to [`Room::set_is_direct`], only the entries of the given
+    /// users are touched, and the update is retried if the account data is
+    /// modified concurrently, e.g. by another device.
+    pub async fn mark_as_direct_with(&self, user_ids: &[OwnedUserId]) -> Result<()> {
+        let room_id = self.room_id();
+
+        self.client
+            .account()
+            .update_direct_rooms(|content| {
+                let mut changed = false;
+
+                for user_id in user_ids {
+                    let rooms = content.entry(user_id.into()).or_default();
+
+                    if !rooms.iter().any(|id| id == room_id) {
+                        rooms.push(room_id.to_owned());
+                        changed = true;
+                    }
+                }
+
+                changed
+            })
+            .await
+    }
+
+    /// Unmarks this room as a DM with each of the given users, in the
+    /// `m.direct` account data.
+    ///
+    /// The room stays marked as a DM with the other users. The update is
+    /// retried if the account data is modified concurrently, e.g. by another
+    /// device.
+    pub async fn unmark_as_direct_with(&self, user_ids: &[OwnedUserId]) -> Result<()> {
+        let room_id = self.room_id();
+
+        self.client
+            .account()
+            .update_direct_rooms(|content| {
+                let mut changed = false;
+
+                for user_id in user_ids {
+                    let user_id = <&DirectUserIdentifier>::from(&**user_id);
+
+                    let Some(rooms) = content.get_mut(user_id) else {
+                        continue;
+                    };
+
+                    let previous_len = rooms.len();
+                    rooms.retain(|id| id != room_id);
+                    changed |= rooms.len() != previous_len;
+
+                    // Remove users that don't have any room marked as DM.
+                    if rooms.is_empty() {
+                        content.remove(user_id);
+                    }
+                }
+
+                changed
+            })
+            .await
+    }
+
+    /// Whether this room is a DM with the given user.
+    ///
+    /// This is the case if we're in the room, it's marked as a DM with the user
+    /// in the `m.direct` account data, and the user is still in the room, i.e.
+    /// joined or invited. For a room we're invited to, it's also the case if
+    /// the user invited us to a DM, since `m.direct` is usually only updated
+    /// once the invite has been accepted.
+    ///
+    /// Note that we can have several DMs with the same user, and that a DM can
+    /// be shared with several users.
+    pub async fn is_direct_with(&self, user_id: &UserId) -> Result<bool> {
+        if user_id == self.own_user_id() {
+            return Ok(false);
+        }
+
+        let is_marked_as_direct =
+            self.direct_targets().contains(<&DirectUserIdentifier>::from(user_id));
+
+        match self.state() {
+            RoomState::Joined => {}
+
+            RoomState::Invited => {
+                let inviter = self.invite_details().await?.inviter;
+
+                if inviter.is_some_and(|inviter| inviter.user_id() == user_id)
+                    && self.inner.is_direct().await?
+                {
+                    return Ok(true);
+                }
+            }
+
+            RoomState::Left | RoomState::Banned | RoomState::Knocked => return Ok(false),
+        }
+
+        if !is_marked_as_direct {
+            return Ok(false);
+        }
+
+        let Some(member) = self.get_member(user_id).await? else {
+            return Ok(false);
+        };
+
+        Ok(matches!(member.membership(), MembershipState::Join | MembershipState::Invite))
+    }
+
     /// Tries to decrypt a room event.
     ///
     /// # Arguments
@@ -1878,6 +2230,120 @@ impl Room {
         Ok(())
     }
 
+    /// Whether this room only shares its room keys with trusted devices.
+    ///
+    /// See [`Room::set_trusted_devices_only`].
+    #[cfg(feature = "e2e-encryption")]
+    pub async fn is_trusted_devices_only(&self) -> Result<bool> {
+        let olm = self.client.olm_machine().await;
+        let olm = olm.as_ref().ok_or(Error::NoOlmMachine)?;
+
+        Ok(olm.store().get_room_only_allow_trusted_devices(self.room_id()).await?)
+    }
+
+    /// Mark this room as only sharing its room keys with trusted devices.
+    ///
+    /// When enabled, sending an encrypted event in this room fails with
+    /// [`Error::UntrustedDevices`] as long as some devices of its members are
+    /// neither verified nor have been confirmed with
+    /// [`Room::confirm_untrusted_devices`], instead of silently sharing the
+    /// room key with them.
+    #[cfg(feature = "e2e-encryption")]
+    pub async fn set_trusted_devices_only(&self, enabled: bool) -> Result<()> {
+        let olm = self.client.olm_machine().await;
+        let olm = olm.as_ref().ok_or(Error::NoOlmMachine)?;
+
+        Ok(olm.store().set_room_only_allow_trusted_devices(self.room_id(), enabled).await?)
+    }
+
+    /// Get the devices of the members of this room which would receive the
+    /// room key without being trusted.
+    ///
+    /// This only relies on the devices we already know about, so
+    /// [`Room::sync_members`] and a key query might be needed beforehand to get
+    /// an accurate report.
+    #[cfg(feature = "e2e-encryption")]
+    pub async fn untrusted_devices_report(&self) -> Result<UntrustedDevicesReport> {
+        // Only the joined members receive the room key if the history visibility is
+        // set to `Joined`, see `BaseClient::share_room_key`.
+        let memberships = if self.history_visibility_or_default() == HistoryVisibility::Joined {
+            RoomMemberships::JOIN
+        } else {
+            RoomMemberships::ACTIVE
+        };
+
+        let members = self.client.state_store().get_user_ids(self.room_id(), memberships).await?;
+        let own_device_id = self.client.device_id();
+
+        let confirmed_devices = {
+            let olm = self.client.olm_machine().await;
+            let olm = olm.as_ref().ok_or(Error::NoOlmMachine)?;
+            olm.store().get_room_confirmed_untrusted_devices(self.room_id()).await?
+        };
+
+        let mut devices = Vec::new();
+
+        for user_id in members {
+            let user_devices = self.client.encryption().get_user_devices(&user_id).await?;
+
+            for device in user_devices.devices() {
+                if Some(device.device_id()) == own_device_id
+                    || confirmed_devices
+                        .get(&user_id)
+                        .is_some_and(|device_ids| device_ids.contains(device.device_id()))
+                {
+                    continue;
+                }
+
+                if let Some(reason) = UntrustedDeviceReason::for_device(&device) {
+                    devices.push(UntrustedDevice {
+                        user_id: user_id.clone(),
+                        device_id: device.device_id().to_owned(),
+                        display_name: device.display_name().map(ToOwned::to_owned),
+                        reason,
+                    });
+                }
+            }
+        }
+
+        Ok(UntrustedDevicesReport { devices })
+    }
+
+    /// Confirm that the room keys can be shared with the devices of the given
+    /// report, obtained with [`Room::untrusted_devices_report`] or from an
+    /// [`Error::UntrustedDevices`].
+    ///
+    /// The confirmation only applies to this room: the trust state of the
+    /// confirmed devices isn't changed, and they're still reported in the
+    /// other rooms.
+    #[cfg(feature = "e2e-encryption")]
+    pub async fn confirm_untrusted_devices(&self, report: &UntrustedDevicesReport) -> Result<()> {
+        let olm = self.client.olm_machine().await;
+        let olm = olm.as_ref().ok_or(Error::NoOlmMachine)?;
+
+        let devices =
+            report.devices.iter().map(|device| (device.user_id.clone(), device.device_id.clone()));
+
+        Ok(olm.store().confirm_room_untrusted_devices(self.room_id(), devices).await?)
+    }
+
+    /// Make sure that no untrusted device would receive the room key, if this
+    /// room only shares its room keys with trusted devices.
+    #[cfg(feature = "e2e-encryption")]
+    async fn ensure_untrusted_devices_confirmed(&self) -> Result<()> {
+        if !self.is_trusted_devices_only().await? {
+            return Ok(());
+        }
+
+        let report = self.untrusted_devices_report().await?;
+
+        if report.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::UntrustedDevices(Box::new(report)))
+        }
+    }
+
     /// Share a room key with users in the given room.
     ///
     /// This will create Olm sessions with all the users/device pairs in the
@@ -2220,6 +2686,11 @@ impl Room {
     ) -> Result<send_message_event::v3::Response> {
         self.ensure_room_joined()?;
 
+        #[cfg(feature = "image-proc")]
+        let (content_type, data) = config.apply_processing(content_type.clone(), data).await?;
+        #[cfg(feature = "image-proc")]
+        let content_type = &content_type;
+
         let txn_id = config.txn_id.take();
         let mentions = config.mentions.take();
 
@@ -2424,6 +2895,59 @@ impl Room {
         Ok(())
     }
 
+    /// Applies changes to the power levels of some users and to the levels
+    /// required for actions in this room, in a single `m.room.power_levels`
+    /// event.
+    ///
+    /// `base` are the power levels the changes have been made from, e.g. the
+    /// ones displayed on an admin screen. The current power levels are fetched
+    /// from the homeserver, and if they have been modified since, for instance
+    /// by another admin, nothing is sent and [`Error::PowerLevelsConflict`] is
+    /// returned, so the changes can be reviewed against the new power levels.
+    ///
+    /// Any values that are `None` in the given `RoomPowerLevelChanges` will
+    /// remain unchanged.
+    pub async fn update_power_levels_from(
+        &self,
+        base: &RoomPowerLevels,
+        users: Vec<(&UserId, Int)>,
+        changes: RoomPowerLevelChanges,
+    ) -> Result<send_state_event::v3::Response> {
+        // The power levels of the store may not include a concurrent change yet, so
+        // compare against the ones of the homeserver.
+        let response = self
+            .client
+            .send(get_state_events_for_key::v3::Request::new(
+                self.room_id().to_owned(),
+                StateEventType::RoomPowerLevels,
+                "".to_owned(),
+            ))
+            .await?;
+        let mut power_levels = RoomPowerLevels::from(
+            response.content.deserialize_as::<RoomPowerLevelsEventContent>()?,
+        );
+
+        let as_json = |power_levels: &RoomPowerLevels| {
+            serde_json::to_value(RoomPowerLevelsEventContent::from(power_levels.clone()))
+        };
+
+        if as_json(&power_levels)? != as_json(base)? {
+            return Err(Error::PowerLevelsConflict);
+        }
+
+        for (user_id, new_level) in users {
+            if new_level == power_levels.users_default {
+                power_levels.users.remove(user_id);
+            } else {
+                power_levels.users.insert(user_id.to_owned(), new_level);
+            }
+        }
+
+        power_levels.apply(changes)?;
+
+        self.send_state_event(RoomPowerLevelsEventContent::from(power_levels)).await
+    }
+
     /// Resets the room's power levels to the default values
     ///
     /// [spec]: https://spec.matrix.org/v1.9/client-server-api/#mroompower_levels
@@ -2683,6 +3207,125 @@ impl Room {
         Ok(self.client.send(request).await?)
     }
 
+    /// Send a batch of state events to the homeserver, one after the other.
+    ///
+    /// Before sending anything, the power levels of the room are used to
+    /// check that the current user is allowed to send every state event of
+    /// the batch. If one of them isn't allowed, or if the batch is a dry run,
+    /// nothing is sent.
+    ///
+    /// Sending stops at the first state event that fails to be sent, and the
+    /// following ones are marked as skipped. The returned report can be used
+    /// to build a batch that restores the previous state, with
+    /// [`StateEventsBatchReport::rollback_batch()`].
+    ///
+    /// Returns an error if the room isn't joined, or if the current user or
+    /// the power levels of the room are unknown.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # async {
+    /// # let room: matrix_sdk::Room = todo!();
+    /// use matrix_sdk::{
+    ///     room::StateEventsBatch,
+    ///     ruma::events::room::{
+    ///         name::RoomNameEventContent, topic::RoomTopicEventContent,
+    ///     },
+    /// };
+    ///
+    /// let mut batch = StateEventsBatch::new();
+    /// batch.add("", RoomNameEventContent::new("Book club".to_owned()))?;
+    /// batch.add("", RoomTopicEventContent::new("Let's read!".to_owned()))?;
+    ///
+    /// let report = room.send_state_events(batch).await?;
+    ///
+    /// if !report.is_success() {
+    ///     room.send_state_events(report.rollback_batch()).await?;
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn send_state_events(
+        &self,
+        batch: StateEventsBatch,
+    ) -> Result<StateEventsBatchReport> {
+        self.ensure_room_joined()?;
+
+        let own_user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
+        let power_levels = self.power_levels().await?;
+
+        let StateEventsBatch { events, dry_run } = batch;
+
+        let allowed: Vec<_> = events
+            .iter()
+            .map(|event| power_levels.user_can_send_state(own_user_id, event.event_type.clone()))
+            .collect();
+
+        if dry_run || allowed.contains(&false) {
+            let results = events
+                .into_iter()
+                .zip(allowed)
+                .map(|(event, allowed)| {
+                    let outcome = if allowed {
+                        StateEventOutcome::Allowed
+                    } else {
+                        StateEventOutcome::Forbidden
+                    };
+                    (event, outcome)
+                })
+                .collect();
+
+            return Ok(StateEventsBatchReport { results, dry_run });
+        }
+
+        let mut results = Vec::with_capacity(events.len());
+        let mut failed = false;
+
+        for event in events {
+            if failed {
+                results.push((event, StateEventOutcome::Skipped));
+                continue;
+            }
+
+            let previous_state = match self
+                .get_state_event(event.event_type.clone(), &event.state_key)
+                .await
+            {
+                Ok(Some(RawAnySyncOrStrippedState::Sync(raw))) => match raw.get_field("content") {
+                    Ok(Some(content)) => PreviousState::Known(content),
+                    _ => PreviousState::Unknown,
+                },
+                Ok(Some(RawAnySyncOrStrippedState::Stripped(_))) => PreviousState::Unknown,
+                Ok(None) => PreviousState::Absent,
+                Err(error) => {
+                    warn!("Couldn't load the previous `{}` state event: {error}", event.event_type);
+                    PreviousState::Unknown
+                }
+            };
+
+            let outcome = match self
+                .send_state_event_raw(
+                    &event.event_type.to_string(),
+                    &event.state_key,
+                    &event.content,
+                )
+                .await
+            {
+                Ok(response) => {
+                    StateEventOutcome::Sent { event_id: response.event_id, previous_state }
+                }
+                Err(error) => {
+                    failed = true;
+                    StateEventOutcome::Failed(error)
+                }
+            };
+
+            results.push((event, outcome));
+        }
+
+        Ok(StateEventsBatchReport { results, dry_run })
+    }
+
     /// Strips all information out of an event of the room.
     ///
     /// Returns the [`redact_event::v3::Response`] from the server.
@@ -2733,6 +3376,14 @@ impl Room {
         self.client.send(request).await
     }
 
+    /// Get an editor for the server access control list of this room.
+    ///
+    /// The editor starts from the current `m.room.server_acl` state event, and
+    /// refuses to save changes that would deny the own server of the user.
+    pub async fn server_acl(&self) -> Result<ServerAcl, ServerAclError> {
+        ServerAcl::load(self.clone()).await
+    }
+
     /// Get a list of servers that should know this room.
     ///
     /// Uses the synced members of the room and the suggested [routing
@@ -3595,6 +4246,12 @@ impl Room {
         RoomPrivacySettings::new(&self.inner, &self.client)
     }
 
+    /// Access the widgets configured in this room.
+    #[cfg(feature = "experimental-widgets")]
+    pub fn widgets(&self) -> crate::widget::RoomWidgets {
+        crate::widget::RoomWidgets::new(self.clone())
+    }
+
     /// Retrieve a list of all the threads for the current room.
     ///
     /// Since this client-server API is paginated, the return type may include a
@@ -3770,6 +4427,36 @@ impl Receipts {
     }
 }
 
+/// Options to leave a room with [`Room::leave_with_options()`].
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+pub struct LeaveOptions {
+    /// The reason for leaving the room, which is visible to the other members
+    /// of the room, or to the inviter when rejecting an invite.
+    #[cfg_attr(feature = "uniffi", uniffi(default = None))]
+    pub reason: Option<String>,
+
+    /// Whether to forget the room once it has been left.
+    ///
+    /// Rejected invites are always forgotten.
+    #[cfg_attr(feature = "uniffi", uniffi(default = false))]
+    pub forget: bool,
+}
+
+/// What leaving a room meant, depending on its state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+pub enum LeaveOutcome {
+    /// The user left a joined room.
+    Left,
+
+    /// The user rejected an invite to the room.
+    InviteRejected,
+
+    /// The user withdrew their request to join the room.
+    KnockWithdrawn,
+}
+
 /// [Parent space](https://spec.matrix.org/v1.8/client-server-api/#mspaceparent-relationships)
 /// listed by a room, possibly validated by checking the space's state.
 #[derive(Debug)]
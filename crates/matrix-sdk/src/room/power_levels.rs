@@ -54,6 +54,17 @@ pub struct RoomPowerLevelChanges {
     /// The level required to change the room's topic.
     #[cfg_attr(feature = "uniffi", uniffi(default = None))]
     pub room_topic: Option<i64>,
+    /// The level required to pin or unpin events in the room.
+    #[cfg_attr(feature = "uniffi", uniffi(default = None))]
+    pub room_pinned_events: Option<i64>,
+    /// The level required to change the power levels of the room.
+    #[cfg_attr(feature = "uniffi", uniffi(default = None))]
+    pub room_power_levels: Option<i64>,
+
+    // Notifications
+    /// The level required to trigger an `@room` notification.
+    #[cfg_attr(feature = "uniffi", uniffi(default = None))]
+    pub room_notification: Option<i64>,
 }
 
 impl RoomPowerLevelChanges {
@@ -70,6 +81,9 @@ impl RoomPowerLevelChanges {
             room_name: None,
             room_avatar: None,
             room_topic: None,
+            room_pinned_events: None,
+            room_power_levels: None,
+            room_notification: None,
         }
     }
 }
@@ -105,6 +119,17 @@ impl From<RoomPowerLevels> for RoomPowerLevelChanges {
                 .get(&StateEventType::RoomTopic.into())
                 .map(|v| (*v).into())
                 .or(Some(value.state_default.into())),
+            room_pinned_events: value
+                .events
+                .get(&StateEventType::RoomPinnedEvents.into())
+                .map(|v| (*v).into())
+                .or(Some(value.state_default.into())),
+            room_power_levels: value
+                .events
+                .get(&StateEventType::RoomPowerLevels.into())
+                .map(|v| (*v).into())
+                .or(Some(value.state_default.into())),
+            room_notification: Some(value.notifications.room.into()),
         }
     }
 }
@@ -150,6 +175,17 @@ impl RoomPowerLevelsExt for RoomPowerLevels {
         if let Some(room_topic) = settings.room_topic {
             self.events.insert(StateEventType::RoomTopic.into(), room_topic.try_into()?);
         }
+        if let Some(room_pinned_events) = settings.room_pinned_events {
+            self.events
+                .insert(StateEventType::RoomPinnedEvents.into(), room_pinned_events.try_into()?);
+        }
+        if let Some(room_power_levels) = settings.room_power_levels {
+            self.events
+                .insert(StateEventType::RoomPowerLevels.into(), room_power_levels.try_into()?);
+        }
+        if let Some(room_notification) = settings.room_notification {
+            self.notifications.room = room_notification.try_into()?;
+        }
 
         Ok(())
     }
@@ -221,6 +257,9 @@ mod tests {
             room_name: None,
             room_avatar: None,
             room_topic: None,
+            room_pinned_events: None,
+            room_power_levels: None,
+            room_notification: None,
         };
 
         // When applying the settings to the power levels.
@@ -257,6 +296,9 @@ mod tests {
             room_name: Some(new_level.into()),
             room_avatar: Some(new_level.into()),
             room_topic: Some(new_level.into()),
+            room_pinned_events: Some(new_level.into()),
+            room_power_levels: Some(new_level.into()),
+            room_notification: None,
         };
 
         // When applying the settings to the power levels.
@@ -270,6 +312,8 @@ mod tests {
                 (StateEventType::RoomName.into(), new_level),
                 (StateEventType::RoomAvatar.into(), new_level),
                 (StateEventType::RoomTopic.into(), new_level),
+                (StateEventType::RoomPinnedEvents.into(), new_level),
+                (StateEventType::RoomPowerLevels.into(), new_level),
             ])
         );
         // And the rest should remain unchanged.
@@ -282,6 +326,28 @@ mod tests {
         assert_eq!(power_levels.users_default, original_levels.users_default);
     }
 
+    #[test]
+    fn test_apply_room_notification() {
+        // Given a set of power levels and some settings that only change the level
+        // required to notify the whole room.
+        let mut power_levels = default_power_levels();
+
+        let new_level = int!(100);
+        let settings = RoomPowerLevelChanges {
+            room_notification: Some(new_level.into()),
+            ..Default::default()
+        };
+
+        // When applying the settings to the power levels.
+        let original_levels = power_levels.clone();
+        power_levels.apply(settings).unwrap();
+
+        // Then only the notification level should be updated.
+        assert_eq!(power_levels.notifications.room, new_level);
+        assert_eq!(power_levels.events, original_levels.events);
+        assert_eq!(power_levels.state_default, original_levels.state_default);
+    }
+
     #[test]
     fn test_apply_state_event_to_default() {
         // Given a set of power levels and some settings that change the room name level
@@ -305,6 +371,9 @@ mod tests {
             room_name: Some(power_levels.state_default.into()),
             room_avatar: None,
             room_topic: None,
+            room_pinned_events: None,
+            room_power_levels: None,
+            room_notification: None,
         };
 
         // When applying the settings to the power levels.
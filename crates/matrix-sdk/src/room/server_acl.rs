@@ -0,0 +1,262 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Editing of the server access control list of a room, with the
+//! `m.room.server_acl` state event.
+//!
+//! A mistake in this event can prevent the own server of the user from
+//! participating in the room, which can't be undone by the user. The
+//! [`ServerAcl`] editor checks the changes before sending them, to avoid
+//! that.
+
+use matrix_sdk_base::deserialized_responses::SyncOrStrippedState;
+use ruma::{
+    api::client::state::send_state_event, events::room::server_acl::RoomServerAclEventContent,
+    OwnedServerName, ServerName,
+};
+
+use crate::Room;
+
+/// An error when editing the server access control list of a room.
+#[derive(Debug, thiserror::Error)]
+pub enum ServerAclError {
+    /// The server name pattern isn't valid.
+    #[error("`{0}` isn't a valid server name pattern")]
+    InvalidServerName(String),
+
+    /// The changes would deny the own server of the user, which would lock it
+    /// out of the room.
+    #[error("the server {0} of the user wouldn't be allowed in the room anymore")]
+    OwnServerDenied(OwnedServerName),
+
+    /// The state event couldn't be read or sent.
+    #[error(transparent)]
+    Sdk(#[from] crate::Error),
+}
+
+/// An editor for the server access control list of a room.
+///
+/// The editor starts from the current `m.room.server_acl` state event of the
+/// room, or from a list allowing every server if the room doesn't have one.
+/// The changes are only sent to the room with [`ServerAcl::save`].
+///
+/// The server name patterns can use the `*` wildcard, matching zero or more
+/// characters, and the `?` wildcard, matching exactly one character.
+#[derive(Debug, Clone)]
+pub struct ServerAcl {
+    room: Room,
+    content: RoomServerAclEventContent,
+}
+
+impl ServerAcl {
+    pub(crate) async fn load(room: Room) -> Result<Self, ServerAclError> {
+        let event = room
+            .get_state_event_static::<RoomServerAclEventContent>()
+            .await?
+            .and_then(|raw| raw.deserialize().ok());
+
+        let content = event
+            .and_then(|event| match event {
+                SyncOrStrippedState::Sync(event) => {
+                    event.as_original().map(|event| event.content.clone())
+                }
+                SyncOrStrippedState::Stripped(event) => Some(event.content),
+            })
+            .unwrap_or_else(|| RoomServerAclEventContent::new(true, vec!["*".to_owned()], vec![]));
+
+        Ok(Self { room, content })
+    }
+
+    /// The patterns of the servers allowed in the room.
+    pub fn allow(&self) -> &[String] {
+        &self.content.allow
+    }
+
+    /// The patterns of the servers denied in the room.
+    ///
+    /// A server matching both an allowed and a denied pattern is denied.
+    pub fn deny(&self) -> &[String] {
+        &self.content.deny
+    }
+
+    /// Whether servers using an IP address as their name are allowed in the
+    /// room.
+    pub fn allow_ip_literals(&self) -> bool {
+        self.content.allow_ip_literals
+    }
+
+    /// Whether the given server is allowed in the room with the current
+    /// changes.
+    pub fn is_allowed(&self, server_name: &ServerName) -> bool {
+        self.content.is_allowed(server_name)
+    }
+
+    /// Allow the servers matching the given pattern.
+    ///
+    /// The pattern is removed from the denied servers if it was there.
+    pub fn allow_server(&mut self, pattern: &str) -> Result<(), ServerAclError> {
+        validate_pattern(pattern)?;
+
+        self.content.deny.retain(|denied| denied != pattern);
+        if !self.content.allow.iter().any(|allowed| allowed == pattern) {
+            self.content.allow.push(pattern.to_owned());
+        }
+
+        Ok(())
+    }
+
+    /// Deny the servers matching the given pattern.
+    ///
+    /// The pattern is removed from the allowed servers if it was there.
+    pub fn deny_server(&mut self, pattern: &str) -> Result<(), ServerAclError> {
+        validate_pattern(pattern)?;
+
+        self.content.allow.retain(|allowed| allowed != pattern);
+        if !self.content.deny.iter().any(|denied| denied == pattern) {
+            self.content.deny.push(pattern.to_owned());
+        }
+
+        Ok(())
+    }
+
+    /// Remove the given pattern from the allowed and the denied servers.
+    ///
+    /// Returns `false` if the pattern was in neither list.
+    pub fn remove_server(&mut self, pattern: &str) -> bool {
+        let len = self.content.allow.len() + self.content.deny.len();
+
+        self.content.allow.retain(|allowed| allowed != pattern);
+        self.content.deny.retain(|denied| denied != pattern);
+
+        self.content.allow.len() + self.content.deny.len() != len
+    }
+
+    /// Set whether servers using an IP address as their name are allowed in
+    /// the room.
+    pub fn set_allow_ip_literals(&mut self, allow_ip_literals: bool) {
+        self.content.allow_ip_literals = allow_ip_literals;
+    }
+
+    /// Check that the own server of the user is still allowed in the room
+    /// with the current changes.
+    pub fn validate(&self) -> Result<(), ServerAclError> {
+        check_own_server_allowed(&self.content, self.room.own_user_id().server_name())
+    }
+
+    /// Send the `m.room.server_acl` state event with the current changes to
+    /// the room.
+    ///
+    /// Nothing is sent if [`ServerAcl::validate`] fails.
+    pub async fn save(&self) -> Result<send_state_event::v3::Response, ServerAclError> {
+        self.validate()?;
+        Ok(self.room.send_state_event(self.content.clone()).await?)
+    }
+}
+
+/// Check that the given server name pattern is valid.
+///
+/// A pattern is a server name without a port, which may contain wildcards.
+fn validate_pattern(pattern: &str) -> Result<(), ServerAclError> {
+    let is_valid_char =
+        |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '*' | '?' | '[' | ']');
+
+    // Only IPv6 literals, between brackets, can contain colons.
+    let has_port = if pattern.starts_with('[') {
+        pattern.rsplit_once(']').is_some_and(|(_, port)| !port.is_empty())
+    } else {
+        pattern.contains(':')
+    };
+
+    if pattern.is_empty()
+        || has_port
+        || !pattern.chars().all(|c| is_valid_char(c) || (c == ':' && pattern.starts_with('[')))
+    {
+        return Err(ServerAclError::InvalidServerName(pattern.to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Check that the given server is allowed by the given access control list.
+fn check_own_server_allowed(
+    content: &RoomServerAclEventContent,
+    own_server: &ServerName,
+) -> Result<(), ServerAclError> {
+    if content.is_allowed(own_server) {
+        Ok(())
+    } else {
+        Err(ServerAclError::OwnServerDenied(own_server.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches2::assert_matches;
+    use ruma::{events::room::server_acl::RoomServerAclEventContent, server_name};
+
+    use super::{check_own_server_allowed, validate_pattern, ServerAclError};
+
+    #[test]
+    fn test_validate_pattern() {
+        validate_pattern("matrix.org").unwrap();
+        validate_pattern("*.evil.example").unwrap();
+        validate_pattern("server-?.example").unwrap();
+        validate_pattern("[2001:db8::1]").unwrap();
+
+        assert_matches!(validate_pattern(""), Err(ServerAclError::InvalidServerName(_)));
+        assert_matches!(
+            validate_pattern("matrix.org:8448"),
+            Err(ServerAclError::InvalidServerName(_))
+        );
+        assert_matches!(validate_pattern("[::1]:8448"), Err(ServerAclError::InvalidServerName(_)));
+        assert_matches!(
+            validate_pattern("evil example"),
+            Err(ServerAclError::InvalidServerName(_))
+        );
+    }
+
+    #[test]
+    fn test_own_server_allowed() {
+        let own_server = server_name!("matrix.example.org");
+
+        let content = RoomServerAclEventContent::new(true, vec!["*".to_owned()], vec![]);
+        check_own_server_allowed(&content, own_server).unwrap();
+
+        // Denied with a wildcard.
+        let content = RoomServerAclEventContent::new(
+            true,
+            vec!["*".to_owned()],
+            vec!["*.example.org".to_owned()],
+        );
+        assert_matches!(
+            check_own_server_allowed(&content, own_server),
+            Err(ServerAclError::OwnServerDenied(server))
+        );
+        assert_eq!(server, own_server);
+
+        // Not in the allowed servers.
+        let content = RoomServerAclEventContent::new(true, vec!["other.org".to_owned()], vec![]);
+        assert_matches!(
+            check_own_server_allowed(&content, own_server),
+            Err(ServerAclError::OwnServerDenied(_))
+        );
+
+        // An IP literal is denied if IP literals aren't allowed.
+        let content = RoomServerAclEventContent::new(false, vec!["*".to_owned()], vec![]);
+        assert_matches!(
+            check_own_server_allowed(&content, server_name!("127.0.0.1")),
+            Err(ServerAclError::OwnServerDenied(_))
+        );
+    }
+}
@@ -0,0 +1,158 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Computation of the `order` of the children of a space.
+//!
+//! The children of a space are sorted by their `order`, compared
+//! lexicographically, the children without `order` coming last. An `order` is
+//! made of at most 50 characters in the `\x20` to `\x7E` range.
+
+use ruma::SpaceChildOrder;
+
+/// The smallest character allowed in an `order`.
+const MIN_CHAR: u8 = 0x20;
+
+/// The biggest character allowed in an `order`.
+const MAX_CHAR: u8 = 0x7E;
+
+/// Compute an `order` sorted strictly between the two given ones.
+///
+/// `None` stands for the start of the children for `previous`, and for their
+/// end for `next`.
+///
+/// Returns `None` if there's no valid `order` between them, e.g. if
+/// `previous` isn't smaller than `next`, or if the result would be too long.
+pub(super) fn order_between(
+    previous: Option<&SpaceChildOrder>,
+    next: Option<&SpaceChildOrder>,
+) -> Option<SpaceChildOrder> {
+    let previous = previous.map_or(&[][..], |order| order.as_str().as_bytes());
+    let next = next.map(|order| order.as_str().as_bytes());
+
+    if next.is_some_and(|next| previous >= next) {
+        return None;
+    }
+
+    let mut order = Vec::new();
+    midpoint(previous, next, &mut order)?;
+
+    SpaceChildOrder::parse(String::from_utf8(order).ok()?).ok()
+}
+
+/// Push to `order` the shortest string sorted strictly between `previous` and
+/// `next`, given that `previous < next`.
+fn midpoint(previous: &[u8], next: Option<&[u8]>, order: &mut Vec<u8>) -> Option<()> {
+    // Keep the common prefix.
+    if let Some(next) = next {
+        let common = previous.iter().zip(next).take_while(|(a, b)| a == b).count();
+
+        if common > 0 {
+            order.extend_from_slice(&next[..common]);
+            return midpoint(&previous[common..], Some(&next[common..]), order);
+        }
+    }
+
+    // The first characters are different, and the bounds are exclusive.
+    let low = previous.first().map_or(MIN_CHAR - 1, |c| *c);
+    let high = next.and_then(|next| next.first()).map_or(MAX_CHAR + 1, |c| *c);
+
+    if high - low > 1 {
+        order.push(low + (high - low) / 2);
+        return Some(());
+    }
+
+    if let Some(next) = next.filter(|next| next.len() > 1) {
+        // The first character of `next` alone is sorted before it.
+        order.push(next[0]);
+        return Some(());
+    }
+
+    if previous.is_empty() {
+        // There's nothing between the empty string and the smallest character.
+        return None;
+    }
+
+    // Keep the first character of `previous`, and find a suffix bigger than the
+    // rest of it.
+    order.push(low);
+    midpoint(&previous[1..], None, order)
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::SpaceChildOrder;
+
+    use super::order_between;
+
+    fn order(s: &str) -> SpaceChildOrder {
+        SpaceChildOrder::parse(s).unwrap()
+    }
+
+    fn between(previous: Option<&str>, next: Option<&str>) -> Option<String> {
+        let previous = previous.map(order);
+        let next = next.map(order);
+
+        let result = order_between(previous.as_ref(), next.as_ref())?;
+
+        // The result is always sorted between the bounds.
+        if let Some(previous) = &previous {
+            assert!(previous.as_str() < result.as_str());
+        }
+        if let Some(next) = &next {
+            assert!(result.as_str() < next.as_str());
+        }
+
+        Some(result.as_str().to_owned())
+    }
+
+    #[test]
+    fn test_order_without_bounds() {
+        assert_eq!(between(None, None).as_deref(), Some("O"));
+    }
+
+    #[test]
+    fn test_order_after() {
+        assert_eq!(between(Some("a"), None).as_deref(), Some("p"));
+        assert_eq!(between(Some("~"), None).as_deref(), Some("~O"));
+    }
+
+    #[test]
+    fn test_order_before() {
+        assert_eq!(between(None, Some("b")).as_deref(), Some("@"));
+        assert_eq!(between(None, Some("!a")).as_deref(), Some(" "));
+        // Nothing is sorted before the smallest order.
+        assert_eq!(between(None, Some(" ")), None);
+    }
+
+    #[test]
+    fn test_order_between() {
+        assert_eq!(between(Some("a"), Some("c")).as_deref(), Some("b"));
+        assert_eq!(between(Some("a"), Some("b")).as_deref(), Some("aO"));
+        assert_eq!(between(Some("ab"), Some("abc")).as_deref(), Some("abA"));
+        assert_eq!(between(Some("a~"), Some("b")).as_deref(), Some("a~O"));
+        assert_eq!(between(Some("a"), Some("bb")).as_deref(), Some("b"));
+
+        // The bounds must be sorted.
+        assert_eq!(between(Some("b"), Some("a")), None);
+        assert_eq!(between(Some("a"), Some("a")), None);
+    }
+
+    #[test]
+    fn test_order_too_long() {
+        let previous = "a".repeat(50);
+        let next = format!("{}b", "a".repeat(49));
+
+        assert_eq!(between(Some(&previous), Some(&next)), None);
+    }
+}
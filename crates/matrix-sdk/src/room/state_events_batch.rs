@@ -0,0 +1,269 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sending several state events to a room, one after the other, with a report
+//! of what happened to each of them.
+//!
+//! See [`Room::send_state_events`](super::Room::send_state_events).
+
+use std::borrow::Borrow;
+
+use ruma::{
+    events::{AnyStateEventContent, StateEventContent, StateEventType},
+    serde::Raw,
+    OwnedEventId,
+};
+use serde_json::json;
+
+use crate::{utils::IntoRawStateEventContent, Error};
+
+/// A state event that is part of a [`StateEventsBatch`].
+#[derive(Clone, Debug)]
+pub struct BatchedStateEvent {
+    /// The type of the state event.
+    pub event_type: StateEventType,
+
+    /// The state key of the state event.
+    pub state_key: String,
+
+    /// The content of the state event.
+    pub content: Raw<AnyStateEventContent>,
+}
+
+/// A list of state events to send to a room, in order.
+#[derive(Clone, Debug, Default)]
+pub struct StateEventsBatch {
+    pub(super) events: Vec<BatchedStateEvent>,
+    pub(super) dry_run: bool,
+}
+
+impl StateEventsBatch {
+    /// Create a new, empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a state event of a statically-known type to the batch.
+    ///
+    /// Returns an error if the content couldn't be serialized.
+    pub fn add<C, K>(&mut self, state_key: &K, content: C) -> serde_json::Result<&mut Self>
+    where
+        C: StateEventContent,
+        C::StateKey: Borrow<K>,
+        K: AsRef<str> + ?Sized,
+    {
+        let event_type = content.event_type();
+        let content = Raw::new(&content)?.cast();
+        Ok(self.add_raw(event_type, state_key.as_ref(), content))
+    }
+
+    /// Add a raw state event to the batch.
+    pub fn add_raw(
+        &mut self,
+        event_type: StateEventType,
+        state_key: &str,
+        content: impl IntoRawStateEventContent,
+    ) -> &mut Self {
+        self.events.push(BatchedStateEvent {
+            event_type,
+            state_key: state_key.to_owned(),
+            content: content.into_raw_state_event_content(),
+        });
+        self
+    }
+
+    /// Only check that the current user is allowed to send all the state
+    /// events of the batch, without sending any of them.
+    pub fn dry_run(&mut self, dry_run: bool) -> &mut Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// The state events of the batch, in the order they will be sent.
+    pub fn events(&self) -> &[BatchedStateEvent] {
+        &self.events
+    }
+
+    /// Whether the batch has no state events.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// What happened to a single state event of a [`StateEventsBatch`].
+#[derive(Debug)]
+pub enum StateEventOutcome {
+    /// The state event was sent successfully.
+    Sent {
+        /// The ID of the event returned by the homeserver.
+        event_id: OwnedEventId,
+
+        /// The state event that was replaced, as it was known locally.
+        previous_state: PreviousState,
+    },
+
+    /// The current user is allowed to send the state event, but it wasn't
+    /// sent because the batch was a dry run, or because another state event
+    /// of the batch isn't allowed.
+    Allowed,
+
+    /// The current user isn't allowed to send this state event, according to
+    /// the power levels of the room.
+    Forbidden,
+
+    /// Sending the state event failed.
+    Failed(Error),
+
+    /// The state event wasn't sent because a previous state event of the batch
+    /// failed to be sent.
+    Skipped,
+}
+
+/// The state that a sent state event of a [`StateEventsBatch`] replaced.
+#[derive(Clone, Debug)]
+pub enum PreviousState {
+    /// There was a state event, with this content.
+    Known(Raw<AnyStateEventContent>),
+
+    /// There was no such state event in the room.
+    Absent,
+
+    /// The previous state event couldn't be loaded, or only its stripped form
+    /// is known, so it can't be restored.
+    Unknown,
+}
+
+/// The report of sending a [`StateEventsBatch`].
+#[derive(Debug)]
+pub struct StateEventsBatchReport {
+    /// The state events of the batch, with what happened to each of them, in
+    /// the order of the batch.
+    pub results: Vec<(BatchedStateEvent, StateEventOutcome)>,
+
+    /// Whether the batch was a dry run.
+    pub dry_run: bool,
+}
+
+impl StateEventsBatchReport {
+    /// Whether all the state events were sent, or would be allowed to be sent
+    /// in case of a dry run.
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(|(_, outcome)| {
+            if self.dry_run {
+                matches!(outcome, StateEventOutcome::Allowed)
+            } else {
+                matches!(outcome, StateEventOutcome::Sent { .. })
+            }
+        })
+    }
+
+    /// The state events that were sent successfully, with the returned event
+    /// IDs.
+    pub fn sent(&self) -> impl Iterator<Item = (&BatchedStateEvent, &OwnedEventId)> {
+        self.results.iter().filter_map(|(event, outcome)| match outcome {
+            StateEventOutcome::Sent { event_id, .. } => Some((event, event_id)),
+            _ => None,
+        })
+    }
+
+    /// The first state event that couldn't be sent, with the reason.
+    pub fn first_failure(&self) -> Option<(&BatchedStateEvent, &StateEventOutcome)> {
+        self.results
+            .iter()
+            .find(|(_, outcome)| {
+                matches!(outcome, StateEventOutcome::Forbidden | StateEventOutcome::Failed(_))
+            })
+            .map(|(event, outcome)| (event, outcome))
+    }
+
+    /// Build a batch that restores the previous state of every state event
+    /// that was sent successfully, in reverse order.
+    ///
+    /// Matrix doesn't allow to remove a state event, so state events that
+    /// didn't exist before are replaced with an empty content. State events
+    /// whose previous state is [`PreviousState::Unknown`] are left as they
+    /// are, since restoring them could wipe the actual state of the room.
+    pub fn rollback_batch(&self) -> StateEventsBatch {
+        let mut batch = StateEventsBatch::new();
+
+        for (event, outcome) in self.results.iter().rev() {
+            let StateEventOutcome::Sent { previous_state, .. } = outcome else {
+                continue;
+            };
+
+            let content = match previous_state {
+                PreviousState::Known(content) => content.clone(),
+                PreviousState::Absent => json!({}).into_raw_state_event_content(),
+                PreviousState::Unknown => continue,
+            };
+
+            batch.add_raw(event.event_type.clone(), &event.state_key, content);
+        }
+
+        batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::{events::StateEventType, owned_event_id, serde::Raw};
+    use serde_json::json;
+
+    use super::{BatchedStateEvent, PreviousState, StateEventOutcome, StateEventsBatchReport};
+
+    fn sent(
+        event_type: StateEventType,
+        previous_state: PreviousState,
+    ) -> (BatchedStateEvent, StateEventOutcome) {
+        let event = BatchedStateEvent {
+            event_type,
+            state_key: String::new(),
+            content: Raw::new(&json!({ "new": true })).unwrap().cast(),
+        };
+        let outcome =
+            StateEventOutcome::Sent { event_id: owned_event_id!("$sent"), previous_state };
+        (event, outcome)
+    }
+
+    #[test]
+    fn test_rollback_batch_skips_unknown_previous_state() {
+        let report = StateEventsBatchReport {
+            results: vec![
+                sent(
+                    StateEventType::RoomName,
+                    PreviousState::Known(Raw::new(&json!({ "name": "Old name" })).unwrap().cast()),
+                ),
+                sent(StateEventType::RoomTopic, PreviousState::Absent),
+                sent(StateEventType::RoomAvatar, PreviousState::Unknown),
+            ],
+            dry_run: false,
+        };
+
+        let rollback = report.rollback_batch();
+        let events = rollback.events();
+
+        // The avatar is left untouched, and the other events are restored in
+        // reverse order.
+        assert_eq!(events.len(), 2);
+
+        assert_eq!(events[0].event_type, StateEventType::RoomTopic);
+        assert_eq!(events[0].content.json().get(), "{}");
+
+        assert_eq!(events[1].event_type, StateEventType::RoomName);
+        assert_eq!(
+            events[1].content.get_field::<String>("name").unwrap().as_deref(),
+            Some("Old name")
+        );
+    }
+}
@@ -0,0 +1,146 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reports of the untrusted devices which would receive the room keys of a
+//! room.
+#![cfg(feature = "e2e-encryption")]
+
+use std::collections::BTreeMap;
+
+use matrix_sdk_base::crypto::LocalTrust;
+use ruma::{OwnedDeviceId, OwnedUserId};
+
+use crate::encryption::identities::Device;
+
+/// The reason why a device is considered untrusted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UntrustedDeviceReason {
+    /// The device hasn't been signed by its owner, so there's no guarantee
+    /// that it belongs to them.
+    UnsignedDevice,
+
+    /// The device has been signed by its owner, but the identity of the owner
+    /// hasn't been verified.
+    UnverifiedUser,
+}
+
+impl UntrustedDeviceReason {
+    /// Get the reason why the given device is untrusted, or `None` if the
+    /// device is trusted, or won't receive any room key anyway.
+    pub(super) fn for_device(device: &Device) -> Option<Self> {
+        if device.is_blacklisted() || device.is_deleted() {
+            return None;
+        }
+
+        // Devices which have been manually trusted, or whose trust is ignored, are
+        // shared the room keys in every room, as if they were verified.
+        if matches!(device.local_trust_state(), LocalTrust::Verified | LocalTrust::Ignored)
+            || device.is_verified()
+        {
+            return None;
+        }
+
+        Some(if device.is_cross_signed_by_owner() {
+            Self::UnverifiedUser
+        } else {
+            Self::UnsignedDevice
+        })
+    }
+}
+
+/// An untrusted device of a member of a room.
+#[derive(Clone, Debug)]
+pub struct UntrustedDevice {
+    /// The owner of the device.
+    pub user_id: OwnedUserId,
+
+    /// The ID of the device.
+    pub device_id: OwnedDeviceId,
+
+    /// The display name of the device, if any.
+    pub display_name: Option<String>,
+
+    /// Why the device is untrusted.
+    pub reason: UntrustedDeviceReason,
+}
+
+/// The untrusted devices of the members of a room, which need to be confirmed
+/// before sending in a room that only shares its keys with trusted devices.
+///
+/// See [`Room::untrusted_devices_report`](super::Room::untrusted_devices_report)
+/// and
+/// [`Room::confirm_untrusted_devices`](super::Room::confirm_untrusted_devices).
+#[derive(Clone, Debug, Default)]
+pub struct UntrustedDevicesReport {
+    /// The untrusted devices.
+    pub devices: Vec<UntrustedDevice>,
+}
+
+impl UntrustedDevicesReport {
+    /// Whether the report doesn't contain any device.
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// The untrusted devices, grouped by their owner.
+    pub fn user_device_map(&self) -> BTreeMap<OwnedUserId, Vec<OwnedDeviceId>> {
+        let mut map = BTreeMap::<_, Vec<_>>::new();
+
+        for device in &self.devices {
+            map.entry(device.user_id.clone()).or_default().push(device.device_id.clone());
+        }
+
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::{owned_device_id, owned_user_id};
+
+    use super::{UntrustedDevice, UntrustedDeviceReason, UntrustedDevicesReport};
+
+    #[test]
+    fn test_user_device_map_groups_devices_by_owner() {
+        let device = |user_id: &str, device_id: &str| UntrustedDevice {
+            user_id: user_id.try_into().unwrap(),
+            device_id: device_id.into(),
+            display_name: None,
+            reason: UntrustedDeviceReason::UnsignedDevice,
+        };
+
+        let report = UntrustedDevicesReport {
+            devices: vec![
+                device("@bob:example.org", "BOBDEVICE1"),
+                device("@carol:example.org", "CAROLDEVICE"),
+                device("@bob:example.org", "BOBDEVICE2"),
+            ],
+        };
+
+        assert!(!report.is_empty());
+
+        let map = report.user_device_map();
+        assert_eq!(map.len(), 2);
+        assert_eq!(
+            map[&owned_user_id!("@bob:example.org")],
+            vec![owned_device_id!("BOBDEVICE1"), owned_device_id!("BOBDEVICE2")]
+        );
+        assert_eq!(
+            map[&owned_user_id!("@carol:example.org")],
+            vec![owned_device_id!("CAROLDEVICE")]
+        );
+
+        assert!(UntrustedDevicesReport::default().user_device_map().is_empty());
+    }
+}
@@ -0,0 +1,537 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sending a group of requests, in order, where a request is only sent after
+//! the previous request of the group has been sent successfully.
+
+use std::borrow::Borrow;
+
+use matrix_sdk_base::{
+    store::{
+        ChildTransactionId, DependentQueuedRequest, DependentQueuedRequestKind, DynStateStore,
+        QueueWedgeError, QueuedRequestKind, SerializableEventContent,
+    },
+    RoomState,
+};
+use ruma::{
+    events::{AnyMessageLikeEventContent, StateEventContent, StateEventType},
+    serde::Raw,
+    MilliSecondsSinceUnixEpoch, OwnedTransactionId, TransactionId,
+};
+use tracing::{instrument, trace};
+
+use super::{
+    LocalEcho, LocalEchoContent, QueueStorage, RoomSendQueue, RoomSendQueueError,
+    RoomSendQueueStorageError, RoomSendQueueUpdate, SendHandle,
+};
+use crate::utils::IntoRawStateEventContent;
+
+/// A group of events to send to a room, in order, with
+/// [`RoomSendQueue::send_group`].
+#[derive(Debug, Default)]
+pub struct SendGroup {
+    requests: Vec<QueuedRequestKind>,
+}
+
+impl SendGroup {
+    /// Create a new, empty group.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a message-like event to the group.
+    ///
+    /// Returns an error if the content couldn't be serialized.
+    pub fn add_message(
+        &mut self,
+        content: AnyMessageLikeEventContent,
+    ) -> serde_json::Result<&mut Self> {
+        let content = SerializableEventContent::new(&content)?;
+        self.requests.push(content.into());
+        Ok(self)
+    }
+
+    /// Add a raw message-like event to the group.
+    pub fn add_raw_message(
+        &mut self,
+        content: Raw<AnyMessageLikeEventContent>,
+        event_type: String,
+    ) -> &mut Self {
+        self.requests.push(SerializableEventContent::from_raw(content, event_type).into());
+        self
+    }
+
+    /// Add a state event of a statically-known type to the group.
+    ///
+    /// Returns an error if the content couldn't be serialized.
+    pub fn add_state_event<C, K>(
+        &mut self,
+        state_key: &K,
+        content: C,
+    ) -> serde_json::Result<&mut Self>
+    where
+        C: StateEventContent,
+        C::StateKey: Borrow<K>,
+        K: AsRef<str> + ?Sized,
+    {
+        let event_type = content.event_type();
+        let content = Raw::new(&content)?.cast();
+        Ok(self.add_raw_state_event(event_type, state_key.as_ref(), content))
+    }
+
+    /// Add a raw state event to the group.
+    pub fn add_raw_state_event(
+        &mut self,
+        event_type: StateEventType,
+        state_key: &str,
+        content: impl IntoRawStateEventContent,
+    ) -> &mut Self {
+        self.requests.push(QueuedRequestKind::StateEvent {
+            event_type: event_type.to_string(),
+            state_key: state_key.to_owned(),
+            content: content.into_raw_state_event_content(),
+        });
+        self
+    }
+
+    /// The number of events in the group.
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    /// Whether the group has no events.
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+}
+
+/// The status of a request of a group sent with
+/// [`RoomSendQueue::send_group`].
+#[derive(Clone, Debug)]
+pub enum SendGroupItemStatus {
+    /// The request is in the send queue, waiting to be sent, or being sent.
+    Queued,
+
+    /// The request is held until the previous request of the group has been
+    /// sent.
+    Held,
+
+    /// The request couldn't be sent because of an unrecoverable error.
+    ///
+    /// The later requests of the group are held until it's unwedged, with
+    /// [`SendGroupHandle::unwedge`], or the group is aborted, with
+    /// [`SendGroupHandle::abort`].
+    Wedged(QueueWedgeError),
+
+    /// The request isn't in the send queue anymore: either it's been sent, or
+    /// it's been aborted.
+    Done,
+}
+
+/// A handle to follow and manipulate a group of requests sent with
+/// [`RoomSendQueue::send_group`].
+#[derive(Clone, Debug)]
+pub struct SendGroupHandle {
+    /// Link to the send queue used to send the group.
+    room: RoomSendQueue,
+
+    /// The transaction IDs of the requests of the group, in order.
+    transaction_ids: Vec<OwnedTransactionId>,
+}
+
+impl SendGroupHandle {
+    /// The transaction IDs of the requests of the group, in order.
+    ///
+    /// They're the transaction IDs used in the [`RoomSendQueueUpdate`]s about
+    /// the requests of the group.
+    pub fn transaction_ids(&self) -> &[OwnedTransactionId] {
+        &self.transaction_ids
+    }
+
+    /// Get the status of every request of the group, in order.
+    pub async fn status(&self) -> Result<Vec<SendGroupItemStatus>, RoomSendQueueStorageError> {
+        self.room.inner.queue.group_status(&self.transaction_ids).await
+    }
+
+    /// Whether all the requests of the group are done.
+    ///
+    /// See [`SendGroupItemStatus::Done`].
+    pub async fn is_done(&self) -> Result<bool, RoomSendQueueStorageError> {
+        Ok(self.status().await?.iter().all(|status| matches!(status, SendGroupItemStatus::Done)))
+    }
+
+    /// Abort all the requests of the group that haven't been sent yet.
+    ///
+    /// A request that is being sent can't be aborted, but the requests after
+    /// it are.
+    ///
+    /// Returns whether at least one request has been aborted.
+    #[instrument(skip(self), fields(room_id = %self.room.inner.room.room_id()))]
+    pub async fn abort(&self) -> Result<bool, RoomSendQueueStorageError> {
+        trace!("received a request to abort a group");
+
+        let aborted = self.room.inner.queue.abort_group(&self.transaction_ids).await?;
+
+        for transaction_id in &aborted {
+            let _ = self.room.inner.updates.send(RoomSendQueueUpdate::CancelledLocalEvent {
+                transaction_id: transaction_id.clone(),
+            });
+        }
+
+        Ok(!aborted.is_empty())
+    }
+
+    /// Unwedge the request of the group that couldn't be sent, if any, and try
+    /// to resend it, along with the later requests of the group.
+    pub async fn unwedge(&self) -> Result<(), RoomSendQueueError> {
+        let room = &self.room.inner;
+
+        for (transaction_id, status) in self.transaction_ids.iter().zip(self.status().await?) {
+            if matches!(status, SendGroupItemStatus::Wedged(_)) {
+                room.queue.mark_as_unwedged(transaction_id).await?;
+
+                let _ = room.updates.send(RoomSendQueueUpdate::RetryEvent {
+                    transaction_id: transaction_id.clone(),
+                });
+            }
+        }
+
+        // Wake up the queue, in case the room was asleep before unwedging the request.
+        room.notifier.notify_one();
+
+        Ok(())
+    }
+}
+
+impl RoomSendQueue {
+    /// Queues a group of events for sending them to this room, in order.
+    ///
+    /// Each event of the group is only sent once the previous one has been
+    /// sent successfully. If an event can't be sent because of an
+    /// unrecoverable error, the later events of the group are held, until the
+    /// failing event is unwedged or the group is aborted, with the returned
+    /// [`SendGroupHandle`]. Other events of the send queue are not held.
+    ///
+    /// This is useful to send events that only make sense together, for
+    /// example changing the topic of a room and announcing it with a
+    /// message.
+    ///
+    /// Local echoes are created for the message-like events of the group, as
+    /// with [`Self::send`], but not for its state events.
+    pub async fn send_group(
+        &self,
+        group: SendGroup,
+    ) -> Result<SendGroupHandle, RoomSendQueueError> {
+        let Some(room) = self.inner.room.get() else {
+            return Err(RoomSendQueueError::RoomDisappeared);
+        };
+        if room.state() != RoomState::Joined {
+            return Err(RoomSendQueueError::RoomNotJoined);
+        }
+        if group.is_empty() {
+            return Err(RoomSendQueueError::EmptyGroup);
+        }
+
+        let created_at = MilliSecondsSinceUnixEpoch::now();
+        let transaction_ids =
+            self.inner.queue.push_group(group.requests.clone(), created_at).await?;
+        trace!(?transaction_ids, "manager sends a group of requests to the background task");
+
+        self.inner.notifier.notify_one();
+
+        for (transaction_id, request) in transaction_ids.iter().zip(group.requests) {
+            let QueuedRequestKind::Event { content } = request else {
+                continue;
+            };
+
+            let send_handle = SendHandle {
+                room: self.clone(),
+                transaction_id: transaction_id.clone(),
+                media_handles: vec![],
+                created_at,
+            };
+
+            let _ = self.inner.updates.send(RoomSendQueueUpdate::NewLocalEvent(LocalEcho {
+                transaction_id: transaction_id.clone(),
+                content: LocalEchoContent::Event {
+                    serialized_event: content,
+                    send_handle,
+                    send_error: None,
+                },
+            }));
+        }
+
+        Ok(SendGroupHandle { room: self.clone(), transaction_ids })
+    }
+}
+
+impl QueueStorage {
+    /// Push a group of requests: the first one is a queued request, and every
+    /// other one is a dependent request of the previous one.
+    ///
+    /// Returns the transaction IDs of the requests, in order.
+    async fn push_group(
+        &self,
+        requests: Vec<QueuedRequestKind>,
+        created_at: MilliSecondsSinceUnixEpoch,
+    ) -> Result<Vec<OwnedTransactionId>, RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+        let client = guard.client()?;
+        let store = client.state_store();
+
+        let mut transaction_ids: Vec<OwnedTransactionId> = Vec::with_capacity(requests.len());
+
+        for request in requests {
+            match transaction_ids.last() {
+                None => {
+                    let transaction_id = OwnedTransactionId::from(ChildTransactionId::new());
+
+                    store
+                        .save_send_queue_request(
+                            &self.room_id,
+                            transaction_id.clone(),
+                            created_at,
+                            request,
+                            Self::LOW_PRIORITY,
+                        )
+                        .await?;
+
+                    transaction_ids.push(transaction_id);
+                }
+
+                Some(parent_transaction_id) => {
+                    let transaction_id = ChildTransactionId::new();
+
+                    store
+                        .save_dependent_queued_request(
+                            &self.room_id,
+                            parent_transaction_id,
+                            transaction_id.clone(),
+                            created_at,
+                            DependentQueuedRequestKind::SendInGroup { request: Box::new(request) },
+                        )
+                        .await?;
+
+                    transaction_ids.push(transaction_id.into());
+                }
+            }
+        }
+
+        Ok(transaction_ids)
+    }
+
+    /// Get the status of the requests of a group pushed with
+    /// [`Self::push_group`].
+    async fn group_status(
+        &self,
+        transaction_ids: &[OwnedTransactionId],
+    ) -> Result<Vec<SendGroupItemStatus>, RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+        let client = guard.client()?;
+        let store = client.state_store();
+
+        let queued_requests = store.load_send_queue_requests(&self.room_id).await?;
+        let dependent_requests = store.load_dependent_queued_requests(&self.room_id).await?;
+
+        Ok(transaction_ids
+            .iter()
+            .map(|transaction_id| {
+                if let Some(queued) =
+                    queued_requests.iter().find(|queued| queued.transaction_id == *transaction_id)
+                {
+                    match &queued.error {
+                        Some(error) => SendGroupItemStatus::Wedged(error.clone()),
+                        None => SendGroupItemStatus::Queued,
+                    }
+                } else if dependent_requests
+                    .iter()
+                    .any(|dependent| *dependent.own_transaction_id == **transaction_id)
+                {
+                    SendGroupItemStatus::Held
+                } else {
+                    SendGroupItemStatus::Done
+                }
+            })
+            .collect())
+    }
+
+    /// Remove the requests of a group pushed with [`Self::push_group`] that
+    /// haven't been sent yet, and aren't being sent.
+    ///
+    /// Returns the transaction IDs of the removed requests.
+    async fn abort_group(
+        &self,
+        transaction_ids: &[OwnedTransactionId],
+    ) -> Result<Vec<OwnedTransactionId>, RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+        let client = guard.client()?;
+        let store = client.state_store();
+
+        let being_sent = guard.being_sent.as_ref().map(|info| info.transaction_id.clone());
+        let mut aborted = Vec::new();
+
+        for transaction_id in transaction_ids {
+            if being_sent.as_ref() == Some(transaction_id) {
+                continue;
+            }
+
+            let removed = store.remove_send_queue_request(&self.room_id, transaction_id).await?
+                || store
+                    .remove_dependent_queued_request(
+                        &self.room_id,
+                        &ChildTransactionId::from(transaction_id.clone()),
+                    )
+                    .await?;
+
+            if removed {
+                aborted.push(transaction_id.clone());
+            }
+        }
+
+        Ok(aborted)
+    }
+    /// Remove a request of a group that is held until the previous request of
+    /// the group has been sent.
+    ///
+    /// The next request of the group, if any, is then held by the previous
+    /// request instead.
+    ///
+    /// Returns whether the request has been removed.
+    pub(super) async fn remove_held_in_group(
+        &self,
+        store: &DynStateStore,
+        transaction_id: &TransactionId,
+    ) -> Result<bool, RoomSendQueueStorageError> {
+        let dependent_requests = store.load_dependent_queued_requests(&self.room_id).await?;
+
+        let Some(held) = dependent_requests.iter().find(|dependent| {
+            *dependent.own_transaction_id == *transaction_id
+                && matches!(dependent.kind, DependentQueuedRequestKind::SendInGroup { .. })
+        }) else {
+            return Ok(false);
+        };
+
+        if !store.remove_dependent_queued_request(&self.room_id, &held.own_transaction_id).await? {
+            return Ok(false);
+        }
+
+        self.promote_next_in_group(store, &dependent_requests, transaction_id, Some(held)).await?;
+
+        Ok(true)
+    }
+
+    /// Replace the content of a request of a group that is held until the
+    /// previous request of the group has been sent.
+    ///
+    /// Returns whether the request has been replaced; state events of a group
+    /// can't be replaced.
+    pub(super) async fn replace_held_in_group(
+        &self,
+        store: &DynStateStore,
+        transaction_id: &TransactionId,
+        serializable: SerializableEventContent,
+    ) -> Result<bool, RoomSendQueueStorageError> {
+        let dependent_requests = store.load_dependent_queued_requests(&self.room_id).await?;
+
+        let Some(held) = dependent_requests.iter().find(|dependent| {
+            *dependent.own_transaction_id == *transaction_id
+                && matches!(
+                    &dependent.kind,
+                    DependentQueuedRequestKind::SendInGroup { request }
+                        if matches!(**request, QueuedRequestKind::Event { .. })
+                )
+        }) else {
+            return Ok(false);
+        };
+
+        Ok(store
+            .update_dependent_queued_request(
+                &self.room_id,
+                &held.own_transaction_id,
+                DependentQueuedRequestKind::SendInGroup { request: Box::new(serializable.into()) },
+            )
+            .await?)
+    }
+
+    /// Once the request with the given transaction ID has been removed from a
+    /// group, attach the next request of the group, if any, to the previous
+    /// one, so that it's not held forever.
+    ///
+    /// If the removed request was the head of the group, i.e. `removed_held`
+    /// is `None`, the next request is queued instead.
+    pub(super) async fn promote_next_in_group(
+        &self,
+        store: &DynStateStore,
+        dependent_requests: &[DependentQueuedRequest],
+        transaction_id: &TransactionId,
+        removed_held: Option<&DependentQueuedRequest>,
+    ) -> Result<(), RoomSendQueueStorageError> {
+        let Some((next, request)) =
+            dependent_requests.iter().find_map(|dependent| match &dependent.kind {
+                DependentQueuedRequestKind::SendInGroup { request }
+                    if *dependent.parent_transaction_id == *transaction_id =>
+                {
+                    Some((dependent, request))
+                }
+                _ => None,
+            })
+        else {
+            return Ok(());
+        };
+
+        store.remove_dependent_queued_request(&self.room_id, &next.own_transaction_id).await?;
+
+        match removed_held {
+            Some(removed_held) => {
+                store
+                    .save_dependent_queued_request(
+                        &self.room_id,
+                        &removed_held.parent_transaction_id,
+                        next.own_transaction_id.clone(),
+                        next.created_at,
+                        next.kind.clone(),
+                    )
+                    .await?;
+
+                // If the previous request has already been sent, the next request is ready to
+                // be sent too.
+                if let Some(parent_key) = removed_held.parent_key.clone() {
+                    store
+                        .mark_dependent_queued_requests_as_ready(
+                            &self.room_id,
+                            &removed_held.parent_transaction_id,
+                            parent_key,
+                        )
+                        .await?;
+                }
+            }
+
+            None => {
+                // Queue the request with a high priority, as if the removed request had been
+                // sent.
+                store
+                    .save_send_queue_request(
+                        &self.room_id,
+                        next.own_transaction_id.clone().into(),
+                        next.created_at,
+                        (**request).clone(),
+                        Self::HIGH_PRIORITY,
+                    )
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
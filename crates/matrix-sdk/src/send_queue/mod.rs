@@ -67,6 +67,11 @@
 //! This allows implementing deferred edits/redacts, as hinted to in the
 //! previous section.
 //!
+//! It also allows sending a group of requests in order, with
+//! [`RoomSendQueue::send_group`]: each request of the group is a
+//! [`DependentQueuedRequestKind::SendInGroup`] of the previous one, so it's
+//! held until the previous one has been sent.
+//!
 //! ## Media upload
 //!
 //! This dependency system also allows uploading medias, since the media's
@@ -129,7 +134,7 @@
 //! remembered and fixed up into the media event, just before sending it.
 
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     str::FromStr as _,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -138,6 +143,7 @@ use std::{
 };
 
 use as_variant::as_variant;
+use eyeball::{SharedObservable, Subscriber};
 #[cfg(feature = "unstable-msc4274")]
 use matrix_sdk_base::store::FinishGalleryItemInfo;
 use matrix_sdk_base::{
@@ -172,15 +178,18 @@ use tracing::{debug, error, info, instrument, trace, warn};
 #[cfg(feature = "e2e-encryption")]
 use crate::crypto::{OlmError, SessionRecipientCollectionError};
 use crate::{
-    client::WeakClient,
+    client::{ShutdownTracker, WeakClient},
     config::RequestConfig,
     error::RetryKind,
     room::{edit::EditedContent, WeakRoom},
     Client, Media, Room,
 };
 
+mod group;
 mod upload;
 
+pub use group::{SendGroup, SendGroupHandle, SendGroupItemStatus};
+
 /// A client-wide send queue, for all the rooms known by a client.
 pub struct SendQueue {
     client: Client,
@@ -396,7 +405,7 @@ impl RoomSendQueue {
         let notifier = Arc::new(Notify::new());
 
         let weak_room = WeakRoom::new(WeakClient::from_client(client), room_id);
-        let locally_enabled = Arc::new(AtomicBool::new(globally_enabled));
+        let locally_enabled = SharedObservable::new(globally_enabled);
 
         let task = spawn(Self::sending_task(
             weak_room.clone(),
@@ -406,6 +415,7 @@ impl RoomSendQueue {
             locally_enabled.clone(),
             global_error_reporter,
             is_dropping,
+            client.inner.shutdown.clone(),
         ));
 
         Self {
@@ -513,19 +523,29 @@ impl RoomSendQueue {
     /// background for each room that has a send queue.
     ///
     /// It only progresses forward: nothing can be cancelled at any point, which
-    /// makes the implementation not overly complicated to follow.
+    /// makes the implementation not overly complicated to follow. The only
+    /// exception is the client shutting down, which stops the task while it's
+    /// waiting, or while a request is in flight; that request stays in the
+    /// queue, to be sent again later.
+    #[allow(clippy::too_many_arguments)]
     #[instrument(skip_all, fields(room_id = %room.room_id()))]
     async fn sending_task(
         room: WeakRoom,
         queue: QueueStorage,
         notifier: Arc<Notify>,
         updates: broadcast::Sender<RoomSendQueueUpdate>,
-        locally_enabled: Arc<AtomicBool>,
+        locally_enabled: SharedObservable<bool>,
         global_error_reporter: broadcast::Sender<SendQueueRoomError>,
         is_dropping: Arc<AtomicBool>,
+        shutdown: ShutdownTracker,
     ) {
         trace!("spawned the sending task");
 
+        let Some(_shutdown_guard) = shutdown.enter() else {
+            trace!("the client has been shut down, not starting");
+            return;
+        };
+
         loop {
             // A request to shut down should be preferred above everything else.
             if is_dropping.load(Ordering::SeqCst) {
@@ -544,10 +564,13 @@ impl RoomSendQueue {
                 let _ = updates.send(up);
             }
 
-            if !locally_enabled.load(Ordering::SeqCst) {
+            if !locally_enabled.get() {
                 trace!("not enabled, sleeping");
                 // Wait for an explicit wakeup.
-                notifier.notified().await;
+                if shutdown.run_until_shut_down(notifier.notified()).await.is_none() {
+                    trace!("the client is shutting down");
+                    break;
+                }
                 continue;
             }
 
@@ -557,7 +580,10 @@ impl RoomSendQueue {
                 Ok(None) => {
                     trace!("queue is empty, sleeping");
                     // Wait for an explicit wakeup.
-                    notifier.notified().await;
+                    if shutdown.run_until_shut_down(notifier.notified()).await.is_none() {
+                        trace!("the client is shutting down");
+                        break;
+                    }
                     continue;
                 }
 
@@ -580,7 +606,17 @@ impl RoomSendQueue {
                 continue;
             };
 
-            match Self::handle_request(&room, queued_request, cancel_upload_rx).await {
+            let Some(result) = shutdown
+                .run_until_shut_down(Self::handle_request(&room, queued_request, cancel_upload_rx))
+                .await
+            else {
+                // The request is still in the queue; it will be sent again by the next client
+                // using this store.
+                trace!(txn_id = %txn_id, "the client is shutting down, giving up on the request");
+                break;
+            };
+
+            match result {
                 Ok(Some(parent_key)) => match queue.mark_as_sent(&txn_id, parent_key.clone()).await
                 {
                     Ok(()) => match parent_key {
@@ -629,7 +665,7 @@ impl RoomSendQueue {
                     };
 
                     // Disable the queue for this room after any kind of error happened.
-                    locally_enabled.store(false, Ordering::SeqCst);
+                    locally_enabled.set_if_not_eq(false);
 
                     if is_recoverable {
                         warn!(txn_id = %txn_id, error = ?err, "Recoverable error when sending request: {err}, disabling send queue");
@@ -696,6 +732,13 @@ impl RoomSendQueue {
                 Ok(Some(SentRequestKey::Event(res.event_id)))
             }
 
+            QueuedRequestKind::StateEvent { event_type, state_key, content } => {
+                let res = room.send_state_event_raw(&event_type, &state_key, content).await?;
+
+                trace!(txn_id = %request.transaction_id, event_id = %res.event_id, "state event successfully sent");
+                Ok(Some(SentRequestKey::Event(res.event_id)))
+            }
+
             QueuedRequestKind::MediaUpload {
                 content_type,
                 cache_key,
@@ -791,12 +834,21 @@ impl RoomSendQueue {
 
     /// Returns whether the room is enabled, at the room level.
     pub fn is_enabled(&self) -> bool {
-        self.inner.locally_enabled.load(Ordering::SeqCst)
+        self.inner.locally_enabled.get()
+    }
+
+    /// Subscribe to the enablement status of this room's send queue.
+    ///
+    /// The stream yields a new value whenever the queue is paused or resumed,
+    /// be it by a call to [`Self::set_enabled`] or because the queue ran into
+    /// an error and disabled itself.
+    pub fn subscribe_enabled(&self) -> Subscriber<bool> {
+        self.inner.locally_enabled.subscribe()
     }
 
     /// Set the locally enabled flag for this room queue.
     pub fn set_enabled(&self, enabled: bool) {
-        self.inner.locally_enabled.store(enabled, Ordering::SeqCst);
+        self.inner.locally_enabled.set_if_not_eq(enabled);
 
         // No need to wake a task to tell it it's been disabled, so only notify if we're
         // re-enabling the queue.
@@ -804,6 +856,33 @@ impl RoomSendQueue {
             self.inner.notifier.notify_one();
         }
     }
+
+    /// Remove all the requests that haven't been sent yet in this room,
+    /// including the wedged ones.
+    ///
+    /// The request that's being sent at the moment, if any, isn't interrupted
+    /// and will be sent as usual. Each removed local echo is reported to
+    /// observers with a [`RoomSendQueueUpdate::CancelledLocalEvent`].
+    ///
+    /// This is useful before leaving a room, so that messages that couldn't be
+    /// sent aren't kept around forever. It's recommended to pause the queue
+    /// first, with [`Self::set_enabled`], so no new request starts being sent
+    /// in the meantime.
+    ///
+    /// Returns the number of removed local echoes.
+    pub async fn clear_unsent(&self) -> Result<usize, RoomSendQueueStorageError> {
+        let removed = self.inner.queue.clear_unsent().await?;
+        let num_removed = removed.len();
+
+        for transaction_id in removed {
+            let _ = self
+                .inner
+                .updates
+                .send(RoomSendQueueUpdate::CancelledLocalEvent { transaction_id });
+        }
+
+        Ok(num_removed)
+    }
 }
 
 impl From<&crate::Error> for QueueWedgeError {
@@ -828,6 +907,11 @@ impl From<&crate::Error> for QueueWedgeError {
                 _ => QueueWedgeError::GenericApiError { msg: value.to_string() },
             },
 
+            #[cfg(feature = "e2e-encryption")]
+            crate::Error::UntrustedDevices(report) => {
+                QueueWedgeError::UntrustedDevices { user_device_map: report.user_device_map() }
+            }
+
             // Flatten errors of `Self` type.
             crate::Error::SendQueueWedgeError(error) => *error.clone(),
 
@@ -859,7 +943,7 @@ struct RoomSendQueueInner {
 
     /// Should the room process new requests or not (because e.g. it might be
     /// running off the network)?
-    locally_enabled: Arc<AtomicBool>,
+    locally_enabled: SharedObservable<bool>,
 
     /// Handle to the actual sending task. Unused, but kept alive along this
     /// data structure.
@@ -1143,13 +1227,91 @@ impl QueueStorage {
             return Ok(true);
         }
 
-        let removed = guard
-            .client()?
-            .state_store()
-            .remove_send_queue_request(&self.room_id, transaction_id)
-            .await?;
+        let client = guard.client()?;
+        let store = client.state_store();
+
+        if store.remove_send_queue_request(&self.room_id, transaction_id).await? {
+            // If this request was the head of a group, the next request of the group isn't
+            // held anymore.
+            let dependent_requests = store.load_dependent_queued_requests(&self.room_id).await?;
+            self.promote_next_in_group(store, &dependent_requests, transaction_id, None).await?;
+
+            return Ok(true);
+        }
+
+        // The request may be held in a group.
+        self.remove_held_in_group(store, transaction_id).await
+    }
+
+    /// Remove all the requests of this queue that aren't being sent right now,
+    /// along with their dependent requests.
+    ///
+    /// The request being sent, and everything that transitively depends on
+    /// it (e.g. the event accompanying a media upload), is kept.
+    ///
+    /// Returns the transaction ids of the removed local echoes, i.e. the ones
+    /// that were materialized as events or reactions.
+    async fn clear_unsent(&self) -> Result<Vec<OwnedTransactionId>, RoomSendQueueStorageError> {
+        // Keep the lock until we're done touching the storage.
+        let guard = self.store.lock().await;
+        let client = guard.client()?;
+        let store = client.state_store();
+
+        let mut kept_txns = HashSet::new();
+        if let Some(info) = guard.being_sent.as_ref() {
+            kept_txns.insert(info.transaction_id.clone());
+        }
+
+        let dependent_requests = store.load_dependent_queued_requests(&self.room_id).await?;
+
+        // A dependent request may be the parent of another one (e.g. a file upload
+        // depending on its thumbnail upload, and being itself the parent of the
+        // media event), so propagate the kept set until it's stable.
+        loop {
+            let mut changed = false;
+            for dep in &dependent_requests {
+                if kept_txns.contains(&dep.parent_transaction_id) {
+                    changed |= kept_txns.insert(dep.own_transaction_id.clone().into());
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let mut removed_local_echoes = Vec::new();
+
+        for queued in store.load_send_queue_requests(&self.room_id).await? {
+            if kept_txns.contains(&queued.transaction_id) {
+                continue;
+            }
+
+            let removed =
+                store.remove_send_queue_request(&self.room_id, &queued.transaction_id).await?;
+
+            if removed && matches!(queued.kind, QueuedRequestKind::Event { .. }) {
+                removed_local_echoes.push(queued.transaction_id);
+            }
+        }
+
+        for dep in dependent_requests {
+            if kept_txns.contains(&dep.parent_transaction_id) {
+                continue;
+            }
+
+            let removed = store
+                .remove_dependent_queued_request(&self.room_id, &dep.own_transaction_id)
+                .await?;
+
+            if removed
+                && (dep.is_own_event()
+                    || matches!(dep.kind, DependentQueuedRequestKind::ReactEvent { .. }))
+            {
+                removed_local_echoes.push(dep.own_transaction_id.into());
+            }
+        }
 
-        Ok(removed)
+        Ok(removed_local_echoes)
     }
 
     /// Replace an event that has been sent with [`Self::push`] with the given
@@ -1184,13 +1346,18 @@ impl QueueStorage {
             return Ok(true);
         }
 
-        let edited = guard
-            .client()?
-            .state_store()
-            .update_send_queue_request(&self.room_id, transaction_id, serializable.into())
-            .await?;
+        let client = guard.client()?;
+        let store = client.state_store();
 
-        Ok(edited)
+        if store
+            .update_send_queue_request(&self.room_id, transaction_id, serializable.clone().into())
+            .await?
+        {
+            return Ok(true);
+        }
+
+        // The request may be held in a group.
+        self.replace_held_in_group(store, transaction_id, serializable).await
     }
 
     /// Push requests (and dependents) to upload a media.
@@ -1516,6 +1683,11 @@ impl QueueStorage {
                             // event represented as a dependent request should be sufficient.
                             return None;
                         }
+
+                        QueuedRequestKind::StateEvent { .. } => {
+                            // State events have no local echo.
+                            return None;
+                        }
                     },
                 })
             });
@@ -1584,6 +1756,26 @@ impl QueueStorage {
                         item_infos,
                     )
                 }
+
+                DependentQueuedRequestKind::SendInGroup { request } => match *request {
+                    // Materialize as an event local echo.
+                    QueuedRequestKind::Event { content } => Some(LocalEcho {
+                        transaction_id: dep.own_transaction_id.clone().into(),
+                        content: LocalEchoContent::Event {
+                            serialized_event: content,
+                            send_handle: SendHandle {
+                                room: room.clone(),
+                                transaction_id: dep.own_transaction_id.into(),
+                                media_handles: vec![],
+                                created_at: dep.created_at,
+                            },
+                            send_error: None,
+                        },
+                    }),
+
+                    QueuedRequestKind::MediaUpload { .. }
+                    | QueuedRequestKind::StateEvent { .. } => None,
+                },
             });
 
         Ok(local_requests.chain(reactions_and_medias).collect())
@@ -1871,6 +2063,27 @@ impl QueueStorage {
                 )
                 .await?;
             }
+
+            DependentQueuedRequestKind::SendInGroup { request } => {
+                if parent_key.is_none() {
+                    // The previous request of the group hasn't been sent yet, we should retry
+                    // later => false.
+                    return Ok(false);
+                }
+
+                // Queue the request with a high priority, so the requests of the group are sent
+                // one after the other.
+                store
+                    .save_send_queue_request(
+                        &self.room_id,
+                        dependent_request.own_transaction_id.into(),
+                        dependent_request.created_at,
+                        *request,
+                        Self::HIGH_PRIORITY,
+                    )
+                    .await
+                    .map_err(RoomSendQueueStorageError::StateStoreError)?;
+            }
         }
 
         Ok(true)
@@ -2099,6 +2312,15 @@ pub enum RoomSendQueueError {
     #[error("the attachment event could not be created")]
     FailedToCreateAttachment,
 
+    /// The attachment failed to be processed before being uploaded.
+    #[cfg(feature = "image-proc")]
+    #[error("the attachment could not be processed")]
+    FailedToProcessAttachment,
+
+    /// The group of requests contains no items.
+    #[error("the group contains no items")]
+    EmptyGroup,
+
     /// The gallery contains no items.
     #[cfg(feature = "unstable-msc4274")]
     #[error("the gallery contains no items")]
@@ -2179,6 +2401,14 @@ pub struct SendHandle {
 }
 
 impl SendHandle {
+    /// The transaction id of the local echo of the event.
+    ///
+    /// It identifies the event in the [`RoomSendQueueUpdate`]s, until it's
+    /// been sent and [`RoomSendQueueUpdate::SentEvent`] gives its event id.
+    pub fn transaction_id(&self) -> &TransactionId {
+        &self.transaction_id
+    }
+
     fn nyi_for_uploads(&self) -> Result<(), RoomSendQueueStorageError> {
         if !self.media_handles.is_empty() {
             Err(RoomSendQueueStorageError::OperationNotImplementedYet)
@@ -2215,6 +2445,10 @@ impl SendHandle {
         if queue.cancel_event(&self.transaction_id).await? {
             trace!("successful abort");
 
+            // Wake up the queue, in case the aborted request was holding the next request
+            // of a group.
+            self.room.inner.notifier.notify_one();
+
             // Propagate a cancelled update too.
             let _ = self.room.inner.updates.send(RoomSendQueueUpdate::CancelledLocalEvent {
                 transaction_id: self.transaction_id.clone(),
@@ -2472,7 +2706,8 @@ fn canonicalize_dependent_requests(
 
             DependentQueuedRequestKind::UploadFileOrThumbnail { .. }
             | DependentQueuedRequestKind::FinishUpload { .. }
-            | DependentQueuedRequestKind::ReactEvent { .. } => {
+            | DependentQueuedRequestKind::ReactEvent { .. }
+            | DependentQueuedRequestKind::SendInGroup { .. } => {
                 // These requests can't be canonicalized, push them as is.
                 prevs.push(d);
             }
@@ -209,6 +209,19 @@ impl RoomSendQueue {
             return Err(RoomSendQueueError::RoomNotJoined);
         }
 
+        // Downscale the images when the low-bandwidth mode is enabled.
+        #[cfg(feature = "image-proc")]
+        if let Some(max_dimension) = room.client().bandwidth_profile().max_image_dimension {
+            config.cap_image_dimension(max_dimension);
+        }
+
+        #[cfg(feature = "image-proc")]
+        let (content_type, data) =
+            config.apply_processing(content_type, data).await.map_err(|err| {
+                warn!("couldn't process the attachment: {err}");
+                RoomSendQueueError::FailedToProcessAttachment
+            })?;
+
         let filename = filename.into();
         let upload_file_txn = TransactionId::new();
         let send_event_txn = config.txn_id.map_or_else(ChildTransactionId::new, Into::into);
@@ -38,7 +38,7 @@ use matrix_sdk_base::RequestedRequiredStates;
 use matrix_sdk_common::{executor::spawn, timer};
 use ruma::{
     api::client::{error::ErrorKind, sync::sync_events::v5 as http},
-    assign, OwnedRoomId, RoomId,
+    assign, OwnedRoomId, RoomId, UInt,
 };
 use serde::{Deserialize, Serialize};
 use tokio::{
@@ -55,7 +55,7 @@ use self::{
     client::SlidingSyncResponseProcessor,
     sticky_parameters::{LazyTransactionId, SlidingSyncStickyManager, StickyData},
 };
-use crate::{config::RequestConfig, Client, Result};
+use crate::{config::RequestConfig, BandwidthProfile, Client, Result};
 
 /// The Sliding Sync instance.
 ///
@@ -182,6 +182,53 @@ impl SlidingSync {
         }
     }
 
+    /// Whether the given extension is enabled for this sliding sync.
+    pub fn is_extension_enabled(&self, extension: SlidingSyncExtension) -> bool {
+        extension.is_enabled(&self.inner.sticky.read().unwrap().data().extensions)
+    }
+
+    /// Enable or disable the given extension, while the sliding sync is
+    /// running.
+    ///
+    /// The new configuration is sent with the next request, and the in-flight
+    /// request, if any, is cancelled so that it's sent as soon as possible.
+    /// Nothing happens if the extension is already in the requested state.
+    ///
+    /// Re-enabling the to-device extension resumes from the last to-device
+    /// token, so no to-device message is missed. Re-enabling the e2ee
+    /// extension marks all the users tracked by the `OlmMachine` as dirty, as
+    /// device list updates may have been missed while it was disabled.
+    pub async fn set_extension_enabled(
+        &self,
+        extension: SlidingSyncExtension,
+        enabled: bool,
+    ) -> Result<()> {
+        if self.is_extension_enabled(extension) == enabled {
+            return Ok(());
+        }
+
+        // This can fail, so it's done before changing the configuration, which is then
+        // left untouched on error.
+        #[cfg(feature = "e2e-encryption")]
+        if enabled && extension == SlidingSyncExtension::E2ee {
+            if let Some(olm_machine) = &*self.inner.client.olm_machine().await {
+                info!("Marking all tracked users as dirty");
+                olm_machine.mark_all_tracked_users_as_dirty().await?;
+            }
+        }
+
+        {
+            let mut sticky = self.inner.sticky.write().unwrap();
+            *extension.enabled_mut(&mut sticky.data_mut().extensions) = Some(enabled);
+        }
+
+        self.inner.internal_channel_send_if_possible(
+            SlidingSyncInternalMessage::SyncLoopSkipOverCurrentIteration,
+        );
+
+        Ok(())
+    }
+
     /// Find a list by its name, and do something on it if it exists.
     pub async fn on_list<Function, FunctionOutput, R>(
         &self,
@@ -371,6 +418,8 @@ impl SlidingSync {
         // Collect requests for lists.
         let mut requests_lists = BTreeMap::new();
 
+        let bandwidth_profile = self.inner.client.bandwidth_profile();
+
         let require_timeout = {
             let lists = self.inner.lists.read().await;
 
@@ -378,7 +427,11 @@ impl SlidingSync {
             let mut require_timeout = true;
 
             for (name, list) in lists.iter() {
-                requests_lists.insert(name.clone(), list.next_request(txn_id)?);
+                let mut request = list.next_request(txn_id)?;
+                request.room_details.timeline_limit =
+                    cap_timeline_limit(&bandwidth_profile, request.room_details.timeline_limit);
+
+                requests_lists.insert(name.clone(), request);
                 require_timeout = require_timeout && list.requires_timeout();
             }
 
@@ -456,8 +509,31 @@ impl SlidingSync {
             lists: requests_lists,
         });
 
-        // Apply sticky parameters, if needs be.
-        self.inner.sticky.write().unwrap().maybe_apply(&mut request, txn_id);
+        {
+            let mut sticky = self.inner.sticky.write().unwrap();
+
+            // The timeline limits of the room subscriptions depend on the bandwidth
+            // profile: if it has changed, send all the room subscriptions again
+            // with the new limits.
+            if sticky.data().bandwidth_profile.as_ref() != Some(&bandwidth_profile) {
+                let data = sticky.data_mut();
+                data.bandwidth_profile = Some(bandwidth_profile.clone());
+
+                for (state, _room_subscription) in data.room_subscriptions.values_mut() {
+                    *state = RoomSubscriptionState::Pending;
+                }
+            }
+
+            // Apply sticky parameters, if needs be.
+            sticky.maybe_apply(&mut request, txn_id);
+        }
+
+        // The room subscriptions keep the timeline limits they've been created with, so
+        // they're restored when the bandwidth profile changes again.
+        for room_subscription in request.room_subscriptions.values_mut() {
+            room_subscription.timeline_limit =
+                cap_timeline_limit(&bandwidth_profile, room_subscription.timeline_limit);
+        }
 
         // Extensions are now applied (via sticky parameters).
         //
@@ -478,7 +554,11 @@ impl SlidingSync {
             // Configure long-polling. We need some time for the long-poll itself,
             // and extra time for the network delays.
             RequestConfig::default()
-                .timeout(self.inner.poll_timeout + self.inner.network_timeout)
+                .timeout(
+                    self.inner.poll_timeout
+                        + self.inner.network_timeout
+                            * bandwidth_profile.sync_network_timeout_factor,
+                )
                 .retry_limit(3),
             position_guard,
         ))
@@ -648,13 +728,32 @@ impl SlidingSync {
 
         let mut internal_channel_receiver = self.inner.internal_channel.subscribe();
 
+        let shutdown = self.inner.client.inner.shutdown.clone();
+        let shutdown_guard = shutdown.enter();
+
         stream! {
+            let Some(_shutdown_guard) = shutdown_guard else {
+                debug!("The client has been shut down, not starting the sync stream");
+                return;
+            };
+
             loop {
                 debug!("Sync stream is running");
 
                 select! {
                     biased;
 
+                    _ = shutdown.wait_for_shut_down() => {
+                        debug!("The client is shutting down, stopping the sync stream");
+
+                        // The response to the request that was in flight, if any, is handled in a
+                        // task that can't be cancelled, and that holds the position lock: wait for
+                        // it to be done.
+                        drop(self.inner.position.lock().await);
+
+                        break;
+                    }
+
                     internal_message = internal_channel_receiver.recv() => {
                         use SlidingSyncInternalMessage::*;
 
@@ -831,6 +930,46 @@ pub struct UpdateSummary {
     pub rooms: Vec<OwnedRoomId>,
 }
 
+/// An extension of sliding sync, which can be enabled or disabled at runtime
+/// with [`SlidingSync::set_extension_enabled`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlidingSyncExtension {
+    /// The end-to-end encryption extension, for device lists and one-time key
+    /// counts.
+    E2ee,
+    /// The to-device messages extension.
+    ToDevice,
+    /// The account data extension.
+    AccountData,
+    /// The read receipts extension.
+    Receipts,
+    /// The typing notifications extension.
+    Typing,
+}
+
+impl SlidingSyncExtension {
+    fn is_enabled(self, extensions: &http::request::Extensions) -> bool {
+        match self {
+            Self::E2ee => extensions.e2ee.enabled,
+            Self::ToDevice => extensions.to_device.enabled,
+            Self::AccountData => extensions.account_data.enabled,
+            Self::Receipts => extensions.receipts.enabled,
+            Self::Typing => extensions.typing.enabled,
+        }
+        .unwrap_or(false)
+    }
+
+    fn enabled_mut(self, extensions: &mut http::request::Extensions) -> &mut Option<bool> {
+        match self {
+            Self::E2ee => &mut extensions.e2ee.enabled,
+            Self::ToDevice => &mut extensions.to_device.enabled,
+            Self::AccountData => &mut extensions.account_data.enabled,
+            Self::Receipts => &mut extensions.receipts.enabled,
+            Self::Typing => &mut extensions.typing.enabled,
+        }
+    }
+}
+
 /// A very basic bool-ish enum to represent the state of a
 /// [`http::request::RoomSubscription`]. A `RoomSubscription` that has been sent
 /// once should ideally not being sent again, to mostly save bandwidth.
@@ -859,6 +998,9 @@ pub(super) struct SlidingSyncStickyParameters {
     /// The intended state of the extensions being supplied to sliding /sync
     /// calls.
     extensions: http::request::Extensions,
+
+    /// The bandwidth profile the room subscriptions have last been sent with.
+    bandwidth_profile: Option<BandwidthProfile>,
 }
 
 impl SlidingSyncStickyParameters {
@@ -875,6 +1017,7 @@ impl SlidingSyncStickyParameters {
                 })
                 .collect(),
             extensions,
+            bandwidth_profile: None,
         }
     }
 }
@@ -902,6 +1045,13 @@ impl StickyData for SlidingSyncStickyParameters {
     }
 }
 
+/// Cap the timeline limit of a sliding sync list or room subscription,
+/// according to the given [`BandwidthProfile`].
+fn cap_timeline_limit(bandwidth_profile: &BandwidthProfile, timeline_limit: UInt) -> UInt {
+    let timeline_limit = u32::try_from(timeline_limit).unwrap_or(u32::MAX);
+    bandwidth_profile.cap_sync_timeline_limit(timeline_limit).into()
+}
+
 #[cfg(all(test, not(target_family = "wasm")))]
 #[allow(clippy::dbg_macro)]
 mod tests {
@@ -936,8 +1086,8 @@ mod tests {
     use super::{
         http,
         sticky_parameters::{LazyTransactionId, SlidingSyncStickyManager},
-        SlidingSync, SlidingSyncList, SlidingSyncListBuilder, SlidingSyncMode,
-        SlidingSyncStickyParameters,
+        SlidingSync, SlidingSyncExtension, SlidingSyncList, SlidingSyncListBuilder,
+        SlidingSyncMode, SlidingSyncStickyParameters,
     };
     use crate::{
         sliding_sync::cache::restore_sliding_sync_state,
@@ -1493,6 +1643,60 @@ mod tests {
         Ok(())
     }
 
+    #[async_test]
+    async fn test_set_extension_enabled() -> Result<()> {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+
+        let sync = client
+            .sliding_sync("test-slidingsync")?
+            .add_list(SlidingSyncList::builder("new_list"))
+            .with_all_extensions()
+            .build()
+            .await?;
+
+        assert!(sync.is_extension_enabled(SlidingSyncExtension::Typing));
+        assert!(sync.is_extension_enabled(SlidingSyncExtension::Receipts));
+
+        // Commit the initial sticky parameters.
+        let txn_id = TransactionId::new();
+        let (request, _, _) = sync
+            .generate_sync_request(&mut LazyTransactionId::from_owned(txn_id.to_owned()))
+            .await?;
+        assert_eq!(request.extensions.typing.enabled, Some(true));
+        sync.inner.sticky.write().unwrap().maybe_commit(&txn_id);
+
+        // Disable the typing extension.
+        sync.set_extension_enabled(SlidingSyncExtension::Typing, false).await?;
+        assert!(!sync.is_extension_enabled(SlidingSyncExtension::Typing));
+        assert!(sync.inner.sticky.read().unwrap().is_invalidated());
+
+        // The next request disables it explicitly, and keeps the other extensions.
+        let txn_id = TransactionId::new();
+        let (request, _, _) = sync
+            .generate_sync_request(&mut LazyTransactionId::from_owned(txn_id.to_owned()))
+            .await?;
+        assert_eq!(request.extensions.typing.enabled, Some(false));
+        assert_eq!(request.extensions.receipts.enabled, Some(true));
+        assert_eq!(request.extensions.e2ee.enabled, Some(true));
+        sync.inner.sticky.write().unwrap().maybe_commit(&txn_id);
+
+        // Disabling it again does nothing.
+        sync.set_extension_enabled(SlidingSyncExtension::Typing, false).await?;
+        assert!(!sync.inner.sticky.read().unwrap().is_invalidated());
+
+        // Re-enable it.
+        sync.set_extension_enabled(SlidingSyncExtension::Typing, true).await?;
+        assert!(sync.is_extension_enabled(SlidingSyncExtension::Typing));
+
+        let (request, _, _) = sync
+            .generate_sync_request(&mut LazyTransactionId::from_owned(TransactionId::new()))
+            .await?;
+        assert_eq!(request.extensions.typing.enabled, Some(true));
+
+        Ok(())
+    }
+
     // With MSC4186, with the `e2ee` extension enabled, if a request has no `pos`,
     // all the tracked users by the `OlmMachine` must be marked as dirty, i.e.
     // `/key/query` requests must be sent. See the code to see the details.
@@ -2732,6 +2936,77 @@ mod tests {
         Ok(())
     }
 
+    #[async_test]
+    async fn test_low_bandwidth_mode() -> Result<()> {
+        let (_server, sliding_sync) = new_sliding_sync(vec![SlidingSyncList::builder("foo")
+            .sync_mode(SlidingSyncMode::new_selective().add_range(0..=10))
+            .timeline_limit(10)])
+        .await?;
+
+        let room_id = room_id!("!r0:bar.org");
+        sliding_sync.subscribe_to_rooms(
+            &[room_id],
+            Some(assign!(http::request::RoomSubscription::default(), {
+                timeline_limit: uint!(20),
+            })),
+            false,
+        );
+
+        let (request, request_config, _) =
+            sliding_sync.generate_sync_request(&mut LazyTransactionId::new()).await?;
+
+        assert_eq!(request.lists["foo"].room_details.timeline_limit, uint!(10));
+        assert_eq!(request.room_subscriptions[room_id].timeline_limit, uint!(20));
+        let normal_timeout = request_config.timeout;
+
+        // Commit the sticky parameters, as if the server had received them.
+        {
+            let mut txn_id = LazyTransactionId::new();
+            sliding_sync.generate_sync_request(&mut txn_id).await?;
+            let txn_id = txn_id.get().unwrap().to_owned();
+            sliding_sync.inner.sticky.write().unwrap().maybe_commit(&txn_id);
+        }
+
+        let (request, _, _) =
+            sliding_sync.generate_sync_request(&mut LazyTransactionId::new()).await?;
+        assert!(request.room_subscriptions.is_empty());
+
+        // Once the low-bandwidth mode is enabled, the timeline limits are capped, the
+        // existing room subscriptions are sent again, and the timeout is longer.
+        sliding_sync.inner.client.set_low_bandwidth_mode(true);
+
+        let other_room_id = room_id!("!r1:bar.org");
+        sliding_sync.subscribe_to_rooms(
+            &[other_room_id],
+            Some(assign!(http::request::RoomSubscription::default(), {
+                timeline_limit: uint!(20),
+            })),
+            false,
+        );
+
+        let (request, request_config, _) =
+            sliding_sync.generate_sync_request(&mut LazyTransactionId::new()).await?;
+
+        assert_eq!(request.lists["foo"].room_details.timeline_limit, uint!(5));
+        assert_eq!(request.room_subscriptions[room_id].timeline_limit, uint!(5));
+        assert_eq!(request.room_subscriptions[other_room_id].timeline_limit, uint!(5));
+        assert!(request_config.timeout > normal_timeout);
+
+        // Once it's disabled again, the room subscriptions get their original timeline
+        // limits back.
+        sliding_sync.inner.client.set_low_bandwidth_mode(false);
+
+        let (request, request_config, _) =
+            sliding_sync.generate_sync_request(&mut LazyTransactionId::new()).await?;
+
+        assert_eq!(request.lists["foo"].room_details.timeline_limit, uint!(10));
+        assert_eq!(request.room_subscriptions[room_id].timeline_limit, uint!(20));
+        assert_eq!(request.room_subscriptions[other_room_id].timeline_limit, uint!(20));
+        assert_eq!(request_config.timeout, normal_timeout);
+
+        Ok(())
+    }
+
     #[async_test]
     async fn test_sync_beat_is_notified_on_sync_response() -> Result<()> {
         let server = MockServer::start().await;
@@ -3027,6 +3027,12 @@ impl RoomRelationsResponseTemplate {
 pub struct ReceiptEndpoint;
 
 impl<'a> MockEndpoint<'a, ReceiptEndpoint> {
+    /// Ensures that the body of the request is a superset of the provided
+    /// `body` parameter.
+    pub fn body_matches_partial_json(self, body: Value) -> Self {
+        Self { mock: self.mock.and(body_partial_json(body)), ..self }
+    }
+
     /// Returns a successful empty response.
     pub fn ok(self) -> MatrixMock<'a> {
         self.respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
@@ -13,6 +13,8 @@ use url::Url;
 pub mod client;
 #[cfg(not(target_family = "wasm"))]
 pub mod mocks;
+#[cfg(feature = "experimental-widgets")]
+pub mod widget;
 
 use self::client::mock_matrix_session;
 use crate::{config::RequestConfig, Client, ClientBuilder};
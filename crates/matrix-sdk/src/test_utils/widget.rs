@@ -0,0 +1,337 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A mock widget, and a conformance harness checking that a [`WidgetDriver`]
+//! integration behaves as the widget API specifies, without a browser.
+//!
+//! The mock widget talks to the driver through its [`WidgetDriverHandle`],
+//! exactly like a widget living in a webview would, so the harness exercises
+//! the capability prompts (via the [`CapabilitiesProvider`]) and the routing of
+//! the messages between the widget and the driver.
+//!
+//! ```no_run
+//! # use matrix_sdk::{
+//! #     test_utils::widget::{GrantAllCapabilitiesProvider, WidgetConformanceHarness},
+//! #     widget::{WidgetDriver, WidgetSettings},
+//! #     Room,
+//! # };
+//! # async fn example(room: Room) {
+//! let settings = WidgetSettings::new("my-widget".to_owned(), false, "https://foo.bar/widget")
+//!     .unwrap();
+//! let (driver, handle) = WidgetDriver::new(settings);
+//! matrix_sdk::executor::spawn(driver.run(room, GrantAllCapabilitiesProvider));
+//!
+//! let harness = WidgetConformanceHarness::new("my-widget", handle);
+//! harness.run_all(&["org.matrix.msc2762.receive.event:m.room.message"]).await;
+//! # }
+//! ```
+//!
+//! [`WidgetDriver`]: crate::widget::WidgetDriver
+
+use std::time::Duration;
+
+use futures_util::FutureExt;
+use matrix_sdk_common::timeout::timeout;
+use ruma::serde::JsonObject;
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+
+use crate::widget::{Capabilities, CapabilitiesProvider, WidgetDriverHandle};
+
+/// How long to wait for a message from the driver before failing.
+const RECV_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How long to wait before concluding that the driver didn't send anything.
+const NO_MESSAGE_DELAY: Duration = Duration::from_millis(100);
+
+/// A [`CapabilitiesProvider`] granting all the capabilities a widget asks for.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GrantAllCapabilitiesProvider;
+
+impl CapabilitiesProvider for GrantAllCapabilitiesProvider {
+    async fn acquire_capabilities(&self, capabilities: Capabilities) -> Capabilities {
+        capabilities
+    }
+}
+
+/// A mock widget, talking to a [`WidgetDriver`] through its
+/// [`WidgetDriverHandle`].
+///
+/// All the methods panic if the driver doesn't behave as expected, so they
+/// can be used directly in tests.
+///
+/// [`WidgetDriver`]: crate::widget::WidgetDriver
+#[derive(Debug)]
+pub struct MockWidget {
+    widget_id: String,
+    handle: WidgetDriverHandle,
+}
+
+impl MockWidget {
+    /// Create a new mock widget with the given ID, talking to the driver
+    /// through the given handle.
+    pub fn new(widget_id: impl Into<String>, handle: WidgetDriverHandle) -> Self {
+        Self { widget_id: widget_id.into(), handle }
+    }
+
+    /// The ID of this widget.
+    pub fn widget_id(&self) -> &str {
+        &self.widget_id
+    }
+
+    /// Receive the next message from the driver.
+    ///
+    /// Panics if the driver doesn't send any message in time, or if the
+    /// message isn't a JSON object.
+    pub async fn recv(&self) -> JsonObject {
+        let message = timeout(self.handle.recv(), RECV_TIMEOUT)
+            .await
+            .expect("the widget driver didn't send any message in time")
+            .expect("the widget driver has stopped");
+
+        serde_json::from_str(&message).expect("the widget driver sent an invalid message")
+    }
+
+    /// Receive the next message from the driver, and check it's a `toWidget`
+    /// request with the given action.
+    pub async fn recv_to_widget_request(&self, action: &str) -> JsonObject {
+        let message = self.recv().await;
+
+        assert_eq!(message["api"], "toWidget", "unexpected message: {message:?}");
+        assert_eq!(message["widgetId"], self.widget_id.as_str());
+        assert_eq!(message["action"], action, "unexpected message: {message:?}");
+        assert!(message["requestId"].is_string(), "missing request ID: {message:?}");
+        assert!(message.get("response").is_none(), "unexpected response: {message:?}");
+
+        message
+    }
+
+    /// Check that the driver doesn't have any message for us.
+    pub async fn assert_no_message(&self) {
+        // Give the driver some time to process what we've sent.
+        crate::sleep::sleep(NO_MESSAGE_DELAY).await;
+
+        if let Some(Some(message)) = self.handle.recv().now_or_never() {
+            panic!("unexpected message from the widget driver: {message:?}");
+        }
+    }
+
+    /// Send a `fromWidget` request to the driver.
+    pub async fn send_request(&self, request_id: &str, action: &str, data: impl Serialize) {
+        let message = json!({
+            "api": "fromWidget",
+            "widgetId": self.widget_id,
+            "requestId": request_id,
+            "action": action,
+            "data": data,
+        });
+
+        assert!(self.handle.send(message.to_string()).await, "the widget driver has stopped");
+    }
+
+    /// Send a `fromWidget` request to the driver, and wait for its response.
+    ///
+    /// Checks that the response is routed back correctly: it must carry the
+    /// same API, widget ID, request ID, action and data as the request.
+    pub async fn request(&self, request_id: &str, action: &str, data: JsonValue) -> JsonValue {
+        self.send_request(request_id, action, &data).await;
+
+        let message = self.recv().await;
+        assert_eq!(message["api"], "fromWidget", "unexpected message: {message:?}");
+        assert_eq!(message["widgetId"], self.widget_id.as_str());
+        assert_eq!(message["requestId"], request_id, "unexpected message: {message:?}");
+        assert_eq!(message["action"], action);
+        assert_eq!(message["data"], data);
+
+        message.get("response").cloned().expect("the response is missing")
+    }
+
+    /// Respond to a `toWidget` request received from the driver.
+    pub async fn respond(&self, request: &JsonObject, response: impl Serialize) {
+        let mut message = request.clone();
+        message.insert(
+            "response".to_owned(),
+            serde_json::to_value(response).expect("the response must serialize"),
+        );
+
+        assert!(
+            self.handle.send(JsonValue::Object(message).to_string()).await,
+            "the widget driver has stopped"
+        );
+    }
+
+    /// Go through the capability negotiation: answer the driver's
+    /// `capabilities` request with the `requested` capabilities, and
+    /// acknowledge the `notify_capabilities` request.
+    ///
+    /// Checks that the driver doesn't grant more than what has been requested.
+    ///
+    /// Returns the approved capabilities.
+    pub async fn negotiate_capabilities(&self, requested: &[&str]) -> Vec<String> {
+        let request = self.recv_to_widget_request("capabilities").await;
+        self.finish_capabilities_negotiation(&request, requested).await
+    }
+
+    /// Like [`MockWidget::negotiate_capabilities`], but for a `capabilities`
+    /// request which has already been received.
+    pub async fn finish_capabilities_negotiation(
+        &self,
+        request: &JsonObject,
+        requested: &[&str],
+    ) -> Vec<String> {
+        self.respond(request, json!({ "capabilities": requested })).await;
+
+        let notification = self.recv_to_widget_request("notify_capabilities").await;
+        assert_eq!(notification["data"]["requested"], json!(requested));
+
+        let approved: Vec<String> =
+            serde_json::from_value(notification["data"]["approved"].clone())
+                .expect("the approved capabilities must be a list of strings");
+
+        for capability in &approved {
+            assert!(
+                requested.contains(&capability.as_str()),
+                "the capability `{capability}` has been approved without being requested"
+            );
+        }
+
+        self.respond(&notification, json!({})).await;
+
+        approved
+    }
+}
+
+/// A harness checking that a [`WidgetDriver`] integration behaves as the
+/// widget API specifies.
+///
+/// Each check is independent and can be run separately, but some of them need
+/// the capabilities to have been negotiated first, see
+/// [`WidgetConformanceHarness::run_all`] for a sensible order.
+///
+/// [`WidgetDriver`]: crate::widget::WidgetDriver
+#[derive(Debug)]
+pub struct WidgetConformanceHarness {
+    widget: MockWidget,
+}
+
+impl WidgetConformanceHarness {
+    /// Create a new harness, driving a mock widget with the given ID through
+    /// the given handle.
+    ///
+    /// The driver must have been created with `init_on_content_load` set to
+    /// `false`, so it starts the capability negotiation immediately.
+    pub fn new(widget_id: impl Into<String>, handle: WidgetDriverHandle) -> Self {
+        Self { widget: MockWidget::new(widget_id, handle) }
+    }
+
+    /// The mock widget, to run custom checks.
+    pub fn widget(&self) -> &MockWidget {
+        &self.widget
+    }
+
+    /// Run all the checks, negotiating the `requested` capabilities.
+    ///
+    /// The capabilities must not allow sending `m.room.message` events, since
+    /// it's used to check that the capabilities are enforced. They must not
+    /// allow reading room state either, since the initial state update would
+    /// be interleaved with the responses the checks expect.
+    ///
+    /// Returns the approved capabilities.
+    pub async fn run_all(&self, requested: &[&str]) -> Vec<String> {
+        let approved = self.check_capability_negotiation(requested).await;
+
+        self.check_supported_api_versions().await;
+        self.check_unknown_action_is_rejected().await;
+        self.check_messages_for_other_widgets_are_ignored().await;
+        self.check_capabilities_are_enforced().await;
+
+        approved
+    }
+
+    /// Check that the driver negotiates the capabilities, even if the widget
+    /// sends requests in the meantime.
+    pub async fn check_capability_negotiation(&self, requested: &[&str]) -> Vec<String> {
+        let request = self.widget.recv_to_widget_request("capabilities").await;
+
+        // The driver must keep processing requests while it waits for the widget to
+        // answer its own request.
+        self.check_supported_api_versions().await;
+
+        self.widget.finish_capabilities_negotiation(&request, requested).await
+    }
+
+    /// Check that the driver answers a `supported_api_versions` request.
+    pub async fn check_supported_api_versions(&self) {
+        let response = self
+            .widget
+            .request("conformance-supported-api-versions", "supported_api_versions", json!({}))
+            .await;
+
+        let versions = response["supported_versions"]
+            .as_array()
+            .expect("the supported versions must be a list");
+        assert!(!versions.is_empty(), "the driver must support at least one version");
+        assert!(versions.iter().all(JsonValue::is_string));
+    }
+
+    /// Check that the driver rejects a request with an unknown action, with an
+    /// error response.
+    pub async fn check_unknown_action_is_rejected(&self) {
+        let response = self
+            .widget
+            .request("conformance-unknown-action", "org.example.conformance.unknown", json!({}))
+            .await;
+
+        assert!(
+            response["error"]["message"].is_string(),
+            "the driver must reject unknown actions, got: {response:?}"
+        );
+    }
+
+    /// Check that the driver ignores the requests with the ID of another
+    /// widget.
+    pub async fn check_messages_for_other_widgets_are_ignored(&self) {
+        let message = json!({
+            "api": "fromWidget",
+            "widgetId": format!("{}-other", self.widget.widget_id()),
+            "requestId": "conformance-other-widget",
+            "action": "supported_api_versions",
+            "data": {},
+        });
+        assert!(self.widget.handle.send(message.to_string()).await);
+
+        self.widget.assert_no_message().await;
+    }
+
+    /// Check that the driver rejects sending an `m.room.message` event, which
+    /// hasn't been allowed by the negotiated capabilities.
+    pub async fn check_capabilities_are_enforced(&self) {
+        let response = self
+            .widget
+            .request(
+                "conformance-send-without-capability",
+                "send_event",
+                json!({
+                    "type": "m.room.message",
+                    "content": { "msgtype": "m.text", "body": "conformance" },
+                }),
+            )
+            .await;
+
+        assert!(
+            response["error"]["message"].is_string(),
+            "the driver must reject events not allowed by the capabilities, got: {response:?}"
+        );
+    }
+}
@@ -15,7 +15,11 @@
 //! Types and traits related to the capabilities that a widget can request from
 //! a client.
 
-use std::{fmt, future::Future};
+use std::{
+    fmt,
+    future::Future,
+    sync::{Arc, RwLock as StdRwLock},
+};
 
 use matrix_sdk_common::{SendOutsideWasm, SyncOutsideWasm};
 use serde::{ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
@@ -57,6 +61,34 @@ pub struct Capabilities {
     pub update_delayed_event: bool,
     /// This allows the widget to send events with a delay.
     pub send_delayed_event: bool,
+    /// This allows the widget to upload media to the content repository, and
+    /// to get its configuration ([MSC4039]).
+    ///
+    /// [MSC4039]: https://github.com/matrix-org/matrix-spec-proposals/pull/4039
+    pub upload_file: bool,
+    /// This allows the widget to download media from the content repository
+    /// ([MSC4039]).
+    ///
+    /// [MSC4039]: https://github.com/matrix-org/matrix-spec-proposals/pull/4039
+    pub download_file: bool,
+    /// This allows the widget to observe the public read receipts of the room
+    /// members.
+    ///
+    /// Private read receipts are never forwarded to the widget.
+    pub read_receipts: bool,
+    /// This allows the widget to observe the position of the fully-read
+    /// marker of the user in the room.
+    pub fully_read_marker: bool,
+    /// This allows the widget to observe which room members are typing.
+    pub typing: bool,
+    /// This allows the widget to send typing notifications on behalf of the
+    /// user.
+    pub send_typing: bool,
+    /// This allows the widget to ask the client to navigate to a Matrix
+    /// entity, like a room or a user, given its URI ([MSC2931]).
+    ///
+    /// [MSC2931]: https://github.com/matrix-org/matrix-spec-proposals/pull/2931
+    pub navigate: bool,
 }
 
 impl Capabilities {
@@ -102,6 +134,95 @@ impl Capabilities {
     pub(super) fn has_read_filter_for_type(&self, event_type: &str) -> bool {
         self.read.iter().any(|f| f.filter_event_type() == event_type)
     }
+
+    /// Whether these capabilities allow the widget to receive anything from
+    /// the room, i.e. whether the widget needs to subscribe to room updates.
+    pub(super) fn requires_subscription(&self) -> bool {
+        !self.read.is_empty() || self.read_receipts || self.fully_read_marker || self.typing
+    }
+}
+
+/// The capabilities granted to a widget, as enforced by the
+/// [`MatrixDriver`](super::matrix::MatrixDriver).
+///
+/// The driver keeps its own copy of the negotiated capabilities, so that what
+/// it reads, sends and forwards doesn't solely rely on the checks done by the
+/// widget machine. No capability is granted until [`Self::set`] is called.
+#[derive(Clone, Debug, Default)]
+pub(super) struct CapabilitiesFilter {
+    capabilities: Arc<StdRwLock<Capabilities>>,
+}
+
+impl CapabilitiesFilter {
+    /// Replaces the capabilities granted to the widget.
+    pub(super) fn set(&self, capabilities: Capabilities) {
+        *self.capabilities.write().unwrap() = capabilities;
+    }
+
+    /// Checks if a given event is allowed to be forwarded to the widget.
+    ///
+    /// See [`Capabilities::allow_reading`].
+    pub(super) fn allow_reading<'a>(
+        &self,
+        event_filter_input: impl TryInto<FilterInput<'a>>,
+    ) -> bool {
+        self.capabilities.read().unwrap().allow_reading(event_filter_input)
+    }
+
+    /// Checks if a given event is allowed to be sent by the widget.
+    ///
+    /// See [`Capabilities::allow_sending`].
+    pub(super) fn allow_sending<'a>(
+        &self,
+        event_filter_input: impl TryInto<FilterInput<'a>>,
+    ) -> bool {
+        self.capabilities.read().unwrap().allow_sending(event_filter_input)
+    }
+
+    /// Checks if the widget is allowed to send delayed events.
+    pub(super) fn allow_sending_delayed_events(&self) -> bool {
+        self.capabilities.read().unwrap().send_delayed_event
+    }
+
+    /// Checks if the widget is allowed to update delayed events.
+    pub(super) fn allow_updating_delayed_events(&self) -> bool {
+        self.capabilities.read().unwrap().update_delayed_event
+    }
+
+    /// Checks if the widget is allowed to upload media.
+    pub(super) fn allow_uploading_media(&self) -> bool {
+        self.capabilities.read().unwrap().upload_file
+    }
+
+    /// Checks if the widget is allowed to download media.
+    pub(super) fn allow_downloading_media(&self) -> bool {
+        self.capabilities.read().unwrap().download_file
+    }
+
+    /// Checks if the widget is allowed to observe read receipts.
+    pub(super) fn allow_reading_receipts(&self) -> bool {
+        self.capabilities.read().unwrap().read_receipts
+    }
+
+    /// Checks if the widget is allowed to observe the fully-read marker.
+    pub(super) fn allow_reading_fully_read_marker(&self) -> bool {
+        self.capabilities.read().unwrap().fully_read_marker
+    }
+
+    /// Checks if the widget is allowed to observe typing notifications.
+    pub(super) fn allow_reading_typing(&self) -> bool {
+        self.capabilities.read().unwrap().typing
+    }
+
+    /// Checks if the widget is allowed to send typing notifications.
+    pub(super) fn allow_sending_typing(&self) -> bool {
+        self.capabilities.read().unwrap().send_typing
+    }
+
+    /// Checks if the widget is allowed to ask the client to navigate.
+    pub(super) fn allow_navigating(&self) -> bool {
+        self.capabilities.read().unwrap().navigate
+    }
 }
 
 pub(super) const SEND_EVENT: &str = "org.matrix.msc2762.send.event";
@@ -113,6 +234,13 @@ pub(super) const READ_TODEVICE: &str = "org.matrix.msc3819.receive.to_device";
 pub(super) const REQUIRES_CLIENT: &str = "io.element.requires_client";
 pub(super) const SEND_DELAYED_EVENT: &str = "org.matrix.msc4157.send.delayed_event";
 pub(super) const UPDATE_DELAYED_EVENT: &str = "org.matrix.msc4157.update_delayed_event";
+pub(super) const UPLOAD_FILE: &str = "org.matrix.msc4039.upload_file";
+pub(super) const DOWNLOAD_FILE: &str = "org.matrix.msc4039.download_file";
+pub(super) const READ_RECEIPTS: &str = "io.element.receive.read_receipts";
+pub(super) const READ_FULLY_READ_MARKER: &str = "io.element.receive.fully_read_marker";
+pub(super) const READ_TYPING: &str = "io.element.receive.typing";
+pub(super) const SEND_TYPING: &str = "io.element.send.typing";
+pub(super) const NAVIGATE: &str = "org.matrix.msc2931.navigate";
 
 impl Serialize for Capabilities {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -174,6 +302,27 @@ impl Serialize for Capabilities {
         if self.send_delayed_event {
             seq.serialize_element(SEND_DELAYED_EVENT)?;
         }
+        if self.upload_file {
+            seq.serialize_element(UPLOAD_FILE)?;
+        }
+        if self.download_file {
+            seq.serialize_element(DOWNLOAD_FILE)?;
+        }
+        if self.read_receipts {
+            seq.serialize_element(READ_RECEIPTS)?;
+        }
+        if self.fully_read_marker {
+            seq.serialize_element(READ_FULLY_READ_MARKER)?;
+        }
+        if self.typing {
+            seq.serialize_element(READ_TYPING)?;
+        }
+        if self.send_typing {
+            seq.serialize_element(SEND_TYPING)?;
+        }
+        if self.navigate {
+            seq.serialize_element(NAVIGATE)?;
+        }
         for filter in &self.read {
             let name = match filter {
                 Filter::MessageLike(_) => READ_EVENT,
@@ -204,6 +353,13 @@ impl<'de> Deserialize<'de> for Capabilities {
             RequiresClient,
             UpdateDelayedEvent,
             SendDelayedEvent,
+            UploadFile,
+            DownloadFile,
+            ReadReceipts,
+            ReadFullyReadMarker,
+            ReadTyping,
+            SendTyping,
+            Navigate,
             Read(Filter),
             Send(Filter),
             Unknown,
@@ -224,6 +380,27 @@ impl<'de> Deserialize<'de> for Capabilities {
                 if s == SEND_DELAYED_EVENT {
                     return Ok(Self::SendDelayedEvent);
                 }
+                if s == UPLOAD_FILE {
+                    return Ok(Self::UploadFile);
+                }
+                if s == DOWNLOAD_FILE {
+                    return Ok(Self::DownloadFile);
+                }
+                if s == READ_RECEIPTS {
+                    return Ok(Self::ReadReceipts);
+                }
+                if s == READ_FULLY_READ_MARKER {
+                    return Ok(Self::ReadFullyReadMarker);
+                }
+                if s == READ_TYPING {
+                    return Ok(Self::ReadTyping);
+                }
+                if s == SEND_TYPING {
+                    return Ok(Self::SendTyping);
+                }
+                if s == NAVIGATE {
+                    return Ok(Self::Navigate);
+                }
 
                 match s.split_once(':') {
                     Some((READ_EVENT, filter_s)) => Ok(Permission::Read(Filter::MessageLike(
@@ -284,6 +461,13 @@ impl<'de> Deserialize<'de> for Capabilities {
                 Permission::Unknown => {}
                 Permission::UpdateDelayedEvent => capabilities.update_delayed_event = true,
                 Permission::SendDelayedEvent => capabilities.send_delayed_event = true,
+                Permission::UploadFile => capabilities.upload_file = true,
+                Permission::DownloadFile => capabilities.download_file = true,
+                Permission::ReadReceipts => capabilities.read_receipts = true,
+                Permission::ReadFullyReadMarker => capabilities.fully_read_marker = true,
+                Permission::ReadTyping => capabilities.typing = true,
+                Permission::SendTyping => capabilities.send_typing = true,
+                Permission::Navigate => capabilities.navigate = true,
             }
         }
 
@@ -321,7 +505,14 @@ mod tests {
             "org.matrix.msc2762.send.state_event:org.matrix.msc3401.call.member#@user:matrix.server",
             "org.matrix.msc3819.send.to_device:io.element.call.encryption_keys",
             "org.matrix.msc4157.send.delayed_event",
-            "org.matrix.msc4157.update_delayed_event"
+            "org.matrix.msc4157.update_delayed_event",
+            "org.matrix.msc4039.upload_file",
+            "org.matrix.msc4039.download_file",
+            "io.element.receive.read_receipts",
+            "io.element.receive.fully_read_marker",
+            "io.element.receive.typing",
+            "io.element.send.typing",
+            "org.matrix.msc2931.navigate"
         ]"#;
 
         let parsed = serde_json::from_str::<Capabilities>(capabilities_str).unwrap();
@@ -351,6 +542,13 @@ mod tests {
             requires_client: true,
             update_delayed_event: true,
             send_delayed_event: true,
+            upload_file: true,
+            download_file: true,
+            read_receipts: true,
+            fully_read_marker: true,
+            typing: true,
+            send_typing: true,
+            navigate: true,
         };
 
         assert_eq!(parsed, expected);
@@ -381,10 +579,66 @@ mod tests {
             requires_client: true,
             update_delayed_event: false,
             send_delayed_event: false,
+            upload_file: true,
+            download_file: false,
+            read_receipts: true,
+            fully_read_marker: false,
+            typing: false,
+            send_typing: true,
+            navigate: false,
         };
 
         let capabilities_str = serde_json::to_string(&capabilities).unwrap();
         let parsed = serde_json::from_str::<Capabilities>(&capabilities_str).unwrap();
         assert_eq!(parsed, capabilities);
     }
+
+    #[test]
+    fn capabilities_filter_denies_everything_until_set() {
+        let filter = CapabilitiesFilter::default();
+        let shared = filter.clone();
+
+        assert!(!filter.allow_reading(FilterInput::message_like("io.element.custom")));
+        assert!(!filter.allow_sending(FilterInput::state("m.room.member", "@user:matrix.server")));
+        assert!(!filter.allow_sending_delayed_events());
+        assert!(!filter.allow_updating_delayed_events());
+        assert!(!filter.allow_uploading_media());
+        assert!(!filter.allow_downloading_media());
+        assert!(!filter.allow_reading_receipts());
+        assert!(!filter.allow_reading_fully_read_marker());
+        assert!(!filter.allow_reading_typing());
+        assert!(!filter.allow_sending_typing());
+        assert!(!filter.allow_navigating());
+
+        shared.set(Capabilities {
+            read: vec![Filter::MessageLike(MessageLikeEventFilter::WithType(
+                "io.element.custom".into(),
+            ))],
+            send: vec![Filter::State(StateEventFilter::WithType(StateEventType::RoomMember))],
+            requires_client: false,
+            update_delayed_event: true,
+            send_delayed_event: false,
+            upload_file: false,
+            download_file: true,
+            read_receipts: true,
+            fully_read_marker: false,
+            typing: true,
+            send_typing: false,
+            navigate: true,
+        });
+
+        assert!(filter.allow_reading(FilterInput::message_like("io.element.custom")));
+        assert!(!filter.allow_reading(FilterInput::message_like("m.room.message")));
+        assert!(filter.allow_sending(FilterInput::state("m.room.member", "@user:matrix.server")));
+        assert!(!filter.allow_sending(FilterInput::to_device("io.element.custom")));
+        assert!(!filter.allow_sending_delayed_events());
+        assert!(filter.allow_updating_delayed_events());
+        assert!(!filter.allow_uploading_media());
+        assert!(filter.allow_downloading_media());
+        assert!(filter.allow_reading_receipts());
+        assert!(!filter.allow_reading_fully_read_marker());
+        assert!(filter.allow_reading_typing());
+        assert!(!filter.allow_sending_typing());
+        assert!(filter.allow_navigating());
+    }
 }
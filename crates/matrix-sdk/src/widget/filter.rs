@@ -20,6 +20,7 @@ use ruma::{
     serde::Raw,
 };
 use serde::Deserialize;
+use serde_json::value::RawValue as RawJsonValue;
 use tracing::debug;
 
 use super::machine::{SendEventRequest, SendToDeviceRequest};
@@ -184,6 +185,44 @@ impl<'a> FilterInput<'a> {
     pub fn state(event_type: &'a str, state_key: &'a str) -> Self {
         Self::State(FilterInputState { event_type, state_key })
     }
+
+    pub(super) fn to_device(event_type: &'a str) -> Self {
+        Self::ToDevice(FilterInputToDevice { event_type })
+    }
+
+    /// Builds the filter input for an event of the given `event_type`,
+    /// `state_key` and `content` that a widget wants to send.
+    pub(super) fn sending(
+        event_type: &'a str,
+        state_key: Option<&'a str>,
+        content: &'a RawJsonValue,
+    ) -> Self {
+        match state_key {
+            None => match event_type {
+                "m.room.message" => {
+                    if let Some(msgtype) =
+                        serde_json::from_str::<MessageLikeFilterEventContent<'a>>(content.get())
+                            .unwrap_or_else(|e| {
+                                debug!("Failed to deserialize event content for filter: {e}");
+                                // Fallback to empty content is safe.
+                                // If we do have a filter matching any content type, it will match
+                                // independent of the body.
+                                // Any filter that does only match a specific content type will
+                                // not match the empty content.
+                                Default::default()
+                            })
+                            .msgtype
+                    {
+                        FilterInput::message_with_msgtype(msgtype)
+                    } else {
+                        FilterInput::message_like("m.room.message")
+                    }
+                }
+                _ => FilterInput::message_like(event_type),
+            },
+            Some(state_key) => FilterInput::state(event_type, state_key),
+        }
+    }
 }
 
 /// Filter input data that is used for a [`FilterInput::State`] filter.
@@ -254,39 +293,13 @@ impl<'a> TryFrom<&'a Raw<AnyToDeviceEvent>> for FilterInput<'a> {
 
 impl<'a> From<&'a SendToDeviceRequest> for FilterInput<'a> {
     fn from(request: &'a SendToDeviceRequest) -> Self {
-        FilterInput::ToDevice(FilterInputToDevice { event_type: &request.event_type })
+        FilterInput::to_device(&request.event_type)
     }
 }
 
 impl<'a> From<&'a SendEventRequest> for FilterInput<'a> {
     fn from(request: &'a SendEventRequest) -> Self {
-        match &request.state_key {
-            None => match request.event_type.as_str() {
-                "m.room.message" => {
-                    if let Some(msgtype) =
-                        serde_json::from_str::<MessageLikeFilterEventContent<'a>>(
-                            request.content.get(),
-                        )
-                        .unwrap_or_else(|e| {
-                            debug!("Failed to deserialize event content for filter: {e}");
-                            // Fallback to empty content is safe.
-                            // If we do have a filter matching any content type, it will match
-                            // independent of the body.
-                            // Any filter that does only match a specific content type will not
-                            // match the empty content.
-                            Default::default()
-                        })
-                        .msgtype
-                    {
-                        FilterInput::message_with_msgtype(msgtype)
-                    } else {
-                        FilterInput::message_like("m.room.message")
-                    }
-                }
-                _ => FilterInput::message_like(&request.event_type),
-            },
-            Some(state_key) => FilterInput::state(&request.event_type, state_key),
-        }
+        FilterInput::sending(&request.event_type, request.state_key.as_deref(), &request.content)
     }
 }
 
@@ -0,0 +1,139 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The endpoint listing the delayed events of the current user, as defined
+//! in [MSC4140](https://github.com/matrix-org/matrix-spec-proposals/pull/4140).
+//!
+//! Ruma doesn't provide it yet, so it's implemented here.
+
+use bytes::BufMut;
+use ruma::{
+    api::{
+        client::Error as ClientApiError,
+        error::{DeserializationError, FromHttpResponseError, IntoHttpError},
+        metadata, EndpointError, IncomingResponse, MatrixVersion, Metadata, OutgoingRequest,
+        SendAccessToken,
+    },
+    MilliSecondsSinceUnixEpoch, OwnedRoomId, UInt,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue as RawJsonValue;
+
+/// A delayed event that hasn't been sent by the homeserver yet.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct DelayedEvent {
+    /// The identifier of the delayed event, used to update it.
+    pub(crate) delay_id: String,
+
+    /// The room the event will be sent to.
+    pub(crate) room_id: OwnedRoomId,
+
+    /// The type of the event.
+    #[serde(rename = "type")]
+    pub(crate) event_type: String,
+
+    /// The state key of the event, if it's a state event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) state_key: Option<String>,
+
+    /// The delay after which the event is sent, in milliseconds.
+    pub(crate) delay: UInt,
+
+    /// When the delay started, i.e. when the event was scheduled or last
+    /// restarted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) running_since: Option<MilliSecondsSinceUnixEpoch>,
+
+    /// The content of the event.
+    pub(crate) content: Box<RawJsonValue>,
+}
+
+/// A request for a page of the delayed events of the current user.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Request {
+    /// The pagination token returned in the `next_batch` of the previous
+    /// page, if any.
+    pub(crate) from: Option<String>,
+}
+
+/// A page of the delayed events of the current user.
+#[derive(Debug, Deserialize)]
+pub(crate) struct Response {
+    /// The delayed events of this page, in all the rooms.
+    #[serde(default)]
+    pub(crate) delayed_events: Vec<DelayedEvent>,
+
+    /// The token to get the next page, if there's one.
+    pub(crate) next_batch: Option<String>,
+}
+
+impl OutgoingRequest for Request {
+    type EndpointError = ClientApiError;
+    type IncomingResponse = Response;
+
+    const METADATA: Metadata = metadata! {
+        method: GET,
+        rate_limited: true,
+        authentication: AccessToken,
+        history: {
+            unstable => "/_matrix/client/unstable/org.matrix.msc4140/delayed_events",
+        }
+    };
+
+    fn try_into_http_request<T: Default + BufMut>(
+        self,
+        base_url: &str,
+        access_token: SendAccessToken<'_>,
+        considering_versions: &[MatrixVersion],
+    ) -> Result<http::Request<T>, IntoHttpError> {
+        let query_string = self
+            .from
+            .map(|from| {
+                url::form_urlencoded::Serializer::new(String::new())
+                    .append_pair("from", &from)
+                    .finish()
+            })
+            .unwrap_or_default();
+
+        let url =
+            Self::METADATA.make_endpoint_url(considering_versions, base_url, &[], &query_string)?;
+
+        let access_token =
+            access_token.get_required_for_endpoint().ok_or(IntoHttpError::NeedsAuthentication)?;
+
+        Ok(http::Request::builder()
+            .method(Self::METADATA.method)
+            .uri(url)
+            .header(http::header::AUTHORIZATION, format!("Bearer {access_token}"))
+            .body(T::default())?)
+    }
+}
+
+impl IncomingResponse for Response {
+    type EndpointError = ClientApiError;
+
+    fn try_from_http_response<T: AsRef<[u8]>>(
+        response: http::Response<T>,
+    ) -> Result<Self, FromHttpResponseError<Self::EndpointError>> {
+        if response.status().as_u16() >= 400 {
+            return Err(FromHttpResponseError::Server(ClientApiError::from_http_response(
+                response,
+            )));
+        }
+
+        serde_json::from_slice(response.body().as_ref()).map_err(|error| {
+            FromHttpResponseError::Deserialization(DeserializationError::from(error))
+        })
+    }
+}
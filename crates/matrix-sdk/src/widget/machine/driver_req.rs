@@ -22,17 +22,23 @@ use ruma::{
         to_device::send_event_to_device,
     },
     events::{AnyStateEvent, AnyTimelineEvent, AnyToDeviceEventContent},
-    serde::Raw,
+    matrix_uri::MatrixId,
+    serde::{Base64, Raw},
     to_device::DeviceIdOrAllDevices,
-    OwnedUserId,
+    IdParseError, MatrixToUri, MatrixUri, OwnedMxcUri, OwnedServerName, OwnedUserId,
 };
 use serde::{de, Deserialize};
 use serde_json::value::RawValue as RawJsonValue;
 use tracing::error;
 
 use super::{
-    from_widget::SendEventResponse, incoming::MatrixDriverResponse, Action,
-    MatrixDriverRequestMeta, WidgetMachine,
+    from_widget::{
+        CancelDelayedEventsResponse, DownloadFileResponse, GetDelayedEventsResponse,
+        MediaConfigResponse, NavigateResponse, SendEventResponse, SendTypingResponse,
+        UploadFileResponse,
+    },
+    incoming::MatrixDriverResponse,
+    Action, MatrixDriverRequestMeta, WidgetMachine,
 };
 use crate::widget::{Capabilities, StateKeySelector};
 
@@ -62,6 +68,27 @@ pub(crate) enum MatrixDriverRequestData {
 
     /// Data for sending a UpdateDelayedEvent client server api request.
     UpdateDelayedEvent(UpdateDelayedEventRequest),
+
+    /// List the delayed events of the room that haven't been sent yet.
+    GetDelayedEvents,
+
+    /// Cancel several delayed events at once.
+    CancelDelayedEvents(CancelDelayedEventsRequest),
+
+    /// Get the configuration of the media repository.
+    GetMediaConfig,
+
+    /// Upload a file to the media repository.
+    UploadFile(UploadFileRequest),
+
+    /// Download a file from the media repository.
+    DownloadFile(DownloadFileRequest),
+
+    /// Send a typing notification on behalf of the user.
+    SendTyping(SendTypingRequest),
+
+    /// Ask the client to navigate to a Matrix entity.
+    Navigate(NavigationRequest),
 }
 
 /// A handle to a pending `toWidget` request.
@@ -356,3 +383,229 @@ impl FromMatrixDriverResponse for update_delayed_event::unstable::Response {
         }
     }
 }
+
+/// Ask the client for the delayed events of the room that haven't been sent
+/// yet.
+#[derive(Debug)]
+pub(crate) struct GetDelayedEventsRequest;
+
+impl From<GetDelayedEventsRequest> for MatrixDriverRequestData {
+    fn from(_: GetDelayedEventsRequest) -> Self {
+        MatrixDriverRequestData::GetDelayedEvents
+    }
+}
+
+impl MatrixDriverRequest for GetDelayedEventsRequest {
+    type Response = GetDelayedEventsResponse;
+}
+
+impl FromMatrixDriverResponse for GetDelayedEventsResponse {
+    fn from_response(ev: MatrixDriverResponse) -> Option<Self> {
+        match ev {
+            MatrixDriverResponse::DelayedEventsRead(response) => Some(response),
+            _ => {
+                error!("bug in MatrixDriver, received wrong event response");
+                None
+            }
+        }
+    }
+}
+
+/// Ask the client to cancel the delayed events with the given `delay_ids`.
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct CancelDelayedEventsRequest {
+    pub(crate) delay_ids: Vec<String>,
+}
+
+impl From<CancelDelayedEventsRequest> for MatrixDriverRequestData {
+    fn from(value: CancelDelayedEventsRequest) -> Self {
+        MatrixDriverRequestData::CancelDelayedEvents(value)
+    }
+}
+
+impl MatrixDriverRequest for CancelDelayedEventsRequest {
+    type Response = CancelDelayedEventsResponse;
+}
+
+impl FromMatrixDriverResponse for CancelDelayedEventsResponse {
+    fn from_response(ev: MatrixDriverResponse) -> Option<Self> {
+        match ev {
+            MatrixDriverResponse::DelayedEventsCancelled(response) => Some(response),
+            _ => {
+                error!("bug in MatrixDriver, received wrong event response");
+                None
+            }
+        }
+    }
+}
+
+/// Ask the client for the configuration of the media repository of the
+/// homeserver ([MSC4039](https://github.com/matrix-org/matrix-spec-proposals/pull/4039)).
+#[derive(Debug)]
+pub(crate) struct GetMediaConfigRequest;
+
+impl From<GetMediaConfigRequest> for MatrixDriverRequestData {
+    fn from(_: GetMediaConfigRequest) -> Self {
+        MatrixDriverRequestData::GetMediaConfig
+    }
+}
+
+impl MatrixDriverRequest for GetMediaConfigRequest {
+    type Response = MediaConfigResponse;
+}
+
+impl FromMatrixDriverResponse for MediaConfigResponse {
+    fn from_response(ev: MatrixDriverResponse) -> Option<Self> {
+        match ev {
+            MatrixDriverResponse::MediaConfigReceived(response) => Some(response),
+            _ => {
+                error!("bug in MatrixDriver, received wrong event response");
+                None
+            }
+        }
+    }
+}
+
+/// Ask the client to upload a file to the media repository, and return its
+/// MXC URI ([MSC4039](https://github.com/matrix-org/matrix-spec-proposals/pull/4039)).
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct UploadFileRequest {
+    /// The content of the file, encoded in base64.
+    pub(crate) file: Base64,
+    /// The MIME type of the file, `application/octet-stream` if it's not set.
+    pub(crate) mime_type: Option<String>,
+}
+
+impl From<UploadFileRequest> for MatrixDriverRequestData {
+    fn from(value: UploadFileRequest) -> Self {
+        MatrixDriverRequestData::UploadFile(value)
+    }
+}
+
+impl MatrixDriverRequest for UploadFileRequest {
+    type Response = UploadFileResponse;
+}
+
+impl FromMatrixDriverResponse for UploadFileResponse {
+    fn from_response(ev: MatrixDriverResponse) -> Option<Self> {
+        match ev {
+            MatrixDriverResponse::FileUploaded(response) => Some(response),
+            _ => {
+                error!("bug in MatrixDriver, received wrong event response");
+                None
+            }
+        }
+    }
+}
+
+/// Ask the client to download a file from the media repository, and return its
+/// content ([MSC4039](https://github.com/matrix-org/matrix-spec-proposals/pull/4039)).
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct DownloadFileRequest {
+    /// The MXC URI of the file.
+    pub(crate) content_uri: OwnedMxcUri,
+}
+
+impl From<DownloadFileRequest> for MatrixDriverRequestData {
+    fn from(value: DownloadFileRequest) -> Self {
+        MatrixDriverRequestData::DownloadFile(value)
+    }
+}
+
+impl MatrixDriverRequest for DownloadFileRequest {
+    type Response = DownloadFileResponse;
+}
+
+impl FromMatrixDriverResponse for DownloadFileResponse {
+    fn from_response(ev: MatrixDriverResponse) -> Option<Self> {
+        match ev {
+            MatrixDriverResponse::FileDownloaded(response) => Some(response),
+            _ => {
+                error!("bug in MatrixDriver, received wrong event response");
+                None
+            }
+        }
+    }
+}
+
+/// Ask the client to send a typing notification on behalf of the user.
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct SendTypingRequest {
+    /// Whether the user is typing, or has stopped typing.
+    pub(crate) typing: bool,
+}
+
+impl From<SendTypingRequest> for MatrixDriverRequestData {
+    fn from(value: SendTypingRequest) -> Self {
+        MatrixDriverRequestData::SendTyping(value)
+    }
+}
+
+impl MatrixDriverRequest for SendTypingRequest {
+    type Response = SendTypingResponse;
+}
+
+impl FromMatrixDriverResponse for SendTypingResponse {
+    fn from_response(ev: MatrixDriverResponse) -> Option<Self> {
+        match ev {
+            MatrixDriverResponse::TypingSent(response) => Some(response),
+            _ => {
+                error!("bug in MatrixDriver, received wrong event response");
+                None
+            }
+        }
+    }
+}
+
+/// A request from a widget to navigate to a Matrix entity, like a room, an
+/// event or a user.
+///
+/// It is validated by the widget driver, then forwarded to the client with
+/// [`WidgetDriverHandle::recv_navigation_request`], which is responsible for
+/// actually showing the entity to the user.
+///
+/// [`WidgetDriverHandle::recv_navigation_request`]: crate::widget::WidgetDriverHandle::recv_navigation_request
+#[derive(Clone, Debug)]
+pub struct NavigationRequest {
+    /// The Matrix entity to navigate to.
+    pub id: MatrixId,
+
+    /// The servers which can be used to join the room, if the entity is a
+    /// room or an event in a room.
+    pub via: Vec<OwnedServerName>,
+}
+
+impl NavigationRequest {
+    /// Parse the URI sent by the widget, either a `matrix.to` link or a
+    /// `matrix:` URI.
+    pub(crate) fn parse(uri: &str) -> Result<Self, IdParseError> {
+        if let Ok(uri) = MatrixToUri::parse(uri) {
+            return Ok(Self { id: uri.id().clone(), via: uri.via().to_owned() });
+        }
+
+        let uri = MatrixUri::parse(uri)?;
+        Ok(Self { id: uri.id().clone(), via: uri.via().to_owned() })
+    }
+}
+
+impl From<NavigationRequest> for MatrixDriverRequestData {
+    fn from(value: NavigationRequest) -> Self {
+        MatrixDriverRequestData::Navigate(value)
+    }
+}
+
+impl MatrixDriverRequest for NavigationRequest {
+    type Response = NavigateResponse;
+}
+
+impl FromMatrixDriverResponse for NavigateResponse {
+    fn from_response(ev: MatrixDriverResponse) -> Option<Self> {
+        match ev {
+            MatrixDriverResponse::Navigated(response) => Some(response),
+            _ => {
+                error!("bug in MatrixDriver, received wrong event response");
+                None
+            }
+        }
+    }
+}
@@ -16,16 +16,25 @@ use as_variant::as_variant;
 use ruma::{
     api::client::{
         delayed_events::{delayed_message_event, delayed_state_event, update_delayed_event},
-        error::{ErrorBody, StandardErrorBody},
+        error::{ErrorBody, ErrorKind, RetryAfter, StandardErrorBody},
     },
     events::AnyTimelineEvent,
-    serde::Raw,
-    OwnedEventId, OwnedRoomId,
+    serde::{Base64, Raw},
+    OwnedEventId, OwnedMxcUri, OwnedRoomId, UInt,
 };
 use serde::{Deserialize, Serialize};
 
-use super::{driver_req::SendToDeviceRequest, SendEventRequest, UpdateDelayedEventRequest};
-use crate::{widget::StateKeySelector, Error, HttpError, RumaApiError};
+use super::{
+    driver_req::{
+        CancelDelayedEventsRequest, DownloadFileRequest, SendToDeviceRequest, SendTypingRequest,
+        UploadFileRequest,
+    },
+    SendEventRequest, UpdateDelayedEventRequest,
+};
+use crate::{
+    widget::{get_delayed_events::DelayedEvent, StateKeySelector},
+    Error, HttpError, RumaApiError,
+};
 
 #[derive(Deserialize, Debug)]
 #[serde(tag = "action", rename_all = "snake_case", content = "data")]
@@ -40,6 +49,20 @@ pub(super) enum FromWidgetRequest {
     SendToDevice(SendToDeviceRequest),
     #[serde(rename = "org.matrix.msc4157.update_delayed_event")]
     DelayedEventUpdate(UpdateDelayedEventRequest),
+    #[serde(rename = "org.matrix.msc4157.get_delayed_events")]
+    GetDelayedEvents {},
+    #[serde(rename = "org.matrix.msc4157.cancel_delayed_events")]
+    CancelDelayedEvents(CancelDelayedEventsRequest),
+    #[serde(rename = "org.matrix.msc4039.get_media_config")]
+    GetMediaConfig {},
+    #[serde(rename = "org.matrix.msc4039.upload_file")]
+    UploadFile(UploadFileRequest),
+    #[serde(rename = "org.matrix.msc4039.download_file")]
+    DownloadFile(DownloadFileRequest),
+    #[serde(rename = "io.element.send_typing")]
+    SendTyping(SendTypingRequest),
+    #[serde(rename = "org.matrix.msc2931.navigate")]
+    Navigate(NavigateRequest),
 }
 
 /// The full response a client sends to a [`FromWidgetRequest`] in case of an
@@ -60,18 +83,20 @@ impl FromWidgetErrorResponse {
             _ => None,
         };
 
-        Self {
-            error: FromWidgetError {
-                message,
-                matrix_api_error: matrix_api_error.and_then(|api_error| match api_error.body {
-                    ErrorBody::Standard { kind, message } => Some(FromWidgetMatrixErrorBody {
-                        http_status: api_error.status_code.as_u16().into(),
-                        response: StandardErrorBody { kind, message },
-                    }),
-                    _ => None,
-                }),
-            },
-        }
+        let matrix_api_error = matrix_api_error.and_then(|api_error| match api_error.body {
+            ErrorBody::Standard { kind, message } => Some(FromWidgetMatrixErrorBody {
+                http_status: api_error.status_code.as_u16().into(),
+                response: StandardErrorBody { kind, message },
+            }),
+            _ => None,
+        });
+        let rate_limit = matrix_api_error.as_ref().and_then(|error| {
+            as_variant!(&error.response.kind, ErrorKind::LimitExceeded { retry_after } => {
+                FromWidgetRateLimitError::new(retry_after.as_ref())
+            })
+        });
+
+        Self { error: FromWidgetError { message, matrix_api_error, rate_limit } }
     }
 
     /// Create an error response to send to the widget from a Matrix SDK error.
@@ -87,7 +112,13 @@ impl FromWidgetErrorResponse {
 
     /// Create a error response to send to the widget from a string.
     pub(crate) fn from_string<S: Into<String>>(error: S) -> Self {
-        Self { error: FromWidgetError { message: error.into(), matrix_api_error: None } }
+        Self {
+            error: FromWidgetError {
+                message: error.into(),
+                matrix_api_error: None,
+                rate_limit: None,
+            },
+        }
     }
 }
 
@@ -105,6 +136,32 @@ struct FromWidgetError {
     /// Optional Matrix error hinting at workarounds for specific errors.
     #[serde(skip_serializing_if = "Option::is_none")]
     matrix_api_error: Option<FromWidgetMatrixErrorBody>,
+
+    /// Set when the action failed because the homeserver rate-limited it, and
+    /// the retries of the client were exhausted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rate_limit: Option<FromWidgetRateLimitError>,
+}
+
+/// Serializable section of a widget response that represents a rate limit
+/// enforced by the homeserver.
+#[derive(Serialize)]
+struct FromWidgetRateLimitError {
+    /// How long the widget should wait before trying again, in milliseconds,
+    /// if the homeserver said so.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_after_ms: Option<u64>,
+}
+
+impl FromWidgetRateLimitError {
+    fn new(retry_after: Option<&RetryAfter>) -> Self {
+        let retry_after_ms = retry_after.and_then(|retry_after| match retry_after {
+            RetryAfter::Delay(delay) => Some(delay.as_millis().try_into().unwrap_or(u64::MAX)),
+            RetryAfter::DateTime(_) => None,
+        });
+
+        Self { retry_after_ms }
+    }
 }
 
 /// Serializable section of a widget response that represents a Matrix error.
@@ -136,6 +193,7 @@ impl SupportedApiVersionsResponse {
                 ApiVersion::MSC2762UpdateState,
                 ApiVersion::MSC2871,
                 ApiVersion::MSC3819,
+                ApiVersion::MSC4039,
             ],
         }
     }
@@ -183,6 +241,10 @@ pub(super) enum ApiVersion {
     /// Supports access to the TURN servers.
     #[serde(rename = "town.robin.msc3846")]
     MSC3846,
+
+    /// Supports uploading and downloading media.
+    #[serde(rename = "org.matrix.msc4039")]
+    MSC4039,
 }
 
 #[derive(Deserialize, Debug)]
@@ -245,6 +307,21 @@ impl From<update_delayed_event::unstable::Response> for UpdateDelayedEventRespon
     }
 }
 
+/// The response to the widget with the delayed events of the room that haven't
+/// been sent yet.
+#[derive(Serialize, Debug)]
+pub(crate) struct GetDelayedEventsResponse {
+    pub(crate) delayed_events: Vec<DelayedEvent>,
+}
+
+/// The empty response to the widget once its delayed events have been
+/// cancelled.
+///
+/// Like [`UpdateDelayedEventResponse`], this is an empty struct so that it
+/// serializes to `{}`.
+#[derive(Serialize, Debug)]
+pub(crate) struct CancelDelayedEventsResponse {}
+
 /// The response to the widget that it received the to-device event.
 /// Only used as the response for the successful send case.
 /// FromWidgetErrorResponse will be used otherwise.
@@ -252,3 +329,50 @@ impl From<update_delayed_event::unstable::Response> for UpdateDelayedEventRespon
 /// serializes to `{}` instead of `Null` when returned to the widget as json.
 #[derive(Serialize, Debug)]
 pub(crate) struct SendToDeviceEventResponse {}
+
+/// The response to the widget with the configuration of the media repository
+/// of the homeserver.
+#[derive(Serialize, Debug)]
+pub(crate) struct MediaConfigResponse {
+    /// The maximum size of an upload, in bytes.
+    #[serde(rename = "m.upload.size")]
+    pub(crate) upload_size: UInt,
+}
+
+/// The response to the widget with the URI of the media it uploaded.
+#[derive(Serialize, Debug)]
+pub(crate) struct UploadFileResponse {
+    /// The MXC URI of the uploaded media.
+    pub(crate) content_uri: OwnedMxcUri,
+}
+
+/// The response to the widget with the content of the media it downloaded.
+#[derive(Serialize, Debug)]
+pub(crate) struct DownloadFileResponse {
+    /// The content of the media, encoded in base64.
+    pub(crate) file: Base64,
+}
+
+/// The empty response to the widget once its typing notification has been
+/// sent.
+///
+/// Like [`UpdateDelayedEventResponse`], this is an empty struct so that it
+/// serializes to `{}`.
+#[derive(Serialize, Debug)]
+pub(crate) struct SendTypingResponse {}
+
+/// A request from the widget to navigate to the Matrix entity with the given
+/// URI.
+#[derive(Deserialize, Debug)]
+pub(super) struct NavigateRequest {
+    /// A `matrix.to` link or a `matrix:` URI.
+    pub(super) uri: String,
+}
+
+/// The empty response to the widget once the client has been asked to
+/// navigate.
+///
+/// Like [`UpdateDelayedEventResponse`], this is an empty struct so that it
+/// serializes to `{}`.
+#[derive(Serialize, Debug)]
+pub(crate) struct NavigateResponse {}
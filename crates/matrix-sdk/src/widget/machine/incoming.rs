@@ -14,17 +14,25 @@
 
 use ruma::{
     api::client::{account::request_openid_token, delayed_events, to_device::send_event_to_device},
-    events::{AnyStateEvent, AnyTimelineEvent, AnyToDeviceEvent},
+    events::{
+        fully_read::FullyReadEvent, receipt::ReceiptEvent, typing::TypingEvent, AnyStateEvent,
+        AnyTimelineEvent, AnyToDeviceEvent,
+    },
     serde::Raw,
 };
-use serde::{de, Deserialize, Deserializer};
-use serde_json::value::RawValue as RawJsonValue;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::{value::RawValue as RawJsonValue, Map, Value};
+use tracing::warn;
 use uuid::Uuid;
 
 #[cfg(doc)]
 use super::MatrixDriverRequestData;
 use super::{
-    from_widget::{FromWidgetRequest, SendEventResponse},
+    from_widget::{
+        CancelDelayedEventsResponse, DownloadFileResponse, FromWidgetRequest,
+        GetDelayedEventsResponse, MediaConfigResponse, NavigateResponse, SendEventResponse,
+        SendTypingResponse, UploadFileResponse,
+    },
     to_widget::ToWidgetResponse,
 };
 use crate::widget::Capabilities;
@@ -50,18 +58,106 @@ pub(crate) enum IncomingMessage {
     ///
     /// This means that the machine previously subscribed to some events
     /// ([`crate::widget::Action::SubscribeTimeline`] request).
-    MatrixEventReceived(Raw<AnyTimelineEvent>),
+    MatrixEventReceived(ForwardedEvent<AnyTimelineEvent>),
 
     /// The `MatrixDriver` notified the `WidgetMachine` of a change in room
     /// state.
     ///
+    /// The events are ordered chronologically: the ones from the state section
+    /// of the sync come first, followed by the state events of the timeline.
+    ///
     /// This means that the machine previously subscribed to some events
     /// ([`crate::widget::Action::Subscribe`] request).
-    StateUpdateReceived(Vec<Raw<AnyStateEvent>>),
+    StateUpdateReceived(Vec<ForwardedEvent<AnyStateEvent>>),
 
     /// The `MatrixDriver` notified the `WidgetMachine` of a new to-device
     /// event.
     ToDeviceReceived(Raw<AnyToDeviceEvent>),
+
+    /// The `MatrixDriver` notified the `WidgetMachine` of new public read
+    /// receipts in the room.
+    ReadReceiptsReceived(Raw<ReceiptEvent>),
+
+    /// The `MatrixDriver` notified the `WidgetMachine` that the fully-read
+    /// marker of the user moved in the room.
+    FullyReadMarkerReceived(Raw<FullyReadEvent>),
+
+    /// The `MatrixDriver` notified the `WidgetMachine` that the list of room
+    /// members who are typing changed.
+    TypingReceived(Raw<TypingEvent>),
+}
+
+/// The sections of a sync response an event forwarded by the `MatrixDriver`
+/// has been found in.
+///
+/// An event can be part of both sections, if it's part of the timeline and
+/// the server also included it in the resolved state.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub(crate) struct EventOrigin {
+    /// The event has been found in the timeline section.
+    pub(crate) timeline: bool,
+
+    /// The event has been found in the state section.
+    pub(crate) state: bool,
+}
+
+impl EventOrigin {
+    /// An event only found in the timeline section.
+    pub(crate) const TIMELINE: Self = Self { timeline: true, state: false };
+
+    /// An event only found in the state section.
+    pub(crate) const STATE: Self = Self { timeline: false, state: true };
+}
+
+/// An event forwarded by the `MatrixDriver`, along with the sections of the
+/// sync response it has been found in.
+#[derive(Clone, Debug)]
+pub(crate) struct ForwardedEvent<T> {
+    /// The event, with its room ID attached.
+    pub(crate) event: Raw<T>,
+
+    /// Where the event comes from.
+    pub(crate) origin: EventOrigin,
+}
+
+impl<T> ForwardedEvent<T> {
+    /// The field of the `unsigned` object of an event forwarded to the widget
+    /// which holds its [`EventOrigin`].
+    pub(crate) const ORIGIN_FIELD: &'static str = "io.element.origin";
+
+    pub(crate) fn new(event: Raw<T>, origin: EventOrigin) -> Self {
+        Self { event, origin }
+    }
+
+    /// Get the event to forward to the widget, with its origin attached in the
+    /// [`Self::ORIGIN_FIELD`] of its `unsigned` object.
+    pub(crate) fn into_annotated_event(self) -> Raw<T> {
+        let mut event = match self.event.deserialize_as::<Map<String, Value>>() {
+            Ok(event) => event,
+            Err(error) => {
+                warn!("Couldn't attach the origin to a forwarded event: {error}");
+                return self.event;
+            }
+        };
+
+        let unsigned = event.entry("unsigned").or_insert_with(|| Value::Object(Map::new()));
+        let Value::Object(unsigned) = unsigned else {
+            warn!("Couldn't attach the origin to a forwarded event with a malformed `unsigned`");
+            return self.event;
+        };
+
+        match serde_json::to_value(self.origin) {
+            Ok(origin) => {
+                unsigned.insert(Self::ORIGIN_FIELD.to_owned(), origin);
+            }
+            Err(error) => {
+                warn!("Couldn't serialize the origin of a forwarded event: {error}");
+                return self.event;
+            }
+        }
+
+        Raw::new(&event).map(Raw::cast).unwrap_or(self.event)
+    }
 }
 
 pub(crate) enum MatrixDriverResponse {
@@ -86,6 +182,28 @@ pub(crate) enum MatrixDriverResponse {
     /// Client updated a delayed event.
     /// A response to a [`MatrixDriverRequestData::UpdateDelayedEvent`] command.
     DelayedEventUpdated(delayed_events::update_delayed_event::unstable::Response),
+    /// Client listed the delayed events of the room.
+    /// A response to a [`MatrixDriverRequestData::GetDelayedEvents`] command.
+    DelayedEventsRead(GetDelayedEventsResponse),
+    /// Client cancelled some delayed events.
+    /// A response to a [`MatrixDriverRequestData::CancelDelayedEvents`]
+    /// command.
+    DelayedEventsCancelled(CancelDelayedEventsResponse),
+    /// Client got the configuration of the media repository.
+    /// A response to a [`MatrixDriverRequestData::GetMediaConfig`] command.
+    MediaConfigReceived(MediaConfigResponse),
+    /// Client uploaded a file. The response contains its MXC URI.
+    /// A response to a [`MatrixDriverRequestData::UploadFile`] command.
+    FileUploaded(UploadFileResponse),
+    /// Client downloaded a file. The response contains its content.
+    /// A response to a [`MatrixDriverRequestData::DownloadFile`] command.
+    FileDownloaded(DownloadFileResponse),
+    /// Client sent a typing notification.
+    /// A response to a [`MatrixDriverRequestData::SendTyping`] command.
+    TypingSent(SendTypingResponse),
+    /// Client was asked to navigate to a Matrix entity.
+    /// A response to a [`MatrixDriverRequestData::Navigate`] command.
+    Navigated(NavigateResponse),
 }
 
 pub(super) struct IncomingWidgetMessage {
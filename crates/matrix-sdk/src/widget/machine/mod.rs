@@ -26,13 +26,16 @@ use ruma::{
 };
 use serde::Serialize;
 use serde_json::value::RawValue as RawJsonValue;
-use to_widget::NotifyNewToDeviceMessage;
+use to_widget::{
+    NotifyFullyReadMarker, NotifyNewToDeviceMessage, NotifyReadReceipts, NotifyTyping,
+};
 use tracing::{error, info, instrument, warn};
 use uuid::Uuid;
 
 use self::{
     driver_req::{
-        AcquireCapabilities, MatrixDriverRequest, MatrixDriverRequestHandle, RequestOpenId,
+        AcquireCapabilities, GetDelayedEventsRequest, GetMediaConfigRequest, MatrixDriverRequest,
+        MatrixDriverRequestHandle, RequestOpenId,
     },
     from_widget::{
         FromWidgetErrorResponse, FromWidgetRequest, ReadEventsResponse,
@@ -49,7 +52,9 @@ use self::{
 #[cfg(doc)]
 use super::WidgetDriver;
 use super::{
-    capabilities::{SEND_DELAYED_EVENT, UPDATE_DELAYED_EVENT},
+    capabilities::{
+        DOWNLOAD_FILE, NAVIGATE, SEND_DELAYED_EVENT, SEND_TYPING, UPDATE_DELAYED_EVENT, UPLOAD_FILE,
+    },
     filter::FilterInput,
     Capabilities, StateEventFilter, StateKeySelector,
 };
@@ -64,10 +69,15 @@ mod pending;
 mod tests;
 mod to_widget;
 
+pub use self::driver_req::NavigationRequest;
 pub(crate) use self::{
     driver_req::{MatrixDriverRequestData, SendEventRequest, SendToDeviceRequest},
-    from_widget::SendEventResponse,
-    incoming::{IncomingMessage, MatrixDriverResponse},
+    from_widget::{
+        CancelDelayedEventsResponse, DownloadFileResponse, GetDelayedEventsResponse,
+        MediaConfigResponse, NavigateResponse, SendEventResponse, SendTypingResponse,
+        UploadFileResponse,
+    },
+    incoming::{EventOrigin, ForwardedEvent, IncomingMessage, MatrixDriverResponse},
 };
 
 /// A command to perform in reaction to an [`IncomingMessage`].
@@ -195,13 +205,21 @@ impl WidgetMachine {
             IncomingMessage::MatrixDriverResponse { request_id, response } => {
                 self.process_matrix_driver_response(request_id, response)
             }
-            IncomingMessage::MatrixEventReceived(event_raw) => {
+            IncomingMessage::MatrixEventReceived(ForwardedEvent { event: event_raw, origin }) => {
                 let CapabilitiesState::Negotiated(capabilities) = &self.capabilities else {
                     error!("Received Matrix event before capabilities negotiation");
                     return Vec::new();
                 };
 
+                // Only events from the timeline are new events; the state section is reported
+                // with state updates.
+                if !origin.timeline {
+                    warn!("Received a Matrix event which isn't part of the timeline, ignoring");
+                    return Vec::new();
+                }
+
                 if capabilities.allow_reading(&event_raw) {
+                    let event_raw = ForwardedEvent::new(event_raw, origin).into_annotated_event();
                     self.send_to_widget_request(NotifyNewMatrixEvent(event_raw))
                         .map(|(_request, action)| vec![action])
                         .unwrap_or_default()
@@ -223,13 +241,56 @@ impl WidgetMachine {
                     vec![]
                 }
             }
+            IncomingMessage::ReadReceiptsReceived(receipts) => {
+                let CapabilitiesState::Negotiated(capabilities) = &self.capabilities else {
+                    error!("Received read receipts before capabilities negotiation");
+                    return Vec::new();
+                };
+
+                if capabilities.read_receipts {
+                    self.send_to_widget_request(NotifyReadReceipts(receipts))
+                        .map(|(_request, action)| vec![action])
+                        .unwrap_or_default()
+                } else {
+                    vec![]
+                }
+            }
+            IncomingMessage::FullyReadMarkerReceived(fully_read) => {
+                let CapabilitiesState::Negotiated(capabilities) = &self.capabilities else {
+                    error!("Received fully-read marker before capabilities negotiation");
+                    return Vec::new();
+                };
+
+                if capabilities.fully_read_marker {
+                    self.send_to_widget_request(NotifyFullyReadMarker(fully_read))
+                        .map(|(_request, action)| vec![action])
+                        .unwrap_or_default()
+                } else {
+                    vec![]
+                }
+            }
+            IncomingMessage::TypingReceived(typing) => {
+                let CapabilitiesState::Negotiated(capabilities) = &self.capabilities else {
+                    error!("Received typing notification before capabilities negotiation");
+                    return Vec::new();
+                };
+
+                if capabilities.typing {
+                    self.send_to_widget_request(NotifyTyping(typing))
+                        .map(|(_request, action)| vec![action])
+                        .unwrap_or_default()
+                } else {
+                    vec![]
+                }
+            }
             IncomingMessage::StateUpdateReceived(mut state) => {
                 let CapabilitiesState::Negotiated(capabilities) = &self.capabilities else {
                     error!("Received state update before capabilities negotiation");
                     return Vec::new();
                 };
 
-                state.retain(|event| capabilities.allow_reading(event));
+                state.retain(|forwarded| capabilities.allow_reading(&forwarded.event));
+                let state = resolve_state_update(state);
 
                 match &mut self.pending_state_updates {
                     Some(InitialStateUpdate { postponed_updates, .. }) => {
@@ -381,9 +442,105 @@ impl WidgetMachine {
                 })
                 .unwrap_or_default()
             }
+
+            FromWidgetRequest::GetDelayedEvents {} => self.process_gated_request(
+                GetDelayedEventsRequest,
+                raw_request,
+                |capabilities| capabilities.update_delayed_event,
+                UPDATE_DELAYED_EVENT,
+            ),
+
+            FromWidgetRequest::CancelDelayedEvents(req) => self.process_gated_request(
+                req,
+                raw_request,
+                |capabilities| capabilities.update_delayed_event,
+                UPDATE_DELAYED_EVENT,
+            ),
+
+            FromWidgetRequest::GetMediaConfig {} => self.process_gated_request(
+                GetMediaConfigRequest,
+                raw_request,
+                |capabilities| capabilities.upload_file,
+                UPLOAD_FILE,
+            ),
+
+            FromWidgetRequest::UploadFile(req) => self.process_gated_request(
+                req,
+                raw_request,
+                |capabilities| capabilities.upload_file,
+                UPLOAD_FILE,
+            ),
+
+            FromWidgetRequest::DownloadFile(req) => self.process_gated_request(
+                req,
+                raw_request,
+                |capabilities| capabilities.download_file,
+                DOWNLOAD_FILE,
+            ),
+
+            FromWidgetRequest::SendTyping(req) => self.process_gated_request(
+                req,
+                raw_request,
+                |capabilities| capabilities.send_typing,
+                SEND_TYPING,
+            ),
+
+            FromWidgetRequest::Navigate(req) => match NavigationRequest::parse(&req.uri) {
+                Ok(request) => self.process_gated_request(
+                    request,
+                    raw_request,
+                    |capabilities| capabilities.navigate,
+                    NAVIGATE,
+                ),
+                Err(error) => vec![Self::send_from_widget_error_string_response(
+                    raw_request,
+                    format!("Invalid Matrix URI `{}`: {error}", req.uri),
+                )],
+            },
         }
     }
 
+    /// Forward a request to the Matrix driver, if the widget has been granted
+    /// the given `capability`.
+    fn process_gated_request<T>(
+        &mut self,
+        request: T,
+        raw_request: Raw<FromWidgetRequest>,
+        is_allowed: impl FnOnce(&Capabilities) -> bool,
+        capability: &str,
+    ) -> Vec<Action>
+    where
+        T: MatrixDriverRequest,
+        T::Response: Serialize,
+    {
+        let CapabilitiesState::Negotiated(capabilities) = &self.capabilities else {
+            return vec![Self::send_from_widget_error_string_response(
+                raw_request,
+                "Received request before capabilities were negotiated",
+            )];
+        };
+
+        if !is_allowed(capabilities) {
+            return vec![Self::send_from_widget_error_string_response(
+                raw_request,
+                format!("Not allowed: missing the {capability} capability."),
+            )];
+        }
+
+        self.send_matrix_driver_request(request)
+            .map(|(request, request_action)| {
+                request.add_response_handler(|result, _machine| {
+                    vec![Self::send_from_widget_response(
+                        raw_request,
+                        result.map_err(FromWidgetErrorResponse::from_error),
+                    )]
+                });
+
+                vec![request_action]
+            })
+            .unwrap_or_default()
+    }
+
     /// Send a response to a request to read events.
     ///
     /// `events` represents the message-like events provided by the
@@ -770,7 +927,7 @@ impl WidgetMachine {
         });
 
         let mut actions = Vec::new();
-        if !approved.read.is_empty() {
+        if approved.requires_subscription() {
             actions.push(Action::Subscribe);
         }
 
@@ -871,8 +1028,10 @@ impl WidgetMachine {
 
         // XXX: This branch appears to be accounting for capability **re**negotiation
         // (MSC2974), which isn't implemented yet
-        if matches!(&self.capabilities, CapabilitiesState::Negotiated(c) if !c.read.is_empty()) {
-            actions.push(Action::Unsubscribe);
+        if let CapabilitiesState::Negotiated(c) = &self.capabilities {
+            if c.requires_subscription() {
+                actions.push(Action::Unsubscribe);
+            }
         }
 
         self.capabilities = CapabilitiesState::Negotiating;
@@ -924,3 +1083,35 @@ enum CapabilitiesState {
     /// The capabilities have already been negotiated.
     Negotiated(Capabilities),
 }
+
+/// Collapse the updates to the same room state entry in a state update,
+/// keeping only the most recent one.
+///
+/// The events are expected to be ordered chronologically, as forwarded by the
+/// `MatrixDriver`, so an entry updated in the timeline supersedes its value
+/// from the state section. The remaining events are annotated with their
+/// [`EventOrigin`].
+fn resolve_state_update(state: Vec<ForwardedEvent<AnyStateEvent>>) -> Vec<Raw<AnyStateEvent>> {
+    let mut resolved = IndexMap::with_capacity(state.len());
+
+    for forwarded in state {
+        let (Ok(Some(event_type)), Ok(Some(state_key))) = (
+            forwarded.event.get_field::<String>("type"),
+            forwarded.event.get_field::<String>("state_key"),
+        ) else {
+            warn!(
+                origin = ?forwarded.origin,
+                "Received a state event without a type or state key, ignoring"
+            );
+            continue;
+        };
+
+        // Remove the previous update first, so the entry takes the position of its most
+        // recent update.
+        let key = (event_type, state_key);
+        resolved.shift_remove(&key);
+        resolved.insert(key, forwarded);
+    }
+
+    resolved.into_values().map(ForwardedEvent::into_annotated_event).collect()
+}
@@ -19,7 +19,9 @@ use serde_json::{from_value, json};
 
 use super::{parse_msg, WIDGET_ID};
 use crate::widget::{
-    capabilities::{READ_EVENT, READ_STATE, READ_TODEVICE},
+    capabilities::{
+        READ_EVENT, READ_FULLY_READ_MARKER, READ_RECEIPTS, READ_STATE, READ_TODEVICE, READ_TYPING,
+    },
     machine::{
         incoming::MatrixDriverResponse, Action, IncomingMessage, MatrixDriverRequestData,
         WidgetMachine,
@@ -195,7 +197,10 @@ pub(super) fn assert_capabilities_dance(
     };
 
     // We get the `Subscribe` command if we requested some reading capabilities.
-    if [READ_EVENT, READ_STATE, READ_TODEVICE].into_iter().any(|c| capability.starts_with(c)) {
+    if [READ_EVENT, READ_STATE, READ_TODEVICE, READ_RECEIPTS, READ_FULLY_READ_MARKER, READ_TYPING]
+        .into_iter()
+        .any(|c| capability.starts_with(c))
+    {
         let action = actions.remove(0);
         assert_matches!(action, Action::Subscribe);
     }
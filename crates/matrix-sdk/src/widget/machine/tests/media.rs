@@ -0,0 +1,92 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use assert_matches2::assert_let;
+use ruma::{owned_mxc_uri, owned_room_id};
+use serde_json::json;
+
+use super::{capabilities::assert_capabilities_dance, parse_msg, WIDGET_ID};
+use crate::widget::machine::{
+    Action, IncomingMessage, MatrixDriverRequestData, MatrixDriverResponse, UploadFileResponse,
+    WidgetMachine,
+};
+
+#[test]
+fn test_upload_file_request_handling_works() {
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), owned_room_id!("!a98sd12bjh:example.org"), false);
+    assert_capabilities_dance(&mut machine, actions, Some("org.matrix.msc4039.upload_file"));
+
+    // The widget asks to upload a file, which is forwarded to the driver.
+    let actions = machine.process(IncomingMessage::WidgetMessage(json_string!({
+        "api": "fromWidget",
+        "widgetId": WIDGET_ID,
+        "requestId": "upload-request-id",
+        "action": "org.matrix.msc4039.upload_file",
+        "data": {
+            "file": "aGVsbG8",
+            "mime_type": "text/plain",
+        },
+    })));
+
+    let [action]: [Action; 1] = actions.try_into().unwrap();
+    assert_let!(
+        Action::MatrixDriverRequest {
+            request_id,
+            data: MatrixDriverRequestData::UploadFile(req)
+        } = action
+    );
+    assert_eq!(req.file.as_bytes(), b"hello");
+    assert_eq!(req.mime_type.as_deref(), Some("text/plain"));
+
+    // The driver uploaded it, the widget gets its URI.
+    let actions = machine.process(IncomingMessage::MatrixDriverResponse {
+        request_id,
+        response: Ok(MatrixDriverResponse::FileUploaded(UploadFileResponse {
+            content_uri: owned_mxc_uri!("mxc://example.org/abcdef"),
+        })),
+    });
+
+    let [action]: [Action; 1] = actions.try_into().unwrap();
+    assert_let!(Action::SendToWidget(msg) = action);
+    let (msg, request_id) = parse_msg(&msg);
+    assert_eq!(request_id, "upload-request-id");
+    assert_eq!(msg["response"], json!({ "content_uri": "mxc://example.org/abcdef" }));
+}
+
+#[test]
+fn test_download_file_requires_the_capability() {
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), owned_room_id!("!a98sd12bjh:example.org"), false);
+    assert_capabilities_dance(&mut machine, actions, Some("org.matrix.msc4039.upload_file"));
+
+    // The widget can't download a file, the driver isn't even asked.
+    let actions = machine.process(IncomingMessage::WidgetMessage(json_string!({
+        "api": "fromWidget",
+        "widgetId": WIDGET_ID,
+        "requestId": "download-request-id",
+        "action": "org.matrix.msc4039.download_file",
+        "data": {
+            "content_uri": "mxc://example.org/abcdef",
+        },
+    })));
+
+    let [action]: [Action; 1] = actions.try_into().unwrap();
+    assert_let!(Action::SendToWidget(msg) = action);
+    let (msg, _) = parse_msg(&msg);
+    assert_eq!(
+        msg["response"]["error"]["message"],
+        "Not allowed: missing the org.matrix.msc4039.download_file capability."
+    );
+}
@@ -25,8 +25,12 @@ macro_rules! json_string {
 mod api_versions;
 mod capabilities;
 mod error;
+mod media;
+mod navigate;
 mod openid;
+mod read_markers;
 mod send_event;
+mod typing;
 
 const WIDGET_ID: &str = "test-widget";
 
@@ -0,0 +1,138 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use assert_matches2::assert_let;
+use ruma::{matrix_uri::MatrixId, owned_room_id, owned_server_name};
+use serde_json::json;
+
+use super::{capabilities::assert_capabilities_dance, parse_msg, WIDGET_ID};
+use crate::widget::machine::{
+    Action, IncomingMessage, MatrixDriverRequestData, MatrixDriverResponse, NavigateResponse,
+    WidgetMachine,
+};
+
+#[test]
+fn test_navigate_request_handling_works() {
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), owned_room_id!("!a98sd12bjh:example.org"), false);
+    assert_capabilities_dance(&mut machine, actions, Some("org.matrix.msc2931.navigate"));
+
+    // The widget asks to navigate to a room, which is forwarded to the driver.
+    let actions = machine.process(IncomingMessage::WidgetMessage(json_string!({
+        "api": "fromWidget",
+        "widgetId": WIDGET_ID,
+        "requestId": "navigate-request-id",
+        "action": "org.matrix.msc2931.navigate",
+        "data": {
+            "uri": "https://matrix.to/#/!other:example.org?via=example.org",
+        },
+    })));
+
+    let [action]: [Action; 1] = actions.try_into().unwrap();
+    assert_let!(
+        Action::MatrixDriverRequest { request_id, data: MatrixDriverRequestData::Navigate(req) } =
+            action
+    );
+    assert_let!(MatrixId::Room(room_id) = req.id);
+    assert_eq!(room_id, "!other:example.org");
+    assert_eq!(req.via, vec![owned_server_name!("example.org")]);
+
+    // The client has been asked to navigate, the widget gets an empty response.
+    let actions = machine.process(IncomingMessage::MatrixDriverResponse {
+        request_id,
+        response: Ok(MatrixDriverResponse::Navigated(NavigateResponse {})),
+    });
+
+    let [action]: [Action; 1] = actions.try_into().unwrap();
+    assert_let!(Action::SendToWidget(msg) = action);
+    let (msg, request_id) = parse_msg(&msg);
+    assert_eq!(request_id, "navigate-request-id");
+    assert_eq!(msg["response"], json!({}));
+}
+
+#[test]
+fn test_navigate_accepts_matrix_scheme_uris() {
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), owned_room_id!("!a98sd12bjh:example.org"), false);
+    assert_capabilities_dance(&mut machine, actions, Some("org.matrix.msc2931.navigate"));
+
+    let actions = machine.process(IncomingMessage::WidgetMessage(json_string!({
+        "api": "fromWidget",
+        "widgetId": WIDGET_ID,
+        "requestId": "navigate-request-id",
+        "action": "org.matrix.msc2931.navigate",
+        "data": {
+            "uri": "matrix:u/alice:example.org",
+        },
+    })));
+
+    let [action]: [Action; 1] = actions.try_into().unwrap();
+    assert_let!(
+        Action::MatrixDriverRequest { data: MatrixDriverRequestData::Navigate(req), .. } = action
+    );
+    assert_let!(MatrixId::User(user_id) = req.id);
+    assert_eq!(user_id, "@alice:example.org");
+    assert!(req.via.is_empty());
+}
+
+#[test]
+fn test_navigate_rejects_invalid_uris() {
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), owned_room_id!("!a98sd12bjh:example.org"), false);
+    assert_capabilities_dance(&mut machine, actions, Some("org.matrix.msc2931.navigate"));
+
+    // The URI isn't a Matrix URI, the driver isn't even asked.
+    let actions = machine.process(IncomingMessage::WidgetMessage(json_string!({
+        "api": "fromWidget",
+        "widgetId": WIDGET_ID,
+        "requestId": "navigate-request-id",
+        "action": "org.matrix.msc2931.navigate",
+        "data": {
+            "uri": "https://example.org/not-a-permalink",
+        },
+    })));
+
+    let [action]: [Action; 1] = actions.try_into().unwrap();
+    assert_let!(Action::SendToWidget(msg) = action);
+    let (msg, _) = parse_msg(&msg);
+    assert!(msg["response"]["error"]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Invalid Matrix URI `https://example.org/not-a-permalink`"));
+}
+
+#[test]
+fn test_navigate_requires_the_capability() {
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), owned_room_id!("!a98sd12bjh:example.org"), false);
+    assert_capabilities_dance(&mut machine, actions, Some("io.element.receive.typing"));
+
+    let actions = machine.process(IncomingMessage::WidgetMessage(json_string!({
+        "api": "fromWidget",
+        "widgetId": WIDGET_ID,
+        "requestId": "navigate-request-id",
+        "action": "org.matrix.msc2931.navigate",
+        "data": {
+            "uri": "https://matrix.to/#/!other:example.org",
+        },
+    })));
+
+    let [action]: [Action; 1] = actions.try_into().unwrap();
+    assert_let!(Action::SendToWidget(msg) = action);
+    let (msg, _) = parse_msg(&msg);
+    assert_eq!(
+        msg["response"]["error"]["message"],
+        "Not allowed: missing the org.matrix.msc2931.navigate capability."
+    );
+}
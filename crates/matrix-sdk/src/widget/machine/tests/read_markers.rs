@@ -0,0 +1,99 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use assert_matches2::assert_let;
+use ruma::{owned_room_id, serde::Raw};
+use serde_json::json;
+
+use super::{capabilities::assert_capabilities_dance, parse_msg, WIDGET_ID};
+use crate::widget::machine::{Action, IncomingMessage, WidgetMachine};
+
+#[test]
+fn test_read_receipts_are_forwarded_with_the_capability() {
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), owned_room_id!("!a98sd12bjh:example.org"), false);
+    assert_capabilities_dance(&mut machine, actions, Some("io.element.receive.read_receipts"));
+
+    let receipts = json!({
+        "type": "m.receipt",
+        "room_id": "!a98sd12bjh:example.org",
+        "content": {
+            "$event:example.org": {
+                "m.read": {
+                    "@alice:example.org": { "ts": 1676512345 },
+                },
+            },
+        },
+    });
+
+    let actions =
+        machine.process(IncomingMessage::ReadReceiptsReceived(Raw::new(&receipts).unwrap().cast()));
+
+    let [action]: [Action; 1] = actions.try_into().unwrap();
+    assert_let!(Action::SendToWidget(msg) = action);
+    let (msg, _request_id) = parse_msg(&msg);
+    assert_eq!(
+        msg,
+        json!({
+            "api": "toWidget",
+            "widgetId": WIDGET_ID,
+            "action": "io.element.read_receipts",
+            "data": receipts,
+        }),
+    );
+
+    // The fully-read marker hasn't been granted, it's not forwarded.
+    let fully_read = json!({
+        "type": "m.fully_read",
+        "room_id": "!a98sd12bjh:example.org",
+        "content": { "event_id": "$event:example.org" },
+    });
+
+    let actions = machine
+        .process(IncomingMessage::FullyReadMarkerReceived(Raw::new(&fully_read).unwrap().cast()));
+    assert!(actions.is_empty());
+}
+
+#[test]
+fn test_fully_read_marker_is_forwarded_with_the_capability() {
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), owned_room_id!("!a98sd12bjh:example.org"), false);
+    assert_capabilities_dance(&mut machine, actions, Some("io.element.receive.fully_read_marker"));
+
+    let fully_read = json!({
+        "type": "m.fully_read",
+        "room_id": "!a98sd12bjh:example.org",
+        "content": { "event_id": "$event:example.org" },
+    });
+
+    let actions = machine
+        .process(IncomingMessage::FullyReadMarkerReceived(Raw::new(&fully_read).unwrap().cast()));
+
+    let [action]: [Action; 1] = actions.try_into().unwrap();
+    assert_let!(Action::SendToWidget(msg) = action);
+    let (msg, _request_id) = parse_msg(&msg);
+    assert_eq!(msg["action"], "io.element.fully_read_marker");
+    assert_eq!(msg["data"], fully_read);
+
+    // Read receipts haven't been granted, they're not forwarded.
+    let receipts = json!({
+        "type": "m.receipt",
+        "room_id": "!a98sd12bjh:example.org",
+        "content": {},
+    });
+
+    let actions =
+        machine.process(IncomingMessage::ReadReceiptsReceived(Raw::new(&receipts).unwrap().cast()));
+    assert!(actions.is_empty());
+}
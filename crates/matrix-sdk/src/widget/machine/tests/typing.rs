@@ -0,0 +1,139 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use assert_matches2::assert_let;
+use ruma::{owned_room_id, serde::Raw};
+use serde_json::json;
+
+use super::{capabilities::assert_capabilities_dance, parse_msg, WIDGET_ID};
+use crate::widget::machine::{
+    Action, IncomingMessage, MatrixDriverRequestData, MatrixDriverResponse, SendTypingResponse,
+    WidgetMachine,
+};
+
+#[test]
+fn test_typing_notifications_are_forwarded_with_the_capability() {
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), owned_room_id!("!a98sd12bjh:example.org"), false);
+    assert_capabilities_dance(&mut machine, actions, Some("io.element.receive.typing"));
+
+    let typing = json!({
+        "type": "m.typing",
+        "room_id": "!a98sd12bjh:example.org",
+        "content": {
+            "user_ids": ["@alice:example.org"],
+        },
+    });
+
+    let actions =
+        machine.process(IncomingMessage::TypingReceived(Raw::new(&typing).unwrap().cast()));
+
+    let [action]: [Action; 1] = actions.try_into().unwrap();
+    assert_let!(Action::SendToWidget(msg) = action);
+    let (msg, _request_id) = parse_msg(&msg);
+    assert_eq!(
+        msg,
+        json!({
+            "api": "toWidget",
+            "widgetId": WIDGET_ID,
+            "action": "io.element.typing",
+            "data": typing,
+        }),
+    );
+}
+
+#[test]
+fn test_typing_notifications_are_not_forwarded_without_the_capability() {
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), owned_room_id!("!a98sd12bjh:example.org"), false);
+    assert_capabilities_dance(&mut machine, actions, Some("io.element.receive.read_receipts"));
+
+    let typing = json!({
+        "type": "m.typing",
+        "room_id": "!a98sd12bjh:example.org",
+        "content": {
+            "user_ids": ["@alice:example.org"],
+        },
+    });
+
+    let actions =
+        machine.process(IncomingMessage::TypingReceived(Raw::new(&typing).unwrap().cast()));
+    assert!(actions.is_empty());
+}
+
+#[test]
+fn test_send_typing_request_handling_works() {
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), owned_room_id!("!a98sd12bjh:example.org"), false);
+    assert_capabilities_dance(&mut machine, actions, Some("io.element.send.typing"));
+
+    // The widget says the user is typing, which is forwarded to the driver.
+    let actions = machine.process(IncomingMessage::WidgetMessage(json_string!({
+        "api": "fromWidget",
+        "widgetId": WIDGET_ID,
+        "requestId": "typing-request-id",
+        "action": "io.element.send_typing",
+        "data": {
+            "typing": true,
+        },
+    })));
+
+    let [action]: [Action; 1] = actions.try_into().unwrap();
+    assert_let!(
+        Action::MatrixDriverRequest {
+            request_id,
+            data: MatrixDriverRequestData::SendTyping(req)
+        } = action
+    );
+    assert!(req.typing);
+
+    // The driver sent the typing notification, the widget gets an empty response.
+    let actions = machine.process(IncomingMessage::MatrixDriverResponse {
+        request_id,
+        response: Ok(MatrixDriverResponse::TypingSent(SendTypingResponse {})),
+    });
+
+    let [action]: [Action; 1] = actions.try_into().unwrap();
+    assert_let!(Action::SendToWidget(msg) = action);
+    let (msg, request_id) = parse_msg(&msg);
+    assert_eq!(request_id, "typing-request-id");
+    assert_eq!(msg["response"], json!({}));
+}
+
+#[test]
+fn test_send_typing_requires_the_capability() {
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), owned_room_id!("!a98sd12bjh:example.org"), false);
+    assert_capabilities_dance(&mut machine, actions, Some("io.element.receive.typing"));
+
+    // Observing typing notifications doesn't allow sending them, the driver
+    // isn't even asked.
+    let actions = machine.process(IncomingMessage::WidgetMessage(json_string!({
+        "api": "fromWidget",
+        "widgetId": WIDGET_ID,
+        "requestId": "typing-request-id",
+        "action": "io.element.send_typing",
+        "data": {
+            "typing": true,
+        },
+    })));
+
+    let [action]: [Action; 1] = actions.try_into().unwrap();
+    assert_let!(Action::SendToWidget(msg) = action);
+    let (msg, _) = parse_msg(&msg);
+    assert_eq!(
+        msg["response"]["error"]["message"],
+        "Not allowed: missing the io.element.send.typing capability."
+    );
+}
@@ -15,7 +15,10 @@
 use std::marker::PhantomData;
 
 use ruma::{
-    events::{AnyStateEvent, AnyTimelineEvent, AnyToDeviceEvent},
+    events::{
+        fully_read::FullyReadEvent, receipt::ReceiptEvent, typing::TypingEvent, AnyStateEvent,
+        AnyTimelineEvent, AnyToDeviceEvent,
+    },
     serde::Raw,
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
@@ -150,3 +153,36 @@ impl ToWidgetRequest for NotifyNewToDeviceMessage {
     const ACTION: &'static str = "send_to_device";
     type ResponseData = Empty;
 }
+
+/// Notify the widget that we received new public read receipts in the room.
+/// This is a "response" to the widget subscribing to the events in the room.
+#[derive(Serialize)]
+#[serde(transparent)]
+pub(crate) struct NotifyReadReceipts(pub(crate) Raw<ReceiptEvent>);
+
+impl ToWidgetRequest for NotifyReadReceipts {
+    const ACTION: &'static str = "io.element.read_receipts";
+    type ResponseData = Empty;
+}
+
+/// Notify the widget that the fully-read marker of the user moved in the room.
+/// This is a "response" to the widget subscribing to the events in the room.
+#[derive(Serialize)]
+#[serde(transparent)]
+pub(crate) struct NotifyFullyReadMarker(pub(crate) Raw<FullyReadEvent>);
+
+impl ToWidgetRequest for NotifyFullyReadMarker {
+    const ACTION: &'static str = "io.element.fully_read_marker";
+    type ResponseData = Empty;
+}
+
+/// Notify the widget that the list of room members who are typing changed.
+/// This is a "response" to the widget subscribing to the events in the room.
+#[derive(Serialize)]
+#[serde(transparent)]
+pub(crate) struct NotifyTyping(pub(crate) Raw<TypingEvent>);
+
+impl ToWidgetRequest for NotifyTyping {
+    const ACTION: &'static str = "io.element.typing";
+    type ResponseData = Empty;
+}
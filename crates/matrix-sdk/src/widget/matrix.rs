@@ -15,25 +15,41 @@
 //! Matrix driver implementation that exposes Matrix functionality
 //! that is relevant for the widget API.
 
-use std::collections::BTreeMap;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    sync::Mutex,
+    time::Duration,
+};
 
+use async_channel::{Sender, TrySendError};
+use futures_util::future::join_all;
 use matrix_sdk_base::deserialized_responses::{EncryptionInfo, RawAnySyncOrStrippedState};
+use mime::Mime;
 use ruma::{
     api::client::{
         account::request_openid_token::v3::{Request as OpenIdRequest, Response as OpenIdResponse},
         delayed_events::{self, update_delayed_event::unstable::UpdateAction},
+        error::ErrorKind,
         filter::RoomEventFilter,
+        redact::redact_event,
+        state::send_state_event,
         to_device::send_event_to_device::{self, v3::Request as RumaToDeviceRequest},
     },
     assign,
     events::{
+        fully_read::FullyReadEvent,
+        receipt::{ReceiptEvent, ReceiptType, SyncReceiptEvent},
+        room::MediaSource,
+        typing::{SyncTypingEvent, TypingEvent},
         AnyMessageLikeEventContent, AnyStateEvent, AnyStateEventContent, AnySyncStateEvent,
         AnySyncTimelineEvent, AnyTimelineEvent, AnyToDeviceEvent, AnyToDeviceEventContent,
         MessageLikeEventType, StateEventType, TimelineEventType, ToDeviceEventType,
     },
     serde::{from_raw_json_value, Raw},
+    time::Instant,
     to_device::DeviceIdOrAllDevices,
-    EventId, OwnedUserId, RoomId, TransactionId,
+    EventId, OwnedDeviceId, OwnedEventId, OwnedMxcUri, OwnedUserId, RoomId, TransactionId, UInt,
+    UserId,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{value::RawValue as RawJsonValue, Value};
@@ -43,32 +59,112 @@ use tokio::sync::{
 };
 use tracing::{error, trace, warn};
 
-use super::{machine::SendEventResponse, StateKeySelector};
+use super::{
+    capabilities::{
+        CapabilitiesFilter, DOWNLOAD_FILE, NAVIGATE, SEND_DELAYED_EVENT, SEND_TYPING,
+        UPDATE_DELAYED_EVENT, UPLOAD_FILE,
+    },
+    filter::FilterInput,
+    get_delayed_events::{self, DelayedEvent},
+    machine::{EventOrigin, ForwardedEvent, NavigationRequest, SendEventResponse},
+    Capabilities, StateKeySelector,
+};
 use crate::{
-    event_handler::EventHandlerDropGuard, room::MessagesOptions, sync::RoomUpdate, Client, Error,
-    Result, Room,
+    config::RequestConfig,
+    crypto::TrustRequirement,
+    encryption::identities::{Device, UserIdentity},
+    event_handler::EventHandlerDropGuard,
+    media::{MediaFormat, MediaRequestParameters},
+    room::MessagesOptions,
+    sync::RoomUpdate,
+    Client, Error, Result, Room,
 };
 
+/// The maximum number of pages of the `/delayed_events` endpoint fetched when
+/// listing the delayed events of the widget.
+const MAX_DELAYED_EVENTS_PAGES: usize = 10;
+
 /// Thin wrapper around a [`Room`] that provides functionality relevant for
 /// widgets.
+///
+/// The driver only reads, sends and forwards the events allowed by the
+/// capabilities granted to the widget, see [`MatrixDriver::set_capabilities`].
 pub(crate) struct MatrixDriver {
     room: Room,
+    capabilities: CapabilitiesFilter,
+    /// Where the navigation requests of the widget are forwarded to the
+    /// client, see [`MatrixDriver::navigate`].
+    navigation_tx: Sender<NavigationRequest>,
+    /// The OpenID tokens already requested by the widget, see
+    /// [`MatrixDriver::get_open_id`].
+    openid_tokens: OpenIdTokenCache,
+    /// The configuration of the requests sending the events of the widget,
+    /// which limits how many times they're retried, see
+    /// [`MatrixDriver::send`].
+    send_request_config: RequestConfig,
+    /// The IDs of the delayed events sent by the widget in this room, the only
+    /// ones it can list and cancel in bulk.
+    delay_ids: Mutex<HashSet<String>>,
 }
 
 impl MatrixDriver {
     /// Creates a new `MatrixDriver` for a given `room`.
-    pub(crate) fn new(room: Room) -> Self {
-        Self { room }
+    ///
+    /// No capability is granted to the widget until
+    /// [`MatrixDriver::set_capabilities`] is called. Cached OpenID tokens are
+    /// refreshed `openid_token_refresh_skew` before they expire, and
+    /// rate-limited sends are retried up to `max_rate_limit_retries` times.
+    ///
+    /// The retries are done by the HTTP client, so the limit also applies to
+    /// the other transient failures, like server or network errors.
+    pub(crate) fn new(
+        room: Room,
+        navigation_tx: Sender<NavigationRequest>,
+        openid_token_refresh_skew: Duration,
+        max_rate_limit_retries: u32,
+    ) -> Self {
+        Self {
+            room,
+            capabilities: CapabilitiesFilter::default(),
+            navigation_tx,
+            openid_tokens: OpenIdTokenCache::new(openid_token_refresh_skew),
+            // The HTTP client retries the rate-limited requests after the delay requested by
+            // the homeserver. The retry limit counts the first attempt.
+            send_request_config: room.client.request_config().retry_limit(
+                usize::try_from(max_rate_limit_retries).unwrap_or(usize::MAX).saturating_add(1),
+            ),
+            delay_ids: Default::default(),
+        }
+    }
+
+    /// Sets the capabilities granted to the widget, which are enforced on
+    /// every read, send and forwarded event.
+    pub(crate) fn set_capabilities(&self, capabilities: Capabilities) {
+        self.capabilities.set(capabilities);
     }
 
     /// Requests an OpenID token for the current user.
+    ///
+    /// The token is cached, and returned again until it's about to expire, so
+    /// that a busy widget doesn't hit the homeserver for every request.
     pub(crate) async fn get_open_id(&self) -> Result<OpenIdResponse> {
-        let user_id = self.room.own_user_id().to_owned();
-        self.room
+        let user_id = self.room.own_user_id();
+
+        if let Some(response) = self.openid_tokens.get(user_id, Instant::now()) {
+            trace!("Reusing the cached OpenID token");
+            return Ok(response);
+        }
+
+        let response = self
+            .room
             .client
-            .send(OpenIdRequest::new(user_id))
+            .send(OpenIdRequest::new(user_id.to_owned()))
             .await
-            .map_err(|error| Error::Http(Box::new(error)))
+            .map_err(|error| Error::Http(Box::new(error)))?;
+
+        self.openid_tokens.insert(user_id.to_owned(), response.clone(), Instant::now());
+
+        Ok(response)
     }
 
     /// Reads the latest `limit` events of a given `event_type` from the room's
@@ -92,6 +188,7 @@ impl MatrixDriver {
             .chunk
             .into_iter()
             .map(|ev| ev.into_raw().cast())
+            .filter(|ev| self.capabilities.allow_reading(ev))
             .filter(|ev| match &state_key {
                 Some(state_key) => {
                     ev.get_field::<String>("state_key").is_ok_and(|key| match state_key {
@@ -115,7 +212,8 @@ impl MatrixDriver {
     ) -> Result<Vec<Raw<AnyStateEvent>>> {
         let room_id = self.room.room_id();
         let convert = |sync_or_stripped_state| match sync_or_stripped_state {
-            RawAnySyncOrStrippedState::Sync(ev) => Some(attach_room_id_state(&ev, room_id)),
+            RawAnySyncOrStrippedState::Sync(ev) => Some(attach_room_id_state(&ev, room_id))
+                .filter(|ev| self.capabilities.allow_reading(ev)),
             RawAnySyncOrStrippedState::Stripped(_) => {
                 error!("MatrixDriver can't operate in invited rooms");
                 None
@@ -153,23 +251,49 @@ impl MatrixDriver {
     ) -> Result<SendEventResponse> {
         let type_str = event_type.to_string();
 
+        if delayed_event_parameters.is_some() && !self.capabilities.allow_sending_delayed_events() {
+            return Err(not_allowed(format!("missing the {SEND_DELAYED_EVENT} capability")));
+        }
+
+        if !self.capabilities.allow_sending(FilterInput::sending(
+            &type_str,
+            state_key.as_deref(),
+            &content,
+        )) {
+            return Err(not_allowed(format!("missing the capability to send {type_str} events")));
+        }
+
+        let config = self.send_request_config;
+
         if let Some(redacts) = from_raw_json_value::<Value, serde_json::Error>(&content)
             .ok()
             .and_then(|b| b["redacts"].as_str().and_then(|s| EventId::parse(s).ok()))
         {
-            return Ok(SendEventResponse::from_event_id(
-                self.room.redact(&redacts, None, None).await?.event_id,
-            ));
+            let request = redact_event::v3::Request::new(
+                self.room.room_id().to_owned(),
+                redacts,
+                TransactionId::new(),
+            );
+            let response = self.room.client.send(request).with_request_config(config).await?;
+            return Ok(SendEventResponse::from_event_id(response.event_id));
         }
 
-        Ok(match (state_key, delayed_event_parameters) {
+        let response = match (state_key, delayed_event_parameters) {
             (None, None) => SendEventResponse::from_event_id(
-                self.room.send_raw(&type_str, content).await?.event_id,
+                self.room.send_raw(&type_str, content).with_request_config(config).await?.event_id,
             ),
 
-            (Some(key), None) => SendEventResponse::from_event_id(
-                self.room.send_state_event_raw(&type_str, &key, content).await?.event_id,
-            ),
+            (Some(key), None) => {
+                let request = send_state_event::v3::Request::new_raw(
+                    self.room.room_id().to_owned(),
+                    StateEventType::from(type_str),
+                    key,
+                    Raw::from_json(content),
+                );
+                SendEventResponse::from_event_id(
+                    self.room.client.send(request).with_request_config(config).await?.event_id,
+                )
+            }
 
             (None, Some(delayed_event_parameters)) => {
                 let r = delayed_events::delayed_message_event::unstable::Request::new_raw(
@@ -179,7 +303,7 @@ impl MatrixDriver {
                     delayed_event_parameters,
                     Raw::<AnyMessageLikeEventContent>::from_json(content),
                 );
-                self.room.client.send(r).await.map(|r| r.into())?
+                self.room.client.send(r).with_request_config(config).await.map(|r| r.into())?
             }
 
             (Some(key), Some(delayed_event_parameters)) => {
@@ -190,9 +314,15 @@ impl MatrixDriver {
                     delayed_event_parameters,
                     Raw::<AnyStateEventContent>::from_json(content),
                 );
-                self.room.client.send(r).await.map(|r| r.into())?
+                self.room.client.send(r).with_request_config(config).await.map(|r| r.into())?
             }
-        })
+        };
+
+        if let Some(delay_id) = &response.delay_id {
+            self.delay_ids.lock().unwrap().insert(delay_id.clone());
+        }
+
+        Ok(response)
     }
 
     /// Send a request to the `/delayed_events`` endpoint ([MSC4140](https://github.com/matrix-org/matrix-spec-proposals/pull/4140))
@@ -204,31 +334,269 @@ impl MatrixDriver {
         delay_id: String,
         action: UpdateAction,
     ) -> Result<delayed_events::update_delayed_event::unstable::Response> {
+        if !self.capabilities.allow_updating_delayed_events() {
+            return Err(not_allowed(format!("missing the {UPDATE_DELAYED_EVENT} capability")));
+        }
+
         let r = delayed_events::update_delayed_event::unstable::Request::new(delay_id, action);
         self.room.client.send(r).await.map_err(|error| Error::Http(Box::new(error)))
     }
 
+    /// Lists the delayed events sent by the widget in the room that haven't
+    /// been sent by the homeserver yet, fetching the pages of the
+    /// `/delayed_events` endpoint
+    /// ([MSC4140](https://github.com/matrix-org/matrix-spec-proposals/pull/4140)).
+    ///
+    /// At most [`MAX_DELAYED_EVENTS_PAGES`] pages are fetched.
+    pub(crate) async fn list_delayed_events(&self) -> Result<Vec<DelayedEvent>> {
+        if !self.capabilities.allow_updating_delayed_events() {
+            return Err(not_allowed(format!("missing the {UPDATE_DELAYED_EVENT} capability")));
+        }
+
+        let mut delayed_events = Vec::new();
+        let mut from = None;
+
+        for _ in 0..MAX_DELAYED_EVENTS_PAGES {
+            let response = self.room.client.send(get_delayed_events::Request { from }).await?;
+
+            // The endpoint returns the delayed events of the user in all the rooms, only
+            // keep the ones the widget sent in this room.
+            {
+                let delay_ids = self.delay_ids.lock().unwrap();
+                delayed_events.extend(response.delayed_events.into_iter().filter(|ev| {
+                    ev.room_id == self.room.room_id() && delay_ids.contains(&ev.delay_id)
+                }));
+            }
+
+            match response.next_batch {
+                Some(next_batch) => from = Some(next_batch),
+                None => return Ok(delayed_events),
+            }
+        }
+
+        warn!("Too many pages of delayed events, stopping at {MAX_DELAYED_EVENTS_PAGES}");
+
+        Ok(delayed_events)
+    }
+
+    /// Cancels all the given delayed events.
+    ///
+    /// The widget can only cancel the delayed events it sent in the room; the
+    /// request is rejected if any of them wasn't.
+    ///
+    /// All the cancellations are attempted, even if some of them fail; the
+    /// first error is returned in that case. Delayed events that have already
+    /// been sent or cancelled are ignored.
+    pub(crate) async fn cancel_delayed_events(&self, delay_ids: Vec<String>) -> Result<()> {
+        if !self.capabilities.allow_updating_delayed_events() {
+            return Err(not_allowed(format!("missing the {UPDATE_DELAYED_EVENT} capability")));
+        }
+
+        {
+            let own_delay_ids = self.delay_ids.lock().unwrap();
+
+            if let Some(delay_id) = delay_ids.iter().find(|id| !own_delay_ids.contains(*id)) {
+                return Err(not_allowed(format!(
+                    "the delayed event {delay_id} wasn't sent by the widget in this room"
+                )));
+            }
+        }
+
+        let results = join_all(delay_ids.into_iter().map(|delay_id| async move {
+            let request = delayed_events::update_delayed_event::unstable::Request::new(
+                delay_id.clone(),
+                UpdateAction::Cancel,
+            );
+            (delay_id, self.room.client.send(request).await)
+        }))
+        .await;
+
+        let mut first_error = None;
+
+        for (delay_id, result) in results {
+            match result {
+                Ok(_) => {}
+                Err(error) if error.client_api_error_kind() == Some(&ErrorKind::NotFound) => {
+                    trace!(%delay_id, "delayed event was already sent or cancelled");
+                }
+                Err(error) => {
+                    warn!(%delay_id, "couldn't cancel delayed event: {error}");
+                    first_error.get_or_insert(error);
+                    continue;
+                }
+            }
+
+            // The delayed event doesn't exist anymore.
+            self.delay_ids.lock().unwrap().remove(&delay_id);
+        }
+
+        match first_error {
+            Some(error) => Err(Error::Http(Box::new(error))),
+            None => Ok(()),
+        }
+    }
+
+    /// Gets the maximum size of an upload to the media repository, in bytes.
+    pub(crate) async fn get_media_config(&self) -> Result<UInt> {
+        if !self.capabilities.allow_uploading_media() {
+            return Err(not_allowed(format!("missing the {UPLOAD_FILE} capability")));
+        }
+
+        self.room.client.load_or_fetch_max_upload_size().await
+    }
+
+    /// Uploads the given `data` to the media repository, with the given
+    /// `mime_type` or `application/octet-stream` if it's not set.
+    ///
+    /// Returns the MXC URI of the uploaded media.
+    pub(crate) async fn upload_media(
+        &self,
+        data: Vec<u8>,
+        mime_type: Option<&str>,
+    ) -> Result<OwnedMxcUri> {
+        if !self.capabilities.allow_uploading_media() {
+            return Err(not_allowed(format!("missing the {UPLOAD_FILE} capability")));
+        }
+
+        let mime_type = match mime_type {
+            Some(mime_type) => {
+                mime_type.parse::<Mime>().map_err(|error| Error::UnknownError(Box::new(error)))?
+            }
+            None => mime::APPLICATION_OCTET_STREAM,
+        };
+
+        Ok(self.room.client.media().upload(&mime_type, data, None).await?.content_uri)
+    }
+
+    /// Downloads the content of the media with the given MXC URI.
+    pub(crate) async fn download_media(&self, uri: OwnedMxcUri) -> Result<Vec<u8>> {
+        if !self.capabilities.allow_downloading_media() {
+            return Err(not_allowed(format!("missing the {DOWNLOAD_FILE} capability")));
+        }
+
+        let request =
+            MediaRequestParameters { source: MediaSource::Plain(uri), format: MediaFormat::File };
+        self.room.client.media().get_media_content(&request, true).await
+    }
+
+    /// Sends a typing notification on behalf of the user: whether they're
+    /// typing, or have stopped typing.
+    pub(crate) async fn send_typing(&self, typing: bool) -> Result<()> {
+        if !self.capabilities.allow_sending_typing() {
+            return Err(not_allowed(format!("missing the {SEND_TYPING} capability")));
+        }
+
+        self.room.typing_notice(typing).await
+    }
+
+    /// Asks the client to navigate to the Matrix entity of the given request.
+    ///
+    /// The request is rejected, instead of waiting, if the client hasn't
+    /// handled the previous navigation requests yet.
+    pub(crate) fn navigate(&self, request: NavigationRequest) -> Result<()> {
+        if !self.capabilities.allow_navigating() {
+            return Err(not_allowed(format!("missing the {NAVIGATE} capability")));
+        }
+
+        self.navigation_tx.try_send(request).map_err(|err| match err {
+            TrySendError::Full(_) => {
+                Error::UnknownError("too many navigation requests are pending".into())
+            }
+            TrySendError::Closed(_) => {
+                Error::UnknownError("the client doesn't listen to navigation requests".into())
+            }
+        })
+    }
+
     /// Starts forwarding new room events. Once the returned `EventReceiver`
     /// is dropped, forwarding will be stopped.
-    pub(crate) fn events(&self) -> EventReceiver<Raw<AnyTimelineEvent>> {
+    pub(crate) fn events(&self) -> EventReceiver<ForwardedEvent<AnyTimelineEvent>> {
         let (tx, rx) = unbounded_channel();
         let room_id = self.room.room_id().to_owned();
+        let capabilities = self.capabilities.clone();
 
         let handle = self.room.add_event_handler(move |raw: Raw<AnySyncTimelineEvent>| {
-            let _ = tx.send(attach_room_id(raw.cast_ref(), &room_id));
+            let event = attach_room_id(raw.cast_ref(), &room_id);
+            if capabilities.allow_reading(&event) {
+                let _ = tx.send(ForwardedEvent::new(event, EventOrigin::TIMELINE));
+            }
             async {}
         });
         let drop_guard = self.room.client().event_handler_drop_guard(handle);
 
         // The receiver will get a combination of state and message like events.
         // These always come from the timeline (rather than the state section of the
-        // sync).
+        // sync), the state section is forwarded by `Self::state_updates`.
+        EventReceiver { rx, _drop_guard: drop_guard }
+    }
+
+    /// Starts forwarding the public read receipts of the room. Once the
+    /// returned `EventReceiver` is dropped, forwarding will be stopped.
+    ///
+    /// Private read receipts are stripped before being forwarded, so they are
+    /// never disclosed to the widget.
+    pub(crate) fn read_receipts(&self) -> EventReceiver<Raw<ReceiptEvent>> {
+        let (tx, rx) = unbounded_channel();
+        let room_id = self.room.room_id().to_owned();
+        let capabilities = self.capabilities.clone();
+
+        let handle = self.room.add_event_handler(move |raw: Raw<SyncReceiptEvent>| {
+            if capabilities.allow_reading_receipts() {
+                if let Some(receipts) = public_receipts(&raw, &room_id) {
+                    let _ = tx.send(receipts);
+                }
+            }
+            async {}
+        });
+        let drop_guard = self.room.client().event_handler_drop_guard(handle);
+
+        EventReceiver { rx, _drop_guard: drop_guard }
+    }
+
+    /// Starts forwarding the updates of the fully-read marker of the user in
+    /// the room. Once the returned `EventReceiver` is dropped, forwarding will
+    /// be stopped.
+    pub(crate) fn fully_read_marker(&self) -> EventReceiver<Raw<FullyReadEvent>> {
+        let (tx, rx) = unbounded_channel();
+        let room_id = self.room.room_id().to_owned();
+        let capabilities = self.capabilities.clone();
+
+        let handle = self.room.add_event_handler(move |raw: Raw<FullyReadEvent>| {
+            if capabilities.allow_reading_fully_read_marker() {
+                let _ = tx.send(attach_room_id(raw.cast_ref(), &room_id).cast());
+            }
+            async {}
+        });
+        let drop_guard = self.room.client().event_handler_drop_guard(handle);
+
+        EventReceiver { rx, _drop_guard: drop_guard }
+    }
+
+    /// Starts forwarding the typing notifications of the room members. Once
+    /// the returned `EventReceiver` is dropped, forwarding will be stopped.
+    pub(crate) fn typing(&self) -> EventReceiver<Raw<TypingEvent>> {
+        let (tx, rx) = unbounded_channel();
+        let room_id = self.room.room_id().to_owned();
+        let capabilities = self.capabilities.clone();
+
+        let handle = self.room.add_event_handler(move |raw: Raw<SyncTypingEvent>| {
+            if capabilities.allow_reading_typing() {
+                let _ = tx.send(attach_room_id(raw.cast_ref(), &room_id).cast());
+            }
+            async {}
+        });
+        let drop_guard = self.room.client().event_handler_drop_guard(handle);
+
         EventReceiver { rx, _drop_guard: drop_guard }
     }
 
-    /// Starts forwarding new updates to room state.
+    /// Starts forwarding new updates to room state, coming from both the
+    /// state and the timeline sections of the sync.
     pub(crate) fn state_updates(&self) -> StateUpdateReceiver {
-        StateUpdateReceiver { room_updates: self.room.subscribe_to_updates() }
+        StateUpdateReceiver {
+            room_updates: self.room.subscribe_to_updates(),
+            capabilities: self.capabilities.clone(),
+        }
     }
 
     /// Starts forwarding new room events. Once the returned `EventReceiver`
@@ -237,6 +605,7 @@ impl MatrixDriver {
         let (tx, rx) = unbounded_channel();
 
         let room_id = self.room.room_id().to_owned();
+        let capabilities = self.capabilities.clone();
         let to_device_handle = self.room.client().add_event_handler(
 
             async move |raw: Raw<AnyToDeviceEvent>, encryption_info: Option<EncryptionInfo>, client: Client| {
@@ -247,6 +616,10 @@ impl MatrixDriver {
                     return;
                 }
 
+                if !capabilities.allow_reading(&raw) {
+                    return;
+                }
+
                 // Encryption can be enabled after the widget has been instantiated,
                 // we want to keep track of the latest status
                 let Some(room) = client.get_room(&room_id) else {
@@ -338,8 +711,13 @@ impl MatrixDriver {
         filtered
     }
 
-    /// It will ignore all devices where errors occurred or where the device is
-    /// not verified or where th user has a has_verification_violation.
+    /// Sends the given to-device `messages` of type `event_type`.
+    ///
+    /// If `encrypted` is set, the messages are Olm-encrypted for each of the
+    /// recipient devices before being sent, and only sent to the devices
+    /// meeting the trust requirement of the client. The devices for which
+    /// sending failed don't prevent the others from receiving the message,
+    /// and are reported in the returned error.
     pub(crate) async fn send_to_device(
         &self,
         event_type: ToDeviceEventType,
@@ -351,18 +729,149 @@ impl MatrixDriver {
     ) -> Result<send_event_to_device::v3::Response> {
         let client = self.room.client();
 
-        let request = if encrypted {
-            return Err(Error::UnknownError(
-                "Sending encrypted to-device events is not supported by the widget driver.".into(),
-            ));
-        } else {
-            RumaToDeviceRequest::new_raw(event_type, TransactionId::new(), messages)
-        };
+        let type_str = event_type.to_string();
+        if !self.capabilities.allow_sending(FilterInput::to_device(&type_str)) {
+            return Err(not_allowed(format!(
+                "missing the capability to send {type_str} to-device events"
+            )));
+        }
+
+        if encrypted {
+            self.send_encrypted_to_device(&event_type, messages).await?;
+            return Ok(send_event_to_device::v3::Response::new());
+        }
+
+        let request = RumaToDeviceRequest::new_raw(event_type, TransactionId::new(), messages);
 
         let response = client.send(request).await;
 
         response.map_err(Into::into)
     }
+
+    /// Olm-encrypts and sends the given to-device `messages`.
+    ///
+    /// Recipient devices are grouped by message content, so that each distinct
+    /// content is encrypted and sent once for all the devices it targets.
+    ///
+    /// The devices are filtered according to the trust requirement of the
+    /// client: a message targeting all the devices of a user is only sent to
+    /// the devices meeting it, while a message targeting a specific device
+    /// that doesn't meet it, or is unknown, is reported as a failure.
+    async fn send_encrypted_to_device(
+        &self,
+        event_type: &ToDeviceEventType,
+        messages: BTreeMap<
+            OwnedUserId,
+            BTreeMap<DeviceIdOrAllDevices, Raw<AnyToDeviceEventContent>>,
+        >,
+    ) -> Result<()> {
+        let client = &self.room.client;
+        let encryption = client.encryption();
+        let trust_requirement = client.decryption_settings().sender_device_trust_requirement;
+
+        let mut devices_by_content: BTreeMap<String, (Raw<AnyToDeviceEventContent>, Vec<Device>)> =
+            BTreeMap::new();
+        let mut failures = Vec::new();
+
+        for (user_id, device_messages) in messages {
+            let user_devices = encryption.get_user_devices(&user_id).await?;
+            let owner_identity = encryption.get_user_identity(&user_id).await?;
+
+            for (recipient, content) in device_messages {
+                let devices: Vec<_> = match recipient {
+                    DeviceIdOrAllDevices::AllDevices => user_devices
+                        .devices()
+                        .filter(|device| {
+                            is_trusted_recipient(device, owner_identity.as_ref(), trust_requirement)
+                        })
+                        .collect(),
+                    DeviceIdOrAllDevices::DeviceId(device_id) => {
+                        match user_devices.get(&device_id) {
+                            Some(device)
+                                if is_trusted_recipient(
+                                    &device,
+                                    owner_identity.as_ref(),
+                                    trust_requirement,
+                                ) =>
+                            {
+                                vec![device]
+                            }
+                            Some(_) => {
+                                warn!(%user_id, %device_id, "Untrusted device, not sending the to-device message");
+                                failures.push((user_id.clone(), device_id));
+                                Vec::new()
+                            }
+                            None => {
+                                warn!(%user_id, %device_id, "Unknown device, not sending the to-device message");
+                                failures.push((user_id.clone(), device_id));
+                                Vec::new()
+                            }
+                        }
+                    }
+                };
+
+                let (_, recipients) = devices_by_content
+                    .entry(content.json().get().to_owned())
+                    .or_insert_with(|| (content, Vec::new()));
+
+                recipients.extend(devices);
+            }
+        }
+
+        for (content, devices) in devices_by_content.into_values() {
+            if devices.is_empty() {
+                continue;
+            }
+
+            let send_failures = encryption
+                .encrypt_and_send_raw_to_device(
+                    devices.iter().collect(),
+                    &event_type.to_string(),
+                    content,
+                )
+                .await?;
+
+            if !send_failures.is_empty() {
+                warn!(
+                    failures = ?send_failures,
+                    "Failed to send an encrypted to-device message to some devices"
+                );
+                failures.extend(send_failures);
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::UnknownError(Box::new(ToDeviceFailuresError(failures))))
+        }
+    }
+}
+
+/// Whether an encrypted to-device message can be sent to the given device,
+/// according to the trust requirement of the client.
+///
+/// Blacklisted devices, and devices of users with a verification violation,
+/// never receive messages. The devices of users without a cross-signing
+/// identity are considered legacy devices, as they can't be cross-signed.
+fn is_trusted_recipient(
+    device: &Device,
+    owner_identity: Option<&UserIdentity>,
+    trust_requirement: TrustRequirement,
+) -> bool {
+    if device.is_blacklisted()
+        || owner_identity.is_some_and(|identity| identity.has_verification_violation())
+    {
+        return false;
+    }
+
+    let is_cross_signed = device.is_verified() || device.is_cross_signed_by_owner();
+
+    match trust_requirement {
+        TrustRequirement::Untrusted => true,
+        TrustRequirement::CrossSignedOrLegacy => is_cross_signed || owner_identity.is_none(),
+        TrustRequirement::CrossSigned => is_cross_signed,
+    }
 }
 
 /// A simple entity that wraps an `UnboundedReceiver`
@@ -382,19 +891,68 @@ impl<T> EventReceiver<T> {
 /// handler.
 pub(crate) struct StateUpdateReceiver {
     room_updates: Receiver<RoomUpdate>,
+    capabilities: CapabilitiesFilter,
 }
 
 impl StateUpdateReceiver {
-    pub(crate) async fn recv(&mut self) -> Result<Vec<Raw<AnyStateEvent>>, RecvError> {
+    /// Receive the next state update, ordered chronologically: the events
+    /// from the state section come first, followed by the state events of the
+    /// timeline.
+    pub(crate) async fn recv(&mut self) -> Result<Vec<ForwardedEvent<AnyStateEvent>>, RecvError> {
         loop {
             match self.room_updates.recv().await? {
                 RoomUpdate::Joined { room, updates } => {
-                    if !updates.state.is_empty() {
-                        return Ok(updates
-                            .state
-                            .into_iter()
-                            .map(|ev| attach_room_id_state(&ev, room.room_id()))
-                            .collect());
+                    let room_id = room.room_id();
+
+                    let timeline_state = updates
+                        .timeline
+                        .events
+                        .iter()
+                        .map(|event| event.raw())
+                        .filter(|raw| {
+                            raw.get_field::<String>("state_key").is_ok_and(|key| key.is_some())
+                        })
+                        .collect::<Vec<_>>();
+
+                    let timeline_event_ids = timeline_state
+                        .iter()
+                        .filter_map(|raw| raw.get_field::<OwnedEventId>("event_id").ok().flatten())
+                        .collect::<BTreeSet<_>>();
+
+                    // Events of the state section which are also in the timeline are forwarded
+                    // at their position in the timeline.
+                    let mut state_event_ids = BTreeSet::new();
+                    let mut state = Vec::new();
+
+                    for raw in &updates.state {
+                        match raw.get_field::<OwnedEventId>("event_id").ok().flatten() {
+                            Some(event_id) if timeline_event_ids.contains(&event_id) => {
+                                state_event_ids.insert(event_id);
+                            }
+                            _ => state.push(ForwardedEvent::new(
+                                attach_room_id_state(raw, room_id),
+                                EventOrigin::STATE,
+                            )),
+                        }
+                    }
+
+                    for raw in timeline_state {
+                        let in_state_section = raw
+                            .get_field::<OwnedEventId>("event_id")
+                            .ok()
+                            .flatten()
+                            .is_some_and(|event_id| state_event_ids.contains(&event_id));
+
+                        state.push(ForwardedEvent::new(
+                            attach_room_id(raw, room_id).cast(),
+                            EventOrigin { timeline: true, state: in_state_section },
+                        ));
+                    }
+
+                    state.retain(|forwarded| self.capabilities.allow_reading(&forwarded.event));
+
+                    if !state.is_empty() {
+                        return Ok(state);
                     }
                 }
                 _ => {
@@ -406,23 +964,133 @@ impl StateUpdateReceiver {
     }
 }
 
+/// An error returned when an encrypted to-device message couldn't be sent to
+/// some of the devices it targets.
+#[derive(Debug, thiserror::Error)]
+#[error("the to-device message couldn't be sent to the devices: {}", format_devices(.0))]
+struct ToDeviceFailuresError(Vec<(OwnedUserId, OwnedDeviceId)>);
+
+fn format_devices(devices: &[(OwnedUserId, OwnedDeviceId)]) -> String {
+    devices
+        .iter()
+        .map(|(user_id, device_id)| format!("{user_id} ({device_id})"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// An error returned when the widget tries to use a capability it hasn't been
+/// granted.
+#[derive(Debug, thiserror::Error)]
+#[error("Not allowed: {0}")]
+struct NotAllowedError(String);
+
+fn not_allowed(reason: String) -> Error {
+    Error::UnknownError(Box::new(NotAllowedError(reason)))
+}
+
 fn attach_room_id(raw_ev: &Raw<AnySyncTimelineEvent>, room_id: &RoomId) -> Raw<AnyTimelineEvent> {
     let mut ev_obj = raw_ev.deserialize_as::<BTreeMap<String, Box<RawJsonValue>>>().unwrap();
     ev_obj.insert("room_id".to_owned(), serde_json::value::to_raw_value(room_id).unwrap());
     Raw::new(&ev_obj).unwrap().cast()
 }
 
+/// Strip the private read receipts from a receipt event, and attach the room ID
+/// to it.
+///
+/// Returns `None` if the event only contained private read receipts.
+fn public_receipts(raw: &Raw<SyncReceiptEvent>, room_id: &RoomId) -> Option<Raw<ReceiptEvent>> {
+    let mut event = match raw.deserialize_as::<BTreeMap<String, Value>>() {
+        Ok(event) => event,
+        Err(error) => {
+            warn!("Received a malformed receipt event, ignoring: {error}");
+            return None;
+        }
+    };
+
+    let Some(Value::Object(content)) = event.get_mut("content") else {
+        warn!("Received a receipt event without content, ignoring");
+        return None;
+    };
+
+    // The content maps event IDs to receipt types, which map user IDs to receipts.
+    content.retain(|_event_id, receipts| match receipts {
+        Value::Object(receipts) => {
+            receipts.remove(ReceiptType::ReadPrivate.as_str());
+            !receipts.is_empty()
+        }
+        _ => false,
+    });
+
+    if content.is_empty() {
+        return None;
+    }
+
+    event.insert("room_id".to_owned(), Value::String(room_id.to_string()));
+    Some(Raw::new(&event).ok()?.cast())
+}
+
 fn attach_room_id_state(raw_ev: &Raw<AnySyncStateEvent>, room_id: &RoomId) -> Raw<AnyStateEvent> {
     attach_room_id(raw_ev.cast_ref(), room_id).cast()
 }
 
+/// A cache of OpenID tokens, keyed by user, honoring their expiry.
+#[derive(Debug)]
+struct OpenIdTokenCache {
+    /// How long before its expiry a cached token is considered stale.
+    refresh_skew: Duration,
+    tokens: Mutex<HashMap<OwnedUserId, CachedOpenIdToken>>,
+}
+
+#[derive(Debug)]
+struct CachedOpenIdToken {
+    response: OpenIdResponse,
+    expires_at: Instant,
+}
+
+impl OpenIdTokenCache {
+    fn new(refresh_skew: Duration) -> Self {
+        Self { refresh_skew, tokens: Default::default() }
+    }
+
+    /// Get the cached token of the given user, if it's still valid for longer
+    /// than the refresh skew at `now`.
+    ///
+    /// The `expires_in` field of the returned token is the remaining validity
+    /// of the token.
+    fn get(&self, user_id: &UserId, now: Instant) -> Option<OpenIdResponse> {
+        let tokens = self.tokens.lock().unwrap();
+        let token = tokens.get(user_id)?;
+
+        let remaining = token.expires_at.checked_duration_since(now)?;
+        if remaining <= self.refresh_skew {
+            return None;
+        }
+
+        let mut response = token.response.clone();
+        response.expires_in = remaining;
+        Some(response)
+    }
+
+    /// Cache the token of the given user, received at `now`.
+    fn insert(&self, user_id: OwnedUserId, response: OpenIdResponse, now: Instant) {
+        let expires_at = now + response.expires_in;
+        self.tokens.lock().unwrap().insert(user_id, CachedOpenIdToken { response, expires_at });
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use insta;
-    use ruma::{events::AnyTimelineEvent, room_id, serde::Raw};
+    use ruma::{
+        api::client::account::request_openid_token::v3::Response as OpenIdResponse,
+        authentication::TokenType, events::AnyTimelineEvent, owned_server_name, room_id,
+        serde::Raw, time::Instant, user_id,
+    };
     use serde_json::{json, Value};
 
-    use super::attach_room_id;
+    use super::{attach_room_id, public_receipts, OpenIdTokenCache};
 
     #[test]
     fn test_add_room_id_to_raw() {
@@ -476,4 +1144,94 @@ mod tests {
         let attached: AnyTimelineEvent = new.deserialize().unwrap();
         assert_eq!(attached.room_id(), room_id);
     }
+
+    #[test]
+    fn test_public_receipts_strips_private_receipts() {
+        let room_id = room_id!("!my_id:example.org");
+        let raw = Raw::new(&json!({
+            "type": "m.receipt",
+            "content": {
+                "$public:example.org": {
+                    "m.read": {
+                        "@alice:example.org": { "ts": 1676512345 },
+                    },
+                    "m.read.private": {
+                        "@me:example.org": { "ts": 1676512345 },
+                    },
+                },
+                "$private:example.org": {
+                    "m.read.private": {
+                        "@me:example.org": { "ts": 1676512346 },
+                    },
+                },
+            },
+        }))
+        .unwrap()
+        .cast();
+
+        let receipts = public_receipts(&raw, room_id).unwrap();
+        assert_eq!(
+            receipts.deserialize_as::<Value>().unwrap(),
+            json!({
+                "type": "m.receipt",
+                "room_id": "!my_id:example.org",
+                "content": {
+                    "$public:example.org": {
+                        "m.read": {
+                            "@alice:example.org": { "ts": 1676512345 },
+                        },
+                    },
+                },
+            })
+        );
+
+        // An event which only contains private receipts isn't forwarded at all.
+        let raw = Raw::new(&json!({
+            "type": "m.receipt",
+            "content": {
+                "$private:example.org": {
+                    "m.read.private": {
+                        "@me:example.org": { "ts": 1676512346 },
+                    },
+                },
+            },
+        }))
+        .unwrap()
+        .cast();
+
+        assert!(public_receipts(&raw, room_id).is_none());
+    }
+
+    #[test]
+    fn test_openid_token_cache_honors_expiry() {
+        let alice = user_id!("@alice:example.org");
+        let cache = OpenIdTokenCache::new(Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(cache.get(alice, now).is_none());
+
+        cache.insert(
+            alice.to_owned(),
+            OpenIdResponse::new(
+                "token".to_owned(),
+                TokenType::Bearer,
+                owned_server_name!("example.org"),
+                Duration::from_secs(3600),
+            ),
+            now,
+        );
+
+        // The token is returned with its remaining validity.
+        let response = cache.get(alice, now + Duration::from_secs(600)).unwrap();
+        assert_eq!(response.access_token, "token");
+        assert_eq!(response.expires_in, Duration::from_secs(3000));
+
+        // The token isn't shared with other users.
+        assert!(cache.get(user_id!("@bob:example.org"), now).is_none());
+
+        // The token must be refreshed when it's about to expire, or when it's
+        // expired.
+        assert!(cache.get(alice, now + Duration::from_secs(3550)).is_none());
+        assert!(cache.get(alice, now + Duration::from_secs(4000)).is_none());
+    }
 }
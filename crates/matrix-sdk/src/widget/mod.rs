@@ -20,7 +20,7 @@ use std::{fmt, time::Duration};
 use async_channel::{Receiver, Sender};
 use futures_util::StreamExt;
 use matrix_sdk_common::executor::spawn;
-use ruma::api::client::delayed_events::DelayParameters;
+use ruma::{api::client::delayed_events::DelayParameters, serde::Base64};
 use serde::de::{self, Deserialize, Deserializer, Visitor};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
 use tokio_stream::wrappers::UnboundedReceiverStream;
@@ -28,8 +28,9 @@ use tokio_util::sync::{CancellationToken, DropGuard};
 
 use self::{
     machine::{
-        Action, IncomingMessage, MatrixDriverRequestData, MatrixDriverResponse, SendEventRequest,
-        WidgetMachine,
+        Action, CancelDelayedEventsResponse, DownloadFileResponse, GetDelayedEventsResponse,
+        IncomingMessage, MatrixDriverRequestData, MatrixDriverResponse, MediaConfigResponse,
+        NavigateResponse, SendEventRequest, SendTypingResponse, UploadFileResponse, WidgetMachine,
     },
     matrix::MatrixDriver,
 };
@@ -37,18 +38,27 @@ use crate::{room::Room, Result};
 
 mod capabilities;
 mod filter;
+mod get_delayed_events;
 mod machine;
 mod matrix;
+mod room_widgets;
 mod settings;
 
 pub use self::{
     capabilities::{Capabilities, CapabilitiesProvider},
     filter::{Filter, MessageLikeEventFilter, StateEventFilter, ToDeviceEventFilter},
+    machine::NavigationRequest,
+    room_widgets::{RoomWidget, RoomWidgetError, RoomWidgetEventContent, RoomWidgets},
     settings::{
         ClientProperties, EncryptionSystem, Intent, VirtualElementCallWidgetOptions, WidgetSettings,
     },
 };
 
+/// The maximum number of navigation requests of a widget waiting to be
+/// received with [`WidgetDriverHandle::recv_navigation_request`]; the next
+/// ones are rejected.
+const NAVIGATION_REQUESTS_CAPACITY: usize = 8;
+
 /// An object that handles all interactions of a widget living inside a webview
 /// or iframe with the Matrix world.
 #[derive(Debug)]
@@ -66,6 +76,10 @@ pub struct WidgetDriver {
     /// These can be both requests and responses.
     to_widget_tx: Sender<String>,
 
+    /// Navigation requests of the widget, forwarded to the client once they
+    /// have been validated.
+    navigation_tx: Sender<NavigationRequest>,
+
     /// Drop guard for an event handler forwarding all events from the Matrix
     /// room to the widget.
     ///
@@ -92,6 +106,10 @@ pub struct WidgetDriverHandle {
     /// care what's what though because they are only supposed to forward
     /// messages between the webview / iframe, and the SDK's widget driver.
     from_widget_tx: Sender<String>,
+
+    /// Navigation requests from the widget to the client, see
+    /// [`WidgetDriverHandle::recv_navigation_request`].
+    navigation_rx: Receiver<NavigationRequest>,
 }
 
 impl WidgetDriverHandle {
@@ -110,6 +128,18 @@ impl WidgetDriverHandle {
     pub async fn send(&self, message: String) -> bool {
         self.from_widget_tx.send(message).await.is_ok()
     }
+
+    /// Receive a request from the widget to navigate to a Matrix entity, like
+    /// a room or a user.
+    ///
+    /// The widget must have been granted the navigation capability, and the
+    /// URI it sent has already been validated. It's up to the client to show
+    /// the entity to the user.
+    ///
+    /// Returns `None` if the widget driver is no longer running.
+    pub async fn recv_navigation_request(&self) -> Option<NavigationRequest> {
+        self.navigation_rx.recv().await.ok()
+    }
 }
 
 impl WidgetDriver {
@@ -118,9 +148,16 @@ impl WidgetDriver {
     pub fn new(settings: WidgetSettings) -> (Self, WidgetDriverHandle) {
         let (from_widget_tx, from_widget_rx) = async_channel::unbounded();
         let (to_widget_tx, to_widget_rx) = async_channel::unbounded();
+        let (navigation_tx, navigation_rx) = async_channel::bounded(NAVIGATION_REQUESTS_CAPACITY);
 
-        let driver = Self { settings, from_widget_rx, to_widget_tx, event_forwarding_guard: None };
-        let channels = WidgetDriverHandle { from_widget_tx, to_widget_rx };
+        let driver = Self {
+            settings,
+            from_widget_rx,
+            to_widget_tx,
+            navigation_tx,
+            event_forwarding_guard: None,
+        };
+        let channels = WidgetDriverHandle { from_widget_tx, to_widget_rx, navigation_rx };
 
         (driver, channels)
     }
@@ -168,7 +205,12 @@ impl WidgetDriver {
             self.settings.init_on_content_load(),
         );
 
-        let matrix_driver = MatrixDriver::new(room.clone());
+        let matrix_driver = MatrixDriver::new(
+            room.clone(),
+            self.navigation_tx.clone(),
+            self.settings.openid_token_refresh_skew(),
+            self.settings.max_rate_limit_retries(),
+        );
 
         // Convert the incoming message receiver into a stream of actions.
         let stream = UnboundedReceiverStream::new(incoming_msg_rx)
@@ -205,6 +247,7 @@ impl WidgetDriver {
                         let obtained = capabilities_provider
                             .acquire_capabilities(cmd.desired_capabilities)
                             .await;
+                        matrix_driver.set_capabilities(obtained.clone());
                         Ok(MatrixDriverResponse::CapabilitiesAcquired(obtained))
                     }
 
@@ -242,6 +285,22 @@ impl WidgetDriver {
                         .await
                         .map(MatrixDriverResponse::DelayedEventUpdated),
 
+                    MatrixDriverRequestData::GetDelayedEvents => {
+                        matrix_driver.list_delayed_events().await.map(|delayed_events| {
+                            MatrixDriverResponse::DelayedEventsRead(GetDelayedEventsResponse {
+                                delayed_events,
+                            })
+                        })
+                    }
+
+                    MatrixDriverRequestData::CancelDelayedEvents(req) => {
+                        matrix_driver.cancel_delayed_events(req.delay_ids).await.map(|()| {
+                            MatrixDriverResponse::DelayedEventsCancelled(
+                                CancelDelayedEventsResponse {},
+                            )
+                        })
+                    }
+
                     MatrixDriverRequestData::SendToDeviceEvent(send_to_device_request) => {
                         matrix_driver
                             .send_to_device(
@@ -252,6 +311,38 @@ impl WidgetDriver {
                             .await
                             .map(MatrixDriverResponse::ToDeviceSent)
                     }
+
+                    MatrixDriverRequestData::GetMediaConfig => {
+                        matrix_driver.get_media_config().await.map(|upload_size| {
+                            MatrixDriverResponse::MediaConfigReceived(MediaConfigResponse {
+                                upload_size,
+                            })
+                        })
+                    }
+
+                    MatrixDriverRequestData::UploadFile(req) => matrix_driver
+                        .upload_media(req.file.into_inner(), req.mime_type.as_deref())
+                        .await
+                        .map(|content_uri| {
+                            MatrixDriverResponse::FileUploaded(UploadFileResponse { content_uri })
+                        }),
+
+                    MatrixDriverRequestData::DownloadFile(req) => {
+                        matrix_driver.download_media(req.content_uri).await.map(|file| {
+                            MatrixDriverResponse::FileDownloaded(DownloadFileResponse {
+                                file: Base64::new(file),
+                            })
+                        })
+                    }
+
+                    MatrixDriverRequestData::SendTyping(req) => matrix_driver
+                        .send_typing(req.typing)
+                        .await
+                        .map(|()| MatrixDriverResponse::TypingSent(SendTypingResponse {})),
+
+                    MatrixDriverRequestData::Navigate(req) => matrix_driver
+                        .navigate(req)
+                        .map(|()| MatrixDriverResponse::Navigated(NavigateResponse {})),
                 };
 
                 // Forward the Matrix driver response to the incoming message stream.
@@ -276,6 +367,9 @@ impl WidgetDriver {
                 let mut events = matrix_driver.events();
                 let mut state_updates = matrix_driver.state_updates();
                 let mut to_device_events = matrix_driver.to_device_events();
+                let mut read_receipts = matrix_driver.read_receipts();
+                let mut fully_read_marker = matrix_driver.fully_read_marker();
+                let mut typing = matrix_driver.typing();
                 let incoming_msg_tx = incoming_msg_tx.clone();
 
                 spawn(async move {
@@ -300,6 +394,21 @@ impl WidgetDriver {
                                 // Forward all events to the incoming messages stream.
                                 let _ = incoming_msg_tx.send(IncomingMessage::ToDeviceReceived(event));
                             }
+
+                            Some(receipts) = read_receipts.recv() => {
+                                let message = IncomingMessage::ReadReceiptsReceived(receipts);
+                                let _ = incoming_msg_tx.send(message);
+                            }
+
+                            Some(fully_read) = fully_read_marker.recv() => {
+                                let message = IncomingMessage::FullyReadMarkerReceived(fully_read);
+                                let _ = incoming_msg_tx.send(message);
+                            }
+
+                            Some(typing) = typing.recv() => {
+                                let message = IncomingMessage::TypingReceived(typing);
+                                let _ = incoming_msg_tx.send(message);
+                            }
                         }
                     }
                 });
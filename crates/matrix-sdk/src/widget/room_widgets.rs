@@ -0,0 +1,294 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Management of the widgets configured in a room, with the
+//! `im.vector.modular.widgets` state events.
+
+use async_stream::stream;
+use futures_core::Stream;
+use matrix_sdk_base::deserialized_responses::SyncOrStrippedState;
+use ruma::{
+    api::client::state::send_state_event,
+    events::SyncStateEvent,
+    exports::ruma_macros::EventContent,
+    serde::{JsonObject, Raw},
+    OwnedUserId,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+use url::Url;
+
+use crate::{sync::RoomUpdate, Room};
+
+/// The type of the state events describing the widgets of a room.
+const WIDGET_EVENT_TYPE: &str = "im.vector.modular.widgets";
+
+/// The content of an `im.vector.modular.widgets` state event, describing a
+/// widget of a room.
+///
+/// The state key of the event is the ID of the widget. A widget is removed
+/// from a room by replacing its state event with one with an empty content.
+#[derive(Clone, Debug, Deserialize, Serialize, EventContent)]
+#[ruma_event(type = "im.vector.modular.widgets", kind = State, state_key_type = String)]
+pub struct RoomWidgetEventContent {
+    /// The ID of the widget, which is also the state key of the event.
+    pub id: String,
+
+    /// The type of the widget, e.g. `m.custom` or `m.jitsi`.
+    #[serde(rename = "type")]
+    pub widget_type: String,
+
+    /// The URL of the widget, which may contain template variables like
+    /// `$matrix_user_id`.
+    pub url: String,
+
+    /// The human-readable name of the widget.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Widget-specific data, usually used to fill the template variables of
+    /// the URL.
+    #[serde(default, skip_serializing_if = "JsonObject::is_empty")]
+    pub data: JsonObject,
+
+    /// The ID of the user who added the widget.
+    #[serde(rename = "creatorUserId")]
+    pub creator_user_id: OwnedUserId,
+
+    /// Whether the client should wait for the widget's iframe to be loaded
+    /// before starting to communicate with it.
+    #[serde(rename = "waitForIframeLoad", default)]
+    pub wait_for_iframe_load: bool,
+}
+
+impl RoomWidgetEventContent {
+    /// Create a new `RoomWidgetEventContent` with the given ID, type, URL and
+    /// creator.
+    pub fn new(id: String, widget_type: String, url: String, creator_user_id: OwnedUserId) -> Self {
+        Self {
+            id,
+            widget_type,
+            url,
+            name: None,
+            data: JsonObject::new(),
+            creator_user_id,
+            wait_for_iframe_load: false,
+        }
+    }
+
+    /// Check that this widget can be added to a room.
+    fn validate(&self) -> Result<(), RoomWidgetError> {
+        if self.id.is_empty() {
+            return Err(RoomWidgetError::EmptyId);
+        }
+
+        if self.widget_type.is_empty() {
+            return Err(RoomWidgetError::EmptyType);
+        }
+
+        let url = Url::parse(&self.url)?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(RoomWidgetError::UnsupportedUrlScheme(url.scheme().to_owned()));
+        }
+
+        Ok(())
+    }
+}
+
+/// A widget configured in a room.
+#[derive(Clone, Debug)]
+pub struct RoomWidget {
+    /// The description of the widget.
+    pub content: RoomWidgetEventContent,
+
+    /// The user who sent the state event of the widget.
+    pub sender: OwnedUserId,
+}
+
+/// An error when managing the widgets of a room.
+#[derive(Debug, thiserror::Error)]
+pub enum RoomWidgetError {
+    /// The ID of the widget is empty.
+    #[error("the widget ID is empty")]
+    EmptyId,
+
+    /// The type of the widget is empty.
+    #[error("the widget type is empty")]
+    EmptyType,
+
+    /// The URL of the widget couldn't be parsed.
+    #[error("the widget URL is invalid: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+
+    /// The URL of the widget has a scheme that isn't `http` or `https`.
+    #[error("the widget URL scheme `{0}` isn't supported")]
+    UnsupportedUrlScheme(String),
+
+    /// The state event of the widget couldn't be read or sent.
+    #[error(transparent)]
+    Sdk(#[from] crate::Error),
+}
+
+/// A helper to group the methods in [`Room`] related to the widgets of the
+/// room.
+#[derive(Debug, Clone)]
+pub struct RoomWidgets {
+    room: Room,
+}
+
+impl RoomWidgets {
+    pub(crate) fn new(room: Room) -> Self {
+        Self { room }
+    }
+
+    /// Get the widgets currently configured in the room, sorted by ID.
+    ///
+    /// Removed widgets, and widgets whose state event can't be deserialized,
+    /// are skipped.
+    pub async fn list(&self) -> Result<Vec<RoomWidget>, RoomWidgetError> {
+        let events = self.room.get_state_events_static::<RoomWidgetEventContent>().await?;
+
+        let mut widgets = events
+            .into_iter()
+            .filter_map(|raw| match raw.deserialize() {
+                Ok(SyncOrStrippedState::Sync(SyncStateEvent::Original(event)))
+                    if event.state_key == event.content.id =>
+                {
+                    Some(RoomWidget { content: event.content, sender: event.sender })
+                }
+                // A removed widget has an empty content, which doesn't deserialize.
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        widgets.sort_by(|a, b| a.content.id.cmp(&b.content.id));
+
+        Ok(widgets)
+    }
+
+    /// Add a widget to the room, or replace the widget with the same ID.
+    ///
+    /// The widget is validated before being sent: its ID and type must not be
+    /// empty, and its URL must be a valid `http` or `https` URL.
+    pub async fn add(
+        &self,
+        widget: RoomWidgetEventContent,
+    ) -> Result<send_state_event::v3::Response, RoomWidgetError> {
+        widget.validate()?;
+
+        let widget_id = widget.id.clone();
+        Ok(self.room.send_state_event_for_key(&widget_id, widget).await?)
+    }
+
+    /// Remove the widget with the given ID from the room.
+    pub async fn remove(
+        &self,
+        widget_id: &str,
+    ) -> Result<send_state_event::v3::Response, RoomWidgetError> {
+        Ok(self.room.send_state_event_raw(WIDGET_EVENT_TYPE, widget_id, json!({})).await?)
+    }
+
+    /// Subscribe to the widgets of the room.
+    ///
+    /// The current widgets are emitted immediately, then the whole list is
+    /// emitted again whenever a sync changes a widget of the room.
+    pub fn subscribe(&self) -> impl Stream<Item = Vec<RoomWidget>> {
+        let this = self.clone();
+        let mut room_updates = self.room.subscribe_to_updates();
+
+        stream! {
+            match this.list().await {
+                Ok(widgets) => yield widgets,
+                Err(err) => warn!("Failed to get the initial widgets: {err}"),
+            }
+
+            loop {
+                let changed = match room_updates.recv().await {
+                    Ok(RoomUpdate::Joined { updates, .. }) => {
+                        updates.state.iter().any(is_widget_event)
+                            || updates
+                                .timeline
+                                .events
+                                .iter()
+                                .any(|event| is_widget_event(event.raw()))
+                    }
+                    Ok(_) => false,
+                    // We may have missed a change, so let's read the widgets again.
+                    Err(RecvError::Lagged(_)) => true,
+                    Err(RecvError::Closed) => break,
+                };
+
+                if changed {
+                    match this.list().await {
+                        Ok(widgets) => yield widgets,
+                        Err(err) => warn!("Failed to get the updated widgets: {err}"),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Whether the given event is a state event describing a widget.
+fn is_widget_event<T>(raw: &Raw<T>) -> bool {
+    raw.get_field::<String>("type").ok().flatten().is_some_and(|ty| ty == WIDGET_EVENT_TYPE)
+        && raw.get_field::<String>("state_key").is_ok_and(|key| key.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches2::assert_matches;
+    use ruma::owned_user_id;
+
+    use super::{RoomWidgetError, RoomWidgetEventContent};
+
+    fn widget(url: &str) -> RoomWidgetEventContent {
+        RoomWidgetEventContent::new(
+            "widget".to_owned(),
+            "m.custom".to_owned(),
+            url.to_owned(),
+            owned_user_id!("@alice:example.org"),
+        )
+    }
+
+    #[test]
+    fn test_validate_widget() {
+        widget("https://example.org/widget?user=$matrix_user_id").validate().unwrap();
+
+        assert_matches!(widget("not a url").validate(), Err(RoomWidgetError::InvalidUrl(_)));
+        assert_matches!(
+            widget("javascript:alert(1)").validate(),
+            Err(RoomWidgetError::UnsupportedUrlScheme(scheme))
+        );
+        assert_eq!(scheme, "javascript");
+
+        let mut content = widget("https://example.org");
+        content.id.clear();
+        assert_matches!(content.validate(), Err(RoomWidgetError::EmptyId));
+    }
+
+    #[test]
+    fn test_serialize_widget() {
+        let mut content = widget("https://example.org");
+        content.name = Some("Widget".to_owned());
+
+        let json = serde_json::to_value(&content).unwrap();
+        assert_eq!(json["type"], "m.custom");
+        assert_eq!(json["creatorUserId"], "@alice:example.org");
+        assert_eq!(json["waitForIframeLoad"], false);
+        assert!(json.get("data").is_none());
+    }
+}
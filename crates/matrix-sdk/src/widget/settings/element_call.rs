@@ -299,7 +299,13 @@ impl WidgetSettings {
         raw_url.set_fragment(Some(&format!("?{query}")));
 
         // for EC we always want init on content load to be true.
-        Ok(Self { widget_id: props.widget_id, init_on_content_load: true, raw_url })
+        Ok(Self {
+            widget_id: props.widget_id,
+            init_on_content_load: true,
+            raw_url,
+            openid_token_refresh_skew: super::DEFAULT_OPENID_TOKEN_REFRESH_SKEW,
+            max_rate_limit_retries: super::DEFAULT_MAX_RATE_LIMIT_RETRIES,
+        })
     }
 }
 
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::Duration;
+
 use language_tags::LanguageTag;
 use ruma::{api::client::profile::get_profile, DeviceId, RoomId, UserId};
 use url::Url;
@@ -23,12 +25,20 @@ mod url_params;
 
 pub use self::element_call::{EncryptionSystem, Intent, VirtualElementCallWidgetOptions};
 
+/// How long before its expiry a cached OpenID token is refreshed, by default.
+const DEFAULT_OPENID_TOKEN_REFRESH_SKEW: Duration = Duration::from_secs(60);
+
+/// How many times a rate-limited send is retried, by default.
+const DEFAULT_MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
 /// Settings of the widget.
 #[derive(Debug, Clone)]
 pub struct WidgetSettings {
     widget_id: String,
     init_on_content_load: bool,
     raw_url: Url,
+    openid_token_refresh_skew: Duration,
+    max_rate_limit_retries: u32,
 }
 
 impl WidgetSettings {
@@ -38,7 +48,47 @@ impl WidgetSettings {
         init_on_content_load: bool,
         raw_url: &str,
     ) -> Result<Self, url::ParseError> {
-        Ok(Self { widget_id: id, init_on_content_load, raw_url: Url::parse(raw_url)? })
+        Ok(Self {
+            widget_id: id,
+            init_on_content_load,
+            raw_url: Url::parse(raw_url)?,
+            openid_token_refresh_skew: DEFAULT_OPENID_TOKEN_REFRESH_SKEW,
+            max_rate_limit_retries: DEFAULT_MAX_RATE_LIMIT_RETRIES,
+        })
+    }
+
+    /// Set how long before its expiry a cached OpenID token is refreshed.
+    ///
+    /// The OpenID tokens requested by the widget are cached, and reused
+    /// until they're about to expire. Defaults to one minute.
+    pub fn with_openid_token_refresh_skew(mut self, skew: Duration) -> Self {
+        self.openid_token_refresh_skew = skew;
+        self
+    }
+
+    /// How long before its expiry a cached OpenID token is refreshed.
+    pub fn openid_token_refresh_skew(&self) -> Duration {
+        self.openid_token_refresh_skew
+    }
+
+    /// Set how many times an event sent by the widget is retried when the
+    /// homeserver rate-limits it.
+    ///
+    /// The send is retried after the delay requested by the homeserver. Once
+    /// the retries are exhausted, the rate limit is reported to the widget.
+    /// Defaults to 3, and 0 disables the retries.
+    ///
+    /// The retries are done by the HTTP client, so this limit also applies to
+    /// the other transient failures of the send, like server errors, or
+    /// network errors, which are retried with an exponential backoff.
+    pub fn with_max_rate_limit_retries(mut self, max_retries: u32) -> Self {
+        self.max_rate_limit_retries = max_retries;
+        self
+    }
+
+    /// How many times a rate-limited event sent by the widget is retried.
+    pub fn max_rate_limit_retries(&self) -> u32 {
+        self.max_rate_limit_retries
     }
 
     /// Widget's unique identifier.
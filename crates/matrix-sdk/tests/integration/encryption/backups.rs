@@ -12,7 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{fs::File, io::Write, sync::Arc, time::Duration};
+use std::{
+    fs::File,
+    io::Write,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use anyhow::Result;
 use assert_matches::assert_matches;
@@ -26,7 +31,11 @@ use matrix_sdk::{
         types::EventEncryptionAlgorithm,
     },
     encryption::{
-        backups::{futures::SteadyStateError, BackupState, UploadState},
+        backups::{
+            futures::{SteadyStateError, UpgradeAlgorithmError},
+            BackupDownloadSettings, BackupState, UploadState,
+            PREVIOUS_BACKUP_RECOVERY_KEY_SECRET_NAME,
+        },
         secret_storage::SecretStore,
         BackupDownloadStrategy, EncryptionSettings,
     },
@@ -38,7 +47,7 @@ use matrix_sdk::{
 };
 use matrix_sdk_base::crypto::olm::OutboundGroupSession;
 use matrix_sdk_common::timeout::timeout;
-use matrix_sdk_test::{async_test, JoinedRoomBuilder, SyncResponseBuilder};
+use matrix_sdk_test::{async_test, JoinedRoomBuilder, LeftRoomBuilder, SyncResponseBuilder};
 use ruma::{
     api::client::room::create_room::v3::Request as CreateRoomRequest,
     assign, device_id, event_id,
@@ -47,7 +56,7 @@ use ruma::{
 };
 use serde_json::{json, Value};
 use tempfile::tempdir;
-use tokio::spawn;
+use tokio::{spawn, time::sleep};
 use vodozemac::{
     olm::IdentityKeys, Curve25519PublicKey, Curve25519SecretKey, Ed25519PublicKey, Ed25519SecretKey,
 };
@@ -1263,6 +1272,75 @@ async fn test_enable_from_secret_storage_and_manual_download() {
     server.verify().await;
 }
 
+#[async_test]
+async fn test_download_all_room_keys_in_batches() {
+    let first_room_id = room_id!("!DovneieKSTkdHKpIXy:morpheus.localhost");
+    let second_room_id = room_id!("!SVkFJHzfwvuaIEawgC:localhost");
+    let left_room_id = room_id!("!left:localhost");
+
+    let session = matrix_session_example2();
+    let (builder, server) = test_client_builder_with_server().await;
+    let client =
+        builder.request_config(RequestConfig::new().disable_retry()).build().await.unwrap();
+
+    client.restore_session(session).await.unwrap();
+
+    let sync = SyncResponseBuilder::new()
+        .add_joined_room(JoinedRoomBuilder::new(first_room_id))
+        .add_joined_room(JoinedRoomBuilder::new(second_room_id))
+        .add_left_room(LeftRoomBuilder::new(left_room_id))
+        .build_json_sync_response();
+    mock_sync(&server, sync, None).await;
+
+    client.sync_once(Default::default()).await.expect("We should be able to sync with the server");
+
+    init_client_secret_storage_and_backup(&client, &server).await;
+
+    // The room keys of each room, including the left ones, are downloaded once per
+    // complete download.
+    for room_id in [first_room_id, second_room_id, left_room_id] {
+        Mock::given(method("GET"))
+            .and(path(format!("/_matrix/client/r0/room_keys/keys/{room_id}")))
+            .and(header("authorization", "Bearer 1234"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "sessions": {} })))
+            .expect(2)
+            .mount(&server)
+            .await;
+    }
+
+    let settings = BackupDownloadSettings { batch_size: 1, batch_delay: Duration::ZERO };
+    let backups = client.encryption().backups();
+
+    // While the download is paused, nothing happens.
+    backups.pause_download();
+
+    let download_task = spawn({
+        let client = client.clone();
+        async move { client.encryption().backups().download_all_room_keys_in_batches(settings).await }
+    });
+
+    sleep(Duration::from_millis(100)).await;
+    assert!(!download_task.is_finished());
+
+    // Once resumed, the room keys of all the rooms are downloaded.
+    backups.resume_download();
+
+    timeout(download_task, Duration::from_secs(1))
+        .await
+        .expect("The download should finish once resumed")
+        .unwrap()
+        .expect("We should be able to download the room keys in batches");
+
+    // The previous download completed, so its progress has been cleared and
+    // downloading again fetches the room keys of all the rooms again.
+    backups
+        .download_all_room_keys_in_batches(settings)
+        .await
+        .expect("We should be able to download the room keys in batches again");
+
+    server.verify().await;
+}
+
 #[async_test]
 async fn test_enable_from_secret_storage_and_download_after_utd() {
     let room_id = room_id!("!DovneieKSTkdHKpIXy:morpheus.localhost");
@@ -1498,6 +1576,179 @@ async fn test_enable_from_secret_storage_and_download_after_utd_from_old_message
     server.verify().await;
 }
 
+#[async_test]
+async fn test_upgrade_algorithm() {
+    let session = matrix_session_example();
+    let (client, server) = no_retry_test_client_with_server().await;
+    client.restore_session(session).await.unwrap();
+
+    let secret_store = init_secret_store(&client, &server).await;
+
+    Mock::given(method("GET"))
+        .and(path("_matrix/client/r0/room_keys/version"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "algorithm": "org.example.deprecated_backup_algorithm",
+            "auth_data": {},
+            "count": 42,
+            "etag": "anopaquestring",
+            "version": "1",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    mount_and_assert_called_once(
+        &server,
+        "POST",
+        "_matrix/client/unstable/room_keys/version",
+        ResponseTemplate::new(200).set_body_json(json!({ "version": "2" })),
+    )
+    .await;
+
+    // The backup recovery key of the new backup version is stored in the secret
+    // storage, and the one of the old backup version is moved to another secret…
+    mock_upgrade_secret_storage(&server).await;
+
+    // …since the old backup version is kept, as it may contain room keys this
+    // device doesn't have.
+    Mock::given(method("DELETE"))
+        .and(path("_matrix/client/r0/room_keys/version/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let backups = client.encryption().backups();
+    let upgraded = backups
+        .upgrade_algorithm(&secret_store)
+        .await
+        .expect("We should be able to upgrade the backup algorithm");
+
+    assert!(upgraded);
+    assert_eq!(backups.state(), BackupState::Enabled);
+
+    server.verify().await;
+}
+
+#[async_test]
+async fn test_upgrade_algorithm_with_upload_failure() {
+    let session = matrix_session_example();
+    let (client, server) = no_retry_test_client_with_server().await;
+    client.restore_session(session).await.unwrap();
+
+    let secret_store = init_secret_store(&client, &server).await;
+
+    // Import a room key, to have something to upload to the new backup version.
+    let dir = tempdir().unwrap();
+    let room_key_path = dir.path().join("room_key.txt");
+    File::create(&room_key_path).unwrap().write_all(ROOM_KEY).unwrap();
+    client.encryption().import_room_keys(room_key_path, "1234").await.unwrap();
+
+    Mock::given(method("GET"))
+        .and(path("_matrix/client/r0/room_keys/version"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "algorithm": "org.example.deprecated_backup_algorithm",
+            "auth_data": {},
+            "count": 42,
+            "etag": "anopaquestring",
+            "version": "1",
+        })))
+        .mount(&server)
+        .await;
+
+    mount_and_assert_called_once(
+        &server,
+        "POST",
+        "_matrix/client/unstable/room_keys/version",
+        ResponseTemplate::new(200).set_body_json(json!({ "version": "2" })),
+    )
+    .await;
+
+    mock_upgrade_secret_storage(&server).await;
+
+    // The upload of the room keys fails halfway through.
+    Mock::given(method("PUT"))
+        .and(path("_matrix/client/unstable/room_keys/keys"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+
+    let backups = client.encryption().backups();
+    let result = backups.upgrade_algorithm(&secret_store).await;
+
+    assert_matches!(result, Err(UpgradeAlgorithmError::Upload(SteadyStateError::Connection)));
+
+    // The new backup version is still the current one, and its backup recovery
+    // key has already been stored in the secret storage, so the backup stays
+    // readable from other devices while the upload is retried.
+    assert_eq!(backups.state(), BackupState::Enabled);
+
+    server.verify().await;
+}
+
+/// Mock the requests to move the backup recovery key of the old backup version
+/// to another secret, and to store the new one, when upgrading the algorithm of
+/// the backup.
+async fn mock_upgrade_secret_storage(server: &wiremock::MockServer) {
+    let previous_key_path = format!(
+        "_matrix/client/r0/user/@example:morpheus.localhost/account_data/{PREVIOUS_BACKUP_RECOVERY_KEY_SECRET_NAME}"
+    );
+
+    Mock::given(method("GET"))
+        .and(path(&previous_key_path))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_NOT_FOUND",
+            "error": "Account data not found"
+        })))
+        .mount(server)
+        .await;
+
+    mount_and_assert_called_once(
+        server,
+        "PUT",
+        &previous_key_path,
+        ResponseTemplate::new(200).set_body_json(json!({})),
+    )
+    .await;
+
+    mount_and_assert_called_once(
+        server,
+        "PUT",
+        "_matrix/client/r0/user/@example:morpheus.localhost/account_data/m.megolm_backup.v1",
+        ResponseTemplate::new(200).set_body_json(json!({})),
+    )
+    .await;
+}
+
+#[async_test]
+async fn test_upgrade_algorithm_is_noop_with_the_latest_algorithm() {
+    let session = matrix_session_example();
+    let (client, server) = no_retry_test_client_with_server().await;
+    client.restore_session(session).await.unwrap();
+
+    let secret_store = init_secret_store(&client, &server).await;
+    mock_query_key_backup(&server).await;
+
+    Mock::given(method("POST"))
+        .and(path("_matrix/client/unstable/room_keys/version"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "version": "7" })))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let upgraded = client
+        .encryption()
+        .backups()
+        .upgrade_algorithm(&secret_store)
+        .await
+        .expect("We should be able to check the backup algorithm");
+
+    assert!(!upgraded);
+
+    server.verify().await;
+}
+
 /// Set up secret storage, and allow the client to import the backup
 /// decryption key from 4S.
 async fn init_client_secret_storage_and_backup(client: &Client, server: &wiremock::MockServer) {
@@ -1612,3 +1863,47 @@ async fn mock_download_session_from_key_backup(
         .mount(server)
         .await;
 }
+
+#[async_test]
+async fn test_room_keys_import_with_progress_and_export() {
+    let (client, _server) = no_retry_test_client_with_server().await;
+
+    let dir = tempdir().unwrap();
+    let import_path = dir.path().join("room_key.txt");
+    File::create(&import_path).unwrap().write_all(ROOM_KEY).unwrap();
+
+    // A wrong passphrase is rejected.
+    client.encryption().import_room_keys(import_path.clone(), "wrong").await.unwrap_err();
+
+    let progress = Mutex::new(Vec::new());
+    let result = client
+        .encryption()
+        .import_room_keys_with_progress(import_path.clone(), "1234", |processed, total| {
+            progress.lock().unwrap().push((processed, total));
+        })
+        .await
+        .unwrap();
+
+    assert!(result.total_count > 0);
+    assert_eq!(result.imported_count, result.total_count);
+
+    // The listener is called once per room key.
+    let expected_progress = (0..result.total_count)
+        .map(|processed| (processed, result.total_count))
+        .collect::<Vec<_>>();
+    assert_eq!(progress.into_inner().unwrap(), expected_progress);
+
+    // Importing the same keys again doesn't import anything new.
+    let again = client.encryption().import_room_keys(import_path, "1234").await.unwrap();
+    assert_eq!(again.imported_count, 0);
+    assert_eq!(again.total_count, result.total_count);
+
+    // The exported keys can be imported by another client.
+    let export_path = dir.path().join("exported_room_keys.txt");
+    client.encryption().export_room_keys(export_path.clone(), "secret", |_| true).await.unwrap();
+
+    let (other_client, _other_server) = no_retry_test_client_with_server().await;
+    let imported = other_client.encryption().import_room_keys(export_path, "secret").await.unwrap();
+    assert_eq!(imported.imported_count, result.total_count);
+    assert_eq!(imported.total_count, result.total_count);
+}
@@ -16,6 +16,11 @@ use assert_matches2::assert_let;
 use matrix_sdk::{encryption::CrossSigningResetAuthType, test_utils::mocks::MatrixMockServer};
 use matrix_sdk_test::async_test;
 use ruma::api::client::uiaa;
+use serde_json::json;
+use wiremock::{
+    matchers::{body_partial_json, method, path_regex},
+    Mock, ResponseTemplate,
+};
 
 #[async_test]
 async fn test_reset_legacy_auth() {
@@ -97,6 +102,63 @@ async fn test_reset_legacy_auth_invalid_password() {
         .expect_err("Resetting with the wrong password should return the error");
 }
 
+#[async_test]
+async fn test_reset_legacy_auth_with_fallback() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    server.mock_upload_keys().ok().mock_once().mount().await;
+
+    // The homeserver only allows to authenticate with SSO, on its fallback web
+    // page.
+    let reset_handle = {
+        let _guard = Mock::given(method("POST"))
+            .and(path_regex(r"^/_matrix/client/v3/keys/device_signing/upload"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+                "flows": [{ "stages": ["m.login.sso"] }],
+                "params": {},
+                "session": "sso_session",
+            })))
+            .expect(1)
+            .mount_as_scoped(server.server())
+            .await;
+
+        client
+            .encryption()
+            .reset_cross_signing()
+            .await
+            .unwrap()
+            .expect("We should have received a reset handle")
+    };
+
+    assert_let!(CrossSigningResetAuthType::Uiaa(uiaa_info) = reset_handle.auth_type());
+    assert_eq!(uiaa_info.flows[0].stages, [uiaa::AuthType::Sso]);
+    let session = uiaa_info.session.clone().unwrap();
+
+    // Once the stage has been completed, the upload is acknowledged with the
+    // session alone.
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/v3/keys/device_signing/upload"))
+        .and(body_partial_json(json!({ "auth": { "session": "sso_session" } })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(server.server())
+        .await;
+    server.mock_upload_cross_signing_signatures().ok().expect(1).mount().await;
+
+    reset_handle
+        .auth(Some(uiaa::AuthData::FallbackAcknowledgement(uiaa::FallbackAcknowledgement::new(
+            session,
+        ))))
+        .await
+        .expect("We should be able to reset the cross-signing keys after the fallback stage");
+
+    assert!(
+        client.encryption().cross_signing_status().await.unwrap().is_complete(),
+        "After the reset we have the cross-signing available.",
+    );
+}
+
 #[async_test]
 async fn test_reset_oauth() {
     use assert_matches2::assert_let;
@@ -1,7 +1,10 @@
 use assert_matches2::assert_matches;
-use futures_util::FutureExt;
+use futures_util::{FutureExt, StreamExt};
 use matrix_sdk::{
-    encryption::VerificationState,
+    encryption::{
+        verification::VerificationRequestState, DetailedVerificationState, UnverifiedReason,
+        VerificationState,
+    },
     test_utils::{logged_in_client_with_server, mocks::MatrixMockServer},
     Client,
 };
@@ -142,6 +145,116 @@ async fn test_reset_cross_signing_resets_verification() {
     );
 }
 
+#[async_test]
+async fn test_detailed_verification_state() {
+    let server = MatrixMockServer::new().await;
+    server.mock_crypto_endpoints_preset().await;
+
+    let user_id = owned_user_id!("@alice:example.org");
+    let device_id = owned_device_id!("4L1C3");
+    let alice = server.client_builder_for_crypto_end_to_end(&user_id, &device_id).build().await;
+
+    // Have Alice bootstrap cross-signing, and run a keys query to pick up the
+    // cross-signing state.
+    bootstrap_cross_signing(&alice).await;
+    server
+        .mock_sync()
+        .ok_and_run(&alice, |builder| {
+            builder.add_change_device(&user_id);
+        })
+        .await;
+
+    // The device is signed by the user identity.
+    assert_eq!(
+        alice.encryption().detailed_verification_state().get(),
+        DetailedVerificationState::Verified
+    );
+
+    // A second device of Alice isn't signed by the user identity.
+    let device_id = owned_device_id!("AliceDevice2");
+    let alice2 = server.client_builder_for_crypto_end_to_end(&user_id, &device_id).build().await;
+    server
+        .mock_sync()
+        .ok_and_run(&alice2, |builder| {
+            builder.add_change_device(&user_id);
+        })
+        .await;
+
+    assert_eq!(
+        alice2.encryption().detailed_verification_state().get(),
+        DetailedVerificationState::Unverified { reason: UnverifiedReason::MissingSignature }
+    );
+
+    // Alice resets cross-signing on her second device.
+    bootstrap_cross_signing(&alice2).await;
+    server
+        .mock_sync()
+        .ok_and_run(&alice, |builder| {
+            builder.add_change_device(&user_id);
+        })
+        .await;
+
+    // The first device was verified with the previous user identity.
+    assert_eq!(
+        alice.encryption().detailed_verification_state().get(),
+        DetailedVerificationState::Unverified { reason: UnverifiedReason::NewIdentity }
+    );
+    assert_eq!(alice.encryption().verification_state().get(), VerificationState::Unverified);
+}
+
+#[async_test]
+async fn test_concurrent_own_verification_requests() {
+    let server = MatrixMockServer::new().await;
+    server.mock_crypto_endpoints_preset().await;
+    server.mock_send_to_device().ok().mount().await;
+
+    let user_id = owned_user_id!("@alice:example.org");
+    let device_id = owned_device_id!("4L1C3");
+    let alice = server.client_builder_for_crypto_end_to_end(&user_id, &device_id).build().await;
+
+    bootstrap_cross_signing(&alice).await;
+
+    let own_identity = alice.encryption().get_user_identity(&user_id).await.unwrap().unwrap();
+
+    // Start two verification flows of the own devices at the same time.
+    let first = own_identity.request_verification().await.unwrap();
+    let second = own_identity.request_verification().await.unwrap();
+
+    // Each flow has its own ID, under which it's tracked.
+    assert_ne!(first.flow_id(), second.flow_id());
+
+    for request in [&first, &second] {
+        let found = alice
+            .encryption()
+            .get_verification_request(&user_id, request.flow_id())
+            .await
+            .expect("the verification request should be tracked");
+        assert_eq!(found.flow_id(), request.flow_id());
+    }
+
+    let mut first_changes = first.changes();
+    let mut second_changes = second.changes();
+
+    // Cancelling one flow doesn't affect the other one.
+    first.cancel().await.unwrap();
+
+    assert!(first.is_cancelled());
+    assert_matches!(
+        first_changes.next().now_or_never().flatten(),
+        Some(VerificationRequestState::Cancelled(_))
+    );
+
+    assert!(!second.is_cancelled());
+    assert!(second_changes.next().now_or_never().is_none());
+
+    let second = alice
+        .encryption()
+        .get_verification_request(&user_id, second.flow_id())
+        .await
+        .expect("the other verification request should still be tracked");
+    assert!(!second.is_cancelled());
+}
+
 #[async_test]
 async fn test_unchecked_mutual_verification() {
     let server = MatrixMockServer::new().await;
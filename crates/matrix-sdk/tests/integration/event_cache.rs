@@ -9,17 +9,20 @@ use matrix_sdk::{
     assert_let_timeout, assert_next_matches_with_timeout,
     deserialized_responses::TimelineEvent,
     event_cache::{
-        BackPaginationOutcome, EventCacheError, RoomEventCacheUpdate, RoomPaginationStatus,
+        BackPaginationOutcome, DebugChunkContent, EventCacheError, EventsOrigin, PrefetchConfig,
+        RoomEventCacheSemanticUpdate, RoomEventCacheUpdate, RoomPaginationStatus,
     },
     linked_chunk::{ChunkIdentifier, LinkedChunkId, Position, Update},
+    media::{MediaFormat, MediaRequestParameters},
+    room::IncludeRelations,
     store::StoreConfig,
     test_utils::{
         assert_event_matches_msg,
-        mocks::{MatrixMockServer, RoomMessagesResponseTemplate},
+        mocks::{MatrixMockServer, RoomMessagesResponseTemplate, RoomRelationsResponseTemplate},
     },
 };
 use matrix_sdk_base::event_cache::{
-    store::{EventCacheStore, MemoryStore},
+    store::{media::IgnoreMediaRetentionPolicy, EventCacheStore, MemoryStore},
     Gap,
 };
 use matrix_sdk_test::{
@@ -28,11 +31,15 @@ use matrix_sdk_test::{
 };
 use ruma::{
     event_id,
-    events::{AnySyncMessageLikeEvent, AnySyncTimelineEvent, TimelineEventType},
-    room_id, user_id, EventId, RoomVersionId,
+    events::{
+        relation::RelationType, room::MediaSource, AnySyncMessageLikeEvent, AnySyncTimelineEvent,
+        TimelineEventType,
+    },
+    owned_mxc_uri, room_id, user_id, EventId, RoomVersionId,
 };
 use serde_json::json;
 use tokio::{spawn, sync::broadcast, time::sleep};
+use wiremock::ResponseTemplate;
 
 macro_rules! assert_event_id {
     ($timeline_event:expr, $event_id:literal) => {
@@ -284,7 +291,7 @@ async fn test_backpaginate_once() {
     };
 
     // I'll get all the previous events, in "reverse" order (same as the response).
-    let BackPaginationOutcome { events, reached_start } = outcome;
+    let BackPaginationOutcome { events, reached_start, .. } = outcome;
 
     // The event cache figures this is the last chunk of events in the room, because
     // there's no prior gap this time.
@@ -720,7 +727,7 @@ async fn test_backpaginating_without_token() {
 
     // If we try to back-paginate with a token, it will hit the end of the timeline
     // and give us the resulting event.
-    let BackPaginationOutcome { events, reached_start } =
+    let BackPaginationOutcome { events, reached_start, .. } =
         pagination.run_backwards_once(20).await.unwrap();
 
     assert!(reached_start);
@@ -741,6 +748,293 @@ async fn test_backpaginating_without_token() {
     assert!(room_stream.is_empty());
 }
 
+#[async_test]
+async fn test_backpaginate_with_unavailable_history() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    client.event_cache().subscribe().unwrap();
+
+    let room_id = room_id!("!omelette:fromage.fr");
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).set_timeline_prev_batch("prev_batch".to_owned()),
+        )
+        .await;
+
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+    // The server refuses to return the older events, e.g. because they've been
+    // purged by a retention policy.
+    server
+        .mock_room_messages()
+        .match_from("prev_batch")
+        .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+            "errcode": "M_FORBIDDEN",
+            "error": "History is unavailable",
+        })))
+        .mock_once()
+        .mount()
+        .await;
+
+    let pagination = room_event_cache.pagination();
+    let outcome = pagination.run_backwards_once(20).await.unwrap();
+
+    // The back-pagination doesn't fail, but indicates that the start of the
+    // readable history has been reached.
+    assert!(outcome.reached_start);
+    assert!(outcome.history_unavailable);
+    assert!(outcome.events.is_empty());
+
+    assert_eq!(pagination.status().get(), RoomPaginationStatus::Idle { hit_timeline_start: true });
+}
+
+#[async_test]
+async fn test_backpaginate_with_unrelated_not_found_error() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    client.event_cache().subscribe().unwrap();
+
+    let room_id = room_id!("!omelette:fromage.fr");
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).set_timeline_prev_batch("prev_batch".to_owned()),
+        )
+        .await;
+
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+    // A 404 that isn't about the history, e.g. a misconfigured reverse proxy.
+    server
+        .mock_room_messages()
+        .match_from("prev_batch")
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_UNRECOGNIZED",
+            "error": "Unrecognized request",
+        })))
+        .mock_once()
+        .mount()
+        .await;
+
+    let pagination = room_event_cache.pagination();
+
+    // The back-pagination fails, instead of claiming the start of the timeline.
+    pagination.run_backwards_once(20).await.unwrap_err();
+    assert_eq!(pagination.status().get(), RoomPaginationStatus::Idle { hit_timeline_start: false });
+}
+
+#[async_test]
+async fn test_backpaginate_reanchors_gap_with_rejected_token() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    client.event_cache().subscribe().unwrap();
+
+    let room_id = room_id!("!omelette:fromage.fr");
+    let f = EventFactory::new().room(room_id).sender(user_id!("@a:b.c"));
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("hello").event_id(event_id!("$1")))
+                .set_timeline_prev_batch("expired".to_owned()),
+        )
+        .await;
+
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+    // The server doesn't know about the token anymore.
+    server
+        .mock_room_messages()
+        .match_from("expired")
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "errcode": "M_INVALID_PARAM",
+            "error": "Unknown pagination token",
+        })))
+        .mock_once()
+        .mount()
+        .await;
+
+    // A new token is obtained from the event after the gap,
+    server
+        .mock_room_event_context()
+        .match_event_id()
+        .ok(f.text_msg("hello").event_id(event_id!("$1")).into_event(), "fresh", "end")
+        .mock_once()
+        .mount()
+        .await;
+
+    // And the back-pagination goes on from it.
+    server
+        .mock_room_messages()
+        .match_from("fresh")
+        .ok(RoomMessagesResponseTemplate::default()
+            .events(vec![f.text_msg("before").event_id(event_id!("$0")).into_raw_timeline()])
+            .end_token("older"))
+        .mock_once()
+        .mount()
+        .await;
+
+    let pagination = room_event_cache.pagination();
+    let outcome = pagination.run_backwards_once(20).await.unwrap();
+
+    assert!(!outcome.reached_start);
+    assert!(!outcome.history_unavailable);
+    assert_eq!(outcome.events.len(), 1);
+    assert_event_matches_msg(&outcome.events[0], "before");
+
+    assert_eq!(pagination.status().get(), RoomPaginationStatus::Idle { hit_timeline_start: false });
+
+    // The older history is still reachable, from the new gap.
+    assert!(room_event_cache.debug_chunks().await.iter().any(|chunk| matches!(
+        &chunk.content,
+        DebugChunkContent::Gap { prev_token } if prev_token == "older"
+    )));
+}
+
+#[async_test]
+async fn test_backpaginate_keeps_gap_with_rejected_token_without_context() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    client.event_cache().subscribe().unwrap();
+
+    let room_id = room_id!("!omelette:fromage.fr");
+    let f = EventFactory::new().room(room_id).sender(user_id!("@a:b.c"));
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("hello").event_id(event_id!("$1")))
+                .set_timeline_prev_batch("expired".to_owned()),
+        )
+        .await;
+
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+    server
+        .mock_room_messages()
+        .match_from("expired")
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "errcode": "M_INVALID_PARAM",
+            "error": "Unknown pagination token",
+        })))
+        .mock_once()
+        .mount()
+        .await;
+
+    // The gap can't be re-anchored.
+    server
+        .mock_room_event_context()
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_NOT_FOUND",
+            "error": "Event not found",
+        })))
+        .mock_once()
+        .mount()
+        .await;
+
+    let outcome = room_event_cache.pagination().run_backwards_once(20).await.unwrap();
+
+    // The older history is reported as unavailable, and the gap is kept.
+    assert!(outcome.history_unavailable);
+    assert!(outcome.events.is_empty());
+    assert!(room_event_cache
+        .debug_chunks()
+        .await
+        .iter()
+        .any(|chunk| matches!(chunk.content, DebugChunkContent::Gap { .. })));
+}
+
+#[async_test]
+async fn test_backpaginate_from_primed_event() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!omelette:fromage.fr");
+    let f = EventFactory::new().room(room_id).sender(user_id!("@a:b.c"));
+
+    // A notification process saves an event.
+    client
+        .event_cache()
+        .save_notification_event(room_id, f.text_msg("hello").event_id(event_id!("$1")).into())
+        .await
+        .unwrap();
+
+    client.event_cache().subscribe().unwrap();
+
+    let room = server.sync_joined_room(&client, room_id).await;
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+    // The history before the primed event is reached with a token obtained from it.
+    server
+        .mock_room_event_context()
+        .match_event_id()
+        .ok(f.text_msg("hello").event_id(event_id!("$1")).into_event(), "fresh", "end")
+        .mock_once()
+        .mount()
+        .await;
+    server
+        .mock_room_messages()
+        .match_from("fresh")
+        .ok(RoomMessagesResponseTemplate::default()
+            .events(vec![f.text_msg("before").event_id(event_id!("$0")).into_raw_timeline()])
+            .end_token("older"))
+        .mock_once()
+        .mount()
+        .await;
+
+    let outcome = room_event_cache.pagination().run_backwards_once(20).await.unwrap();
+
+    assert!(!outcome.reached_start);
+    assert_eq!(outcome.events.len(), 1);
+    assert_event_matches_msg(&outcome.events[0], "before");
+
+    let events = room_event_cache.events().await;
+    assert_eq!(events.len(), 2);
+    assert_event_matches_msg(&events[0], "before");
+    assert_event_matches_msg(&events[1], "hello");
+}
+
+#[async_test]
+async fn test_backpaginate_with_unrelated_invalid_param_error() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    client.event_cache().subscribe().unwrap();
+
+    let room_id = room_id!("!omelette:fromage.fr");
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).set_timeline_prev_batch("prev_batch".to_owned()),
+        )
+        .await;
+
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+    // A 400 that isn't about the token.
+    server
+        .mock_room_messages()
+        .match_from("prev_batch")
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "errcode": "M_INVALID_PARAM",
+            "error": "Invalid filter",
+        })))
+        .mock_once()
+        .mount()
+        .await;
+
+    let pagination = room_event_cache.pagination();
+
+    // The back-pagination fails, and the gap isn't touched.
+    pagination.run_backwards_once(20).await.unwrap_err();
+    assert_eq!(pagination.status().get(), RoomPaginationStatus::Idle { hit_timeline_start: false });
+}
+
 #[async_test]
 async fn test_limited_timeline_resets_pagination() {
     let server = MatrixMockServer::new().await;
@@ -779,7 +1073,7 @@ async fn test_limited_timeline_resets_pagination() {
 
     // If we try to back-paginate with a token, it will hit the end of the timeline
     // and give us the resulting event.
-    let BackPaginationOutcome { events, reached_start } =
+    let BackPaginationOutcome { events, reached_start, .. } =
         pagination.run_backwards_once(20).await.unwrap();
 
     assert_eq!(events.len(), 1);
@@ -1451,6 +1745,88 @@ async fn test_apply_redaction_when_redaction_comes_later() {
     );
 }
 
+#[async_test]
+async fn test_redaction_removes_media() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let event_cache = client.event_cache();
+    event_cache.subscribe().unwrap();
+
+    let mut redacted_media = event_cache.subscribe_to_redacted_media();
+
+    let room_id = room_id!("!omelette:fromage.fr");
+    let f = EventFactory::new().room(room_id).sender(user_id!("@a:b.c"));
+
+    let mxc = owned_mxc_uri!("mxc://localhost/cat");
+    let request = MediaRequestParameters {
+        source: MediaSource::Plain(mxc.clone()),
+        format: MediaFormat::File,
+    };
+    client
+        .event_cache_store()
+        .lock()
+        .await
+        .unwrap()
+        .add_media_content(&request, b"meow".to_vec(), IgnoreMediaRetentionPolicy::No)
+        .await
+        .unwrap();
+
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(
+                    f.image("cat.png".to_owned(), mxc.clone()).event_id(event_id!("$1")),
+                )
+                .add_timeline_event(f.text_msg("no media here").event_id(event_id!("$2"))),
+        )
+        .await;
+
+    // Two subscribers to the room, like two timelines.
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+    let (_, _subscriber1) = room_event_cache.subscribe().await;
+    let (_, _subscriber2) = room_event_cache.subscribe().await;
+
+    // Redacting an event without media doesn't remove anything.
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).add_timeline_event(f.redaction(event_id!("$2"))),
+        )
+        .await;
+
+    // Redacting the image removes its media, and notifies the subscribers once.
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).add_timeline_event(f.redaction(event_id!("$1"))),
+        )
+        .await;
+
+    assert_let_timeout!(Ok(media) = redacted_media.recv());
+    assert_eq!(media.room_id, room_id);
+    assert_eq!(media.event_id, event_id!("$1"));
+    assert_let!([MediaSource::Plain(uri)] = media.sources.as_slice());
+    assert_eq!(*uri, mxc);
+
+    let content =
+        client.event_cache_store().lock().await.unwrap().get_media_content(&request).await.unwrap();
+    assert!(content.is_none());
+
+    // Receiving the redaction again doesn't notify about the media again.
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.redaction(event_id!("$1")).event_id(event_id!("$3"))),
+        )
+        .await;
+
+    sleep(Duration::from_millis(100)).await;
+    assert!(redacted_media.is_empty());
+}
+
 #[async_test]
 async fn test_apply_redaction_on_an_in_store_event() {
     let room_id = room_id!("!foo:bar.baz");
@@ -2520,3 +2896,516 @@ async fn test_sync_while_back_paginate() {
 
     assert!(subscriber.is_empty());
 }
+
+#[async_test]
+async fn test_semantic_updates() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let event_cache = client.event_cache();
+    event_cache.subscribe().unwrap();
+
+    let room_id = room_id!("!omelette:fromage.fr");
+    let f = EventFactory::new().room(room_id).sender(user_id!("@a:b.c"));
+
+    let room = server.sync_joined_room(&client, room_id).await;
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+    let mut semantic_updates = room_event_cache.updates_as_semantic_events().await;
+    let (_events, mut subscriber) = room_event_cache.subscribe().await;
+
+    // New events from a sync are appended live.
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("hello").event_id(event_id!("$1")))
+                .add_timeline_event(f.text_msg("world").event_id(event_id!("$2")))
+                .set_timeline_prev_batch("prev_batch".to_owned()),
+        )
+        .await;
+
+    assert_let_timeout!(Ok(update) = semantic_updates.recv());
+    assert_eq!(update, RoomEventCacheSemanticUpdate::LiveAppended { count: 2 });
+
+    // The semantic update is only sent after the diffs it describes.
+    assert_let!(
+        Ok(RoomEventCacheUpdate::UpdateTimelineEvents { diffs, .. }) = subscriber.try_recv()
+    );
+    assert!(!diffs.is_empty());
+
+    // A redaction is appended live, and redacts its target.
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.redaction(event_id!("$1")).into_raw_sync()),
+        )
+        .await;
+
+    assert_let_timeout!(Ok(update) = semantic_updates.recv());
+    assert_eq!(update, RoomEventCacheSemanticUpdate::LiveAppended { count: 1 });
+    assert_let_timeout!(Ok(update) = semantic_updates.recv());
+    assert_eq!(
+        update,
+        RoomEventCacheSemanticUpdate::Redacted { event_id: event_id!("$1").to_owned() }
+    );
+
+    // Back-paginated events are inserted at the start.
+    server
+        .mock_room_messages()
+        .match_from("prev_batch")
+        .ok(RoomMessagesResponseTemplate::default()
+            .events(vec![f.text_msg("oh hi").event_id(event_id!("$0"))]))
+        .mock_once()
+        .mount()
+        .await;
+
+    room_event_cache.pagination().run_backwards_once(20).await.unwrap();
+
+    assert_let_timeout!(Ok(update) = semantic_updates.recv());
+    assert_eq!(update, RoomEventCacheSemanticUpdate::PaginatedBackwards { count: 1 });
+
+    // Clearing the room resets it.
+    room_event_cache.clear().await.unwrap();
+
+    assert_let_timeout!(Ok(update) = semantic_updates.recv());
+    assert_eq!(update, RoomEventCacheSemanticUpdate::Reset);
+
+    assert!(semantic_updates.is_empty());
+}
+
+#[async_test]
+async fn test_thread_events_and_back_pagination() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let event_cache = client.event_cache();
+    event_cache.subscribe().unwrap();
+
+    let room_id = room_id!("!galette:saucisse.bzh");
+    let f = EventFactory::new().room(room_id).sender(user_id!("@a:b.c"));
+
+    let thread_root = event_id!("$root");
+
+    let room = server.sync_joined_room(&client, room_id).await;
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+    // The latest in-thread event is received from the sync, along with an unrelated
+    // event.
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("unrelated").event_id(event_id!("$unrelated")))
+                .add_timeline_event(
+                    f.text_msg("reply 2")
+                        .in_thread(thread_root, event_id!("$r1"))
+                        .event_id(event_id!("$r2")),
+                ),
+        )
+        .await;
+
+    let thread_events = room_event_cache.thread_events(thread_root).await;
+    assert_eq!(thread_events.len(), 1);
+    assert_event_id!(thread_events[0], "$r2");
+
+    // Back-paginating the thread fetches older in-thread events from the server.
+    server
+        .mock_room_relations()
+        .match_target_event(thread_root.to_owned())
+        .match_subrequest(IncludeRelations::RelationsOfType(RelationType::Thread))
+        .ok(RoomRelationsResponseTemplate::default()
+            .events(vec![
+                f.text_msg("reply 2")
+                    .in_thread(thread_root, event_id!("$r1"))
+                    .event_id(event_id!("$r2")),
+                f.text_msg("reply 1")
+                    .in_thread(thread_root, event_id!("$r0"))
+                    .event_id(event_id!("$r1")),
+            ])
+            .next_batch("next"))
+        .mock_once()
+        .mount()
+        .await;
+
+    let outcome =
+        room_event_cache.paginate_thread_backwards(thread_root.to_owned(), None, 2).await.unwrap();
+    assert_eq!(outcome.prev_token.as_deref(), Some("next"));
+    assert_eq!(outcome.events.len(), 2);
+    assert_event_id!(outcome.events[0], "$r2");
+    assert_event_id!(outcome.events[1], "$r1");
+
+    server
+        .mock_room_relations()
+        .match_target_event(thread_root.to_owned())
+        .match_from("next")
+        .match_subrequest(IncludeRelations::RelationsOfType(RelationType::Thread))
+        .ok(RoomRelationsResponseTemplate::default().events(vec![f
+            .text_msg("reply 0")
+            .in_thread(thread_root, thread_root)
+            .event_id(event_id!("$r0"))]))
+        .mock_once()
+        .mount()
+        .await;
+
+    let outcome = room_event_cache
+        .paginate_thread_backwards(thread_root.to_owned(), outcome.prev_token, 2)
+        .await
+        .unwrap();
+    assert!(outcome.prev_token.is_none());
+    assert_eq!(outcome.events.len(), 1);
+    assert_event_id!(outcome.events[0], "$r0");
+
+    // The fetched events have been saved in the cache…
+    assert!(room_event_cache.event(event_id!("$r1")).await.is_some());
+    assert!(room_event_cache.event(event_id!("$r0")).await.is_some());
+
+    // … but they're not part of the room's timeline, since their position in it is
+    // unknown.
+    let thread_events = room_event_cache.thread_events(thread_root).await;
+    assert_eq!(thread_events.len(), 1);
+    assert_event_id!(thread_events[0], "$r2");
+}
+
+#[async_test]
+async fn test_verify_and_heal_ordering() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let event_cache = client.event_cache();
+    event_cache.subscribe().unwrap();
+
+    let room_id = room_id!("!omelette:fromage.fr");
+    let f = EventFactory::new().room(room_id).sender(user_id!("@a:b.c"));
+
+    // The event cache has an event the server doesn't know about, e.g. because it
+    // has been purged, and misses another one.
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("hello").event_id(event_id!("$1")).server_ts(1000))
+                .add_timeline_event(
+                    f.text_msg("purged").event_id(event_id!("$phantom")).server_ts(2000),
+                )
+                .add_timeline_event(f.text_msg("world").event_id(event_id!("$3")).server_ts(3000)),
+        )
+        .await;
+
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+    let (events, mut room_stream) = room_event_cache.subscribe().await;
+    if events.is_empty() {
+        assert_let_timeout!(
+            Ok(RoomEventCacheUpdate::UpdateTimelineEvents { .. }) = room_stream.recv()
+        );
+    }
+
+    server
+        .mock_room_messages()
+        .match_limit(10)
+        .ok(RoomMessagesResponseTemplate::default().events(vec![
+            f.text_msg("world").event_id(event_id!("$3")).server_ts(3000),
+            f.text_msg("missed").event_id(event_id!("$2")).server_ts(2500),
+            f.text_msg("hello").event_id(event_id!("$1")).server_ts(1000),
+        ]))
+        .expect(2)
+        .mount()
+        .await;
+
+    let report = room_event_cache.verify_and_heal_ordering(10).await.unwrap();
+
+    assert!(!report.is_consistent());
+    assert_eq!(report.checked, 3);
+    assert_eq!(report.unconfirmed, vec![event_id!("$phantom").to_owned()]);
+    assert_eq!(report.inserted, vec![event_id!("$2").to_owned()]);
+    assert!(report.same_order);
+
+    assert_let_timeout!(
+        Ok(RoomEventCacheUpdate::UpdateTimelineEvents { diffs, .. }) = room_stream.recv()
+    );
+    assert!(!diffs.is_empty());
+
+    // The missed event is inserted right before the event following it on the
+    // server, and the unconfirmed event is kept.
+    let events = room_event_cache.events().await;
+    assert_eq!(events.len(), 4);
+    assert_event_id!(events[0], "$1");
+    assert_event_id!(events[1], "$phantom");
+    assert_event_id!(events[2], "$2");
+    assert_event_id!(events[3], "$3");
+
+    // Once healed, nothing is missing anymore.
+    let report = room_event_cache.verify_and_heal_ordering(10).await.unwrap();
+    assert!(report.inserted.is_empty());
+    assert_eq!(report.unconfirmed, vec![event_id!("$phantom").to_owned()]);
+    assert!(report.same_order);
+    assert!(room_stream.is_empty());
+}
+
+#[async_test]
+async fn test_verify_and_heal_ordering_without_common_events() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let event_cache = client.event_cache();
+    event_cache.subscribe().unwrap();
+
+    let room_id = room_id!("!omelette:fromage.fr");
+    let f = EventFactory::new().room(room_id).sender(user_id!("@a:b.c"));
+
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("hello").event_id(event_id!("$1")).server_ts(1000))
+                .add_timeline_event(f.text_msg("world").event_id(event_id!("$2")).server_ts(2000)),
+        )
+        .await;
+
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+    let (events, mut room_stream) = room_event_cache.subscribe().await;
+    if events.is_empty() {
+        assert_let_timeout!(
+            Ok(RoomEventCacheUpdate::UpdateTimelineEvents { .. }) = room_stream.recv()
+        );
+    }
+
+    // The page of the server doesn't contain any of the cached events, even if
+    // they're in the same range of time.
+    server
+        .mock_room_messages()
+        .match_limit(1)
+        .ok(RoomMessagesResponseTemplate::default()
+            .events(vec![f.text_msg("other").event_id(event_id!("$other")).server_ts(1500)]))
+        .mock_once()
+        .mount()
+        .await;
+
+    let report = room_event_cache.verify_and_heal_ordering(1).await.unwrap();
+
+    // Nothing can be compared, so nothing is touched.
+    assert_eq!(report.checked, 0);
+    assert!(report.inserted.is_empty());
+    assert!(report.unconfirmed.is_empty());
+
+    let events = room_event_cache.events().await;
+    assert_eq!(events.len(), 2);
+    assert_event_id!(events[0], "$1");
+    assert_event_id!(events[1], "$2");
+    assert!(room_stream.is_empty());
+}
+
+#[async_test]
+async fn test_prefetch_after_back_pagination() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let event_cache = client.event_cache();
+    event_cache.subscribe().unwrap();
+    event_cache.config_mut().prefetch = Some(PrefetchConfig { batch_size: 20 });
+
+    let room_id = room_id!("!omelette:fromage.fr");
+    let f = EventFactory::new().room(room_id).sender(user_id!("@a:b.c"));
+
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("hello").event_id(event_id!("$2")))
+                .add_timeline_event(f.text_msg("world").event_id(event_id!("$3")))
+                .set_timeline_prev_batch("prev_batch".to_owned())
+                .set_timeline_limited(),
+        )
+        .await;
+
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+    let (events, mut room_stream) = room_event_cache.subscribe().await;
+    if events.is_empty() {
+        assert_let_timeout!(
+            Ok(RoomEventCacheUpdate::UpdateTimelineEvents { .. }) = room_stream.recv()
+        );
+    }
+
+    server
+        .mock_room_messages()
+        .match_from("prev_batch")
+        .ok(RoomMessagesResponseTemplate::default()
+            .events(vec![f.text_msg("second").event_id(event_id!("$1"))])
+            .end_token("prev_batch2"))
+        .mock_once()
+        .mount()
+        .await;
+    server
+        .mock_room_messages()
+        .match_from("prev_batch2")
+        .ok(RoomMessagesResponseTemplate::default()
+            .events(vec![f.text_msg("first").event_id(event_id!("$0"))]))
+        .mock_once()
+        .mount()
+        .await;
+
+    let pagination = room_event_cache.pagination();
+
+    // The back-pagination requested by the user…
+    let outcome = pagination.run_backwards_once(20).await.unwrap();
+    assert!(!outcome.reached_start);
+
+    assert_let_timeout!(
+        Ok(RoomEventCacheUpdate::UpdateTimelineEvents { origin: EventsOrigin::Pagination, .. }) =
+            room_stream.recv()
+    );
+
+    // …is followed by a back-pagination in the background.
+    assert_let_timeout!(
+        Ok(RoomEventCacheUpdate::UpdateTimelineEvents { origin: EventsOrigin::Pagination, .. }) =
+            room_stream.recv()
+    );
+
+    let events = room_event_cache.events().await;
+    assert_eq!(events.len(), 4);
+    assert_event_id!(events[0], "$0");
+    assert_event_id!(events[1], "$1");
+    assert_event_id!(events[2], "$2");
+    assert_event_id!(events[3], "$3");
+}
+
+#[cfg(feature = "e2e-encryption")]
+#[async_test]
+async fn test_retry_decryption_of_unloaded_events() {
+    use std::{collections::BTreeSet, io::Write};
+
+    use matrix_sdk::deserialized_responses::TimelineEventKind;
+    use ruma::events::room::encrypted::{
+        EncryptedEventScheme, MegolmV1AesSha2ContentInit, RoomEncryptedEventContent,
+    };
+    use tempfile::NamedTempFile;
+
+    const SESSION_ID: &str = "gM8i47Xhu0q52xLfgUXzanCMpLinoyVyH7R58cBuVBU";
+    const SESSION_KEY: &[u8] = b"\
+        -----BEGIN MEGOLM SESSION DATA-----\n\
+        ASKcWoiAVUM97482UAi83Avce62hSLce7i5JhsqoF6xeAAAACqt2Cg3nyJPRWTTMXxXH7TXnkfdlmBXbQtq5\
+        bpHo3LRijcq2Gc6TXilESCmJN14pIsfKRJrWjZ0squ/XsoTFytuVLWwkNaW3QF6obeg2IoVtJXLMPdw3b2vO\
+        vgwGY3OMP0XafH13j1vcb6YLzvgLkZQLnYvd47hv3yK/9GmKS9tokuaQ7dCVYckYcIOS09EDTs70YdxUd5WG\
+        rQynATCLFP1p/NAGv70r9MK7Cy/mNpjD0r4qC7UEDIoi1kOWzHgnLo19wtvwsb8Fg8ATxcs3Wmtj8hIUYpDx\
+        ia4sM10zbytUuaPUAfCDf42IyxdmOnGe1CueXhgI71y+RW0s0argNqUt7jB70JT0o9CyX6UBGRaqLk2MPY9T\
+        hUu5J8X3UgIa6rcbWigzohzWm9rdbEHFrSWqjpfQYMaAKQQgETrjSy4XTrp2RhC2oNqG/hylI4ab+F4X6fpH\
+        DYP1NqNMP5g36xNu7LhDnrUB5qsPjYOmWORxGLfudpF3oLYCSlr3DgHqEIB6HjQblLZ3KQuPBse3zxyROTnS\
+        AhdPH4a/z1wioFtKNVph3hecsiKEdqnz4Y2coSIdhz58mJ9JWNQoFAENE5CSsoEZAGvafYZVpW4C75YY2zq1\
+        wIeiFi1dT43/jLAUGkslsi1VvnyfUu8qO404RxYO3XHoGLMFoFLOO+lZ+VGci2Vz10AhxJhEBHxRKxw4k2uB\
+        HztoSJUr/2Y\n\
+        -----END MEGOLM SESSION DATA-----";
+
+    let room_id = room_id!("!DovneieKSTkdHKpIXy:morpheus.localhost");
+    let f = EventFactory::new().room(room_id).sender(*BOB);
+
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    // The event cache store contains a UTD in the first chunk, which won't be
+    // loaded in memory, since only the last chunk is loaded initially.
+    let utd = f
+        .event(RoomEncryptedEventContent::new(
+            EncryptedEventScheme::MegolmV1AesSha2(
+                MegolmV1AesSha2ContentInit {
+                    ciphertext: "\
+                        AwgAEtABPRMavuZMDJrPo6pGQP4qVmpcuapuXtzKXJyi3YpEsjSWdzuRKIgJzD4P\
+                        cSqJM1A8kzxecTQNJsC5q22+KSFEPxPnI4ltpm7GFowSoPSW9+bFdnlfUzEP1jPq\
+                        YevHAsMJp2fRKkzQQbPordrUk1gNqEpGl4BYFeRqKl9GPdKFwy45huvQCLNNueql\
+                        CFZVoYMuhxrfyMiJJAVNTofkr2um2mKjDTlajHtr39pTG8k0eOjSXkLOSdZvNOMz\
+                        hGhSaFNeERSA2G2YbeknOvU7MvjiO0AKuxaAe1CaVhAI14FCgzrJ8g0y5nly+n7x\
+                        QzL2G2Dn8EoXM5Iqj8W99iokQoVsSrUEnaQ1WnSIfewvDDt4LCaD/w7PGETMCQ"
+                        .to_owned(),
+                    sender_key: "DeHIg4gwhClxzFYcmNntPNF9YtsdZbmMy8+3kzCMXHA".to_owned(),
+                    device_id: "NLAZCWIOCO".into(),
+                    session_id: SESSION_ID.into(),
+                }
+                .into(),
+            ),
+            None,
+        ))
+        .event_id(event_id!("$ev0"))
+        .into_utd_sync_timeline_event();
+
+    client
+        .event_cache_store()
+        .lock()
+        .await
+        .unwrap()
+        .handle_linked_chunk_updates(
+            LinkedChunkId::Room(room_id),
+            vec![
+                Update::NewItemsChunk { previous: None, new: ChunkIdentifier::new(0), next: None },
+                Update::PushItems {
+                    at: Position::new(ChunkIdentifier::new(0), 0),
+                    items: vec![utd],
+                },
+                Update::NewItemsChunk {
+                    previous: Some(ChunkIdentifier::new(0)),
+                    new: ChunkIdentifier::new(1),
+                    next: None,
+                },
+                Update::PushItems {
+                    at: Position::new(ChunkIdentifier::new(1), 0),
+                    items: vec![f.text_msg("hello").event_id(event_id!("$ev1")).into_event()],
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+    // Import the key of the session, before the event cache listens to the received
+    // room keys, so the event isn't decrypted automatically.
+    {
+        let mut tempfile = NamedTempFile::new().unwrap();
+        tempfile.write_all(SESSION_KEY).unwrap();
+        let tempfile_path = tempfile.into_temp_path();
+
+        let result = client
+            .encryption()
+            .import_room_keys(tempfile_path.to_path_buf(), "1234")
+            .await
+            .unwrap();
+        assert_eq!(result.imported_count, 1);
+    }
+
+    client.event_cache().subscribe().unwrap();
+
+    let room = server.sync_joined_room(&client, room_id).await;
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+
+    // The UTD isn't loaded in memory.
+    let events = room_event_cache.events().await;
+    assert_eq!(events.len(), 1);
+    assert_event_id!(events[0], "$ev1");
+
+    // Retrying with another session doesn't decrypt anything.
+    let session_ids = BTreeSet::from(["another_session".to_owned()]);
+    assert_eq!(room_event_cache.retry_decryption(&session_ids).await.unwrap(), 0);
+
+    // Retrying with the session of the UTD decrypts it, even though it's only in
+    // the store.
+    let session_ids = BTreeSet::from([SESSION_ID.to_owned()]);
+    assert_eq!(room_event_cache.retry_decryption(&session_ids).await.unwrap(), 1);
+
+    let event = room_event_cache.event(event_id!("$ev0")).await.unwrap();
+    assert_matches!(event.kind, TimelineEventKind::Decrypted(_));
+
+    // It's saved in the store too.
+    let event = client
+        .event_cache_store()
+        .lock()
+        .await
+        .unwrap()
+        .find_event(room_id, event_id!("$ev0"))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_matches!(event.kind, TimelineEventKind::Decrypted(_));
+
+    // There's nothing left to decrypt.
+    assert_eq!(room_event_cache.retry_decryption(&session_ids).await.unwrap(), 0);
+}
@@ -28,7 +28,7 @@ use ruma::{
 use serde_json::{from_value as from_json_value, json, to_value as to_json_value};
 use url::Url;
 use wiremock::{
-    matchers::{method, path},
+    matchers::{body_partial_json, method, path, path_regex, query_param},
     Mock, MockServer, Request, ResponseTemplate,
 };
 
@@ -292,6 +292,131 @@ async fn test_register_error() {
     }
 }
 
+#[async_test]
+async fn test_is_username_available() {
+    let (client, server) = no_retry_test_client_with_server().await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/.*/register/available"))
+        .and(query_param("username", "alice"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "available": true })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/.*/register/available"))
+        .and(query_param("username", "bob"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "errcode": "M_USER_IN_USE",
+            "error": "Desired user ID is already taken.",
+        })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/.*/register/available"))
+        .and(query_param("username", "_bridge_carol"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "errcode": "M_EXCLUSIVE",
+            "error": "This username is reserved by an application service.",
+        })))
+        .mount(&server)
+        .await;
+
+    let auth = client.matrix_auth();
+    assert!(auth.is_username_available("alice").await.unwrap());
+    assert!(!auth.is_username_available("bob").await.unwrap());
+
+    // Other errors are forwarded to the caller.
+    let error = auth.is_username_available("_bridge_carol").await.unwrap_err();
+    assert_matches!(error.client_api_error_kind(), Some(client_api::error::ErrorKind::Exclusive));
+}
+
+#[async_test]
+async fn test_register_username_with_uiaa_stages() {
+    let (client, server) = no_retry_test_client_with_server().await;
+
+    // The homeserver first requires a registration token.
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/.*/register$"))
+        .and(body_partial_json(json!({ "username": "alice", "password": "wordpass" })))
+        .and(|request: &Request| {
+            let body: serde_json::Value = request.body_json().unwrap();
+            body.get("auth").is_none()
+        })
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "flows": [{ "stages": ["m.login.registration_token"] }],
+            "params": {},
+            "session": "uiaa-session",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/.*/register$"))
+        .and(body_partial_json(json!({
+            "username": "alice",
+            "password": "wordpass",
+            "initial_device_display_name": "My app",
+            "auth": {
+                "type": "m.login.registration_token",
+                "token": "the-token",
+                "session": "uiaa-session",
+            },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "user_id": "@alice:example.org",
+            "access_token": "abc123",
+            "device_id": "ABCDEFGH",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let auth = client.matrix_auth();
+
+    let error = auth
+        .register_username("alice", "wordpass")
+        .await
+        .expect_err("the first request should require user-interactive authentication");
+    let info = error.as_uiaa_response().expect("the error should be a UIAA response");
+    assert_eq!(info.flows.len(), 1);
+    assert_eq!(info.flows[0].stages, vec![uiaa::AuthType::RegistrationToken]);
+    assert!(!auth.logged_in());
+
+    let token = assign!(uiaa::RegistrationToken::new("the-token".to_owned()), {
+        session: info.session.clone(),
+    });
+    let response = auth
+        .register_username("alice", "wordpass")
+        .auth(AuthData::RegistrationToken(token))
+        .initial_device_display_name("My app")
+        .await
+        .unwrap();
+
+    assert_eq!(response.user_id, "@alice:example.org");
+
+    // The client is logged in with the new account.
+    assert!(auth.logged_in());
+    assert_eq!(client.user_id().unwrap(), "@alice:example.org");
+    assert_eq!(client.device_id().unwrap(), "ABCDEFGH");
+
+    let session = auth.session().expect("the session should be set");
+    assert_eq!(session.meta.user_id, "@alice:example.org");
+    assert_eq!(session.meta.device_id, "ABCDEFGH");
+    assert_eq!(session.tokens.access_token, "abc123");
+
+    // And its device has been set up.
+    #[cfg(feature = "e2e-encryption")]
+    {
+        let olm_machine = client.olm_machine_for_testing().await;
+        let olm_machine = olm_machine.as_ref().expect("the olm machine should be set up");
+        assert_eq!(olm_machine.user_id(), "@alice:example.org");
+        assert_eq!(olm_machine.device_id(), "ABCDEFGH");
+    }
+}
+
 #[test]
 fn test_deserialize_session() {
     // First version, or second version without refresh token.
@@ -422,3 +422,184 @@ async fn test_observing_live_location_does_not_return_own_beacon_updates() {
 
     assert!(stream.next().now_or_never().is_none());
 }
+
+#[async_test]
+async fn test_observe_active_live_location_shares() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let room_id = room_id!("!a:b.c");
+    let beacon_info_event_id = event_id!("$beacon_info:b.c");
+    let user_id = user_id!("@example2:localhost");
+
+    let f = EventFactory::new().room(room_id);
+
+    let joined_room_builder = JoinedRoomBuilder::new(room_id).add_state_bulk(vec![f
+        .event(BeaconInfoEventContent::new(None, Duration::from_secs(60), true, None))
+        .event_id(beacon_info_event_id)
+        .sender(user_id)
+        .state_key(user_id)
+        .into_raw_timeline()
+        .cast()]);
+
+    let room = server.sync_room(&client, joined_room_builder).await;
+
+    let observable_live_location_shares = room.observe_live_location_shares();
+    let stream = observable_live_location_shares.subscribe_to_active_shares();
+    pin_mut!(stream);
+
+    // No location is known yet for the live location share.
+    let shares = stream.next().await.expect("the active shares should be emitted first");
+    assert!(shares.is_empty());
+
+    // A location is received for the live location share.
+    let beacon_event = f
+        .event(BeaconEventContent::new(
+            beacon_info_event_id.to_owned(),
+            "geo:51.5008,0.1247;u=35".to_owned(),
+            None,
+        ))
+        .event_id(event_id!("$beacon:b.c"))
+        .sender(user_id)
+        .into_raw_sync();
+
+    server
+        .sync_room(&client, JoinedRoomBuilder::new(room_id).add_timeline_event(beacon_event))
+        .await;
+
+    let shares = stream.next().await.expect("the active shares should be updated");
+    assert_eq!(shares.len(), 1);
+    assert_eq!(shares[0].user_id, user_id);
+    assert_eq!(shares[0].last_location.location.uri, "geo:51.5008,0.1247;u=35");
+
+    // The live location share is stopped.
+    let stopped_beacon_info = f
+        .event(BeaconInfoEventContent::new(None, Duration::from_secs(60), false, None))
+        .event_id(event_id!("$stopped_beacon_info:b.c"))
+        .sender(user_id)
+        .state_key(user_id)
+        .into_raw_timeline()
+        .cast();
+
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).add_state_bulk(vec![stopped_beacon_info]),
+        )
+        .await;
+
+    let shares = stream.next().await.expect("the active shares should be updated");
+    assert!(shares.is_empty());
+}
+
+#[async_test]
+async fn test_observe_active_live_location_shares_starts_with_the_current_shares() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    client.event_cache().subscribe().unwrap();
+
+    let room_id = room_id!("!a:b.c");
+    let beacon_info_event_id = event_id!("$beacon_info:b.c");
+    let user_id = user_id!("@example2:localhost");
+
+    let f = EventFactory::new().room(room_id);
+
+    let room = server.sync_joined_room(&client, room_id).await;
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+    let (_, mut room_event_cache_updates) = room_event_cache.subscribe().await;
+
+    // A live location share is active, and a location has been received for it.
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_state_bulk(vec![f
+                    .event(BeaconInfoEventContent::new(None, Duration::from_secs(60), true, None))
+                    .event_id(beacon_info_event_id)
+                    .sender(user_id)
+                    .state_key(user_id)
+                    .into_raw_timeline()
+                    .cast()])
+                .add_timeline_event(
+                    f.event(BeaconEventContent::new(
+                        beacon_info_event_id.to_owned(),
+                        "geo:51.5008,0.1247;u=35".to_owned(),
+                        None,
+                    ))
+                    .event_id(event_id!("$beacon:b.c"))
+                    .sender(user_id),
+                ),
+        )
+        .await;
+
+    while room_event_cache.events().await.is_empty() {
+        room_event_cache_updates.recv().await.unwrap();
+    }
+
+    let observable_live_location_shares = room.observe_live_location_shares();
+    let stream = observable_live_location_shares.subscribe_to_active_shares();
+    pin_mut!(stream);
+
+    // The active share is emitted first.
+    let shares = stream.next().await.expect("the active shares should be emitted first");
+    assert_eq!(shares.len(), 1);
+    assert_eq!(shares[0].user_id, user_id);
+    assert_eq!(shares[0].last_location.location.uri, "geo:51.5008,0.1247;u=35");
+    assert!(shares[0].beacon_info.as_ref().unwrap().is_live());
+}
+
+#[async_test]
+async fn test_observe_active_live_location_shares_removes_expired_shares() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let room_id = room_id!("!a:b.c");
+    let beacon_info_event_id = event_id!("$beacon_info:b.c");
+    let user_id = user_id!("@example2:localhost");
+
+    let f = EventFactory::new().room(room_id);
+
+    // The live location share expires in one second.
+    let timeout = Duration::from_secs(60);
+    let started_at = MilliSecondsSinceUnixEpoch::from_system_time(
+        SystemTime::now() - timeout + Duration::from_secs(1),
+    );
+
+    let joined_room_builder = JoinedRoomBuilder::new(room_id).add_state_bulk(vec![f
+        .event(BeaconInfoEventContent::new(None, timeout, true, started_at))
+        .event_id(beacon_info_event_id)
+        .sender(user_id)
+        .state_key(user_id)
+        .into_raw_timeline()
+        .cast()]);
+
+    let room = server.sync_room(&client, joined_room_builder).await;
+
+    let observable_live_location_shares = room.observe_live_location_shares();
+    let stream = observable_live_location_shares.subscribe_to_active_shares();
+    pin_mut!(stream);
+
+    let shares = stream.next().await.expect("the active shares should be emitted first");
+    assert!(shares.is_empty());
+
+    // A location is received for the live location share.
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).add_timeline_event(
+                f.event(BeaconEventContent::new(
+                    beacon_info_event_id.to_owned(),
+                    "geo:51.5008,0.1247;u=35".to_owned(),
+                    None,
+                ))
+                .event_id(event_id!("$beacon:b.c"))
+                .sender(user_id),
+            ),
+        )
+        .await;
+
+    let shares = stream.next().await.expect("the active shares should be updated");
+    assert_eq!(shares.len(), 1);
+
+    // The share is removed when it expires, without any new event.
+    let shares = stream.next().await.expect("the active shares should be updated");
+    assert!(shares.is_empty());
+}
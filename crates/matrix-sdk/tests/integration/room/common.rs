@@ -845,6 +845,119 @@ async fn test_is_direct() {
     assert!(!room.is_direct().await.unwrap());
 }
 
+#[async_test]
+async fn test_mark_as_direct_with_retries_on_conflict() {
+    let (client, server) = logged_in_client_with_server().await;
+    let own_user_id = client.user_id().unwrap();
+    let carol = user_id!("@carol:localhost");
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+
+    let bob_member_event = json!({
+        "content": {
+            "membership": "join",
+        },
+        "event_id": "$747273582443PhrSn:localhost",
+        "origin_server_ts": 1472735824,
+        "sender": *BOB,
+        "state_key": *BOB,
+        "type": "m.room.member",
+    });
+
+    let mut sync_builder = SyncResponseBuilder::new();
+    sync_builder.add_joined_room(
+        JoinedRoomBuilder::new(&DEFAULT_TEST_ROOM_ID)
+            .add_state_event(StateTestEvent::Member)
+            .add_state_event(StateTestEvent::Custom(bob_member_event.clone())),
+    );
+    mock_sync(&server, sync_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(sync_settings.clone()).await.unwrap();
+    server.reset().await;
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+    assert!(!room.is_direct_with(*BOB).await.unwrap());
+
+    let other_device_content = json!({
+        carol: ["!carol:localhost"],
+    });
+    let direct_content = json!({
+        *BOB: [*DEFAULT_TEST_ROOM_ID],
+        carol: ["!carol:localhost"],
+    });
+
+    // There's no `m.direct` account data at first…
+    Mock::given(method("GET"))
+        .and(path_regex(format!("^/_matrix/client/r0/user/{own_user_id}/account_data/m.direct$")))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_NOT_FOUND",
+            "error": "Account data not found",
+        })))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // … but another device overwrites our update…
+    Mock::given(method("GET"))
+        .and(path_regex(format!("^/_matrix/client/r0/user/{own_user_id}/account_data/m.direct$")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&other_device_content))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // … so we update it again, and it sticks this time.
+    Mock::given(method("GET"))
+        .and(path_regex(format!("^/_matrix/client/r0/user/{own_user_id}/account_data/m.direct$")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&direct_content))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(format!("^/_matrix/client/r0/user/{own_user_id}/account_data/m.direct$")))
+        .and(body_json(json!({ *BOB: [*DEFAULT_TEST_ROOM_ID] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(format!("^/_matrix/client/r0/user/{own_user_id}/account_data/m.direct$")))
+        .and(body_json(&direct_content))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    room.mark_as_direct_with(&[BOB.to_owned()]).await.unwrap();
+    server.verify().await;
+    server.reset().await;
+
+    // Once the account data has been synced, the room is a DM with Bob, who is in
+    // the room, but not with Carol.
+    sync_builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+        "type": "m.direct",
+        "content": direct_content,
+    })));
+    mock_sync(&server, sync_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(sync_settings).await.unwrap();
+
+    Mock::given(method("GET"))
+        .and(path_regex(format!("^/_matrix/client/r0/rooms/{}/members$", *DEFAULT_TEST_ROOM_ID)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "chunk": [
+                *test_json::MEMBER,
+                bob_member_event,
+            ],
+        })))
+        .mount(&server)
+        .await;
+
+    assert!(room.is_direct_with(*BOB).await.unwrap());
+    assert!(!room.is_direct_with(carol).await.unwrap());
+    assert_eq!(client.direct_rooms_with(*BOB).await.unwrap().len(), 1);
+}
+
 #[async_test]
 async fn test_room_avatar() {
     let (client, server) = logged_in_client_with_server().await;
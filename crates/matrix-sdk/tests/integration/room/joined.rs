@@ -1,5 +1,5 @@
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet},
     sync::{Arc, Mutex},
     time::Duration,
 };
@@ -10,7 +10,11 @@ use futures_util::{future::join_all, pin_mut};
 use matrix_sdk::{
     assert_next_with_timeout, assert_recv_with_timeout,
     config::SyncSettings,
-    room::{edit::EditedContent, Receipts, ReportedContentScore, RoomMemberRole},
+    room::{
+        edit::EditedContent, power_levels::RoomPowerLevelChanges, server_acl::ServerAclError,
+        LeaveOptions, LeaveOutcome, PreviousState, Receipts, ReportedContentScore, RoomMemberRole,
+        StateEventOutcome, StateEventsBatch,
+    },
     test_utils::mocks::MatrixMockServer,
 };
 use matrix_sdk_base::{EncryptionState, RoomMembersUpdate, RoomState};
@@ -37,13 +41,17 @@ use ruma::{
         direct::DirectUserIdentifier,
         receipt::ReceiptThread,
         room::{
+            avatar::RoomAvatarEventContent,
             member::MembershipState,
             message::{RoomMessageEventContent, RoomMessageEventContentWithoutRelation},
+            name::RoomNameEventContent,
+            power_levels::RoomPowerLevelsEventContent,
+            topic::RoomTopicEventContent,
         },
-        Mentions, RoomAccountDataEventType, TimelineEventType,
+        Mentions, RoomAccountDataEventType, StateEventType, TimelineEventType,
     },
-    int, mxc_uri, owned_device_id, owned_event_id, room_id, thirdparty, user_id, OwnedUserId,
-    TransactionId,
+    int, mxc_uri, owned_device_id, owned_event_id, room_id, server_name, thirdparty, user_id,
+    OwnedUserId, TransactionId,
 };
 use serde_json::{from_value, json, Value};
 use stream_assert::assert_pending;
@@ -205,6 +213,49 @@ async fn test_leave_invited_room_with_no_permissions() -> Result<(), anyhow::Err
     Ok(())
 }
 
+#[async_test]
+async fn test_leave_with_options_forgets_joined_room() -> Result<(), anyhow::Error> {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let room_id = *DEFAULT_TEST_ROOM_ID;
+
+    server.mock_room_leave().ok(room_id).mock_once().mount().await;
+    server.mock_room_forget().ok().mock_once().mount().await;
+
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    let options = LeaveOptions { reason: Some("Bye".to_owned()), forget: true };
+    let outcome = room.leave_with_options(options).await?;
+
+    assert_eq!(outcome, LeaveOutcome::Left);
+    assert_eq!(room.state(), RoomState::Left);
+
+    let forgotten_room = client.get_room(room_id);
+    assert!(forgotten_room.is_none());
+
+    Ok(())
+}
+
+#[async_test]
+async fn test_leave_with_options_rejects_invite() -> Result<(), anyhow::Error> {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let room_id = *DEFAULT_TEST_ROOM_ID;
+
+    server.mock_room_leave().ok(room_id).mock_once().mount().await;
+    server.mock_room_forget().ok().mock_once().mount().await;
+
+    let invited_room_builder = InvitedRoomBuilder::new(room_id);
+    let room = server.sync_room(&client, invited_room_builder).await;
+
+    let outcome = room.leave_with_options(LeaveOptions::default()).await?;
+
+    assert_eq!(outcome, LeaveOutcome::InviteRejected);
+    assert!(client.get_room(room_id).is_none());
+
+    Ok(())
+}
+
 #[async_test]
 async fn test_ban_user() {
     let (client, server) = logged_in_client_with_server().await;
@@ -763,6 +814,101 @@ async fn test_reset_power_levels() {
     room.reset_power_levels().await.unwrap();
 }
 
+#[async_test]
+async fn test_update_power_levels_from() {
+    let (client, server) = logged_in_client_with_server().await;
+
+    mock_sync(&server, &*CUSTOM_ROOM_POWER_LEVELS, None).await;
+
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+    let _response = client.sync_once(sync_settings).await.unwrap();
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    let base = room.power_levels().await.unwrap();
+
+    // The power levels of the homeserver are the same as the base ones.
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.room.power_levels/$"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(RoomPowerLevelsEventContent::from(base.clone())),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.room.power_levels/$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_partial_json(json!({
+            "events": {
+                "m.room.name": 100,
+            },
+            "users": {
+                "@alice:localhost": 50,
+            },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let changes = RoomPowerLevelChanges { room_name: Some(100), ..Default::default() };
+    room.update_power_levels_from(&base, vec![(user_id!("@alice:localhost"), int!(50))], changes)
+        .await
+        .unwrap();
+}
+
+#[async_test]
+async fn test_update_power_levels_from_conflict() {
+    let (client, server) = logged_in_client_with_server().await;
+
+    mock_sync(&server, &*CUSTOM_ROOM_POWER_LEVELS, None).await;
+
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+    let _response = client.sync_once(sync_settings).await.unwrap();
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    let base = room.power_levels().await.unwrap();
+
+    // Another admin has changed the power levels on the homeserver, and the change
+    // hasn't been received with a sync yet.
+    let mut server_power_levels = base.clone();
+    server_power_levels.users.insert(user_id!("@bob:localhost").to_owned(), int!(100));
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.room.power_levels/$"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(RoomPowerLevelsEventContent::from(server_power_levels)),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.room.power_levels/$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    // The local power levels are still the base ones…
+    assert_eq!(room.power_levels().await.unwrap().users, base.users);
+
+    // …but the conflict is detected anyway.
+    let result = room
+        .update_power_levels_from(
+            &base,
+            vec![(user_id!("@alice:localhost"), int!(50))],
+            RoomPowerLevelChanges::new(),
+        )
+        .await;
+    assert_matches!(result, Err(matrix_sdk::Error::PowerLevelsConflict));
+}
+
 #[async_test]
 async fn test_is_direct_invite_by_3pid() {
     let (client, server) = logged_in_client_with_server().await;
@@ -1409,3 +1555,174 @@ async fn test_report_room() {
 
     room.report_room(Some(reason.to_owned())).await.unwrap();
 }
+
+#[async_test]
+async fn test_send_state_events_batch() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let room_id = room_id!("!a:b.c");
+    let own_user_id = client.user_id().unwrap();
+
+    let f = EventFactory::new().room(room_id).sender(own_user_id);
+    let mut user_map = BTreeMap::from([(own_user_id.to_owned(), 100.into())]);
+
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.power_levels(&mut user_map).state_key(""))
+                .add_timeline_event(f.room_name("Old name")),
+        )
+        .await;
+
+    server
+        .mock_room_send_state()
+        .for_type(StateEventType::RoomName)
+        .ok(event_id!("$name"))
+        .mock_once()
+        .mount()
+        .await;
+    server
+        .mock_room_send_state()
+        .for_type(StateEventType::RoomTopic)
+        .error500()
+        .mock_once()
+        .mount()
+        .await;
+    server
+        .mock_room_send_state()
+        .for_type(StateEventType::RoomAvatar)
+        .ok(event_id!("$avatar"))
+        .never()
+        .mount()
+        .await;
+
+    let mut batch = StateEventsBatch::new();
+    batch.add("", RoomNameEventContent::new("New name".to_owned())).unwrap();
+    batch.add("", RoomTopicEventContent::new("New topic".to_owned())).unwrap();
+    batch.add("", RoomAvatarEventContent::new()).unwrap();
+
+    let report = room.send_state_events(batch).await.unwrap();
+    assert!(!report.is_success());
+    assert_eq!(report.results.len(), 3);
+
+    // The name was sent, and its previous content was remembered.
+    assert_let!(
+        StateEventOutcome::Sent {
+            event_id,
+            previous_state: PreviousState::Known(previous_content)
+        } = &report.results[0].1
+    );
+    assert_eq!(event_id, event_id!("$name"));
+    assert_eq!(previous_content.get_field::<String>("name").unwrap().as_deref(), Some("Old name"));
+
+    // The topic failed, so the avatar was never sent.
+    assert_matches!(report.results[1].1, StateEventOutcome::Failed(_));
+    assert_matches!(report.results[2].1, StateEventOutcome::Skipped);
+
+    let (failed_event, _) = report.first_failure().unwrap();
+    assert_eq!(failed_event.event_type, StateEventType::RoomTopic);
+
+    // Rolling back only restores the name.
+    let rollback = report.rollback_batch();
+    assert_eq!(rollback.events().len(), 1);
+    let restored = &rollback.events()[0];
+    assert_eq!(restored.event_type, StateEventType::RoomName);
+    assert_eq!(restored.content.get_field::<String>("name").unwrap().as_deref(), Some("Old name"));
+}
+
+#[async_test]
+async fn test_send_state_events_batch_checks_power_levels_first() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let room_id = room_id!("!a:b.c");
+    let own_user_id = client.user_id().unwrap();
+
+    // The current user can send `m.room.topic` events, but not other state
+    // events.
+    let f = EventFactory::new().room(room_id).sender(user_id!("@admin:b.c"));
+    let mut power_levels = RoomPowerLevelsEventContent::new();
+    power_levels.users.insert(own_user_id.to_owned(), 10.into());
+    power_levels.events.insert(TimelineEventType::RoomTopic, 10.into());
+
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).add_timeline_event(f.event(power_levels).state_key("")),
+        )
+        .await;
+
+    server.mock_room_send_state().ok(event_id!("$never")).never().mount().await;
+
+    let mut batch = StateEventsBatch::new();
+    batch.add("", RoomTopicEventContent::new("New topic".to_owned())).unwrap();
+    batch.add("", RoomNameEventContent::new("New name".to_owned())).unwrap();
+
+    // Nothing is sent because the name isn't allowed.
+    let report = room.send_state_events(batch).await.unwrap();
+    assert!(!report.is_success());
+    assert_matches!(report.results[0].1, StateEventOutcome::Allowed);
+    assert_matches!(report.results[1].1, StateEventOutcome::Forbidden);
+
+    // A dry run only with allowed events is a success, but nothing is sent.
+    let mut batch = StateEventsBatch::new();
+    batch.add("", RoomTopicEventContent::new("New topic".to_owned())).unwrap();
+    batch.dry_run(true);
+
+    let report = room.send_state_events(batch).await.unwrap();
+    assert!(report.is_success());
+    assert!(report.dry_run);
+    assert_matches!(report.results[0].1, StateEventOutcome::Allowed);
+    assert_eq!(report.sent().count(), 0);
+}
+
+#[async_test]
+async fn test_edit_server_acl() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let room_id = room_id!("!a:b.c");
+
+    let f = EventFactory::new().room(room_id).sender(user_id!("@admin:b.c"));
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).add_timeline_event(
+                f.server_acl(true, vec!["*".to_owned()], vec!["evil.org".to_owned()]).state_key(""),
+            ),
+        )
+        .await;
+
+    server
+        .mock_room_send_state()
+        .for_type(StateEventType::RoomServerAcl)
+        .body_matches_partial_json(json!({
+            "allow": ["*"],
+            "deny": ["evil.org", "*.spam.org"],
+            "allow_ip_literals": false,
+        }))
+        .ok(event_id!("$acl"))
+        .mock_once()
+        .mount()
+        .await;
+
+    let mut acl = room.server_acl().await.unwrap();
+    assert_eq!(acl.allow(), ["*"]);
+    assert_eq!(acl.deny(), ["evil.org"]);
+    assert!(acl.allow_ip_literals());
+
+    // Invalid patterns are rejected.
+    assert_matches!(acl.deny_server("spam.org:8448"), Err(ServerAclError::InvalidServerName(_)));
+
+    // Denying the own server of the user isn't saved.
+    acl.deny_server("local*").unwrap();
+    assert!(!acl.is_allowed(server_name!("localhost")));
+    assert_matches!(acl.save().await, Err(ServerAclError::OwnServerDenied(_)));
+
+    assert!(acl.remove_server("local*"));
+    acl.deny_server("*.spam.org").unwrap();
+    acl.set_allow_ip_literals(false);
+    assert!(!acl.is_allowed(server_name!("matrix.spam.org")));
+
+    let response = acl.save().await.unwrap();
+    assert_eq!(response.event_id, "$acl");
+}
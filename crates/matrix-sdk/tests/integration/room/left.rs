@@ -253,3 +253,32 @@ async fn test_knocking() {
     let room = client.knock(room_id, None, Vec::new()).await.unwrap();
     assert_eq!(room.state(), RoomState::Knocked);
 }
+
+#[async_test]
+async fn test_withdraw_knock() {
+    let (client, server) = logged_in_client_with_server().await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/unstable/xyz.amorgan.knock/knock/.*"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "room_id": *DEFAULT_TEST_ROOM_ID })),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/leave"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let room_id = OwnedRoomOrAliasId::from((*DEFAULT_TEST_ROOM_ID).to_owned());
+    let room = client.knock(room_id, None, Vec::new()).await.unwrap();
+    assert_eq!(room.state(), RoomState::Knocked);
+
+    // Leaving a knocked room withdraws the knock.
+    room.leave().await.unwrap();
+    assert_eq!(room.state(), RoomState::Left);
+}
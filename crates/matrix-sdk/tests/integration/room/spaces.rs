@@ -2,10 +2,18 @@ use std::time::Duration;
 
 use assert_matches2::assert_let;
 use futures_util::StreamExt;
-use matrix_sdk::{config::SyncSettings, room::ParentSpace, Client};
-use matrix_sdk_test::{async_test, test_json, DEFAULT_TEST_ROOM_ID};
+use matrix_sdk::{
+    config::SyncSettings,
+    room::{LeaveOptions, LeaveOutcome, ParentSpace},
+    test_utils::mocks::MatrixMockServer,
+    Client,
+};
+use matrix_sdk_base::RoomState;
+use matrix_sdk_test::{
+    async_test, test_json, JoinedRoomBuilder, StateTestEvent, DEFAULT_TEST_ROOM_ID,
+};
 use once_cell::sync::Lazy;
-use ruma::{room_id, RoomId};
+use ruma::{events::StateEventType, owned_event_id, room_id, RoomId, SpaceChildOrder};
 use serde_json::{json, Value as JsonValue};
 use wiremock::{
     matchers::{header, method, path_regex},
@@ -405,3 +413,163 @@ async fn test_parent_space_powerlevel_too_low() {
     assert_let!(ParentSpace::Illegitimate(space) = spaces.first().unwrap());
     assert_eq!(space.room_id(), *DEFAULT_TEST_SPACE_ID);
 }
+
+#[async_test]
+async fn test_add_space_child() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let space = server.sync_joined_room(&client, &DEFAULT_TEST_SPACE_ID).await;
+
+    // The current user is allowed to send state events to the child room.
+    let child_room_id = room_id!("!child:localhost");
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(child_room_id).add_state_event(StateTestEvent::PowerLevels),
+        )
+        .await;
+
+    server
+        .mock_room_send_state()
+        .for_type(StateEventType::SpaceChild)
+        .body_matches_partial_json(json!({
+            "via": ["localhost"],
+            "order": "a",
+            "suggested": true,
+        }))
+        .ok(owned_event_id!("$child"))
+        .mock_once()
+        .mount()
+        .await;
+    server
+        .mock_room_send_state()
+        .for_type(StateEventType::SpaceParent)
+        .body_matches_partial_json(json!({ "via": ["localhost"] }))
+        .ok(owned_event_id!("$parent"))
+        .mock_once()
+        .mount()
+        .await;
+
+    space
+        .add_space_child(child_room_id, Some(SpaceChildOrder::parse("a").unwrap()), true)
+        .await
+        .unwrap();
+}
+
+#[async_test]
+async fn test_space_child_order_between() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let child_event = |child: &str, order: Option<&str>| {
+        let mut content = json!({ "via": ["localhost"] });
+        if let Some(order) = order {
+            content["order"] = order.into();
+        }
+
+        StateTestEvent::Custom(json!({
+            "content": content,
+            "event_id": format!("$event_{}", child.trim_start_matches('!')),
+            "origin_server_ts": 1432735824653_u64,
+            "sender": "@example:localhost",
+            "state_key": child,
+            "type": "m.space.child",
+        }))
+    };
+
+    let first = room_id!("!first:localhost");
+    let second = room_id!("!second:localhost");
+    let unordered = room_id!("!unordered:localhost");
+
+    let space = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(&DEFAULT_TEST_SPACE_ID)
+                .add_state_event(child_event(first.as_str(), Some("a")))
+                .add_state_event(child_event(second.as_str(), Some("c")))
+                .add_state_event(child_event(unordered.as_str(), None)),
+        )
+        .await;
+
+    let order = space.space_child_order_between(Some(first), Some(second)).await.unwrap();
+    assert_eq!(order.as_ref().map(|order| order.as_str()), Some("b"));
+
+    let order = space.space_child_order_between(None, Some(first)).await.unwrap();
+    assert!(order.is_some_and(|order| order.as_str() < "a"));
+
+    let order = space.space_child_order_between(Some(second), None).await.unwrap();
+    assert!(order.is_some_and(|order| order.as_str() > "c"));
+
+    // A child can't be ordered after a child without order.
+    let order = space.space_child_order_between(Some(unordered), None).await.unwrap();
+    assert_eq!(order, None);
+}
+
+#[async_test]
+async fn test_remove_unknown_space_child() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let space = server.sync_joined_room(&client, &DEFAULT_TEST_SPACE_ID).await;
+
+    server
+        .mock_room_send_state()
+        .for_type(StateEventType::SpaceChild)
+        .ok(owned_event_id!("$child"))
+        .mock_once()
+        .mount()
+        .await;
+    // The child room isn't known, so its parent can't be removed.
+    server
+        .mock_room_send_state()
+        .for_type(StateEventType::SpaceParent)
+        .ok(owned_event_id!("$parent"))
+        .never()
+        .mount()
+        .await;
+
+    space.remove_space_child(room_id!("!child:localhost")).await.unwrap();
+}
+
+#[async_test]
+async fn test_leave_space() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let child_room_id = room_id!("!child:localhost");
+    let child = server.sync_joined_room(&client, child_room_id).await;
+
+    let child_event = json!({
+        "content": {
+            "via": ["localhost"],
+        },
+        "event_id": "$child:localhost",
+        "origin_server_ts": 1432735824653_u64,
+        "sender": "@example:localhost",
+        "state_key": child_room_id,
+        "type": "m.space.child",
+    });
+    let space = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(&DEFAULT_TEST_SPACE_ID)
+                .add_state_event(StateTestEvent::Custom(child_event)),
+        )
+        .await;
+
+    server.mock_room_leave().ok(child_room_id).expect(2).mount().await;
+    server.mock_room_forget().ok().never().mount().await;
+
+    let results = space.leave_space(LeaveOptions::default()).await.unwrap();
+
+    // The child room is left first, then the space itself.
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].0, child_room_id);
+    assert_let!(Ok(LeaveOutcome::Left) = &results[0].1);
+    assert_eq!(results[1].0, *DEFAULT_TEST_SPACE_ID);
+    assert_let!(Ok(LeaveOutcome::Left) = &results[1].1);
+
+    assert_eq!(child.state(), RoomState::Left);
+    assert_eq!(space.state(), RoomState::Left);
+}
@@ -11,7 +11,7 @@ use matrix_sdk::{
     room::reply::Reply,
     send_queue::{
         LocalEcho, LocalEchoContent, RoomSendQueue, RoomSendQueueError, RoomSendQueueStorageError,
-        RoomSendQueueUpdate, SendHandle,
+        RoomSendQueueUpdate, SendGroup, SendGroupItemStatus, SendHandle,
     },
     test_utils::mocks::{MatrixMock, MatrixMockServer},
     Client, MemoryStore,
@@ -34,6 +34,7 @@ use ruma::{
                 ImageMessageEventContent, MessageType, Relation, ReplyWithinThread,
                 RoomMessageEventContent,
             },
+            topic::RoomTopicEventContent,
             MediaSource,
         },
         AnyMessageLikeEventContent, EventContent as _, Mentions,
@@ -737,6 +738,68 @@ async fn test_disjoint_enabled_status() {
     assert!(room2.send_queue().is_enabled());
 }
 
+#[async_test]
+async fn test_clear_unsent_in_room() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the rooms as joined.
+    let room_id1 = room_id!("!a:b.c");
+    let room_id2 = room_id!("!b:b.c");
+
+    let client = mock.client_builder().build().await;
+    let room1 = mock.sync_joined_room(&client, room_id1).await;
+    let room2 = mock.sync_joined_room(&client, room_id2).await;
+
+    // Nothing should be sent from the first room.
+    mock.mock_room_send().ok(event_id!("$1")).expect(0).mount().await;
+
+    let q1 = room1.send_queue();
+    let mut enabled = q1.subscribe_enabled();
+    assert!(enabled.get());
+
+    // When I pause the send queue of the first room only,
+    q1.set_enabled(false);
+
+    // Observers are told about it,
+    assert_eq!(enabled.next().await, Some(false));
+
+    // And it doesn't touch the state of the other room.
+    assert!(client.send_queue().is_enabled());
+    assert!(room2.send_queue().is_enabled());
+
+    let (local_echoes, mut watch) = q1.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+
+    // Queued messages aren't sent.
+    let handle = q1.send(RoomMessageEventContent::text_plain("msg1").into()).await.unwrap();
+    q1.send(RoomMessageEventContent::text_plain("msg2").into()).await.unwrap();
+    handle.react("👍".to_owned()).await.unwrap().unwrap();
+
+    let (txn1, _) = assert_update!(watch => local echo { body = "msg1" });
+    let (txn2, _) = assert_update!(watch => local echo { body = "msg2" });
+    let reaction = assert_update!(watch => local reaction { key = "👍", parent = txn1 });
+    assert!(watch.is_empty());
+
+    // When I clear the unsent requests of the room,
+    assert_eq!(q1.clear_unsent().await.unwrap(), 3);
+
+    // All the local echoes are cancelled.
+    assert_update!(watch => cancelled { txn = txn1 });
+    assert_update!(watch => cancelled { txn = txn2 });
+    assert_update!(watch => cancelled { txn = reaction });
+    assert!(watch.is_empty());
+
+    let (local_echoes, _) = q1.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+
+    // Resuming the queue doesn't send anything, and observers are told about it.
+    q1.set_enabled(true);
+    assert_eq!(enabled.next().await, Some(true));
+
+    sleep(Duration::from_millis(100)).await;
+    assert!(watch.is_empty());
+}
+
 #[async_test]
 async fn test_cancellation() {
     let mock = MatrixMockServer::new().await;
@@ -1134,6 +1197,7 @@ async fn test_edit_while_being_sent_and_fails() {
 
     // Receiving updates for local echoes.
     let (txn1, _) = assert_update!(watch => local echo { body = "yo" });
+    assert_eq!(handle.transaction_id(), &*txn1);
     assert!(watch.is_empty());
 
     // Let the background task start now.
@@ -3543,3 +3607,349 @@ async fn test_update_caption_while_sending_media_event() {
     // That's all, folks!
     assert!(watch.is_empty());
 }
+
+#[async_test]
+async fn test_send_group() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+    assert!(watch.is_empty());
+
+    // An empty group can't be sent.
+    assert_matches!(q.send_group(SendGroup::new()).await, Err(RoomSendQueueError::EmptyGroup));
+
+    mock.mock_room_state_encryption().plain().mount().await;
+    mock.mock_room_send_state().ok(event_id!("$topic")).mock_once().mount().await;
+    mock.mock_room_send().ok(event_id!("$welcome")).mock_once().mount().await;
+
+    // When I send a group with a state event, then a message,
+    let mut group = SendGroup::new();
+    group
+        .add_state_event("", RoomTopicEventContent::new("new topic".to_owned()))
+        .unwrap()
+        .add_message(RoomMessageEventContent::text_plain("welcome").into())
+        .unwrap();
+    assert_eq!(group.len(), 2);
+
+    let handle = q.send_group(group).await.unwrap();
+    let txns = handle.transaction_ids().to_owned();
+    assert_eq!(txns.len(), 2);
+
+    // Only the message has a local echo.
+    let (txn, _) = assert_update!(watch => local echo { body = "welcome" });
+    assert_eq!(txn, txns[1]);
+    assert!(watch.is_empty());
+
+    // The message is held until the state event has been sent.
+    assert_matches!(
+        handle.status().await.unwrap().as_slice(),
+        [SendGroupItemStatus::Queued, SendGroupItemStatus::Held]
+    );
+
+    // Let the background task start now.
+    yield_now().await;
+
+    // Both are sent, in order.
+    assert_update!(watch => sent { txn = txns[0], event_id = event_id!("$topic") });
+    assert_update!(watch => sent { txn = txns[1], event_id = event_id!("$welcome") });
+    assert!(watch.is_empty());
+
+    assert!(handle.is_done().await.unwrap());
+}
+
+#[async_test]
+async fn test_send_group_holds_requests_after_failure() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+    assert!(watch.is_empty());
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    // The state event can't be sent, and the message must never be sent.
+    mock.mock_room_send_state().error_too_large().mock_once().mount().await;
+    mock.mock_room_send().ok(event_id!("$welcome")).expect(0).mount().await;
+
+    let mut group = SendGroup::new();
+    group
+        .add_state_event("", RoomTopicEventContent::new("new topic".to_owned()))
+        .unwrap()
+        .add_message(RoomMessageEventContent::text_plain("welcome").into())
+        .unwrap();
+
+    let handle = q.send_group(group).await.unwrap();
+    let txns = handle.transaction_ids().to_owned();
+
+    let (txn, _) = assert_update!(watch => local echo { body = "welcome" });
+    assert_eq!(txn, txns[1]);
+
+    // Let the background task start now.
+    yield_now().await;
+
+    // The state event is wedged, and the message is held.
+    assert_update!(watch => error { recoverable = false, txn = txns[0] });
+    assert!(watch.is_empty());
+
+    assert_matches!(
+        handle.status().await.unwrap().as_slice(),
+        [SendGroupItemStatus::Wedged(_), SendGroupItemStatus::Held]
+    );
+
+    // The message is still held after a while.
+    sleep(Duration::from_millis(300)).await;
+    assert!(watch.is_empty());
+
+    // Aborting the group removes both requests.
+    assert!(handle.abort().await.unwrap());
+
+    assert_update!(watch => cancelled { txn = txns[0] });
+    assert_update!(watch => cancelled { txn = txns[1] });
+    assert!(watch.is_empty());
+
+    assert!(handle.is_done().await.unwrap());
+    assert!(q.subscribe().await.unwrap().0.is_empty());
+}
+
+#[async_test]
+async fn test_send_group_edit_held_request() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+    assert!(watch.is_empty());
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    // The first message can't be sent.
+    mock.mock_room_send().error_too_large().mock_once().mount().await;
+
+    let mut group = SendGroup::new();
+    group
+        .add_message(RoomMessageEventContent::text_plain("first").into())
+        .unwrap()
+        .add_message(RoomMessageEventContent::text_plain("second").into())
+        .unwrap();
+
+    let handle = q.send_group(group).await.unwrap();
+    let txns = handle.transaction_ids().to_owned();
+
+    assert_update!(watch => local echo { body = "first" });
+    let (txn, second_handle) = assert_update!(watch => local echo { body = "second" });
+    assert_eq!(txn, txns[1]);
+
+    // Let the background task start now.
+    yield_now().await;
+
+    assert_update!(watch => error { recoverable = false, txn = txns[0] });
+    assert!(watch.is_empty());
+
+    // When I edit the held message,
+    let edited = second_handle
+        .edit(RoomMessageEventContent::text_plain("second, edited").into())
+        .await
+        .unwrap();
+    assert!(edited);
+
+    assert_update!(watch => edit { body = "second, edited", txn = txns[1] });
+
+    // It's still held.
+    assert_matches!(
+        handle.status().await.unwrap().as_slice(),
+        [SendGroupItemStatus::Wedged(_), SendGroupItemStatus::Held]
+    );
+
+    // And once the first message is unwedged, the edited content is sent.
+    mock.mock_room_send()
+        .body_matches_partial_json(json!({ "body": "first" }))
+        .ok(event_id!("$first"))
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_room_send()
+        .body_matches_partial_json(json!({ "body": "second, edited" }))
+        .ok(event_id!("$second"))
+        .mock_once()
+        .mount()
+        .await;
+
+    q.set_enabled(true);
+    handle.unwedge().await.unwrap();
+
+    assert_update!(watch => retry { txn = txns[0] });
+    assert_update!(watch => sent { txn = txns[0], event_id = event_id!("$first") });
+    assert_update!(watch => sent { txn = txns[1], event_id = event_id!("$second") });
+    assert!(watch.is_empty());
+
+    assert!(handle.is_done().await.unwrap());
+}
+
+#[async_test]
+async fn test_send_group_abort_held_requests() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+    assert!(watch.is_empty());
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    // The first message can't be sent.
+    mock.mock_room_send().error_too_large().mock_once().mount().await;
+
+    let mut group = SendGroup::new();
+    group
+        .add_message(RoomMessageEventContent::text_plain("first").into())
+        .unwrap()
+        .add_message(RoomMessageEventContent::text_plain("second").into())
+        .unwrap()
+        .add_message(RoomMessageEventContent::text_plain("third").into())
+        .unwrap();
+
+    let handle = q.send_group(group).await.unwrap();
+    let txns = handle.transaction_ids().to_owned();
+
+    let (_, first_handle) = assert_update!(watch => local echo { body = "first" });
+    let (_, second_handle) = assert_update!(watch => local echo { body = "second" });
+    assert_update!(watch => local echo { body = "third" });
+
+    // Let the background task start now.
+    yield_now().await;
+
+    assert_update!(watch => error { recoverable = false, txn = txns[0] });
+    assert!(watch.is_empty());
+
+    // When I abort a held message in the middle of the group, the next one is still
+    // held.
+    assert!(second_handle.abort().await.unwrap());
+    assert_update!(watch => cancelled { txn = txns[1] });
+
+    assert_matches!(
+        handle.status().await.unwrap().as_slice(),
+        [SendGroupItemStatus::Wedged(_), SendGroupItemStatus::Done, SendGroupItemStatus::Held]
+    );
+
+    // When I abort the head of the group, the next message isn't held anymore.
+    assert!(first_handle.abort().await.unwrap());
+    assert_update!(watch => cancelled { txn = txns[0] });
+
+    assert_matches!(
+        handle.status().await.unwrap().as_slice(),
+        [SendGroupItemStatus::Done, SendGroupItemStatus::Done, SendGroupItemStatus::Queued]
+    );
+
+    // So it's sent once the queue is enabled again.
+    mock.mock_room_send()
+        .body_matches_partial_json(json!({ "body": "third" }))
+        .ok(event_id!("$third"))
+        .mock_once()
+        .mount()
+        .await;
+
+    q.set_enabled(true);
+
+    assert_update!(watch => sent { txn = txns[2], event_id = event_id!("$third") });
+    assert!(watch.is_empty());
+
+    assert!(handle.is_done().await.unwrap());
+}
+
+#[cfg(feature = "image-proc")]
+#[async_test]
+async fn test_low_bandwidth_mode_downscales_images() {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, ImageFormat, RgbaImage};
+
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    mock.mock_authenticated_media_config().ok_default().mount().await;
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    client.set_low_bandwidth_mode(true);
+    let max_dimension = client.bandwidth_profile().max_image_dimension.unwrap();
+
+    let q = room.send_queue();
+    let (_, mut watch) = q.subscribe().await.unwrap();
+
+    // Delay the upload, so the media stays in the local cache of the send queue.
+    mock.mock_upload()
+        .expect_mime_type("image/png")
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "content_uri": "mxc://sdk.rs/media" }))
+                .set_delay(Duration::from_secs(60)),
+        )
+        .mock_once()
+        .mount()
+        .await;
+
+    let mut data = Vec::new();
+    DynamicImage::ImageRgba8(RgbaImage::new(max_dimension * 2, max_dimension))
+        .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+        .unwrap();
+
+    let config = AttachmentConfig::new().info(AttachmentInfo::Image(BaseImageInfo {
+        width: Some((max_dimension * 2).into()),
+        height: Some(max_dimension.into()),
+        ..Default::default()
+    }));
+    q.send_attachment("image.png", mime::IMAGE_PNG, data, config)
+        .await
+        .expect("queuing the attachment works");
+
+    // The local echo has the dimensions of the downscaled image.
+    let (_, _, content) = assert_update!(watch => local echo event);
+    assert_let!(MessageType::Image(img_content) = content.msgtype);
+
+    let info = img_content.info.unwrap();
+    assert_eq!(info.width, Some(max_dimension.into()));
+    assert_eq!(info.height, Some((max_dimension / 2).into()));
+
+    // And the downscaled image is the one which will be uploaded.
+    let file_media = client
+        .media()
+        .get_media_content(
+            &MediaRequestParameters { source: img_content.source, format: MediaFormat::File },
+            true,
+        )
+        .await
+        .expect("media should be found");
+    let image = image::load_from_memory_with_format(&file_media, ImageFormat::Png).unwrap();
+    assert_eq!(image.width(), max_dimension);
+    assert_eq!(image.height(), max_dimension / 2);
+}
@@ -15,11 +15,16 @@
 use std::{pin::pin, time::Duration};
 
 use assert_matches::assert_matches;
-use futures_util::FutureExt;
+use futures_util::{FutureExt, StreamExt};
 use matrix_sdk::{
-    test_utils::mocks::{MatrixMockServer, RoomMessagesResponseTemplate},
+    encryption::LocalTrust,
+    test_utils::{
+        mocks::{MatrixMockServer, RoomMessagesResponseTemplate},
+        widget::WidgetConformanceHarness,
+    },
     widget::{
-        Capabilities, CapabilitiesProvider, WidgetDriver, WidgetDriverHandle, WidgetSettings,
+        Capabilities, CapabilitiesProvider, RoomWidgetError, RoomWidgetEventContent, WidgetDriver,
+        WidgetDriverHandle, WidgetSettings,
     },
     Client,
 };
@@ -32,7 +37,7 @@ use ruma::{
     event_id,
     events::{
         room::{member::MembershipState, message::RoomMessageEventContent},
-        AnySyncStateEvent, MessageLikeEventType, StateEventType,
+        AnySyncStateEvent, AnyToDeviceEvent, MessageLikeEventType, StateEventType,
     },
     owned_room_id, room_id,
     serde::{JsonObject, Raw},
@@ -42,7 +47,7 @@ use serde::Serialize;
 use serde_json::{json, Value as JsonValue};
 use tracing::error;
 use wiremock::{
-    matchers::{method, path_regex},
+    matchers::{method, path_regex, query_param, query_param_is_missing},
     Mock, ResponseTemplate,
 };
 
@@ -215,6 +220,29 @@ async fn test_negotiate_capabilities_immediately() {
     assert_matches!(driver_handle.recv().now_or_never(), None);
 }
 
+#[async_test]
+async fn test_conformance_harness() {
+    let (_, _, driver_handle) = run_test_driver(false, false).await;
+
+    let harness = WidgetConformanceHarness::new(WIDGET_ID, driver_handle);
+    let approved = harness
+        .run_all(&[
+            "org.matrix.msc2762.receive.event:m.room.message",
+            "org.matrix.msc2762.send.event:org.example.custom",
+        ])
+        .await;
+
+    assert_eq!(
+        approved,
+        [
+            "org.matrix.msc2762.receive.event:m.room.message",
+            "org.matrix.msc2762.send.event:org.example.custom",
+        ]
+    );
+
+    harness.widget().assert_no_message().await;
+}
+
 static HELLO_EVENT: Lazy<JsonValue> = Lazy::new(|| {
     json!({
         "content": {
@@ -814,6 +842,128 @@ async fn test_receive_state() {
     assert_eq!(msg["data"]["content"]["name"], "even newer room name");
 }
 
+#[async_test]
+async fn test_receive_state_from_the_timeline() {
+    let (client, mock_server, driver_handle) = run_test_driver(false, false).await;
+
+    let f = EventFactory::new().room(&ROOM_ID);
+
+    negotiate_capabilities(
+        &driver_handle,
+        json!(["org.matrix.msc2762.receive.state_event:m.room.name#"]),
+    )
+    .await;
+
+    // The initial state is empty.
+    assert_state_synced(&driver_handle, json!([])).await;
+
+    let name_event_1: Raw<AnySyncStateEvent> = f.room_name("room name").sender(&BOB).into();
+
+    mock_server
+        .mock_sync()
+        .ok_and_run(&client, |sync_builder| {
+            sync_builder.add_joined_room(
+                JoinedRoomBuilder::new(&ROOM_ID)
+                    // set room name - only in the state section
+                    .add_state_event(name_event_1)
+                    // set room name - only in the timeline, more recent than the state section
+                    .add_timeline_event(f.room_name("new room name").sender(&BOB)),
+            );
+        })
+        .await;
+
+    // The driver sends the timeline event, and a state update with the most recent
+    // room name only.
+    let msg1 = recv_message(&driver_handle).await;
+    let msg2 = recv_message(&driver_handle).await;
+    assert_matches!(recv_message(&driver_handle).now_or_never(), None);
+
+    let (update_state, send_events): (Vec<_>, _) =
+        [msg1, msg2].into_iter().partition(|msg| msg["action"] == "update_state");
+    assert_eq!(update_state.len(), 1);
+    assert_eq!(send_events.len(), 1);
+
+    let msg = &update_state[0];
+    assert_eq!(msg["data"]["state"].as_array().unwrap().len(), 1);
+    assert_eq!(msg["data"]["state"][0]["type"], "m.room.name");
+    assert_eq!(msg["data"]["state"][0]["room_id"], ROOM_ID.as_str());
+    assert_eq!(msg["data"]["state"][0]["content"]["name"], "new room name");
+
+    let msg = &send_events[0];
+    assert_eq!(msg["data"]["type"], "m.room.name");
+    assert_eq!(msg["data"]["content"]["name"], "new room name");
+}
+
+#[async_test]
+async fn test_receive_state_with_origin() {
+    let (client, mock_server, driver_handle) = run_test_driver(false, false).await;
+
+    let f = EventFactory::new().room(&ROOM_ID);
+
+    negotiate_capabilities(
+        &driver_handle,
+        json!([
+            "org.matrix.msc2762.receive.state_event:m.room.name#",
+            "org.matrix.msc2762.receive.state_event:m.room.topic#",
+        ]),
+    )
+    .await;
+
+    // The initial state is empty.
+    assert_state_synced(&driver_handle, json!([])).await;
+
+    let topic_event: Raw<AnySyncStateEvent> =
+        f.room_topic("room topic").sender(&BOB).event_id(event_id!("$topic")).into();
+    let name_event: Raw<AnySyncStateEvent> =
+        f.room_name("room name").sender(&BOB).event_id(event_id!("$name")).into();
+
+    mock_server
+        .mock_sync()
+        .ok_and_run(&client, |sync_builder| {
+            sync_builder.add_joined_room(
+                JoinedRoomBuilder::new(&ROOM_ID)
+                    // set room topic - only in the state section
+                    .add_state_event(topic_event)
+                    // set room name - in both the timeline and the state section
+                    .add_timeline_event(name_event.clone().cast())
+                    .add_state_event(name_event),
+            );
+        })
+        .await;
+
+    let msg1 = recv_message(&driver_handle).await;
+    let msg2 = recv_message(&driver_handle).await;
+    assert_matches!(recv_message(&driver_handle).now_or_never(), None);
+
+    let (update_state, send_events): (Vec<_>, _) =
+        [msg1, msg2].into_iter().partition(|msg| msg["action"] == "update_state");
+    assert_eq!(update_state.len(), 1);
+    assert_eq!(send_events.len(), 1);
+
+    // Each event of the state update tells which sections of the sync it comes
+    // from.
+    let state = update_state[0]["data"]["state"].as_array().unwrap();
+    assert_eq!(state.len(), 2);
+    assert_eq!(state[0]["type"], "m.room.topic");
+    assert_eq!(
+        state[0]["unsigned"]["io.element.origin"],
+        json!({ "timeline": false, "state": true })
+    );
+    assert_eq!(state[1]["type"], "m.room.name");
+    assert_eq!(
+        state[1]["unsigned"]["io.element.origin"],
+        json!({ "timeline": true, "state": true })
+    );
+
+    // New events come from the timeline.
+    let msg = &send_events[0];
+    assert_eq!(msg["data"]["type"], "m.room.name");
+    assert_eq!(
+        msg["data"]["unsigned"]["io.element.origin"],
+        json!({ "timeline": true, "state": false })
+    );
+}
+
 #[async_test]
 async fn test_send_room_message() {
     let (_, mock_server, driver_handle) = run_test_driver(false, false).await;
@@ -997,13 +1147,14 @@ async fn test_fail_sending_delay_rate_limit() {
     )
     .await;
 
+    // The first attempt, and the 3 retries.
     mock_server
         .mock_room_send()
         .respond_with(ResponseTemplate::new(400).set_body_json(json!({
             "errcode": "M_LIMIT_EXCEEDED",
             "error": "Sending too many delay events"
         })))
-        .mock_once()
+        .expect(4)
         .mount()
         .await;
 
@@ -1037,12 +1188,94 @@ async fn test_fail_sending_delay_rate_limit() {
                   "error": "Sending too many delay events"
                 },
               },
-              "message": "the server returned an error: [400 / M_LIMIT_EXCEEDED] Sending too many delay events"
+              "message": "the server returned an error: [400 / M_LIMIT_EXCEEDED] Sending too many delay events",
+              "rate_limit": {}
             }
         })
     );
 }
 
+#[async_test]
+async fn test_retry_rate_limited_send() {
+    let (_, mock_server, driver_handle) = run_test_driver(false, false).await;
+
+    negotiate_capabilities(&driver_handle, json!(["org.matrix.msc2762.send.event:m.room.message"]))
+        .await;
+
+    mock_server
+        .mock_room_send()
+        .respond_with(ResponseTemplate::new(429).set_body_json(json!({
+            "errcode": "M_LIMIT_EXCEEDED",
+            "error": "Too many requests",
+            "retry_after_ms": 10,
+        })))
+        .mock_once()
+        .mount()
+        .await;
+    mock_server.mock_room_send().ok(event_id!("$foobar")).mock_once().mount().await;
+
+    send_request(
+        &driver_handle,
+        "send-room-message",
+        "send_event",
+        json!({
+            "type": "m.room.message",
+            "content": {
+                "msgtype": "m.text",
+                "body": "Message from a widget!",
+            },
+        }),
+    )
+    .await;
+
+    // The send was retried after the delay requested by the server.
+    let msg = recv_message(&driver_handle).await;
+    assert_eq!(msg["action"], "send_event");
+    assert_eq!(msg["response"]["event_id"], "$foobar");
+}
+
+#[async_test]
+async fn test_fail_sending_after_rate_limit_retries() {
+    let (_, mock_server, driver_handle) = run_test_driver(false, false).await;
+
+    negotiate_capabilities(&driver_handle, json!(["org.matrix.msc2762.send.event:m.room.message"]))
+        .await;
+
+    // The first attempt, and the 3 retries.
+    mock_server
+        .mock_room_send()
+        .respond_with(ResponseTemplate::new(429).set_body_json(json!({
+            "errcode": "M_LIMIT_EXCEEDED",
+            "error": "Too many requests",
+            "retry_after_ms": 10,
+        })))
+        .expect(4)
+        .mount()
+        .await;
+
+    send_request(
+        &driver_handle,
+        "send-room-message",
+        "send_event",
+        json!({
+            "type": "m.room.message",
+            "content": {
+                "msgtype": "m.text",
+                "body": "Message from a widget!",
+            },
+        }),
+    )
+    .await;
+
+    // The rate limit is reported to the widget once the retries are exhausted.
+    let msg = recv_message(&driver_handle).await;
+    assert_eq!(msg["action"], "send_event");
+    let error = &msg["response"]["error"];
+    assert_eq!(error["matrix_api_error"]["http_status"], 429);
+    assert_eq!(error["matrix_api_error"]["response"]["errcode"], "M_LIMIT_EXCEEDED");
+    assert_eq!(error["rate_limit"], json!({ "retry_after_ms": 10 }));
+}
+
 #[async_test]
 async fn test_try_send_delayed_state_event_without_permission() {
     let (_, _mock_server, driver_handle) = run_test_driver(false, false).await;
@@ -1170,6 +1403,247 @@ async fn test_try_update_delayed_event_without_permission_negotiate() {
     }
 }
 
+/// The capabilities allowing the widget to send delayed `m.room.name` events,
+/// and to list and cancel them.
+fn delayed_events_capabilities() -> JsonValue {
+    json!([
+        "org.matrix.msc4157.send.delayed_event",
+        "org.matrix.msc4157.update_delayed_event",
+        "org.matrix.msc2762.send.state_event:m.room.name#",
+    ])
+}
+
+/// Make the widget send a delayed `m.room.name` event, which gets the given
+/// delay ID.
+async fn send_delayed_event(
+    mock_server: &MatrixMockServer,
+    driver_handle: &WidgetDriverHandle,
+    delay_id: &str,
+) {
+    let _guard = mock_server
+        .mock_room_send_state()
+        .match_delayed_event(Duration::from_millis(1000))
+        .for_type(StateEventType::RoomName)
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "delay_id": delay_id })))
+        .mock_once()
+        .mount_as_scoped()
+        .await;
+
+    send_request(
+        driver_handle,
+        "send-delayed-event",
+        "send_event",
+        json!({
+            "type": "m.room.name",
+            "state_key": "",
+            "content": { "name": "Delayed name" },
+            "delay": 1000,
+        }),
+    )
+    .await;
+
+    let msg = recv_message(driver_handle).await;
+    assert_eq!(msg["response"]["delay_id"], delay_id);
+}
+
+fn delayed_event(delay_id: &str, room_id: &str) -> JsonValue {
+    json!({
+        "delay_id": delay_id,
+        "room_id": room_id,
+        "type": "m.room.name",
+        "state_key": "",
+        "delay": 1000,
+        "running_since": 1721732853284_u64,
+        "content": { "name": "Delayed name" },
+    })
+}
+
+#[async_test]
+async fn test_get_delayed_events() {
+    let (_, mock_server, driver_handle) = run_test_driver(false, false).await;
+
+    negotiate_capabilities(&driver_handle, delayed_events_capabilities()).await;
+
+    send_delayed_event(&mock_server, &driver_handle, "1").await;
+    send_delayed_event(&mock_server, &driver_handle, "3").await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/unstable/org.matrix.msc4140/delayed_events$"))
+        .and(query_param_is_missing("from"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "delayed_events": [
+                delayed_event("1", ROOM_ID.as_str()),
+                // Sent in another room.
+                delayed_event("2", "!another:example.org"),
+            ],
+            "next_batch": "page2",
+        })))
+        .expect(1)
+        .mount(mock_server.server())
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/unstable/org.matrix.msc4140/delayed_events$"))
+        .and(query_param("from", "page2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "delayed_events": [
+                delayed_event("3", ROOM_ID.as_str()),
+                // Sent in the room, but not by the widget.
+                delayed_event("4", ROOM_ID.as_str()),
+            ],
+        })))
+        .expect(1)
+        .mount(mock_server.server())
+        .await;
+
+    send_request(
+        &driver_handle,
+        "get-delayed-events",
+        "org.matrix.msc4157.get_delayed_events",
+        json!({}),
+    )
+    .await;
+
+    let response = recv_message(&driver_handle).await;
+    assert_eq!(response["api"], "fromWidget");
+    assert_eq!(response["action"], "org.matrix.msc4157.get_delayed_events");
+
+    // All the pages are fetched, and only the delayed events sent by the widget in
+    // the room are returned.
+    let delayed_events = response["response"]["delayed_events"].as_array().unwrap();
+    assert_eq!(delayed_events.len(), 2);
+    assert_eq!(delayed_events[0], delayed_event("1", ROOM_ID.as_str()));
+    assert_eq!(delayed_events[1], delayed_event("3", ROOM_ID.as_str()));
+}
+
+#[async_test]
+async fn test_get_delayed_events_stops_after_too_many_pages() {
+    let (_, mock_server, driver_handle) = run_test_driver(false, false).await;
+
+    negotiate_capabilities(&driver_handle, delayed_events_capabilities()).await;
+
+    send_delayed_event(&mock_server, &driver_handle, "1").await;
+
+    // The homeserver always returns another page.
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/unstable/org.matrix.msc4140/delayed_events$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "delayed_events": [delayed_event("1", ROOM_ID.as_str())],
+            "next_batch": "next",
+        })))
+        .expect(10)
+        .mount(mock_server.server())
+        .await;
+
+    send_request(
+        &driver_handle,
+        "get-delayed-events",
+        "org.matrix.msc4157.get_delayed_events",
+        json!({}),
+    )
+    .await;
+
+    let response = recv_message(&driver_handle).await;
+    assert_eq!(response["action"], "org.matrix.msc4157.get_delayed_events");
+    assert_eq!(response["response"]["delayed_events"].as_array().unwrap().len(), 10);
+}
+
+#[async_test]
+async fn test_cancel_delayed_events() {
+    let (_, mock_server, driver_handle) = run_test_driver(false, false).await;
+
+    negotiate_capabilities(&driver_handle, delayed_events_capabilities()).await;
+
+    send_delayed_event(&mock_server, &driver_handle, "1").await;
+    send_delayed_event(&mock_server, &driver_handle, "2").await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/unstable/org.matrix.msc4140/delayed_events/1$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(mock_server.server())
+        .await;
+
+    // The second delayed event has already been sent, which isn't an error.
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/unstable/org.matrix.msc4140/delayed_events/2$"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_NOT_FOUND",
+            "error": "Unknown delayed event",
+        })))
+        .expect(1)
+        .mount(mock_server.server())
+        .await;
+
+    send_request(
+        &driver_handle,
+        "cancel-delayed-events",
+        "org.matrix.msc4157.cancel_delayed_events",
+        json!({ "delay_ids": ["1", "2"] }),
+    )
+    .await;
+
+    let response = recv_message(&driver_handle).await;
+    assert_eq!(response["api"], "fromWidget");
+    assert_eq!(response["action"], "org.matrix.msc4157.cancel_delayed_events");
+    assert_eq!(response["response"], json!({}));
+}
+
+#[async_test]
+async fn test_try_cancel_delayed_events_of_someone_else() {
+    let (_, mock_server, driver_handle) = run_test_driver(false, false).await;
+
+    negotiate_capabilities(&driver_handle, delayed_events_capabilities()).await;
+
+    send_delayed_event(&mock_server, &driver_handle, "1").await;
+
+    // Nothing is cancelled if one of the delayed events wasn't sent by the widget.
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/unstable/org.matrix.msc4140/delayed_events/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(0)
+        .mount(mock_server.server())
+        .await;
+
+    send_request(
+        &driver_handle,
+        "cancel-delayed-events",
+        "org.matrix.msc4157.cancel_delayed_events",
+        json!({ "delay_ids": ["1", "2"] }),
+    )
+    .await;
+
+    let response = recv_message(&driver_handle).await;
+    assert_eq!(response["action"], "org.matrix.msc4157.cancel_delayed_events");
+    assert_eq!(
+        response["response"]["error"]["message"].as_str().unwrap(),
+        "Not allowed: the delayed event 2 wasn't sent by the widget in this room."
+    );
+}
+
+#[async_test]
+async fn test_try_cancel_delayed_events_without_permission() {
+    let (_, _mock_server, driver_handle) = run_test_driver(false, false).await;
+
+    negotiate_capabilities(&driver_handle, json!([])).await;
+
+    send_request(
+        &driver_handle,
+        "cancel-delayed-events",
+        "org.matrix.msc4157.cancel_delayed_events",
+        json!({ "delay_ids": ["1"] }),
+    )
+    .await;
+
+    let response = recv_message(&driver_handle).await;
+    assert_eq!(response["api"], "fromWidget");
+    assert_eq!(response["action"], "org.matrix.msc4157.cancel_delayed_events");
+    assert_eq!(
+        response["response"]["error"]["message"].as_str().unwrap(),
+        "Not allowed: missing the org.matrix.msc4157.update_delayed_event capability."
+    );
+}
+
 #[async_test]
 async fn test_send_redaction() {
     let (_, mock_server, driver_handle) = run_test_driver(false, false).await;
@@ -1300,12 +1774,311 @@ async fn test_send_encrypted_to_device_event() {
                 },
             }
         }),
-        json! {{"error":{"message":"Sending encrypted to-device events is not supported by the widget driver."}}},
+        json! {{"error":{"message":"the to-device message couldn't be sent to the devices: @username:test.org (DEVICEID)"}}},
         0,
     )
     .await;
 }
 
+#[async_test]
+async fn test_send_encrypted_to_device_event_is_received() {
+    let (alice, bob, mock_server, driver_handle) = run_test_driver_e2e(false).await;
+
+    negotiate_capabilities(
+        &driver_handle,
+        json!(["org.matrix.msc3819.send.to_device:my.custom.to.device"]),
+    )
+    .await;
+
+    let (received_tx, received_rx) = tokio::sync::oneshot::channel();
+    let received_tx = std::sync::Mutex::new(Some(received_tx));
+    bob.add_event_handler(move |raw: Raw<AnyToDeviceEvent>| {
+        if let Some(tx) = received_tx.lock().unwrap().take() {
+            let _ = tx.send(raw);
+        }
+        async {}
+    });
+
+    let event_synced_future =
+        mock_server.mock_capture_put_to_device_then_sync_back(alice.user_id().unwrap(), &bob).await;
+
+    send_request(
+        &driver_handle,
+        "send-encrypted-to-device",
+        "send_to_device",
+        json!({
+            "type": "my.custom.to.device",
+            "encrypted": true,
+            "messages": {
+                bob.user_id().unwrap().to_string(): {
+                    bob.device_id().unwrap().to_string(): {
+                        "call_id": "1234",
+                    },
+                },
+            }
+        }),
+    )
+    .await;
+
+    let msg = recv_message(&driver_handle).await;
+    assert_eq!(msg["api"], "fromWidget");
+    assert_eq!(msg["action"], "send_to_device");
+    assert_eq!(msg["response"], json!({}));
+
+    event_synced_future.await;
+
+    // Bob received and decrypted the message.
+    let received = received_rx.await.unwrap();
+    assert_eq!(received.get_field::<String>("type").unwrap().unwrap(), "my.custom.to.device");
+    assert_eq!(received.get_field::<JsonValue>("content").unwrap().unwrap()["call_id"], "1234");
+}
+
+#[async_test]
+async fn test_send_encrypted_to_device_event_to_blacklisted_device() {
+    let (alice, bob, mock_server, driver_handle) = run_test_driver_e2e(false).await;
+
+    negotiate_capabilities(
+        &driver_handle,
+        json!(["org.matrix.msc3819.send.to_device:my.custom.to.device"]),
+    )
+    .await;
+
+    let bob_device = alice
+        .encryption()
+        .get_device(bob.user_id().unwrap(), bob.device_id().unwrap())
+        .await
+        .unwrap()
+        .expect("Alice should know about Bob's device");
+    bob_device.set_local_trust(LocalTrust::BlackListed).await.unwrap();
+
+    // Nothing is sent to the blacklisted device.
+    mock_server.mock_send_to_device().ok().expect(0).mount().await;
+
+    send_request(
+        &driver_handle,
+        "send-encrypted-to-device",
+        "send_to_device",
+        json!({
+            "type": "my.custom.to.device",
+            "encrypted": true,
+            "messages": {
+                bob.user_id().unwrap().to_string(): {
+                    bob.device_id().unwrap().to_string(): {
+                        "call_id": "1234",
+                    },
+                },
+            }
+        }),
+    )
+    .await;
+
+    // And the widget is told about it.
+    let msg = recv_message(&driver_handle).await;
+    assert_eq!(msg["api"], "fromWidget");
+    assert_eq!(msg["action"], "send_to_device");
+    assert_eq!(
+        msg["response"]["error"]["message"].as_str().unwrap(),
+        format!(
+            "the to-device message couldn't be sent to the devices: {} ({})",
+            bob.user_id().unwrap(),
+            bob.device_id().unwrap()
+        )
+    );
+}
+
+#[async_test]
+async fn test_send_encrypted_to_device_event_skips_blacklisted_devices_of_all_devices() {
+    let (alice, bob, mock_server, driver_handle) = run_test_driver_e2e(false).await;
+
+    negotiate_capabilities(
+        &driver_handle,
+        json!(["org.matrix.msc3819.send.to_device:my.custom.to.device"]),
+    )
+    .await;
+
+    let bob_device = alice
+        .encryption()
+        .get_device(bob.user_id().unwrap(), bob.device_id().unwrap())
+        .await
+        .unwrap()
+        .expect("Alice should know about Bob's device");
+    bob_device.set_local_trust(LocalTrust::BlackListed).await.unwrap();
+
+    // Bob's only device is blacklisted, so nothing is sent.
+    mock_server.mock_send_to_device().ok().expect(0).mount().await;
+
+    send_request(
+        &driver_handle,
+        "send-encrypted-to-device",
+        "send_to_device",
+        json!({
+            "type": "my.custom.to.device",
+            "encrypted": true,
+            "messages": {
+                bob.user_id().unwrap().to_string(): {
+                    "*": {
+                        "call_id": "1234",
+                    },
+                },
+            }
+        }),
+    )
+    .await;
+
+    // Targeting all the devices of a user isn't a failure when some are filtered
+    // out.
+    let msg = recv_message(&driver_handle).await;
+    assert_eq!(msg["api"], "fromWidget");
+    assert_eq!(msg["action"], "send_to_device");
+    assert_eq!(msg["response"], json!({}));
+}
+
+#[async_test]
+async fn test_manage_room_widgets() {
+    let mock_server = MatrixMockServer::new().await;
+    let client = mock_server.client_builder().build().await;
+    let room = mock_server.sync_joined_room(&client, &ROOM_ID).await;
+
+    let widget_event = |widget_id: &str, content: JsonValue| -> Raw<AnySyncStateEvent> {
+        Raw::new(&json!({
+            "type": "im.vector.modular.widgets",
+            "state_key": widget_id,
+            "event_id": format!("$widget-{widget_id}"),
+            "sender": BOB.as_str(),
+            "origin_server_ts": 1234,
+            "content": content,
+        }))
+        .unwrap()
+        .cast()
+    };
+    let widget_content = |widget_id: &str| {
+        json!({
+            "id": widget_id,
+            "type": "m.custom",
+            "url": "https://example.org/widget",
+            "name": "My widget",
+            "creatorUserId": BOB.as_str(),
+        })
+    };
+
+    let widgets = room.widgets();
+    let mut subscriber = pin!(widgets.subscribe());
+
+    // There are no widgets initially.
+    assert!(subscriber.next().await.unwrap().is_empty());
+
+    mock_server
+        .mock_sync()
+        .ok_and_run(&client, |sync_builder| {
+            sync_builder.add_joined_room(
+                JoinedRoomBuilder::new(&ROOM_ID)
+                    .add_state_event(widget_event("b", widget_content("b")))
+                    .add_timeline_event(widget_event("a", widget_content("a"))),
+            );
+        })
+        .await;
+
+    // Both widgets are emitted, sorted by ID.
+    let list = subscriber.next().await.unwrap();
+    assert_eq!(list.len(), 2);
+    assert_eq!(list[0].content.id, "a");
+    assert_eq!(list[0].content.name.as_deref(), Some("My widget"));
+    assert_eq!(list[0].sender, *BOB);
+    assert_eq!(list[1].content.id, "b");
+
+    // A removed widget isn't listed anymore.
+    mock_server
+        .mock_sync()
+        .ok_and_run(&client, |sync_builder| {
+            sync_builder.add_joined_room(
+                JoinedRoomBuilder::new(&ROOM_ID).add_timeline_event(widget_event("a", json!({}))),
+            );
+        })
+        .await;
+
+    let list = subscriber.next().await.unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].content.id, "b");
+    assert_eq!(widgets.list().await.unwrap().len(), 1);
+
+    // Adding a widget sends its state event, once validated.
+    mock_server
+        .mock_room_send_state()
+        .for_type("im.vector.modular.widgets".into())
+        .for_key("c".to_owned())
+        .body_matches_partial_json(json!({ "type": "m.custom", "url": "https://example.org/c" }))
+        .ok(event_id!("$c"))
+        .mock_once()
+        .mount()
+        .await;
+
+    let content = RoomWidgetEventContent::new(
+        "c".to_owned(),
+        "m.custom".to_owned(),
+        "https://example.org/c".to_owned(),
+        client.user_id().unwrap().to_owned(),
+    );
+    assert_eq!(widgets.add(content.clone()).await.unwrap().event_id, event_id!("$c"));
+
+    let mut invalid_content = content;
+    invalid_content.url = "file:///etc/passwd".to_owned();
+    assert_matches!(
+        widgets.add(invalid_content).await,
+        Err(RoomWidgetError::UnsupportedUrlScheme(_))
+    );
+
+    // Removing a widget sends an empty state event.
+    mock_server
+        .mock_room_send_state()
+        .for_type("im.vector.modular.widgets".into())
+        .for_key("b".to_owned())
+        .body_matches_partial_json(json!({}))
+        .ok(event_id!("$b"))
+        .mock_once()
+        .mount()
+        .await;
+
+    widgets.remove("b").await.unwrap();
+}
+
+#[async_test]
+async fn test_pending_navigation_requests_are_bounded() {
+    let (_, _mock_server, driver_handle) = run_test_driver(false, false).await;
+
+    negotiate_capabilities(&driver_handle, json!(["org.matrix.msc2931.navigate"])).await;
+
+    let navigate = |request_id: String| {
+        let driver_handle = &driver_handle;
+        async move {
+            send_request(
+                driver_handle,
+                &request_id,
+                "org.matrix.msc2931.navigate",
+                json!({ "uri": "matrix:r/room:example.org" }),
+            )
+            .await;
+
+            let msg = recv_message(driver_handle).await;
+            assert_eq!(msg["requestId"], request_id);
+            msg["response"].clone()
+        }
+    };
+
+    // The client doesn't handle the navigation requests, so only a few of them are
+    // kept, and the next ones are rejected.
+    for i in 0..8 {
+        assert_eq!(navigate(format!("navigate-{i}")).await, json!({}));
+    }
+
+    let response = navigate("navigate-rejected".to_owned()).await;
+    let error_message = response["error"]["message"].as_str().unwrap();
+    assert!(error_message.contains("too many navigation requests are pending"), "{error_message}");
+
+    // Once the client handles a request, another one can be made.
+    assert!(driver_handle.recv_navigation_request().await.is_some());
+    assert_eq!(navigate("navigate-accepted".to_owned()).await, json!({}));
+}
+
 async fn negotiate_capabilities(driver_handle: &WidgetDriverHandle, caps: JsonValue) {
     {
         // Receive toWidget capabilities request
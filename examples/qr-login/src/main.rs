@@ -129,6 +129,9 @@ async fn login(proxy: Option<Url>) -> Result<()> {
                 LoginProgress::WaitingForToken { user_code } => {
                     println!("Please use your other device to confirm the log in {user_code}")
                 }
+                LoginProgress::SyncingSecrets => {
+                    println!("Waiting for the secrets from the other device")
+                }
                 LoginProgress::Done => break,
             }
         }
@@ -13,6 +13,7 @@ use color_eyre::Result;
 use crossterm::{
     event::{
         self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+        MouseButton, MouseEvent, MouseEventKind,
     },
     execute,
 };
@@ -25,18 +26,26 @@ use matrix_sdk::{
     config::StoreConfig,
     encryption::{BackupDownloadStrategy, EncryptionSettings},
     reqwest::Url,
-    ruma::OwnedRoomId,
+    ruma::{MatrixToUri, OwnedRoomId, OwnedRoomOrAliasId, matrix_uri::MatrixId},
 };
 use matrix_sdk_common::locks::Mutex;
 use matrix_sdk_ui::{
     Timeline as SdkTimeline,
-    room_list_service::{self, filters::new_filter_non_left},
+    room_list_service::{
+        self,
+        filters::{BoxedFilterFn, new_filter_non_left},
+    },
     sync_service::SyncService,
     timeline::{RoomExt as _, TimelineFocus, TimelineItem},
 };
 use ratatui::{prelude::*, style::palette::tailwind, widgets::*};
+use ratatui_image::picker::Picker;
 use throbber_widgets_tui::{Throbber, ThrobberState};
-use tokio::{spawn, task::JoinHandle};
+use tokio::{
+    spawn,
+    sync::mpsc::{UnboundedReceiver, unbounded_channel},
+    task::JoinHandle,
+};
 use tracing::{error, warn};
 use tracing_subscriber::EnvFilter;
 use widgets::{
@@ -47,6 +56,7 @@ use crate::widgets::{
     help::HelpView,
     room_list::{ExtraRoomInfo, RoomInfos, RoomList, Rooms},
     status::Status,
+    verification::VerificationView,
 };
 
 mod widgets;
@@ -82,6 +92,8 @@ pub enum GlobalMode {
     Help,
     /// Mode where we have opened the settings screen.
     Settings { view: SettingsView },
+    /// Mode where we are going through an incoming verification request.
+    Verification,
     /// Mode where we are shutting our tasks down and exiting multiverse.
     Exiting { shutdown_task: JoinHandle<()> },
 }
@@ -116,7 +128,16 @@ async fn main() -> Result<()> {
 
     let terminal = ratatui::init();
     execute!(stdout(), EnableMouseCapture)?;
-    let mut app = App::new(client).await?;
+
+    // Figure out which graphics protocol the terminal supports for the media
+    // previews, falling back to unicode blocks if it doesn't answer. This must
+    // happen before we start reading terminal events.
+    let picker = Picker::from_query_stdio().unwrap_or_else(|err| {
+        warn!("couldn't query the terminal's graphics capabilities: {err}");
+        Picker::from_fontsize((8, 16))
+    });
+
+    let mut app = App::new(client, picker).await?;
 
     app.run(terminal).await
 }
@@ -160,6 +181,9 @@ struct App {
     /// The status widget at the bottom of the screen.
     status: Status,
 
+    /// The verification popup, listening for incoming verification requests.
+    verification_view: VerificationView,
+
     state: AppState,
 
     last_tick: Instant,
@@ -168,7 +192,7 @@ struct App {
 impl App {
     const TICK_RATE: Duration = Duration::from_millis(250);
 
-    async fn new(client: Client) -> Result<Self> {
+    async fn new(client: Client, picker: Picker) -> Result<Self> {
         let sync_service = Arc::new(SyncService::builder(client.clone()).build().await?);
 
         let rooms = Rooms::default();
@@ -178,11 +202,14 @@ impl App {
         let room_list_service = sync_service.room_list_service();
         let all_rooms = room_list_service.all_rooms().await?;
 
+        let (filter_sender, filter_receiver) = unbounded_channel();
+
         let listen_task = spawn(Self::listen_task(
             rooms.clone(),
             room_infos.clone(),
             timelines.clone(),
             all_rooms,
+            filter_receiver,
         ));
 
         // This will sync (with encryption) until an error happens or the program is
@@ -190,10 +217,17 @@ impl App {
         sync_service.start().await;
 
         let status = Status::new();
-        let room_list =
-            RoomList::new(client.clone(), rooms, room_infos, sync_service.clone(), status.handle());
-
-        let room_view = RoomView::new(client.clone(), timelines.clone(), status.handle());
+        let room_list = RoomList::new(
+            client.clone(),
+            rooms,
+            room_infos,
+            sync_service.clone(),
+            filter_sender,
+            status.handle(),
+        );
+
+        let room_view = RoomView::new(client.clone(), timelines.clone(), status.handle(), picker);
+        let verification_view = VerificationView::new(client.clone());
 
         Ok(Self {
             sync_service,
@@ -203,6 +237,7 @@ impl App {
             client,
             listen_task,
             status,
+            verification_view,
             state: AppState::default(),
             last_tick: Instant::now(),
         })
@@ -213,6 +248,7 @@ impl App {
         room_infos: RoomInfos,
         timelines: Timelines,
         all_rooms: room_list_service::RoomList,
+        mut filter_receiver: UnboundedReceiver<BoxedFilterFn>,
     ) {
         let (stream, entries_controller) = all_rooms.entries_with_dynamic_adapters(50_000);
         entries_controller.set_filter(Box::new(new_filter_non_left()));
@@ -221,7 +257,21 @@ impl App {
 
         let mut previous_rooms = HashSet::new();
 
-        while let Some(diffs) = stream.next().await {
+        loop {
+            let diffs = tokio::select! {
+                Some(filter) = filter_receiver.recv() => {
+                    // The stream restarts with a reset of the entries matching the new filter.
+                    entries_controller.set_filter(filter);
+                    continue;
+                }
+
+                diffs = stream.next() => diffs,
+            };
+
+            let Some(diffs) = diffs else {
+                break;
+            };
+
             let all_rooms = {
                 // Apply the diffs to the list of room entries.
                 let mut rooms = rooms.lock();
@@ -310,6 +360,13 @@ impl App {
     async fn handle_global_event(&mut self, event: Event) -> Result<bool> {
         use KeyCode::*;
 
+        if self.room_list.is_editing_search() {
+            if let Event::Key(key) = event {
+                self.room_list.handle_search_key(key);
+            }
+            return Ok(false);
+        }
+
         match event {
             Event::Key(KeyEvent { code: F(1), modifiers: KeyModifiers::NONE, .. }) => {
                 self.set_global_mode(GlobalMode::Help)
@@ -342,6 +399,18 @@ impl App {
                 self.room_view.mark_as_read().await
             }
 
+            Event::Key(KeyEvent { code: Char('f'), modifiers: KeyModifiers::ALT, .. }) => {
+                self.room_list.cycle_filter()
+            }
+
+            Event::Key(KeyEvent { code: Char('o'), modifiers: KeyModifiers::ALT, .. }) => {
+                self.room_list.cycle_sort_mode()
+            }
+
+            Event::Key(KeyEvent { code: Char('n'), modifiers: KeyModifiers::ALT, .. }) => {
+                self.room_list.start_search()
+            }
+
             Event::Key(KeyEvent { code: Char('q'), modifiers: KeyModifiers::CONTROL, .. }) => {
                 if !matches!(self.state.global_mode, GlobalMode::Default) {
                     self.set_global_mode(GlobalMode::Default);
@@ -350,17 +419,82 @@ impl App {
                 }
             }
 
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                ..
+            }) => {
+                if let Some(room_id) = self.room_list.select_room_at(column, row) {
+                    self.room_view.set_selected_room(Some(room_id));
+                } else if let Some(uri) = self.room_view.matrix_to_link_at(column, row) {
+                    self.open_matrix_to_link(uri).await;
+                } else {
+                    self.room_view.handle_event(event).await;
+                }
+            }
+
             _ => self.room_view.handle_event(event).await,
         }
 
         Ok(false)
     }
 
+    /// Jump to the room, or the event, a matrix.to link points to.
+    async fn open_matrix_to_link(&mut self, uri: MatrixToUri) {
+        let (room_or_alias_id, event_id): (OwnedRoomOrAliasId, _) = match uri.id() {
+            MatrixId::Room(room_id) => (room_id.clone().into(), None),
+            MatrixId::RoomAlias(alias) => (alias.clone().into(), None),
+            MatrixId::Event(room_or_alias_id, event_id) => {
+                (room_or_alias_id.clone(), Some(event_id.clone()))
+            }
+            MatrixId::User(user_id) => {
+                self.status.set_message(format!("can't open links to users ({user_id})"));
+                return;
+            }
+            _ => {
+                self.status.set_message(format!("unsupported link: {uri}"));
+                return;
+            }
+        };
+
+        let room_id = match OwnedRoomId::try_from(room_or_alias_id) {
+            Ok(room_id) => room_id,
+            Err(alias) => match self.client.resolve_room_alias(&alias).await {
+                Ok(response) => response.room_id,
+                Err(err) => {
+                    self.status.set_message(format!("couldn't resolve {alias}: {err}"));
+                    return;
+                }
+            },
+        };
+
+        if !self.room_list.select_room_by_id(&room_id) {
+            self.status.set_message(format!("{room_id} isn't in the room list"));
+            return;
+        }
+
+        self.room_view.set_selected_room(Some(room_id));
+
+        if let Some(event_id) = event_id
+            && !self.room_view.select_event(&event_id)
+        {
+            self.status.set_message(format!("{event_id} isn't loaded in the timeline"));
+        }
+    }
+
     fn on_tick(&mut self) {
         self.state.throbber_state.calc_next();
+        self.verification_view.on_tick();
 
         match &mut self.state.global_mode {
-            GlobalMode::Help | GlobalMode::Default | GlobalMode::Exiting { .. } => {}
+            GlobalMode::Default => {
+                // Pop the verification view up as soon as a verification request comes in.
+                if self.verification_view.is_active() {
+                    self.set_global_mode(GlobalMode::Verification);
+                }
+            }
+            GlobalMode::Help | GlobalMode::Verification | GlobalMode::Exiting { .. } => {}
             GlobalMode::Settings { view } => {
                 view.on_tick();
             }
@@ -411,12 +545,22 @@ impl App {
                             self.set_global_mode(GlobalMode::Default);
                         }
                     }
+                    GlobalMode::Verification => {
+                        if let Event::Key(key) = event
+                            && self.verification_view.handle_key_press(key)
+                        {
+                            self.set_global_mode(GlobalMode::Default);
+                        }
+                    }
                     GlobalMode::Exiting { .. } => {}
                 }
             }
 
             match &self.state.global_mode {
-                GlobalMode::Default | GlobalMode::Help | GlobalMode::Settings { .. } => {}
+                GlobalMode::Default
+                | GlobalMode::Help
+                | GlobalMode::Settings { .. }
+                | GlobalMode::Verification => {}
                 GlobalMode::Exiting { shutdown_task } => {
                     if shutdown_task.is_finished() {
                         break;
@@ -480,6 +624,9 @@ impl Widget for &mut App {
                 let mut help_view = HelpView::new();
                 help_view.render(area, buf);
             }
+            GlobalMode::Verification => {
+                self.verification_view.render(area, buf);
+            }
         }
     }
 }
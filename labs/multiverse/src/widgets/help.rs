@@ -35,6 +35,14 @@ impl Widget for &mut HelpView {
                 Cell::from("Alt-m"),
                 Cell::from("Mark the currently selected room as read"),
             ]),
+            Row::new(vec![
+                Cell::from("Alt-p"),
+                Cell::from("Toggle the markdown preview of the message being composed"),
+            ]),
+            Row::new(vec![
+                Cell::from("Tab"),
+                Cell::from("Insert the first suggested member when typing an @-mention"),
+            ]),
             Row::new(vec![Cell::from("Ctrl-q"), Cell::from("Quit Multiverse")]),
             Row::new(vec![
                 Cell::from("Ctrl-j / Ctrl-down"),
@@ -44,6 +52,18 @@ impl Widget for &mut HelpView {
                 Cell::from("Ctrl-k / Ctrl-up"),
                 Cell::from("Switch to the previous room in the list"),
             ]),
+            Row::new(vec![
+                Cell::from("Alt-f"),
+                Cell::from("Cycle the room list filter (all, unread, favourites, invites)"),
+            ]),
+            Row::new(vec![
+                Cell::from("Alt-o"),
+                Cell::from("Cycle the room list sort mode (recency, alphabetical, unread count)"),
+            ]),
+            Row::new(vec![
+                Cell::from("Alt-n"),
+                Cell::from("Search rooms by name; Enter keeps the search, Esc clears it"),
+            ]),
             Row::new(vec![
                 Cell::from("Page-Up"),
                 Cell::from("Backpaginate the currently selected room"),
@@ -60,10 +80,39 @@ impl Widget for &mut HelpView {
                 Cell::from("Ctrl-p"),
                 Cell::from("Focus on the previous item in the timeline view"),
             ]),
+            Row::new(vec![
+                Cell::from("Alt-x"),
+                Cell::from("Open the moderation actions for the focused timeline item"),
+            ]),
+            Row::new(vec![
+                Cell::from("r"),
+                Cell::from("Open the reaction picker for the focused timeline item"),
+            ]),
+            Row::new(vec![
+                Cell::from("i"),
+                Cell::from("Inspect the raw data of the focused timeline item"),
+            ]),
+            Row::new(vec![Cell::from("p"), Cell::from("Pin or unpin the focused timeline item")]),
+            Row::new(vec![
+                Cell::from("Alt-s"),
+                Cell::from("Show the pinned messages of the selected room"),
+            ]),
+            Row::new(vec![
+                Cell::from("Alt-u"),
+                Cell::from("Fetch the keys of the undecryptable events from the backup"),
+            ]),
             Row::new(vec![
                 Cell::from("Ctrl-t"),
                 Cell::from("Open a thread on the focused timeline item"),
             ]),
+            Row::new(vec![
+                Cell::from("Click"),
+                Cell::from("Select a room or a timeline item, or open a matrix.to link"),
+            ]),
+            Row::new(vec![
+                Cell::from("Mouse wheel"),
+                Cell::from("Scroll through the timeline view"),
+            ]),
         ];
         let widths = [Constraint::Length(5), Constraint::Length(5)];
 
@@ -7,6 +7,7 @@ pub mod room_list;
 pub mod room_view;
 pub mod settings;
 pub mod status;
+pub mod verification;
 
 /// A hyperlink widget that renders a hyperlink in the terminal using [OSC 8].
 ///
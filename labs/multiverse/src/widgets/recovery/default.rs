@@ -60,6 +60,11 @@ enum Mode {
         disable_task: JoinHandle<Result<(), RecoveryError>>,
         throbber_state: ThrobberState,
     },
+    ConfirmingKeyChange,
+    ChangingKey {
+        change_task: JoinHandle<Result<String, RecoveryError>>,
+        throbber_state: ThrobberState,
+    },
     Done {
         result: DoneResult,
     },
@@ -69,11 +74,21 @@ enum Mode {
 enum DoneResult {
     Enabling(Result<String, RecoveryError>),
     Disabling(Result<(), RecoveryError>),
+    ChangingKey(Result<String, RecoveryError>),
+}
+
+impl DoneResult {
+    /// Does this result contain a new recovery key the user needs to store
+    /// before we let them continue?
+    fn has_new_recovery_key(&self) -> bool {
+        matches!(self, DoneResult::Enabling(Ok(_)) | DoneResult::ChangingKey(Ok(_)))
+    }
 }
 
 enum MenuEntries {
     Recovery = 0,
     KeyStorage = 1,
+    ChangeRecoveryKey = 2,
 }
 
 impl From<usize> for MenuEntries {
@@ -81,7 +96,8 @@ impl From<usize> for MenuEntries {
         match value {
             0 => MenuEntries::Recovery,
             1 => MenuEntries::KeyStorage,
-            _ => unreachable!("The recovery disabled view has only 2 options"),
+            2 => MenuEntries::ChangeRecoveryKey,
+            _ => unreachable!("The recovery disabled view has only 3 options"),
         }
     }
 }
@@ -134,6 +150,21 @@ impl DefaultRecoveryView {
         }
     }
 
+    fn handle_change_key_action(&mut self) {
+        // Changing the recovery key only makes sense if recovery is enabled and we
+        // have all the secrets, otherwise we'd upload an incomplete secret store.
+        if matches!(self.recovery_state, RecoveryState::Enabled) {
+            self.mode = Mode::ConfirmingKeyChange;
+        }
+    }
+
+    fn change_recovery_key(&mut self) {
+        let client = self.client.clone();
+        let change_task = spawn(async move { client.encryption().recovery().reset_key().await });
+
+        self.mode = Mode::ChangingKey { change_task, throbber_state: ThrobberState::default() };
+    }
+
     async fn handle_backup_action(&mut self) {
         let backup_state = self.backup_info.backup_state;
         let backup_exists = self.backup_info.backup_exists.load(Ordering::SeqCst);
@@ -177,6 +208,7 @@ impl DefaultRecoveryView {
                         match selected.into() {
                             MenuEntries::Recovery => self.handle_recovery_action(),
                             MenuEntries::KeyStorage => self.handle_backup_action().await,
+                            MenuEntries::ChangeRecoveryKey => self.handle_change_key_action(),
                         }
                     }
 
@@ -184,8 +216,22 @@ impl DefaultRecoveryView {
                 }
                 _ => No,
             },
-            Mode::Enabling { .. } | Mode::Disabling { .. } => No,
-            Mode::Done { .. } => {
+            Mode::ConfirmingKeyChange => {
+                match key.code {
+                    KeyCode::Char('y') | KeyCode::Enter => self.change_recovery_key(),
+                    _ => self.mode = Mode::Default,
+                }
+
+                No
+            }
+            Mode::Enabling { .. } | Mode::Disabling { .. } | Mode::ChangingKey { .. } => No,
+            Mode::Done { ref result } => {
+                // A freshly generated recovery key is shown only once, so make sure the user
+                // explicitly confirms that they have stored it before we hide it.
+                if result.has_new_recovery_key() && key.code != KeyCode::Enter {
+                    return No;
+                }
+
                 self.mode = Mode::Default;
                 OnlySubScreen
             }
@@ -196,17 +242,21 @@ impl DefaultRecoveryView {
         use Mode::*;
 
         match &mut self.mode {
-            Enabling { throbber_state, .. } | Disabling { throbber_state, .. } => {
-                throbber_state.calc_next()
-            }
-            Default | Done { .. } => {}
+            Enabling { throbber_state, .. }
+            | Disabling { throbber_state, .. }
+            | ChangingKey { throbber_state, .. } => throbber_state.calc_next(),
+            Default | ConfirmingKeyChange | Done { .. } => {}
         }
     }
 
     pub fn is_idle(&self) -> bool {
         match self.mode {
             Mode::Default => true,
-            Mode::Enabling { .. } | Mode::Disabling { .. } | Mode::Done { .. } => false,
+            Mode::Enabling { .. }
+            | Mode::Disabling { .. }
+            | Mode::ConfirmingKeyChange
+            | Mode::ChangingKey { .. }
+            | Mode::Done { .. } => false,
         }
     }
 
@@ -240,6 +290,18 @@ impl DefaultRecoveryView {
                     self.mode = Done { result: DoneResult::Disabling(result) };
                 }
             }
+            ChangingKey { change_task, .. } => {
+                if change_task.is_finished() {
+                    let result = change_task
+                        .now_or_never()
+                        .expect("The task should have finished, we checked it")
+                        .expect("The recovery key changing task should never panic");
+                    self.mode = Done { result: DoneResult::ChangingKey(result) };
+                }
+            }
+
+            // Waiting for the user to confirm that they want to change the recovery key.
+            ConfirmingKeyChange => {}
 
             // Done only transitions into another state if the user presses a button.
             Done { .. } => {}
@@ -256,9 +318,11 @@ impl Widget for &mut DefaultRecoveryView {
 
         let style = match &self.mode {
             Mode::Default => Style::default(),
-            Mode::Enabling { .. } | Mode::Done { .. } | Mode::Disabling { .. } => {
-                Style::default().dim()
-            }
+            Mode::Enabling { .. }
+            | Mode::Done { .. }
+            | Mode::Disabling { .. }
+            | Mode::ConfirmingKeyChange
+            | Mode::ChangingKey { .. } => Style::default().dim(),
         };
 
         let recovery_item = match self.recovery_state {
@@ -290,7 +354,14 @@ impl Widget for &mut DefaultRecoveryView {
             }
         };
 
-        let list = List::new(vec![recovery_item, backups])
+        let change_key = match self.recovery_state {
+            RecoveryState::Enabled => ListItem::new("Change recovery key").style(style),
+            RecoveryState::Unknown | RecoveryState::Disabled | RecoveryState::Incomplete => {
+                ListItem::new("Change recovery key").style(Style::default().dim())
+            }
+        };
+
+        let list = List::new(vec![recovery_item, backups, change_key])
             .highlight_symbol("> ")
             .highlight_spacing(ratatui::widgets::HighlightSpacing::Always);
 
@@ -312,25 +383,26 @@ impl Widget for &mut DefaultRecoveryView {
                 let centered_area = create_centered_throbber_area(area);
                 StatefulWidget::render(throbber, centered_area, buf, throbber_state);
             }
+            Mode::ChangingKey { throbber_state, .. } => {
+                let throbber = Throbber::default()
+                    .label("Changing the recovery key")
+                    .throbber_set(throbber_widgets_tui::BRAILLE_EIGHT_DOUBLE);
+                let centered_area = create_centered_throbber_area(area);
+                StatefulWidget::render(throbber, centered_area, buf, throbber_state);
+            }
+            Mode::ConfirmingKeyChange => {
+                let text = "Changing the recovery key will make the current one unusable.\n\
+                            Press (y) to continue, or any other key to cancel.";
 
-            Mode::Done { result } => {
-                let vertical = Layout::vertical([
-                    Constraint::Fill(1),
-                    Constraint::Length(4),
-                    Constraint::Fill(1),
-                ])
-                .flex(Flex::Center);
-                let horizontal = Layout::horizontal([Constraint::Length(70)]).flex(Flex::Center);
-                let [_, area, _] = vertical.areas(area);
-                let [popup] = horizontal.areas(area);
-
-                Clear.render(popup, buf);
+                render_popup(text.to_owned(), area, buf);
+            }
 
-                let block = Block::new().borders(Borders::all());
+            Mode::Done { result } => {
+                let confirmation = "Store the key somewhere safe, then press (Enter) to continue.";
 
                 let text = match result {
                     DoneResult::Enabling(Ok(recovery_key)) => {
-                        format!("Recovery has been enabled:\n{recovery_key}")
+                        format!("Recovery has been enabled:\n{recovery_key}\n{confirmation}")
                     }
                     DoneResult::Enabling(Err(error)) => {
                         format!("Failed to enable recovery: {error:?}")
@@ -339,10 +411,33 @@ impl Widget for &mut DefaultRecoveryView {
                     DoneResult::Disabling(Err(error)) => {
                         format!("Failed to disable recovery: {error:?}")
                     }
+                    DoneResult::ChangingKey(Ok(recovery_key)) => {
+                        format!(
+                            "The recovery key has been changed:\n{recovery_key}\n{confirmation}"
+                        )
+                    }
+                    DoneResult::ChangingKey(Err(error)) => {
+                        format!("Failed to change the recovery key: {error:?}")
+                    }
                 };
 
-                Paragraph::new(text).centered().block(block).render(popup, buf);
+                render_popup(text, area, buf);
             }
         }
     }
 }
+
+fn render_popup(text: String, area: Rect, buf: &mut Buffer) {
+    let vertical =
+        Layout::vertical([Constraint::Fill(1), Constraint::Length(5), Constraint::Fill(1)])
+            .flex(Flex::Center);
+    let horizontal = Layout::horizontal([Constraint::Length(70)]).flex(Flex::Center);
+    let [_, area, _] = vertical.areas(area);
+    let [popup] = horizontal.areas(area);
+
+    Clear.render(popup, buf);
+
+    let block = Block::new().borders(Borders::all());
+
+    Paragraph::new(text).centered().block(block).render(popup, buf);
+}
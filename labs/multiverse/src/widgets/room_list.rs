@@ -1,9 +1,24 @@
 use std::{collections::HashMap, sync::Arc};
 
+use crossterm::event::{KeyCode, KeyEvent};
 use imbl::Vector;
-use matrix_sdk::{Client, Room, locks::Mutex, ruma::OwnedRoomId};
-use matrix_sdk_ui::sync_service::SyncService;
+use matrix_sdk::{
+    Client, Room,
+    locks::Mutex,
+    ruma::{OwnedRoomId, RoomId},
+};
+use matrix_sdk_ui::{
+    room_list_service::{
+        filters::{
+            BoxedFilterFn, new_filter_all, new_filter_favourite, new_filter_fuzzy_match_room_name,
+            new_filter_invite, new_filter_non_left, new_filter_unread,
+        },
+        sorters::{new_sorter_name, new_sorter_recency},
+    },
+    sync_service::SyncService,
+};
 use ratatui::{prelude::*, widgets::*};
+use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
     ALT_ROW_COLOR, HEADER_BG, NORMAL_ROW_COLOR, SELECTED_STYLE_FG, TEXT_COLOR,
@@ -26,6 +41,64 @@ pub struct ExtraRoomInfo {
 pub type Rooms = Arc<Mutex<Vector<Room>>>;
 pub type RoomInfos = Arc<Mutex<HashMap<OwnedRoomId, ExtraRoomInfo>>>;
 
+/// Which rooms are shown in the room list, on top of the name search.
+#[derive(Clone, Copy, Default)]
+pub enum RoomListFilterKind {
+    #[default]
+    All,
+    Unread,
+    Favourites,
+    Invites,
+}
+
+impl RoomListFilterKind {
+    fn next(self) -> Self {
+        match self {
+            Self::All => Self::Unread,
+            Self::Unread => Self::Favourites,
+            Self::Favourites => Self::Invites,
+            Self::Invites => Self::All,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::Unread => "unread",
+            Self::Favourites => "favourites",
+            Self::Invites => "invites",
+        }
+    }
+}
+
+/// How the rooms of the room list are sorted.
+#[derive(Clone, Copy, Default)]
+pub enum RoomListSortMode {
+    /// The order of the room list service, i.e. by recency then by name.
+    #[default]
+    Recency,
+    Alphabetical,
+    UnreadCount,
+}
+
+impl RoomListSortMode {
+    fn next(self) -> Self {
+        match self {
+            Self::Recency => Self::Alphabetical,
+            Self::Alphabetical => Self::UnreadCount,
+            Self::UnreadCount => Self::Recency,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Recency => "recency",
+            Self::Alphabetical => "alphabetical",
+            Self::UnreadCount => "unread count",
+        }
+    }
+}
+
 pub struct RoomList {
     pub state: ListState,
 
@@ -43,6 +116,23 @@ pub struct RoomList {
 
     /// The sync service used for synchronizing events.
     sync_service: Arc<SyncService>,
+
+    /// Sender of the filters to apply to the room list service's entries.
+    filter_sender: UnboundedSender<BoxedFilterFn>,
+
+    filter_kind: RoomListFilterKind,
+
+    sort_mode: RoomListSortMode,
+
+    /// The pattern to fuzzy-match the room names against.
+    search: String,
+
+    /// Whether the key presses are currently typed into the search.
+    is_editing_search: bool,
+
+    /// The area where the rooms were rendered last, to find the room under the
+    /// mouse cursor.
+    list_area: Rect,
 }
 
 impl RoomList {
@@ -52,6 +142,7 @@ impl RoomList {
 
         room_infos: RoomInfos,
         sync_service: Arc<SyncService>,
+        filter_sender: UnboundedSender<BoxedFilterFn>,
         status_handle: StatusHandle,
     ) -> Self {
         Self {
@@ -62,14 +153,125 @@ impl RoomList {
             room_infos,
             current_room_subscription: None,
             sync_service,
+            filter_sender,
+            filter_kind: Default::default(),
+            sort_mode: Default::default(),
+            search: String::new(),
+            is_editing_search: false,
+            list_area: Rect::default(),
         }
     }
 
+    /// Switch to the next filter kind, and apply it.
+    pub fn cycle_filter(&mut self) {
+        self.filter_kind = self.filter_kind.next();
+        self.apply_filter();
+    }
+
+    /// Switch to the next sort mode, keeping the selected room selected.
+    pub fn cycle_sort_mode(&mut self) {
+        let selected_room_id = self.get_selected_room_id();
+
+        self.sort_mode = self.sort_mode.next();
+
+        let new_index = selected_room_id.and_then(|room_id| {
+            self.displayed_rooms().iter().position(|room| room.room_id() == room_id)
+        });
+        self.state.select(new_index);
+    }
+
+    /// Start typing into the room name search.
+    pub fn start_search(&mut self) {
+        self.is_editing_search = true;
+    }
+
+    /// Whether the key presses should go to [`Self::handle_search_key`].
+    pub fn is_editing_search(&self) -> bool {
+        self.is_editing_search
+    }
+
+    /// Handle a key press while typing into the room name search.
+    ///
+    /// Enter keeps the search, while Esc clears it.
+    pub fn handle_search_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char(c) => {
+                self.search.push(c);
+                self.apply_filter();
+            }
+
+            KeyCode::Backspace => {
+                self.search.pop();
+                self.apply_filter();
+            }
+
+            KeyCode::Enter => {
+                self.is_editing_search = false;
+            }
+
+            KeyCode::Esc => {
+                self.is_editing_search = false;
+                self.search.clear();
+                self.apply_filter();
+            }
+
+            _ => {}
+        }
+    }
+
+    /// Send the filter matching the current filter kind and search to the
+    /// room list service.
+    fn apply_filter(&mut self) {
+        let mut filters: Vec<BoxedFilterFn> = vec![Box::new(new_filter_non_left())];
+
+        match self.filter_kind {
+            RoomListFilterKind::All => {}
+            RoomListFilterKind::Unread => filters.push(Box::new(new_filter_unread())),
+            RoomListFilterKind::Favourites => filters.push(Box::new(new_filter_favourite())),
+            RoomListFilterKind::Invites => filters.push(Box::new(new_filter_invite())),
+        }
+
+        if !self.search.is_empty() {
+            filters.push(Box::new(new_filter_fuzzy_match_room_name(&self.search)));
+        }
+
+        // The entries are reset, so the selected index doesn't mean anything anymore.
+        self.state.select(None);
+
+        if self.filter_sender.send(Box::new(new_filter_all(filters))).is_err() {
+            self.status_handle.set_message("couldn't update the room list filter".to_owned());
+        }
+    }
+
+    /// The rooms, in the order they're displayed, according to the sort mode.
+    fn displayed_rooms(&self) -> Vector<Room> {
+        let mut rooms = self.rooms.lock().clone();
+
+        match self.sort_mode {
+            RoomListSortMode::Recency => {}
+            RoomListSortMode::Alphabetical => {
+                let sorter = new_sorter_name();
+                rooms.sort_by(|left, right| sorter(left, right));
+            }
+            RoomListSortMode::UnreadCount => {
+                let sorter = new_sorter_recency();
+                rooms.sort_by(|left, right| {
+                    right
+                        .num_unread_messages()
+                        .cmp(&left.num_unread_messages())
+                        .then_with(|| sorter(left, right))
+                });
+            }
+        }
+
+        rooms
+    }
+
     /// Focus the list on the next item, wraps around if needs be.
     ///
     /// Returns the index only if there was a meaningful change.
     pub fn next_room(&mut self) {
-        let num_items = self.rooms.lock().len();
+        let num_items = self.displayed_rooms().len();
 
         // If there's no item to select, leave early.
         if num_items == 0 {
@@ -91,7 +293,7 @@ impl RoomList {
     ///
     /// Returns the index only if there was a meaningful change.
     pub fn previous_room(&mut self) {
-        let num_items = self.rooms.lock().len();
+        let num_items = self.displayed_rooms().len();
 
         // If there's no item to select, leave early.
         if num_items == 0 {
@@ -109,9 +311,43 @@ impl RoomList {
         }
     }
 
+    /// Select the room displayed at the given position of the terminal, if
+    /// any.
+    ///
+    /// Returns the ID of the newly selected room.
+    pub fn select_room_at(&mut self, column: u16, row: u16) -> Option<OwnedRoomId> {
+        if !self.list_area.contains(Position::new(column, row)) {
+            return None;
+        }
+
+        // Every room takes a single line.
+        let index = self.state.offset() + usize::from(row - self.list_area.y);
+        self.select_room(index)
+    }
+
+    /// Select the room with the given ID, if it's displayed in the list.
+    ///
+    /// Returns whether the room was found.
+    pub fn select_room_by_id(&mut self, room_id: &RoomId) -> bool {
+        let index = self.displayed_rooms().iter().position(|room| room.room_id() == room_id);
+        index.and_then(|index| self.select_room(index)).is_some()
+    }
+
+    /// Select the `nth` room of the list, and subscribe to it.
+    fn select_room(&mut self, index: usize) -> Option<OwnedRoomId> {
+        let room_id = self.get_room_id_of_entry(index)?;
+
+        if self.state.selected() != Some(index) {
+            self.state.select(Some(index));
+            self.subscribe_to_room(index);
+        }
+
+        Some(room_id)
+    }
+
     /// Returns the [`OwnedRoomId`] of the `nth` room within the [`RoomList`].
     pub fn get_room_id_of_entry(&self, nth: usize) -> Option<OwnedRoomId> {
-        self.rooms.lock().get(nth).cloned().map(|room| room.room_id().to_owned())
+        self.displayed_rooms().get(nth).map(|room| room.room_id().to_owned())
     }
 
     /// Returns the [`OwnedRoomId`] of the currently selected room, if any.
@@ -153,21 +389,41 @@ impl Widget for &mut RoomList {
             Block::default().borders(Borders::NONE).fg(TEXT_COLOR).bg(NORMAL_ROW_COLOR);
 
         // We get the inner area from outer_block. We'll use this area later to render
-        // the table.
+        // the filter bar and the table.
         let outer_area = area;
-        let inner_area = outer_block.inner(outer_area);
+        let [filter_bar_area, inner_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)])
+                .areas(outer_block.inner(outer_area));
 
         // We can render the header in outer_area.
         outer_block.render(outer_area, buf);
 
+        self.list_area = inner_area;
+
+        let search = if self.is_editing_search {
+            format!("{}_", self.search)
+        } else if self.search.is_empty() {
+            "-".to_owned()
+        } else {
+            self.search.clone()
+        };
+
+        Paragraph::new(format!(
+            "filter: {} | sort: {} | search: {search}",
+            self.filter_kind.label(),
+            self.sort_mode.label()
+        ))
+        .fg(TEXT_COLOR)
+        .bg(HEADER_BG)
+        .render(filter_bar_area, buf);
+
         // Don't keep this lock too long by cloning the content. RAM's free these days,
         // right?
         let mut room_info = self.room_infos.lock().clone();
 
         // Iterate through all elements in the `items` and stylize them.
         let items: Vec<ListItem<'_>> = self
-            .rooms
-            .lock()
+            .displayed_rooms()
             .iter()
             .enumerate()
             .map(|(i, room)| {
@@ -1,10 +1,29 @@
+use std::{collections::BTreeSet, sync::Arc};
+
 use clap::{Parser, Subcommand};
-use crossterm::event::KeyEvent;
-use matrix_sdk::Room;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use matrix_sdk::{
+    Room, RoomMemberships,
+    locks::Mutex,
+    ruma::{
+        OwnedUserId,
+        events::{
+            Mentions,
+            room::message::{MessageType, RoomMessageEventContentWithoutRelation},
+        },
+    },
+};
 use ratatui::{prelude::*, widgets::*};
 use style::palette::tailwind;
+use tokio::{spawn, task::JoinHandle};
 use tui_textarea::TextArea;
 
+/// The maximum number of members suggested when autocompleting a mention.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// The number of lines used by the markdown preview pane.
+const PREVIEW_HEIGHT: u16 = 6;
+
 #[derive(Debug, Parser)]
 #[command(name = "multiverse", disable_help_flag = true, disable_help_subcommand = true)]
 struct Cli {
@@ -19,15 +38,38 @@ pub enum Command {
 }
 
 pub enum MessageOrCommand {
-    Message(String),
+    Message(RoomMessageEventContentWithoutRelation),
     Command(Command),
 }
 
+/// A member of the room that can be mentioned.
+#[derive(Clone)]
+struct MentionCandidate {
+    user_id: OwnedUserId,
+    name: String,
+}
+
 /// A widget representing a text input to send messages to a room.
+///
+/// The input is interpreted as markdown, and can be previewed in a pane above
+/// the input line. Typing `@` starts the autocompletion of a member of the
+/// room, which is inserted as a mention by pressing `Tab`.
 #[derive(Default)]
 pub struct Input {
     /// The text area that will keep track of what the user has input.
     textarea: TextArea<'static>,
+
+    /// The members of the current room, loaded from the room member cache.
+    members: Arc<Mutex<Vec<MentionCandidate>>>,
+
+    /// The task loading the members of the current room.
+    members_task: Option<JoinHandle<()>>,
+
+    /// The users mentioned through the autocompletion.
+    mentions: BTreeSet<OwnedUserId>,
+
+    /// Whether the markdown preview pane is shown.
+    show_preview: bool,
 }
 
 impl Input {
@@ -35,14 +77,65 @@ impl Input {
     pub fn new() -> Self {
         let textarea = TextArea::default();
 
-        Self { textarea }
+        Self { textarea, ..Default::default() }
+    }
+
+    /// Set the room the messages are composed for, and load its members from
+    /// the room member cache to autocomplete mentions.
+    pub fn set_room(&mut self, room: Option<Room>) {
+        if let Some(task) = self.members_task.take() {
+            task.abort();
+        }
+
+        self.members.lock().clear();
+
+        let Some(room) = room else {
+            return;
+        };
+
+        let members = self.members.clone();
+
+        self.members_task = Some(spawn(async move {
+            // Don't sync the members, only use the ones we already know about.
+            let Ok(room_members) = room.members_no_sync(RoomMemberships::JOIN).await else {
+                return;
+            };
+
+            *members.lock() = room_members
+                .iter()
+                .map(|member| MentionCandidate {
+                    user_id: member.user_id().to_owned(),
+                    name: member.name().to_owned(),
+                })
+                .collect();
+        }));
     }
 
     /// Receive a key press event and handle it.
     pub fn handle_key_press(&mut self, event: KeyEvent) {
+        if let (KeyModifiers::NONE, KeyCode::Tab) = (event.modifiers, event.code)
+            && self.complete_mention()
+        {
+            return;
+        }
+
         self.textarea.input(event);
     }
 
+    /// Show or hide the markdown preview pane.
+    pub fn toggle_preview(&mut self) {
+        self.show_preview = !self.show_preview;
+    }
+
+    /// The number of lines needed to render the widget.
+    pub fn height(&self) -> u16 {
+        let preview_height = if self.show_preview { PREVIEW_HEIGHT } else { 0 };
+        // At most `MAX_SUGGESTIONS`, so the cast can't truncate.
+        let suggestions_height = self.suggestions().len() as u16;
+
+        1 + preview_height + suggestions_height
+    }
+
     /// Get the currently input text.
     pub fn get_input(&self) -> Result<MessageOrCommand, clap::Error> {
         let input = self.textarea.lines().join("\n");
@@ -57,7 +150,7 @@ impl Input {
             Cli::try_parse_from(std::iter::once("multiverse").chain(arguments))
                 .map(|cli| MessageOrCommand::Command(cli.command))
         } else {
-            Ok(MessageOrCommand::Message(input))
+            Ok(MessageOrCommand::Message(self.message_content(input)))
         }
     }
 
@@ -70,6 +163,118 @@ impl Input {
     /// Clear the text from the input area.
     pub fn clear(&mut self) {
         self.textarea = TextArea::default();
+        self.mentions.clear();
+    }
+
+    /// Build the content of the message for the given input, with the mentions
+    /// that are still part of it.
+    fn message_content(&self, input: String) -> RoomMessageEventContentWithoutRelation {
+        let user_ids = self
+            .mentions
+            .iter()
+            .filter(|user_id| input.contains(user_id.as_str()))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        RoomMessageEventContentWithoutRelation::text_markdown(input)
+            .add_mentions(Mentions::with_user_ids(user_ids))
+    }
+
+    /// Get the mention being typed before the cursor, without the leading
+    /// `@`, if any.
+    fn mention_query(&self) -> Option<String> {
+        let (row, column) = self.textarea.cursor();
+        let line = self.textarea.lines().get(row)?;
+        let before_cursor = line.chars().take(column).collect::<String>();
+
+        let (prefix, query) = before_cursor.rsplit_once('@')?;
+
+        let starts_word = prefix.chars().last().is_none_or(char::is_whitespace);
+        (starts_word && !query.contains(char::is_whitespace)).then(|| query.to_owned())
+    }
+
+    /// Get the members matching the mention being typed, if any.
+    fn suggestions(&self) -> Vec<MentionCandidate> {
+        let Some(query) = self.mention_query() else {
+            return Vec::new();
+        };
+
+        let query = query.to_lowercase();
+
+        self.members
+            .lock()
+            .iter()
+            .filter(|member| {
+                member.name.to_lowercase().contains(&query)
+                    || member.user_id.localpart().to_lowercase().contains(&query)
+            })
+            .take(MAX_SUGGESTIONS)
+            .cloned()
+            .collect()
+    }
+
+    /// Replace the mention being typed with a link to the first suggested
+    /// member.
+    ///
+    /// Returns whether a mention has been inserted.
+    fn complete_mention(&mut self) -> bool {
+        let Some(query) = self.mention_query() else {
+            return false;
+        };
+
+        let Some(member) = self.suggestions().into_iter().next() else {
+            return false;
+        };
+
+        // Remove the query, and the `@` that triggered the autocompletion.
+        for _ in 0..=query.chars().count() {
+            self.textarea.delete_char();
+        }
+
+        self.textarea.insert_str(format!("[{}]({}) ", member.name, member.user_id.matrix_to_uri()));
+        self.mentions.insert(member.user_id);
+
+        true
+    }
+
+    /// Render the markdown preview of the current input.
+    fn render_preview(&self, area: Rect, buf: &mut Buffer) {
+        let content = self.message_content(self.textarea.lines().join("\n"));
+
+        let body = match &content.msgtype {
+            MessageType::Text(text) => match &text.formatted {
+                Some(formatted) => formatted.body.clone(),
+                None => text.body.clone(),
+            },
+            _ => String::new(),
+        };
+
+        let mut lines = vec![Line::from(body)];
+
+        if let Some(mentions) = &content.mentions
+            && !mentions.user_ids.is_empty()
+        {
+            let user_ids = mentions.user_ids.iter().map(|user_id| user_id.as_str());
+            lines.push(Line::from(format!("Mentions: {}", itertools::join(user_ids, ", "))).bold());
+        }
+
+        Paragraph::new(lines)
+            .block(Block::bordered().title(" Markdown preview "))
+            .bg(tailwind::BLUE.c900)
+            .wrap(Wrap { trim: false })
+            .render(area, buf);
+    }
+
+    /// Render the members matching the mention being typed.
+    fn render_suggestions(&self, suggestions: Vec<MentionCandidate>, area: Rect, buf: &mut Buffer) {
+        let items = suggestions.into_iter().enumerate().map(|(i, member)| {
+            let item = ListItem::new(format!("{} ({})", member.name, member.user_id));
+
+            // The first suggestion is the one inserted when pressing Tab.
+            if i == 0 { item.bold() } else { item }
+        });
+
+        Widget::render(List::new(items).bg(tailwind::BLUE.c600), area, buf);
     }
 }
 
@@ -96,11 +301,29 @@ impl<'a> StatefulWidget for &'a mut Input {
             self.textarea.set_placeholder_text("(No room selected)");
         }
 
+        let suggestions = self.suggestions();
+        let preview_height = if self.show_preview { PREVIEW_HEIGHT } else { 0 };
+
+        let [suggestions_area, preview_area, textarea_area] = Layout::vertical([
+            Constraint::Length(suggestions.len() as u16),
+            Constraint::Length(preview_height),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+
+        if !suggestions.is_empty() {
+            self.render_suggestions(suggestions, suggestions_area, buf);
+        }
+
+        if self.show_preview {
+            self.render_preview(preview_area, buf);
+        }
+
         // Let's first create a block to set the background color.
         let input_block = Block::new().borders(Borders::NONE).bg(tailwind::BLUE.c400);
 
         // Now we set the block and we render the textarea.
         self.textarea.set_block(input_block);
-        self.textarea.render(area, buf);
+        self.textarea.render(textarea_area, buf);
     }
 }
@@ -0,0 +1,132 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use matrix_sdk::{
+    deserialized_responses::EncryptionInfo,
+    ruma::{events::AnySyncTimelineEvent, serde::Raw},
+};
+use matrix_sdk_ui::timeline::EventTimelineItem;
+use ratatui::{prelude::*, widgets::*};
+use serde_json::Value;
+
+use crate::popup_area;
+
+/// The number of lines scrolled by Page-Up and Page-Down.
+const PAGE_HEIGHT: u16 = 10;
+
+/// A popup showing the raw data of the selected timeline event: its JSON, its
+/// unsigned data, its encryption info and its latest edit.
+pub struct EventInspector {
+    lines: Vec<Line<'static>>,
+    scroll: u16,
+}
+
+impl EventInspector {
+    pub fn new(event: &EventTimelineItem) -> Self {
+        let mut lines = Vec::new();
+
+        let event_id = event.event_id().map_or_else(|| "(local echo)".to_owned(), Into::into);
+        lines.push(Line::from(format!("Event ID: {event_id}")));
+        lines.push(Line::from(format!("Sender: {}", event.sender())));
+
+        let original = event.original_json().map(parse_json);
+
+        push_section(&mut lines, "Original event");
+        match &original {
+            Some(Ok(json)) => push_json(&mut lines, json),
+            Some(Err(err)) => lines.push(Line::from(format!("(invalid JSON: {err})"))),
+            None => lines.push(Line::from("(not received from the server yet)")),
+        }
+
+        push_section(&mut lines, "Unsigned data");
+        match original.as_ref().and_then(|json| json.as_ref().ok()?.get("unsigned")) {
+            Some(unsigned) => push_json(&mut lines, unsigned),
+            None => lines.push(Line::from("(none)")),
+        }
+
+        push_section(&mut lines, "Encryption");
+        match event.encryption_info() {
+            Some(info) => push_encryption_info(&mut lines, info),
+            None => lines.push(Line::from("(not encrypted)")),
+        }
+
+        push_section(&mut lines, "Latest edit");
+        match event.latest_edit_json().map(parse_json) {
+            Some(Ok(json)) => push_json(&mut lines, &json),
+            Some(Err(err)) => lines.push(Line::from(format!("(invalid JSON: {err})"))),
+            None => lines.push(Line::from("(not edited)")),
+        }
+
+        Self { lines, scroll: 0 }
+    }
+
+    /// Handle a key press, returns whether the inspector should be closed.
+    pub fn handle_key_press(&mut self, key: KeyEvent) -> bool {
+        use KeyCode::*;
+
+        let max_scroll = u16::try_from(self.lines.len().saturating_sub(1)).unwrap_or(u16::MAX);
+
+        match (key.modifiers, key.code) {
+            (_, Esc | Char('q') | Char('i')) => return true,
+            (_, Down) | (KeyModifiers::CONTROL, Char('n')) => {
+                self.scroll = self.scroll.saturating_add(1)
+            }
+            (_, Up) | (KeyModifiers::CONTROL, Char('p')) => {
+                self.scroll = self.scroll.saturating_sub(1)
+            }
+            (_, PageDown) => self.scroll = self.scroll.saturating_add(PAGE_HEIGHT),
+            (_, PageUp) => self.scroll = self.scroll.saturating_sub(PAGE_HEIGHT),
+            (_, Home) => self.scroll = 0,
+            (_, End) => self.scroll = max_scroll,
+            _ => {}
+        }
+
+        self.scroll = self.scroll.min(max_scroll);
+
+        false
+    }
+}
+
+impl Widget for &mut EventInspector {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let area = popup_area(area, 80, 80);
+        Clear.render(area, buf);
+
+        let block = Block::bordered()
+            .title(" Event inspector ")
+            .title_bottom(" Up/Down/Page-Up/Page-Down to scroll, Esc to close ")
+            .padding(Padding::horizontal(1));
+
+        Paragraph::new(self.lines.clone())
+            .block(block)
+            .wrap(Wrap { trim: false })
+            .scroll((self.scroll, 0))
+            .render(area, buf);
+    }
+}
+
+fn parse_json(raw: &Raw<AnySyncTimelineEvent>) -> serde_json::Result<Value> {
+    serde_json::from_str(raw.json().get())
+}
+
+fn push_section(lines: &mut Vec<Line<'static>>, title: &str) {
+    lines.push(Line::default());
+    lines.push(Line::from(format!("== {title} ==")).bold());
+}
+
+fn push_json(lines: &mut Vec<Line<'static>>, json: &Value) {
+    // Serializing a `Value` can't fail.
+    let pretty = serde_json::to_string_pretty(json).unwrap_or_default();
+    lines.extend(pretty.lines().map(|line| Line::from(line.to_owned())));
+}
+
+fn push_encryption_info(lines: &mut Vec<Line<'static>>, info: &EncryptionInfo) {
+    let device = info.sender_device.as_ref().map_or("(unknown)", |device_id| device_id.as_str());
+    let session_id = info.session_id().unwrap_or("(unknown)");
+
+    lines.push(Line::from(format!("Sender: {}", info.sender)));
+    lines.push(Line::from(format!("Sender device: {device}")));
+    lines.push(Line::from(format!("Megolm session ID: {session_id}")));
+    lines.push(Line::from(format!("Verification state: {:?}", info.verification_state)));
+}
@@ -0,0 +1,220 @@
+//! Previews of media events (images, videos and stickers), rendered as terminal
+//! graphics.
+//!
+//! Thumbnails are downloaded through the media cache of the client, decoded,
+//! and then converted once to the graphics protocol supported by the terminal
+//! (kitty, sixel, or unicode half-blocks as a fallback). The rendered frames
+//! are cached by MXC URI, so scrolling through the timeline doesn't re-encode
+//! anything.
+
+use std::{collections::HashMap, sync::Arc};
+
+use matrix_sdk::{
+    Client,
+    locks::Mutex,
+    media::{MediaFormat, MediaRequestParameters, MediaThumbnailSettings},
+    ruma::{
+        OwnedMxcUri,
+        events::{
+            room::{MediaSource, message::MessageType},
+            sticker::StickerEventContent,
+        },
+        uint,
+    },
+};
+use ratatui::prelude::*;
+use ratatui_image::{
+    Image, Resize,
+    picker::Picker,
+    protocol::{Protocol, ProtocolType},
+};
+use tokio::{spawn, task::spawn_blocking};
+use tracing::{debug, warn};
+
+/// The maximum width of a preview, in terminal cells.
+const MAX_PREVIEW_WIDTH: u16 = 40;
+
+/// The maximum height of a preview, in terminal cells.
+const MAX_PREVIEW_HEIGHT: u16 = 10;
+
+/// Where to fetch the preview of a media event from.
+#[derive(Clone, Debug)]
+pub struct PreviewSource {
+    source: MediaSource,
+    format: MediaFormat,
+}
+
+impl PreviewSource {
+    /// Get the preview source for a message, if it's a media message which can
+    /// be previewed.
+    pub fn from_message(msgtype: &MessageType) -> Option<Self> {
+        match msgtype {
+            MessageType::Image(content) => {
+                Some(match content.info.as_ref().and_then(|info| info.thumbnail_source.clone()) {
+                    Some(thumbnail) => Self::file(thumbnail),
+                    None => Self::image(content.source.clone()),
+                })
+            }
+
+            // Don't download whole videos, only use their thumbnail if they have one.
+            MessageType::Video(content) => {
+                content.info.as_ref().and_then(|info| info.thumbnail_source.clone()).map(Self::file)
+            }
+
+            _ => None,
+        }
+    }
+
+    /// Get the preview source for a sticker.
+    pub fn from_sticker(content: &StickerEventContent) -> Self {
+        Self::image(content.source.clone().into())
+    }
+
+    /// The MXC URI of the media, used as the key of the cache.
+    pub fn uri(&self) -> OwnedMxcUri {
+        match &self.source {
+            MediaSource::Plain(uri) => uri.clone(),
+            MediaSource::Encrypted(file) => file.url.clone(),
+        }
+    }
+
+    fn file(source: MediaSource) -> Self {
+        Self { source, format: MediaFormat::File }
+    }
+
+    /// Ask the server for a thumbnail of an image, or download the whole image
+    /// if it's encrypted, since servers can't thumbnail encrypted media.
+    fn image(source: MediaSource) -> Self {
+        let format = match &source {
+            MediaSource::Plain(_) => {
+                MediaFormat::Thumbnail(MediaThumbnailSettings::new(uint!(320), uint!(240)))
+            }
+            MediaSource::Encrypted(_) => MediaFormat::File,
+        };
+
+        Self { source, format }
+    }
+}
+
+/// The state of the preview of a single media.
+enum MediaPreview {
+    /// The thumbnail is being downloaded and rendered.
+    Loading,
+    /// The preview is ready to be displayed.
+    Ready {
+        /// The rendered frame, encoded for the terminal's graphics protocol.
+        protocol: Protocol,
+        /// The size of the rendered frame, in terminal cells.
+        size: (u16, u16),
+    },
+    /// The thumbnail couldn't be downloaded or decoded.
+    Failed,
+}
+
+/// A cheap summary of the state of a preview, used to lay out the timeline.
+#[derive(Clone, Copy, Debug)]
+pub enum PreviewStatus {
+    Loading,
+    Ready { height: u16 },
+    Failed,
+}
+
+/// The media preview subsystem: downloads thumbnails, and renders and caches
+/// them as terminal graphics.
+#[derive(Clone)]
+pub struct MediaPreviews {
+    client: Client,
+    picker: Picker,
+    cache: Arc<Mutex<HashMap<OwnedMxcUri, MediaPreview>>>,
+}
+
+impl MediaPreviews {
+    pub fn new(client: Client, picker: Picker) -> Self {
+        if picker.protocol_type() == ProtocolType::Halfblocks {
+            debug!("no graphics protocol detected, using unicode blocks for media previews");
+        }
+
+        Self { client, picker, cache: Default::default() }
+    }
+
+    /// Get the status of the preview for the given source, starting to load it
+    /// in the background if it's not been requested yet.
+    pub fn status(&self, source: &PreviewSource) -> PreviewStatus {
+        let uri = source.uri();
+        let mut cache = self.cache.lock();
+
+        match cache.get(&uri) {
+            Some(MediaPreview::Loading) => PreviewStatus::Loading,
+            Some(MediaPreview::Ready { size: (_, height), .. }) => {
+                PreviewStatus::Ready { height: *height }
+            }
+            Some(MediaPreview::Failed) => PreviewStatus::Failed,
+            None => {
+                cache.insert(uri.clone(), MediaPreview::Loading);
+                drop(cache);
+
+                spawn(self.clone().load(uri, source.clone()));
+
+                PreviewStatus::Loading
+            }
+        }
+    }
+
+    /// Render the preview of the given media in the given area, if it's ready.
+    pub fn render(&self, uri: &OwnedMxcUri, area: Rect, buf: &mut Buffer) {
+        if let Some(MediaPreview::Ready { protocol, .. }) = self.cache.lock().get(uri) {
+            Image::new(protocol).render(area, buf);
+        }
+    }
+
+    async fn load(self, uri: OwnedMxcUri, source: PreviewSource) {
+        let request = MediaRequestParameters { source: source.source, format: source.format };
+
+        let preview = match self.client.media().get_media_content(&request, true).await {
+            Ok(data) => {
+                let picker = self.picker.clone();
+
+                match spawn_blocking(move || render_preview(&picker, &data)).await {
+                    Ok(Ok((protocol, size))) => MediaPreview::Ready { protocol, size },
+                    Ok(Err(err)) => {
+                        warn!("couldn't render the preview of {uri}: {err}");
+                        MediaPreview::Failed
+                    }
+                    Err(err) => {
+                        warn!("the rendering task of the preview of {uri} panicked: {err}");
+                        MediaPreview::Failed
+                    }
+                }
+            }
+
+            Err(err) => {
+                warn!("couldn't download the preview of {uri}: {err}");
+                MediaPreview::Failed
+            }
+        };
+
+        self.cache.lock().insert(uri, preview);
+    }
+}
+
+/// Decode the given image, and encode it for the terminal's graphics protocol,
+/// scaled down to fit in the maximum preview size.
+fn render_preview(picker: &Picker, data: &[u8]) -> color_eyre::Result<(Protocol, (u16, u16))> {
+    let image = image::load_from_memory(data)?;
+
+    let (font_width, font_height) = picker.font_size();
+    let (font_width, font_height) = (f64::from(font_width), f64::from(font_height));
+    let (image_width, image_height) = (f64::from(image.width()), f64::from(image.height()));
+
+    // Never scale images up, only down.
+    let scale = (f64::from(MAX_PREVIEW_WIDTH) * font_width / image_width)
+        .min(f64::from(MAX_PREVIEW_HEIGHT) * font_height / image_height)
+        .min(1.0);
+
+    let width = ((image_width * scale / font_width).ceil() as u16).clamp(1, MAX_PREVIEW_WIDTH);
+    let height = ((image_height * scale / font_height).ceil() as u16).clamp(1, MAX_PREVIEW_HEIGHT);
+
+    let protocol = picker.new_protocol(image, Rect::new(0, 0, width, height), Resize::Fit(None))?;
+
+    Ok((protocol, (width, height)))
+}
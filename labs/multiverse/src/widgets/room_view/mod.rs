@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crossterm::event::{Event, KeyCode, KeyModifiers};
+use crossterm::event::{Event, KeyCode, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use futures_util::StreamExt;
 use imbl::Vector;
 use input::MessageOrCommand;
@@ -10,22 +10,35 @@ use matrix_sdk::{
     locks::Mutex,
     room::reply::{EnforceThread::Threaded, Reply},
     ruma::{
-        OwnedEventId, OwnedRoomId, RoomId, UserId,
+        EventId, MatrixToUri, OwnedEventId, OwnedRoomId, RoomId, UserId,
         api::client::receipt::create_receipt::v3::ReceiptType,
-        events::room::message::{
-            ReplyWithinThread, RoomMessageEventContent, RoomMessageEventContentWithoutRelation,
+        events::{
+            StateEventType,
+            room::message::{ReplyWithinThread, RoomMessageEventContentWithoutRelation},
         },
     },
 };
 use matrix_sdk_ui::{
     Timeline,
-    timeline::{TimelineBuilder, TimelineFocus, TimelineItem},
+    timeline::{TimelineBuilder, TimelineEventItemId, TimelineFocus, TimelineItem},
 };
 use ratatui::{prelude::*, widgets::*};
+use ratatui_image::picker::Picker;
 use tokio::{spawn, sync::OnceCell, task::JoinHandle};
 use tracing::info;
 
-use self::{details::RoomDetails, input::Input, timeline::TimelineView};
+use self::{
+    details::RoomDetails,
+    input::Input,
+    inspector::EventInspector,
+    media_preview::MediaPreviews,
+    moderation::{MenuOutcome, ModerationAction, ModerationMenu, ModerationTarget},
+    pinned::{PinnedEvents, PinnedOutcome},
+    reactions::{PickerOutcome, ReactionPicker},
+    timeline::TimelineView,
+    typing::Typing,
+    utd::UtdSummary,
+};
 use super::status::StatusHandle;
 use crate::{
     HEADER_BG, NORMAL_ROW_COLOR, TEXT_COLOR, Timelines,
@@ -34,8 +47,15 @@ use crate::{
 
 mod details;
 mod input;
+mod inspector;
 mod invited_room;
+mod media_preview;
+mod moderation;
+mod pinned;
+mod reactions;
 mod timeline;
+mod typing;
+mod utd;
 
 const DEFAULT_TILING_DIRECTION: Direction = Direction::Horizontal;
 
@@ -85,11 +105,37 @@ pub struct RoomView {
     timeline_list: TimelineListState,
 
     input: Input,
+
+    /// The typing notifications of the selected room.
+    typing: Typing,
+
+    /// The menu of moderation actions for the selected timeline event, if
+    /// opened.
+    moderation_menu: Option<ModerationMenu>,
+
+    /// The reaction picker for the selected timeline event, if opened.
+    reaction_picker: Option<ReactionPicker>,
+
+    /// The inspector of the raw data of the selected timeline event, if
+    /// opened.
+    inspector: Option<EventInspector>,
+
+    /// The list of the pinned events of the room, if opened.
+    pinned_events: Option<PinnedEvents>,
+
+    /// The previews of the media events displayed in the timeline.
+    media_previews: MediaPreviews,
 }
 
 impl RoomView {
-    pub fn new(client: Client, timelines: Timelines, status_handle: StatusHandle) -> Self {
+    pub fn new(
+        client: Client,
+        timelines: Timelines,
+        status_handle: StatusHandle,
+        picker: Picker,
+    ) -> Self {
         Self {
+            media_previews: MediaPreviews::new(client.clone(), picker),
             client,
             timelines,
             status_handle,
@@ -97,7 +143,12 @@ impl RoomView {
             mode: Mode::Normal { invited_room_view: None },
             kind: TimelineKind::Room { room: None },
             input: Input::new(),
+            typing: Typing::default(),
             timeline_list: TimelineListState::default(),
+            moderation_menu: None,
+            reaction_picker: None,
+            inspector: None,
+            pinned_events: None,
         }
     }
 
@@ -198,10 +249,76 @@ impl RoomView {
     pub async fn handle_event(&mut self, event: Event) {
         use KeyCode::*;
 
+        // The moderation menu, when opened, gets all the key presses.
+        if let Some(menu) = &mut self.moderation_menu {
+            if let Event::Key(key) = event {
+                match menu.handle_key_press(key) {
+                    MenuOutcome::Continue => {}
+                    MenuOutcome::Close => self.moderation_menu = None,
+                    MenuOutcome::Run { target, action, reason } => {
+                        self.moderation_menu = None;
+                        self.run_moderation_action(target, action, reason).await;
+                    }
+                }
+            }
+
+            return;
+        }
+
+        // Same for the reaction picker.
+        if let Some(picker) = &mut self.reaction_picker {
+            if let Event::Key(key) = event {
+                match picker.handle_key_press(key) {
+                    PickerOutcome::Continue => {}
+                    PickerOutcome::Close => self.reaction_picker = None,
+                    PickerOutcome::Toggle { item_id, key } => {
+                        self.reaction_picker = None;
+                        self.toggle_reaction(&item_id, &key).await;
+                    }
+                }
+            }
+
+            return;
+        }
+
+        // Same for the event inspector.
+        if let Some(inspector) = &mut self.inspector {
+            if let Event::Key(key) = event
+                && inspector.handle_key_press(key)
+            {
+                self.inspector = None;
+            }
+
+            return;
+        }
+
+        // Same for the list of pinned events.
+        if let Some(pinned_events) = &mut self.pinned_events {
+            if let Event::Key(key) = event {
+                match pinned_events.handle_key_press(key) {
+                    PinnedOutcome::Continue => {}
+                    PinnedOutcome::Close => self.pinned_events = None,
+                    PinnedOutcome::Select(event_id) => {
+                        self.pinned_events = None;
+
+                        if !self.select_event(&event_id) {
+                            self.status_handle.set_message(format!(
+                                "the pinned event {event_id} isn't loaded in the timeline"
+                            ));
+                        }
+                    }
+                }
+            }
+
+            return;
+        }
+
         match &mut self.mode {
             Mode::Normal { invited_room_view } => {
                 if let Some(view) = invited_room_view {
                     view.handle_event(event);
+                } else if let Event::Mouse(mouse) = event {
+                    self.handle_mouse_event(mouse);
                 } else if let Event::Key(key) = event {
                     match (key.modifiers, key.code) {
                         (KeyModifiers::NONE, Enter) => {
@@ -220,6 +337,9 @@ impl RoomView {
                                         self.input.clear();
                                     }
                                 }
+
+                                // The input is cleared once the message is sent.
+                                self.typing.set_typing(!self.input.is_empty());
                             }
                         }
 
@@ -235,6 +355,32 @@ impl RoomView {
                             self.toggle_reaction_to_latest_msg().await
                         }
 
+                        (KeyModifiers::ALT, Char('p')) => self.input.toggle_preview(),
+
+                        (KeyModifiers::ALT, Char('x')) => self.open_moderation_menu(),
+
+                        (KeyModifiers::NONE, Char('r'))
+                            if self.timeline_list.selected().is_some() =>
+                        {
+                            self.open_reaction_picker()
+                        }
+
+                        (KeyModifiers::NONE, Char('i'))
+                            if self.timeline_list.selected().is_some() =>
+                        {
+                            self.open_inspector()
+                        }
+
+                        (KeyModifiers::NONE, Char('p'))
+                            if self.timeline_list.selected().is_some() =>
+                        {
+                            self.toggle_pin().await
+                        }
+
+                        (KeyModifiers::ALT, Char('s')) => self.open_pinned_events().await,
+
+                        (KeyModifiers::ALT, Char('u')) => self.recover_utds(),
+
                         (KeyModifiers::NONE, PageUp) => self.back_paginate(),
 
                         (KeyModifiers::ALT, Char('e')) => {
@@ -278,13 +424,18 @@ impl RoomView {
                             self.switch_to_thread_timeline();
                         }
 
-                        _ => self.input.handle_key_press(key),
+                        _ => {
+                            self.input.handle_key_press(key);
+                            self.typing.set_typing(!self.input.is_empty());
+                        }
                     }
                 }
             }
 
             Mode::Details { view, tiling_direction } => {
-                if let Event::Key(key) = event {
+                if let Event::Mouse(mouse) = event {
+                    self.handle_mouse_event(mouse);
+                } else if let Event::Key(key) = event {
                     match (key.modifiers, key.code) {
                         (KeyModifiers::NONE, PageUp) => self.back_paginate(),
 
@@ -337,12 +488,66 @@ impl RoomView {
         }
     }
 
+    /// Scroll the timeline with the mouse wheel, and select the clicked
+    /// timeline item.
+    fn handle_mouse_event(&mut self, mouse: MouseEvent) {
+        match mouse.kind {
+            MouseEventKind::ScrollUp => self.timeline_list.select_previous(),
+            MouseEventKind::ScrollDown => self.timeline_list.select_next(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.timeline_list.select_at(mouse.column, mouse.row);
+            }
+            _ => {}
+        }
+    }
+
+    /// Find the matrix.to link displayed at the given position of the
+    /// terminal, if any.
+    pub fn matrix_to_link_at(&self, column: u16, row: u16) -> Option<MatrixToUri> {
+        // Links can't be clicked when the timeline is covered.
+        if self.moderation_menu.is_some()
+            || self.reaction_picker.is_some()
+            || self.inspector.is_some()
+            || self.pinned_events.is_some()
+            || matches!(self.mode, Mode::Normal { invited_room_view: Some(_) })
+        {
+            return None;
+        }
+
+        let (item_index, content_column) = self.timeline_list.item_at(column, row)?;
+        let items = self.get_selected_timeline_items()?;
+
+        timeline::matrix_to_link_at(items.get(item_index)?, content_column?)
+    }
+
+    /// Select the timeline item of the given event, if it's loaded in the
+    /// timeline.
+    ///
+    /// Returns whether the event was found.
+    pub fn select_event(&mut self, event_id: &EventId) -> bool {
+        let Some(items) = self.get_selected_timeline_items() else {
+            return false;
+        };
+
+        let item_index = items
+            .iter()
+            .position(|item| item.as_event().and_then(|event| event.event_id()) == Some(event_id));
+
+        if let Some(item_index) = item_index {
+            self.timeline_list.select_item(item_index);
+        }
+
+        item_index.is_some()
+    }
+
     pub fn set_selected_room(&mut self, room_id: Option<OwnedRoomId>) {
         if let Some(room_id) = room_id.as_deref() {
             let maybe_room = self.client.get_room(room_id);
 
             if let Some(room) = maybe_room {
                 self.switch_to_room_timeline(Some(room_id.to_owned()));
+                self.input.set_room(Some(room.clone()));
+                self.typing.set_room(Some(room.clone()));
 
                 if matches!(room.state(), RoomState::Invited) {
                     let view = InvitedRoomView::new(room);
@@ -431,6 +636,190 @@ impl RoomView {
         }
     }
 
+    /// Open the reaction picker for the selected timeline event.
+    fn open_reaction_picker(&mut self) {
+        let Some(item) = self.get_selected_event() else {
+            self.status_handle.set_message("no selected item to react to".to_owned());
+            return;
+        };
+
+        let Some(event) = item.as_event() else {
+            self.status_handle.set_message("the selected item isn't an event".to_owned());
+            return;
+        };
+
+        self.reaction_picker = Some(ReactionPicker::new(event, self.client.user_id()));
+    }
+
+    /// Open the inspector of the raw data of the selected timeline event.
+    fn open_inspector(&mut self) {
+        let Some(item) = self.get_selected_event() else {
+            self.status_handle.set_message("no selected item to inspect".to_owned());
+            return;
+        };
+
+        let Some(event) = item.as_event() else {
+            self.status_handle.set_message("the selected item isn't an event".to_owned());
+            return;
+        };
+
+        self.inspector = Some(EventInspector::new(event));
+    }
+
+    /// Fetch the keys of the undecryptable events of the timeline from the
+    /// key backup, and retry to decrypt them.
+    fn recover_utds(&mut self) {
+        let Some((sdk_timeline, items)) =
+            self.get_selected_timeline().zip(self.get_selected_timeline_items())
+        else {
+            self.status_handle.set_message("missing timeline for room".to_owned());
+            return;
+        };
+
+        let session_ids = UtdSummary::new(&items).session_ids().clone();
+
+        if session_ids.is_empty() {
+            self.status_handle.set_message("no undecryptable event to recover".to_owned());
+            return;
+        }
+
+        self.status_handle
+            .set_message(format!("fetching {} keys from the backup…", session_ids.len()));
+
+        spawn(utd::recover_keys(
+            self.client.clone(),
+            sdk_timeline,
+            session_ids,
+            self.status_handle.clone(),
+        ));
+    }
+
+    /// Open the list of the pinned events of the room.
+    async fn open_pinned_events(&mut self) {
+        let Some(room) = self.room() else {
+            self.status_handle.set_message("missing room".to_owned());
+            return;
+        };
+
+        self.pinned_events = Some(PinnedEvents::load(&room).await);
+    }
+
+    /// Pin the selected timeline event if it isn't pinned yet, unpin it
+    /// otherwise.
+    async fn toggle_pin(&mut self) {
+        let Some(item) = self.get_selected_event() else {
+            self.status_handle.set_message("no selected item to pin".to_owned());
+            return;
+        };
+
+        let Some(event_id) = item.as_event().and_then(|event| event.event_id()) else {
+            self.status_handle.set_message("can't pin a local echo".to_owned());
+            return;
+        };
+
+        let Some((room, sdk_timeline)) = self.room().zip(self.get_selected_timeline()) else {
+            self.status_handle.set_message("missing timeline for room".to_owned());
+            return;
+        };
+
+        let can_pin = match room.power_levels().await {
+            Ok(power_levels) => power_levels
+                .user_can_send_state(room.own_user_id(), StateEventType::RoomPinnedEvents),
+            Err(err) => {
+                self.status_handle.set_message(format!("error when loading power levels: {err}"));
+                return;
+            }
+        };
+
+        if !can_pin {
+            self.status_handle
+                .set_message("you're not allowed to pin events in this room".to_owned());
+            return;
+        }
+
+        let result = if room.clone_info().is_pinned_event(event_id) {
+            sdk_timeline.unpin_event(event_id).await.map(|changed| (changed, "unpinned"))
+        } else {
+            sdk_timeline.pin_event(event_id).await.map(|changed| (changed, "pinned"))
+        };
+
+        match result {
+            Ok((true, action)) => self.status_handle.set_message(format!("event {action}!")),
+            Ok((false, action)) => {
+                self.status_handle.set_message(format!("event already {action}"))
+            }
+            Err(err) => self.status_handle.set_message(format!("error when pinning: {err}")),
+        }
+    }
+
+    /// Toggle the given reaction on a timeline event: send it if the current
+    /// user hasn't reacted with it yet, redact it otherwise.
+    async fn toggle_reaction(&mut self, item_id: &TimelineEventItemId, key: &str) {
+        let Some(sdk_timeline) = self.get_selected_timeline() else {
+            self.status_handle.set_message("missing timeline for room".to_owned());
+            return;
+        };
+
+        match sdk_timeline.toggle_reaction(item_id, key).await {
+            Ok(()) => self.status_handle.set_message(format!("toggled the {key} reaction!")),
+            Err(err) => self.status_handle.set_message(format!("error when reacting: {err}")),
+        }
+    }
+
+    /// Open the menu of moderation actions for the selected timeline event.
+    fn open_moderation_menu(&mut self) {
+        let Some(item) = self.get_selected_event() else {
+            self.status_handle.set_message("no selected item to moderate".to_owned());
+            return;
+        };
+
+        let Some(event) = item.as_event() else {
+            self.status_handle.set_message("the selected item isn't an event".to_owned());
+            return;
+        };
+
+        self.moderation_menu = Some(ModerationMenu::new(ModerationTarget::from(event)));
+    }
+
+    /// Run a moderation action chosen in the moderation menu.
+    async fn run_moderation_action(
+        &mut self,
+        target: ModerationTarget,
+        action: ModerationAction,
+        reason: Option<String>,
+    ) {
+        let Some((room, sdk_timeline)) = self.room().zip(self.get_selected_timeline()) else {
+            self.status_handle.set_message("missing timeline for room".to_owned());
+            return;
+        };
+
+        let result = match action {
+            ModerationAction::Remove => sdk_timeline
+                .redact(&target.item_id, reason.as_deref())
+                .await
+                .map_err(|err| err.to_string()),
+
+            ModerationAction::BanSender => room
+                .ban_user(&target.sender, reason.as_deref())
+                .await
+                .map_err(|err| err.to_string()),
+
+            ModerationAction::Report => match target.event_id {
+                Some(event_id) => room
+                    .report_content(event_id, None, reason)
+                    .await
+                    .map(|_| ())
+                    .map_err(|err| err.to_string()),
+                None => Err("can't report a local echo".to_owned()),
+            },
+        };
+
+        match result {
+            Ok(()) => self.status_handle.set_message(format!("{action}: done!")),
+            Err(err) => self.status_handle.set_message(format!("{action}: error: {err}")),
+        }
+    }
+
     /// Attempt to find the currently selected room and pass it to the async
     /// callback.
     async fn call_with_room(&self, function: impl AsyncFnOnce(Room, &StatusHandle)) {
@@ -490,14 +879,11 @@ impl RoomView {
         }
     }
 
-    async fn send_message(&mut self, message: String) {
+    async fn send_message(&mut self, message: RoomMessageEventContentWithoutRelation) {
         match &self.kind {
             TimelineKind::Room { .. } => {
                 if let Some(sdk_timeline) = self.get_selected_timeline() {
-                    match sdk_timeline
-                        .send(RoomMessageEventContent::text_plain(message).into())
-                        .await
-                    {
+                    match sdk_timeline.send(message.with_relation(None).into()).await {
                         Ok(_) => {
                             self.input.clear();
                         }
@@ -535,7 +921,7 @@ impl RoomView {
                     // TODO: ogod this is awful
                     match sdk_timeline
                         .send_reply(
-                            RoomMessageEventContentWithoutRelation::text_plain(message),
+                            message,
                             Reply {
                                 event_id: prev_item_event_id,
                                 enforce_thread: Threaded(ReplyWithinThread::No),
@@ -605,10 +991,21 @@ impl Widget for &mut RoomView {
     {
         self.update();
 
-        // Create a space for the header, timeline, and input area.
-        let vertical =
-            Layout::vertical([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)]);
-        let [header_area, middle_area, input_area] = vertical.areas(area);
+        let utds = self
+            .get_selected_timeline_items()
+            .map(|items| UtdSummary::new(&items))
+            .unwrap_or_default();
+
+        // Create a space for the header, the banner of undecryptable events, the
+        // timeline, typing notifications, and input area.
+        let vertical = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(utds.height()),
+            Constraint::Min(0),
+            Constraint::Length(self.typing.height()),
+            Constraint::Length(self.input.height()),
+        ]);
+        let [header_area, utd_area, middle_area, typing_area, input_area] = vertical.areas(area);
 
         let is_thread_view = matches!(self.kind, TimelineKind::Thread { .. });
         let title = if is_thread_view { "Thread view" } else { "Room view" };
@@ -628,6 +1025,7 @@ impl Widget for &mut RoomView {
         // Let's render the backgrounds for the header and the timeline.
         header_block.render(header_area, buf);
         middle_block.render(middle_area, buf);
+        utds.render(utd_area, buf);
 
         // Helper to render some string as a paragraph.
         let render_paragraph = |buf: &mut Buffer, content: String| {
@@ -650,6 +1048,7 @@ impl Widget for &mut RoomView {
 
                         None
                     } else {
+                        self.typing.render(typing_area, buf);
                         self.input.render(input_area, buf, &mut maybe_room);
                         Some(middle_area)
                     }
@@ -676,9 +1075,30 @@ impl Widget for &mut RoomView {
                 && let Some(items) = self.get_selected_timeline_items()
             {
                 let is_thread = matches!(self.kind, TimelineKind::Thread { .. });
-                let mut timeline = TimelineView::new(&items, is_thread);
+                let mut timeline = TimelineView::new(
+                    &items,
+                    is_thread,
+                    self.client.user_id(),
+                    &self.media_previews,
+                );
                 timeline.render(timeline_area, buf, &mut self.timeline_list);
             }
+
+            if let Some(menu) = &mut self.moderation_menu {
+                menu.render(middle_area, buf);
+            }
+
+            if let Some(picker) = &mut self.reaction_picker {
+                picker.render(middle_area, buf);
+            }
+
+            if let Some(inspector) = &mut self.inspector {
+                inspector.render(middle_area, buf);
+            }
+
+            if let Some(pinned_events) = &mut self.pinned_events {
+                pinned_events.render(middle_area, buf);
+            }
         } else {
             render_paragraph(buf, "Nothing to see here...".to_owned())
         };
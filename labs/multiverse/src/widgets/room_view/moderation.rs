@@ -0,0 +1,166 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use matrix_sdk::ruma::{OwnedEventId, OwnedUserId};
+use matrix_sdk_ui::timeline::{EventTimelineItem, TimelineEventItemId};
+use ratatui::{prelude::*, widgets::*};
+use strum::{Display, EnumIter, IntoEnumIterator};
+use tui_textarea::TextArea;
+
+use crate::popup_area;
+
+/// A moderation action that can be run on the selected timeline event.
+#[derive(Clone, Copy, Debug, Display, EnumIter, PartialEq)]
+pub enum ModerationAction {
+    #[strum(to_string = "Remove the message")]
+    Remove,
+    #[strum(to_string = "Ban the sender")]
+    BanSender,
+    #[strum(to_string = "Report the message to the server")]
+    Report,
+}
+
+impl ModerationAction {
+    /// Whether a reason is asked for before running the action.
+    fn prompts_for_reason(&self) -> bool {
+        matches!(self, Self::BanSender | Self::Report)
+    }
+}
+
+/// The event the moderation actions are run on.
+pub struct ModerationTarget {
+    pub item_id: TimelineEventItemId,
+    pub event_id: Option<OwnedEventId>,
+    pub sender: OwnedUserId,
+}
+
+impl From<&EventTimelineItem> for ModerationTarget {
+    fn from(event: &EventTimelineItem) -> Self {
+        Self {
+            item_id: event.identifier(),
+            event_id: event.event_id().map(ToOwned::to_owned),
+            sender: event.sender().to_owned(),
+        }
+    }
+}
+
+/// What should happen after the menu has handled a key press.
+pub enum MenuOutcome {
+    /// The menu stays open.
+    Continue,
+    /// The menu has been dismissed.
+    Close,
+    /// The given action should be run, and the menu closed.
+    Run { target: ModerationTarget, action: ModerationAction, reason: Option<String> },
+}
+
+enum Step {
+    /// The user is choosing an action.
+    ChoosingAction { state: ListState },
+    /// The user is typing the reason of the chosen action.
+    TypingReason { action: ModerationAction, textarea: TextArea<'static> },
+}
+
+/// A context menu listing the moderation actions for the selected timeline
+/// event.
+pub struct ModerationMenu {
+    target: Option<ModerationTarget>,
+    step: Step,
+}
+
+impl ModerationMenu {
+    pub fn new(target: ModerationTarget) -> Self {
+        let state = ListState::default().with_selected(Some(0));
+        Self { target: Some(target), step: Step::ChoosingAction { state } }
+    }
+
+    pub fn handle_key_press(&mut self, key: KeyEvent) -> MenuOutcome {
+        use KeyCode::*;
+
+        match &mut self.step {
+            Step::ChoosingAction { state } => match (key.modifiers, key.code) {
+                (_, Esc) => MenuOutcome::Close,
+                (_, Down) | (KeyModifiers::CONTROL, Char('n')) => {
+                    state.select_next();
+                    MenuOutcome::Continue
+                }
+                (_, Up) | (KeyModifiers::CONTROL, Char('p')) => {
+                    state.select_previous();
+                    MenuOutcome::Continue
+                }
+                (_, Enter) => {
+                    let Some(action) =
+                        state.selected().and_then(|nth| ModerationAction::iter().nth(nth))
+                    else {
+                        return MenuOutcome::Continue;
+                    };
+
+                    if action.prompts_for_reason() {
+                        let mut textarea = TextArea::default();
+                        textarea.set_placeholder_text("(Optional reason, Enter to confirm)");
+                        self.step = Step::TypingReason { action, textarea };
+                        MenuOutcome::Continue
+                    } else {
+                        self.run(action, None)
+                    }
+                }
+                _ => MenuOutcome::Continue,
+            },
+
+            Step::TypingReason { action, textarea } => match (key.modifiers, key.code) {
+                (_, Esc) => MenuOutcome::Close,
+                (KeyModifiers::NONE, Enter) => {
+                    let action = *action;
+                    let reason = textarea.lines().join("\n");
+                    let reason = (!reason.trim().is_empty()).then_some(reason);
+                    self.run(action, reason)
+                }
+                _ => {
+                    textarea.input(key);
+                    MenuOutcome::Continue
+                }
+            },
+        }
+    }
+
+    fn run(&mut self, action: ModerationAction, reason: Option<String>) -> MenuOutcome {
+        match self.target.take() {
+            Some(target) => MenuOutcome::Run { target, action, reason },
+            None => MenuOutcome::Close,
+        }
+    }
+}
+
+impl Widget for &mut ModerationMenu {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let area = popup_area(area, 50, 30);
+        Clear.render(area, buf);
+
+        match &mut self.step {
+            Step::ChoosingAction { state } => {
+                let block = Block::bordered()
+                    .title(" Moderation ")
+                    .title_bottom(" Enter to choose, Esc to cancel ")
+                    .padding(Padding::horizontal(1));
+
+                let list = List::new(ModerationAction::iter().map(|action| action.to_string()))
+                    .block(block)
+                    .highlight_symbol("> ")
+                    .highlight_style(Style::new().bold());
+
+                StatefulWidget::render(list, area, buf, state);
+            }
+
+            Step::TypingReason { action, textarea } => {
+                let block = Block::bordered()
+                    .title(format!(" {action} "))
+                    .title_bottom(" Enter to confirm, Esc to cancel ")
+                    .padding(Padding::horizontal(1));
+
+                textarea.set_block(block);
+                textarea.render(area, buf);
+            }
+        }
+    }
+}
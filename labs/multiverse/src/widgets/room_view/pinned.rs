@@ -0,0 +1,137 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use matrix_sdk::{
+    Room,
+    deserialized_responses::TimelineEvent,
+    ruma::{
+        OwnedEventId, OwnedUserId,
+        events::{AnySyncMessageLikeEvent, AnySyncTimelineEvent, SyncMessageLikeEvent},
+    },
+};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::popup_area;
+
+/// A pinned event of the room, with its body resolved from the event cache.
+struct PinnedEvent {
+    event_id: OwnedEventId,
+    sender: Option<OwnedUserId>,
+    body: String,
+}
+
+impl PinnedEvent {
+    fn new(event_id: OwnedEventId, event: Option<TimelineEvent>) -> Self {
+        let Some(event) = event else {
+            return Self { event_id, sender: None, body: "(not in the event cache)".to_owned() };
+        };
+
+        let Ok(event) = event.raw().deserialize() else {
+            return Self { event_id, sender: None, body: "(invalid event)".to_owned() };
+        };
+
+        let body = match &event {
+            AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
+                SyncMessageLikeEvent::Original(message),
+            )) => message.content.body().to_owned(),
+            AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomEncrypted(_)) => {
+                "(unable to decrypt)".to_owned()
+            }
+            event => format!("({})", event.event_type()),
+        };
+
+        Self { event_id, sender: Some(event.sender().to_owned()), body }
+    }
+}
+
+/// What should happen after the pinned events popup has handled a key press.
+pub enum PinnedOutcome {
+    /// The popup stays open.
+    Continue,
+    /// The popup has been dismissed.
+    Close,
+    /// The given event should be selected in the timeline, and the popup
+    /// closed.
+    Select(OwnedEventId),
+}
+
+/// A popup listing the pinned events of the room.
+pub struct PinnedEvents {
+    events: Vec<PinnedEvent>,
+    state: ListState,
+}
+
+impl PinnedEvents {
+    /// Load the pinned events of the room, and resolve their bodies from the
+    /// event cache.
+    pub async fn load(room: &Room) -> Self {
+        let event_ids = room.pinned_event_ids().unwrap_or_default();
+        let event_cache = room.event_cache().await.ok();
+
+        let mut events = Vec::with_capacity(event_ids.len());
+
+        for event_id in event_ids {
+            let event = match &event_cache {
+                Some((room_event_cache, _drop_handles)) => room_event_cache.event(&event_id).await,
+                None => None,
+            };
+
+            events.push(PinnedEvent::new(event_id, event));
+        }
+
+        let state = ListState::default().with_selected((!events.is_empty()).then_some(0));
+
+        Self { events, state }
+    }
+
+    pub fn handle_key_press(&mut self, key: KeyEvent) -> PinnedOutcome {
+        use KeyCode::*;
+
+        match (key.modifiers, key.code) {
+            (_, Esc) | (KeyModifiers::ALT, Char('s')) => PinnedOutcome::Close,
+            (_, Down) | (KeyModifiers::CONTROL, Char('n')) => {
+                self.state.select_next();
+                PinnedOutcome::Continue
+            }
+            (_, Up) | (KeyModifiers::CONTROL, Char('p')) => {
+                self.state.select_previous();
+                PinnedOutcome::Continue
+            }
+            (_, Enter) => match self.state.selected().and_then(|nth| self.events.get(nth)) {
+                Some(event) => PinnedOutcome::Select(event.event_id.clone()),
+                None => PinnedOutcome::Continue,
+            },
+            _ => PinnedOutcome::Continue,
+        }
+    }
+}
+
+impl Widget for &mut PinnedEvents {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let area = popup_area(area, 70, 50);
+        Clear.render(area, buf);
+
+        let block = Block::bordered()
+            .title(format!(" Pinned messages ({}) ", self.events.len()))
+            .title_bottom(" Enter to jump to the message, Esc to close ")
+            .padding(Padding::horizontal(1));
+
+        if self.events.is_empty() {
+            Paragraph::new("No pinned messages in this room.").block(block).render(area, buf);
+            return;
+        }
+
+        let items = self.events.iter().map(|event| {
+            let sender = event.sender.as_ref().map_or("?", |sender| sender.as_str());
+            Line::from(vec![Span::from(sender).bold(), Span::from(": "), Span::from(&*event.body)])
+        });
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_symbol("> ")
+            .highlight_style(Style::new().bold());
+
+        StatefulWidget::render(list, area, buf, &mut self.state);
+    }
+}
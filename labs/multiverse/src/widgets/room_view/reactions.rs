@@ -0,0 +1,144 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use matrix_sdk::ruma::UserId;
+use matrix_sdk_ui::timeline::{EventTimelineItem, ReactionsByKeyBySender, TimelineEventItemId};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::popup_area;
+
+/// The reactions which are always offered by the picker, in addition to the
+/// ones already present on the event.
+const DEFAULT_REACTIONS: [&str; 8] = ["👍", "👎", "😄", "🎉", "😕", "❤️", "🚀", "👀"];
+
+/// What should happen after the picker has handled a key press.
+pub enum PickerOutcome {
+    /// The picker stays open.
+    Continue,
+    /// The picker has been dismissed.
+    Close,
+    /// The given reaction should be toggled on the event, and the picker
+    /// closed.
+    Toggle { item_id: TimelineEventItemId, key: String },
+}
+
+/// A reaction offered by the picker.
+struct Choice {
+    key: String,
+    /// Whether the current user has already sent this reaction.
+    is_own: bool,
+}
+
+/// A popup to choose a reaction to toggle on the selected timeline event.
+pub struct ReactionPicker {
+    item_id: Option<TimelineEventItemId>,
+    choices: Vec<Choice>,
+    state: ListState,
+}
+
+impl ReactionPicker {
+    pub fn new(event: &EventTimelineItem, own_user_id: Option<&UserId>) -> Self {
+        let reactions = event.content().reactions();
+
+        // Offer the default reactions first, then the other ones already sent by
+        // someone, so they can be toggled too.
+        let mut keys = DEFAULT_REACTIONS.iter().map(|key| (*key).to_owned()).collect::<Vec<_>>();
+        for key in reactions.into_iter().flat_map(|reactions| reactions.keys()) {
+            if !keys.contains(key) {
+                keys.push(key.clone());
+            }
+        }
+
+        let choices = keys
+            .into_iter()
+            .map(|key| {
+                let is_own = reactions
+                    .zip(own_user_id)
+                    .is_some_and(|(reactions, user_id)| has_reacted(reactions, &key, user_id));
+                Choice { key, is_own }
+            })
+            .collect();
+
+        let state = ListState::default().with_selected(Some(0));
+
+        Self { item_id: Some(event.identifier()), choices, state }
+    }
+
+    pub fn handle_key_press(&mut self, key: KeyEvent) -> PickerOutcome {
+        use KeyCode::*;
+
+        match (key.modifiers, key.code) {
+            (_, Esc) => PickerOutcome::Close,
+            (_, Down) | (KeyModifiers::CONTROL, Char('n')) => {
+                self.state.select_next();
+                PickerOutcome::Continue
+            }
+            (_, Up) | (KeyModifiers::CONTROL, Char('p')) => {
+                self.state.select_previous();
+                PickerOutcome::Continue
+            }
+            (_, Enter) => {
+                let Some(choice) = self.state.selected().and_then(|nth| self.choices.get(nth))
+                else {
+                    return PickerOutcome::Continue;
+                };
+
+                match self.item_id.take() {
+                    Some(item_id) => PickerOutcome::Toggle { item_id, key: choice.key.clone() },
+                    None => PickerOutcome::Close,
+                }
+            }
+            _ => PickerOutcome::Continue,
+        }
+    }
+}
+
+impl Widget for &mut ReactionPicker {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let area = popup_area(area, 30, 50);
+        Clear.render(area, buf);
+
+        let block = Block::bordered()
+            .title(" React ")
+            .title_bottom(" Enter to toggle, Esc to cancel ")
+            .padding(Padding::horizontal(1));
+
+        let list = List::new(self.choices.iter().map(|choice| {
+            if choice.is_own { format!("{} (remove)", choice.key) } else { choice.key.clone() }
+        }))
+        .block(block)
+        .highlight_symbol("> ")
+        .highlight_style(Style::new().bold());
+
+        StatefulWidget::render(list, area, buf, &mut self.state);
+    }
+}
+
+/// Has the given user sent a reaction with the given key?
+fn has_reacted(reactions: &ReactionsByKeyBySender, key: &str, user_id: &UserId) -> bool {
+    reactions.get(key).is_some_and(|senders| senders.contains_key(user_id))
+}
+
+/// Format the reactions of a timeline event as a line of chips, one per
+/// reaction key, highlighting the ones sent by the current user.
+pub fn format_reactions(
+    reactions: &ReactionsByKeyBySender,
+    own_user_id: Option<&UserId>,
+) -> Option<Line<'static>> {
+    if reactions.is_empty() {
+        return None;
+    }
+
+    let mut spans = vec![Span::raw(" ")];
+
+    for (key, senders) in reactions.iter() {
+        let is_own = own_user_id.is_some_and(|user_id| senders.contains_key(user_id));
+        let style = if is_own { Style::new().reversed() } else { Style::new() };
+
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(format!("[{key} {}]", senders.len()), style));
+    }
+
+    Some(Line::from(spans))
+}
@@ -1,23 +1,61 @@
 use std::sync::Arc;
 
 use imbl::Vector;
-use matrix_sdk::ruma::{UserId, events::room::message::MessageType};
+use matrix_sdk::ruma::{MatrixToUri, OwnedMxcUri, UserId, events::room::message::MessageType};
 use matrix_sdk_ui::timeline::{
     MembershipChange, Message, MsgLikeContent, MsgLikeKind, RoomMembershipChange, ThreadSummary,
     TimelineDetails, TimelineItem, TimelineItemContent, TimelineItemKind, VirtualTimelineItem,
 };
 use ratatui::{prelude::*, widgets::*};
 
+use super::{
+    media_preview::{MediaPreviews, PreviewSource, PreviewStatus},
+    reactions::format_reactions,
+};
 use crate::{ALT_ROW_COLOR, NORMAL_ROW_COLOR, SELECTED_STYLE_FG, TEXT_COLOR};
 
+/// The width of the highlight symbol of the list, plus the indentation of the
+/// media previews.
+const PREVIEW_INDENT: u16 = 3;
+
+/// The width of the highlight symbol of the list.
+const HIGHLIGHT_WIDTH: u16 = 1;
+
+/// The prefix of the matrix.to links.
+const MATRIX_TO_PREFIX: &str = "https://matrix.to/#/";
+
 pub struct TimelineView<'a> {
     items: &'a Vector<Arc<TimelineItem>>,
     is_thread: bool,
+    /// The ID of the current user, to highlight their own reactions.
+    own_user_id: Option<&'a UserId>,
+    media_previews: &'a MediaPreviews,
 }
 
 impl<'a> TimelineView<'a> {
-    pub fn new(items: &'a Vector<Arc<TimelineItem>>, is_thread: bool) -> Self {
-        Self { items, is_thread }
+    pub fn new(
+        items: &'a Vector<Arc<TimelineItem>>,
+        is_thread: bool,
+        own_user_id: Option<&'a UserId>,
+        media_previews: &'a MediaPreviews,
+    ) -> Self {
+        Self { items, is_thread, own_user_id, media_previews }
+    }
+}
+
+/// A timeline item, formatted for display in the list.
+struct FormattedItem<'a> {
+    item: ListItem<'a>,
+    /// The MXC URI of the media preview displayed below the first line of the
+    /// item, if any.
+    preview: Option<OwnedMxcUri>,
+    /// The number of lines displayed below the media preview, if any.
+    footer_height: u16,
+}
+
+impl<'a> From<Text<'a>> for FormattedItem<'a> {
+    fn from(text: Text<'a>) -> Self {
+        Self { item: ListItem::new(text), preview: None, footer_height: 0 }
     }
 }
 
@@ -27,6 +65,13 @@ pub struct TimelineListState {
     /// An index from a rendered list item to the original timeline item index
     /// (since some timeline items may not be rendered).
     list_index_to_item_index: Vec<usize>,
+    /// The height of each rendered list item.
+    list_item_heights: Vec<u16>,
+    /// The area where the list was rendered last.
+    area: Rect,
+    /// The timeline item index to select at the next render, once the list
+    /// items are known.
+    pending_selection: Option<usize>,
 }
 
 impl TimelineListState {
@@ -43,6 +88,52 @@ impl TimelineListState {
         let rendered_index = self.state.selected()?;
         self.list_index_to_item_index.get(rendered_index).copied()
     }
+
+    /// Select the timeline item with the given index, at the next render.
+    pub fn select_item(&mut self, item_index: usize) {
+        self.pending_selection = Some(item_index);
+    }
+
+    /// Select the timeline item displayed at the given position of the
+    /// terminal, if any.
+    ///
+    /// Returns the index of the timeline item, and the column of the position
+    /// relative to the start of the item's content, if the position is on the
+    /// first line of the item.
+    pub fn select_at(&mut self, column: u16, row: u16) -> Option<(usize, Option<u16>)> {
+        let (list_index, content_column) = self.list_index_at(column, row)?;
+        self.state.select(Some(list_index));
+        Some((self.list_index_to_item_index[list_index], content_column))
+    }
+
+    /// Same as [`Self::select_at`], without selecting the timeline item.
+    pub fn item_at(&self, column: u16, row: u16) -> Option<(usize, Option<u16>)> {
+        let (list_index, content_column) = self.list_index_at(column, row)?;
+        Some((self.list_index_to_item_index[list_index], content_column))
+    }
+
+    /// Find the rendered list item at the given position of the terminal.
+    fn list_index_at(&self, column: u16, row: u16) -> Option<(usize, Option<u16>)> {
+        if !self.area.contains(Position::new(column, row)) {
+            return None;
+        }
+
+        let mut y = self.area.y;
+
+        for (list_index, height) in
+            self.list_item_heights.iter().enumerate().skip(self.state.offset())
+        {
+            if row < y + height {
+                let content_column =
+                    (row == y).then(|| column.saturating_sub(self.area.x + HIGHLIGHT_WIDTH));
+                return Some((list_index, content_column));
+            }
+
+            y += height;
+        }
+
+        None
+    }
 }
 
 impl StatefulWidget for &mut TimelineView<'_> {
@@ -53,14 +144,46 @@ impl StatefulWidget for &mut TimelineView<'_> {
         Self: Sized,
     {
         timeline_list_state.list_index_to_item_index.clear();
+        timeline_list_state.area = area;
 
-        let content = self.items.iter().enumerate().filter_map(|(i, item)| {
-            let result = format_timeline_item(item, self.is_thread)?;
-            timeline_list_state.list_index_to_item_index.push(i);
-            Some(result)
-        });
+        let content = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| {
+                let result = format_timeline_item(
+                    item,
+                    self.is_thread,
+                    self.own_user_id,
+                    self.media_previews,
+                )?;
+                timeline_list_state.list_index_to_item_index.push(i);
+                Some(result)
+            })
+            .collect::<Vec<_>>();
+
+        // Remember where the previews go, before the items are consumed by the list.
+        let previews = content
+            .iter()
+            .map(|formatted| {
+                (formatted.item.height(), formatted.preview.clone(), formatted.footer_height)
+            })
+            .collect::<Vec<_>>();
+
+        timeline_list_state.list_item_heights =
+            previews.iter().map(|(height, ..)| *height as u16).collect();
+
+        if let Some(item_index) = timeline_list_state.pending_selection.take() {
+            let list_index = timeline_list_state
+                .list_index_to_item_index
+                .iter()
+                .position(|index| *index == item_index);
+            timeline_list_state.state.select(list_index);
+        }
 
         let list_items = content
+            .into_iter()
+            .map(|formatted| formatted.item)
             .enumerate()
             .map(|(i, line)| {
                 let bg_color = match i % 2 {
@@ -78,24 +201,91 @@ impl StatefulWidget for &mut TimelineView<'_> {
             .highlight_style(SELECTED_STYLE_FG);
 
         StatefulWidget::render(list, area, buf, &mut timeline_list_state.state);
+
+        // Now that the list knows which items are visible, draw the media previews on
+        // top of the blank lines which have been reserved for them.
+        let mut y = area.y;
+
+        for (height, preview, footer_height) in
+            previews.into_iter().skip(timeline_list_state.state.offset())
+        {
+            if y >= area.bottom() {
+                break;
+            }
+
+            if let Some(uri) = preview {
+                // The preview starts on the line following the item's first line, and spans the
+                // rest of the item, except its footer.
+                let preview_area = Rect::new(
+                    area.x + PREVIEW_INDENT,
+                    y + 1,
+                    area.width.saturating_sub(PREVIEW_INDENT),
+                    (height as u16).saturating_sub(1 + footer_height),
+                );
+
+                // Only draw previews which are fully visible, graphics protocols don't support
+                // clipping images.
+                if preview_area.bottom() <= area.bottom() {
+                    self.media_previews.render(&uri, preview_area, buf);
+                }
+            }
+
+            y = y.saturating_add(height as u16);
+        }
     }
 }
 
-fn format_timeline_item(item: &Arc<TimelineItem>, is_thread: bool) -> Option<ListItem<'_>> {
-    let item = match item.kind() {
+fn format_timeline_item<'a>(
+    item: &'a Arc<TimelineItem>,
+    is_thread: bool,
+    own_user_id: Option<&UserId>,
+    media_previews: &MediaPreviews,
+) -> Option<FormattedItem<'a>> {
+    let item: Text<'a> = match item.kind() {
         TimelineItemKind::Event(ev) => {
             let sender = ev.sender();
+            let reactions = ev
+                .content()
+                .reactions()
+                .and_then(|reactions| format_reactions(reactions, own_user_id));
 
-            match ev.content() {
+            let mut text: Text<'a> = match ev.content() {
                 TimelineItemContent::MsgLike(MsgLikeContent {
                     kind: MsgLikeKind::Message(message),
                     ..
                 }) => {
+                    if let Some(source) = PreviewSource::from_message(message.msgtype()) {
+                        let label = format!("🖼️ {}", message.body());
+                        return Some(format_media_message(
+                            sender,
+                            label,
+                            source,
+                            reactions,
+                            media_previews,
+                        ));
+                    }
+
                     let thread_summary =
                         if is_thread { None } else { ev.content().thread_summary() };
                     format_text_message(sender, message, thread_summary)?
                 }
 
+                TimelineItemContent::MsgLike(MsgLikeContent {
+                    kind: MsgLikeKind::Sticker(sticker),
+                    ..
+                }) => {
+                    let content = sticker.content();
+                    let source = PreviewSource::from_sticker(content);
+                    let label = format!("🏷️ {}", content.body);
+                    return Some(format_media_message(
+                        sender,
+                        label,
+                        source,
+                        reactions,
+                        media_previews,
+                    ));
+                }
+
                 TimelineItemContent::MsgLike(MsgLikeContent {
                     kind: MsgLikeKind::Redacted,
                     ..
@@ -108,11 +298,7 @@ fn format_timeline_item(item: &Arc<TimelineItem>, is_thread: bool) -> Option<Lis
 
                 TimelineItemContent::MembershipChange(m) => format_membership_change(m)?,
 
-                TimelineItemContent::MsgLike(MsgLikeContent {
-                    kind: MsgLikeKind::Sticker(_),
-                    ..
-                })
-                | TimelineItemContent::ProfileChange(_)
+                TimelineItemContent::ProfileChange(_)
                 | TimelineItemContent::OtherState(_)
                 | TimelineItemContent::FailedToParseMessageLike { .. }
                 | TimelineItemContent::FailedToParseState { .. }
@@ -123,24 +309,98 @@ fn format_timeline_item(item: &Arc<TimelineItem>, is_thread: bool) -> Option<Lis
                 | TimelineItemContent::CallNotify => {
                     return None;
                 }
+            };
+
+            // Display the reactions below the event.
+            if let Some(reactions) = reactions {
+                text.push_line(reactions);
             }
+
+            text
         }
 
         TimelineItemKind::Virtual(virt) => match virt {
             VirtualTimelineItem::DateDivider(unix_ts) => format!("Date: {unix_ts:?}").into(),
             VirtualTimelineItem::ReadMarker => "Read marker".to_owned().into(),
             VirtualTimelineItem::TimelineStart => "🥳 Timeline start! 🥳".to_owned().into(),
+            VirtualTimelineItem::HistoryUnavailable => {
+                "Older messages are unavailable".to_owned().into()
+            }
         },
     };
 
-    Some(item)
+    Some(item.into())
+}
+
+/// Format a media message, reserving space for its preview below its first
+/// line once it's ready.
+fn format_media_message(
+    sender: &UserId,
+    label: String,
+    source: PreviewSource,
+    reactions: Option<Line<'static>>,
+    media_previews: &MediaPreviews,
+) -> FormattedItem<'static> {
+    let mut lines = vec![Line::from(format!("{sender}: {label}"))];
+
+    let preview = match media_previews.status(&source) {
+        PreviewStatus::Loading => {
+            lines.push(Line::from("  (loading preview…)"));
+            None
+        }
+        PreviewStatus::Ready { height } => {
+            lines.extend((0..height).map(|_| Line::default()));
+            Some(source.uri())
+        }
+        PreviewStatus::Failed => None,
+    };
+
+    let footer_height = if let Some(reactions) = reactions {
+        lines.push(reactions);
+        1
+    } else {
+        0
+    };
+
+    FormattedItem { item: ListItem::from(lines), preview, footer_height }
+}
+
+/// Find the matrix.to link of a text message displayed at the given column of
+/// its first line, if any.
+pub fn matrix_to_link_at(item: &TimelineItem, column: u16) -> Option<MatrixToUri> {
+    let event = item.as_event()?;
+    let MessageType::Text(text) = event.content().as_message()?.msgtype() else {
+        return None;
+    };
+
+    // Must match the first line built in `format_text_message`.
+    let prefix = format!("{}: ", event.sender());
+    let column = usize::from(column);
+
+    find_matrix_to_links(&text.body).find_map(|(start, link)| {
+        let link_start = Line::from(format!("{prefix}{}", &text.body[..start])).width();
+        let link_end = link_start + Line::from(link).width();
+
+        if (link_start..link_end).contains(&column) { MatrixToUri::parse(link).ok() } else { None }
+    })
+}
+
+/// Find the matrix.to links in a message body, with their byte offsets.
+fn find_matrix_to_links(body: &str) -> impl Iterator<Item = (usize, &str)> {
+    body.match_indices(MATRIX_TO_PREFIX).map(|(start, _)| {
+        let rest = &body[start..];
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let link =
+            rest[..end].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '>', '"', '\'']);
+        (start, link)
+    })
 }
 
 fn format_text_message(
     sender: &UserId,
     message: &Message,
     thread_summary: Option<ThreadSummary>,
-) -> Option<ListItem<'static>> {
+) -> Option<Text<'static>> {
     if let MessageType::Text(text) = message.msgtype() {
         let mut lines = Vec::new();
         let first_line = Line::from(format!("{}: {}", sender, text.body));
@@ -173,13 +433,13 @@ fn format_text_message(
             }
         }
 
-        Some(ListItem::from(lines))
+        Some(Text::from(lines))
     } else {
         None
     }
 }
 
-fn format_membership_change(membership: &RoomMembershipChange) -> Option<ListItem<'static>> {
+fn format_membership_change(membership: &RoomMembershipChange) -> Option<Text<'static>> {
     if let Some(change) = membership.change() {
         let display_name =
             membership.display_name().unwrap_or_else(|| membership.user_id().to_string());
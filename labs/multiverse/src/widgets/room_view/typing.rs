@@ -0,0 +1,139 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use matrix_sdk::{Room, locks::Mutex};
+use ratatui::{prelude::*, widgets::*};
+use style::palette::tailwind;
+use tokio::{spawn, sync::broadcast::error::RecvError, task::JoinHandle};
+use tracing::warn;
+
+/// How long to wait before refreshing the typing notice while the user keeps
+/// typing.
+///
+/// The SDK sends typing notices with a timeout of 4 seconds, so they must be
+/// refreshed before that.
+const TYPING_NOTICE_REFRESH: Duration = Duration::from_secs(3);
+
+/// The typing notifications of the selected room: the other users who are
+/// typing, and whether the current user is typing in the composer.
+#[derive(Default)]
+pub struct Typing {
+    /// The room the typing notifications are about.
+    room: Option<Room>,
+
+    /// The names of the other users who are typing in the room.
+    typing_users: Arc<Mutex<Vec<String>>>,
+
+    /// The task listening to the typing notifications of the room.
+    task: Option<JoinHandle<()>>,
+
+    /// When the last typing notice was sent, if the current user is typing.
+    last_notice: Option<Instant>,
+}
+
+impl Typing {
+    /// Set the room to display and send typing notifications for.
+    pub fn set_room(&mut self, room: Option<Room>) {
+        // We're not typing in the previous room anymore.
+        self.set_typing(false);
+
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+
+        self.typing_users.lock().clear();
+        self.room = room.clone();
+
+        let Some(room) = room else {
+            return;
+        };
+
+        let typing_users = self.typing_users.clone();
+
+        self.task = Some(spawn(async move {
+            let (_drop_guard, mut receiver) = room.subscribe_to_typing_notifications();
+
+            loop {
+                let user_ids = match receiver.recv().await {
+                    Ok(user_ids) => user_ids,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                };
+
+                let mut names = Vec::with_capacity(user_ids.len());
+
+                for user_id in user_ids {
+                    // Don't sync the members, only use the ones we already know about.
+                    let name = match room.get_member_no_sync(&user_id).await {
+                        Ok(Some(member)) => member.name().to_owned(),
+                        _ => user_id.to_string(),
+                    };
+
+                    names.push(name);
+                }
+
+                *typing_users.lock() = names;
+            }
+        }));
+    }
+
+    /// Update whether the current user is typing, sending a typing notice to
+    /// the room if needed.
+    ///
+    /// While the user is typing, the typing notice is only sent again after
+    /// [`TYPING_NOTICE_REFRESH`].
+    pub fn set_typing(&mut self, typing: bool) {
+        let Some(room) = self.room.clone() else {
+            return;
+        };
+
+        if typing {
+            if self.last_notice.is_some_and(|sent_at| sent_at.elapsed() < TYPING_NOTICE_REFRESH) {
+                return;
+            }
+
+            self.last_notice = Some(Instant::now());
+        } else if self.last_notice.take().is_none() {
+            // We weren't typing.
+            return;
+        }
+
+        spawn(async move {
+            if let Err(err) = room.typing_notice(typing).await {
+                warn!("couldn't send the typing notice: {err}");
+            }
+        });
+    }
+
+    /// The text describing who is typing, if anyone.
+    fn text(&self) -> Option<String> {
+        let typing_users = self.typing_users.lock();
+
+        match typing_users.as_slice() {
+            [] => None,
+            [user] => Some(format!("{user} is typing…")),
+            [first, second] => Some(format!("{first} and {second} are typing…")),
+            [first, second, others @ ..] => {
+                Some(format!("{first}, {second} and {} others are typing…", others.len()))
+            }
+        }
+    }
+
+    /// The number of lines needed to render the widget.
+    pub fn height(&self) -> u16 {
+        if self.typing_users.lock().is_empty() { 0 } else { 1 }
+    }
+}
+
+impl Widget for &Typing {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        if let Some(text) = self.text() {
+            Paragraph::new(text).italic().bg(tailwind::BLUE.c900).render(area, buf);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::{collections::BTreeSet, sync::Arc};
+
+use imbl::Vector;
+use matrix_sdk::Client;
+use matrix_sdk_ui::{
+    Timeline,
+    timeline::{EncryptedMessage, TimelineItem},
+};
+use ratatui::{prelude::*, widgets::*};
+use style::palette::tailwind;
+
+use crate::widgets::status::StatusHandle;
+
+/// The undecryptable events of a timeline.
+#[derive(Default)]
+pub struct UtdSummary {
+    /// The number of undecryptable events.
+    num_events: usize,
+
+    /// The IDs of the Megolm sessions needed to decrypt the events.
+    session_ids: BTreeSet<String>,
+}
+
+impl UtdSummary {
+    pub fn new(items: &Vector<Arc<TimelineItem>>) -> Self {
+        let mut summary = Self::default();
+
+        for encrypted in
+            items.iter().filter_map(|item| item.as_event()?.content().as_unable_to_decrypt())
+        {
+            summary.num_events += 1;
+
+            if let EncryptedMessage::MegolmV1AesSha2 { session_id, .. } = encrypted {
+                summary.session_ids.insert(session_id.clone());
+            }
+        }
+
+        summary
+    }
+
+    /// The IDs of the Megolm sessions needed to decrypt the events.
+    pub fn session_ids(&self) -> &BTreeSet<String> {
+        &self.session_ids
+    }
+
+    /// The number of lines needed to render the widget.
+    pub fn height(&self) -> u16 {
+        if self.num_events == 0 { 0 } else { 1 }
+    }
+}
+
+impl Widget for &UtdSummary {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        if self.num_events == 0 {
+            return;
+        }
+
+        let text = format!(
+            "{} undecryptable event(s) from {} session(s), Alt-u to fetch the keys from the backup",
+            self.num_events,
+            self.session_ids.len(),
+        );
+
+        Paragraph::new(text).bold().bg(tailwind::RED.c900).render(area, buf);
+    }
+}
+
+/// Download the keys of the given sessions from the key backup, then retry to
+/// decrypt the events of the timeline.
+///
+/// Decryption is retried even if no key could be downloaded, in case the keys
+/// have been received by other means in the meantime.
+pub async fn recover_keys(
+    client: Client,
+    timeline: Arc<Timeline>,
+    session_ids: BTreeSet<String>,
+    status_handle: StatusHandle,
+) {
+    let room_id = timeline.room().room_id().to_owned();
+    let backups = client.encryption().backups();
+
+    if !backups.are_enabled().await {
+        status_handle.set_message("the key backup isn't enabled, retrying decryption".to_owned());
+        timeline.retry_decryption(&session_ids).await;
+        return;
+    }
+
+    let mut num_downloaded = 0;
+    let mut last_error = None;
+
+    for session_id in &session_ids {
+        match backups.download_room_key(&room_id, session_id).await {
+            Ok(true) => num_downloaded += 1,
+            Ok(false) => {}
+            Err(err) => last_error = Some(err),
+        }
+    }
+
+    timeline.retry_decryption(&session_ids).await;
+
+    let message = match last_error {
+        Some(err) => format!(
+            "downloaded {num_downloaded}/{} keys from the backup, last error: {err}",
+            session_ids.len()
+        ),
+        None => format!(
+            "downloaded {num_downloaded}/{} keys from the backup, retried decryption",
+            session_ids.len()
+        ),
+    };
+
+    status_handle.set_message(message);
+}
@@ -0,0 +1,427 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use layout::Flex;
+use matrix_sdk::{
+    Client,
+    encryption::verification::{
+        SasState, SasVerification, VerificationRequest, VerificationRequestState,
+    },
+    event_handler::EventHandlerHandle,
+    ruma::events::{
+        key::verification::request::ToDeviceKeyVerificationRequestEvent,
+        room::message::{MessageType, OriginalSyncRoomMessageEvent},
+    },
+};
+use matrix_sdk_common::executor::spawn;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Clear, Padding, Paragraph},
+};
+use throbber_widgets_tui::{Throbber, ThrobberState};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tracing::error;
+
+use crate::{popup_area, widgets::recovery::create_centered_throbber_area};
+
+/// A view which listens for incoming verification requests and guides the user
+/// through an emoji based (SAS) verification.
+pub struct VerificationView {
+    client: Client,
+
+    /// The receiving end of the incoming verification requests, filled by our
+    /// event handlers.
+    request_receiver: UnboundedReceiver<VerificationRequest>,
+
+    /// Handles of the event handlers listening for verification requests, they
+    /// are removed once the view is dropped.
+    event_handlers: Vec<EventHandlerHandle>,
+
+    throbber_state: ThrobberState,
+    mode: Mode,
+}
+
+#[derive(Debug, Default)]
+enum Mode {
+    /// There's no verification flow going on.
+    #[default]
+    Idle,
+
+    /// We received a verification request, and are either waiting for the
+    /// user to accept it or for the verification to transition into a SAS
+    /// verification.
+    Request { request: VerificationRequest },
+
+    /// The request has been transitioned into a SAS verification.
+    Sas { sas: SasVerification },
+
+    /// The verification flow has concluded, successfully or not.
+    Done { message: String },
+}
+
+impl Drop for VerificationView {
+    fn drop(&mut self) {
+        for handle in self.event_handlers.drain(..) {
+            self.client.remove_event_handler(handle);
+        }
+    }
+}
+
+impl VerificationView {
+    pub fn new(client: Client) -> Self {
+        let (sender, request_receiver) = unbounded_channel();
+
+        let event_handlers = vec![
+            client.add_event_handler({
+                let sender = sender.clone();
+
+                move |event: ToDeviceKeyVerificationRequestEvent, client: Client| {
+                    let sender = sender.clone();
+
+                    async move {
+                        let request = client
+                            .encryption()
+                            .get_verification_request(&event.sender, &event.content.transaction_id)
+                            .await;
+
+                        Self::forward_request(&sender, request);
+                    }
+                }
+            }),
+            client.add_event_handler(move |event: OriginalSyncRoomMessageEvent, client: Client| {
+                let sender = sender.clone();
+
+                async move {
+                    if let MessageType::VerificationRequest(_) = &event.content.msgtype {
+                        let request = client
+                            .encryption()
+                            .get_verification_request(&event.sender, &event.event_id)
+                            .await;
+
+                        Self::forward_request(&sender, request);
+                    }
+                }
+            }),
+        ];
+
+        Self {
+            client,
+            request_receiver,
+            event_handlers,
+            throbber_state: ThrobberState::default(),
+            mode: Mode::default(),
+        }
+    }
+
+    fn forward_request(
+        sender: &UnboundedSender<VerificationRequest>,
+        request: Option<VerificationRequest>,
+    ) {
+        // Requests we started ourselves are sent to us as well, we only care about the
+        // incoming ones.
+        if let Some(request) = request.filter(|request| !request.we_started()) {
+            // The receiver lives as long as the view, which removes the event handlers
+            // when it gets dropped.
+            let _ = sender.send(request);
+        }
+    }
+
+    /// Is there a verification flow going on which should be displayed?
+    pub fn is_active(&self) -> bool {
+        !matches!(self.mode, Mode::Idle)
+    }
+
+    fn update_state(&mut self) {
+        match &self.mode {
+            Mode::Idle => {
+                if let Ok(request) = self.request_receiver.try_recv() {
+                    self.mode = Mode::Request { request };
+                }
+            }
+
+            Mode::Request { request } => match request.state() {
+                VerificationRequestState::Created { .. }
+                | VerificationRequestState::Requested { .. }
+                | VerificationRequestState::Ready { .. } => {}
+
+                VerificationRequestState::Transitioned { verification } => {
+                    if let Some(sas) = verification.sas() {
+                        // If the other side started the SAS verification, we need to accept it
+                        // so the keys get exchanged.
+                        if !sas.we_started() {
+                            let sas = sas.clone();
+                            spawn(async move {
+                                if let Err(error) = sas.accept().await {
+                                    error!("Couldn't accept the SAS verification: {error}");
+                                }
+                            });
+                        }
+
+                        self.mode = Mode::Sas { sas };
+                    } else {
+                        let request = request.clone();
+                        spawn(async move {
+                            if let Err(error) = request.cancel().await {
+                                error!("Couldn't cancel the verification request: {error}");
+                            }
+                        });
+
+                        self.mode = Mode::Done {
+                            message: "Only emoji verification is supported".to_owned(),
+                        };
+                    }
+                }
+
+                VerificationRequestState::Done => {
+                    self.mode = Mode::Done { message: "The verification is done".to_owned() };
+                }
+
+                VerificationRequestState::Cancelled(cancel_info) => {
+                    self.mode = Mode::Done {
+                        message: format!(
+                            "The verification has been cancelled: {}",
+                            cancel_info.reason()
+                        ),
+                    };
+                }
+            },
+
+            Mode::Sas { sas } => match sas.state() {
+                SasState::Created { .. }
+                | SasState::Started { .. }
+                | SasState::Accepted { .. }
+                | SasState::KeysExchanged { .. }
+                | SasState::Confirmed => {}
+
+                SasState::Done { .. } => {
+                    let device = sas.other_device();
+                    self.mode = Mode::Done {
+                        message: format!(
+                            "Successfully verified the device {} of {}",
+                            device.device_id(),
+                            device.user_id()
+                        ),
+                    };
+                }
+
+                SasState::Cancelled(cancel_info) => {
+                    self.mode = Mode::Done {
+                        message: format!(
+                            "The verification has been cancelled: {}",
+                            cancel_info.reason()
+                        ),
+                    };
+                }
+            },
+
+            Mode::Done { .. } => {}
+        }
+    }
+
+    pub fn on_tick(&mut self) {
+        self.throbber_state.calc_next();
+        self.update_state();
+    }
+
+    fn cancel(&mut self) {
+        match &self.mode {
+            Mode::Request { request } => {
+                let request = request.clone();
+                spawn(async move {
+                    if let Err(error) = request.cancel().await {
+                        error!("Couldn't cancel the verification request: {error}");
+                    }
+                });
+            }
+            Mode::Sas { sas } => {
+                let sas = sas.clone();
+                spawn(async move {
+                    if let Err(error) = sas.cancel().await {
+                        error!("Couldn't cancel the SAS verification: {error}");
+                    }
+                });
+            }
+            Mode::Idle | Mode::Done { .. } => {}
+        }
+
+        self.mode = Mode::Idle;
+    }
+
+    /// Handle a key press, returns `true` if the verification view should be
+    /// closed.
+    pub fn handle_key_press(&mut self, key: KeyEvent) -> bool {
+        use KeyCode::*;
+
+        if key.kind != KeyEventKind::Press {
+            return false;
+        }
+
+        match &self.mode {
+            Mode::Idle => matches!(key.code, Esc | Char('q')),
+
+            Mode::Request { request } => match (request.state(), key.code) {
+                (_, Esc | Char('n')) => {
+                    self.cancel();
+                    true
+                }
+                (VerificationRequestState::Requested { .. }, Enter | Char('y')) => {
+                    let request = request.clone();
+                    spawn(async move {
+                        if let Err(error) = request.accept().await {
+                            error!("Couldn't accept the verification request: {error}");
+                        }
+                    });
+
+                    false
+                }
+                (VerificationRequestState::Ready { .. }, Char('s')) => {
+                    let request = request.clone();
+                    spawn(async move {
+                        if let Err(error) = request.start_sas().await {
+                            error!("Couldn't start the SAS verification: {error}");
+                        }
+                    });
+
+                    false
+                }
+                _ => false,
+            },
+
+            Mode::Sas { sas } => match (sas.state(), key.code) {
+                (_, Esc) => {
+                    self.cancel();
+                    true
+                }
+                (SasState::KeysExchanged { .. }, Enter | Char('y')) => {
+                    let sas = sas.clone();
+                    spawn(async move {
+                        if let Err(error) = sas.confirm().await {
+                            error!("Couldn't confirm the SAS verification: {error}");
+                        }
+                    });
+
+                    false
+                }
+                (SasState::KeysExchanged { .. }, Char('n')) => {
+                    let sas = sas.clone();
+                    spawn(async move {
+                        if let Err(error) = sas.mismatch().await {
+                            error!("Couldn't cancel the SAS verification: {error}");
+                        }
+                    });
+
+                    false
+                }
+                _ => false,
+            },
+
+            // Any key closes the view once the verification is done.
+            Mode::Done { .. } => {
+                self.mode = Mode::Idle;
+                true
+            }
+        }
+    }
+
+    fn render_throbber(&mut self, label: &'static str, area: Rect, buf: &mut Buffer) {
+        let throbber = Throbber::default()
+            .label(label)
+            .throbber_set(throbber_widgets_tui::BRAILLE_EIGHT_DOUBLE);
+        let centered_area = create_centered_throbber_area(area);
+        StatefulWidget::render(throbber, centered_area, buf, &mut self.throbber_state);
+    }
+
+    fn render_text(text: impl Into<Text<'static>>, area: Rect, buf: &mut Buffer) {
+        let text = text.into();
+        let vertical = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(text.height() as u16),
+            Constraint::Fill(1),
+        ])
+        .flex(Flex::Center);
+        let [_, middle, _] = vertical.areas(area);
+
+        Paragraph::new(text).centered().render(middle, buf);
+    }
+}
+
+impl Widget for &mut VerificationView {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        self.update_state();
+
+        let area = popup_area(area, 50, 30);
+        Clear.render(area, buf);
+
+        let block = Block::bordered()
+            .title(" Verification ")
+            .borders(Borders::ALL)
+            .padding(Padding::horizontal(1));
+        let inner_area = block.inner(area);
+        block.render(area, buf);
+
+        match &self.mode {
+            Mode::Idle => {
+                VerificationView::render_text("No verification request", inner_area, buf);
+            }
+
+            Mode::Request { request } => match request.state() {
+                VerificationRequestState::Requested { other_device_data, .. } => {
+                    let text = format!(
+                        "{} wants to verify the device {}\n\n\
+                         Press y to accept or n to decline",
+                        request.other_user_id(),
+                        other_device_data.device_id(),
+                    );
+                    VerificationView::render_text(text, inner_area, buf);
+                }
+                VerificationRequestState::Ready { .. } => {
+                    let text = "Waiting for the other device to start the verification\n\n\
+                                Press s to start it from here";
+                    VerificationView::render_text(text, inner_area, buf);
+                }
+                _ => self.render_throbber("Accepting", inner_area, buf),
+            },
+
+            Mode::Sas { sas } => match sas.state() {
+                SasState::KeysExchanged { decimals, .. } => {
+                    let lines = if let Some(emojis) = sas.emoji() {
+                        let symbols = emojis.iter().map(|emoji| format!("{:^12}", emoji.symbol));
+                        let descriptions =
+                            emojis.iter().map(|emoji| format!("{:^12}", emoji.description));
+
+                        vec![
+                            Line::from("Do the emojis match the ones on the other device?"),
+                            Line::default(),
+                            Line::from(symbols.collect::<String>()),
+                            Line::from(descriptions.collect::<String>()),
+                        ]
+                    } else {
+                        let (first, second, third) = decimals;
+
+                        vec![
+                            Line::from("Do the numbers match the ones on the other device?"),
+                            Line::default(),
+                            Line::from(format!("{first} {second} {third}")),
+                        ]
+                    };
+
+                    let mut text = Text::from(lines);
+                    text.push_line(Line::default());
+                    text.push_line("Press y to confirm or n if they don't match");
+
+                    VerificationView::render_text(text, inner_area, buf);
+                }
+                SasState::Confirmed => {
+                    self.render_throbber("Waiting for the other device", inner_area, buf)
+                }
+                _ => self.render_throbber("Exchanging keys", inner_area, buf),
+            },
+
+            Mode::Done { message } => {
+                let text = format!("{message}\n\nPress any key to continue");
+                VerificationView::render_text(text, inner_area, buf);
+            }
+        }
+    }
+}